@@ -0,0 +1,44 @@
+//! Benchmarks of the analysis of large stores, generated with [Dataset].
+//!
+//! Run with `cargo bench --features testing`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use netpulse::analyze::{analyze, outage_summaries, write_all_outages};
+use netpulse::testing::Dataset;
+
+/// Numbers of checks in the benchmarked stores, the largest is about a year of the defaults
+const SIZES: &[usize] = &[10_000, 100_000, 300_000];
+
+fn bench_outages(c: &mut Criterion) {
+    let mut group = c.benchmark_group("outages");
+    group.sample_size(10);
+    for size in SIZES {
+        let store = Dataset::with_checks(*size).store();
+        group.bench_with_input(BenchmarkId::new("summaries", size), &store, |b, store| {
+            b.iter(|| outage_summaries(store))
+        });
+        group.bench_with_input(BenchmarkId::new("write_all", size), &store, |b, store| {
+            b.iter(|| {
+                let mut out = Vec::new();
+                write_all_outages(store, &mut out).unwrap();
+                out
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_analyze(c: &mut Criterion) {
+    let mut group = c.benchmark_group("analyze");
+    group.sample_size(10);
+    for size in SIZES {
+        let store = Dataset::with_checks(*size).store();
+        group.bench_with_input(BenchmarkId::from_parameter(size), &store, |b, store| {
+            b.iter(|| analyze(store).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_outages, bench_analyze);
+criterion_main!(benches);
@@ -8,6 +8,9 @@
 //! - Latency analysis
 //! - Report generation
 //!
+//! [Aggregates](crate::records::aggregate::Aggregate) of downsampled checks are merged into the statistics transparently.
+//! Outages can only be detected from raw [Checks](Check).
+//!
 //! The main entry point is the [analyze] function which generates
 //! a comprehensive report of the store's contents.
 //!
@@ -25,25 +28,60 @@
 //!
 //! The analysis report contains several sections:
 //! - General statistics (total checks, success rates)
-//! - HTTP-specific metrics
-//! - Outage analysis
+//! - Why failed checks failed, like timeouts or refused connections
+//! - HTTP-specific metrics and status code distribution
+//! - QUIC metrics, to tell a broken UDP path from a broken HTTP service
+//! - Packet loss of ICMP bursts, in total and per day
+//! - DNS metrics, separately for UDP, HTTPS and TLS
+//! - Clock offset per NTP server
+//! - Path MTU per target
+//! - Where the paths of failed checks died, see [Trace](crate::records::trace::Trace)
+//! - Reachability of the default gateway, and whether outages were local or upstream
+//! - Throughput of the bandwidth samples, in total and per day
+//! - Per target statistics, by label if configured
+//! - Statistics and outages per interface, to compare uplinks, see [InterfaceStats]
+//! - Outage analysis, with hints about the causes in the detailed output, see [diagnose]
+//! - Gaps in which no checks were made, like when the daemon was stopped, see [gap]
+//! - Uptime, outages, downtime and unmonitored time per calendar month
+//! - Error budget of the current period, if an SLA is configured
+//! - Failures by day of the week and hour of the day, as a heatmap
 //! - Store metadata (hashes, versions)
+//!
+//! [period_summary] and [calendar] show the uptime per calendar week or month and per day on
+//! their own.
+//!
+//! The sections and labels can be shown in German instead of English, see [i18n].
 
-use chrono::{DateTime, Local};
-use deepsize::DeepSizeOf;
+use chrono::{DateTime, Datelike, Local, TimeZone};
 use tracing::{error, trace};
 
+use crate::config::{Config, SeverityConfig, SlaConfig};
 use crate::errors::AnalysisError;
 use crate::records::{display_group, Check, CheckType, IpType};
 use crate::store::Store;
 
-use std::collections::HashMap;
+use std::borrow::Borrow;
 use std::fmt::{Display, Write};
-use std::os::unix::fs::MetadataExt;
 
-use self::outage::Outage;
+use self::gap::Gap;
+use self::i18n::Language;
+use self::outage::{Location, Outage, OutageSummary};
 
+pub mod constraints;
+pub mod diagnose;
+pub mod gap;
+#[cfg(feature = "graph")]
+pub mod graph;
+pub mod i18n;
 pub mod outage;
+pub mod report;
+
+pub use self::constraints::CheckAccessConstraints;
+use self::report::{
+    Calendar, CalendarDay, Coverage, FailureReasons, Heatmap, InterfaceStats, LossStats, MtuStats,
+    NtpStats, Period, PeriodStats, SlaStats, Stats, StatusCodes, StoreMeta, TargetStats,
+    ThroughputStats, TraceStats, UptimeBasis, WEEKDAYS,
+};
 
 /// Formatting rules for timestamps that are easily readable by humans.
 ///
@@ -54,6 +92,14 @@ pub mod outage;
 /// println!("it is now: {}", datetime.format(TIME_FORMAT_HUMANS));
 /// ```
 pub const TIME_FORMAT_HUMANS: &str = "%Y-%m-%d %H:%M:%S %Z";
+/// How many of the latest days the text report shows the packet loss of
+pub const LOSS_TREND_DAYS: usize = 14;
+/// How many of the latest traces of failed checks the text report shows
+pub const LATEST_TRACES: usize = 3;
+/// How many of the latest and of the most severe outages the text report shows by default
+pub const OUTAGE_LIMIT: usize = 10;
+/// How many calendar months [calendar] shows by default, including the current one
+pub const CALENDAR_MONTHS: u32 = 3;
 /// A group of [Checks](Check)
 pub type CheckGroup<'check> = Vec<&'check Check>;
 
@@ -75,6 +121,9 @@ fn more_indent(buf: &str) -> String {
 /// - Report string formatting fails
 /// - Store hash calculation fails
 ///
+/// The outages are ranked and the SLA is measured with the default [Config], see
+/// [analyze_with_limit] to use another.
+///
 /// # Example
 ///
 /// ```rust,no_run
@@ -85,21 +134,140 @@ fn more_indent(buf: &str) -> String {
 /// println!("{}", report);
 /// ```
 pub fn analyze(store: &Store) -> Result<String, AnalysisError> {
+    analyze_with_limit(store, Some(OUTAGE_LIMIT), &Config::default())
+}
+
+/// Like [analyze], but shows up to `outage_limit` of the latest and of the most severe outages
+/// instead of [OUTAGE_LIMIT], or all of them if it is [None].
+///
+/// The most severe outages are ranked by the [severity](Config::severity) and the error budget
+/// is measured against the [SLA](Config::sla) of `config`. The labels are written in its
+/// [language](Config::language).
+///
+/// # Errors
+///
+/// Returns [AnalysisError] in the same cases as [analyze].
+pub fn analyze_with_limit(
+    store: &Store,
+    outage_limit: Option<usize>,
+    config: &Config,
+) -> Result<String, AnalysisError> {
+    let language = config.language;
     let mut f = String::new();
-    barrier(&mut f, "General")?;
-    generalized(store, &mut f)?;
-    barrier(&mut f, "HTTP")?;
-    generic_type_analyze(store, &mut f, CheckType::Http)?;
-    barrier(&mut f, "ICMP")?;
-    generic_type_analyze(store, &mut f, CheckType::Icmp)?;
-    barrier(&mut f, "IPv4")?;
-    gereric_ip_analyze(store, &mut f, IpType::V4)?;
-    barrier(&mut f, "IPv6")?;
-    gereric_ip_analyze(store, &mut f, IpType::V6)?;
-    barrier(&mut f, "Outages")?;
-    outages(store, &mut f)?;
-    barrier(&mut f, "Store Metadata")?;
-    store_meta(store, &mut f)?;
+    barrier(&mut f, language.label("General"))?;
+    generalized(store, &mut f, language)?;
+    barrier(&mut f, language.label("Failure Reasons"))?;
+    failure_reasons(store, &mut f, language)?;
+    barrier(&mut f, language.label("HTTP"))?;
+    generic_type_analyze(store, &mut f, CheckType::Http, language)?;
+    barrier(&mut f, language.label("HTTP Status Codes"))?;
+    http_status_codes(store, &mut f)?;
+    barrier(&mut f, language.label("QUIC"))?;
+    generic_type_analyze(store, &mut f, CheckType::Quic, language)?;
+    barrier(&mut f, language.label("ICMP"))?;
+    generic_type_analyze(store, &mut f, CheckType::Icmp, language)?;
+    barrier(&mut f, language.label("Packet Loss"))?;
+    packet_loss(store, &mut f, language)?;
+    barrier(&mut f, language.label("DNS"))?;
+    generic_type_analyze(store, &mut f, CheckType::Dns, language)?;
+    barrier(&mut f, language.label("DNS over HTTPS"))?;
+    generic_type_analyze(store, &mut f, CheckType::Doh, language)?;
+    barrier(&mut f, language.label("DNS over TLS"))?;
+    generic_type_analyze(store, &mut f, CheckType::Dot, language)?;
+    barrier(&mut f, language.label("NTP"))?;
+    generic_type_analyze(store, &mut f, CheckType::Ntp, language)?;
+    clock_offset(store, &mut f)?;
+    barrier(&mut f, language.label("MTU"))?;
+    generic_type_analyze(store, &mut f, CheckType::Mtu, language)?;
+    path_mtu(store, &mut f)?;
+    barrier(&mut f, language.label("Gateway"))?;
+    generic_type_analyze(store, &mut f, CheckType::Gateway, language)?;
+    outage_locations(store, &mut f, language)?;
+    barrier(&mut f, language.label("Bandwidth"))?;
+    generic_type_analyze(store, &mut f, CheckType::Bandwidth, language)?;
+    throughput(store, &mut f, language)?;
+    barrier(&mut f, language.label("Traces"))?;
+    traces(store, &mut f, language)?;
+    barrier(&mut f, language.label("IPv4"))?;
+    gereric_ip_analyze(store, &mut f, IpType::V4, language)?;
+    barrier(&mut f, language.label("IPv6"))?;
+    gereric_ip_analyze(store, &mut f, IpType::V6, language)?;
+    barrier(&mut f, language.label("Targets"))?;
+    targets(store, &mut f)?;
+    barrier(&mut f, language.label("Interfaces"))?;
+    interfaces(store, &mut f)?;
+    barrier(&mut f, language.label("Outages"))?;
+    outages(store, &mut f, outage_limit, &config.severity)?;
+    barrier(&mut f, language.label("Gaps"))?;
+    gaps(store, &mut f, outage_limit, language)?;
+    barrier(&mut f, language.label("Monthly Summary"))?;
+    periods(store, &mut f, Period::Monthly)?;
+    barrier(&mut f, language.label("SLA"))?;
+    sla(store, &mut f, &config.sla, language)?;
+    barrier(&mut f, language.label("Failures by Time"))?;
+    heatmap(store, &mut f, language)?;
+    barrier(&mut f, language.label("Store Metadata"))?;
+    store_meta(store, &mut f, language)?;
+
+    Ok(f)
+}
+
+/// Generate an analysis report for multiple stores, for example from different hosts.
+///
+/// Each store is given with a label that identifies it in the report. The report starts with an
+/// overview of all hosts, followed by the full [analyze] report of each store, headed by its
+/// label. The stores can be given as owned [Stores](Store) or as references. Like [analyze], it
+/// uses the default [Config].
+///
+/// # Errors
+///
+/// Returns [AnalysisError] if the report for any of the stores fails, see [analyze].
+pub fn analyze_hosts<S: Borrow<Store>>(stores: &[(String, S)]) -> Result<String, AnalysisError> {
+    analyze_hosts_with_limit(stores, Some(OUTAGE_LIMIT), &Config::default())
+}
+
+/// Like [analyze_hosts], but with a custom limit of outages and [Config], see
+/// [analyze_with_limit].
+///
+/// # Errors
+///
+/// Returns [AnalysisError] if the report for any of the stores fails, see [analyze].
+pub fn analyze_hosts_with_limit<S: Borrow<Store>>(
+    stores: &[(String, S)],
+    outage_limit: Option<usize>,
+    config: &Config,
+) -> Result<String, AnalysisError> {
+    let language = config.language;
+    let mut f = String::new();
+    barrier(&mut f, language.label("Hosts"))?;
+    for (label, store) in stores {
+        let store = store.borrow();
+        let aggregated: usize = store.aggregates().iter().map(|a| a.total() as usize).sum();
+        let aggregated_ok: usize = store
+            .aggregates()
+            .iter()
+            .map(|a| a.successes() as usize)
+            .sum();
+        let total = store.checks().len() + aggregated;
+        let total_ok = store.checks().iter().filter(|c| c.is_success()).count() + aggregated_ok;
+        key_value_write(
+            &mut f,
+            label,
+            format!(
+                "{:08} checks, {:03.02}% ok",
+                total,
+                success_ratio(total, total_ok) * 100.0
+            ),
+        )?;
+    }
+    writeln!(f)?;
+
+    for (label, store) in stores {
+        barrier(&mut f, &format!("Host {label}"))?;
+        writeln!(f)?;
+        f.push_str(&analyze_with_limit(store.borrow(), outage_limit, config)?);
+        writeln!(f)?;
+    }
 
     Ok(f)
 }
@@ -126,6 +294,134 @@ pub fn fmt_timestamp(timestamp: impl Into<DateTime<Local>>) -> String {
     format!("{}", a.format(TIME_FORMAT_HUMANS))
 }
 
+/// Generate a summary of the uptime per calendar week or month.
+///
+/// For each [Period], the summary shows the uptime, the number of outages, the total downtime
+/// and the longest outage, see [PeriodStats]. This is the section that [analyze] shows for
+/// months, on its own. The labels are written in `language`, see [i18n].
+///
+/// # Errors
+///
+/// Returns [AnalysisError] if string formatting fails.
+pub fn period_summary(
+    store: &Store,
+    period: Period,
+    language: Language,
+) -> Result<String, AnalysisError> {
+    let mut f = String::new();
+    barrier(
+        &mut f,
+        language.label(match period {
+            Period::Weekly => "Weekly Summary",
+            Period::Monthly => "Monthly Summary",
+        }),
+    )?;
+    periods(store, &mut f, period)?;
+    Ok(f)
+}
+
+/// Shows the uptime of every day of the last `months` calendar months, see [Calendar].
+///
+/// Like a contribution graph, every column is a week and every row a day of the week, starting
+/// on monday. Each day is shaded by its ratio of failed checks, like the cells of the heatmap of
+/// the full analysis. The labels are written in `language`, see [i18n].
+///
+/// # Errors
+///
+/// Returns [AnalysisError] if string formatting fails.
+pub fn calendar(store: &Store, months: u32, language: Language) -> Result<String, AnalysisError> {
+    let mut f = String::new();
+    barrier(&mut f, language.label("Calendar"))?;
+    calendar_days(&Calendar::of_store(store, months), &mut f, language)?;
+    Ok(f)
+}
+
+/// Write the days of a [Calendar], followed by the number of days without failures and the
+/// worst day.
+fn calendar_days(
+    calendar: &Calendar,
+    f: &mut String,
+    language: Language,
+) -> Result<(), AnalysisError> {
+    if calendar.is_empty() {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+    let first = calendar.days[0].day();
+    // the first week starts on monday, before the first day if that is not a monday
+    let offset = first.weekday().num_days_from_monday() as usize;
+    let weeks = (offset + calendar.days.len()).div_ceil(7);
+    let day_at = |week: usize, weekday: usize| {
+        (week * 7 + weekday)
+            .checked_sub(offset)
+            .and_then(|idx| calendar.days.get(idx))
+    };
+
+    // the name of each month goes above the week of its first day
+    let mut months = String::new();
+    for week in 0..weeks {
+        let column = 4 + week * 2;
+        if let Some(day) = (0..7)
+            .filter_map(|weekday| day_at(week, weekday))
+            .find(|d| d.day().day() == 1)
+        {
+            if months.len() <= column {
+                months.push_str(&" ".repeat(column - months.len()));
+                months.push_str(&day.day().format("%b").to_string());
+            }
+        }
+    }
+    writeln!(f, "{months}")?;
+    for (weekday, name) in WEEKDAYS.iter().enumerate() {
+        let mut row = name.to_string();
+        for week in 0..weeks {
+            let shade = day_at(week, weekday).map_or(' ', |d| heat_shade(d.failure_ratio()));
+            write!(row, " {shade}")?;
+        }
+        writeln!(f, "{}", row.trim_end())?;
+    }
+    writeln!(
+        f,
+        "\n' ' no checks, '.' no failures, '░' < 1%, '▒' < 5%, '▓' < 20%, '█' >= 20% failed\n"
+    )?;
+
+    let checked: Vec<&CalendarDay> = calendar.days.iter().filter(|d| d.checks > 0).collect();
+    key_value_write(
+        f,
+        language.label("days without failures"),
+        format!(
+            "{} of {}",
+            checked.iter().filter(|d| d.checks_ok == d.checks).count(),
+            checked.len()
+        ),
+    )?;
+    if let Some((day, uptime)) = checked
+        .iter()
+        .filter_map(|d| Some((d, d.uptime()?)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+    {
+        key_value_write(
+            f,
+            language.label("worst day"),
+            format!("{}, {:03.03}% up", day.date, uptime * 100.0),
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Formats a throughput in bytes per second as megabits per second.
+///
+/// # Examples
+///
+/// ```rust
+/// # use netpulse::analyze::fmt_throughput;
+/// assert_eq!(fmt_throughput(1_250_000), "10.00 Mbit/s");
+/// ```
+pub fn fmt_throughput(bytes_per_second: u32) -> String {
+    format!("{:.02} Mbit/s", bytes_per_second as f64 * 8.0 / 1_000_000.0)
+}
+
 /// Adds a section divider to the report with a title.
 ///
 /// Creates a divider line of '=' characters with the title centered.
@@ -152,53 +448,147 @@ fn key_value_write(
 /// Analyzes and formats outage information from the store.
 ///
 /// Groups consecutive failed checks by check type and creates
-/// Outage records for reporting.
-fn outages(store: &Store, f: &mut String) -> Result<(), AnalysisError> {
+/// Outage records for reporting. Shows up to `limit` outages per list, or all if it is [None].
+/// The most severe outages are ranked by `weights`.
+fn outages(
+    store: &Store,
+    f: &mut String,
+    limit: Option<usize>,
+    weights: &SeverityConfig,
+) -> Result<(), AnalysisError> {
     let all: Vec<&Check> = store.checks().iter().collect();
-    let fails_exist = !all.iter().all(|c| c.is_success());
+    let fails_exist = !all.iter().all(|c| is_healthy(c));
     if !fails_exist || all.is_empty() {
         writeln!(f, "None\n")?;
         return Ok(());
     }
 
-    let fail_groups = fail_groups(&all);
-    let mut outages: Vec<Outage> = fail_groups
-        .iter()
-        .map(|a| Outage::try_from(a).expect("check fail group was empty"))
+    let mut outages: Vec<Outage> = fail_groups(&all)
+        .into_iter()
+        .map(|group| Outage::try_from(group).expect("check fail group was empty"))
         .collect();
     outages.sort();
 
+    let shown = limit.unwrap_or(outages.len()).min(outages.len());
+
     writeln!(f, "Latest\n")?;
 
-    for (outage_idx, outage) in outages.iter().rev().enumerate() {
+    for (outage_idx, outage) in outages.iter().rev().take(shown).enumerate() {
         writeln!(f, "{outage_idx}:\t{}", &outage.short_report()?)?;
-        if outage_idx >= 9 {
-            writeln!(f, "\nshowing only the 10 latest outages...\n")?;
-            break;
-        }
+    }
+    if shown < outages.len() {
+        writeln!(
+            f,
+            "\nshowing only the {shown} latest of {} outages...\n",
+            outages.len()
+        )?;
     }
 
     writeln!(f, "\nMost severe\n")?;
 
-    outages.sort_by(Outage::cmp_severity);
+    outages.sort_by(|a, b| a.cmp_severity(b, weights));
 
-    for (outage_idx, outage) in outages.iter().rev().enumerate() {
+    for (outage_idx, outage) in outages.iter().rev().take(shown).enumerate() {
         writeln!(f, "{outage_idx}:\t{}", &outage.short_report()?)?;
-        if outage_idx >= 9 {
-            writeln!(f, "\nshowing only the 10 most severe outages...")?;
-            break;
-        }
+    }
+    if shown < outages.len() {
+        writeln!(
+            f,
+            "\nshowing only the {shown} most severe of {} outages...",
+            outages.len()
+        )?;
     }
     writeln!(f)?;
     Ok(())
 }
 
+/// Write the [Gaps](Gap) in which no checks were made, latest first.
+///
+/// Shows up to `limit` gaps, or all if it is [None]. The gaps are not outages, nothing is known
+/// about the connection in them.
+fn gaps(
+    store: &Store,
+    f: &mut String,
+    limit: Option<usize>,
+    language: Language,
+) -> Result<(), AnalysisError> {
+    let gaps = Gap::of_store(store);
+    if gaps.is_empty() {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+
+    let unmonitored: i64 = gaps.iter().map(Gap::duration).sum();
+    let (first, last) = match (store.checks().first(), store.checks().last()) {
+        (Some(first), Some(last)) => (first.timestamp(), last.timestamp()),
+        _ => (0, 0),
+    };
+    key_value_write(f, language.label("gaps"), gaps.len())?;
+    key_value_write(
+        f,
+        language.label("unmonitored"),
+        format!("{}h {:02}m", unmonitored / 3600, unmonitored % 3600 / 60),
+    )?;
+    if last > first {
+        key_value_write(
+            f,
+            language.label("monitored"),
+            format!(
+                "{:03.03}% of the time since the first check",
+                (1.0 - unmonitored as f64 / (last - first) as f64).max(0.0) * 100.0
+            ),
+        )?;
+    }
+    writeln!(f)?;
+
+    let shown = limit.unwrap_or(gaps.len()).min(gaps.len());
+    for (gap_idx, gap) in gaps.iter().rev().take(shown).enumerate() {
+        writeln!(f, "{gap_idx}:\t{}", gap.report(language))?;
+    }
+    if shown < gaps.len() {
+        writeln!(
+            f,
+            "\nshowing only the {shown} latest of {} gaps...",
+            gaps.len()
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Writes the short report of every outage in the [Store] to `out`, one per line and latest
+/// first.
+///
+/// Unlike the report of [analyze], the outages are not limited, and each line is written as soon
+/// as it is ready, so the output can be piped into a pager. An outage that lasted until the last
+/// round of checks is marked as ongoing, see [outage_summaries].
+///
+/// # Errors
+///
+/// Returns [AnalysisError::Io] if writing to `out` fails, for example because the pager was
+/// closed.
+pub fn write_all_outages(
+    store: &Store,
+    out: &mut impl std::io::Write,
+) -> Result<(), AnalysisError> {
+    for (outage_idx, summary) in outage_summaries(store).iter().rev().enumerate() {
+        writeln!(out, "{outage_idx}:\t{}", summary.short_report()?)?;
+    }
+    Ok(())
+}
+
 /// Analyzes and formats outage information from the store.
 ///
 /// Groups consecutive failed checks by check type and creates
-/// Outage records for reporting. This is the more detailed version of [outages]
-pub fn outages_detailed(all: &[&Check], f: &mut String, dump: bool) -> Result<(), AnalysisError> {
-    let fails_exist = !all.iter().all(|c| c.is_success());
+/// Outage records for reporting. This is the more detailed version of [outages]. The labels
+/// are written in `language`, see [i18n].
+pub fn outages_detailed(
+    all: &[&Check],
+    f: &mut String,
+    dump: bool,
+    language: Language,
+) -> Result<(), AnalysisError> {
+    let fails_exist = !all.iter().all(|c| is_healthy(c));
     if !fails_exist || all.is_empty() {
         writeln!(f, "None\n")?;
         return Ok(());
@@ -211,7 +601,11 @@ pub fn outages_detailed(all: &[&Check], f: &mut String, dump: bool) -> Result<()
             continue;
         }
         let outage = Outage::try_from(group).expect("fail group was empty");
-        writeln!(f, "{outage_idx}:\n{}", more_indent(&outage.to_string()))?;
+        writeln!(
+            f,
+            "{outage_idx}:\n{}",
+            more_indent(&outage.report(language)?)
+        )?;
         if dump {
             let mut buf = String::new();
             display_group(outage.all(), &mut buf)?;
@@ -223,111 +617,246 @@ pub fn outages_detailed(all: &[&Check], f: &mut String, dump: bool) -> Result<()
     Ok(())
 }
 
-fn group_by_time<'check>(checks: &[&'check Check]) -> HashMap<i64, CheckGroup<'check>> {
-    let mut groups: HashMap<i64, CheckGroup<'check>> = HashMap::new();
+/// Finds all outages in the [Store] and summarizes them, sorted by time.
+///
+/// This is the stable API for integrators that need the outages as data instead of text. See
+/// [OutageSummary] for the contained information.
+///
+/// If the [OutageLog](crate::records::outage::OutageLog) of the [Store] is up to date, the
+/// outages are read from it, including those whose checks were downsampled or archived since.
+/// Otherwise they are found in the checks.
+pub fn outage_summaries(store: &Store) -> Vec<OutageSummary> {
+    if let Some(log) = store.outage_log() {
+        return log.records().iter().map(|r| r.to_summary()).collect();
+    }
+    let all: Vec<&Check> = store.checks().iter().collect();
+    let mut summaries: Vec<OutageSummary> = fail_groups(&all)
+        .into_iter()
+        .filter_map(|group| Outage::try_from(group).ok())
+        .map(|outage| outage.to_summary())
+        .collect();
+    if let (Some(summary), Some(last)) = (summaries.last_mut(), store.checks().last()) {
+        summary.ongoing = summary.end == last.timestamp();
+    }
+    summaries
+}
+
+/// How the latest round of checks went, see [latest_health].
+///
+/// Ordered from best to worst, so the worst of several can be found with [Iterator::max].
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub enum Health {
+    /// All checks succeeded in time
+    Healthy,
+    /// All checks succeeded, but some were [degraded](Check::is_degraded)
+    Degraded,
+    /// Some checks failed
+    Outage,
+}
 
-    for check in checks {
-        groups.entry(check.timestamp()).or_default().push(check);
+impl Health {
+    /// Returns the exit code that `netpulse --status` exits with: `0` if [healthy](Self::Healthy),
+    /// `1` if [degraded](Self::Degraded) and `2` in an [outage](Self::Outage).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Healthy => 0,
+            Self::Degraded => 1,
+            Self::Outage => 2,
+        }
     }
+}
 
-    groups
+impl Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Healthy => write!(f, "healthy"),
+            Self::Degraded => write!(f, "degraded"),
+            Self::Outage => write!(f, "outage"),
+        }
+    }
 }
 
-fn fail_groups<'check>(checks: &[&'check Check]) -> Vec<CheckGroup<'check>> {
+/// Tells the [Health] of the latest round of time-sorted `checks`, like [Store::checks].
+///
+/// Returns [None] if there are no checks.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use netpulse::analyze::latest_health;
+/// use netpulse::store::Store;
+///
+/// let store = Store::load(true).unwrap();
+/// if let Some(health) = latest_health(store.checks()) {
+///     println!("the network is {health}");
+/// }
+/// ```
+pub fn latest_health(checks: &[Check]) -> Option<Health> {
+    let last = checks.last()?.timestamp();
+    let round = checks.iter().rev().take_while(|c| c.timestamp() == last);
+    let mut health = Health::Healthy;
+    for check in round {
+        if !check.is_success() {
+            return Some(Health::Outage);
+        }
+        if check.is_degraded() {
+            health = Health::Degraded;
+        }
+    }
+    Some(health)
+}
+
+/// Splits time-sorted `checks` into the rounds of checks made at the same time.
+fn group_by_time<'a, 'check>(
+    checks: &'a [&'check Check],
+) -> impl Iterator<Item = &'a [&'check Check]> {
+    checks.chunk_by(|a, b| a.timestamp() == b.timestamp())
+}
+
+/// Returns true if `check` succeeded and was not [degraded](Check::is_degraded), so it does not
+/// belong to an outage.
+pub(crate) fn is_healthy(check: &Check) -> bool {
+    check.is_success() && !check.is_degraded()
+}
+
+/// Groups consecutive rounds with failed or degraded checks, oldest first.
+///
+/// A round is made of the checks made at the same time. Each group holds all checks of its
+/// rounds. The checks are walked once in time order, they are only sorted first if they are not
+/// sorted already.
+pub(crate) fn fail_groups<'check>(checks: &[&'check Check]) -> Vec<CheckGroup<'check>> {
     trace!("calculating fail groups");
+    let sorted: Vec<&Check>;
+    let checks = if checks.is_sorted() {
+        checks
+    } else {
+        sorted = {
+            let mut sorted = checks.to_vec();
+            sorted.sort();
+            sorted
+        };
+        &sorted
+    };
+
     let mut groups: Vec<CheckGroup<'check>> = Vec::new();
-    let by_time = group_by_time(checks);
-    let mut time_sorted_values: Vec<&Vec<&Check>> = by_time.values().collect();
-    time_sorted_values.sort();
-
-    let mut in_group = false;
-    let mut current_group: Vec<&Check> = Vec::new();
-
-    for checks in time_sorted_values {
-        let ok = checks.iter().all(|a| a.is_success());
-        if !ok {
-            if !in_group {
-                in_group = true;
+    let mut current: CheckGroup<'check> = Vec::new();
+    for round in group_by_time(checks) {
+        if round.iter().all(|c| is_healthy(c)) {
+            // end of the outage, if there was one
+            if !current.is_empty() {
+                groups.push(std::mem::take(&mut current));
             }
-            current_group.extend(checks);
-        } else if in_group && ok {
-            // end of the outage
-
-            in_group = false;
-            groups.push(current_group);
-            current_group = Vec::new();
+        } else {
+            current.extend_from_slice(round);
         }
     }
-
-    // finishing up, some might be left over
-    if in_group {
-        groups.push(current_group);
+    if !current.is_empty() {
+        groups.push(current);
     }
-
-    groups.sort();
     groups
 }
 
-/// Analyze metrics for a specific check type.
+/// Write the [Stats] of a set of checks.
 ///
-/// Calculates and formats:
+/// Formats:
 /// - Total check count
 /// - Success/failure counts
 /// - Success ratio
 /// - First/last check timestamps
 ///
+/// The counts of [Aggregates](crate::records::aggregate::Aggregate) are included in the [Stats].
+///
 /// # Errors
 ///
 /// Returns [AnalysisError] if formatting fails.
 fn analyze_check_type_set(
     f: &mut String,
-    all: &[&Check],
-    successes: &[&Check],
+    stats: &Stats,
+    language: Language,
 ) -> Result<(), AnalysisError> {
-    if all.is_empty() {
+    if stats.is_empty() {
         writeln!(f, "None\n")?;
         return Ok(());
     }
-    key_value_write(f, "checks", format!("{:08}", all.len()))?;
-    key_value_write(f, "checks ok", format!("{:08}", successes.len()))?;
-    key_value_write(
-        f,
-        "checks bad",
-        format!("{:08}", all.len() - successes.len()),
-    )?;
+
+    key_value_write(f, language.label("checks"), format!("{:08}", stats.checks))?;
     key_value_write(
         f,
-        "success ratio",
-        format!(
-            "{:03.02}%",
-            success_ratio(all.len(), successes.len()) * 100.0
-        ),
+        language.label("checks ok"),
+        format!("{:08}", stats.checks_ok),
     )?;
+    if stats.checks_ok_retried > 0 {
+        key_value_write(
+            f,
+            language.label("checks ok on retry"),
+            format!("{:08}", stats.checks_ok_retried),
+        )?;
+    }
+    if stats.checks_ok_degraded > 0 {
+        key_value_write(
+            f,
+            language.label("checks ok but degraded"),
+            format!("{:08}", stats.checks_ok_degraded),
+        )?;
+    }
     key_value_write(
         f,
-        "first check at",
-        fmt_timestamp(all.first().unwrap().timestamp_parsed()),
+        language.label("checks bad"),
+        format!("{:08}", stats.checks_bad),
     )?;
+    if stats.checks_aggregated > 0 {
+        key_value_write(
+            f,
+            language.label("checks aggregated"),
+            format!("{:08}", stats.checks_aggregated),
+        )?;
+    }
     key_value_write(
         f,
-        "last check at",
-        fmt_timestamp(all.last().unwrap().timestamp_parsed()),
+        language.label("success ratio"),
+        format!("{:03.02}%", stats.success_ratio.unwrap_or_default() * 100.0),
     )?;
+    if let Some(first) = stats.first_check_at {
+        key_value_write(
+            f,
+            language.label("first check at"),
+            fmt_timestamp(timestamp_local(first)),
+        )?;
+    }
+    if let Some(last) = stats.last_check_at {
+        key_value_write(
+            f,
+            language.label("last check at"),
+            fmt_timestamp(timestamp_local(last)),
+        )?;
+    }
     writeln!(f)?;
     Ok(())
 }
 
+/// Converts unix seconds into a [DateTime] in the timezone of the user.
+fn timestamp_local(timestamp: i64) -> DateTime<Local> {
+    Local.timestamp_opt(timestamp, 0).unwrap()
+}
+
 /// Write general check statistics section of the report.
 ///
 /// Includes metrics across all check types combined.
-fn generalized(store: &Store, f: &mut String) -> Result<(), AnalysisError> {
-    if store.checks().is_empty() {
+fn generalized(store: &Store, f: &mut String, language: Language) -> Result<(), AnalysisError> {
+    if store.checks().is_empty() && store.aggregates().is_empty() {
         writeln!(f, "Store has no checks yet\n")?;
         return Ok(());
     }
-    let all: Vec<&Check> = store.checks().iter().collect();
-    let successes: Vec<&Check> = store.checks().iter().filter(|c| c.is_success()).collect();
-    analyze_check_type_set(f, &all, &successes)?;
+    analyze_check_type_set(f, &Stats::of_store(store, |_| true, |_| true), language)?;
+    if let Some(uptime) = Coverage::of_store(store).uptime(i64::MIN, i64::MAX) {
+        // counts the time without checks as down, unlike the success ratio
+        key_value_write(
+            f,
+            language.label("time covered as up"),
+            format!("{:03.02}%", uptime * 100.0),
+        )?;
+        writeln!(f)?;
+    }
     Ok(())
 }
 
@@ -354,14 +883,13 @@ fn generalized(store: &Store, f: &mut String) -> Result<(), AnalysisError> {
 /// Prints warning to stderr if:
 /// - Check has both IPv4 and IPv6 flags set
 /// - Check has no IP version flags set
-fn gereric_ip_analyze(store: &Store, f: &mut String, ip_type: IpType) -> Result<(), AnalysisError> {
-    let all: Vec<&Check> = store
-        .checks()
-        .iter()
-        .filter(|c| c.ip_type() == ip_type)
-        .collect();
-    let successes: Vec<&Check> = all.clone().into_iter().filter(|c| c.is_success()).collect();
-    analyze_check_type_set(f, &all, &successes)?;
+fn gereric_ip_analyze(
+    store: &Store,
+    f: &mut String,
+    ip_type: IpType,
+    language: Language,
+) -> Result<(), AnalysisError> {
+    analyze_check_type_set(f, &Stats::of_ip_type(store, ip_type), language)?;
     Ok(())
 }
 /// Includes metrics across all check types combined.
@@ -369,14 +897,482 @@ fn generic_type_analyze(
     store: &Store,
     f: &mut String,
     check_type: CheckType,
+    language: Language,
 ) -> Result<(), AnalysisError> {
-    let all: Vec<&Check> = store
-        .checks()
-        .iter()
-        .filter(|c| c.calc_type().unwrap_or(CheckType::Unknown) == check_type)
-        .collect();
-    let successes: Vec<&Check> = all.clone().into_iter().filter(|c| c.is_success()).collect();
-    analyze_check_type_set(f, &all, &successes)?;
+    analyze_check_type_set(f, &Stats::of_check_type(store, check_type), language)?;
+    Ok(())
+}
+
+/// Write the failure reasons section of the report.
+///
+/// Counts the failed [Checks](Check) per [reason](Check::failure_reason). Failed [Checks](Check)
+/// without a reason are from a store version that did not record it yet, or failed for a reason
+/// that is not one of the [REASONS](crate::records::CheckFlag::REASONS).
+fn failure_reasons(store: &Store, f: &mut String, language: Language) -> Result<(), AnalysisError> {
+    let reasons = FailureReasons::of_checks(store.checks());
+    let total = reasons.total();
+    if total == 0 {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+
+    let write_count = |f: &mut String, title: &str, n: usize| {
+        key_value_write(
+            f,
+            title,
+            format!("{:08} ({:03.02}%)", n, success_ratio(total, n) * 100.0),
+        )
+    };
+    for (reason, n) in &reasons.per_reason {
+        write_count(f, language.label(reason), *n)?;
+    }
+    if reasons.unknown > 0 {
+        write_count(f, language.label("unknown"), reasons.unknown)?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the HTTP status code distribution section of the report.
+///
+/// Counts the HTTP [Checks](Check) per status code. HTTP [Checks](Check) without a status code
+/// either got no response at all (connection failures) or are from a store version that did not
+/// record status codes yet.
+fn http_status_codes(store: &Store, f: &mut String) -> Result<(), AnalysisError> {
+    let codes = StatusCodes::of_store(store);
+    let total = codes.total();
+    if total == 0 {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+
+    let write_count = |f: &mut String, title: &str, n: usize| {
+        key_value_write(
+            f,
+            title,
+            format!("{:08} ({:03.02}%)", n, success_ratio(total, n) * 100.0),
+        )
+    };
+    for (status, n) in &codes.per_status {
+        write_count(f, &status.to_string(), *n)?;
+    }
+    write_count(f, "no response", codes.no_response)?;
+    if codes.not_recorded > 0 {
+        write_count(f, "not recorded", codes.not_recorded)?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the packet loss section of the report.
+///
+/// Shows the total loss of all ICMP bursts and the loss per day for the last
+/// [LOSS_TREND_DAYS] days that have measurements.
+fn packet_loss(store: &Store, f: &mut String, language: Language) -> Result<(), AnalysisError> {
+    let loss = LossStats::of_checks(store.checks());
+    if loss.is_empty() {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+
+    key_value_write(
+        f,
+        language.label("echoes sent"),
+        format!("{:08}", loss.sent),
+    )?;
+    key_value_write(
+        f,
+        language.label("echoes received"),
+        format!("{:08}", loss.received),
+    )?;
+    key_value_write(
+        f,
+        language.label("loss"),
+        format!("{:03.02}%", loss.loss_ratio.unwrap_or_default() * 100.0),
+    )?;
+    writeln!(f, "\nPer day\n")?;
+    let skip = loss.per_day.len().saturating_sub(LOSS_TREND_DAYS);
+    for day in loss.per_day.iter().skip(skip) {
+        let date = Local.timestamp_opt(day.day, 0).unwrap().format("%Y-%m-%d");
+        key_value_write(
+            f,
+            &date.to_string(),
+            format!("{:03.02}% of {:08}", day.loss_ratio * 100.0, day.sent),
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the measured clock offset per NTP server.
+///
+/// Writes nothing if there are no NTP measurements, the [Stats] of the NTP checks already say
+/// so.
+fn clock_offset(store: &Store, f: &mut String) -> Result<(), AnalysisError> {
+    let servers = NtpStats::of_checks(store.checks());
+    if servers.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "Clock offset\n")?;
+    for server in servers {
+        key_value_write(
+            f,
+            &server.name(),
+            format!("latest {} ms, largest {} ms", server.latest, server.largest),
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the measured throughput of the bandwidth samples.
+///
+/// Shows the average and latest throughput and the throughput per day for the last
+/// [LOSS_TREND_DAYS] days that have samples. Writes nothing if there are no samples, the [Stats]
+/// of the bandwidth checks already say so.
+fn throughput(store: &Store, f: &mut String, language: Language) -> Result<(), AnalysisError> {
+    let throughput = ThroughputStats::of_checks(store.checks());
+    if throughput.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "Throughput\n")?;
+    key_value_write(
+        f,
+        language.label("samples"),
+        format!("{:08}", throughput.samples),
+    )?;
+    key_value_write(
+        f,
+        language.label("average"),
+        fmt_throughput(throughput.average.unwrap_or_default()),
+    )?;
+    key_value_write(
+        f,
+        language.label("latest"),
+        fmt_throughput(throughput.latest.unwrap_or_default()),
+    )?;
+    writeln!(f, "\nPer day\n")?;
+    let skip = throughput.per_day.len().saturating_sub(LOSS_TREND_DAYS);
+    for day in throughput.per_day.iter().skip(skip) {
+        let date = Local.timestamp_opt(day.day, 0).unwrap().format("%Y-%m-%d");
+        key_value_write(
+            f,
+            &date.to_string(),
+            format!(
+                "{} average, {} lowest",
+                fmt_throughput(day.average),
+                fmt_throughput(day.lowest)
+            ),
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the uptime, outages and downtime per [Period].
+fn periods(store: &Store, f: &mut String, period: Period) -> Result<(), AnalysisError> {
+    let periods = PeriodStats::of_store(store, period);
+    if periods.is_empty() {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+    let fmt_duration = |seconds: i64| format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60);
+    for stats in periods {
+        key_value_write(
+            f,
+            &stats.name,
+            format!(
+                "{:03.03}% up, {:03.03}% of time up, {:04} outages, down {}, longest {}, unmonitored {}",
+                stats.uptime * 100.0,
+                stats.time_uptime * 100.0,
+                stats.outages,
+                fmt_duration(stats.downtime),
+                fmt_duration(stats.longest_outage),
+                fmt_duration(stats.unmonitored)
+            ),
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the error budget of the current period, see [SlaStats].
+///
+/// Writes that it is not configured if `config` has no uptime.
+fn sla(
+    store: &Store,
+    f: &mut String,
+    config: &SlaConfig,
+    language: Language,
+) -> Result<(), AnalysisError> {
+    let Some(sla) = SlaStats::of_store(store, config) else {
+        writeln!(f, "Not configured\n")?;
+        return Ok(());
+    };
+    let fmt_minutes = |seconds: i64| format!("{:.01} min", seconds as f64 / 60.0);
+    key_value_write(
+        f,
+        language.label("target"),
+        format!("{:03.03}%", sla.target * 100.0),
+    )?;
+    key_value_write(
+        f,
+        language.label("uptime of"),
+        match sla.basis {
+            UptimeBasis::Checks => "checks",
+            UptimeBasis::Time => "time",
+        },
+    )?;
+    match &sla.current {
+        Some(current) => key_value_write(
+            f,
+            &current.name,
+            format!("{:03.03}%", current.uptime_by(sla.basis) * 100.0),
+        )?,
+        None => key_value_write(f, language.label("current period"), "no checks yet")?,
+    }
+    key_value_write(f, language.label("error budget"), fmt_minutes(sla.budget))?;
+    key_value_write(f, language.label("used"), fmt_minutes(sla.used))?;
+    key_value_write(f, language.label("remaining"), fmt_minutes(sla.remaining))?;
+    if sla.exceeded {
+        writeln!(f, "\nThe error budget of this period is EXCEEDED")?;
+    }
+    if !sla.missed.is_empty() {
+        writeln!(f, "\nMissed the target\n")?;
+        for period in &sla.missed {
+            key_value_write(
+                f,
+                &period.name,
+                format!("{:03.03}%", period.uptime_by(sla.basis) * 100.0),
+            )?;
+        }
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the failures by day of the week and hour of the day, see [Heatmap].
+///
+/// Each cell is shaded by its ratio of failed checks, followed by the worst hour of the day and
+/// the worst day of the week.
+fn heatmap(store: &Store, f: &mut String, language: Language) -> Result<(), AnalysisError> {
+    let heatmap = Heatmap::of_checks(store.checks());
+    if heatmap.is_empty() {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+
+    write!(f, "    ")?;
+    for hour in 0..24 {
+        write!(f, " {hour:02}")?;
+    }
+    writeln!(f)?;
+    for (day, row) in WEEKDAYS.iter().zip(&heatmap.cells) {
+        write!(f, "{day} ")?;
+        for cell in row {
+            let shade = heat_shade(cell.failure_ratio());
+            write!(f, " {shade}{shade}")?;
+        }
+        writeln!(f)?;
+    }
+    writeln!(
+        f,
+        "\n' ' no checks, '.' no failures, '░' < 1%, '▒' < 5%, '▓' < 20%, '█' >= 20% failed\n"
+    )?;
+
+    let worst = |cells: Vec<report::HeatmapCell>| {
+        cells
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, cell)| Some((idx, cell.failure_ratio()?)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    };
+    if let Some((hour, ratio)) = worst(heatmap.per_hour()) {
+        key_value_write(
+            f,
+            language.label("worst hour"),
+            format!(
+                "{hour:02}:00 - {hour:02}:59, {:03.02}% failed",
+                ratio * 100.0
+            ),
+        )?;
+    }
+    if let Some((day, ratio)) = worst(heatmap.per_weekday()) {
+        key_value_write(
+            f,
+            language.label("worst day"),
+            format!("{}, {:03.02}% failed", WEEKDAYS[day], ratio * 100.0),
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Returns the character a cell of the heatmap is shaded with for a ratio of failed checks.
+fn heat_shade(failure_ratio: Option<f64>) -> char {
+    match failure_ratio {
+        None => ' ',
+        Some(0.0) => '.',
+        Some(r) if r < 0.01 => '░',
+        Some(r) if r < 0.05 => '▒',
+        Some(r) if r < 0.2 => '▓',
+        Some(_) => '█',
+    }
+}
+
+/// Write the measured path MTU per target.
+///
+/// Writes nothing if there are no MTU measurements, the [Stats] of the MTU checks already say
+/// so.
+fn path_mtu(store: &Store, f: &mut String) -> Result<(), AnalysisError> {
+    let targets = MtuStats::of_checks(store.checks());
+    if targets.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "Path MTU\n")?;
+    for target in targets {
+        key_value_write(
+            f,
+            &target.name(),
+            format!(
+                "latest {}, smallest {}, largest {}",
+                target.latest, target.smallest, target.largest
+            ),
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write how many outages were in the local network and how many upstream.
+///
+/// Writes nothing if no outage has [gateway checks](CheckType::Gateway), the [Stats] of the
+/// gateway checks already say so.
+fn outage_locations(
+    store: &Store,
+    f: &mut String,
+    language: Language,
+) -> Result<(), AnalysisError> {
+    let summaries = outage_summaries(store);
+    let located: Vec<Location> = summaries.iter().filter_map(|s| s.location).collect();
+    if located.is_empty() {
+        return Ok(());
+    }
+    writeln!(
+        f,
+        "Outages by location
+"
+    )?;
+    for location in [Location::LocalNetwork, Location::Upstream] {
+        let n = located.iter().filter(|l| **l == location).count();
+        key_value_write(f, &location.to_string(), format!("{n:08}"))?;
+    }
+    key_value_write(
+        f,
+        language.label("Unknown"),
+        format!("{:08}", summaries.len() - located.len()),
+    )?;
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the traces section of the report.
+///
+/// Counts where the paths to the targets of failed checks died and shows the
+/// [LATEST_TRACES] latest traces hop by hop.
+fn traces(store: &Store, f: &mut String, language: Language) -> Result<(), AnalysisError> {
+    let stats = TraceStats::of_traces(store.traces());
+    if stats.traces == 0 {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+
+    key_value_write(f, language.label("traces"), format!("{:08}", stats.traces))?;
+    for (segment, n) in &stats.per_segment {
+        key_value_write(
+            f,
+            &segment.to_string(),
+            format!(
+                "{:08} ({:03.02}%)",
+                n,
+                success_ratio(stats.traces, *n) * 100.0
+            ),
+        )?;
+    }
+    writeln!(
+        f,
+        "
+Latest
+"
+    )?;
+    let mut latest: Vec<_> = store.traces().iter().collect();
+    latest.sort_by_key(|t| std::cmp::Reverse(t.timestamp()));
+    for (idx, trace) in latest.iter().take(LATEST_TRACES).enumerate() {
+        writeln!(
+            f,
+            "{idx}: {}\n{}",
+            trace.segment(),
+            more_indent(&trace.to_string())
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the per target section of the report.
+///
+/// Lists each target with the number of checks against it and their success ratio. Targets are
+/// shown by their label if the latest [Check] against them has one.
+fn targets(store: &Store, f: &mut String) -> Result<(), AnalysisError> {
+    let targets = TargetStats::of_store(store);
+    if targets.is_empty() {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+    for target in targets {
+        key_value_write(
+            f,
+            &target.name(),
+            format!(
+                "{:08} checks, {:03.02}% ok",
+                target.checks,
+                target.success_ratio * 100.0
+            ),
+        )?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Write the per interface section of the report.
+///
+/// Each interface that checks were bound to is analyzed like a connection of its own, with its
+/// own outages, so uplinks can be compared. Only raw [Checks](Check) know their interface.
+fn interfaces(store: &Store, f: &mut String) -> Result<(), AnalysisError> {
+    let interfaces = InterfaceStats::of_checks(store.checks());
+    if interfaces.is_empty() {
+        writeln!(f, "None\n")?;
+        return Ok(());
+    }
+    let fmt_duration = |seconds: i64| format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60);
+    for interface in interfaces {
+        key_value_write(
+            f,
+            &interface.interface,
+            format!(
+                "{:08} checks, {:03.02}% ok, {:04} outages, down {}, longest {}",
+                interface.stats.checks,
+                interface.stats.success_ratio.unwrap_or(0.0) * 100.0,
+                interface.outages,
+                fmt_duration(interface.downtime),
+                fmt_duration(interface.longest_outage)
+            ),
+        )?;
+        if let Some(outage) = &interface.latest_outage {
+            writeln!(f, "\tlatest outage: {}", outage.short_report()?)?;
+        }
+    }
+    writeln!(f)?;
     Ok(())
 }
 
@@ -388,21 +1384,40 @@ fn generic_type_analyze(
 /// - Size of in memory [Store], including all children (the actual checks)
 /// - Size of the [Store] file
 /// - Ratio of [Store] file size and in memory [Store]
-fn store_meta(store: &Store, f: &mut String) -> Result<(), AnalysisError> {
-    let store_size_mem = store.deep_size_of();
-    let store_size_fs = std::fs::metadata(Store::path())?.size();
-
-    key_value_write(f, "Hash mem blake3", store.get_hash())?;
-    key_value_write(f, "Hash file sha256", store.get_hash_of_file()?)?;
-    key_value_write(f, "Store Version (mem)", store.version())?;
-    key_value_write(f, "Store Version (file)", Store::peek_file_version()?)?;
-    key_value_write(f, "Store Size (mem)", store_size_mem)?;
-    key_value_write(f, "Store Size (file)", store_size_fs)?;
+/// - When the [Store] was created and last saved, and by which version of netpulse
+/// - The random instance ID of the [Store]
+fn store_meta(store: &Store, f: &mut String, language: Language) -> Result<(), AnalysisError> {
+    let meta = StoreMeta::of_store(store)?;
+
+    key_value_write(f, language.label("Hash mem blake3"), &meta.hash_mem)?;
+    key_value_write(f, language.label("Hash file sha256"), &meta.hash_file)?;
+    key_value_write(f, language.label("Store Version (mem)"), meta.version_mem)?;
+    key_value_write(f, language.label("Store Version (file)"), meta.version_file)?;
+    key_value_write(f, language.label("Store Size (mem)"), meta.size_mem)?;
+    key_value_write(f, language.label("Store Size (file)"), meta.size_file)?;
     key_value_write(
         f,
-        "File to Mem Ratio",
-        store_size_fs as f64 / store_size_mem as f64,
+        language.label("File to Mem Ratio"),
+        meta.size_file as f64 / meta.size_mem as f64,
     )?;
+    let time = |timestamp: i64| {
+        Local
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .map_or_else(|| timestamp.to_string(), fmt_timestamp)
+    };
+    key_value_write(f, language.label("Created"), time(meta.created))?;
+    key_value_write(
+        f,
+        language.label("Last Saved"),
+        meta.last_saved.map_or_else(|| "never".to_string(), time),
+    )?;
+    key_value_write(
+        f,
+        language.label("Written by"),
+        format!("netpulse {}", meta.written_by),
+    )?;
+    key_value_write(f, language.label("Instance ID"), meta.instance_id)?;
     Ok(())
 }
 
@@ -417,13 +1432,24 @@ fn success_ratio(all_checks: usize, subset: usize) -> f64 {
 #[cfg(test)]
 mod tests {
 
-    use chrono::{Timelike, Utc};
+    use std::time::Duration;
+
+    use chrono::{Local, TimeZone, Timelike, Utc};
+    use flagset::FlagSet;
     use tracing_test::traced_test;
 
-    use crate::analyze::Outage;
-    use crate::records::{Check, CheckFlag, TARGETS};
+    use crate::analyze::diagnose::{diagnose, Hint};
+    use crate::analyze::gap::Gap;
+    use crate::analyze::outage::{Family, Location, Severity};
+    use crate::analyze::{latest_health, Health, Outage};
+    use crate::records::trace::{Segment, Trace};
+    use crate::records::{Check, CheckFlag, CheckType, Measurement, TARGETS};
 
-    use super::{fail_groups, group_by_time};
+    use super::i18n::Language;
+    use super::{
+        fail_groups, group_by_time, Calendar, Coverage, Heatmap, InterfaceStats, LossStats,
+        MtuStats, Period, PeriodStats, SlaStats, Stats, ThroughputStats, TraceStats, UptimeBasis,
+    };
 
     #[rustfmt::skip]
     fn basic_check_set() -> Vec<Check>{
@@ -483,6 +1509,291 @@ mod tests {
                 Outage::try_from(fg[1].clone()).unwrap(),
             ];
         }
+
+        // unsorted checks are sorted first
+        let reversed: Vec<&Check> = checks.iter().rev().copied().collect();
+        let fg_reversed = fail_groups(&reversed);
+        assert_eq!(fg_reversed.len(), 2);
+        assert_eq!(fg_reversed[0].len(), 8);
+        assert_eq!(fg_reversed[1].len(), 4);
+        assert!(fg_reversed[0].is_sorted());
+    }
+
+    #[test]
+    fn test_latest_health() {
+        assert_eq!(latest_health(&[]), None);
+
+        let mut checks = basic_check_set();
+        assert_eq!(latest_health(&checks), Some(Health::Outage));
+
+        let time = checks.last().unwrap().timestamp_parsed() + chrono::Duration::minutes(1);
+        let target = TARGETS[0].parse().unwrap();
+        let mut slow = Check::new(
+            time,
+            CheckFlag::Success | CheckFlag::TypeHTTP,
+            Some(Duration::from_secs(2)),
+            target,
+        );
+        slow.check_degraded(Duration::from_secs(1));
+        checks.push(slow);
+        checks.push(Check::new(
+            time,
+            CheckFlag::Success | CheckFlag::TypeIcmp,
+            None,
+            target,
+        ));
+        assert_eq!(latest_health(&checks), Some(Health::Degraded));
+        assert_eq!(Health::Degraded.exit_code(), 1);
+        assert!(Health::Outage > Health::Degraded);
+    }
+
+    #[test]
+    fn test_outage_summary() {
+        let base_checks = basic_check_set();
+        let checks: Vec<&Check> = base_checks.iter().collect();
+
+        let fg = fail_groups(&checks);
+        let summary = Outage::try_from(fg[0].clone()).unwrap().to_summary();
+        assert_eq!(summary.checks, 8);
+        assert_eq!(summary.duration, 60);
+        assert_eq!(summary.end - summary.start, summary.duration);
+        assert_eq!(summary.severity, Severity::Complete);
+        assert_eq!(summary.check_types, vec![CheckType::Http, CheckType::Icmp]);
+        assert_eq!(summary.targets.len(), 2);
+        assert_eq!(summary.location, None);
+        assert_eq!(summary.family, Some(Family::DualStack));
+        assert!(!summary.ongoing);
+        assert_eq!(
+            summary.short_report().unwrap(),
+            Outage::try_from(fg[0].clone())
+                .unwrap()
+                .short_report()
+                .unwrap()
+        );
+
+        let ongoing = super::OutageSummary {
+            ongoing: true,
+            ..summary
+        };
+        assert!(ongoing.short_report().unwrap().ends_with(", ongoing"));
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let other4 = "9.9.9.9".parse().unwrap();
+        let ip6 = TARGETS[1].parse().unwrap();
+        let time = chrono::DateTime::from_timestamp(0, 0).unwrap();
+        let hints = |checks: &[Check]| {
+            let refs: Vec<&Check> = checks.iter().collect();
+            diagnose(&Outage::build(&refs).unwrap())
+        };
+
+        // only HTTP of one target failed, the other IPv4 target and pings worked
+        let checks = [
+            Check::new(time, CheckFlag::TypeHTTP, None, ip4),
+            Check::new(time, CheckFlag::Success | CheckFlag::TypeIcmp, None, ip4),
+            Check::new(time, CheckFlag::Success | CheckFlag::TypeHTTP, None, other4),
+        ];
+        assert_eq!(
+            hints(&checks),
+            vec![Hint::OnlyHttp, Hint::SingleTarget(ip4)]
+        );
+
+        // the only IPv6 target failed, that is no proof of a problem of the remote
+        let checks = [
+            Check::new(time, CheckFlag::Success | CheckFlag::TypeIcmp, None, ip4),
+            Check::new(time, CheckFlag::TypeIcmp, None, ip6),
+        ];
+        assert_eq!(hints(&checks), vec![Hint::Ipv6Only]);
+
+        // everything failed, nothing stands out
+        let checks = [
+            Check::new(time, CheckFlag::TypeIcmp, None, ip4),
+            Check::new(time, CheckFlag::TypeIcmp, None, ip6),
+        ];
+        assert!(hints(&checks).is_empty());
+    }
+
+    #[test]
+    fn test_outage_family() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let ip6 = TARGETS[1].parse().unwrap();
+        let mut checks = Vec::new();
+        for (minute, v4_ok, v6_ok) in [(0, true, false), (1, true, true), (2, false, true)] {
+            let time = chrono::DateTime::from_timestamp(minute * 60, 0).unwrap();
+            let flag = |ok: bool| {
+                if ok {
+                    CheckFlag::Success.into()
+                } else {
+                    FlagSet::default()
+                }
+            };
+            checks.push(Check::new(
+                time,
+                flag(v4_ok) | CheckFlag::TypeHTTP,
+                None,
+                ip4,
+            ));
+            checks.push(Check::new(
+                time,
+                flag(v6_ok) | CheckFlag::TypeHTTP,
+                None,
+                ip6,
+            ));
+        }
+        let refs: Vec<&Check> = checks.iter().collect();
+
+        let families: Vec<Option<Family>> = fail_groups(&refs)
+            .into_iter()
+            .map(|group| Outage::try_from(group).unwrap().family())
+            .collect();
+        assert_eq!(
+            families,
+            vec![Some(Family::Ipv6Only), Some(Family::Ipv4Only)]
+        );
+
+        // without IPv4 checks, failed IPv6 checks can not be told apart from a dead connection
+        let only_v6 = [&checks[1]];
+        assert_eq!(Outage::build(&only_v6).unwrap().family(), None);
+    }
+
+    #[test]
+    fn test_degraded_outage() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let mut checks = Vec::new();
+        for (minute, latency) in [(0, 100), (1, 9000), (2, 8000), (3, 100)] {
+            let mut check = Check::new(
+                chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                Some(Duration::from_millis(latency)),
+                ip4,
+            );
+            check.check_degraded(Duration::from_secs(3));
+            checks.push(check);
+        }
+        assert!(!checks[0].is_degraded());
+        assert!(checks[1].is_degraded());
+        let refs: Vec<&Check> = checks.iter().collect();
+
+        let groups = fail_groups(&refs);
+        assert_eq!(groups.len(), 1);
+        let outage = Outage::try_from(groups[0].clone()).unwrap();
+        assert_eq!(outage.len(), 2);
+        assert_eq!(outage.severity(), Severity::Degraded(1.0));
+        assert!(outage.severity() < Severity::Partial(0.1));
+        assert!(outage.severity() > Severity::None);
+        assert_eq!("degraded:100".parse(), Ok(Severity::Degraded(1.0)));
+
+        let stats = super::Stats::new(&refs, &[]);
+        assert_eq!(stats.checks_ok, 4);
+        assert_eq!(stats.checks_ok_degraded, 2);
+    }
+
+    #[test]
+    fn test_outage_score() {
+        use std::cmp::Ordering;
+
+        use crate::config::SeverityConfig;
+
+        let mut all = vec![
+            Severity::Partial(0.5),
+            Severity::Complete,
+            Severity::None,
+            Severity::Degraded(1.0),
+            Severity::Partial(0.1),
+        ];
+        all.sort();
+        assert_eq!(
+            all,
+            vec![
+                Severity::None,
+                Severity::Degraded(1.0),
+                Severity::Partial(0.1),
+                Severity::Partial(0.5),
+                Severity::Complete,
+            ]
+        );
+
+        let weights = SeverityConfig::default();
+        assert_eq!(weights.score(Severity::Complete, 0), 1.0);
+        assert_eq!(weights.score(Severity::Partial(0.5), 3600), 0.75);
+        assert_eq!(weights.score(Severity::Degraded(1.0), 0), 0.25);
+        assert_eq!(weights.score(Severity::None, 86400), 0.0);
+
+        let ip4 = TARGETS[0].parse().unwrap();
+        let at = |hour: i64| chrono::DateTime::from_timestamp(hour * 3600, 0).unwrap();
+        // every check failed, but only for a moment
+        let short = [Check::new(
+            at(0),
+            CheckFlag::Unreachable | CheckFlag::TypeHTTP,
+            None,
+            ip4,
+        )];
+        // most checks failed for six hours
+        let long: Vec<Check> = (0..5)
+            .map(|hour| {
+                let flags = if hour == 2 {
+                    CheckFlag::Success | CheckFlag::TypeHTTP
+                } else {
+                    CheckFlag::Unreachable | CheckFlag::TypeHTTP
+                };
+                Check::new(at(hour * 2), flags, None, ip4)
+            })
+            .collect();
+        let short = Outage::build(&short.iter().collect::<Vec<_>>()).unwrap();
+        let long = Outage::build(&long.iter().collect::<Vec<_>>()).unwrap();
+        assert!(long.severity() < short.severity());
+        assert_eq!(long.cmp_severity(&short, &weights), Ordering::Greater);
+
+        let ratio_only = SeverityConfig {
+            duration_weight: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(long.score(&ratio_only), 0.8);
+        assert_eq!(long.cmp_severity(&short, &ratio_only), Ordering::Less);
+    }
+
+    #[test]
+    fn test_outage_location() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let gateway = "192.168.1.1".parse().unwrap();
+        let mut checks = Vec::new();
+        for (minute, target_ok, gateway_ok) in
+            [(0, false, true), (1, true, true), (2, false, false)]
+        {
+            let time = chrono::DateTime::from_timestamp(minute * 60, 0).unwrap();
+            let flag = |ok: bool| {
+                if ok {
+                    CheckFlag::Success.into()
+                } else {
+                    FlagSet::default()
+                }
+            };
+            checks.push(Check::new(
+                time,
+                flag(target_ok) | CheckFlag::TypeHTTP,
+                None,
+                ip4,
+            ));
+            checks.push(Check::new(
+                time,
+                flag(gateway_ok) | CheckFlag::TypeGateway,
+                None,
+                gateway,
+            ));
+        }
+        let refs: Vec<&Check> = checks.iter().collect();
+
+        let locations: Vec<Option<Location>> = fail_groups(&refs)
+            .into_iter()
+            .map(|group| Outage::try_from(group).unwrap().location())
+            .collect();
+        assert_eq!(
+            locations,
+            vec![Some(Location::Upstream), Some(Location::LocalNetwork)]
+        );
+        assert_eq!(checks[1].calc_type().unwrap(), CheckType::Gateway);
     }
 
     #[test]
@@ -491,13 +1802,425 @@ mod tests {
         let base_checks = basic_check_set();
         let checks: Vec<&Check> = base_checks.iter().collect();
 
-        let tg = group_by_time(&checks);
+        let tg: Vec<&[&Check]> = group_by_time(&checks).collect();
         assert_eq!(tg.len(), 5);
-        for (k, v) in tg {
+        for v in tg {
             assert_eq!(v.len(), 4);
             for c in v {
-                assert_eq!(k, c.timestamp())
+                assert_eq!(v[0].timestamp(), c.timestamp())
             }
         }
     }
+
+    #[test]
+    fn test_loss_stats() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let day = 86400;
+        let mut checks = Vec::new();
+        for (time, received) in [(0, 10), (60, 5), (day, 9)] {
+            let mut check = Check::new(
+                chrono::DateTime::from_timestamp(time, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeIcmp,
+                Some(Duration::from_millis(10)),
+                ip4,
+            );
+            check.set_measurement(Some(Measurement::Loss { sent: 10, received }));
+            checks.push(check);
+        }
+        // single pings do not count
+        checks.push(Check::new(
+            chrono::DateTime::from_timestamp(120, 0).unwrap(),
+            CheckFlag::TypeIcmp,
+            None,
+            ip4,
+        ));
+
+        let loss = LossStats::of_checks(&checks);
+        assert_eq!(loss.sent, 30);
+        assert_eq!(loss.received, 24);
+        assert!((loss.loss_ratio.unwrap() - 0.2).abs() < f64::EPSILON);
+        let sent_per_day: usize = loss.per_day.iter().map(|d| d.sent).sum();
+        assert_eq!(sent_per_day, 30);
+        assert!(loss.per_day.windows(2).all(|w| w[0].day < w[1].day));
+
+        assert!(LossStats::of_checks(&checks[3..]).is_empty());
+    }
+
+    #[test]
+    fn test_mtu_stats() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let mut checks = Vec::new();
+        for (minute, size) in [(0, 1500), (1, 1492), (2, 1500)] {
+            let mut check = Check::new(
+                chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeMtu,
+                None,
+                ip4,
+            );
+            check.set_measurement(Some(Measurement::Mtu { size }));
+            checks.push(check);
+        }
+
+        let stats = MtuStats::of_checks(&checks);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].measurements, 3);
+        assert_eq!(stats[0].smallest, 1492);
+        assert_eq!(stats[0].largest, 1500);
+        assert_eq!(stats[0].latest, 1500);
+        assert_eq!(checks[0].calc_type().unwrap(), CheckType::Mtu);
+    }
+
+    #[test]
+    fn test_interface_stats() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let mut checks = Vec::new();
+        for minute in 0..5 {
+            // the LTE uplink fails at minutes 1 and 2, the fiber at minute 4
+            for (interface, failed) in [
+                (Some("wwan0"), minute == 1 || minute == 2),
+                (Some("eth0"), minute == 4),
+                (None, false),
+            ] {
+                let flags = if failed {
+                    CheckFlag::Timeout | CheckFlag::TypeIcmp
+                } else {
+                    CheckFlag::Success | CheckFlag::TypeIcmp
+                };
+                let mut check = Check::new(
+                    chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                    flags,
+                    None,
+                    ip4,
+                );
+                check.set_interface(interface.map(str::to_string));
+                checks.push(check);
+            }
+        }
+
+        let stats = InterfaceStats::of_checks(&checks);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].interface, "eth0");
+        assert_eq!(stats[0].stats.checks, 5);
+        assert_eq!(stats[0].outages, 1);
+        assert_eq!(stats[0].latest_outage.as_ref().unwrap().start, 240);
+        assert_eq!(stats[1].interface, "wwan0");
+        assert_eq!(stats[1].stats.checks_bad, 2);
+        assert_eq!(stats[1].outages, 1);
+        assert_eq!(stats[1].downtime, 60);
+        assert_eq!(stats[1].longest_outage, 60);
+    }
+
+    #[test]
+    fn test_throughput_stats() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let day = 86400;
+        let mut checks = Vec::new();
+        for (time, bytes_per_second) in [(0, 1000), (3600, 3000), (day, 500)] {
+            let mut check = Check::new(
+                chrono::DateTime::from_timestamp(time, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeBandwidth,
+                Some(Duration::from_millis(10)),
+                ip4,
+            );
+            check.set_measurement(Some(Measurement::Throughput { bytes_per_second }));
+            checks.push(check);
+        }
+
+        let stats = ThroughputStats::of_checks(&checks);
+        assert_eq!(stats.samples, 3);
+        assert_eq!(stats.average, Some(1500));
+        assert_eq!(stats.latest, Some(500));
+        let samples_per_day: usize = stats.per_day.iter().map(|d| d.samples).sum();
+        assert_eq!(samples_per_day, 3);
+        assert!(stats.per_day.windows(2).all(|w| w[0].day < w[1].day));
+        assert_eq!(stats.per_day.iter().map(|d| d.lowest).min(), Some(500));
+        assert_eq!(checks[0].calc_type().unwrap(), CheckType::Bandwidth);
+
+        assert!(ThroughputStats::of_checks(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_period_stats() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        // 2024-11-04 12:00 UTC, a monday, far enough from a week boundary for any timezone
+        let monday = 1_730_721_600;
+        let day = 86400;
+        let mut checks = Vec::new();
+        for (time, ok) in [
+            (monday, true),
+            (monday + 60, false),
+            (monday + 120, false),
+            (monday + 180, true),
+            (monday + 7 * day, false),
+            (monday + 7 * day + 60, true),
+        ] {
+            let flags = if ok {
+                CheckFlag::Success | CheckFlag::TypeHTTP
+            } else {
+                CheckFlag::TypeHTTP.into()
+            };
+            checks.push(Check::new(
+                chrono::DateTime::from_timestamp(time, 0).unwrap(),
+                flags,
+                None,
+                ip4,
+            ));
+        }
+
+        let gaps = Gap::of_data(&checks, &[], 60);
+        assert_eq!(gaps.len(), 1);
+        let weeks = PeriodStats::of_data(&checks, &[], 60, Period::Weekly);
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(weeks[0].name, "2024-W45");
+        assert_eq!(weeks[0].checks, 4);
+        assert!((weeks[0].uptime - 0.5).abs() < f64::EPSILON);
+        assert_eq!(weeks[0].outages, 1);
+        assert_eq!(weeks[0].downtime, 60);
+        assert_eq!(weeks[0].longest_outage, 60);
+        assert_eq!(weeks[1].name, "2024-W46");
+        assert_eq!(weeks[1].outages, 1);
+        assert_eq!(weeks[0].end, weeks[1].start);
+        // the week without checks is split between both weeks
+        assert_eq!(
+            weeks[0].unmonitored + weeks[1].unmonitored,
+            gaps[0].duration()
+        );
+        assert!(weeks[0].unmonitored > 0 && weeks[1].unmonitored > 0);
+        // the good minutes cover almost nothing of the week
+        assert!(weeks[0].time_uptime < 0.01);
+
+        let months = PeriodStats::of_data(&checks, &[], 60, Period::Monthly);
+        assert_eq!(months.len(), 1);
+        assert_eq!(months[0].name, "2024-11");
+        assert_eq!(months[0].checks, 6);
+        assert_eq!(months[0].outages, 2);
+        assert_eq!(months[0].unmonitored, 7 * day - 240);
+    }
+
+    #[test]
+    fn test_calendar() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        // 2024-11-04 12:00 UTC, a monday, far enough from a day boundary for any timezone
+        let monday = 1_730_721_600;
+        let day = 86400;
+        let mut checks = Vec::new();
+        for (time, ok) in [
+            (monday - 60 * day, false),
+            (monday, true),
+            (monday + 60, false),
+            (monday + 120, false),
+            (monday + 180, true),
+            (monday + 7 * day, false),
+            (monday + 7 * day + 60, true),
+        ] {
+            let flags = if ok {
+                CheckFlag::Success | CheckFlag::TypeHTTP
+            } else {
+                CheckFlag::TypeHTTP.into()
+            };
+            checks.push(Check::new(
+                chrono::DateTime::from_timestamp(time, 0).unwrap(),
+                flags,
+                None,
+                ip4,
+            ));
+        }
+        let today = chrono::NaiveDate::from_ymd_opt(2024, 11, 20).unwrap();
+
+        let calendar = Calendar::of_data(&checks, &[], 2, today);
+        // all of october and november up to today, the check in september is left out
+        assert_eq!(calendar.days.len(), 31 + 20);
+        assert_eq!(calendar.days[0].date, "2024-10-01");
+        assert_eq!(calendar.days.last().unwrap().day(), today);
+        assert_eq!(calendar.days.iter().map(|d| d.checks).sum::<usize>(), 6);
+        let first_monday = &calendar.days[31 + 3];
+        assert_eq!(first_monday.date, "2024-11-04");
+        assert_eq!(first_monday.checks_ok, 2);
+        assert_eq!(first_monday.uptime(), Some(0.5));
+        assert_eq!(calendar.days[0].uptime(), None);
+
+        let mut text = String::new();
+        super::calendar_days(&calendar, &mut text, Language::English).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("    Oct") && lines[0].ends_with("Nov"));
+        // 2024-10-01 was a tuesday, so the first monday of the calendar is in the second week
+        assert_eq!(lines[1], "Mon           █ █");
+        assert!(text.contains("days without failures   : 0 of 2"));
+        assert!(text.contains("worst day               : 2024-11-04, 50.000% up"));
+
+        let mut text = String::new();
+        super::calendar_days(&calendar, &mut text, Language::German).unwrap();
+        assert!(text.contains("Tage ohne Fehler        : 0 of 2"));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let check = |time: i64, ok: bool| {
+            let flags = if ok {
+                CheckFlag::Success | CheckFlag::TypeHTTP
+            } else {
+                CheckFlag::TypeHTTP.into()
+            };
+            Check::new(
+                chrono::DateTime::from_timestamp(time, 0).unwrap(),
+                flags,
+                None,
+                ip4,
+            )
+        };
+        // six good rounds, an hour without checks, and a round with one of two checks failed
+        let mut checks: Vec<Check> = (0..6).map(|round| check(round * 60, true)).collect();
+        checks.push(check(3900, true));
+        checks.push(check(3900, false));
+
+        let coverage = Coverage::of_data(&checks, &[], 60);
+        let uptime = coverage.uptime(i64::MIN, i64::MAX).unwrap();
+        assert!((uptime - 390.0 / 3960.0).abs() < 1e-9);
+        // the first hour, the rounds cover 6 minutes of it
+        assert!((coverage.uptime(0, 3600).unwrap() - 0.1).abs() < 1e-9);
+        assert_eq!(coverage.uptime(7200, 10800), None);
+        assert_eq!(Coverage::default().uptime(i64::MIN, i64::MAX), None);
+
+        // the ratio of checks does not see the hour without checks
+        let stats = Stats::new(&checks.iter().collect::<Vec<_>>(), &[]);
+        assert!((stats.success_ratio.unwrap() - 7.0 / 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sla_stats() {
+        let month = |name: &str, start: &str, end: &str, uptime: f64| PeriodStats {
+            name: name.to_string(),
+            start: parse_local(start),
+            end: parse_local(end),
+            checks: 1000,
+            checks_ok: (uptime * 1000.0) as usize,
+            uptime,
+            outages: 1,
+            downtime: 60,
+            longest_outage: 60,
+            unmonitored: 0,
+            time_uptime: uptime,
+        };
+        let periods = vec![
+            month("2024-10", "2024-10-01", "2024-11-01", 0.99),
+            month("2024-11", "2024-11-01", "2024-12-01", 0.999),
+        ];
+        // ten days into november
+        let now = Local.timestamp_opt(parse_local("2024-11-11"), 0).unwrap();
+
+        let sla = SlaStats::of_periods(
+            periods.clone(),
+            0.999,
+            Period::Monthly,
+            UptimeBasis::Checks,
+            now,
+        );
+        assert_eq!(sla.current.as_ref().unwrap().name, "2024-11");
+        assert_eq!(sla.missed.len(), 1);
+        assert_eq!(sla.missed[0].name, "2024-10");
+        // 0.1% of 30 days, and of 10 days
+        assert!((sla.budget - 2592).abs() <= 1);
+        assert!((sla.used - 864).abs() <= 1);
+        assert_eq!(sla.remaining, sla.budget - sla.used);
+        assert!(!sla.exceeded);
+
+        let sla = SlaStats::of_periods(
+            periods.clone(),
+            0.9999,
+            Period::Monthly,
+            UptimeBasis::Checks,
+            now,
+        );
+        assert!(sla.exceeded);
+        assert!(sla.remaining < 0);
+
+        // november was only covered for part of the time
+        let mut periods = periods;
+        periods[1].time_uptime = 0.5;
+        let sla = SlaStats::of_periods(periods, 0.999, Period::Monthly, UptimeBasis::Time, now);
+        assert_eq!(sla.basis, UptimeBasis::Time);
+        assert!(sla.exceeded);
+        assert_eq!(sla.missed.len(), 1);
+    }
+
+    fn parse_local(day: &str) -> i64 {
+        chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap()
+            .timestamp()
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let mut checks = Vec::new();
+        // every night at 3am for two weeks, one of two checks fails
+        for day in 0..14 {
+            for (minute, ok) in [(0, true), (1, false)] {
+                let time = Local
+                    .with_ymd_and_hms(2024, 11, 4 + day, 3, minute, 0)
+                    .earliest()
+                    .unwrap();
+                let flags = if ok {
+                    CheckFlag::Success | CheckFlag::TypeHTTP
+                } else {
+                    CheckFlag::TypeHTTP.into()
+                };
+                checks.push(Check::new(time, flags, None, ip4));
+            }
+        }
+
+        let heatmap = Heatmap::of_checks(&checks);
+        assert_eq!(heatmap.cells.len(), 7);
+        assert!(heatmap.cells.iter().all(|row| row.len() == 24));
+        // 2024-11-04 is a monday
+        assert_eq!(heatmap.cells[0][3].checks, 4);
+        assert_eq!(heatmap.cells[0][3].failure_ratio(), Some(0.5));
+        assert_eq!(heatmap.cells[0][4].failure_ratio(), None);
+        let per_hour = heatmap.per_hour();
+        assert_eq!(per_hour[3].checks, 28);
+        assert_eq!(per_hour[3].failed, 14);
+        assert_eq!(heatmap.per_weekday()[6].checks, 4);
+        assert!(Heatmap::of_checks(&[]).is_empty());
+
+        assert_eq!(super::heat_shade(None), ' ');
+        assert_eq!(super::heat_shade(Some(0.0)), '.');
+        assert_eq!(super::heat_shade(Some(0.5)), '█');
+    }
+
+    #[test]
+    fn test_trace_stats() {
+        let ip4 = TARGETS[0].parse().unwrap();
+        let router = Some("192.168.1.1".parse().unwrap());
+        let isp = Some("203.0.113.1".parse().unwrap());
+        let traces: Vec<Trace> = [
+            (vec![router], false),
+            (vec![router, isp], false),
+            (vec![router, Some(ip4)], true),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(minute, (hops, reached))| {
+            Trace::new(
+                chrono::DateTime::from_timestamp(minute as i64 * 60, 0).unwrap(),
+                ip4,
+                hops,
+                reached,
+            )
+        })
+        .collect();
+
+        let stats = TraceStats::of_traces(&traces);
+        assert_eq!(stats.traces, 3);
+        assert_eq!(stats.per_segment[&Segment::Lan], 1);
+        assert_eq!(stats.per_segment[&Segment::Isp], 1);
+        assert_eq!(stats.per_segment[&Segment::Target], 1);
+        assert!(!stats.per_segment.contains_key(&Segment::Beyond));
+        assert_eq!(stats.latest.as_ref(), traces.last());
+    }
 }
@@ -0,0 +1,358 @@
+//! Restricting which [Checks](Check) are considered in an analysis.
+//!
+//! A [CheckAccessConstraints] describes a subset of the [Checks](Check) of a [Store], for example
+//! only IPv4 checks since yesterday, or all checks between the start and the end of an incident. It can be used to filter single [Checks](Check) or to
+//! [restrict](CheckAccessConstraints::apply) a loaded [Store], after which all analysis functions
+//! only see the matching data.
+//!
+//! Minor outages, like a single round with a failed check or a few failed checks out of many,
+//! can be left out with [min_outage_len](CheckAccessConstraints::min_outage_len) and
+//! [min_severity](CheckAccessConstraints::min_severity). They then neither show up as outages
+//! nor count against the uptime.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use netpulse::analyze::{self, CheckAccessConstraints};
+//! use netpulse::records::IpType;
+//! use netpulse::store::Store;
+//!
+//! let mut store = Store::load(true).unwrap();
+//! let constraints = CheckAccessConstraints {
+//!     since_date: Some(chrono::Local::now() - chrono::Duration::days(1)),
+//!     ip_type: Some(IpType::V4),
+//!     ..Default::default()
+//! };
+//! constraints.apply(&mut store);
+//! println!("{}", analyze::analyze(&store).unwrap());
+//! ```
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+
+use crate::errors::StoreError;
+use crate::records::aggregate::Aggregate;
+use crate::records::{Check, CheckType, IpType};
+use crate::store::Store;
+
+use super::fail_groups;
+use super::outage::{Outage, Severity};
+
+/// Constraints on which [Checks](Check) to consider.
+///
+/// Fields that are [None] do not constrain anything, so the [Default] matches all
+/// [Checks](Check).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CheckAccessConstraints {
+    /// Only consider [Checks](Check) made at or after this time
+    pub since_date: Option<DateTime<Local>>,
+    /// Only consider [Checks](Check) made before this time
+    ///
+    /// Together with [since_date](Self::since_date), this selects a window, like the time of a
+    /// single incident.
+    pub until_date: Option<DateTime<Local>>,
+    /// Only consider [Checks](Check) that used this IP version
+    pub ip_type: Option<IpType>,
+    /// Only consider [Checks](Check) of this target
+    pub target: Option<IpAddr>,
+    /// Only consider [Checks](Check) of this type
+    pub check_type: Option<CheckType>,
+    /// Only consider [Checks](Check) bound to this interface or source address, see
+    /// [Check::interface]
+    ///
+    /// [Aggregates](Aggregate) do not know their interface, so none of them match.
+    pub interface: Option<String>,
+    /// Leave out outages that span fewer rounds of [Checks](Check) than this
+    ///
+    /// All [Checks](Check) of the rounds of a short outage are left out, so the outage neither
+    /// shows up nor counts against the uptime. Only [apply](Self::apply) honors this, as single
+    /// [Checks](Check) do not know which outage they belong to.
+    pub min_outage_len: Option<usize>,
+    /// Leave out outages whose [Severity] is lower than this
+    ///
+    /// Like with [min_outage_len](Self::min_outage_len), all [Checks](Check) of the rounds of
+    /// such an outage are left out, and only [apply](Self::apply) honors this.
+    pub min_severity: Option<Severity>,
+}
+
+impl CheckAccessConstraints {
+    /// Returns true if no constraints are set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns true if `check` matches all constraints.
+    pub fn matches(&self, check: &Check) -> bool {
+        if let Some(since) = self.since_date {
+            if check.timestamp() < since.timestamp() {
+                return false;
+            }
+        }
+        if let Some(until) = self.until_date {
+            if check.timestamp() >= until.timestamp() {
+                return false;
+            }
+        }
+        if let Some(ip_type) = self.ip_type {
+            if check.ip_type() != ip_type {
+                return false;
+            }
+        }
+        if let Some(target) = self.target {
+            if check.target() != target {
+                return false;
+            }
+        }
+        if let Some(check_type) = self.check_type {
+            if check.calc_type().ok() != Some(check_type) {
+                return false;
+            }
+        }
+        if let Some(interface) = &self.interface {
+            if check.interface() != Some(interface.as_str()) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns true if `aggregate` matches all constraints.
+    ///
+    /// An [Aggregate] matches [since_date](Self::since_date) and [until_date](Self::until_date)
+    /// if any part of its hour is within them, as the exact times of the summarized
+    /// [Checks](Check) are not known.
+    pub fn matches_aggregate(&self, aggregate: &Aggregate) -> bool {
+        if let Some(since) = self.since_date {
+            if aggregate.end() <= since.timestamp() {
+                return false;
+            }
+        }
+        if let Some(until) = self.until_date {
+            if aggregate.start() >= until.timestamp() {
+                return false;
+            }
+        }
+        if let Some(ip_type) = self.ip_type {
+            if aggregate.ip_type() != ip_type {
+                return false;
+            }
+        }
+        if let Some(target) = self.target {
+            if aggregate.target() != target {
+                return false;
+            }
+        }
+        if let Some(check_type) = self.check_type {
+            if aggregate.check_type() != check_type {
+                return false;
+            }
+        }
+        if self.interface.is_some() {
+            return false;
+        }
+        true
+    }
+
+    /// Returns references to all [Checks](Check) in `checks` that match the constraints.
+    pub fn filter<'check>(&self, checks: &'check [Check]) -> Vec<&'check Check> {
+        checks.iter().filter(|c| self.matches(c)).collect()
+    }
+
+    /// Loads the store file at `path` readonly and [applies](Self::apply) the constraints.
+    ///
+    /// If the constraints filter single [Checks](Check), the [Checks](Check) are filtered while
+    /// reading with [Store::load_filtered_from], so a large store does not have to fit into
+    /// memory as a whole. The checksum of the store file is not verified then.
+    ///
+    /// With the `chunked` feature, the archived [Checks](Check) in the time range are read back
+    /// as well, see [Store::restore_archived].
+    ///
+    /// With the `sqlite` feature, `path` can also be a SQLite database, see
+    /// [SqliteStore::to_store](crate::store::sqlite::SqliteStore::to_store).
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the store can not be loaded.
+    pub fn load_from(&self, path: &Path) -> Result<Store, StoreError> {
+        #[cfg(feature = "sqlite")]
+        if crate::store::sqlite::SqliteStore::is_sqlite(path) {
+            let mut store = crate::store::sqlite::SqliteStore::open(path)?.to_store(
+                self.since_date.map_or(i64::MIN, |d| d.timestamp()),
+                self.until_date.map_or(i64::MAX, |d| d.timestamp()),
+                |c| self.matches(c),
+            )?;
+            self.apply(&mut store);
+            return Ok(store);
+        }
+        let filters_checks = self.since_date.is_some()
+            || self.until_date.is_some()
+            || self.ip_type.is_some()
+            || self.target.is_some()
+            || self.check_type.is_some()
+            || self.interface.is_some();
+        let mut store = if filters_checks {
+            Store::load_filtered_from(path, |c| self.matches(c))?
+        } else {
+            Store::load_from(path, true)?
+        };
+        #[cfg(feature = "chunked")]
+        store.restore_archived(
+            self.since_date.map_or(i64::MIN, |d| d.timestamp()),
+            self.until_date.map_or(i64::MAX, |d| d.timestamp()),
+            |c| self.matches(c),
+        )?;
+        self.apply(&mut store);
+        Ok(store)
+    }
+
+    /// Removes all [Checks](Check) and [Aggregates](Aggregate) from `store` that do not match
+    /// the constraints.
+    ///
+    /// This only changes the [Store] in memory. Use it on a readonly [Store] to keep the data on
+    /// disk intact.
+    pub fn apply(&self, store: &mut Store) {
+        if self.is_empty() {
+            return;
+        }
+        // the checks are sorted, so the time range is cut out without looking at every check
+        let range = store.range_between(
+            self.since_date.map_or(i64::MIN, |d| d.timestamp()),
+            self.until_date.map_or(i64::MAX, |d| d.timestamp()),
+        );
+        // the outage log describes all checks, not the constrained ones
+        store.forget_outages();
+        let checks = store.checks_mut();
+        checks.truncate(range.end);
+        checks.drain(..range.start);
+        checks.retain(|c| self.matches(c));
+        store.aggregates_mut().retain(|a| self.matches_aggregate(a));
+        if self.min_outage_len.is_some() || self.min_severity.is_some() {
+            self.remove_minor_outages(store.checks_mut());
+        }
+    }
+
+    /// Removes all [Checks](Check) of the rounds of outages that are shorter than
+    /// [min_outage_len](Self::min_outage_len) or less severe than
+    /// [min_severity](Self::min_severity).
+    fn remove_minor_outages(&self, checks: &mut Vec<Check>) {
+        let refs: Vec<&Check> = checks.iter().collect();
+        let mut minor_rounds: HashSet<i64> = HashSet::new();
+        for group in fail_groups(&refs) {
+            let rounds: HashSet<i64> = group.iter().map(|c| c.timestamp()).collect();
+            let too_short = self.min_outage_len.is_some_and(|min| rounds.len() < min);
+            let too_mild = self.min_severity.is_some_and(|min| {
+                Outage::try_from(group).is_ok_and(|outage| outage.severity() < min)
+            });
+            if too_short || too_mild {
+                minor_rounds.extend(rounds);
+            }
+        }
+        checks.retain(|c| !minor_rounds.contains(&c.timestamp()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+
+    use crate::records::CheckFlag;
+
+    use super::*;
+
+    #[test]
+    fn test_time_window() {
+        let at = |minute: i64| DateTime::from_timestamp(minute * 60, 0).unwrap();
+        let constraints = CheckAccessConstraints {
+            since_date: Some(at(2).into()),
+            until_date: Some(at(4).into()),
+            ..Default::default()
+        };
+        let target = "1.1.1.1".parse().unwrap();
+        let checks: Vec<Check> = (0..6)
+            .map(|minute| {
+                Check::new(
+                    at(minute),
+                    CheckFlag::Success | CheckFlag::TypeHTTP,
+                    None,
+                    target,
+                )
+            })
+            .collect();
+        let minutes: Vec<i64> = constraints
+            .filter(&checks)
+            .iter()
+            .map(|c| c.timestamp() / 60)
+            .collect();
+        assert_eq!(minutes, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_remove_short_outages() {
+        let target = "1.1.1.1".parse().unwrap();
+        let min_len = |min| CheckAccessConstraints {
+            min_outage_len: Some(min),
+            ..Default::default()
+        };
+        // one round of failures at minute 1, two rounds at minutes 3 and 4
+        let mut checks: Vec<Check> = (0..6)
+            .map(|minute| {
+                let flags = if [1, 3, 4].contains(&minute) {
+                    CheckFlag::TypeHTTP.into()
+                } else {
+                    CheckFlag::Success | CheckFlag::TypeHTTP
+                };
+                Check::new(
+                    DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                    flags,
+                    None,
+                    target,
+                )
+            })
+            .collect();
+
+        min_len(1).remove_minor_outages(&mut checks);
+        assert_eq!(checks.len(), 6);
+        min_len(2).remove_minor_outages(&mut checks);
+        let minutes: Vec<i64> = checks.iter().map(|c| c.timestamp() / 60).collect();
+        assert_eq!(minutes, vec![0, 2, 3, 4, 5]);
+        min_len(3).remove_minor_outages(&mut checks);
+        assert!(checks.iter().all(|c| c.is_success()));
+    }
+
+    #[test]
+    fn test_remove_mild_outages() {
+        let targets: [IpAddr; 4] =
+            ["1.1.1.1", "1.0.0.1", "8.8.8.8", "8.8.4.4"].map(|t| t.parse().unwrap());
+        // a quarter of the checks fail at minute 1, all of them at minute 3
+        let mut checks: Vec<Check> = Vec::new();
+        for minute in 0..5 {
+            for (idx, target) in targets.iter().enumerate() {
+                let failed = minute == 3 || (minute == 1 && idx == 0);
+                let flags = if failed {
+                    CheckFlag::TypeHTTP.into()
+                } else {
+                    CheckFlag::Success | CheckFlag::TypeHTTP
+                };
+                let time = DateTime::from_timestamp(minute * 60, 0).unwrap();
+                checks.push(Check::new(time, flags, None, *target));
+            }
+        }
+        let min_severity = |raw: &str| CheckAccessConstraints {
+            min_severity: Some(raw.parse().unwrap()),
+            ..Default::default()
+        };
+
+        min_severity("partial:25").remove_minor_outages(&mut checks);
+        assert_eq!(checks.len(), 20);
+        min_severity("partial:50").remove_minor_outages(&mut checks);
+        assert_eq!(checks.len(), 16);
+        assert!(checks.iter().all(|c| c.timestamp() != 60));
+        min_severity("complete").remove_minor_outages(&mut checks);
+        assert_eq!(checks.len(), 16);
+        assert!("partial:150".parse::<Severity>().is_err());
+        assert!("most".parse::<Severity>().is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! Heuristic hints about the cause of an [`Outage`].
+//!
+//! Which checks of an outage failed and which did not often points to a cause. If only HTTP
+//! failed while pings went through, the connection itself was fine. If only one target failed,
+//! the problem was most likely at that target. [diagnose] looks at the failed and the successful
+//! checks of an outage and gives a [Hint] for every such pattern it finds.
+//!
+//! The hints are guesses, not proof. They are shown in the detailed outage output.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use netpulse::analyze::diagnose::diagnose;
+//! use netpulse::analyze::outage::Outage;
+//!
+//! # let checks = vec![];
+//! let outage = Outage::build(&checks).unwrap();
+//! for hint in diagnose(&outage) {
+//!     println!("{hint}");
+//! }
+//! ```
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::net::IpAddr;
+
+use serde::Serialize;
+
+use crate::records::CheckType;
+
+use super::outage::{Family, Location, Outage};
+
+/// A guess about the cause of an [`Outage`], see [diagnose].
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hint {
+    /// The default gateway did not answer
+    LocalNetwork,
+    /// The default gateway answered, but the targets did not
+    Upstream,
+    /// Only checks over IPv4 failed
+    Ipv4Only,
+    /// Only checks over IPv6 failed
+    Ipv6Only,
+    /// Only HTTP checks failed, other types of checks worked
+    OnlyHttp,
+    /// Only ICMP checks failed, other types of checks worked
+    OnlyIcmp,
+    /// Only DNS checks failed, other types of checks worked
+    OnlyDns,
+    /// Only checks of one target failed, other targets of the same IP version worked
+    SingleTarget(IpAddr),
+}
+
+impl Display for Hint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LocalNetwork => write!(
+                f,
+                "the gateway did not answer - the local network or the router is down"
+            ),
+            Self::Upstream => write!(
+                f,
+                "the gateway answered - likely a problem at the ISP or beyond"
+            ),
+            Self::Ipv4Only => write!(
+                f,
+                "only IPv4 failed - likely a problem with IPv4 at the ISP, like CGNAT"
+            ),
+            Self::Ipv6Only => write!(
+                f,
+                "only IPv6 failed - likely a problem with IPv6 at the ISP or the router"
+            ),
+            Self::OnlyHttp => write!(
+                f,
+                "only HTTP failed - possible DNS, proxy or firewall issue"
+            ),
+            Self::OnlyIcmp => write!(
+                f,
+                "only ICMP failed - pings are likely filtered or rate limited"
+            ),
+            Self::OnlyDns => write!(f, "only DNS failed - the resolvers likely had a problem"),
+            Self::SingleTarget(target) => {
+                write!(f, "only {target} failed - likely a problem of the remote")
+            }
+        }
+    }
+}
+
+/// Looks at the checks of an [`Outage`] and gives [Hints](Hint) about its cause.
+///
+/// Returns no hints if nothing stands out, for example if everything failed and there are no
+/// gateway checks.
+pub fn diagnose(outage: &Outage) -> Vec<Hint> {
+    let mut hints = Vec::new();
+
+    match outage.location() {
+        Some(Location::LocalNetwork) => hints.push(Hint::LocalNetwork),
+        Some(Location::Upstream) => hints.push(Hint::Upstream),
+        None => (),
+    }
+    match outage.family() {
+        Some(Family::Ipv4Only) => hints.push(Hint::Ipv4Only),
+        Some(Family::Ipv6Only) => hints.push(Hint::Ipv6Only),
+        Some(Family::DualStack) | None => (),
+    }
+
+    // gateway checks do not go to the targets, they are covered by the location
+    let checks = outage
+        .all()
+        .iter()
+        .filter(|c| c.calc_type().is_ok_and(|t| t != CheckType::Gateway));
+    let mut failed_types = BTreeSet::new();
+    let mut worked_types = BTreeSet::new();
+    let mut failed_targets = BTreeSet::new();
+    let mut worked_targets = BTreeSet::new();
+    for check in checks {
+        let check_type = check.calc_type().unwrap_or(CheckType::Unknown);
+        if check.is_success() {
+            worked_types.insert(check_type);
+            worked_targets.insert(check.target());
+        } else {
+            failed_types.insert(check_type);
+            failed_targets.insert(check.target());
+        }
+    }
+
+    let only_failed = |check_types: &[CheckType]| {
+        !failed_types.is_empty()
+            && failed_types.iter().all(|t| check_types.contains(t))
+            && worked_types.iter().any(|t| !check_types.contains(t))
+    };
+    if only_failed(&[CheckType::Http]) {
+        hints.push(Hint::OnlyHttp);
+    } else if only_failed(&[CheckType::Icmp]) {
+        hints.push(Hint::OnlyIcmp);
+    } else if only_failed(&[CheckType::Dns, CheckType::Doh, CheckType::Dot]) {
+        hints.push(Hint::OnlyDns);
+    }
+
+    // only a target of the same IP version tells the remote apart from the IP version
+    if let [target] = failed_targets.iter().collect::<Vec<_>>()[..] {
+        if worked_targets
+            .iter()
+            .any(|t| t != target && t.is_ipv4() == target.is_ipv4())
+        {
+            hints.push(Hint::SingleTarget(*target));
+        }
+    }
+
+    hints
+}
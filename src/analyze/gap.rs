@@ -0,0 +1,174 @@
+//! Gaps in the monitoring, where no checks were made at all.
+//!
+//! The daemon makes a round of checks every [period](Store::period_seconds). While it is not
+//! running, or the machine is asleep, there are no checks, so nothing is known about the
+//! connection. Such a time is neither uptime nor an outage. The success ratios only count the
+//! checks that were made, so a month in which the daemon ran for a single good day would look
+//! like a month of full uptime. [Gap::of_store] finds these times, so they can be shown apart
+//! from the outages.
+//!
+//! A [Gap] is a time of more than [GAP_PERIODS] periods without checks.
+//! [Aggregates](Aggregate) of downsampled checks count as checks over their whole
+//! [span](AGGREGATE_SPAN).
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use netpulse::analyze::gap::Gap;
+//! use netpulse::store::Store;
+//!
+//! let store = Store::load(true).unwrap();
+//! for gap in Gap::of_store(&store) {
+//!     println!("{gap}");
+//! }
+//! ```
+
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::records::aggregate::{Aggregate, AGGREGATE_SPAN};
+use crate::records::Check;
+use crate::store::Store;
+
+use super::fmt_timestamp;
+use super::i18n::Language;
+
+/// How many periods without checks make a [Gap]
+///
+/// A round of checks can be late when the machine is busy, so a single missed round is not a gap.
+pub const GAP_PERIODS: i64 = 3;
+
+/// A time in which no checks were made, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct Gap {
+    /// When the first missing round of checks was due, as unix timestamp
+    pub start: i64,
+    /// When the first round of checks after the gap was made, as unix timestamp
+    pub end: i64,
+}
+
+impl Gap {
+    /// Finds the [Gaps](Gap) in the [Checks](Check) and [Aggregates](Aggregate) of a [Store],
+    /// oldest first.
+    pub fn of_store(store: &Store) -> Vec<Self> {
+        Self::of_data(store.checks(), store.aggregates(), store.period_seconds())
+    }
+
+    /// Finds the [Gaps](Gap) in `checks` and `aggregates` that were made every `period` seconds,
+    /// oldest first.
+    ///
+    /// Nothing before the first or after the last check is a [Gap].
+    pub fn of_data(checks: &[Check], aggregates: &[Aggregate], period: i64) -> Vec<Self> {
+        let mut covered: Vec<(i64, i64)> = checks
+            .iter()
+            .map(|c| (c.timestamp(), c.timestamp()))
+            .chain(
+                aggregates
+                    .iter()
+                    .map(|a| (a.start(), a.start() + AGGREGATE_SPAN)),
+            )
+            .collect();
+        covered.sort_unstable();
+
+        let mut gaps = Vec::new();
+        let mut covered = covered.into_iter();
+        let Some((_, mut end)) = covered.next() else {
+            return gaps;
+        };
+        for (start, next_end) in covered {
+            if start - end > GAP_PERIODS * period {
+                gaps.push(Self {
+                    start: end + period,
+                    end: start,
+                });
+            }
+            end = end.max(next_end);
+        }
+        gaps
+    }
+
+    /// Returns the duration of the [Gap] in seconds.
+    pub fn duration(&self) -> i64 {
+        self.end - self.start
+    }
+
+    /// Returns how many seconds of the [Gap] are between `start` and `end`.
+    pub fn overlap(&self, start: i64, end: i64) -> i64 {
+        (self.end.min(end) - self.start.max(start)).max(0)
+    }
+
+    /// Describes the [Gap] in one line, with its labels in `language`.
+    ///
+    /// Displaying the [Gap] gives the same line in English.
+    pub fn report(&self, language: Language) -> String {
+        let time = |timestamp: i64| {
+            chrono::DateTime::from_timestamp(timestamp, 0)
+                .map_or_else(|| timestamp.to_string(), fmt_timestamp)
+        };
+        let duration = self.duration();
+        format!(
+            "{} {} {} {}, {}h {:02}m {}",
+            language.label("From"),
+            time(self.start),
+            language.label("To"),
+            time(self.end),
+            duration / 3600,
+            duration % 3600 / 60,
+            language.label("without checks")
+        )
+    }
+}
+
+impl Display for Gap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.report(Language::English))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::records::aggregate::aggregate_checks;
+    use crate::records::CheckFlag;
+
+    fn check(timestamp: i64) -> Check {
+        Check::new(
+            DateTime::from_timestamp(timestamp, 0).unwrap(),
+            CheckFlag::Success | CheckFlag::TypeHTTP,
+            None,
+            "1.1.1.1".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_gaps() {
+        // a late round is no gap, the daemon being off for an hour is
+        let checks: Vec<Check> = [0, 60, 120, 240, 300, 3900, 3960]
+            .into_iter()
+            .map(check)
+            .collect();
+        let gaps = Gap::of_data(&checks, &[], 60);
+        assert_eq!(
+            gaps,
+            vec![Gap {
+                start: 360,
+                end: 3900
+            }]
+        );
+        assert_eq!(gaps[0].duration(), 3540);
+        assert_eq!(gaps[0].overlap(0, 1000), 640);
+        assert_eq!(gaps[0].overlap(4000, 5000), 0);
+        assert!(gaps[0].to_string().ends_with("0h 59m without checks"));
+
+        // aggregates cover their whole hour
+        let aggregates = aggregate_checks(&checks[..5]);
+        assert!(Gap::of_data(&[check(3700)], &aggregates, 60).is_empty());
+
+        // with a longer period, the same checks have no gap
+        assert!(Gap::of_data(&checks, &[], 1800).is_empty());
+        assert!(Gap::of_data(&[], &[], 60).is_empty());
+    }
+}
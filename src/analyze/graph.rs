@@ -0,0 +1,298 @@
+//! Drawing [Checks](Check) as charts.
+//!
+//! This module is only available with the `graph` feature. It renders the severity of outages
+//! over time, that is the ratio of failed [Checks](Check) in each time span, as an area chart.
+//!
+//! Graphs can be drawn as PNG images with [draw_checks] or as SVG vector graphics with
+//! [draw_checks_svg], which stay crisp when embedded in web pages or PDFs.
+//!
+//! [draw_heatmap] draws the ratio of failed [Checks](Check) by day of the week and hour of the
+//! day instead, see [Heatmap].
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use netpulse::analyze::graph::draw_checks;
+//! use netpulse::config::Config;
+//! use netpulse::records::Check;
+//! use netpulse::store::Store;
+//!
+//! let store = Store::load(true).unwrap();
+//! let checks: Vec<&Check> = store.checks().iter().collect();
+//! draw_checks(&checks, "netpulse.png", &Config::default().severity).unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{DateTime, Local, TimeZone};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::config::SeverityConfig;
+use crate::errors::AnalysisError;
+use crate::records::Check;
+
+use super::outage::Severity;
+use super::report::{Heatmap, WEEKDAYS};
+use crate::store::DEFAULT_PERIOD;
+
+/// Size of the drawn graphs in pixels (width, height)
+pub const GRAPH_SIZE: (u32, u32) = (1600, 900);
+
+/// Maximum number of time spans the checks are grouped into
+///
+/// For long time ranges, each drawn point covers more time, so the graph stays readable.
+const MAX_POINTS: i64 = 1000;
+
+/// Draws the severity over time of `checks` as a PNG image to `path`.
+///
+/// The checks are grouped into time spans of at least [DEFAULT_PERIOD] seconds. For each span,
+/// the [score](SeverityConfig::score) of its failed and degraded checks is drawn as percentage,
+/// weighted by `weights`.
+///
+/// # Errors
+///
+/// Returns [AnalysisError::Graph] if `checks` is empty or the image could not be drawn or
+/// written.
+pub fn draw_checks(
+    checks: &[&Check],
+    path: impl AsRef<Path>,
+    weights: &SeverityConfig,
+) -> Result<(), AnalysisError> {
+    let root = BitMapBackend::new(path.as_ref(), GRAPH_SIZE).into_drawing_area();
+    draw_on(root, checks, weights)
+}
+
+/// Draws the severity over time of `checks` as an SVG image to `path`.
+///
+/// Works just like [draw_checks], but produces vector graphics.
+///
+/// # Errors
+///
+/// See [draw_checks].
+pub fn draw_checks_svg(
+    checks: &[&Check],
+    path: impl AsRef<Path>,
+    weights: &SeverityConfig,
+) -> Result<(), AnalysisError> {
+    let root = SVGBackend::new(path.as_ref(), GRAPH_SIZE).into_drawing_area();
+    draw_on(root, checks, weights)
+}
+
+/// Draws the severity over time of `checks` on any plotters backend.
+fn draw_on<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    checks: &[&Check],
+    weights: &SeverityConfig,
+) -> Result<(), AnalysisError> {
+    let points = severity_over_time(checks, weights);
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return Err(AnalysisError::Graph {
+            reason: "there are no checks to draw".to_string(),
+        });
+    };
+    let start = first.0;
+    let end = if last.0 > start {
+        last.0
+    } else {
+        start + chrono::Duration::seconds(DEFAULT_PERIOD)
+    };
+
+    root.fill(&WHITE).map_err(graph_err)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Severity over time", ("sans-serif", 30))
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(start..end, 0f64..100f64)
+        .map_err(graph_err)?;
+    chart
+        .configure_mesh()
+        .y_desc("severity score (%)")
+        .x_label_formatter(&|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .draw()
+        .map_err(graph_err)?;
+    chart
+        .draw_series(AreaSeries::new(points, 0.0, RED.mix(0.3)).border_style(RED))
+        .map_err(graph_err)?;
+    root.present().map_err(graph_err)?;
+    Ok(())
+}
+
+/// Draws the failures of `checks` by day of the week and hour of the day to `path`.
+///
+/// Each cell of the [Heatmap] is colored from green (no failures) to red (20% or more failed),
+/// hours without checks stay white. The image is an SVG if `path` ends with `.svg`, otherwise a
+/// PNG.
+///
+/// # Errors
+///
+/// Returns [AnalysisError::Graph] if `checks` is empty or the image could not be drawn or
+/// written.
+pub fn draw_heatmap(checks: &[Check], path: impl AsRef<Path>) -> Result<(), AnalysisError> {
+    let path = path.as_ref();
+    let is_svg = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+    if is_svg {
+        draw_heatmap_on(
+            SVGBackend::new(path, GRAPH_SIZE).into_drawing_area(),
+            checks,
+        )
+    } else {
+        draw_heatmap_on(
+            BitMapBackend::new(path, GRAPH_SIZE).into_drawing_area(),
+            checks,
+        )
+    }
+}
+
+/// Draws the failures of `checks` as a [Heatmap] on any plotters backend.
+fn draw_heatmap_on<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    checks: &[Check],
+) -> Result<(), AnalysisError> {
+    let heatmap = Heatmap::of_checks(checks);
+    if heatmap.is_empty() {
+        return Err(AnalysisError::Graph {
+            reason: "there are no checks to draw".to_string(),
+        });
+    }
+
+    root.fill(&WHITE).map_err(graph_err)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Failed checks by time", ("sans-serif", 30))
+        .margin(20)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0u32..24u32, (0u32..6u32).into_segmented())
+        .map_err(graph_err)?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("hour of the day")
+        .x_labels(24)
+        .y_labels(7)
+        .y_label_formatter(&|day| match day {
+            // the first day is drawn at the top
+            SegmentValue::CenterOf(day) => WEEKDAYS
+                .get(6usize.saturating_sub(*day as usize))
+                .unwrap_or(&"")
+                .to_string(),
+            _ => String::new(),
+        })
+        .draw()
+        .map_err(graph_err)?;
+    let cells = heatmap.cells.iter().enumerate().flat_map(|(day, row)| {
+        row.iter().enumerate().map(move |(hour, cell)| {
+            let y = 6 - day as u32;
+            let color = match cell.failure_ratio() {
+                None => WHITE,
+                // full red from 20% on, like the text heatmap
+                Some(r) => {
+                    let heat = (r / 0.2).min(1.0);
+                    RGBColor((255.0 * heat) as u8, (200.0 * (1.0 - heat)) as u8, 0)
+                }
+            };
+            Rectangle::new(
+                [
+                    (hour as u32, SegmentValue::Exact(y)),
+                    (hour as u32 + 1, SegmentValue::Exact(y + 1)),
+                ],
+                color.filled(),
+            )
+        })
+    });
+    chart.draw_series(cells).map_err(graph_err)?;
+    root.present().map_err(graph_err)?;
+    Ok(())
+}
+
+/// Groups `checks` into time spans and calculates the [score](SeverityConfig::score) of each,
+/// as percentage.
+///
+/// The duration is left out of the score, as every span is equally long. Without degraded
+/// checks, this is the percentage of failed checks.
+///
+/// Returns the start of each time span with its score, sorted by time.
+fn severity_over_time(checks: &[&Check], weights: &SeverityConfig) -> Vec<(DateTime<Local>, f64)> {
+    let (Some(first), Some(last)) = (
+        checks.iter().map(|c| c.timestamp()).min(),
+        checks.iter().map(|c| c.timestamp()).max(),
+    ) else {
+        return Vec::new();
+    };
+    let span = ((last - first) / MAX_POINTS).max(DEFAULT_PERIOD);
+
+    // span start -> (all, failed, degraded)
+    let mut buckets: BTreeMap<i64, (usize, usize, usize)> = BTreeMap::new();
+    for check in checks {
+        let bucket = buckets
+            .entry(check.timestamp() - (check.timestamp() - first) % span)
+            .or_default();
+        bucket.0 += 1;
+        bucket.1 += !check.is_success() as usize;
+        bucket.2 += (check.is_success() && check.is_degraded()) as usize;
+    }
+
+    buckets
+        .into_iter()
+        .map(|(start, (all, failed, degraded))| {
+            let severity = Severity::of_counts(all, failed, degraded);
+            (
+                Local.timestamp_opt(start, 0).unwrap(),
+                weights.score(severity, 0) * 100.0,
+            )
+        })
+        .collect()
+}
+
+/// Converts any error of plotters into an [AnalysisError]
+fn graph_err(err: impl std::error::Error) -> AnalysisError {
+    AnalysisError::Graph {
+        reason: err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::records::CheckFlag;
+
+    use super::*;
+
+    #[test]
+    fn test_severity_over_time() {
+        let target = "1.1.1.1".parse().unwrap();
+        let checks: Vec<Check> = (0..4)
+            .flat_map(|minute| {
+                let t = DateTime::from_timestamp(minute * 60, 0).unwrap();
+                [
+                    Check::new(
+                        t,
+                        CheckFlag::Success | CheckFlag::TypeHTTP,
+                        Some(std::time::Duration::from_millis(10)),
+                        target,
+                    ),
+                    Check::new(
+                        t,
+                        if minute % 2 == 0 {
+                            CheckFlag::TypeHTTP.into()
+                        } else {
+                            CheckFlag::Success | CheckFlag::TypeHTTP
+                        },
+                        None,
+                        target,
+                    ),
+                ]
+            })
+            .collect();
+        let refs: Vec<&Check> = checks.iter().collect();
+
+        let points = severity_over_time(&refs, &SeverityConfig::default());
+        let ratios: Vec<f64> = points.iter().map(|p| p.1).collect();
+        assert_eq!(ratios, vec![50.0, 0.0, 50.0, 0.0]);
+        assert_eq!(points[1].0.timestamp(), 60);
+        assert!(severity_over_time(&[], &SeverityConfig::default()).is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+//! Translations of the labels of the analysis.
+//!
+//! The analysis writes its sections and labels in English. They can be shown in another
+//! [Language] instead, which is taken from [Config::language](crate::config::Config::language)
+//! given to the analysis. The `netpulse` binaries let [ENV_LANGUAGE] override it. Only the labels are translated, the
+//! values, the layout of `<label>: <value>` and the JSON output stay the same, so scripts that
+//! parse the analysis only have to know the labels of the language they set.
+//!
+//! Labels are looked up by their English text, see [Language::label]. Only the fixed labels
+//! that the analysis writes itself are translated, so they are `&'static str`. Data like the
+//! names of targets, interfaces or periods is shown as it is, even if it happens to match a
+//! label. Labels without a translation, like the names of protocols, stay English.
+//!
+//! ```rust
+//! use netpulse::analyze::i18n::Language;
+//!
+//! assert_eq!(Language::German.label("success ratio"), "Erfolgsquote");
+//! assert_eq!(Language::German.label("QUIC"), "QUIC");
+//! assert_eq!(Language::English.label("success ratio"), "success ratio");
+//! ```
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Environment variable name for the [Language] of the analysis, like `de`
+///
+/// If set, the binaries use its value instead of
+/// [Config::language](crate::config::Config::language), see [Language::from_env]. Values like `de_DE.UTF-8` work too, so it can be set to the value of `LANG`.
+pub const ENV_LANGUAGE: &str = "NETPULSE_LANGUAGE";
+
+/// Labels of the analysis in German, by their English text
+const GERMAN: &[(&str, &str)] = &[
+    // sections
+    ("General", "Allgemein"),
+    ("Failure Reasons", "Fehlerursachen"),
+    ("HTTP Status Codes", "HTTP-Statuscodes"),
+    ("Packet Loss", "Paketverlust"),
+    ("DNS over HTTPS", "DNS über HTTPS"),
+    ("DNS over TLS", "DNS über TLS"),
+    ("Bandwidth", "Bandbreite"),
+    ("Traces", "Routen"),
+    ("Targets", "Ziele"),
+    ("Interfaces", "Schnittstellen"),
+    ("Outages", "Ausfälle"),
+    ("Gaps", "Lücken"),
+    ("Monthly Summary", "Monatsübersicht"),
+    ("Weekly Summary", "Wochenübersicht"),
+    ("Failures by Time", "Fehler nach Zeit"),
+    ("Store Metadata", "Store-Metadaten"),
+    ("Calendar", "Kalender"),
+    // statistics
+    ("checks", "Prüfungen"),
+    ("checks ok", "Prüfungen ok"),
+    ("checks ok on retry", "ok nach Wiederholung"),
+    ("checks ok but degraded", "ok, aber langsam"),
+    ("checks bad", "Prüfungen fehlerhaft"),
+    ("checks aggregated", "Prüfungen aggregiert"),
+    ("success ratio", "Erfolgsquote"),
+    ("first check at", "erste Prüfung"),
+    ("last check at", "letzte Prüfung"),
+    ("time covered as up", "Zeit verfügbar"),
+    ("timeout", "Zeitüberschreitung"),
+    ("connection refused", "Verbindung abgelehnt"),
+    ("unreachable", "nicht erreichbar"),
+    ("DNS failure", "DNS-Fehler"),
+    ("server error", "Serverfehler"),
+    ("unknown", "unbekannt"),
+    ("Unknown", "Unbekannt"),
+    ("echoes sent", "Echos gesendet"),
+    ("echoes received", "Echos empfangen"),
+    ("loss", "Verlust"),
+    ("samples", "Messungen"),
+    ("average", "Durchschnitt"),
+    ("latest", "zuletzt"),
+    ("traces", "Routen"),
+    ("target", "Ziel"),
+    // outages and gaps
+    ("From", "Von"),
+    ("To", "Bis"),
+    ("Total", "Gesamt"),
+    ("Severity", "Schwere"),
+    ("Family", "Familie"),
+    ("Location", "Ort"),
+    ("without checks", "ohne Prüfungen"),
+    ("gaps", "Lücken"),
+    ("unmonitored", "unüberwacht"),
+    ("monitored", "überwacht"),
+    // uptime
+    ("uptime of", "Verfügbarkeit nach"),
+    ("current period", "aktueller Zeitraum"),
+    ("error budget", "Fehlerbudget"),
+    ("used", "verbraucht"),
+    ("remaining", "verbleibend"),
+    ("worst hour", "schlechteste Stunde"),
+    ("worst day", "schlechtester Tag"),
+    ("days without failures", "Tage ohne Fehler"),
+    // store metadata
+    ("Hash mem blake3", "Hash Speicher blake3"),
+    ("Hash file sha256", "Hash Datei sha256"),
+    ("Store Version (mem)", "Store-Version (Speicher)"),
+    ("Store Version (file)", "Store-Version (Datei)"),
+    ("Store Size (mem)", "Store-Größe (Speicher)"),
+    ("Store Size (file)", "Store-Größe (Datei)"),
+    ("File to Mem Ratio", "Datei zu Speicher"),
+    ("Created", "Erstellt"),
+    ("Last Saved", "Zuletzt gespeichert"),
+    ("Written by", "Geschrieben von"),
+    ("Instance ID", "Instanz-ID"),
+];
+
+/// Language of the labels of the analysis, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Language {
+    /// English, the labels as they are written
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    /// German
+    #[serde(rename = "de")]
+    German,
+}
+
+impl Language {
+    /// Returns the [Language] set in [ENV_LANGUAGE], if any.
+    ///
+    /// An unknown language is logged and taken as English.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(ENV_LANGUAGE).ok()?;
+        Some(value.parse().unwrap_or_else(|_| {
+            warn!("{ENV_LANGUAGE} has an unknown language: {value}, using english");
+            Self::English
+        }))
+    }
+
+    /// Returns `english`, the text of a label, in this [Language].
+    ///
+    /// Returns `english` itself if it has no translation.
+    pub fn label(&self, english: &'static str) -> &'static str {
+        let table = match self {
+            Self::English => return english,
+            Self::German => GERMAN,
+        };
+        table
+            .iter()
+            .find(|(key, _)| *key == english)
+            .map_or(english, |(_, translated)| translated)
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    /// Parses a language code like `de`, or a locale like `de_DE.UTF-8`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.split(['_', '-', '.']).next().unwrap_or_default();
+        match code.to_lowercase().as_str() {
+            "en" | "c" | "posix" => Ok(Self::English),
+            "de" => Ok(Self::German),
+            _ => Err(format!("unknown language: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_labels_fit_the_layout() {
+        for (english, german) in GERMAN {
+            // the labels are padded to 24 characters and followed by a colon
+            assert!(german.chars().count() <= 24, "{german} is too long");
+            assert!(!german.contains(':'), "{german} contains a colon");
+            assert_eq!(Language::German.label(english), *german);
+            assert_eq!(Language::English.label(english), *english);
+        }
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!("de".parse(), Ok(Language::German));
+        assert_eq!("de_DE.UTF-8".parse(), Ok(Language::German));
+        assert_eq!("EN".parse(), Ok(Language::English));
+        assert_eq!("C.UTF-8".parse(), Ok(Language::English));
+        assert!("fr_FR".parse::<Language>().is_err());
+    }
+}
@@ -2,11 +2,15 @@
 //!
 //! This module provides types and functions for analyzing periods of failed network checks:
 //! - [`Outage`] - Represents a period of consecutive failed checks
-//! - [`Severity`] - Classifies outage impact (complete, partial, none)
+//! - [`Severity`] - Classifies outage impact (complete, partial, degraded, none)
+//! - [`Location`] - Classifies whether the local network or the upstream internet failed
+//! - [`Family`] - Classifies whether only IPv4, only IPv6 or both failed
+//! - [`OutageSummary`] - Plain data about an outage, for serialization and integrators
 //!
 //! # Outage Analysis
 //!
-//! An outage is defined as a period containing one or more failed network checks. The module helps:
+//! An outage is defined as a period containing one or more failed network checks, or successful
+//! checks that were [degraded](Check::is_degraded). The module helps:
 //! - Track start/end times of outages
 //! - Calculate outage severity/impact
 //! - Generate outage reports and statistics
@@ -14,16 +18,22 @@
 use std::cmp::Ordering;
 use std::fmt::Display;
 use std::fmt::Write;
+use std::net::IpAddr;
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::error;
 
-use crate::records::Check;
+use crate::config::SeverityConfig;
+use crate::records::{Check, CheckType, IpType};
 
+use super::i18n::Language;
 use super::{fmt_timestamp, key_value_write, CheckGroup};
 
+/// Error indicating that a [`Severity`] could not be created from a raw failure ratio.
 #[derive(Error, Debug, Clone, Copy)]
 pub enum SeverityError {
+    /// The failure ratio was not in the range `0.0..=1.0`
     #[error("Ratio of severity out of range: {0}")]
     BadRawPercentage(f64),
 }
@@ -48,6 +58,7 @@ pub enum SeverityError {
 /// ```
 #[derive(Error, Debug, Clone, Copy)]
 pub enum OutageError {
+    /// The [`Outage`] would not contain any [Checks](Check)
     #[error("tried to create an empty outage (without any contained checks)")]
     EmptyOutage,
 }
@@ -57,8 +68,13 @@ pub enum OutageError {
 /// Represents how severely network connectivity was impacted during an outage period:
 /// - Complete (only failed checks)
 /// - Partial (some failed checks)
+/// - Degraded (no failed checks, but some [degraded](Check::is_degraded) ones)
 /// - None (no failed checks)
 ///
+/// Severities are totally [ordered](Ord) by kind, then by ratio. That order ignores how long an
+/// outage lasted, so outages are ranked by a score that weighs in their duration, see
+/// [SeverityConfig::score].
+///
 /// # Examples
 ///
 /// ```rust
@@ -71,12 +87,15 @@ pub enum OutageError {
 /// assert!(complete > partial);
 /// assert!(partial > none);
 /// ```
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(tag = "kind", content = "ratio", rename_all = "lowercase")]
 pub enum Severity {
     /// All checks failed (100% failure rate)
     Complete,
     /// Some checks failed (partial failure rate between 0-100%)
     Partial(f64),
+    /// No checks failed, but some were too slow (ratio of degraded checks between 0-100%)
+    Degraded(f64),
     /// No checks failed (0% failure rate)
     None,
 }
@@ -98,16 +117,100 @@ impl TryFrom<f64> for Severity {
     type Error = SeverityError;
 }
 
+impl Severity {
+    /// Calculates the [Severity] of `checks` checks, of which `failed` failed and `degraded`
+    /// were successful but [degraded](Check::is_degraded), see [Outage::severity].
+    ///
+    /// # Panics
+    ///
+    /// Panics if more checks failed than there are.
+    pub(crate) fn of_counts(checks: usize, failed: usize, degraded: usize) -> Self {
+        let percentage = failed as f64 / checks as f64;
+        if percentage == 0.0 && degraded > 0 {
+            return Severity::Degraded(degraded as f64 / checks as f64);
+        }
+        Severity::try_from(percentage).expect("calculated more than 100% success")
+    }
+
+    /// Returns the ratio of failed checks, or of degraded checks for [Severity::Degraded],
+    /// between 0.0 and 1.0.
+    pub fn ratio(&self) -> f64 {
+        match self {
+            Self::Complete => 1.0,
+            Self::Partial(ratio) | Self::Degraded(ratio) => *ratio,
+            Self::None => 0.0,
+        }
+    }
+
+    /// Returns the position of the kind of this [Severity] in its [order](Ord).
+    fn rank(&self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Degraded(_) => 1,
+            Self::Partial(_) => 2,
+            Self::Complete => 3,
+        }
+    }
+}
+
+impl PartialEq for Severity {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Severity {}
+
 impl PartialOrd for Severity {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Severity {
+    /// Orders by kind first (complete > partial > degraded > none), then by the ratio.
+    ///
+    /// Ratios are compared with [f64::total_cmp], so this is a total order even for ratios that
+    /// were not calculated from checks.
+    fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
-            (Self::Complete, Self::Complete) => Some(std::cmp::Ordering::Equal),
-            (Self::Complete, _) => Some(std::cmp::Ordering::Greater),
-            (Self::Partial(p1), Self::Partial(p2)) => p1.partial_cmp(p2),
-            (Self::Partial(_), Self::Complete) => Some(std::cmp::Ordering::Less),
-            (Self::Partial(_), Self::None) => Some(std::cmp::Ordering::Greater),
-            (Self::None, Self::None) => Some(std::cmp::Ordering::Equal),
-            (Self::None, _) => Some(std::cmp::Ordering::Less),
+            (Self::Partial(r1), Self::Partial(r2)) | (Self::Degraded(r1), Self::Degraded(r2)) => {
+                r1.total_cmp(r2)
+            }
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl std::str::FromStr for Severity {
+    type Err = String;
+
+    /// Parses `complete`, `none`, `partial:<percent>` or `degraded:<percent>`, like
+    /// `partial:50` for half of the checks failing.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || {
+            format!(
+                "Not a valid severity: {s}, use complete, partial:<percent> or degraded:<percent>"
+            )
+        };
+        let percent = |p: &str| {
+            p.parse::<f64>()
+                .ok()
+                .filter(|p| (0.0..=100.0).contains(p))
+                .map(|p| p / 100.0)
+        };
+        match s {
+            "complete" => Ok(Self::Complete),
+            "none" => Ok(Self::None),
+            _ => {
+                if let Some(p) = s.strip_prefix("partial:") {
+                    percent(p).map(Self::Partial).ok_or_else(bad)
+                } else if let Some(p) = s.strip_prefix("degraded:") {
+                    percent(p).map(Self::Degraded).ok_or_else(bad)
+                } else {
+                    Err(bad())
+                }
+            }
         }
     }
 }
@@ -118,15 +221,160 @@ impl Display for Severity {
             Self::Complete => write!(f, "Complete")?,
             Self::None => write!(f, "No Outage")?,
             Self::Partial(p) => write!(f, "Partial ({:.02} %)", p * 100.0)?,
+            Self::Degraded(p) => write!(f, "Degraded ({:.02} %)", p * 100.0)?,
         }
         Ok(())
     }
 }
 
-/// Represents a period of consecutive failed network checks.
+/// Where the cause of an [`Outage`] was, judged by its [gateway checks](CheckType::Gateway).
+///
+/// Outages without gateway checks can not be located.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Location {
+    /// The default gateway did not answer either, the local network was down
+    LocalNetwork,
+    /// The default gateway answered, the problem was further away
+    Upstream,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LocalNetwork => write!(f, "Local Network"),
+            Self::Upstream => write!(f, "Upstream"),
+        }
+    }
+}
+
+impl Location {
+    /// Locates an outage by its [gateway checks](CheckType::Gateway), see [Outage::location].
+    ///
+    /// `checked` tells whether the outage has gateway checks at all, `failed` whether any of
+    /// them failed.
+    pub(crate) fn of_gateway(checked: bool, failed: bool) -> Option<Self> {
+        checked.then_some(if failed {
+            Self::LocalNetwork
+        } else {
+            Self::Upstream
+        })
+    }
+}
+
+/// Which IP versions an [`Outage`] affected, judged by the IP versions of its failed checks.
+///
+/// An outage can only be told to affect one IP version if checks of the other version were made
+/// and succeeded. Otherwise there is nothing to compare against.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Family {
+    /// Only checks over IPv4 failed, IPv6 worked
+    Ipv4Only,
+    /// Only checks over IPv6 failed, IPv4 worked
+    Ipv6Only,
+    /// Checks over both IPv4 and IPv6 failed
+    DualStack,
+}
+
+impl Display for Family {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ipv4Only => write!(f, "IPv4-only"),
+            Self::Ipv6Only => write!(f, "IPv6-only"),
+            Self::DualStack => write!(f, "Dual-Stack"),
+        }
+    }
+}
+
+impl Family {
+    /// Tells which IP versions an outage affected, see [Outage::family].
+    ///
+    /// `failed` and `worked` tell whether any check over IPv4 and IPv6, in that order, failed
+    /// or worked.
+    pub(crate) fn of(failed: [bool; 2], worked: [bool; 2]) -> Option<Self> {
+        match failed {
+            [true, true] => Some(Self::DualStack),
+            [true, false] if worked[1] => Some(Self::Ipv4Only),
+            [false, true] if worked[0] => Some(Self::Ipv6Only),
+            _ => None,
+        }
+    }
+}
+
+/// Summary of an [`Outage`] as plain data.
+///
+/// Unlike [`Outage`], this does not borrow the [Checks](Check), and it can be serialized with
+/// [serde]. Use [`Outage::to_summary`] or [outage_summaries](super::outage_summaries) to create
+/// it. Timestamps are unix timestamps in seconds.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use netpulse::analyze::outage_summaries;
+/// use netpulse::store::Store;
+///
+/// let store = Store::load(true).unwrap();
+/// for summary in outage_summaries(&store) {
+///     println!("{} to {}: {}", summary.start, summary.end, summary.severity);
+/// }
+/// ```
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct OutageSummary {
+    /// Time of the first check of the outage
+    pub start: i64,
+    /// Time of the last check of the outage
+    pub end: i64,
+    /// Seconds between the first and the last check of the outage
+    pub duration: i64,
+    /// Number of checks in the outage
+    pub checks: usize,
+    /// How bad the outage was
+    pub severity: Severity,
+    /// Types of the failed or degraded checks, sorted
+    pub check_types: Vec<CheckType>,
+    /// Targets of the failed or degraded checks, sorted
+    pub targets: Vec<IpAddr>,
+    /// Where the cause of the outage was, if it could be told
+    pub location: Option<Location>,
+    /// Which IP versions the outage affected, if it could be told
+    pub family: Option<Family>,
+    /// Whether the outage lasted until the last round of checks, so it might still go on
+    pub ongoing: bool,
+}
+
+impl OutageSummary {
+    /// Generates a concise single-line report of the outage, see [Outage::short_report].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`std::fmt::Error`] if string formatting fails.
+    pub fn short_report(&self) -> Result<String, std::fmt::Error> {
+        let time = |timestamp: i64| {
+            chrono::DateTime::from_timestamp(timestamp, 0).expect("timestamp is out of range")
+        };
+        let mut buf: String = String::new();
+        write!(&mut buf, "From {}", fmt_timestamp(time(self.start)))?;
+        write!(&mut buf, " To {}", fmt_timestamp(time(self.end)))?;
+        write!(&mut buf, ", Total {:>6}", self.checks)?;
+        write!(&mut buf, ", {}", self.severity)?;
+        if let Some(family) = self.family {
+            write!(&mut buf, ", {family}")?;
+        }
+        if let Some(location) = self.location {
+            write!(&mut buf, ", {location}")?;
+        }
+        if self.ongoing {
+            write!(&mut buf, ", ongoing")?;
+        }
+        Ok(buf)
+    }
+}
+
+/// Represents a period of consecutive failed or degraded network checks.
 ///
 /// An outage is defined by:
-/// - One or more consecutive checks that failed
+/// - One or more consecutive checks that failed or were [degraded](Check::is_degraded)
 /// - An outage with no checks is technically allowed, but serves no purpose
 ///
 /// From that, we can extrapolate:
@@ -204,19 +452,46 @@ impl<'check> Outage<'check> {
     ///
     /// Returns [`std::fmt::Error`] if string formatting fails.
     pub fn short_report(&self) -> Result<String, std::fmt::Error> {
+        self.to_summary().short_report()
+    }
+
+    /// Generates the full report of the outage, with its labels in `language`.
+    ///
+    /// Displaying the [Outage] gives the same report in English.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`std::fmt::Error`] if string formatting fails.
+    pub fn report(&self, language: Language) -> Result<String, std::fmt::Error> {
+        let label = |english| language.label(english);
         let mut buf: String = String::new();
-        write!(
+        key_value_write(
             &mut buf,
-            "From {}",
+            label("From"),
             fmt_timestamp(self.first().unwrap().timestamp_parsed()),
         )?;
-        write!(
+        key_value_write(
             &mut buf,
-            " To {}",
+            label("To"),
             fmt_timestamp(self.last().unwrap().timestamp_parsed()),
         )?;
-        write!(&mut buf, ", Total {:>6}", self.len())?;
-        write!(&mut buf, ", {}", self.severity())?;
+        key_value_write(&mut buf, label("Total"), self.len())?;
+        key_value_write(&mut buf, label("Severity"), self.severity())?;
+        if let Some(family) = self.family() {
+            key_value_write(&mut buf, label("Family"), family)?;
+        }
+        if let Some(location) = self.location() {
+            key_value_write(&mut buf, label("Location"), location)?;
+        }
+        let hints = super::diagnose::diagnose(self);
+        if !hints.is_empty() {
+            writeln!(buf, "\nHints")?;
+            for hint in hints {
+                writeln!(buf, "- {hint}")?;
+            }
+        }
+        writeln!(buf, "\nFirst\n{}", self.last().unwrap())?;
+        writeln!(buf, "\nLast\n{}", self.last().unwrap())?;
         Ok(buf)
     }
 
@@ -235,7 +510,7 @@ impl<'check> Outage<'check> {
     ///
     /// Severity is based on the percentage of failed checks:
     /// - 100% = Complete outage
-    /// - 0% = No outage
+    /// - 0% = Degraded if any checks were [degraded](Check::is_degraded), no outage otherwise
     /// - Other = Partial outage
     ///
     /// # Examples
@@ -250,25 +525,109 @@ impl<'check> Outage<'check> {
     /// ```
     pub fn severity(&self) -> Severity {
         let all = self.all();
-        let percentage: f64 =
-            all.iter().filter(|a| !a.is_success()).count() as f64 / all.len() as f64;
-        Severity::try_from(percentage).expect("calculated more than 100% success")
+        Severity::of_counts(
+            all.len(),
+            all.iter().filter(|a| !a.is_success()).count(),
+            all.iter().filter(|a| a.is_degraded()).count(),
+        )
+    }
+
+    /// Tells whether the local network or the upstream internet was down, see [Location].
+    ///
+    /// If any [gateway check](CheckType::Gateway) of this outage failed, the local network was
+    /// down. Returns [None] if there are no gateway checks.
+    pub fn location(&self) -> Option<Location> {
+        let mut gateway_checks = self
+            .all
+            .iter()
+            .filter(|c| c.calc_type().is_ok_and(|t| t == CheckType::Gateway))
+            .peekable();
+        let checked = gateway_checks.peek().is_some();
+        Location::of_gateway(checked, gateway_checks.any(|c| !c.is_success()))
+    }
+
+    /// Tells which IP versions this outage affected, see [Family].
+    ///
+    /// Returns [None] if only checks of one IP version failed, but no checks of the other
+    /// version succeeded, for example because only one version is checked at all.
+    pub fn family(&self) -> Option<Family> {
+        let failed = |ip_type: IpType| {
+            self.all
+                .iter()
+                .any(|c| c.ip_type() == ip_type && !c.is_success())
+        };
+        let worked = |ip_type: IpType| {
+            self.all
+                .iter()
+                .any(|c| c.ip_type() == ip_type && c.is_success())
+        };
+        Family::of(
+            [failed(IpType::V4), failed(IpType::V6)],
+            [worked(IpType::V4), worked(IpType::V6)],
+        )
     }
 
-    /// Compares two outages by severity then by duration.
+    /// Summarizes this [`Outage`] as an [`OutageSummary`].
     ///
-    /// Orders outages first by severity (complete > partial > none),
-    /// then by number of checks for equal severities.
-    pub fn cmp_severity(&self, other: &Self) -> Ordering {
-        match self
-            .severity()
-            .partial_cmp(&other.severity())
-            .unwrap_or(Ordering::Equal)
-        {
-            Ordering::Equal => self.len().cmp(&other.len()),
-            other => other,
+    /// The summary is never [ongoing](OutageSummary::ongoing), an [`Outage`] does not know about
+    /// the checks after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Outage`] is empty.
+    pub fn to_summary(&self) -> OutageSummary {
+        let start = self.first().expect("outage is empty").timestamp();
+        let end = self.last().expect("outage is empty").timestamp();
+        let failed: Vec<&&Check> = self
+            .all
+            .iter()
+            .filter(|c| !c.is_success() || c.is_degraded())
+            .collect();
+
+        let mut check_types: Vec<CheckType> = failed
+            .iter()
+            .map(|c| c.calc_type().unwrap_or(CheckType::Unknown))
+            .collect();
+        check_types.sort();
+        check_types.dedup();
+        let mut targets: Vec<IpAddr> = failed.iter().map(|c| c.target()).collect();
+        targets.sort();
+        targets.dedup();
+
+        OutageSummary {
+            start,
+            end,
+            duration: end - start,
+            checks: self.len(),
+            severity: self.severity(),
+            check_types,
+            targets,
+            location: self.location(),
+            family: self.family(),
+            ongoing: false,
         }
     }
+
+    /// Calculates the score of this [`Outage`] from its [Severity] and duration, see
+    /// [SeverityConfig::score].
+    pub fn score(&self, weights: &SeverityConfig) -> f64 {
+        let duration = match (self.first(), self.last()) {
+            (Some(first), Some(last)) => last.timestamp() - first.timestamp(),
+            _ => 0,
+        };
+        weights.score(self.severity(), duration)
+    }
+
+    /// Compares two outages by their [score](Self::score).
+    ///
+    /// Outages with the same score are ordered by [Severity] (complete > partial > degraded >
+    /// none), then by number of checks.
+    pub fn cmp_severity(&self, other: &Self, weights: &SeverityConfig) -> Ordering {
+        self.score(weights)
+            .total_cmp(&other.score(weights))
+            .then_with(|| self.severity().cmp(&other.severity()))
+            .then_with(|| self.len().cmp(&other.len()))
+    }
 }
 
 impl<'check> TryFrom<&'check [Check]> for Outage<'check> {
@@ -291,32 +650,20 @@ impl<'check> TryFrom<&'check Vec<&Check>> for Outage<'check> {
 impl<'check> TryFrom<CheckGroup<'check>> for Outage<'check> {
     type Error = OutageError;
 
-    fn try_from(value: CheckGroup<'check>) -> Result<Self, Self::Error> {
+    fn try_from(mut value: CheckGroup<'check>) -> Result<Self, Self::Error> {
         if value.is_empty() {
             return Err(OutageError::EmptyOutage);
         }
-        Outage::build(&value)
+        // the group is taken over instead of copied, fail groups are sorted already
+        if !value.is_sorted() {
+            value.sort();
+        }
+        Ok(Self { all: value })
     }
 }
 
 impl Display for Outage<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut buf: String = String::new();
-        key_value_write(
-            &mut buf,
-            "From",
-            fmt_timestamp(self.first().unwrap().timestamp_parsed()),
-        )?;
-        key_value_write(
-            &mut buf,
-            "To",
-            fmt_timestamp(self.last().unwrap().timestamp_parsed()),
-        )?;
-        key_value_write(&mut buf, "Total", self.len())?;
-        key_value_write(&mut buf, "Severity", self.severity())?;
-        writeln!(buf, "\nFirst\n{}", self.last().unwrap())?;
-        writeln!(buf, "\nLast\n{}", self.last().unwrap())?;
-        write!(f, "{buf}")?;
-        Ok(())
+        write!(f, "{}", self.report(Language::English)?)
     }
 }
@@ -0,0 +1,1421 @@
+//! Structured analysis results.
+//!
+//! The text report of [analyze](super::analyze) is meant for humans. The types in this module
+//! hold the same results as plain data that can be serialized with [serde], for example to JSON,
+//! so other tools can consume them without parsing the text.
+//!
+//! Timestamps are unix timestamps in seconds, ratios are between 0.0 and 1.0.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use netpulse::analyze::report::Report;
+//! use netpulse::store::Store;
+//!
+//! let store = Store::load(true).unwrap();
+//! let report = Report::build(&store).unwrap();
+//! println!("{} checks", report.general.checks);
+//! ```
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Datelike, Days, Local, Months, NaiveDate, TimeZone, Timelike};
+use deepsize::DeepSizeOf;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, SlaConfig};
+use crate::errors::AnalysisError;
+use crate::records::aggregate::{Aggregate, AGGREGATE_SPAN};
+use crate::records::trace::{Segment, Trace};
+use crate::records::{Check, CheckType, IpType, Measurement};
+use crate::store::Store;
+
+use super::gap::Gap;
+use super::outage::{Outage, OutageSummary};
+use super::{fail_groups, success_ratio};
+
+/// All results of an analysis of a [Store], see [analyze](super::analyze).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    /// Statistics over all [Checks](Check)
+    pub general: Stats,
+    /// Ratio of the wall-clock time covered by successful checks, see [Coverage::uptime]
+    pub time_uptime: Option<f64>,
+    /// Why the failed [Checks](Check) failed
+    pub failure_reasons: FailureReasons,
+    /// Statistics of the HTTP [Checks](Check)
+    pub http: Stats,
+    /// Distribution of the HTTP status codes
+    pub http_status_codes: StatusCodes,
+    /// Statistics of the QUIC [Checks](Check)
+    pub quic: Stats,
+    /// Statistics of the ICMP [Checks](Check)
+    pub icmp: Stats,
+    /// Packet loss of the ICMP [Checks](Check) that sent a burst of echoes
+    pub loss: LossStats,
+    /// Statistics of the DNS [Checks](Check) over UDP
+    pub dns: Stats,
+    /// Statistics of the DNS [Checks](Check) over HTTPS
+    pub doh: Stats,
+    /// Statistics of the DNS [Checks](Check) over TLS
+    pub dot: Stats,
+    /// Statistics of the NTP [Checks](Check)
+    pub ntp: Stats,
+    /// Measured clock offset per NTP server
+    pub ntp_servers: Vec<NtpStats>,
+    /// Statistics of the MTU [Checks](Check)
+    pub mtu: Stats,
+    /// Measured path MTU per target
+    pub mtu_targets: Vec<MtuStats>,
+    /// Statistics of the gateway [Checks](Check)
+    pub gateway: Stats,
+    /// Statistics of the bandwidth [Checks](Check)
+    pub bandwidth: Stats,
+    /// Measured throughput of the bandwidth [Checks](Check)
+    pub throughput: ThroughputStats,
+    /// Where the paths to targets of failed [Checks](Check) died
+    pub traces: TraceStats,
+    /// Statistics of the [Checks](Check) that used IPv4
+    pub ipv4: Stats,
+    /// Statistics of the [Checks](Check) that used IPv6
+    pub ipv6: Stats,
+    /// Statistics per target
+    pub targets: Vec<TargetStats>,
+    /// Statistics and outages per interface the checks were bound to
+    pub interfaces: Vec<InterfaceStats>,
+    /// All outages, sorted by time
+    pub outages: Vec<OutageReport>,
+    /// Times in which no checks were made, oldest first
+    pub gaps: Vec<Gap>,
+    /// Uptime per calendar month, oldest first
+    pub monthly: Vec<PeriodStats>,
+    /// Error budget of the current period, if an SLA is configured
+    pub sla: Option<SlaStats>,
+    /// Failures by day of the week and hour of the day
+    pub heatmap: Heatmap,
+    /// Metadata of the [Store]
+    pub store: StoreMeta,
+}
+
+/// Statistics of a set of [Checks](Check) and [Aggregates](Aggregate).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    /// Number of all checks, including aggregated ones
+    pub checks: usize,
+    /// Number of successful checks
+    pub checks_ok: usize,
+    /// Number of successful checks that only passed on retry, included in `checks_ok`
+    pub checks_ok_retried: usize,
+    /// Number of successful checks that were [degraded](Check::is_degraded), included in
+    /// `checks_ok`
+    pub checks_ok_degraded: usize,
+    /// Number of failed checks
+    pub checks_bad: usize,
+    /// Number of checks that are only known from [Aggregates](Aggregate)
+    pub checks_aggregated: usize,
+    /// Ratio of successful checks, [None] if there are no checks
+    pub success_ratio: Option<f64>,
+    /// Time of the first check
+    pub first_check_at: Option<i64>,
+    /// Time of the last check
+    pub last_check_at: Option<i64>,
+}
+
+/// Why the failed [Checks](Check) failed, see [Check::failure_reason].
+///
+/// Only raw [Checks](Check) are considered, [Aggregates](Aggregate) do not keep the flags.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailureReasons {
+    /// Number of failed checks per [reason](crate::records::CheckFlag::reason_name)
+    pub per_reason: BTreeMap<&'static str, usize>,
+    /// Number of failed checks without a known reason
+    pub unknown: usize,
+}
+
+/// Distribution of the status codes of HTTP [Checks](Check).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusCodes {
+    /// Number of checks per HTTP status code
+    pub per_status: BTreeMap<u16, usize>,
+    /// Number of failed checks that got no response at all
+    pub no_response: usize,
+    /// Number of successful checks from a store version that did not record status codes
+    pub not_recorded: usize,
+}
+
+/// Packet loss measured by [Checks](Check) with a [Measurement::Loss].
+///
+/// Only raw [Checks](Check) are considered, [Aggregates](Aggregate) do not keep measurements.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LossStats {
+    /// Number of echoes sent
+    pub sent: usize,
+    /// Number of echoes received
+    pub received: usize,
+    /// Ratio of lost echoes, [None] if no echoes were sent
+    pub loss_ratio: Option<f64>,
+    /// Packet loss per day, oldest first
+    pub per_day: Vec<DailyLoss>,
+}
+
+/// Packet loss of one day, see [LossStats].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DailyLoss {
+    /// Start of the day in local time
+    pub day: i64,
+    /// Number of echoes sent
+    pub sent: usize,
+    /// Number of echoes received
+    pub received: usize,
+    /// Ratio of lost echoes
+    pub loss_ratio: f64,
+}
+
+/// Path MTU measured by the MTU [Checks](Check) against one target, see
+/// [Measurement::Mtu].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MtuStats {
+    /// Address of the target
+    pub target: IpAddr,
+    /// Label of the target, taken from the latest [Check] that has one
+    pub label: Option<String>,
+    /// Number of measurements
+    pub measurements: usize,
+    /// Smallest measured MTU
+    pub smallest: u16,
+    /// Largest measured MTU
+    pub largest: u16,
+    /// Latest measured MTU
+    pub latest: u16,
+}
+
+/// Where the paths of the [Traces](Trace) of failed [Checks](Check) died, see [Segment].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceStats {
+    /// Number of traces
+    pub traces: usize,
+    /// Number of traces per [Segment] their path died in
+    pub per_segment: BTreeMap<Segment, usize>,
+    /// The latest trace, if any
+    pub latest: Option<Trace>,
+}
+
+/// Clock offset measured by the NTP [Checks](Check) against one server, see
+/// [Measurement::Ntp].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NtpStats {
+    /// Address of the server
+    pub target: IpAddr,
+    /// Label of the server, taken from the latest [Check] that has one
+    pub label: Option<String>,
+    /// Number of measurements
+    pub measurements: usize,
+    /// Latest offset in milliseconds
+    pub latest: i32,
+    /// Offset furthest from zero in milliseconds
+    pub largest: i32,
+}
+
+/// Throughput measured by the bandwidth [Checks](Check), see [Measurement::Throughput].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThroughputStats {
+    /// Number of samples
+    pub samples: usize,
+    /// Average throughput in bytes per second, [None] if there are no samples
+    pub average: Option<u32>,
+    /// Latest throughput in bytes per second, [None] if there are no samples
+    pub latest: Option<u32>,
+    /// Throughput per day, oldest first
+    pub per_day: Vec<DailyThroughput>,
+}
+
+/// Throughput of one day, see [ThroughputStats].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DailyThroughput {
+    /// Start of the day in local time
+    pub day: i64,
+    /// Number of samples
+    pub samples: usize,
+    /// Average throughput in bytes per second
+    pub average: u32,
+    /// Lowest throughput in bytes per second
+    pub lowest: u32,
+}
+
+/// Statistics of the [Checks](Check) against one target.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TargetStats {
+    /// Address of the target
+    pub target: IpAddr,
+    /// Label of the target, taken from the latest [Check] that has one
+    pub label: Option<String>,
+    /// Number of all checks, including aggregated ones
+    pub checks: usize,
+    /// Number of successful checks
+    pub checks_ok: usize,
+    /// Ratio of successful checks
+    pub success_ratio: f64,
+}
+
+/// Statistics and outages of the [Checks](Check) bound to one interface or source address, see
+/// [Check::interface].
+///
+/// Each interface is analyzed on its own, so an outage of one uplink shows up even while the
+/// other one works. Only raw [Checks](Check) are considered, [Aggregates](Aggregate) do not keep
+/// the interface.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InterfaceStats {
+    /// Name of the interface, or the source address
+    pub interface: String,
+    /// Statistics of the [Checks](Check) bound to the interface
+    pub stats: Stats,
+    /// Number of outages of the interface
+    pub outages: usize,
+    /// Sum of the durations of the outages in seconds
+    pub downtime: i64,
+    /// Duration of the longest outage in seconds
+    pub longest_outage: i64,
+    /// The latest outage of the interface
+    pub latest_outage: Option<OutageSummary>,
+}
+
+/// Length of the calendar periods of a [PeriodStats].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    /// Calendar weeks, starting on monday
+    Weekly,
+    /// Calendar months
+    Monthly,
+}
+
+/// What the uptime of a [PeriodStats] is measured by, see [SlaConfig::basis].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UptimeBasis {
+    /// The ratio of successful checks, see [PeriodStats::uptime]
+    #[default]
+    Checks,
+    /// The ratio of the wall-clock time covered by successful checks, see
+    /// [PeriodStats::time_uptime]
+    Time,
+}
+
+/// Wall-clock time covered by [Checks](Check) and [Aggregates](Aggregate).
+///
+/// The daemon makes a round of checks every period, so each round stands for the period of
+/// time until the next one. A round covers that time as up by the ratio of its successful
+/// checks. An [Aggregate] stands for its whole [span](crate::records::aggregate::AGGREGATE_SPAN).
+/// Times without checks, like [Gaps](Gap), are covered by nothing, so unlike the ratio of
+/// successful checks, [Coverage::uptime] does not count them as up.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Coverage {
+    /// Start, covered seconds and seconds up of every round and aggregated hour, sorted by start
+    spans: Vec<(i64, i64, f64)>,
+}
+
+/// Uptime in one calendar week or month, to compare against the SLA of an ISP.
+///
+/// Outages are counted in the period they started in. The uptime also counts [Checks](Check)
+/// that are only known from [Aggregates](Aggregate), the outages do not. The uptime only knows
+/// about the time that was monitored, the [Gaps](Gap) are counted as unmonitored time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PeriodStats {
+    /// Name of the period, like `2024-W45` or `2024-11`
+    pub name: String,
+    /// Start of the period in local time
+    pub start: i64,
+    /// Start of the next period in local time
+    pub end: i64,
+    /// Number of all checks, including aggregated ones
+    pub checks: usize,
+    /// Number of successful checks
+    pub checks_ok: usize,
+    /// Ratio of successful checks
+    pub uptime: f64,
+    /// Number of outages that started in the period
+    pub outages: usize,
+    /// Sum of the durations of the outages in seconds
+    pub downtime: i64,
+    /// Duration of the longest outage in seconds
+    pub longest_outage: i64,
+    /// Duration of the [Gaps](Gap) in the period in seconds
+    pub unmonitored: i64,
+    /// Ratio of the wall-clock time of the period covered by successful checks, see
+    /// [Coverage::uptime]
+    pub time_uptime: f64,
+}
+
+/// Error budget of the current period against the uptime promised in the [SlaConfig].
+///
+/// The error budget is the downtime the SLA allows in a whole period. The used budget is
+/// estimated from the uptime of the [basis](SlaConfig::basis) and the time that passed since the
+/// start of the period. With [UptimeBasis::Checks], it assumes that the checks cover all of that
+/// time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlaStats {
+    /// Promised uptime as a ratio
+    pub target: f64,
+    /// Calendar period the uptime is promised for
+    pub period: Period,
+    /// What the uptime is measured by
+    pub basis: UptimeBasis,
+    /// Uptime of the current period, [None] if there are no checks in it yet
+    pub current: Option<PeriodStats>,
+    /// Allowed downtime in the whole current period in seconds
+    pub budget: i64,
+    /// Downtime in the current period so far in seconds
+    pub used: i64,
+    /// Downtime that is left in the current period in seconds, negative if exceeded
+    pub remaining: i64,
+    /// Whether the budget of the current period is used up
+    pub exceeded: bool,
+    /// Past periods whose uptime was below the target, oldest first
+    pub missed: Vec<PeriodStats>,
+}
+
+/// Short names of the days of the week, starting on monday like the rows of a [Heatmap]
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Failures of [Checks](Check) by day of the week and hour of the day, in local time.
+///
+/// Shows patterns like a connection that drops every night at 3am. Only raw [Checks](Check) are
+/// considered, [Aggregates](Aggregate) only know the hour they started in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Heatmap {
+    /// One row per day of the week, starting on monday, with one cell per hour of the day
+    pub cells: Vec<Vec<HeatmapCell>>,
+}
+
+/// Checks in one hour of one day of the week, see [Heatmap].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct HeatmapCell {
+    /// Number of checks
+    pub checks: usize,
+    /// Number of failed checks
+    pub failed: usize,
+}
+
+/// Uptime of every day of the last calendar months, in local time, see
+/// [calendar](super::calendar).
+///
+/// Like [PeriodStats], this also counts [Checks](Check) that are only known from
+/// [Aggregates](Aggregate).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Calendar {
+    /// Every day from the first day of the first month up to today, oldest first
+    pub days: Vec<CalendarDay>,
+}
+
+/// Checks of one day of a [Calendar].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CalendarDay {
+    /// The day, like `2024-11-09`
+    pub date: String,
+    /// Start of the day in local time
+    pub start: i64,
+    /// Number of all checks, including aggregated ones
+    pub checks: usize,
+    /// Number of successful checks
+    pub checks_ok: usize,
+}
+
+/// Summary of one [Outage], optionally with all of its [Checks](Check).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutageReport {
+    /// Summary of the outage
+    #[serde(flatten)]
+    pub summary: OutageSummary,
+    /// All checks of the outage, only set if requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contained: Option<Vec<CheckReport>>,
+}
+
+/// A single [Check] in a form that is easy to consume.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckReport {
+    /// Time the check was made
+    pub timestamp: i64,
+    /// Type of the check
+    pub check_type: CheckType,
+    /// Whether the check was successful
+    pub success: bool,
+    /// Whether the check needed more than one attempt
+    pub retried: bool,
+    /// Address of the target
+    pub target: IpAddr,
+    /// Zone of the address of the target, if it is link-local
+    pub zone: Option<String>,
+    /// Label of the target
+    pub label: Option<String>,
+    /// Latency in milliseconds, if the check was successful
+    pub latency: Option<u32>,
+    /// Latency in microseconds, if the check was successful
+    pub latency_us: Option<u32>,
+    /// HTTP status code of the response, if any
+    pub http_status: Option<u16>,
+    /// Interface or source address the check was bound to, if any
+    pub interface: Option<String>,
+    /// Name of the machine that made the check, see [Check::probe]
+    pub probe: Option<String>,
+}
+
+impl From<&Check> for CheckReport {
+    fn from(check: &Check) -> Self {
+        Self {
+            timestamp: check.timestamp(),
+            check_type: check.calc_type().unwrap_or(CheckType::Unknown),
+            success: check.is_success(),
+            retried: check.is_retried(),
+            target: check.target(),
+            zone: check.zone().map(str::to_string),
+            label: check.label().map(str::to_string),
+            latency: check.latency(),
+            latency_us: check.latency_us(),
+            http_status: check.http_status(),
+            interface: check.interface().map(str::to_string),
+            probe: check.probe().map(str::to_string),
+        }
+    }
+}
+
+/// The data of a [Store], for `netpulse --export json`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoreExport {
+    /// The [Checks](Check), oldest first
+    pub checks: Vec<CheckReport>,
+    /// Hourly summaries of old [Checks](Check) that were downsampled
+    pub aggregates: Vec<Aggregate>,
+    /// Traces of the path to targets whose [Checks](Check) failed
+    pub traces: Vec<Trace>,
+}
+
+impl StoreExport {
+    /// Collects the data of `store`, only the failed [Checks](Check) if `failed_only` is true.
+    pub fn of_store(store: &Store, failed_only: bool) -> Self {
+        Self {
+            checks: store
+                .checks()
+                .iter()
+                .filter(|c| !failed_only || !c.is_success())
+                .map(CheckReport::from)
+                .collect(),
+            aggregates: store.aggregates().to_vec(),
+            traces: store.traces().to_vec(),
+        }
+    }
+}
+
+/// Metadata of a [Store] and its file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoreMeta {
+    /// Path of the store file
+    pub path: PathBuf,
+    /// [blake3] hash of the [Store] in memory
+    pub hash_mem: String,
+    /// sha256 hash of the store file
+    pub hash_file: String,
+    /// [Version](crate::store::Version) of the [Store] in memory
+    pub version_mem: u8,
+    /// [Version](crate::store::Version) of the store file
+    pub version_file: u8,
+    /// Size of the [Store] in memory, in bytes
+    pub size_mem: usize,
+    /// Size of the store file, in bytes
+    pub size_file: u64,
+    /// When the [Store] was created, as unix timestamp, see [Store::created]
+    pub created: i64,
+    /// When the store file was last saved, as unix timestamp, see [Store::last_saved]
+    pub last_saved: Option<i64>,
+    /// Version of netpulse that last saved the store file, see [Store::written_by]
+    pub written_by: String,
+    /// Random ID of the [Store], see [Store::instance_id]
+    pub instance_id: uuid::Uuid,
+}
+
+impl Report {
+    /// Analyzes the [Store] and collects all results, with the default [Config].
+    ///
+    /// # Errors
+    ///
+    /// Returns [AnalysisError] if the store file can not be read for the [StoreMeta].
+    pub fn build(store: &Store) -> Result<Self, AnalysisError> {
+        Self::build_with(store, &Config::default())
+    }
+
+    /// Like [build](Self::build), but measures the [SlaStats] against the SLA of `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [AnalysisError] if the store file can not be read for the [StoreMeta].
+    pub fn build_with(store: &Store, config: &Config) -> Result<Self, AnalysisError> {
+        Ok(Self {
+            general: Stats::of_store(store, |_| true, |_| true),
+            time_uptime: Coverage::of_store(store).uptime(i64::MIN, i64::MAX),
+            failure_reasons: FailureReasons::of_checks(store.checks()),
+            http: Stats::of_check_type(store, CheckType::Http),
+            http_status_codes: StatusCodes::of_store(store),
+            quic: Stats::of_check_type(store, CheckType::Quic),
+            icmp: Stats::of_check_type(store, CheckType::Icmp),
+            loss: LossStats::of_checks(store.checks()),
+            dns: Stats::of_check_type(store, CheckType::Dns),
+            doh: Stats::of_check_type(store, CheckType::Doh),
+            dot: Stats::of_check_type(store, CheckType::Dot),
+            ntp: Stats::of_check_type(store, CheckType::Ntp),
+            ntp_servers: NtpStats::of_checks(store.checks()),
+            mtu: Stats::of_check_type(store, CheckType::Mtu),
+            mtu_targets: MtuStats::of_checks(store.checks()),
+            gateway: Stats::of_check_type(store, CheckType::Gateway),
+            bandwidth: Stats::of_check_type(store, CheckType::Bandwidth),
+            throughput: ThroughputStats::of_checks(store.checks()),
+            traces: TraceStats::of_traces(store.traces()),
+            ipv4: Stats::of_ip_type(store, IpType::V4),
+            ipv6: Stats::of_ip_type(store, IpType::V6),
+            targets: TargetStats::of_store(store),
+            interfaces: InterfaceStats::of_checks(store.checks()),
+            outages: OutageReport::of_checks(&store.checks().iter().collect::<Vec<_>>(), false),
+            gaps: Gap::of_store(store),
+            monthly: PeriodStats::of_store(store, Period::Monthly),
+            sla: SlaStats::of_store(store, &config.sla),
+            heatmap: Heatmap::of_checks(store.checks()),
+            store: StoreMeta::of_store(store)?,
+        })
+    }
+}
+
+impl Stats {
+    /// Calculates the [Stats] of `checks` and `aggregates` combined.
+    pub fn new(checks: &[&Check], aggregates: &[&Aggregate]) -> Self {
+        let aggregated: usize = aggregates.iter().map(|a| a.total() as usize).sum();
+        let aggregated_ok: usize = aggregates.iter().map(|a| a.successes() as usize).sum();
+        let total = checks.len() + aggregated;
+        let total_ok = checks.iter().filter(|c| c.is_success()).count() + aggregated_ok;
+        let ok_retried = checks
+            .iter()
+            .filter(|c| c.is_success() && c.is_retried())
+            .count();
+        let ok_degraded = checks.iter().filter(|c| c.is_degraded()).count();
+        let first = aggregates
+            .iter()
+            .map(|a| a.start())
+            .chain(checks.first().map(|c| c.timestamp()))
+            .min();
+        let last = checks
+            .last()
+            .map(|c| c.timestamp())
+            .or_else(|| aggregates.iter().map(|a| a.start()).max());
+
+        Self {
+            checks: total,
+            checks_ok: total_ok,
+            checks_ok_retried: ok_retried,
+            checks_ok_degraded: ok_degraded,
+            checks_bad: total - total_ok,
+            checks_aggregated: aggregated,
+            success_ratio: (total > 0).then(|| success_ratio(total, total_ok)),
+            first_check_at: first,
+            last_check_at: last,
+        }
+    }
+
+    /// Calculates the [Stats] of the [Checks](Check) and [Aggregates](Aggregate) of a [Store]
+    /// that match the given filters.
+    pub fn of_store(
+        store: &Store,
+        check_filter: impl Fn(&Check) -> bool,
+        aggregate_filter: impl Fn(&Aggregate) -> bool,
+    ) -> Self {
+        let checks: Vec<&Check> = store.checks().iter().filter(|c| check_filter(c)).collect();
+        let aggregates: Vec<&Aggregate> = store
+            .aggregates()
+            .iter()
+            .filter(|a| aggregate_filter(a))
+            .collect();
+        Self::new(&checks, &aggregates)
+    }
+
+    /// Calculates the [Stats] of all data of a [Store] of one [CheckType].
+    pub fn of_check_type(store: &Store, check_type: CheckType) -> Self {
+        Self::of_store(
+            store,
+            |c| c.calc_type().unwrap_or(CheckType::Unknown) == check_type,
+            |a| a.check_type() == check_type,
+        )
+    }
+
+    /// Calculates the [Stats] of all data of a [Store] that used one [IpType].
+    pub fn of_ip_type(store: &Store, ip_type: IpType) -> Self {
+        Self::of_store(
+            store,
+            |c| c.ip_type() == ip_type,
+            |a| a.ip_type() == ip_type,
+        )
+    }
+
+    /// Returns true if there is no data at all.
+    pub fn is_empty(&self) -> bool {
+        self.checks == 0
+    }
+}
+
+impl StatusCodes {
+    /// Counts the status codes of the HTTP [Checks](Check) of a [Store].
+    pub fn of_store(store: &Store) -> Self {
+        let mut codes = Self {
+            per_status: BTreeMap::new(),
+            no_response: 0,
+            not_recorded: 0,
+        };
+        for check in store
+            .checks()
+            .iter()
+            .filter(|c| c.calc_type().unwrap_or(CheckType::Unknown) == CheckType::Http)
+        {
+            match check.http_status() {
+                Some(status) => *codes.per_status.entry(status).or_default() += 1,
+                None if check.is_success() => codes.not_recorded += 1,
+                None => codes.no_response += 1,
+            }
+        }
+        codes
+    }
+
+    /// Returns the number of all counted HTTP [Checks](Check).
+    pub fn total(&self) -> usize {
+        self.per_status.values().sum::<usize>() + self.no_response + self.not_recorded
+    }
+}
+
+impl FailureReasons {
+    /// Counts the reasons of the failed `checks`.
+    pub fn of_checks(checks: &[Check]) -> Self {
+        let mut reasons = Self {
+            per_reason: BTreeMap::new(),
+            unknown: 0,
+        };
+        for check in checks.iter().filter(|c| !c.is_success()) {
+            match check.failure_reason().and_then(|flag| flag.reason_name()) {
+                Some(reason) => *reasons.per_reason.entry(reason).or_default() += 1,
+                None => reasons.unknown += 1,
+            }
+        }
+        reasons
+    }
+
+    /// Returns the number of all counted failed [Checks](Check).
+    pub fn total(&self) -> usize {
+        self.per_reason.values().sum::<usize>() + self.unknown
+    }
+}
+
+impl LossStats {
+    /// Sums up the [Measurement::Loss] of `checks` in total and per day.
+    pub fn of_checks(checks: &[Check]) -> Self {
+        // start of day -> (sent, received)
+        let mut per_day: BTreeMap<i64, (usize, usize)> = BTreeMap::new();
+        for check in checks {
+            let Some(Measurement::Loss { sent, received }) = check.measurement() else {
+                continue;
+            };
+            let entry = per_day.entry(start_of_day(check)).or_default();
+            entry.0 += sent as usize;
+            entry.1 += received as usize;
+        }
+
+        let sent = per_day.values().map(|d| d.0).sum();
+        let received = per_day.values().map(|d| d.1).sum();
+        Self {
+            sent,
+            received,
+            loss_ratio: (sent > 0).then(|| 1.0 - success_ratio(sent, received)),
+            per_day: per_day
+                .into_iter()
+                .map(|(day, (sent, received))| DailyLoss {
+                    day,
+                    sent,
+                    received,
+                    loss_ratio: 1.0 - success_ratio(sent, received),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns true if no echoes were sent at all.
+    pub fn is_empty(&self) -> bool {
+        self.sent == 0
+    }
+}
+
+impl MtuStats {
+    /// Collects the [Measurement::Mtu] of `checks` per target, sorted by address.
+    pub fn of_checks(checks: &[Check]) -> Vec<Self> {
+        let mut per_target: BTreeMap<IpAddr, Self> = BTreeMap::new();
+        for check in checks {
+            let Some(Measurement::Mtu { size }) = check.measurement() else {
+                continue;
+            };
+            let entry = per_target.entry(check.target()).or_insert(Self {
+                target: check.target(),
+                label: None,
+                measurements: 0,
+                smallest: size,
+                largest: size,
+                latest: size,
+            });
+            if check.label().is_some() {
+                entry.label = check.label().map(str::to_string);
+            }
+            entry.measurements += 1;
+            entry.smallest = entry.smallest.min(size);
+            entry.largest = entry.largest.max(size);
+            entry.latest = size;
+        }
+        per_target.into_values().collect()
+    }
+
+    /// Returns the label and address of the target, or just the address if there is no label.
+    pub fn name(&self) -> String {
+        match &self.label {
+            Some(label) => format!("{label} ({})", self.target),
+            None => self.target.to_string(),
+        }
+    }
+}
+
+impl TraceStats {
+    /// Counts the [Traces](Trace) per [Segment].
+    pub fn of_traces(traces: &[Trace]) -> Self {
+        let mut per_segment = BTreeMap::new();
+        for trace in traces {
+            *per_segment.entry(trace.segment()).or_default() += 1;
+        }
+        Self {
+            traces: traces.len(),
+            per_segment,
+            latest: traces.iter().max_by_key(|t| t.timestamp()).cloned(),
+        }
+    }
+}
+
+impl NtpStats {
+    /// Collects the [Measurement::Ntp] of `checks` per server, sorted by address.
+    pub fn of_checks(checks: &[Check]) -> Vec<Self> {
+        let mut per_target: BTreeMap<IpAddr, Self> = BTreeMap::new();
+        for check in checks {
+            let Some(Measurement::Ntp { offset }) = check.measurement() else {
+                continue;
+            };
+            let entry = per_target.entry(check.target()).or_insert(Self {
+                target: check.target(),
+                label: None,
+                measurements: 0,
+                latest: offset,
+                largest: offset,
+            });
+            if check.label().is_some() {
+                entry.label = check.label().map(str::to_string);
+            }
+            entry.measurements += 1;
+            entry.latest = offset;
+            if offset.unsigned_abs() > entry.largest.unsigned_abs() {
+                entry.largest = offset;
+            }
+        }
+        per_target.into_values().collect()
+    }
+
+    /// Returns the label and address of the server, or just the address if there is no label.
+    pub fn name(&self) -> String {
+        match &self.label {
+            Some(label) => format!("{label} ({})", self.target),
+            None => self.target.to_string(),
+        }
+    }
+}
+
+impl ThroughputStats {
+    /// Averages the [Measurement::Throughput] of `checks` in total and per day.
+    pub fn of_checks(checks: &[Check]) -> Self {
+        // start of day -> all samples of that day
+        let mut per_day: BTreeMap<i64, Vec<u32>> = BTreeMap::new();
+        let mut latest = None;
+        for check in checks {
+            let Some(Measurement::Throughput { bytes_per_second }) = check.measurement() else {
+                continue;
+            };
+            per_day
+                .entry(start_of_day(check))
+                .or_default()
+                .push(bytes_per_second);
+            latest = Some(bytes_per_second);
+        }
+
+        let all: Vec<u32> = per_day.values().flatten().copied().collect();
+        Self {
+            samples: all.len(),
+            average: average(&all),
+            latest,
+            per_day: per_day
+                .into_iter()
+                .map(|(day, samples)| DailyThroughput {
+                    day,
+                    samples: samples.len(),
+                    average: average(&samples).unwrap_or_default(),
+                    lowest: samples.iter().copied().min().unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns true if there are no samples at all.
+    pub fn is_empty(&self) -> bool {
+        self.samples == 0
+    }
+}
+
+impl TargetStats {
+    /// Calculates the [TargetStats] of every target in a [Store], sorted by address.
+    pub fn of_store(store: &Store) -> Vec<Self> {
+        // target -> (label, total, ok)
+        let mut per_target: BTreeMap<IpAddr, (Option<&str>, usize, usize)> = BTreeMap::new();
+        for check in store.checks() {
+            let entry = per_target.entry(check.target()).or_default();
+            if check.label().is_some() {
+                entry.0 = check.label();
+            }
+            entry.1 += 1;
+            entry.2 += check.is_success() as usize;
+        }
+        for aggregate in store.aggregates() {
+            let entry = per_target.entry(aggregate.target()).or_default();
+            entry.1 += aggregate.total() as usize;
+            entry.2 += aggregate.successes() as usize;
+        }
+
+        per_target
+            .into_iter()
+            .map(|(target, (label, total, ok))| Self {
+                target,
+                label: label.map(str::to_string),
+                checks: total,
+                checks_ok: ok,
+                success_ratio: success_ratio(total, ok),
+            })
+            .collect()
+    }
+
+    /// Returns the label and address of the target, or just the address if there is no label.
+    pub fn name(&self) -> String {
+        match &self.label {
+            Some(label) => format!("{label} ({})", self.target),
+            None => self.target.to_string(),
+        }
+    }
+}
+
+impl InterfaceStats {
+    /// Calculates the [InterfaceStats] of every interface that `checks` were bound to, sorted by
+    /// name.
+    pub fn of_checks(checks: &[Check]) -> Vec<Self> {
+        let mut per_interface: BTreeMap<&str, Vec<&Check>> = BTreeMap::new();
+        for check in checks {
+            if let Some(interface) = check.interface() {
+                per_interface.entry(interface).or_default().push(check);
+            }
+        }
+
+        per_interface
+            .into_iter()
+            .map(|(interface, checks)| {
+                let outages: Vec<OutageSummary> = fail_groups(&checks)
+                    .into_iter()
+                    .filter_map(|group| Outage::try_from(group).ok())
+                    .map(|outage| outage.to_summary())
+                    .collect();
+                Self {
+                    interface: interface.to_string(),
+                    stats: Stats::new(&checks, &[]),
+                    outages: outages.len(),
+                    downtime: outages.iter().map(|o| o.duration).sum(),
+                    longest_outage: outages.iter().map(|o| o.duration).max().unwrap_or(0),
+                    latest_outage: outages.last().cloned(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Period {
+    /// Returns the first day of the period that contains `day`.
+    pub fn start_of(&self, day: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Weekly => day - Days::new(day.weekday().num_days_from_monday() as u64),
+            Self::Monthly => day.with_day(1).expect("every month has a first day"),
+        }
+    }
+
+    /// Returns the first day of the period after the one that starts on `start`.
+    pub fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Weekly => start + Days::new(7),
+            Self::Monthly => start + Months::new(1),
+        }
+    }
+
+    /// Returns the name of the period that starts on `start`, like `2024-W45` or `2024-11`.
+    pub fn name(&self, start: NaiveDate) -> String {
+        match self {
+            Self::Weekly => {
+                let week = start.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Self::Monthly => start.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "weekly" => Ok(Self::Weekly),
+            "monthly" => Ok(Self::Monthly),
+            _ => Err(format!("Not a valid period: {s}, use weekly or monthly")),
+        }
+    }
+}
+
+impl PeriodStats {
+    /// Calculates the uptime of every [Period] of a [Store] that has data, oldest first.
+    pub fn of_store(store: &Store, period: Period) -> Vec<Self> {
+        Self::of_data(
+            store.checks(),
+            store.aggregates(),
+            store.period_seconds(),
+            period,
+        )
+    }
+
+    /// Calculates the uptime of every [Period] that `checks` or `aggregates` fall into, oldest
+    /// first. The checks were made every `check_period` seconds.
+    pub fn of_data(
+        checks: &[Check],
+        aggregates: &[Aggregate],
+        check_period: i64,
+        period: Period,
+    ) -> Vec<Self> {
+        let gaps = Gap::of_data(checks, aggregates, check_period);
+        let coverage = Coverage::of_data(checks, aggregates, check_period);
+        let start_of = |timestamp: i64| {
+            let day = Local
+                .timestamp_opt(timestamp, 0)
+                .earliest()
+                .map_or(NaiveDate::default(), |t| t.date_naive());
+            period.start_of(day)
+        };
+        let empty = |start: NaiveDate| {
+            let timestamp = |day: NaiveDate| {
+                day.and_hms_opt(0, 0, 0)
+                    .and_then(|d| d.and_local_timezone(Local).earliest())
+                    .map_or(0, |d| d.timestamp())
+            };
+            Self {
+                name: period.name(start),
+                start: timestamp(start),
+                end: timestamp(period.next(start)),
+                checks: 0,
+                checks_ok: 0,
+                uptime: 0.0,
+                outages: 0,
+                downtime: 0,
+                longest_outage: 0,
+                unmonitored: 0,
+                time_uptime: 0.0,
+            }
+        };
+
+        let mut per_period: BTreeMap<NaiveDate, Self> = BTreeMap::new();
+        for check in checks {
+            let start = start_of(check.timestamp());
+            let entry = per_period.entry(start).or_insert_with(|| empty(start));
+            entry.checks += 1;
+            entry.checks_ok += check.is_success() as usize;
+        }
+        for aggregate in aggregates {
+            let start = start_of(aggregate.start());
+            let entry = per_period.entry(start).or_insert_with(|| empty(start));
+            entry.checks += aggregate.total() as usize;
+            entry.checks_ok += aggregate.successes() as usize;
+        }
+        let checks: Vec<&Check> = checks.iter().collect();
+        for outage in fail_groups(&checks)
+            .into_iter()
+            .filter_map(|group| Outage::try_from(group).ok())
+        {
+            let summary = outage.to_summary();
+            let start = start_of(summary.start);
+            let entry = per_period.entry(start).or_insert_with(|| empty(start));
+            entry.outages += 1;
+            entry.downtime += summary.duration;
+            entry.longest_outage = entry.longest_outage.max(summary.duration);
+        }
+
+        per_period
+            .into_values()
+            .map(|mut stats| {
+                stats.uptime = success_ratio(stats.checks, stats.checks_ok);
+                stats.unmonitored = gaps
+                    .iter()
+                    .map(|gap| gap.overlap(stats.start, stats.end))
+                    .sum();
+                stats.time_uptime = coverage.uptime(stats.start, stats.end).unwrap_or(0.0);
+                stats
+            })
+            .collect()
+    }
+}
+
+impl PeriodStats {
+    /// Returns the uptime of the period measured by `basis`.
+    pub fn uptime_by(&self, basis: UptimeBasis) -> f64 {
+        match basis {
+            UptimeBasis::Checks => self.uptime,
+            UptimeBasis::Time => self.time_uptime,
+        }
+    }
+}
+
+impl Coverage {
+    /// Calculates the [Coverage] of the [Checks](Check) and [Aggregates](Aggregate) of a
+    /// [Store].
+    pub fn of_store(store: &Store) -> Self {
+        Self::of_data(store.checks(), store.aggregates(), store.period_seconds())
+    }
+
+    /// Calculates the [Coverage] of `checks` and `aggregates` that were made every
+    /// `check_period` seconds.
+    pub fn of_data(checks: &[Check], aggregates: &[Aggregate], check_period: i64) -> Self {
+        let mut sorted: Vec<&Check> = checks.iter().collect();
+        sorted.sort_by_key(|c| c.timestamp());
+        let rounds: Vec<&[&Check]> = sorted
+            .chunk_by(|a, b| a.timestamp() == b.timestamp())
+            .collect();
+        let mut spans: Vec<(i64, i64, f64)> = Vec::new();
+        for (idx, round) in rounds.iter().enumerate() {
+            let start = round[0].timestamp();
+            // a round stands for the time until the next one, but not more than its period
+            let covered = rounds
+                .get(idx + 1)
+                .map_or(check_period, |next| next[0].timestamp() - start)
+                .min(check_period);
+            let ok = round.iter().filter(|c| c.is_success()).count();
+            spans.push((
+                start,
+                covered,
+                covered as f64 * success_ratio(round.len(), ok),
+            ));
+        }
+
+        let mut hours: BTreeMap<i64, (usize, usize)> = BTreeMap::new();
+        for aggregate in aggregates {
+            let entry = hours.entry(aggregate.start()).or_default();
+            entry.0 += aggregate.total() as usize;
+            entry.1 += aggregate.successes() as usize;
+        }
+        for (start, (total, ok)) in hours {
+            let up = AGGREGATE_SPAN as f64 * success_ratio(total, ok);
+            spans.push((start, AGGREGATE_SPAN, up));
+        }
+        spans.sort_by_key(|span| span.0);
+        Self { spans }
+    }
+
+    /// Returns the ratio of the wall-clock time between `start` and `end` that was covered by
+    /// successful checks, [None] if no checks were made in it.
+    ///
+    /// Only the time from the first to the end of the last round of checks is counted, so the
+    /// time before netpulse was set up is not down.
+    pub fn uptime(&self, start: i64, end: i64) -> Option<f64> {
+        let first = self.spans.first()?.0;
+        let last = self.spans.iter().map(|(s, len, _)| s + len).max()?;
+        let mut inside = self
+            .spans
+            .iter()
+            .filter(|(s, _, _)| (start..end).contains(s))
+            .peekable();
+        inside.peek()?;
+        let up: f64 = inside.map(|(_, _, up)| up).sum();
+        let wall = end.min(last) - start.max(first);
+        (wall > 0).then(|| (up / wall as f64).clamp(0.0, 1.0))
+    }
+}
+
+impl SlaStats {
+    /// Calculates the error budget of the [Store] at this moment, [None] if `config` has no
+    /// uptime.
+    pub fn of_store(store: &Store, config: &SlaConfig) -> Option<Self> {
+        let target = config.uptime? / 100.0;
+        Some(Self::of_periods(
+            PeriodStats::of_store(store, config.period),
+            target,
+            config.period,
+            config.basis,
+            Local::now(),
+        ))
+    }
+
+    /// Calculates the error budget at `now`, with `periods` from [PeriodStats::of_store] and
+    /// `target` as a ratio.
+    pub fn of_periods(
+        periods: Vec<PeriodStats>,
+        target: f64,
+        period: Period,
+        basis: UptimeBasis,
+        now: DateTime<Local>,
+    ) -> Self {
+        let target = target.clamp(0.0, 1.0);
+        let start = period.start_of(now.date_naive());
+        let name = period.name(start);
+        let (mut missed, mut current) = (Vec::new(), None);
+        for stats in periods {
+            if stats.name == name {
+                current = Some(stats);
+            } else if stats.start < now.timestamp() && stats.uptime_by(basis) < target {
+                missed.push(stats);
+            }
+        }
+
+        let timestamp = |day: NaiveDate| {
+            day.and_hms_opt(0, 0, 0)
+                .and_then(|d| d.and_local_timezone(Local).earliest())
+                .map_or(0, |d| d.timestamp())
+        };
+        let (period_start, period_end) = (timestamp(start), timestamp(period.next(start)));
+        let budget = ((1.0 - target) * (period_end - period_start) as f64) as i64;
+        let used = current.as_ref().map_or(0, |stats| {
+            ((1.0 - stats.uptime_by(basis)) * (now.timestamp() - period_start) as f64) as i64
+        });
+
+        Self {
+            target,
+            period,
+            basis,
+            current,
+            budget,
+            used,
+            remaining: budget - used,
+            exceeded: used > budget,
+            missed,
+        }
+    }
+}
+
+impl Heatmap {
+    /// Counts the checks and failures of `checks` per day of the week and hour of the day.
+    pub fn of_checks(checks: &[Check]) -> Self {
+        let mut cells = vec![vec![HeatmapCell::default(); 24]; 7];
+        for check in checks {
+            let time = check.timestamp_parsed();
+            let cell =
+                &mut cells[time.weekday().num_days_from_monday() as usize][time.hour() as usize];
+            cell.checks += 1;
+            cell.failed += !check.is_success() as usize;
+        }
+        Self { cells }
+    }
+
+    /// Sums up the cells of every hour of the day over all days of the week.
+    pub fn per_hour(&self) -> Vec<HeatmapCell> {
+        (0..24)
+            .map(|hour| {
+                self.cells
+                    .iter()
+                    .map(|row| row[hour])
+                    .fold(HeatmapCell::default(), HeatmapCell::add)
+            })
+            .collect()
+    }
+
+    /// Sums up the cells of every day of the week over all hours of the day.
+    pub fn per_weekday(&self) -> Vec<HeatmapCell> {
+        self.cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .copied()
+                    .fold(HeatmapCell::default(), HeatmapCell::add)
+            })
+            .collect()
+    }
+
+    /// Returns true if there are no checks at all.
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().flatten().all(|c| c.checks == 0)
+    }
+}
+
+impl HeatmapCell {
+    /// Returns the ratio of failed checks, [None] if there are no checks.
+    pub fn failure_ratio(&self) -> Option<f64> {
+        (self.checks > 0).then(|| success_ratio(self.checks, self.failed))
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            checks: self.checks + other.checks,
+            failed: self.failed + other.failed,
+        }
+    }
+}
+
+impl Calendar {
+    /// Collects the uptime of every day of the last `months` calendar months of a [Store],
+    /// including the current one.
+    pub fn of_store(store: &Store, months: u32) -> Self {
+        Self::of_data(
+            store.checks(),
+            store.aggregates(),
+            months,
+            Local::now().date_naive(),
+        )
+    }
+
+    /// Collects the uptime of every day of the last `months` calendar months before and
+    /// including the one of `today`, up to `today`.
+    pub fn of_data(
+        checks: &[Check],
+        aggregates: &[Aggregate],
+        months: u32,
+        today: NaiveDate,
+    ) -> Self {
+        let first = Period::Monthly.start_of(today) - Months::new(months.saturating_sub(1));
+        let day_of = |timestamp: i64| {
+            Local
+                .timestamp_opt(timestamp, 0)
+                .earliest()
+                .map(|t| t.date_naive())
+        };
+        let mut days: BTreeMap<NaiveDate, CalendarDay> = first
+            .iter_days()
+            .take_while(|day| *day <= today)
+            .map(|day| {
+                let start = day
+                    .and_hms_opt(0, 0, 0)
+                    .and_then(|d| d.and_local_timezone(Local).earliest())
+                    .map_or(0, |d| d.timestamp());
+                let cell = CalendarDay {
+                    date: day.format("%Y-%m-%d").to_string(),
+                    start,
+                    checks: 0,
+                    checks_ok: 0,
+                };
+                (day, cell)
+            })
+            .collect();
+        let counts = checks
+            .iter()
+            .map(|c| (c.timestamp(), 1, c.is_success() as usize))
+            .chain(
+                aggregates
+                    .iter()
+                    .map(|a| (a.start(), a.total() as usize, a.successes() as usize)),
+            );
+        for (timestamp, all, ok) in counts {
+            if let Some(day) = day_of(timestamp).and_then(|d| days.get_mut(&d)) {
+                day.checks += all;
+                day.checks_ok += ok;
+            }
+        }
+        Self {
+            days: days.into_values().collect(),
+        }
+    }
+
+    /// Returns true if no day has any checks.
+    pub fn is_empty(&self) -> bool {
+        self.days.iter().all(|d| d.checks == 0)
+    }
+}
+
+impl CalendarDay {
+    /// Returns the ratio of successful checks, [None] if there are no checks.
+    pub fn uptime(&self) -> Option<f64> {
+        (self.checks > 0).then(|| success_ratio(self.checks, self.checks_ok))
+    }
+
+    /// Returns the ratio of failed checks, [None] if there are no checks.
+    pub fn failure_ratio(&self) -> Option<f64> {
+        (self.checks > 0).then(|| success_ratio(self.checks, self.checks - self.checks_ok))
+    }
+
+    /// Returns the day as date.
+    pub fn day(&self) -> NaiveDate {
+        NaiveDate::parse_from_str(&self.date, "%Y-%m-%d").expect("calendar day is not a date")
+    }
+}
+
+impl OutageReport {
+    /// Summarizes an [Outage].
+    ///
+    /// If `dump` is true, all [Checks](Check) of the [Outage] are included.
+    pub fn new(outage: &Outage, dump: bool) -> Self {
+        Self {
+            summary: outage.to_summary(),
+            contained: dump.then(|| outage.all().iter().map(|c| (*c).into()).collect()),
+        }
+    }
+
+    /// Finds all outages in `checks` and summarizes them, sorted by time.
+    ///
+    /// If `dump` is true, all [Checks](Check) of each [Outage] are included.
+    pub fn of_checks(checks: &[&Check], dump: bool) -> Vec<Self> {
+        fail_groups(checks)
+            .into_iter()
+            .filter_map(|group| Outage::try_from(group).ok())
+            .map(|outage| Self::new(&outage, dump))
+            .collect()
+    }
+}
+
+impl StoreMeta {
+    /// Collects the metadata of a [Store] and its file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [AnalysisError] if the store file can not be read.
+    pub fn of_store(store: &Store) -> Result<Self, AnalysisError> {
+        let path = store.file_path();
+        Ok(Self {
+            size_file: std::fs::metadata(&path)?.len(),
+            hash_mem: store.get_hash().to_string(),
+            hash_file: store.get_hash_of_file()?,
+            version_mem: store.version().raw(),
+            version_file: Store::peek_file_version_of(&path)?.raw(),
+            size_mem: store.deep_size_of(),
+            created: store.created(),
+            last_saved: store.last_saved(),
+            written_by: store.written_by().to_string(),
+            instance_id: store.instance_id(),
+            path,
+        })
+    }
+}
+
+/// Returns the unix timestamp of the start of the local day the [Check] was made on.
+fn start_of_day(check: &Check) -> i64 {
+    check
+        .timestamp_parsed()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|d| d.and_local_timezone(Local).earliest())
+        .map_or(check.timestamp(), |d| d.timestamp())
+}
+
+/// Returns the average of `values`, [None] if there are none.
+fn average(values: &[u32]) -> Option<u32> {
+    (!values.is_empty())
+        .then(|| (values.iter().map(|v| *v as u64).sum::<u64>() / values.len() as u64) as u32)
+}
@@ -1,73 +1,150 @@
 //! Core daemon process that runs network checks at regular intervals.
 //!
-//! The daemon:
+//! The check loop itself lives in the library, see [Daemon]. This is the process around it:
 //! - Loads or creates a [Store]
-//! - Runs checks every [period_seconds](netpulse::store::Store::period_seconds)
+//! - Drops root privileges to the netpulse user, but keeps CAP_NET_RAW for ICMP checks, see
+//!   [drop_privileges]
 //! - Handles graceful shutdown on SIGTERM
-//! - Maintains PID file at [DAEMON_PID_FILE]
+//! - Maintains PID file at [paths::pid_file](netpulse::paths::pid_file)
+//! - Opens the [control socket](netpulse::control) at
+//!   [paths::control_socket](netpulse::paths::control_socket)
+//! - Tells systemd when it is ready and when it stops, see [systemd]
 //!
 //! # Signal Handling
 //!
 //! The daemon handles the following signals:
 //! - SIGTERM: Graceful shutdown, saves state and removes PID file
+//! - SIGINT: The same as SIGTERM, but only in the foreground (`netpulsed --foreground`)
+//! - SIGUSR1: Saves the store right away and logs a summary of the in-memory state
 //!
 //! # Cleanup
 //!
 //! On shutdown, the daemon:
 //! 1. Saves the current store state
-//! 2. Removes its PID file
+//! 2. Removes its PID file and control socket
 //! 3. Logs any cleanup errors
 
 use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 
+use netpulse::common::{drop_privileges, getpid_running};
+use netpulse::config::Config;
+use netpulse::daemon::{remove_control_socket, single_round, tell_systemd, Daemon};
 use netpulse::errors::RunError;
-use netpulse::records::display_group;
-use netpulse::DAEMON_PID_FILE;
 use nix::sys::signal::{self, SigHandler, Signal};
 
 use netpulse::store::Store;
-use tracing::{error, info};
+use netpulse::systemd;
+use netpulse::DAEMON_USER;
+use tracing::{error, info, warn};
 
 use crate::USES_DAEMON_SYSTEM;
 
 static TERMINATE: AtomicBool = AtomicBool::new(false);
 static RESTART: AtomicBool = AtomicBool::new(false);
+static FLUSH: AtomicBool = AtomicBool::new(false);
 
 /// Main daemon process function.
 ///
 /// This function:
-/// 1. Sets up signal handlers
-/// 2. Loads/creates the store
-/// 3. Enters main check loop
-/// 4. Handles graceful shutdown
+/// 1. Sets up signal handlers and drops root privileges, keeping CAP_NET_RAW
+/// 2. Loads/creates the store and the [Daemon] for it
+/// 3. Opens the control socket
+/// 4. Enters main check loop, see [Daemon::tick]
+/// 5. Handles graceful shutdown
+///
+/// In the `foreground`, the daemon keeps the privileges it was started with and also terminates
+/// gracefully on SIGINT, so it can be stopped with Ctrl+C.
 // TODO: better error handling, keep going even if everything goes boom
-pub(crate) fn daemon() {
-    signal_hook();
+pub(crate) fn daemon(foreground: bool) {
+    signal_hook(foreground);
     info!("starting daemon...");
-    let mut store = load_store();
+    if nix::unistd::getuid().is_root() && !netpulse::paths::user_mode() && !foreground {
+        drop_root();
+    }
+    if !netpulse::checks::has_cap_net_raw() && !netpulse::paths::user_mode() {
+        if foreground {
+            warn!("no CAP_NET_RAW, ICMP, MTU and gateway checks will fail");
+        } else {
+            error!("the daemon does not have CAP_NET_RAW, ICMP, MTU and gateway checks will fail, run the setup again");
+        }
+    }
+    let mut daemon = Daemon::new(load_store());
+    if let Err(e) = daemon.open_control_socket() {
+        error!("could not open the control socket, continuing without it: {e}");
+    }
     info!("store loaded, entering main loop");
+    tell_systemd(systemd::ready());
+    if let Some(timeout) = systemd::watchdog_timeout() {
+        let period = daemon.store().period_seconds();
+        if timeout.as_secs() as i64 <= period {
+            warn!(
+                "the systemd watchdog times out after {}s, which is not longer than the period of {}s, the daemon will be restarted",
+                timeout.as_secs(),
+                period
+            );
+        }
+    }
     loop {
         if TERMINATE.load(std::sync::atomic::Ordering::Relaxed) {
             info!("terminating the daemon");
-            if let Err(e) = cleanup(&store) {
+            tell_systemd(systemd::stopping());
+            let result = daemon.shutdown().and_then(|()| cleanup_without_store());
+            if let Err(e) = result {
                 error!("could not clean up before terminating: {e:#?}");
             }
             std::process::exit(1);
         }
+        if FLUSH.swap(false, std::sync::atomic::Ordering::Relaxed) {
+            daemon.flush();
+        }
         if RESTART.load(std::sync::atomic::Ordering::Relaxed) {
             info!("restarting the daemon");
-            store = load_store();
+            daemon.reload(load_store());
         }
-        if chrono::Utc::now().timestamp() % store.period_seconds() == 0 {
-            if let Err(err) = wakeup(&mut store) {
-                error!("error in the wakeup turn: {err}");
-            }
+        // wake up regularly anyway, to react to signals and the control socket
+        let wait = daemon.tick();
+        std::thread::sleep(wait.clamp(Duration::from_millis(100), Duration::from_secs(1)));
+    }
+}
+
+/// Makes one round of checks, adds them to the store and exits, for running netpulse from cron or
+/// a systemd timer (`netpulsed --oneshot`), see [single_round].
+///
+/// Exits with status 1 if a daemon is running already, as it would overwrite the store, or if the
+/// store could not be saved.
+pub(crate) fn oneshot() -> ! {
+    if let Some(pid) = getpid_running() {
+        error!("netpulsed is running already (pid {pid}), it makes the checks itself");
+        std::process::exit(1)
+    }
+    if nix::unistd::getuid().is_root() && !netpulse::paths::user_mode() {
+        drop_root();
+    }
+    let mut store = load_store();
+    match single_round(&mut store, &Config::load_or_default()) {
+        Ok(checks) => {
+            info!(
+                "added {} checks to {}",
+                checks.len(),
+                store.file_path().display()
+            );
+            std::process::exit(0)
+        }
+        Err(e) => {
+            error!("could not save the store: {e}");
+            std::process::exit(1)
         }
-        std::thread::sleep(std::time::Duration::from_secs(1));
     }
 }
 
 fn load_store() -> Store {
+    // in user mode, there is no setup that creates the directory of the store beforehand
+    if netpulse::paths::user_mode() {
+        if let Err(e) = Store::setup() {
+            error!("could not create the directory of the store: {e}");
+        }
+    }
     match Store::load_or_create() {
         Err(e) => {
             error!("{e}");
@@ -76,66 +153,65 @@ fn load_store() -> Store {
             }
             std::process::exit(1)
         }
-        Ok(s) => s,
-    }
-}
-
-/// Run a check iteration and update store.
-///
-/// Called periodically by the daemon main loop to:
-/// - Run configured checks
-/// - Save results to store
-/// - Handle any check errors
-///
-/// # Errors
-///
-/// Returns [RunError] if store operations fail.
-fn wakeup(store: &mut Store) -> Result<(), RunError> {
-    info!("waking up!");
-
-    let mut buf = String::new();
-    display_group(&store.make_checks(), &mut buf)?;
-    info!("Made checks\n{buf}");
-
-    if let Err(err) = store.save() {
-        error!("error while saving to file: {err:}");
+        Ok(mut s) => {
+            // checks that are added again must not count twice
+            s.set_reject_duplicates(true);
+            s
+        }
     }
-
-    info!("done!");
-    Ok(())
 }
 
-fn signal_hook() {
+fn signal_hook(foreground: bool) {
     unsafe {
         signal::signal(Signal::SIGTERM, SigHandler::Handler(handle_signal))
             .expect("failed to set up signal handler");
+        signal::signal(Signal::SIGUSR1, SigHandler::Handler(handle_signal))
+            .expect("failed to set up signal handler");
+        if foreground {
+            signal::signal(Signal::SIGINT, SigHandler::Handler(handle_signal))
+                .expect("failed to set up signal handler");
+        }
     }
 }
 
-/// Clean up daemon resources on shutdown.
-///
-/// Performs:
-/// - Final store save
-/// - PID file removal
+/// Drops the root privileges to [DAEMON_USER], see [drop_privileges].
 ///
-/// # Errors
-///
-/// Returns [RunError] if cleanup operations fail.
-fn cleanup(store: &Store) -> Result<(), RunError> {
-    if let Err(err) = store.save() {
-        error!("error while saving to file: {err:#?}");
-        return Err(err.into());
+/// The directories of the store and the control socket are handed to [DAEMON_USER] first, so the
+/// daemon can still write to them afterwards. Exits if the privileges can not be dropped, the
+/// daemon should not keep running as root.
+fn drop_root() {
+    let result = Store::setup()
+        .map_err(RunError::from)
+        .and_then(|()| chown_runtime_dir())
+        .and_then(|()| drop_privileges(DAEMON_USER));
+    match result {
+        Ok(()) => info!("dropped privileges to the {DAEMON_USER} user"),
+        Err(e) => {
+            error!("could not drop privileges to the {DAEMON_USER} user: {e}");
+            std::process::exit(1)
+        }
     }
+}
 
-    cleanup_without_store()?;
-
+/// Creates the directory of the [control socket](netpulse::paths::control_socket) and hands it to
+/// [DAEMON_USER].
+fn chown_runtime_dir() -> Result<(), RunError> {
+    let path = netpulse::paths::control_socket();
+    let Some(parent) = path.parent() else {
+        return Ok(());
+    };
+    let user = nix::unistd::User::from_name(DAEMON_USER)
+        .map_err(std::io::Error::from)?
+        .ok_or_else(|| RunError::NoSuchUser(DAEMON_USER.to_string()))?;
+    std::fs::create_dir_all(parent)?;
+    std::os::unix::fs::chown(parent, Some(user.uid.into()), Some(user.gid.into()))?;
     Ok(())
 }
 
 fn cleanup_without_store() -> Result<(), RunError> {
     // stuff we only need to do if it's a manual daemon
     if USES_DAEMON_SYSTEM.load(std::sync::atomic::Ordering::Relaxed) {
-        if let Err(err) = std::fs::remove_file(DAEMON_PID_FILE) {
+        if let Err(err) = std::fs::remove_file(netpulse::paths::pid_file()) {
             if matches!(err.kind(), std::io::ErrorKind::NotFound) {
                 // yeah, idk, ignore?
             } else {
@@ -145,6 +221,9 @@ fn cleanup_without_store() -> Result<(), RunError> {
         }
     }
 
+    // the process exits right after this, so the control socket is never dropped
+    remove_control_socket();
+
     Ok(())
 }
 
@@ -161,6 +240,9 @@ extern "C" fn handle_signal(signal: i32) {
         Signal::SIGHUP => {
             RESTART.store(true, std::sync::atomic::Ordering::Relaxed);
         }
+        Signal::SIGUSR1 => {
+            FLUSH.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
         _ => {
             // the default behavior is terminating
             TERMINATE.store(true, std::sync::atomic::Ordering::Relaxed);
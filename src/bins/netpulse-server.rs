@@ -0,0 +1,98 @@
+//! Central server collecting the checks of many netpulse probes.
+//!
+//! Daemons push their checks to this server, see [push](netpulse::push), which keeps a store per
+//! probe and serves the analysis of all of them over HTTP, see [server](netpulse::server).
+//!
+//! # Usage
+//!
+//! Use the `--help` flag for more information about the usage. The token that clients must send
+//! is read from the [ENV_SERVER_TOKEN] environment variable, so it does not show up in the
+//! process list.
+
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use getopts::Options;
+use netpulse::analyze::i18n::Language;
+use netpulse::common::{init_logging, print_usage, setup_panic_handler};
+use netpulse::config::Config;
+use netpulse::errors::RunError;
+use netpulse::server::{Server, DEFAULT_DIR, DEFAULT_LISTEN, ENV_SERVER_TOKEN};
+use tracing::{error, info, warn};
+
+fn main() {
+    setup_panic_handler();
+    init_logging(tracing::Level::INFO);
+    let args: Vec<String> = std::env::args().collect();
+    let program = &args[0];
+    let mut opts = Options::new();
+    opts.optflag("h", "help", "print this help menu");
+    opts.optflag("V", "version", "print the version");
+    opts.optopt(
+        "l",
+        "listen",
+        &format!("address to listen on, {DEFAULT_LISTEN} by default"),
+        "ADDR:PORT",
+    );
+    opts.optopt(
+        "d",
+        "dir",
+        &format!("directory of the stores of the probes, {DEFAULT_DIR} by default"),
+        "DIR",
+    );
+    let matches = match opts.parse(&args[1..]) {
+        Ok(m) if !m.free.is_empty() => {
+            eprintln!("Unexpected argument: {}", m.free.join(" "));
+            print_usage(program, opts);
+        }
+        Ok(m) => m,
+        Err(f) => {
+            eprintln!("{f}");
+            print_usage(program, opts);
+        }
+    };
+
+    if matches.opt_present("help") {
+        print_usage(program, opts);
+    }
+    if matches.opt_present("version") {
+        println!("{} {}", env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION"));
+        std::process::exit(0)
+    }
+
+    let listen = matches
+        .opt_str("listen")
+        .unwrap_or_else(|| DEFAULT_LISTEN.to_string());
+    let dir = PathBuf::from(
+        matches
+            .opt_str("dir")
+            .unwrap_or_else(|| DEFAULT_DIR.to_string()),
+    );
+    if let Err(e) = serve(&listen, dir) {
+        error!("{e}");
+        std::process::exit(1)
+    }
+}
+
+fn serve(listen: &str, dir: PathBuf) -> Result<(), RunError> {
+    let token = std::env::var(ENV_SERVER_TOKEN)
+        .ok()
+        .filter(|t| !t.is_empty());
+    if token.is_none() {
+        warn!("{ENV_SERVER_TOKEN} is not set, anyone who can reach the server can push checks and read the reports");
+    }
+    let mut config = Config::load_or_default();
+    if let Some(language) = Language::from_env() {
+        config.language = language;
+    }
+    let server = Arc::new(Server::open(&dir, token, config)?);
+    let listener = TcpListener::bind(listen)?;
+    info!(
+        "listening on {listen} with {} probes in {}",
+        server.probes().len(),
+        dir.display()
+    );
+    server.serve(listener);
+    Ok(())
+}
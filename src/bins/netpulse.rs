@@ -3,7 +3,24 @@
 //! This binary provides commands to:
 //! - View analysis reports of collected check data
 //! - Run test checks against configured targets
+//! - Migrate the store file to the current store version
+//! - Repair a damaged store file
+//! - Import the checks of another store file
+//! - Remove duplicate and malformed entries from the store file
+//! - Draw the severity of outages over time (with the `graph` feature)
+//! - Draw the failures by day of the week and hour of the day (with the `graph` feature)
+//! - Summarize the uptime per calendar week or month
+//! - Show the uptime of every day of the last months as a calendar
+//! - List every outage, for example to page through them
+//! - Tell whether the latest checks are healthy with the exit code, for scripts
+//! - Diagnose the environment, like the store, capabilities, DNS and the daemon
+//! - Print all results as JSON for other tools
+//! - Print all checks in the InfluxDB line protocol, for example for Grafana
+//! - Export the checks, aggregates and traces to share them, anonymized if wanted
+//! - Show a live dashboard in the terminal (with the `tui` feature)
+//! - Serve the checks as a Grafana datasource (with the `grafana` feature)
 //! - Display version information
+//! - Generate completion scripts for bash, zsh and fish
 //!
 //! # Usage
 //!
@@ -11,36 +28,92 @@
 //!
 //! Use the `--help` flag for more information about the usage.
 
-use getopts::Options;
-use netpulse::analyze::{self, outages_detailed};
-use netpulse::common::{init_logging, print_usage, setup_panic_handler};
-use netpulse::errors::RunError;
-use netpulse::records::{display_group, Check};
-use netpulse::store::Store;
-use tracing::error;
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use netpulse::analyze::i18n::Language;
+use netpulse::analyze::report::{
+    Calendar, CheckReport, OutageReport, Period, PeriodStats, Report, StoreExport,
+};
+use netpulse::analyze::{self, outages_detailed, CheckAccessConstraints};
+use netpulse::checks::bind::parse_scoped;
+use netpulse::common::completions::{self, OptDef, Shell};
+use netpulse::common::{confirm, getpid_running, init_logging, print_usage, setup_panic_handler};
+use netpulse::config::Config;
+use netpulse::doctor::{diagnose, worst, Status};
+use netpulse::errors::{AnalysisError, RunError, StoreError};
+use netpulse::paths::ENV_USER_MODE;
+use netpulse::records::anonymize::Anonymizer;
+use netpulse::records::{display_group, influx, Check, IpType};
+use netpulse::store::{Store, Version};
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+#[cfg(feature = "tui")]
+mod tui;
+
+/// Exit code of `--status` if the [Health](analyze::Health) of the latest checks is not known
+const STATUS_UNKNOWN: i32 = 3;
+
+/// The command line options, see [completions::options]
+const OPTIONS: &[OptDef] = &[
+    OptDef::flag("h", "help", "print this help menu"),
+    OptDef::flag("V", "version", "print the version"),
+    OptDef::flag("t", "test", "test run all checks"),
+    OptDef::flag("o", "outages", "print out all outages, use --dump to show all contained"),
+    OptDef::flag("", "all-outages", "print a line for every outage, latest first, for example to page through them"),
+    OptDef::flag("", "status", "print whether the latest checks are healthy, degraded or in an outage and exit with 0, 1 or 2, or 3 if unknown"),
+    OptDef::flag("", "doctor", "check the store, CAP_NET_RAW, DNS, outbound HTTP and ICMP and the daemon, exit with 1 if anything fails"),
+    OptDef::flag("q", "quiet", "with --status, print nothing and only set the exit code"),
+    OptDef::opt("", "limit", "show this many of the latest and of the most severe outages in the analysis, 10 by default", "N"),
+    OptDef::flag("d", "dump", "print out all checks"),
+    OptDef::flag("", "influx", "print out all checks in the InfluxDB line protocol, for example for Grafana"),
+    OptDef::opt("", "export", "print the checks, aggregates and traces of the store to share them, with --anonymize before sharing them publicly", "json|influx"),
+    OptDef::flag_opt("", "anonymize", "with --export, replace addresses and the probe by pseudonyms and strip labels and interfaces, the same SECRET always gives the same pseudonyms", "SECRET"),
+    OptDef::flag("r", "rewrite", "load store and immediately save to rewrite the file"),
+    OptDef::flag("f", "failed", "only consider failed checks for dumping"),
+    OptDef::flag("m", "migrate", "back up the store file and migrate it to the current store version"),
+    OptDef::flag("", "repair", "back up a damaged store file and recover as many checks as possible from it"),
+    OptDef::flag("", "compact", "remove duplicate and malformed entries from the store and sort it, with --dry-run only report them"),
+    OptDef::opt("", "import", "import the checks of another store file into the store, skipping duplicates", "STORE"),
+    #[cfg(feature = "sqlite")]
+    OptDef::opt("", "export-sqlite", "copy the checks of the store into a SQLite database, creating it if needed", "FILE"),
+    #[cfg(feature = "grafana")]
+    OptDef::opt("", "grafana", "serve the checks of the store as a Grafana datasource, uses the first --store if given", "ADDR:PORT"),
+    OptDef::multi("s", "store", "read this store file instead of the default one, can be given multiple times to compare hosts", "[LABEL=]STORE"),
+    OptDef::opt("", "since", "only consider checks made since this date, like 2024-11-09 or \"2024-11-09 13:00\"", "DATE"),
+    OptDef::opt("", "until", "only consider checks made before this date, in the same formats as --since", "DATE"),
+    OptDef::opt("", "between", "only consider checks made from START until before END, like --since START --until END, so a single day D needs END = D+1", "START END"),
+    OptDef::opt("", "summary", "summarize the uptime, outages and downtime per calendar week or month", "weekly|monthly"),
+    OptDef::flag_opt("", "calendar", "show the uptime of every day of the last MONTHS calendar months, 3 by default", "MONTHS"),
+    OptDef::flag("j", "json", "print the results of the analysis, --dump, --outages, --summary, --calendar, --doctor or --test as JSON"),
+    OptDef::opt("", "min-outage-len", "leave out outages that span fewer rounds of checks, like 2 to ignore single failed rounds", "ROUNDS"),
+    OptDef::opt("", "min-severity", "leave out outages less severe than this, like partial:50 to ignore outages where less than half of the checks failed", "SEVERITY"),
+    OptDef::opt("", "target", "only consider checks of this target, given by its address or its label in the config", "IP|NAME"),
+    OptDef::opt("", "type", "only consider checks of this type", "http|icmp|dns|doh|dot|ntp|mtu|gateway|bandwidth"),
+    OptDef::opt("", "interface", "only consider checks bound to this interface or source address", "NAME"),
+    OptDef::flag("4", "ipv4", "only consider checks that used IPv4"),
+    OptDef::flag("6", "ipv6", "only consider checks that used IPv6"),
+    #[cfg(feature = "graph")]
+    OptDef::opt("g", "graph", "draw the severity of outages over time to FILE, as SVG if it ends with .svg, otherwise as PNG", "FILE"),
+    #[cfg(feature = "graph")]
+    OptDef::opt("", "heatmap", "draw the failures by day of the week and hour of the day to FILE, as SVG if it ends with .svg, otherwise as PNG", "FILE"),
+    #[cfg(feature = "tui")]
+    OptDef::flag("", "tui", "show a dashboard of the store that refreshes periodically, uses the first --store if given"),
+    OptDef::flag("", "dry-run", "with --migrate or --compact, only show what would be done without changing anything"),
+    OptDef::flag("", "user", "use the store and config of the user mode daemon in the XDG directories"),
+    OptDef::opt("", "completions", "print a completion script for the shell, like netpulse --completions bash > /usr/share/bash-completion/completions/netpulse", "bash|zsh|fish"),
+];
 
 fn main() {
     setup_panic_handler();
     init_logging(tracing::Level::INFO);
     let args: Vec<String> = std::env::args().collect();
     let program = &args[0];
-    let mut opts = Options::new();
+    let opts = completions::options(OPTIONS);
     let mut failed_only = false;
-    opts.optflag("h", "help", "print this help menu");
-    opts.optflag("V", "version", "print the version");
-    opts.optflag("t", "test", "test run all checks");
-    opts.optflag(
-        "o",
-        "outages",
-        "print out all outages, use --dump to show all contained",
-    );
-    opts.optflag("d", "dump", "print out all checks");
-    opts.optflag(
-        "r",
-        "rewrite",
-        "load store and immediately save to rewrite the file",
-    );
-    opts.optflag("f", "failed", "only consider failed checks for dumping");
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
         Err(f) => {
@@ -52,24 +125,177 @@ fn main() {
     if matches.opt_present("help") {
         print_usage(program, opts);
     }
+    if matches.opt_present("user") {
+        std::env::set_var(ENV_USER_MODE, "1");
+    }
     if matches.opt_present("failed") {
         failed_only = true;
     }
     if matches.opt_present("version") {
         print_version()
     }
+    if let Some(raw) = matches.opt_str("completions") {
+        match raw.parse::<Shell>() {
+            Ok(shell) => {
+                print!(
+                    "{}",
+                    completions::generate(env!("CARGO_BIN_NAME"), OPTIONS, shell)
+                );
+                std::process::exit(0)
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                print_usage(program, opts);
+            }
+        }
+    }
+    let constraints = match constraints(&matches) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("{e}");
+            print_usage(program, opts);
+        }
+    };
+    let stores = matches.opt_strs("store");
+    let json = matches.opt_present("json");
+    let outage_limit = match matches.opt_str("limit").map(|raw| raw.parse::<usize>()) {
+        None => Some(analyze::OUTAGE_LIMIT),
+        Some(Ok(limit)) => Some(limit),
+        Some(Err(e)) => {
+            eprintln!("Not a valid limit: {e}");
+            print_usage(program, opts);
+        }
+    };
+    #[cfg(feature = "graph")]
+    if let Some(file) = matches.opt_str("graph") {
+        if let Err(e) = graph(&stores, &constraints, Path::new(&file)) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+        std::process::exit(0)
+    }
+    #[cfg(feature = "graph")]
+    if let Some(file) = matches.opt_str("heatmap") {
+        if let Err(e) = heatmap(&stores, &constraints, Path::new(&file)) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+        std::process::exit(0)
+    }
+    #[cfg(feature = "sqlite")]
+    if let Some(db) = matches.opt_str("export-sqlite") {
+        if let Err(e) = export_sqlite(Path::new(&db)) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+        std::process::exit(0)
+    }
+    #[cfg(feature = "grafana")]
+    if let Some(listen) = matches.opt_str("grafana") {
+        let path = stores.first().map_or_else(Store::path, |arg| {
+            PathBuf::from(arg.split_once('=').map_or(arg.as_str(), |(_, p)| p))
+        });
+        if let Err(e) = grafana(&listen, &path, constraints) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+        std::process::exit(0)
+    }
+    #[cfg(feature = "tui")]
+    if matches.opt_present("tui") {
+        let path = stores
+            .first()
+            .map(|arg| PathBuf::from(arg.split_once('=').map_or(arg.as_str(), |(_, p)| p)));
+        if let Err(e) = tui::tui(path, constraints) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+        std::process::exit(0)
+    }
+    if matches.opt_present("status") {
+        status(&stores, &constraints, matches.opt_present("quiet"));
+    }
+    if matches.opt_present("doctor") {
+        match doctor(json) {
+            Ok(Status::Fail) => std::process::exit(1),
+            Ok(_) => std::process::exit(0),
+            Err(e) => {
+                error!("{e}");
+                std::process::exit(1)
+            }
+        }
+    }
     if matches.opt_present("outages") {
-        if let Err(e) = print_outages(None, matches.opt_present("dump")) {
+        if let Err(e) = print_outages(
+            &stores,
+            &constraints,
+            None,
+            matches.opt_present("dump"),
+            json,
+        ) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+    } else if matches.opt_present("all-outages") {
+        if let Err(e) = all_outages(&stores, &constraints) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+    } else if let Some(raw) = matches.opt_str("summary") {
+        let period: Period = match raw.parse() {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("{e}");
+                print_usage(program, opts);
+            }
+        };
+        if let Err(e) = summary(&stores, &constraints, period, json) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+    } else if matches.opt_present("calendar") {
+        let months = match matches.opt_str("calendar").map(|raw| raw.parse::<u32>()) {
+            None => analyze::CALENDAR_MONTHS,
+            Some(Ok(months)) if months > 0 => months,
+            Some(_) => {
+                eprintln!("Not a valid number of months, use 1 or more");
+                print_usage(program, opts);
+            }
+        };
+        if let Err(e) = calendar(&stores, &constraints, months, json) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+    } else if let Some(format) = matches.opt_str("export") {
+        let json = match format.as_str() {
+            "json" => true,
+            "influx" => false,
+            other => {
+                eprintln!("Unknown export format {other}, use json or influx");
+                print_usage(program, opts);
+            }
+        };
+        let anonymizer = matches.opt_present("anonymize").then(|| {
+            matches
+                .opt_str("anonymize")
+                .map_or_else(Anonymizer::new, |secret| Anonymizer::with_secret(&secret))
+        });
+        if let Err(e) = export(&stores, &constraints, json, anonymizer, failed_only) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+    } else if matches.opt_present("influx") {
+        if let Err(e) = influx_lines(&stores, &constraints, failed_only) {
             error!("{e}");
             std::process::exit(1)
         }
     } else if matches.opt_present("dump") {
-        if let Err(e) = dump(failed_only) {
+        if let Err(e) = dump(&stores, &constraints, failed_only, json) {
             error!("{e}");
             std::process::exit(1)
         }
     } else if matches.opt_present("test") {
-        if let Err(e) = test_checks() {
+        if let Err(e) = test_checks(json) {
             error!("{e}");
             std::process::exit(1)
         }
@@ -78,63 +304,667 @@ fn main() {
             error!("{e}");
             std::process::exit(1)
         }
-    } else if let Err(e) = analysis() {
+    } else if matches.opt_present("migrate") {
+        if let Err(e) = migrate(matches.opt_present("dry-run")) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+    } else if matches.opt_present("repair") {
+        if let Err(e) = repair() {
+            error!("{e}");
+            std::process::exit(1)
+        }
+    } else if matches.opt_present("compact") {
+        if let Err(e) = compact(matches.opt_present("dry-run")) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+    } else if let Some(other) = matches.opt_str("import") {
+        if let Err(e) = import(Path::new(&other)) {
+            error!("{e}");
+            std::process::exit(1)
+        }
+    } else if let Err(e) = analysis(&stores, &constraints, outage_limit, json) {
         error!("{e}");
         std::process::exit(1)
     }
 }
 
-fn test_checks() -> Result<(), RunError> {
+fn test_checks(json: bool) -> Result<(), RunError> {
     let mut checks = Vec::new();
     let mut buf = String::new();
-    Store::primitive_make_checks(&mut checks);
+    Store::primitive_make_checks(&mut checks, &Config::load_or_default());
+    if json {
+        let reports: Vec<CheckReport> = checks.iter().map(CheckReport::from).collect();
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+        return Ok(());
+    }
     let hack_checks: Vec<&Check> = checks.iter().collect();
     display_group(&hack_checks, &mut buf)?;
     println!("{buf}");
     Ok(())
 }
 
-fn print_outages(latest: Option<usize>, dump: bool) -> Result<(), RunError> {
-    let store = Store::load(true)?;
-    let mut buf = String::new();
-    let ref_checks: Vec<&Check> = if let Some(limit) = latest {
-        store.checks().iter().rev().take(limit).collect()
+/// Loads the stores given with `--store`, labeled by host.
+///
+/// Each argument is either `LABEL=PATH` or just `PATH`, in which case the path is the label.
+/// Without any `--store` arguments, only the default store is loaded.
+fn load_stores(
+    args: &[String],
+    constraints: &CheckAccessConstraints,
+) -> Result<Vec<(String, Store)>, RunError> {
+    if args.is_empty() {
+        let store = constraints.load_from(&Store::path())?;
+        return Ok(vec![(
+            store.file_path().to_string_lossy().to_string(),
+            store,
+        )]);
+    }
+    args.iter()
+        .map(|arg| {
+            let (label, path) = arg.split_once('=').unwrap_or((arg, arg));
+            Ok((label.to_string(), constraints.load_from(Path::new(path))?))
+        })
+        .collect()
+}
+
+/// Builds the [CheckAccessConstraints] from the `--since`, `--until`, `--between`,
+/// `--min-outage-len`, `--min-severity`, `--target`, `--type`, `--interface`, `--ipv4` and
+/// `--ipv6` flags.
+///
+/// The END of `--between START END` is the only free argument, any other one is an error.
+/// END is exclusive, like the date of `--until`.
+fn constraints(matches: &getopts::Matches) -> Result<CheckAccessConstraints, String> {
+    let mut constraints = CheckAccessConstraints::default();
+    let date = |raw: &str| parse_date(raw).ok_or_else(|| format!("Not a valid date: {raw}"));
+    if let Some(raw) = matches.opt_str("since") {
+        constraints.since_date = Some(date(&raw)?);
+    }
+    if let Some(raw) = matches.opt_str("until") {
+        constraints.until_date = Some(date(&raw)?);
+    }
+    if let Some(start) = matches.opt_str("between") {
+        if matches.opt_present("since") || matches.opt_present("until") {
+            return Err("--between can not be combined with --since or --until".to_string());
+        }
+        let [end] = &matches.free[..] else {
+            return Err("--between needs a START and an END date".to_string());
+        };
+        constraints.since_date = Some(date(&start)?);
+        constraints.until_date = Some(date(end)?);
+    } else if !matches.free.is_empty() {
+        return Err(format!("Unexpected argument: {}", matches.free.join(" ")));
+    }
+    if let (Some(since), Some(until)) = (constraints.since_date, constraints.until_date) {
+        if since >= until {
+            return Err(
+                "The start of the time range must be before its end, which is exclusive"
+                    .to_string(),
+            );
+        }
+    }
+    if let Some(raw) = matches.opt_str("min-outage-len") {
+        constraints.min_outage_len = Some(
+            raw.parse()
+                .map_err(|_| format!("Not a valid number of rounds: {raw}"))?,
+        );
+    }
+    if let Some(raw) = matches.opt_str("min-severity") {
+        constraints.min_severity = Some(raw.parse()?);
+    }
+    if let Some(raw) = matches.opt_str("target") {
+        let target = parse_scoped(&raw)
+            .ok()
+            .map(|(address, _zone)| address)
+            .or_else(|| Config::load_or_default().address_of(&raw))
+            .ok_or_else(|| format!("Not an address or the label of a target: {raw}"))?;
+        constraints.target = Some(target);
+    }
+    if let Some(raw) = matches.opt_str("type") {
+        constraints.check_type = Some(raw.parse()?);
+    }
+    constraints.interface = matches.opt_str("interface");
+    match (matches.opt_present("ipv4"), matches.opt_present("ipv6")) {
+        (true, true) => return Err("--ipv4 and --ipv6 are mutually exclusive".to_string()),
+        (true, false) => constraints.ip_type = Some(IpType::V4),
+        (false, true) => constraints.ip_type = Some(IpType::V6),
+        (false, false) => (),
+    }
+    Ok(constraints)
+}
+
+/// Parses a date in the local timezone, with or without time, or an RFC 3339 timestamp.
+fn parse_date(raw: &str) -> Option<DateTime<Local>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(raw) {
+        return Some(t.with_timezone(&Local));
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(raw, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+/// Prints `make(store)` for every store as JSON.
+///
+/// With a single store, just its value is printed. With multiple stores, the values are printed
+/// as an object keyed by the labels of the stores.
+fn print_json<T: Serialize>(
+    stores: &[(String, Store)],
+    make: impl Fn(&Store) -> Result<T, RunError>,
+) -> Result<(), RunError> {
+    let out = if let [(_, store)] = stores {
+        serde_json::to_string_pretty(&make(store)?)?
     } else {
-        store.checks().iter().collect()
+        let per_host = stores
+            .iter()
+            .map(|(label, store)| Ok((label.as_str(), make(store)?)))
+            .collect::<Result<BTreeMap<&str, T>, RunError>>()?;
+        serde_json::to_string_pretty(&per_host)?
     };
-    if let Err(e) = outages_detailed(&ref_checks, &mut buf, dump) {
+    println!("{out}");
+    Ok(())
+}
+
+#[cfg(feature = "graph")]
+fn graph(
+    stores: &[String],
+    constraints: &CheckAccessConstraints,
+    file: &Path,
+) -> Result<(), RunError> {
+    let stores = load_stores(stores, constraints)?;
+    let checks: Vec<&Check> = stores
+        .iter()
+        .flat_map(|(_, store)| store.checks().iter())
+        .collect();
+    let is_svg = file
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"));
+    let weights = Config::load_or_default().severity;
+    let result = if is_svg {
+        analyze::graph::draw_checks_svg(&checks, file, &weights)
+    } else {
+        analyze::graph::draw_checks(&checks, file, &weights)
+    };
+    if let Err(e) = result {
         eprintln!("{e}");
         std::process::exit(1);
     }
-    println!("{buf}");
+    info!("Graph written to {}", file.display());
     Ok(())
 }
 
-fn dump(failed_only: bool) -> Result<(), RunError> {
-    let store = Store::load(true)?;
-    let mut buf = String::new();
-    let ref_checks: Vec<&Check> = if failed_only {
-        store.checks().iter().filter(|c| !c.is_success()).collect()
-    } else {
-        store.checks().iter().collect()
-    };
-    if let Err(e) = display_group(&ref_checks, &mut buf) {
+#[cfg(feature = "graph")]
+fn heatmap(
+    stores: &[String],
+    constraints: &CheckAccessConstraints,
+    file: &Path,
+) -> Result<(), RunError> {
+    let stores = load_stores(stores, constraints)?;
+    let checks: Vec<Check> = stores
+        .iter()
+        .flat_map(|(_, store)| store.checks().iter().cloned())
+        .collect();
+    if let Err(e) = analyze::graph::draw_heatmap(&checks, file) {
         eprintln!("{e}");
         std::process::exit(1);
     }
-    println!("{buf}");
+    info!("Heatmap written to {}", file.display());
     Ok(())
 }
 
+fn print_outages(
+    stores: &[String],
+    constraints: &CheckAccessConstraints,
+    latest: Option<usize>,
+    dump: bool,
+    json: bool,
+) -> Result<(), RunError> {
+    let stores = load_stores(stores, constraints)?;
+    fn select(store: &Store, latest: Option<usize>) -> Vec<&Check> {
+        if let Some(limit) = latest {
+            store.checks().iter().rev().take(limit).collect()
+        } else {
+            store.checks().iter().collect()
+        }
+    }
+    if json {
+        return print_json(&stores, |store| {
+            Ok(OutageReport::of_checks(&select(store, latest), dump))
+        });
+    }
+    let language = load_config().language;
+    for (label, store) in &stores {
+        let mut buf = String::new();
+        let ref_checks = select(store, latest);
+        if let Err(e) = outages_detailed(&ref_checks, &mut buf, dump, language) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        if stores.len() > 1 {
+            println!("==> {label} <==");
+        }
+        println!("{buf}");
+    }
+    Ok(())
+}
+
+/// Prints the [Health](analyze::Health) of the latest round of checks and exits with its
+/// [exit code](analyze::Health::exit_code).
+///
+/// With several stores, the worst health of their latest rounds counts. Exits with
+/// [STATUS_UNKNOWN] if no store can be loaded or none has any checks.
+fn status(stores: &[String], constraints: &CheckAccessConstraints, quiet: bool) -> ! {
+    let health = match load_stores(stores, constraints) {
+        Ok(stores) => stores
+            .iter()
+            .filter_map(|(_, store)| analyze::latest_health(store.checks()))
+            .max(),
+        Err(e) => {
+            if !quiet {
+                eprintln!("{e}");
+            }
+            std::process::exit(STATUS_UNKNOWN)
+        }
+    };
+    match health {
+        Some(health) => {
+            if !quiet {
+                println!("{health}");
+            }
+            std::process::exit(health.exit_code())
+        }
+        None => {
+            if !quiet {
+                println!("unknown");
+            }
+            std::process::exit(STATUS_UNKNOWN)
+        }
+    }
+}
+
+/// Prints the [findings](netpulse::doctor::Finding) of the diagnostics and returns the worst
+/// [Status].
+fn doctor(json: bool) -> Result<Status, RunError> {
+    let findings = diagnose();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&findings)?);
+    } else {
+        for finding in &findings {
+            println!("{finding}");
+        }
+    }
+    Ok(worst(&findings))
+}
+
+/// Prints a line for every outage to stdout.
+///
+/// A closed stdout, like from quitting a pager early, is not an error.
+fn all_outages(stores: &[String], constraints: &CheckAccessConstraints) -> Result<(), RunError> {
+    let stores = load_stores(stores, constraints)?;
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    for (label, store) in &stores {
+        let result = if stores.len() > 1 {
+            writeln!(out, "==> {label} <==")
+                .map_err(AnalysisError::from)
+                .and_then(|_| analyze::write_all_outages(store, &mut out))
+        } else {
+            analyze::write_all_outages(store, &mut out)
+        };
+        match result.and_then(|_| out.flush().map_err(AnalysisError::from)) {
+            Err(AnalysisError::Io { source }) if source.kind() == ErrorKind::BrokenPipe => {
+                return Ok(())
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+            Ok(()) => (),
+        }
+    }
+    Ok(())
+}
+
+fn summary(
+    stores: &[String],
+    constraints: &CheckAccessConstraints,
+    period: Period,
+    json: bool,
+) -> Result<(), RunError> {
+    let stores = load_stores(stores, constraints)?;
+    if json {
+        return print_json(&stores, |store| Ok(PeriodStats::of_store(store, period)));
+    }
+    let language = load_config().language;
+    for (label, store) in &stores {
+        if stores.len() > 1 {
+            println!("==> {label} <==");
+        }
+        println!("{}", analyze::period_summary(store, period, language)?);
+    }
+    Ok(())
+}
+
+fn calendar(
+    stores: &[String],
+    constraints: &CheckAccessConstraints,
+    months: u32,
+    json: bool,
+) -> Result<(), RunError> {
+    let stores = load_stores(stores, constraints)?;
+    if json {
+        return print_json(&stores, |store| Ok(Calendar::of_store(store, months)));
+    }
+    let language = load_config().language;
+    for (label, store) in &stores {
+        if stores.len() > 1 {
+            println!("==> {label} <==");
+        }
+        println!("{}", analyze::calendar(store, months, language)?);
+    }
+    Ok(())
+}
+
+fn dump(
+    stores: &[String],
+    constraints: &CheckAccessConstraints,
+    failed_only: bool,
+    json: bool,
+) -> Result<(), RunError> {
+    let stores = load_stores(stores, constraints)?;
+    fn select(store: &Store, failed_only: bool) -> Vec<&Check> {
+        if failed_only {
+            store.checks().iter().filter(|c| !c.is_success()).collect()
+        } else {
+            store.checks().iter().collect()
+        }
+    }
+    if json {
+        return print_json(&stores, |store| {
+            Ok(select(store, failed_only)
+                .into_iter()
+                .map(CheckReport::from)
+                .collect::<Vec<_>>())
+        });
+    }
+    for (label, store) in &stores {
+        let mut buf = String::new();
+        let ref_checks = select(store, failed_only);
+        if let Err(e) = display_group(&ref_checks, &mut buf) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        if stores.len() > 1 {
+            println!("==> {label} <==");
+        }
+        println!("{buf}");
+    }
+    Ok(())
+}
+
+/// Prints the data of all stores, anonymized by `anonymizer` if one is given.
+///
+/// As `json`, this is a [StoreExport] per store, like with [print_json]. Otherwise only the
+/// checks are printed in the InfluxDB line protocol, like with [influx_lines].
+fn export(
+    stores: &[String],
+    constraints: &CheckAccessConstraints,
+    json: bool,
+    anonymizer: Option<Anonymizer>,
+    failed_only: bool,
+) -> Result<(), RunError> {
+    let mut stores = load_stores(stores, constraints)?;
+    if let Some(anonymizer) = anonymizer {
+        for (label, store) in stores.iter_mut() {
+            store.anonymize(&anonymizer);
+            *label = anonymizer.name("store", label);
+        }
+    }
+    if json {
+        print_json(&stores, |store| {
+            Ok(StoreExport::of_store(store, failed_only))
+        })
+    } else {
+        write_influx(&stores, failed_only)
+    }
+}
+
+/// Prints the checks of all stores in the InfluxDB line protocol to stdout.
+///
+/// A closed stdout, like from piping into `head`, is not an error.
+fn influx_lines(
+    stores: &[String],
+    constraints: &CheckAccessConstraints,
+    failed_only: bool,
+) -> Result<(), RunError> {
+    write_influx(&load_stores(stores, constraints)?, failed_only)
+}
+
+/// Writes the checks of the loaded `stores` in the InfluxDB line protocol to stdout, see
+/// [influx_lines].
+fn write_influx(stores: &[(String, Store)], failed_only: bool) -> Result<(), RunError> {
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    let checks = stores
+        .iter()
+        .flat_map(|(_, store)| store.checks())
+        .filter(|c| !failed_only || !c.is_success());
+    match influx::write_lines(&mut out, checks).and_then(|_| out.flush()) {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
 fn rewrite() -> Result<(), RunError> {
     let s = Store::load(true)?;
     s.save()?;
     Ok(())
 }
 
-fn analysis() -> Result<(), RunError> {
+fn migrate(dry_run: bool) -> Result<(), RunError> {
+    let path = Store::path();
+    info!(
+        "Checking the version of the store file at {}",
+        path.display()
+    );
+    let version = Store::peek_file_version()?;
+    info!(
+        "Store file has version {version}, the current version is {}",
+        Version::CURRENT
+    );
+    if version == Version::CURRENT {
+        info!("The store is already at the current version, nothing to migrate");
+        return Ok(());
+    }
+    if dry_run {
+        info!(
+            "Would back up the store file to {}",
+            backup_path(&format!("v{version}")).display()
+        );
+    } else {
+        backup_store_file(&format!("v{version}"))?;
+    }
+
+    info!("Loading and migrating the store");
+    let store = Store::load(dry_run)?;
+    info!(
+        "Migrated {} checks and {} aggregates to version {}",
+        store.checks().len(),
+        store.aggregates().len(),
+        store.version()
+    );
+
+    if dry_run {
+        info!("Dry run, not saving the migrated store");
+    } else {
+        info!("Saving the migrated store");
+        store.save()?;
+        info!("Migration done");
+    }
+    Ok(())
+}
+
+fn repair() -> Result<(), RunError> {
+    match Store::load(false) {
+        Ok(_) => {
+            info!("The store loads without problems, nothing to repair");
+            return Ok(());
+        }
+        Err(StoreError::DoesNotExist) => return Err(StoreError::DoesNotExist.into()),
+        Err(e) => warn!("The store can not be loaded normally: {e}"),
+    }
+
+    backup_store_file("damaged")?;
+
+    info!("Recovering what is left of the store");
+    let store = Store::load_damaged()?;
+    info!(
+        "Recovered {} checks and {} aggregates",
+        store.checks().len(),
+        store.aggregates().len()
+    );
+
+    info!("Saving the repaired store");
+    store.save()?;
+    info!("Repair done");
+    Ok(())
+}
+
+fn compact(dry_run: bool) -> Result<(), RunError> {
+    if !dry_run
+        && getpid_running().is_some()
+        && !confirm(
+            "netpulsed is running and might overwrite the compacted store, continue anyway?",
+        )
+    {
+        return Ok(());
+    }
+    let mut store = Store::load(dry_run)?;
+    let report = store.compact();
+    info!("Duplicate checks:     {}", report.duplicate_checks);
+    info!("Checks of no type:    {}", report.unknown_checks);
+    info!("Duplicate aggregates: {}", report.duplicate_aggregates);
+    info!("Duplicate traces:     {}", report.duplicate_traces);
+    if report.total() == 0 {
+        info!("Nothing to remove, the store is already compact");
+    }
+    if dry_run {
+        info!("Dry run, the store was not changed");
+        return Ok(());
+    }
+    backup_store_file("compact")?;
+    store.save()?;
+    info!(
+        "Removed {} entries, the store now has {} checks",
+        report.total(),
+        store.checks().len()
+    );
+    Ok(())
+}
+
+fn import(other: &Path) -> Result<(), RunError> {
+    if getpid_running().is_some()
+        && !confirm(
+            "netpulsed is running and might overwrite the imported checks, continue anyway?",
+        )
+    {
+        return Ok(());
+    }
+    let mut store = Store::load(false)?;
+    let imported = store.merge_from(other)?;
+    if imported.total() == 0 {
+        info!("Nothing new to import");
+        return Ok(());
+    }
+    store.save()?;
+    info!(
+        "Imported {} checks and {} aggregates, the store now has {} checks and {} aggregates",
+        imported.checks,
+        imported.aggregates,
+        store.checks().len(),
+        store.aggregates().len()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn export_sqlite(db: &Path) -> Result<(), RunError> {
     let store = Store::load(true)?;
-    match analyze::analyze(&store) {
+    let mut sqlite = netpulse::store::sqlite::SqliteStore::open(db)?;
+    let exported = sqlite.import_store(&store)?;
+    info!(
+        "Exported {exported} new checks, {} now has {} checks",
+        db.display(),
+        sqlite.len()?
+    );
+    Ok(())
+}
+
+#[cfg(feature = "grafana")]
+fn grafana(listen: &str, path: &Path, constraints: CheckAccessConstraints) -> Result<(), RunError> {
+    use netpulse::server::grafana::Datasource;
+    use netpulse::server::ENV_SERVER_TOKEN;
+
+    let token = std::env::var(ENV_SERVER_TOKEN)
+        .ok()
+        .filter(|t| !t.is_empty());
+    if token.is_none() {
+        warn!("{ENV_SERVER_TOKEN} is not set, anyone who can reach the datasource can read the checks");
+    }
+    // fail early if the store can not be read at all
+    constraints.load_from(path)?;
+    let listener = std::net::TcpListener::bind(listen)?;
+    info!(
+        "serving {} as Grafana datasource on {listen}",
+        path.display()
+    );
+    std::sync::Arc::new(Datasource::new(path, constraints, token)).serve(listener);
+    Ok(())
+}
+
+/// Loads the [Config], with the [Language] of the analysis taken from
+/// [ENV_LANGUAGE](netpulse::analyze::i18n::ENV_LANGUAGE) if it is set.
+fn load_config() -> Config {
+    let mut config = Config::load_or_default();
+    if let Some(language) = Language::from_env() {
+        config.language = language;
+    }
+    config
+}
+
+fn backup_path(suffix: &str) -> PathBuf {
+    let mut backup = Store::path().into_os_string();
+    backup.push(format!(".{suffix}.bak"));
+    PathBuf::from(backup)
+}
+
+fn backup_store_file(suffix: &str) -> Result<PathBuf, RunError> {
+    let backup = backup_path(suffix);
+    info!("Backing up the store file to {}", backup.display());
+    std::fs::copy(Store::path(), &backup)?;
+    Ok(backup)
+}
+
+fn analysis(
+    stores: &[String],
+    constraints: &CheckAccessConstraints,
+    outage_limit: Option<usize>,
+    json: bool,
+) -> Result<(), RunError> {
+    let stores = load_stores(stores, constraints)?;
+    let config = load_config();
+    if json {
+        return print_json(&stores, |store| Ok(Report::build_with(store, &config)?));
+    }
+    let report = if let [(_, store)] = &stores[..] {
+        analyze::analyze_with_limit(store, outage_limit, &config)
+    } else {
+        analyze::analyze_hosts_with_limit(&stores, outage_limit, &config)
+    };
+    match report {
         Err(e) => {
             eprintln!("Error while making the analysis: {e}");
             std::process::exit(1);
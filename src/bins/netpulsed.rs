@@ -4,6 +4,7 @@
 //! - Starting the daemon with proper privileges
 //! - Stopping running daemon instances
 //! - Checking daemon status
+//! - Making the daemon check right away
 //!
 //! # Usage
 //!
@@ -12,50 +13,95 @@
 //! # Privileges
 //!
 //! The daemon requires root to start but drops privileges to run as the netpulse user.
-//! Note that ICMP checks require `CAP_NET_RAW` capability which is lost on privilege drop.
+//! It keeps the `CAP_NET_RAW` capability that ICMP checks require, see
+//! [drop_privileges](netpulse::common::drop_privileges). Under systemd and OpenRC, the service
+//! starts the daemon as the netpulse user right away and grants it the capability.
+//!
+//! With `--foreground`, netpulsed runs the daemon in the terminal instead, as the invoking user
+//! and with colored logs, see [LogBackend::Pretty](netpulse::common::LogBackend::Pretty). It
+//! works without root if the store and the other [paths](netpulse::paths) are writable, like in
+//! user mode.
+//!
+//! With `--oneshot`, netpulsed makes a single round of checks, adds them to the store and exits.
+//! Cron or a systemd timer can then run it instead of the daemon.
+//!
+//! With `--user`, netpulsed runs in [user mode](netpulse::paths::user_mode) instead: as the invoking
+//! user, with the store, control socket and pid file in the XDG directories of that user.
 //!
 //! # Files
 //!
-//! - PID file: `/var/run/netpulse/netpulsed.pid`
-//! - Info log: `/var/log/netpulse/info.log`
-//! - Error log: `/var/log/netpulse/error.log`
+//! - PID file: `/run/netpulse/netpulse.pid`
+//! - Control socket: `/run/netpulse/netpulsed.sock`
+//! - Info log: `/var/log/netpulse.log`
+//! - Error log: `/var/log/netpulse.err`
+//!
+//! All of them can be moved, see [paths](netpulse::paths).
+//!
+//! # Platforms
+//!
+//! The daemon and everything that controls it only work on unix. On other systems, this binary
+//! just exits with an error.
 
+#[cfg(unix)]
 use std::fs;
-use std::io::Write;
-use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
-use std::process::Command;
+#[cfg(unix)]
 use std::sync::atomic::AtomicBool;
 
+#[cfg(unix)]
+use chrono::{Local, TimeZone};
+#[cfg(unix)]
 use getopts::Options;
+#[cfg(unix)]
+use netpulse::analyze::fmt_timestamp;
+#[cfg(unix)]
 use netpulse::common::{
-    confirm, exec_cmd_for_user, getpid_running, init_logging, print_usage, root_guard,
-    setup_panic_handler,
+    getpid_running, init_logging, print_usage, root_guard, setup_panic_handler, ENV_LOG_BACKEND,
 };
+#[cfg(unix)]
+use netpulse::control::{self, Request, Response};
+#[cfg(unix)]
 use netpulse::errors::RunError;
+#[cfg(unix)]
+use netpulse::paths::ENV_USER_MODE;
+#[cfg(unix)]
+use netpulse::records::{display_group, Check};
+#[cfg(unix)]
 use netpulse::store::Store;
-use netpulse::{DAEMON_PID_FILE, DAEMON_USER};
+#[cfg(unix)]
 use nix::errno::Errno;
+#[cfg(unix)]
 use nix::sys::signal::{self, Signal};
+#[cfg(unix)]
 use nix::unistd::Pid;
-use sysinfo::System;
-use tracing::{debug, error, info, trace};
+#[cfg(unix)]
+use tracing::error;
 
+#[cfg(unix)]
 mod daemon;
-use daemon::daemon;
-
-const SERVICE_FILE: &str = include_str!("../../data/netpulsed.service");
-const SYSTEMD_SERVICE_PATH: &str = "/etc/systemd/system/netpulsed.service";
+#[cfg(unix)]
+use daemon::{daemon, oneshot};
+#[cfg(unix)]
+mod setup;
 
+#[cfg(unix)]
 /// Whether the executable is being executed as a daemon by a framework like systemd
 ///
 /// `true` => yes, something like systemd is taking care of things like stdout and pidfile
 /// `false` => no, we're doing it all manually
 static USES_DAEMON_SYSTEM: AtomicBool = AtomicBool::new(false);
 
+#[cfg(not(unix))]
+fn main() {
+    eprintln!(
+        "{} only runs on unix, use netpulse to read a store on this system",
+        env!("CARGO_BIN_NAME")
+    );
+    std::process::exit(1)
+}
+
+#[cfg(unix)]
 fn main() -> Result<(), RunError> {
     setup_panic_handler();
-    init_logging(tracing::Level::INFO);
     let args: Vec<String> = std::env::args().collect();
     let program = &args[0];
     let mut opts = Options::new();
@@ -64,16 +110,46 @@ fn main() -> Result<(), RunError> {
     opts.optflag(
         "u",
         "setup",
-        "setup the directories and so on needed for netpulse, including a systemd service (netpulsed.service)",
+        "setup the directories and so on needed for netpulse, including a service for the init system",
+    );
+    opts.optopt(
+        "",
+        "init",
+        "with --setup, the init system to install the service for instead of the detected one",
+        "systemd|openrc|runit|launchd",
     );
     opts.optflag(
         "d",
         "daemon",
-        "run directly as the daemon, do not setup a pidfile or drop privileges, for use when using a daemonizing system like systemd",
+        "run directly as the daemon, do not setup a pidfile, for use when using a daemonizing system like systemd. If started as root, drops privileges to the netpulse user but keeps CAP_NET_RAW",
+    );
+    opts.optflag(
+        "f",
+        "foreground",
+        "run the daemon in this terminal as the invoking user, with colored logs, stop it with Ctrl+C. For developing and debugging checks",
+    );
+    opts.optflag(
+        "",
+        "oneshot",
+        "make one round of checks, add them to the store and exit, for running from cron or a systemd timer instead of the daemon",
     );
     opts.optflag("i", "info", "info about the running netpulse daemon");
+    opts.optflag(
+        "",
+        "check-now",
+        "tell the running netpulse daemon to make a round of checks right away and show them",
+    );
     opts.optflag("e", "end", "stop the running netpulse daemon");
+    opts.optflag(
+        "",
+        "user",
+        "user mode: run as the invoking user with the files in the XDG directories, no root needed",
+    );
     let matches = match opts.parse(&args[1..]) {
+        Ok(m) if !m.free.is_empty() => {
+            eprintln!("Unexpected argument: {}", m.free.join(" "));
+            print_usage(program, opts);
+        }
         Ok(m) => m,
         Err(f) => {
             eprintln!("{f}");
@@ -81,16 +157,40 @@ fn main() -> Result<(), RunError> {
         }
     };
 
+    if matches.opt_present("user") {
+        std::env::set_var(ENV_USER_MODE, "1");
+    }
+    if matches.opt_present("foreground") && std::env::var_os(ENV_LOG_BACKEND).is_none() {
+        std::env::set_var(ENV_LOG_BACKEND, "pretty");
+    }
+    init_logging(tracing::Level::INFO);
+
     if matches.opt_present("help") {
         print_usage(program, opts);
     } else if matches.opt_present("version") {
         print_version()
     } else if matches.opt_present("info") {
         infod();
+    } else if matches.opt_present("check-now") {
+        check_now();
     } else if matches.opt_present("setup") {
+        if netpulse::paths::user_mode() {
+            eprintln!("The setup installs netpulsed for the whole system, in user mode just run `netpulsed --user --daemon`");
+            std::process::exit(1)
+        }
         root_guard();
-        if let Err(e) = setup_systemd(false) {
-            error!("While making the systemd setup: {e}");
+        let init = match matches.opt_str("init") {
+            Some(name) => setup::by_name(&name).unwrap_or_else(|| {
+                eprintln!("Unknown init system: {name}");
+                print_usage(program, opts);
+            }),
+            None => setup::detect().unwrap_or_else(|| {
+                error!("Could not detect the init system, choose one with --init");
+                std::process::exit(1)
+            }),
+        };
+        if let Err(e) = setup::setup_service(init.as_ref(), false) {
+            error!("While making the {} setup: {e}", init.name());
             std::process::exit(1)
         }
         if let Err(e) = Store::setup() {
@@ -101,132 +201,113 @@ fn main() -> Result<(), RunError> {
         endd();
     } else if matches.opt_present("daemon") {
         USES_DAEMON_SYSTEM.store(true, std::sync::atomic::Ordering::Release);
-        daemon();
+        daemon(false);
+    } else if matches.opt_present("foreground") {
+        daemon(true);
+    } else if matches.opt_present("oneshot") {
+        oneshot();
     } else {
         print_usage(program, opts);
     }
     Ok(())
 }
 
-fn setup_general(skip_checks: bool) -> Result<(), RunError> {
-    debug!("starting general setup");
-    if !skip_checks && !confirm("Perform general daemon setup?") {
-        debug!("general setup skipped");
-        return Ok(());
+#[cfg(unix)]
+fn infod() {
+    match getpid_running() {
+        Some(pid) => {
+            println!("netpulsed is running with pid {pid}")
+        }
+        None => {
+            println!("netpulsed is not running");
+            return;
+        }
     }
-
-    // create netpulse user if it does not exist
-    if !nix::unistd::User::from_name(DAEMON_USER).is_ok_and(|o| o.is_some()) {
-        if skip_checks || confirm("create netpulse user?") {
-            trace!("trying to create a new user with useradd");
-            exec_cmd_for_user(
-                Command::new("useradd")
-                    .arg("--system")
-                    .arg("--shell")
-                    .arg("/sbin/nologin")
-                    .arg(DAEMON_USER),
-                skip_checks,
+    match control::query(&Request::Status) {
+        Ok(Response::Status(status)) => {
+            let fmt_time = |t: i64| fmt_timestamp(Local.timestamp_opt(t, 0).unwrap());
+            let uptime = status.uptime();
+            println!(
+                "uptime: {}h {:02}m {:02}s (since {})",
+                uptime / 3600,
+                uptime % 3600 / 60,
+                uptime % 60,
+                fmt_time(status.started_at)
             );
-        } else {
-            info!("user {DAEMON_USER} exists")
+            println!(
+                "last wakeup: {}",
+                status
+                    .last_wakeup
+                    .map(fmt_time)
+                    .unwrap_or_else(|| "never".to_string())
+            );
+            println!("checks made this session: {}", status.checks_made);
+            println!(
+                "failed saves this session: {} ({} checks not saved yet)",
+                status.save_errors, status.pending_checks
+            );
+            println!("store: {}", status.store_path.display());
         }
+        Ok(other) => eprintln!("unexpected answer from the daemon: {other:?}"),
+        Err(e) => eprintln!(
+            "could not query the daemon over {}: {e}",
+            netpulse::paths::control_socket().display()
+        ),
     }
-
-    // copying netpulsed to /usr/local/bin/
-    let current_exe = std::env::current_exe()?;
-    let target_path = format!("/usr/local/bin/{}", env!("CARGO_BIN_NAME"));
-    info!(
-        "copying the netpulsed executable from '{:?}' to '{target_path}'",
-        current_exe
-    );
-    fs::copy(current_exe, target_path)?;
-
-    Ok(())
 }
 
-fn setup_systemd(skip_checks: bool) -> Result<(), RunError> {
-    if let Some(pid) = getpid_running() {
-        let s = System::new_all();
-        info!("daemon runs with pid {pid}");
-        let process = s
-            .process(pid)
-            .expect("process for the pid of the daemon not found");
-        if !skip_checks || !confirm("terminate the daemon now?") {
-            println!("stopping setup");
-            std::process::exit(0);
-        }
-        process
-            .kill_with(sysinfo::Signal::Term)
-            .expect("SIGTERM does not exist on this platform");
-        process.wait(); // wait until the daemon has stopped
-    }
-
-    setup_general(skip_checks)?;
-
-    // Create service file path
-    let service_path = Path::new(SYSTEMD_SERVICE_PATH);
-
-    // Create parent directories if they don't exist
-    if let Some(parent) = service_path.parent() {
-        info!("creating parent dir of systemd service {parent:?}");
-        fs::create_dir_all(parent)?;
-    }
-
-    // Write service file
-    info!("creating the systemd service");
-    let mut file = fs::File::create(service_path)?;
-    file.write_all(SERVICE_FILE.as_bytes())?;
-
-    // Set permissions to 644 (rw-r--r--)
-    info!("setting permissions for the systemd service");
-    let mut perms = file.metadata()?.permissions();
-    perms.set_mode(0o644);
-    fs::set_permissions(service_path, perms)?;
-
-    info!("Created the netpulsed.service in '{SYSTEMD_SERVICE_PATH}'.");
-    println!("To update the reload the daemon definitions, run the following as root:");
-    println!("  systemctl daemon-reload");
-    println!("To enable and start the service, run the following as root:");
-    println!("  systemctl enable netpulsed.service --now");
-    println!("To just start the service once, run the following as root:");
-    println!("  systemctl start netpulsed.service --now");
-    println!();
-    if !confirm("Reload, enable and start netpulsed.service now?") {
-        return Ok(());
+#[cfg(unix)]
+fn check_now() {
+    if getpid_running().is_none() {
+        println!("netpulsed is not running");
+        std::process::exit(1)
     }
-
-    exec_cmd_for_user(Command::new("systemctl").arg("daemon-reload"), true);
-    exec_cmd_for_user(
-        Command::new("systemctl")
-            .arg("enable")
-            .arg("netpulsed.service"),
-        true,
-    );
-    exec_cmd_for_user(
-        Command::new("systemctl")
-            .arg("restart")
-            .arg("netpulsed.service"),
-        true,
-    );
-
-    Ok(())
-}
-
-fn infod() {
-    match getpid_running() {
-        Some(pid) => {
-            println!("netpulsed is running with pid {pid}")
+    match control::query(&Request::CheckNow) {
+        Ok(Response::Checks { checks }) => {
+            let checks: Vec<&Check> = checks.iter().collect();
+            let mut buf = String::new();
+            if let Err(e) = display_group(&checks, &mut buf) {
+                eprintln!("could not format the checks: {e}");
+                std::process::exit(1)
+            }
+            println!("{buf}");
+        }
+        Ok(Response::Error { message }) => {
+            eprintln!("the daemon could not make the checks: {message}");
+            std::process::exit(1)
+        }
+        Ok(other) => {
+            eprintln!("unexpected answer from the daemon: {other:?}");
+            std::process::exit(1)
+        }
+        Err(e) => {
+            eprintln!(
+                "could not reach the daemon over {}: {e}",
+                netpulse::paths::control_socket().display()
+            );
+            std::process::exit(1)
         }
-        None => println!("netpulsed is not running"),
     }
 }
 
+/// Returns true if a process with `pid` exists.
+///
+/// Signal 0 is not sent, but the kernel still checks if the process exists, which works without
+/// `/proc`, like on macOS.
+#[cfg(unix)]
 fn pid_runs(pid: i32) -> bool {
-    fs::exists(format!("/proc/{pid}")).expect("could not check if the process exists")
+    match signal::kill(Pid::from_raw(pid), None) {
+        // EPERM: it exists, but belongs to someone else
+        Ok(()) | Err(Errno::EPERM) => true,
+        Err(_) => false,
+    }
 }
 
+#[cfg(unix)]
 fn endd() {
-    root_guard();
+    if !netpulse::paths::user_mode() {
+        root_guard();
+    }
     let mut terminated = false;
     let pid: Pid = match getpid_running() {
         None => {
@@ -276,14 +357,19 @@ fn endd() {
             }
         }
     }
-    if fs::exists(DAEMON_PID_FILE).expect("could not check if the pid file exists") {
-        eprintln!("The pid file ({DAEMON_PID_FILE}) still exists even though the daemon is not running, removing it");
-        if let Err(err) = fs::remove_file(DAEMON_PID_FILE) {
+    let pid_file = netpulse::paths::pid_file();
+    if fs::exists(&pid_file).expect("could not check if the pid file exists") {
+        eprintln!(
+            "The pid file ({}) still exists even though the daemon is not running, removing it",
+            pid_file.display()
+        );
+        if let Err(err) = fs::remove_file(&pid_file) {
             eprintln!("Could not remove the pid file: {err}")
         }
     }
 }
 
+#[cfg(unix)]
 fn print_version() -> ! {
     println!("{} {}", env!("CARGO_BIN_NAME"), env!("CARGO_PKG_VERSION"));
     std::process::exit(0)
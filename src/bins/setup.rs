@@ -0,0 +1,358 @@
+//! Setting up netpulsed as a service of the init system.
+//!
+//! `netpulsed --setup` copies the executable to `/usr/local/bin`, creates the netpulse user and
+//! installs a service for the init system, which is detected with [detect] or chosen with
+//! `--init`. Each supported init system is an [InitSystem]:
+//!
+//! - [Systemd]: a unit at [SYSTEMD_SERVICE_PATH]
+//! - [OpenRc]: an init script at [OPENRC_SERVICE_PATH], for example on Alpine
+//! - [Runit]: a service directory at [RUNIT_SERVICE_DIR], for example on Void
+//! - [Launchd]: a property list at [LAUNCHD_SERVICE_PATH], on macOS
+
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use netpulse::common::{confirm, exec_cmd_for_user, getpid_running};
+use netpulse::errors::RunError;
+use netpulse::paths;
+use netpulse::systemd;
+use netpulse::DAEMON_USER;
+use sysinfo::System;
+use tracing::{debug, info, trace};
+
+/// Template of the systemd unit, `{watchdog_sec}` is replaced with [systemd::watchdog_sec] and the
+/// locations with those of [paths]
+const SYSTEMD_SERVICE: &str = include_str!("../../data/netpulsed.service");
+/// Where the systemd unit is installed
+pub(crate) const SYSTEMD_SERVICE_PATH: &str = "/etc/systemd/system/netpulsed.service";
+/// Template of the OpenRC init script, the locations are replaced with those of [paths]
+const OPENRC_SERVICE: &str = include_str!("../../data/netpulsed.openrc");
+/// Where the OpenRC init script is installed
+pub(crate) const OPENRC_SERVICE_PATH: &str = "/etc/init.d/netpulsed";
+/// Template of the `run` script of the runit service, the locations are replaced with those of
+/// [paths]
+const RUNIT_SERVICE: &str = include_str!("../../data/netpulsed.runit");
+/// Where the runit service directory is created
+pub(crate) const RUNIT_SERVICE_DIR: &str = "/etc/sv/netpulsed";
+/// Directories that runit supervises the services of, by distribution
+const RUNIT_ENABLED_DIRS: &[&str] = &["/var/service", "/etc/service", "/run/runit/service"];
+/// Template of the launchd property list, the locations are replaced with those of [paths]
+const LAUNCHD_SERVICE: &str = include_str!("../../data/netpulsed.plist");
+/// Label of the launchd job
+const LAUNCHD_LABEL: &str = "de.cscherr.netpulsed";
+/// Where the launchd property list is installed
+pub(crate) const LAUNCHD_SERVICE_PATH: &str = "/Library/LaunchDaemons/de.cscherr.netpulsed.plist";
+
+/// An init system that netpulsed can be installed as a service of
+pub(crate) trait InitSystem {
+    /// Name of the init system, as accepted by `--init`
+    fn name(&self) -> &'static str;
+
+    /// Returns true if this init system runs the machine.
+    fn is_running(&self) -> bool;
+
+    /// Path of the service file
+    fn service_path(&self) -> PathBuf;
+
+    /// Contents of the service file
+    fn service(&self) -> String;
+
+    /// Permissions of the service file, scripts must be executable
+    fn mode(&self) -> u32 {
+        0o755
+    }
+
+    /// Commands that enable the service and start it
+    fn enable_commands(&self) -> Vec<Command>;
+}
+
+/// systemd, see [SYSTEMD_SERVICE]
+pub(crate) struct Systemd;
+
+impl InitSystem for Systemd {
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    fn is_running(&self) -> bool {
+        Path::new("/run/systemd/system").is_dir()
+    }
+
+    fn service_path(&self) -> PathBuf {
+        PathBuf::from(SYSTEMD_SERVICE_PATH)
+    }
+
+    fn service(&self) -> String {
+        fill(SYSTEMD_SERVICE)
+            .replace("{watchdog_sec}", &systemd::watchdog_sec().to_string())
+            .replace("{pid_file}", &paths::pid_file().to_string_lossy())
+    }
+
+    fn mode(&self) -> u32 {
+        0o644
+    }
+
+    fn enable_commands(&self) -> Vec<Command> {
+        let mut reload = Command::new("systemctl");
+        reload.arg("daemon-reload");
+        let mut enable = Command::new("systemctl");
+        enable.args(["enable", "netpulsed.service"]);
+        let mut restart = Command::new("systemctl");
+        restart.args(["restart", "netpulsed.service"]);
+        vec![reload, enable, restart]
+    }
+}
+
+/// OpenRC, see [OPENRC_SERVICE]
+pub(crate) struct OpenRc;
+
+impl InitSystem for OpenRc {
+    fn name(&self) -> &'static str {
+        "openrc"
+    }
+
+    fn is_running(&self) -> bool {
+        Path::new("/run/openrc").is_dir()
+    }
+
+    fn service_path(&self) -> PathBuf {
+        PathBuf::from(OPENRC_SERVICE_PATH)
+    }
+
+    fn service(&self) -> String {
+        fill(OPENRC_SERVICE)
+            .replace("{info_log}", &paths::info_log().to_string_lossy())
+            .replace("{error_log}", &paths::error_log().to_string_lossy())
+    }
+
+    fn enable_commands(&self) -> Vec<Command> {
+        let mut enable = Command::new("rc-update");
+        enable.args(["add", "netpulsed", "default"]);
+        let mut restart = Command::new("rc-service");
+        restart.args(["netpulsed", "restart"]);
+        vec![enable, restart]
+    }
+}
+
+/// runit, see [RUNIT_SERVICE]
+pub(crate) struct Runit;
+
+impl Runit {
+    /// Returns the directory runit supervises the services of on this machine.
+    fn enabled_dir(&self) -> &'static str {
+        RUNIT_ENABLED_DIRS
+            .iter()
+            .find(|dir| Path::new(dir).is_dir())
+            .unwrap_or(&RUNIT_ENABLED_DIRS[0])
+    }
+}
+
+impl InitSystem for Runit {
+    fn name(&self) -> &'static str {
+        "runit"
+    }
+
+    fn is_running(&self) -> bool {
+        Path::new("/run/runit").is_dir() || Path::new("/etc/runit/runsvdir").is_dir()
+    }
+
+    fn service_path(&self) -> PathBuf {
+        Path::new(RUNIT_SERVICE_DIR).join("run")
+    }
+
+    fn service(&self) -> String {
+        fill(RUNIT_SERVICE)
+    }
+
+    fn enable_commands(&self) -> Vec<Command> {
+        // runsvdir starts the service once it is linked into its directory
+        let mut enable = Command::new("ln");
+        enable.args(["-sfn", RUNIT_SERVICE_DIR, self.enabled_dir()]);
+        vec![enable]
+    }
+}
+
+/// launchd on macOS, see [LAUNCHD_SERVICE]
+pub(crate) struct Launchd;
+
+impl InitSystem for Launchd {
+    fn name(&self) -> &'static str {
+        "launchd"
+    }
+
+    fn is_running(&self) -> bool {
+        cfg!(target_os = "macos") && Path::new("/Library/LaunchDaemons").is_dir()
+    }
+
+    fn service_path(&self) -> PathBuf {
+        PathBuf::from(LAUNCHD_SERVICE_PATH)
+    }
+
+    fn service(&self) -> String {
+        fill(LAUNCHD_SERVICE)
+            .replace("{label}", LAUNCHD_LABEL)
+            .replace("{info_log}", &paths::info_log().to_string_lossy())
+            .replace("{error_log}", &paths::error_log().to_string_lossy())
+    }
+
+    fn mode(&self) -> u32 {
+        0o644
+    }
+
+    fn enable_commands(&self) -> Vec<Command> {
+        // bootstrap fails if the job is loaded already, and bootout if it is not
+        let mut load = Command::new("sh");
+        load.args([
+            "-c",
+            &format!(
+                "launchctl bootout system/{LAUNCHD_LABEL} 2>/dev/null; launchctl bootstrap system {LAUNCHD_SERVICE_PATH}"
+            ),
+        ]);
+        vec![load]
+    }
+}
+
+/// Returns the init system with the given [name](InitSystem::name).
+pub(crate) fn by_name(name: &str) -> Option<Box<dyn InitSystem>> {
+    all().into_iter().find(|init| init.name() == name)
+}
+
+/// Returns the init system that runs the machine, if it is supported.
+pub(crate) fn detect() -> Option<Box<dyn InitSystem>> {
+    all().into_iter().find(|init| init.is_running())
+}
+
+fn all() -> Vec<Box<dyn InitSystem>> {
+    vec![
+        Box::new(Systemd),
+        Box::new(OpenRc),
+        Box::new(Runit),
+        Box::new(Launchd),
+    ]
+}
+
+/// Installs netpulsed as a service of `init` and offers to enable and start it.
+pub(crate) fn setup_service(init: &dyn InitSystem, skip_checks: bool) -> Result<(), RunError> {
+    if let Some(pid) = getpid_running() {
+        let s = System::new_all();
+        info!("daemon runs with pid {pid}");
+        let process = s
+            .process(pid)
+            .expect("process for the pid of the daemon not found");
+        if !skip_checks || !confirm("terminate the daemon now?") {
+            println!("stopping setup");
+            std::process::exit(0);
+        }
+        process
+            .kill_with(sysinfo::Signal::Term)
+            .expect("SIGTERM does not exist on this platform");
+        process.wait(); // wait until the daemon has stopped
+    }
+
+    setup_general(skip_checks)?;
+
+    let service_path = init.service_path();
+    if let Some(parent) = service_path.parent() {
+        info!(
+            "creating parent dir of the {} service {parent:?}",
+            init.name()
+        );
+        fs::create_dir_all(parent)?;
+    }
+
+    info!("creating the {} service", init.name());
+    let mut file = fs::File::create(&service_path)?;
+    file.write_all(init.service().as_bytes())?;
+    let mut perms = file.metadata()?.permissions();
+    perms.set_mode(init.mode());
+    fs::set_permissions(&service_path, perms)?;
+
+    info!(
+        "Created the {} service in '{}'.",
+        init.name(),
+        service_path.display()
+    );
+    let commands = init.enable_commands();
+    println!("To enable and start the service, run the following as root:");
+    for command in &commands {
+        println!("  {}", command_line(command));
+    }
+    println!();
+    if !confirm("Enable and start netpulsed now?") {
+        return Ok(());
+    }
+    for mut command in commands {
+        exec_cmd_for_user(&mut command, true);
+    }
+
+    Ok(())
+}
+
+fn setup_general(skip_checks: bool) -> Result<(), RunError> {
+    debug!("starting general setup");
+    if !skip_checks && !confirm("Perform general daemon setup?") {
+        debug!("general setup skipped");
+        return Ok(());
+    }
+
+    // create netpulse user if it does not exist
+    if !nix::unistd::User::from_name(DAEMON_USER).is_ok_and(|o| o.is_some()) {
+        if skip_checks || confirm("create netpulse user?") {
+            trace!("trying to create a new user");
+            exec_cmd_for_user(&mut useradd(), skip_checks);
+        } else {
+            info!("user {DAEMON_USER} exists")
+        }
+    }
+
+    // copying netpulsed to /usr/local/bin/
+    let current_exe = std::env::current_exe()?;
+    let target_path = format!("/usr/local/bin/{}", env!("CARGO_BIN_NAME"));
+    info!(
+        "copying the netpulsed executable from '{:?}' to '{target_path}'",
+        current_exe
+    );
+    fs::copy(current_exe, target_path)?;
+
+    Ok(())
+}
+
+/// Returns the command that creates [DAEMON_USER] as a system user.
+#[cfg(not(target_os = "macos"))]
+fn useradd() -> Command {
+    let mut cmd = Command::new("useradd");
+    cmd.args(["--system", "--shell", "/sbin/nologin", DAEMON_USER]);
+    cmd
+}
+
+/// Returns the command that creates [DAEMON_USER] as a system user.
+#[cfg(target_os = "macos")]
+fn useradd() -> Command {
+    let mut cmd = Command::new("sysadminctl");
+    cmd.args([
+        "-addUser",
+        DAEMON_USER,
+        "-shell",
+        "/usr/bin/false",
+        "-home",
+        "/var/empty",
+    ]);
+    cmd
+}
+
+/// Fills in the locations that all service templates share, see [paths].
+fn fill(template: &str) -> String {
+    template.replace("{store_dir}", &paths::store_dir().to_string_lossy())
+}
+
+/// Formats `command` like it would be typed into a shell.
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
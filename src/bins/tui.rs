@@ -0,0 +1,257 @@
+//! Terminal dashboard of a netpulse store.
+//!
+//! The dashboard shows:
+//! - The current status: the latest check per target and check type
+//! - A latency sparkline per target
+//! - The latest outages
+//!
+//! The store is read-only and reloaded every [REFRESH] so it follows the daemon. Press `r` to
+//! reload right away and `q` or `Esc` to quit.
+//!
+//! Only available with the `tui` feature.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use chrono::{Local, TimeZone};
+use netpulse::analyze::{self, fmt_timestamp, CheckAccessConstraints};
+use netpulse::errors::RunError;
+use netpulse::records::{Check, CheckType};
+use netpulse::store::Store;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Row, Sparkline, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+/// How often the store is reloaded
+pub const REFRESH: Duration = Duration::from_secs(10);
+/// How many of the latest outages are shown
+const SHOWN_OUTAGES: usize = 10;
+
+/// State of the dashboard
+struct Dashboard {
+    path: Option<PathBuf>,
+    constraints: CheckAccessConstraints,
+    store: Option<Store>,
+    error: Option<String>,
+    loaded_at: Instant,
+}
+
+impl Dashboard {
+    fn new(path: Option<PathBuf>, constraints: CheckAccessConstraints) -> Self {
+        let mut dashboard = Self {
+            path,
+            constraints,
+            store: None,
+            error: None,
+            loaded_at: Instant::now(),
+        };
+        dashboard.reload();
+        dashboard
+    }
+
+    /// Loads the store again, keeping the old one if that fails
+    fn reload(&mut self) {
+        let loaded = match &self.path {
+            Some(path) => Store::load_from(path, true),
+            None => Store::load(true),
+        };
+        match loaded {
+            Ok(mut store) => {
+                self.constraints.apply(&mut store);
+                self.store = Some(store);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+        self.loaded_at = Instant::now();
+    }
+}
+
+/// Runs the dashboard until the user quits.
+///
+/// Reads the store at `path`, or the default store if `path` is [None].
+///
+/// # Errors
+///
+/// Returns [RunError] if the terminal could not be used.
+pub fn tui(path: Option<PathBuf>, constraints: CheckAccessConstraints) -> Result<(), RunError> {
+    let mut dashboard = Dashboard::new(path, constraints);
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut dashboard);
+    ratatui::restore();
+    result
+}
+
+fn run(terminal: &mut DefaultTerminal, dashboard: &mut Dashboard) -> Result<(), RunError> {
+    loop {
+        terminal.draw(|frame| draw(frame, dashboard))?;
+
+        let timeout = REFRESH.saturating_sub(dashboard.loaded_at.elapsed());
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('r') => dashboard.reload(),
+                    _ => (),
+                }
+            }
+        }
+        if dashboard.loaded_at.elapsed() >= REFRESH {
+            dashboard.reload();
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, dashboard: &Dashboard) {
+    let Some(store) = &dashboard.store else {
+        let text = dashboard.error.as_deref().unwrap_or("no store loaded");
+        frame.render_widget(
+            Paragraph::new(text).block(Block::bordered().title("netpulse")),
+            frame.area(),
+        );
+        return;
+    };
+
+    let latest = latest_checks(store.checks());
+    let targets = targets(store.checks());
+    let [status_area, latency_area, outage_area, footer_area] = Layout::vertical([
+        Constraint::Length(latest.len() as u16 + 3),
+        Constraint::Min(3 * targets.len().max(1) as u16),
+        Constraint::Length(SHOWN_OUTAGES as u16 + 2),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    draw_status(frame, status_area, &latest);
+    draw_latencies(frame, latency_area, store.checks(), &targets);
+    draw_outages(frame, outage_area, store);
+
+    let mut footer = format!(
+        "{} | {} checks | q: quit, r: reload",
+        store.file_path().display(),
+        store.checks().len()
+    );
+    if let Some(e) = &dashboard.error {
+        footer.push_str(&format!(" | reload failed: {e}"));
+    }
+    frame.render_widget(Line::from(footer).dim(), footer_area);
+}
+
+fn draw_status(frame: &mut Frame, area: Rect, latest: &[&Check]) {
+    let rows = latest.iter().map(|check| {
+        let (status, color) = if check.is_success() {
+            ("ok", Color::Green)
+        } else {
+            ("FAILED", Color::Red)
+        };
+        Row::new(vec![
+            check.target_name(),
+            check.calc_type().unwrap_or(CheckType::Unknown).to_string(),
+            status.to_string(),
+            check
+                .latency()
+                .map(|l| format!("{l} ms"))
+                .unwrap_or_default(),
+            fmt_timestamp(check.timestamp_parsed()),
+        ])
+        .style(Style::new().fg(color))
+    });
+    let widths = [
+        Constraint::Fill(2),
+        Constraint::Fill(1),
+        Constraint::Length(6),
+        Constraint::Length(8),
+        Constraint::Fill(2),
+    ];
+    let table = Table::new(rows, widths)
+        .header(Row::new(["Target", "Type", "Status", "Latency", "Last Check"]).bold())
+        .block(Block::bordered().title("Current Status"));
+    frame.render_widget(table, area);
+}
+
+fn draw_latencies(frame: &mut Frame, area: Rect, checks: &[Check], targets: &[(IpAddr, String)]) {
+    let block = Block::bordered().title("Latency");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    if targets.is_empty() {
+        return;
+    }
+
+    let areas = Layout::vertical(vec![Constraint::Fill(1); targets.len()]).split(inner);
+    for ((target, name), area) in targets.iter().zip(areas.iter()) {
+        let mut latencies: Vec<u64> = checks
+            .iter()
+            .rev()
+            .filter(|c| c.target() == *target)
+            .take(area.width as usize)
+            .map(|c| c.latency().unwrap_or(0) as u64)
+            .collect();
+        latencies.reverse();
+        let max = latencies.iter().max().copied().unwrap_or(0);
+        let sparkline = Sparkline::default()
+            .block(Block::new().title(format!("{name} (max {max} ms)")))
+            .data(&latencies)
+            .style(Style::new().fg(Color::Cyan));
+        frame.render_widget(sparkline, *area);
+    }
+}
+
+fn draw_outages(frame: &mut Frame, area: Rect, store: &Store) {
+    let items: Vec<ListItem> = analyze::outage_summaries(store)
+        .iter()
+        .rev()
+        .take(SHOWN_OUTAGES)
+        .map(|outage| {
+            let start = Local.timestamp_opt(outage.start, 0).unwrap();
+            let targets: Vec<String> = outage.targets.iter().map(|t| t.to_string()).collect();
+            ListItem::new(format!(
+                "{} for {:>4}m: {}, {}",
+                fmt_timestamp(start),
+                outage.duration / 60,
+                outage.severity,
+                targets.join(", ")
+            ))
+        })
+        .collect();
+    let list = if items.is_empty() {
+        List::new([ListItem::new("None")])
+    } else {
+        List::new(items)
+    };
+    frame.render_widget(list.block(Block::bordered().title("Latest Outages")), area);
+}
+
+/// Returns the latest [Check] per target and [CheckType]
+fn latest_checks(checks: &[Check]) -> Vec<&Check> {
+    let mut latest: BTreeMap<(IpAddr, CheckType), &Check> = BTreeMap::new();
+    for check in checks {
+        let key = (
+            check.target(),
+            check.calc_type().unwrap_or(CheckType::Unknown),
+        );
+        if latest
+            .get(&key)
+            .is_none_or(|old| old.timestamp() <= check.timestamp())
+        {
+            latest.insert(key, check);
+        }
+    }
+    latest.into_values().collect()
+}
+
+/// Returns all targets of the checks with their display names
+fn targets(checks: &[Check]) -> Vec<(IpAddr, String)> {
+    let mut targets: BTreeMap<IpAddr, String> = BTreeMap::new();
+    for check in checks {
+        targets.insert(check.target(), check.target_name());
+    }
+    targets.into_iter().collect()
+}
@@ -1,9 +1,20 @@
 //! Implementation of network connectivity checks.
 //!
 //! This module contains the actual check implementations for different protocols:
-//! - HTTP checks via HEAD requests
-//! - ICMP checks via ping
-//! - DNS checks (planned)
+//! - HTTP checks via HEAD requests, against the bare IP or a configured URL
+//! - ICMP checks via ping, single or as a burst to measure packet loss, see [PingOptions]
+//! - Path MTU checks via unfragmented ICMP echoes, see [mtu]
+//! - Traceroutes to find where the path to a target breaks, see [traceroute]
+//! - Gateway checks via ping of the default gateway, see [gateway]
+//! - DNS checks over UDP, HTTPS and TLS, see [dns]
+//! - NTP checks via SNTP, see [ntp]
+//! - Throughput samples by downloading a payload, see [bandwidth]
+//! - QUIC checks via a handshake and an HTTP/3 request, see [quic]
+//!
+//! Most checks can be bound to a network interface or source address, see [bind].
+//!
+//! Each check type is made by a [Checker](checker::Checker), other implementations can be
+//! registered in its place, see [checker].
 //!
 //! All check functions follow the pattern:
 //! - Take a target IP address
@@ -13,41 +24,142 @@
 //! # Feature Flags
 //!
 //! Check types can be enabled/disabled via feature flags:
-//! - `http` - Enable HTTP checks
+//! - `http` - Enable HTTP checks with curl
+//! - `http-rustls` - Enable HTTP checks with a pure Rust client, for builds without libcurl and
+//!   OpenSSL, like static musl builds. If `http` is enabled too, curl is used.
 //! - `ping` - Enable ICMP checks
+//! - `async` - Enable the asynchronous checks in [r#async]
+//! - `mtu` - Enable MTU checks, see [mtu]
+//! - `traceroute` - Enable traceroutes of failed checks, see [traceroute]
+//! - `doh` - Enable DNS over HTTPS checks, see [dns]
+//! - `dot` - Enable DNS over TLS checks, see [dns]
+//! - `bandwidth` - Enable throughput samples, see [bandwidth]
+//! - `quic` - Enable QUIC checks, see [quic]
 //!
 //! # Example
 //!
 //! ```rust
-//! # #[cfg(feature = "http")] {// only works with that feature
+//! # #[cfg(any(feature = "http", feature = "http-rustls"))] {// only works with that feature
 //! use netpulse::checks;
 //! use std::net::IpAddr;
 //!
 //! let addr: IpAddr = "1.1.1.1".parse().unwrap();
 //!
 //! // Perform HTTP check
-//! if let Ok(latency) = checks::check_http(addr) {
-//!     println!("HTTP latency: {}ms", latency);
+//! if let Ok((latency, status)) = checks::check_http(addr) {
+//!     println!("HTTP latency: {latency:?}, status {status}");
 //! }
 //! # }
 //! ```
+#[cfg(any(feature = "ping", feature = "http", feature = "http-rustls"))]
 use std::net::IpAddr;
+use std::time::Duration;
 
+#[cfg(any(feature = "ping", feature = "http", feature = "http-rustls"))]
 use crate::errors::CheckError;
+#[cfg(any(feature = "http", feature = "http-rustls"))]
+use crate::records::IpType;
 use crate::TIMEOUT;
+#[cfg(any(feature = "ping", feature = "http", feature = "http-rustls"))]
+use bind::Bind;
+
+#[cfg(feature = "async")]
+pub mod r#async;
+#[cfg(feature = "bandwidth")]
+pub mod bandwidth;
+pub mod bind;
+pub mod checker;
+pub mod dns;
+pub mod gateway;
+#[cfg(any(feature = "ping", feature = "mtu", feature = "traceroute"))]
+pub mod icmp;
+#[cfg(feature = "mtu")]
+pub mod mtu;
+pub mod ntp;
+#[cfg(feature = "quic")]
+pub mod quic;
+#[cfg(feature = "traceroute")]
+pub mod traceroute;
+
+/// Returns true if the process may open raw sockets, as ICMP checks need.
+///
+/// That is the case if it runs as root or has the `CAP_NET_RAW` capability.
+///
+/// Other systems than Linux have no capabilities, there only root counts. On Windows, this is
+/// always true, opening the socket will tell.
+pub fn has_cap_net_raw() -> bool {
+    // First check if we're root (which implies all capabilities)
+    #[cfg(unix)]
+    if nix::unistd::getuid().is_root() {
+        return true;
+    }
+
+    // Check current process capabilities
+    #[cfg(target_os = "linux")]
+    return match caps::read(None, caps::CapSet::Effective) {
+        Ok(caps) => caps.contains(&caps::Capability::CAP_NET_RAW),
+        Err(_) => {
+            tracing::warn!("Could not read capabilities");
+            false
+        }
+    };
+    #[cfg(all(unix, not(target_os = "linux")))]
+    return false;
+    #[cfg(not(unix))]
+    return true;
+}
+
+/// Parameters of the ICMP echoes of [ping_with], see [PingConfig](crate::config::PingConfig).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingOptions {
+    /// Bytes of payload after the ICMP header, 56 like `ping` by default
+    pub size: u16,
+    /// Time to live (hop limit for IPv6) of the echo requests, the system default if [None]
+    pub ttl: Option<u8>,
+    /// How long to wait for the reply
+    pub timeout: Duration,
+}
+
+impl Default for PingOptions {
+    fn default() -> Self {
+        Self {
+            size: DEFAULT_PING_SIZE,
+            ttl: None,
+            timeout: TIMEOUT,
+        }
+    }
+}
+
+/// Bytes of payload of an echo request, unless set in the [PingOptions]
+pub const DEFAULT_PING_SIZE: u16 = 56;
+
+/// Reply to an echo request sent by [ping_with]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pong {
+    /// Round-trip time of the echo
+    pub latency: Duration,
+    /// Time to live (hop limit for IPv6) of the reply when it arrived, if it is known
+    ///
+    /// The target sets it to some initial value, like 64, and every router on the way back
+    /// decrements it. A change tells that the path from the target changed.
+    pub ttl: Option<u8>,
+}
 
 /// Performs an ICMP ping check to the specified IP address.
 ///
 /// Uses raw sockets to send ICMP echo request and measure round-trip time.
 /// This function requires the `ping` feature to be enabled.
 ///
+/// This is [ping_with] with the default [PingOptions].
+///
 /// # Required Capabilities
 ///
 /// This function requires the `CAP_NET_RAW` capability to create and use raw sockets for ICMP.
 /// Without this capability, the function will fail with a permission error.
 ///
-/// **Note**: When running as a daemon, this capability is typically lost when dropping privileges
-/// from root to the daemon user. As a result, ICMP checks may not work in daemon mode.
+/// **Note**: The daemon keeps this capability when it drops its privileges, see
+/// [drop_privileges](crate::common::drop_privileges), and the service files grant it to the
+/// daemon user.
 ///
 /// # Arguments
 ///
@@ -55,7 +167,7 @@ use crate::TIMEOUT;
 ///
 /// # Returns
 ///
-/// * `Ok(u16)` - Round-trip time in milliseconds if ping succeeds
+/// * `Ok(Duration)` - Round-trip time if ping succeeds
 /// * `Err(CheckError)` - If ping fails (timeout, network error, etc)
 ///
 /// # Errors
@@ -74,23 +186,137 @@ use crate::TIMEOUT;
 ///
 /// let addr: IpAddr = "1.1.1.1".parse().unwrap();
 /// match just_fucking_ping(addr) {
-///     Ok(latency) => println!("Ping latency: {}ms", latency),
+///     Ok(latency) => println!("Ping latency: {latency:?}"),
 ///     Err(e) => eprintln!("Ping failed: {}", e),
 /// }
 /// ```
 #[cfg(feature = "ping")]
-pub fn just_fucking_ping(remote: IpAddr) -> Result<u16, CheckError> {
-    let now = std::time::Instant::now();
-    match ping::rawsock::ping(remote, Some(TIMEOUT), None, None, None, None) {
-        Ok(_) => Ok(now.elapsed().as_millis() as u16),
-        Err(e) => Err(e.into()),
+pub fn just_fucking_ping(remote: IpAddr) -> Result<Duration, CheckError> {
+    ping_with(remote, &PingOptions::default()).map(|pong| pong.latency)
+}
+
+/// Sends an ICMP echo request with the given [PingOptions] and waits for the reply.
+///
+/// Like [just_fucking_ping], this needs the `CAP_NET_RAW` capability.
+///
+/// # Errors
+///
+/// Returns [CheckError::NoReply] if no reply came within the timeout, or [CheckError::Io] if
+/// the socket failed or a router reported that the target can not be reached, or that the time
+/// to live ran out.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::net::IpAddr;
+/// use std::time::Duration;
+/// use netpulse::checks::{ping_with, PingOptions};
+///
+/// let addr: IpAddr = "1.1.1.1".parse().unwrap();
+/// let options = PingOptions {
+///     size: 1000,
+///     ttl: Some(32),
+///     timeout: Duration::from_secs(2),
+/// };
+/// let pong = ping_with(addr, &options).unwrap();
+/// println!("{:?}, reply arrived with a TTL of {:?}", pong.latency, pong.ttl);
+/// ```
+#[cfg(feature = "ping")]
+pub fn ping_with(remote: IpAddr, options: &PingOptions) -> Result<Pong, CheckError> {
+    ping_via(remote, options, &Bind::default())
+}
+
+/// Works like [ping_with], but sends the echo as set in `bind`, see [bind].
+///
+/// # Errors
+///
+/// See [ping_with] and [Bind::apply].
+#[cfg(feature = "ping")]
+pub fn ping_via(remote: IpAddr, options: &PingOptions, bind: &Bind) -> Result<Pong, CheckError> {
+    use std::io::{Error, ErrorKind};
+
+    use icmp::{EchoSocket, Reply};
+
+    let mut socket = EchoSocket::new(remote)?;
+    socket.bind(bind)?;
+    if let Some(ttl) = options.ttl {
+        socket.set_ttl(ttl as u32)?;
+    }
+    let size = (socket.ip_header_len() + icmp::ICMP_HEADER_LEN).saturating_add(options.size);
+    match socket.echo(size, options.timeout)? {
+        Some(Reply::Echo { rtt, ttl }) => Ok(Pong { latency: rtt, ttl }),
+        Some(Reply::TooBig) => Err(Error::other("the echo request was too big").into()),
+        Some(Reply::TimeExceeded(router)) => Err(Error::new(
+            ErrorKind::HostUnreachable,
+            format!("the time to live ran out at {router}"),
+        )
+        .into()),
+        Some(Reply::Unreachable(router)) => Err(Error::new(
+            ErrorKind::HostUnreachable,
+            format!("{router} reported that {remote} is unreachable"),
+        )
+        .into()),
+        None => Err(CheckError::NoReply),
     }
 }
 
+/// Sends a burst of `count` ICMP echoes to the specified IP address.
+///
+/// Uses [ping_via] for every echo and waits `interval` between them. Echoes that fail for any
+/// reason count as lost.
+///
+/// # Returns
+///
+/// The number of echoes that came back, their mean round-trip time, [None] if none came back,
+/// and the time to live of the last reply, if it is known.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::net::IpAddr;
+/// use std::time::Duration;
+/// use netpulse::checks::bind::Bind;
+/// use netpulse::checks::{ping_burst, PingOptions};
+///
+/// let addr: IpAddr = "1.1.1.1".parse().unwrap();
+/// let options = PingOptions::default();
+/// let (received, latency, _ttl) =
+///     ping_burst(addr, 10, Duration::from_millis(200), &options, &Bind::default());
+/// println!("{received}/10 echoes came back, mean latency {latency:?}");
+/// ```
+#[cfg(feature = "ping")]
+pub fn ping_burst(
+    remote: IpAddr,
+    count: u8,
+    interval: Duration,
+    options: &PingOptions,
+    bind: &Bind,
+) -> (u8, Option<Duration>, Option<u8>) {
+    let mut received: u8 = 0;
+    let mut latency_sum = Duration::ZERO;
+    let mut last_ttl = None;
+    for idx in 0..count {
+        if idx > 0 {
+            std::thread::sleep(interval);
+        }
+        match ping_via(remote, options, bind) {
+            Ok(pong) => {
+                received += 1;
+                latency_sum += pong.latency;
+                last_ttl = pong.ttl.or(last_ttl);
+            }
+            Err(err) => tracing::trace!("echo {idx} to {remote} was lost: {err}"),
+        }
+    }
+    let latency = (received > 0).then(|| latency_sum / received as u32);
+    (received, latency, last_ttl)
+}
+
 /// Performs an HTTP HEAD request to check connectivity to the specified IP address.
 ///
 /// Makes an HTTP/HTTPS HEAD request to measure response time. Uses curl under the hood
-/// and requires the `http` feature to be enabled.
+/// and requires the `http` feature to be enabled, or [ureq] with rustls if only the
+/// `http-rustls` feature is enabled.
 ///
 /// # Arguments
 ///
@@ -98,7 +324,9 @@ pub fn just_fucking_ping(remote: IpAddr) -> Result<u16, CheckError> {
 ///
 /// # Returns
 ///
-/// * `Ok(u16)` - Round-trip time in milliseconds if request succeeds
+/// * `Ok((Duration, u16))` - Round-trip time and the HTTP status code of the
+///   response, if a response was received. Error status codes like 5xx are not an error here,
+///   the caller decides what to make of them.
 /// * `Err(CheckError)` - If request fails (timeout, connection refused, etc)
 ///
 /// # Errors
@@ -107,7 +335,6 @@ pub fn just_fucking_ping(remote: IpAddr) -> Result<u16, CheckError> {
 /// - DNS resolution fails
 /// - Connection fails or is refused
 /// - Request times out ([`TIMEOUT`])
-/// - HTTP response indicates error
 /// - URL construction fails
 ///
 /// # IPv6 Handling
@@ -123,22 +350,167 @@ pub fn just_fucking_ping(remote: IpAddr) -> Result<u16, CheckError> {
 ///
 /// let addr: IpAddr = "1.1.1.1".parse().unwrap();
 /// match check_http(addr) {
-///     Ok(latency) => println!("HTTP latency: {}ms", latency),
+///     Ok((latency, status)) => println!("HTTP latency: {latency:?}, status {status}"),
+///     Err(e) => eprintln!("HTTP check failed: {}", e),
+/// }
+/// ```
+#[cfg(any(feature = "http", feature = "http-rustls"))]
+pub fn check_http(remote: IpAddr) -> Result<(Duration, u16), CheckError> {
+    check_http_via(remote, &Bind::default())
+}
+
+/// Works like [check_http], but sends the request as set in `bind`, see [bind].
+///
+/// # Errors
+///
+/// See [check_http_url_via].
+#[cfg(any(feature = "http", feature = "http-rustls"))]
+pub fn check_http_via(remote: IpAddr, bind: &Bind) -> Result<(Duration, u16), CheckError> {
+    let url = format!("http://{}", bind.url_host(remote));
+    check_http_url_via(&url, IpType::from(remote), bind)
+}
+
+/// Performs an HTTP HEAD request to the given URL.
+///
+/// Works like [check_http], but requests a full URL with scheme, host, port and path, for
+/// example a health endpoint like `https://example.com:8443/health`. Host names in the URL are
+/// only resolved to addresses of `ip_type`, so the check uses the same IP version as its target.
+///
+/// # Errors
+///
+/// See [check_http].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use netpulse::checks::check_http_url;
+/// use netpulse::records::IpType;
+///
+/// match check_http_url("https://example.com/health", IpType::V4) {
+///     Ok((latency, status)) => println!("HTTP latency: {latency:?}, status {status}"),
 ///     Err(e) => eprintln!("HTTP check failed: {}", e),
 /// }
 /// ```
+#[cfg(any(feature = "http", feature = "http-rustls"))]
+pub fn check_http_url(url: &str, ip_type: IpType) -> Result<(Duration, u16), CheckError> {
+    check_http_url_via(url, ip_type, &Bind::default())
+}
+
+/// Works like [check_http_url], but sends the request as set in `bind`, see [bind].
+///
+/// # Errors
+///
+/// See [check_http]. With the `http-rustls` client, requests can not be bound, a `bind` that
+/// [is set](Bind::is_set) fails with [CheckError::Io].
 #[cfg(feature = "http")]
-pub fn check_http(remote: IpAddr) -> Result<u16, CheckError> {
+pub fn check_http_url_via(
+    url: &str,
+    ip_type: IpType,
+    bind: &Bind,
+) -> Result<(Duration, u16), CheckError> {
     let start = std::time::Instant::now();
     let mut easy = curl::easy::Easy::new();
 
-    easy.url(&match remote {
-        IpAddr::V4(_) => remote.to_string(),
-        IpAddr::V6(_) => format!("[{remote}]"),
+    easy.url(url)?;
+    if let Some(interface) = bind.curl_interface() {
+        easy.interface(&interface)?;
+    }
+    easy.ip_resolve(match ip_type {
+        IpType::V4 => curl::easy::IpResolve::V4,
+        IpType::V6 => curl::easy::IpResolve::V6,
     })?;
     easy.nobody(true)?; // HEAD request only
     easy.timeout(TIMEOUT)?;
-    easy.perform()?;
+    if let Err(err) = easy.perform() {
+        // curl does not tell why the connection failed, but the OS does
+        let connection = err.is_couldnt_connect() || err.is_recv_error() || err.is_send_error();
+        return Err(match easy.os_errno()? {
+            errno if connection && errno != 0 => std::io::Error::from_raw_os_error(errno).into(),
+            _ => err.into(),
+        });
+    }
+    let latency = start.elapsed();
+
+    Ok((latency, easy.response_code()? as u16))
+}
+
+/// POSTs `body` with the given `content_type` and further `headers` to `url`.
+///
+/// The headers are given whole, like `Authorization: Bearer <token>`. Returns the status of the
+/// response, its body is discarded. This is shared by everything that sends data somewhere, like
+/// [webhooks](crate::notify), [pushing](crate::push) and [InfluxDB](crate::records::influx).
+///
+/// # Errors
+///
+/// Returns a [curl::Error] if the request could not be made.
+#[cfg(any(feature = "webhook", feature = "push", feature = "influx"))]
+pub(crate) fn post(
+    url: &str,
+    content_type: &str,
+    headers: &[String],
+    body: &[u8],
+) -> Result<u32, curl::Error> {
+    let mut easy = curl::easy::Easy::new();
+    let mut list = curl::easy::List::new();
+    list.append(&format!("Content-Type: {content_type}"))?;
+    for header in headers {
+        list.append(header)?;
+    }
+
+    easy.url(url)?;
+    easy.post(true)?;
+    easy.post_fields_copy(body)?;
+    easy.http_headers(list)?;
+    easy.timeout(TIMEOUT)?;
+    {
+        // we don't care about the response body, but curl would print it to stdout otherwise
+        let mut transfer = easy.transfer();
+        transfer.write_function(|data| Ok(data.len()))?;
+        transfer.perform()?;
+    }
+    easy.response_code()
+}
+
+/// Performs an HTTP HEAD request to the given URL.
+///
+/// This is [check_http_url_via] for builds with the `http-rustls` feature but without `http`, it
+/// uses [ureq] and rustls instead of curl.
+#[cfg(all(feature = "http-rustls", not(feature = "http")))]
+pub fn check_http_url_via(
+    url: &str,
+    ip_type: IpType,
+    bind: &Bind,
+) -> Result<(Duration, u16), CheckError> {
+    use ureq::config::IpFamily;
+
+    if bind.is_set() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "HTTP checks can only be bound with the http feature",
+        )
+        .into());
+    }
+
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .ip_family(match ip_type {
+            IpType::V4 => IpFamily::Ipv4Only,
+            IpType::V6 => IpFamily::Ipv6Only,
+        })
+        .timeout_global(Some(TIMEOUT))
+        // like curl, an error status is still a response
+        .http_status_as_error(false)
+        .build()
+        .into();
+
+    let start = std::time::Instant::now();
+    let response = agent.head(url).call().map_err(|err| match err {
+        // the resolver of std only reports a failed lookup in the message
+        ureq::Error::Io(io) if io.to_string().starts_with("failed to lookup address") => {
+            Box::new(ureq::Error::HostNotFound)
+        }
+        err => Box::new(err),
+    })?;
+    let latency = start.elapsed();
 
-    Ok(start.elapsed().as_millis() as u16)
+    Ok((latency, response.status().as_u16()))
 }
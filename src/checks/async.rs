@@ -0,0 +1,382 @@
+//! Asynchronous implementation of the network checks.
+//!
+//! The functions in [checks](super) block a thread per check, which is fine for a handful of
+//! targets. This module does the same checks on [tokio], so many targets can be checked
+//! concurrently with only a few threads. The results are the same [Checks](Check) as those of
+//! [CheckType::make_target].
+//!
+//! The caller provides the runtime. HTTP checks use [reqwest] instead of curl, QUIC checks run
+//! on the runtime directly. ICMP, DNS and NTP
+//! checks are still made with the blocking implementations, on the blocking thread pool of
+//! [tokio], as are the checks of types with a [registered](super::checker::register)
+//! [Checker](super::checker::Checker).
+//!
+//! [make_checks] follows the same [Config] as the blocking checks of
+//! [Store::primitive_make_checks](crate::store::Store::primitive_make_checks): failed checks are
+//! [retried](crate::config::RetryConfig), echoes are sent as [configured](crate::config::PingConfig)
+//! and the round has the same [deadline](Config::round_deadline), so both give the same
+//! [Checks](Check).
+//!
+//! Requires the `async` feature.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use netpulse::checks::r#async::make_checks;
+//! use netpulse::config::Config;
+//! use netpulse::records::CheckType;
+//!
+//! # async fn run() {
+//! let config = Config::load_or_default();
+//! let checks = make_checks(&config.targets, &[CheckType::Http], &config).await;
+//! println!("made {} checks", checks.len());
+//! # }
+//! ```
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use flagset::FlagSet;
+use tokio::task::JoinSet;
+use tracing::{debug, error, warn};
+
+use super::checker;
+#[cfg(feature = "quic")]
+use super::checker::CheckOutcome;
+use crate::config::{Config, Target};
+use crate::errors::CheckError;
+#[cfg(feature = "mtu")]
+use crate::records::Measurement;
+use crate::records::{Check, CheckFlag, CheckType, IpType};
+use crate::TIMEOUT;
+
+/// Performs an HTTP HEAD request to a target IP address.
+///
+/// Works like [check_http](super::check_http).
+///
+/// # Errors
+///
+/// See [check_http](super::check_http).
+pub async fn check_http(remote: IpAddr) -> Result<(Duration, u16), CheckError> {
+    let url = match remote {
+        IpAddr::V4(_) => format!("http://{remote}"),
+        IpAddr::V6(_) => format!("http://[{remote}]"),
+    };
+    check_http_url(&url, IpType::from(remote)).await
+}
+
+/// Performs an HTTP HEAD request to the given URL.
+///
+/// Works like [check_http_url](super::check_http_url). The URL needs a scheme.
+///
+/// # Errors
+///
+/// See [check_http](super::check_http).
+pub async fn check_http_url(url: &str, ip_type: IpType) -> Result<(Duration, u16), CheckError> {
+    // binding to the unspecified address of a version only allows addresses of that version
+    let local: IpAddr = match ip_type {
+        IpType::V4 => std::net::Ipv4Addr::UNSPECIFIED.into(),
+        IpType::V6 => std::net::Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let client = reqwest::Client::builder()
+        .local_address(local)
+        .timeout(TIMEOUT)
+        .build()?;
+
+    let start = Instant::now();
+    let response = client.head(url).send().await?;
+    let latency = start.elapsed();
+
+    Ok((latency, response.status().as_u16()))
+}
+
+/// Performs an ICMP ping to the specified IP address.
+///
+/// Works like [just_fucking_ping](super::just_fucking_ping), on the blocking thread pool.
+///
+/// # Errors
+///
+/// See [just_fucking_ping](super::just_fucking_ping).
+#[cfg(feature = "ping")]
+pub async fn check_icmp(remote: IpAddr) -> Result<Duration, CheckError> {
+    tokio::task::spawn_blocking(move || super::just_fucking_ping(remote)).await?
+}
+
+/// Creates and performs a new network check of `check_type` against a [Target].
+///
+/// Works like [CheckType::make_target].
+///
+/// # Panics
+///
+/// See [CheckType::make_target]. HTTP checks do not need the `http` feature here.
+pub async fn make_target(check_type: CheckType, target: &Target) -> Check {
+    let remote = target.address;
+    let mut check = new_check(target);
+
+    match check_type {
+        // a registered checker replaces the asynchronous implementation, see checker
+        _ if checker::registered(check_type).is_some() => {
+            let target = target.clone();
+            check = make_blocking(check_type, check, move || check_type.make_target(&target)).await
+        }
+        CheckType::Http => {
+            check.add_flag(CheckFlag::TypeHTTP);
+            let result = match &target.url {
+                Some(url) => check_http_url(url, IpType::from(remote)).await,
+                None => check_http(remote).await,
+            };
+            match result {
+                Err(err) => {
+                    error!("error while performing an Http check: {err}");
+                    check.add_failure(&err);
+                }
+                Ok((lat, status)) => {
+                    check.set_http_status(Some(status));
+                    check.set_latency(Some(lat));
+                    if status >= 500 {
+                        check.add_flag(CheckFlag::ServerError);
+                    } else {
+                        check.add_flag(CheckFlag::Success);
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "ping")]
+        CheckType::Icmp => {
+            check.add_flag(CheckFlag::TypeIcmp);
+            match check_icmp(remote).await {
+                Err(err) => {
+                    error!("error while performing an ICMPv4 check: {err}");
+                    check.add_failure(&err);
+                }
+                Ok(lat) => {
+                    check.add_flag(CheckFlag::Success);
+                    check.set_latency(Some(lat));
+                }
+            }
+        }
+        #[cfg(not(feature = "ping"))]
+        CheckType::Icmp => {
+            panic!("Trying to make a ICMPv4 check, but the ping feature is not enabled")
+        }
+        #[cfg(feature = "mtu")]
+        CheckType::Mtu => {
+            check.add_flag(CheckFlag::TypeMtu);
+            match tokio::task::spawn_blocking(move || super::mtu::path_mtu(remote)).await {
+                Ok(Ok(size)) => {
+                    check.add_flag(CheckFlag::Success);
+                    check.set_measurement(Some(Measurement::Mtu { size }));
+                }
+                Ok(Err(err)) => {
+                    error!("error while performing an MTU check: {err}");
+                    check.add_failure(&err);
+                }
+                Err(err) => error!("error while performing an MTU check: {err}"),
+            }
+        }
+        #[cfg(not(feature = "mtu"))]
+        CheckType::Mtu => {
+            panic!("Trying to make a MTU check, but the mtu feature is not enabled")
+        }
+        #[cfg(feature = "ping")]
+        CheckType::Gateway => {
+            check.add_flag(CheckFlag::TypeGateway);
+            match check_icmp(remote).await {
+                Err(err) => {
+                    error!("error while performing a gateway check: {err}");
+                    check.add_failure(&err);
+                }
+                Ok(lat) => {
+                    check.add_flag(CheckFlag::Success);
+                    check.set_latency(Some(lat));
+                }
+            }
+        }
+        #[cfg(not(feature = "ping"))]
+        CheckType::Gateway => {
+            panic!("Trying to make a gateway check, but the ping feature is not enabled")
+        }
+        // a single query or download is fine for the blocking thread pool
+        CheckType::Dns
+        | CheckType::Doh
+        | CheckType::Dot
+        | CheckType::Ntp
+        | CheckType::Bandwidth => {
+            let target = target.clone();
+            check = make_blocking(check_type, check, move || check_type.make_target(&target)).await
+        }
+        #[cfg(feature = "quic")]
+        CheckType::Quic => {
+            check.add_flag(CheckFlag::TypeQuic);
+            let result = super::quic::check_quic_async(target, &target.bind()).await;
+            CheckOutcome::http(result).record(check_type, &mut check);
+        }
+        #[cfg(not(feature = "quic"))]
+        CheckType::Quic => {
+            panic!("Trying to make a QUIC check, but the quic feature is not enabled")
+        }
+        CheckType::Unknown => panic!("tried to make an Unknown check"),
+    }
+
+    check
+}
+
+/// Creates a [Check] against `target` that has nothing but its label and zone yet.
+fn new_check(target: &Target) -> Check {
+    let mut check = Check::new(Utc::now(), FlagSet::default(), None, target.address);
+    check.set_label(target.label.clone());
+    check.set_zone(target.zone.clone());
+    check
+}
+
+/// Makes a check with `make` on the blocking thread pool.
+///
+/// Returns `check` with the type flag if the check could not be run.
+async fn make_blocking(
+    check_type: CheckType,
+    mut check: Check,
+    make: impl FnOnce() -> Check + Send + 'static,
+) -> Check {
+    match tokio::task::spawn_blocking(make).await {
+        Ok(made) => made,
+        Err(err) => {
+            error!("error while performing a {check_type} check: {err}");
+            if let Some(flag) = check_type.flag() {
+                check.add_flag(flag);
+            }
+            check
+        }
+    }
+}
+
+/// Creates and performs a new network check of `check_type` against a [Target] as configured.
+///
+/// Works like [CheckType::make_configured]: failed checks are retried, ICMP and gateway checks
+/// send their echoes as configured and slow checks are flagged as degraded.
+pub async fn make_configured(
+    check_type: CheckType,
+    target: &Target,
+    config: &Arc<Config>,
+) -> Check {
+    let retry = &config.retry;
+    let mut check = make_once(check_type, target, config).await;
+    for attempt in 1..=retry.retries {
+        if check.is_success() {
+            break;
+        }
+        debug!(
+            "{check_type} check for {} failed, retrying ({attempt}/{})",
+            target.address, retry.retries
+        );
+        tokio::time::sleep(retry.delay()).await;
+        check = make_once(check_type, target, config).await;
+        check.add_flag(CheckFlag::Retried);
+    }
+    if let Some(threshold) = config.degraded.threshold(check_type) {
+        check.check_degraded(threshold);
+    }
+    check
+}
+
+/// Makes a single attempt of a check for [make_configured]
+async fn make_once(check_type: CheckType, target: &Target, config: &Arc<Config>) -> Check {
+    match check_type {
+        // the echoes are sent as configured, which only the blocking checks know how to
+        CheckType::Icmp | CheckType::Gateway => {
+            let check = new_check(target);
+            let (target, config) = (target.clone(), config.clone());
+            make_blocking(check_type, check, move || {
+                check_type.make_once(&target, &config)
+            })
+            .await
+        }
+        _ => make_target(check_type, target).await,
+    }
+}
+
+/// Makes all `check_types` against all `targets` concurrently, as set in the [Config].
+///
+/// Like [Store::primitive_make_checks](crate::store::Store::primitive_make_checks), the checks
+/// are made with [make_configured], and checks that did not finish within the
+/// [deadline](Config::round_deadline) of the round are recorded as failed with
+/// [CheckFlag::Timeout].
+pub async fn make_checks(
+    targets: &[Target],
+    check_types: &[CheckType],
+    config: &Config,
+) -> Vec<Check> {
+    let deadline = tokio::time::Instant::now() + config.round_deadline();
+    let config = Arc::new(config.clone());
+    let mut set = JoinSet::new();
+    for check_type in check_types {
+        for target in targets {
+            let check_type = *check_type;
+            let target = target.clone();
+            let config = config.clone();
+            set.spawn(async move {
+                match tokio::time::timeout_at(
+                    deadline,
+                    make_configured(check_type, &target, &config),
+                )
+                .await
+                {
+                    Ok(check) => check,
+                    Err(_) => {
+                        warn!(
+                            "{check_type} check for {} did not finish in time, recording it as failed",
+                            target.address
+                        );
+                        check_type.timed_out(&target)
+                    }
+                }
+            });
+        }
+    }
+
+    let mut checks = Vec::with_capacity(set.len());
+    while let Some(result) = set.join_next().await {
+        match result {
+            Ok(check) => checks.push(check),
+            Err(err) => error!("a check task failed: {err}"),
+        }
+    }
+    checks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn closed_port() -> Target {
+        Target {
+            address: "127.0.0.1".parse().unwrap(),
+            zone: None,
+            label: Some("closed".to_string()),
+            url: Some("http://127.0.0.1:1/".to_string()),
+            interface: None,
+            source: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_make_checks_unreachable() {
+        let checks = make_checks(&[closed_port()], &[CheckType::Http], &Config::default()).await;
+        assert_eq!(checks.len(), 1);
+        assert!(!checks[0].is_success());
+        assert_eq!(checks[0].calc_type().unwrap(), CheckType::Http);
+        assert_eq!(checks[0].label(), Some("closed"));
+        assert!(!checks[0].flags().contains(CheckFlag::Retried));
+    }
+
+    #[tokio::test]
+    async fn test_make_checks_retries() {
+        let mut config = Config::default();
+        config.retry.retries = 2;
+        let checks = make_checks(&[closed_port()], &[CheckType::Http], &config).await;
+        assert_eq!(checks.len(), 1);
+        assert!(!checks[0].is_success());
+        assert!(checks[0].flags().contains(CheckFlag::Retried));
+    }
+}
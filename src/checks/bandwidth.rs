@@ -0,0 +1,87 @@
+//! Throughput samples by downloading a fixed payload.
+//!
+//! Latency alone does not show a line that answers quickly but is slow. [sample_throughput]
+//! downloads the payload at a configured URL and measures how fast it came in. As that costs
+//! traffic, the daemon only takes a sample every few rounds, see
+//! [BandwidthConfig](crate::config::BandwidthConfig).
+//!
+//! The payload should be small, a few megabytes at most, so a sample fits within [TIMEOUT].
+//!
+//! Requires the `bandwidth` feature.
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use crate::errors::CheckError;
+use crate::TIMEOUT;
+
+/// Label of the [Checks](crate::records::Check) of throughput samples
+pub const BANDWIDTH_LABEL: &str = "bandwidth";
+
+/// Result of a throughput sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// Address of the server the payload came from
+    pub remote: Option<IpAddr>,
+    /// Time until the first byte of the payload came in
+    pub latency: Duration,
+    /// Size of the payload in bytes
+    pub bytes: u64,
+    /// Bytes per second while downloading the payload
+    pub bytes_per_second: u32,
+}
+
+/// Downloads the payload at `url` and measures the throughput.
+///
+/// The throughput only counts the transfer of the payload, not the time until its first byte,
+/// so a slow connection setup shows up as latency instead.
+///
+/// # Errors
+///
+/// Returns [CheckError::Http] if the download failed or did not finish within [TIMEOUT].
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use netpulse::checks::bandwidth::sample_throughput;
+///
+/// match sample_throughput("https://speed.cloudflare.com/__down?bytes=1000000") {
+///     Ok(sample) => println!("{} bytes/s", sample.bytes_per_second),
+///     Err(e) => eprintln!("bandwidth check failed: {e}"),
+/// }
+/// ```
+pub fn sample_throughput(url: &str) -> Result<Sample, CheckError> {
+    let mut easy = curl::easy::Easy::new();
+    easy.url(url)?;
+    easy.follow_location(true)?;
+    easy.fail_on_error(true)?;
+    easy.timeout(TIMEOUT)?;
+
+    let mut bytes: u64 = 0;
+    let start = Instant::now();
+    {
+        let mut transfer = easy.transfer();
+        transfer.write_function(|data| {
+            bytes += data.len() as u64;
+            Ok(data.len())
+        })?;
+        transfer.perform()?;
+    }
+    let total = start.elapsed();
+    let first_byte = easy.starttransfer_time()?.min(total);
+
+    let transfer_time = (total - first_byte).as_secs_f64();
+    let bytes_per_second = if transfer_time > 0.0 {
+        (bytes as f64 / transfer_time).min(u32::MAX as f64) as u32
+    } else {
+        u32::MAX
+    };
+    let remote = easy.primary_ip()?.and_then(|ip| ip.parse().ok());
+
+    Ok(Sample {
+        remote,
+        latency: first_byte,
+        bytes,
+        bytes_per_second,
+    })
+}
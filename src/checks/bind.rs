@@ -0,0 +1,329 @@
+//! Binding checks to a network interface or source address.
+//!
+//! On a host with more than one uplink, the routing table decides which one a check leaves
+//! through, usually always the same. A [Bind] makes the checks of a target leave through one
+//! interface, or from one source address, so each uplink can be monitored on its own. It is set
+//! per [Target](crate::config::Target) or for all targets in the `[bind]` table of the
+//! [Config](crate::config::Config), and recorded in the [Check](crate::records::Check), see
+//! [Check::interface](crate::records::Check::interface).
+//!
+//! Binding to an interface uses `SO_BINDTODEVICE` and only works on Linux. Source addresses work
+//! everywhere, but must be assigned to the host.
+//!
+//! # Zones
+//!
+//! A link-local IPv6 address like `fe80::1` exists on every link, so it needs a zone that tells
+//! which one is meant, like `fe80::1%eth0`. The zone of a [Target](crate::config::Target) is
+//! kept in its [Bind] and becomes the scope ID of the socket addresses, see [Bind::remote]. Zones
+//! are either the number of an interface or, on Linux, its name. See [parse_scoped] and
+//! [fmt_scoped] for the text form.
+//!
+//! These checks can be bound:
+//! - HTTP checks with curl, not with the `http-rustls` client
+//! - ICMP, gateway and MTU checks
+//! - DNS checks over UDP, HTTPS and TLS
+//! - NTP checks
+//!
+//! # Example
+//!
+//! ```toml
+//! [[targets]]
+//! address = "1.1.1.1"
+//! label = "cloudflare-fiber"
+//! interface = "eth0"
+//!
+//! [[targets]]
+//! address = "1.1.1.1"
+//! label = "cloudflare-lte"
+//! interface = "wwan0"
+//! ```
+
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, TcpStream, UdpSocket};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+
+/// Where the checks leave the host, see the [module docs](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bind {
+    /// Name of the network interface, like `eth0`
+    pub interface: Option<String>,
+    /// Local address the checks are sent from
+    pub source: Option<IpAddr>,
+    /// Zone of a link-local target, like `eth0` in `fe80::1%eth0`, taken from the
+    /// [Target](crate::config::Target)
+    #[serde(skip)]
+    pub zone: Option<String>,
+}
+
+impl Bind {
+    /// Returns true if the checks are bound to an interface or a source address.
+    pub fn is_set(&self) -> bool {
+        self.interface.is_some() || self.source.is_some()
+    }
+
+    /// Returns what the checks are bound to, as recorded in the [Check](crate::records::Check):
+    /// the interface if one is set, the source address otherwise.
+    pub fn name(&self) -> Option<String> {
+        self.interface
+            .clone()
+            .or_else(|| self.source.map(|s| s.to_string()))
+    }
+
+    /// Binds `socket` for packets to `remote`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the interface does not exist, binding to an interface is not
+    /// supported on this system, or the source address is not of the IP version of `remote` or
+    /// not assigned to the host.
+    pub fn apply(&self, socket: &Socket, remote: IpAddr) -> io::Result<()> {
+        if let Some(interface) = &self.interface {
+            bind_device(socket, interface)?;
+        }
+        if let Some(source) = self.source {
+            if source.is_ipv4() != remote.is_ipv4() {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("the source address {source} is of another IP version than {remote}"),
+                ));
+            }
+            socket.bind(&SocketAddr::new(source, 0).into())?;
+        }
+        Ok(())
+    }
+
+    /// Returns the socket address of `port` on `remote`, with the scope ID of the
+    /// [zone](Self::zone) if `remote` is an IPv6 address.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zone is not the number or the name of an interface.
+    pub fn remote(&self, remote: IpAddr, port: u16) -> io::Result<SocketAddr> {
+        match (remote, &self.zone) {
+            (IpAddr::V6(ip), Some(zone)) => {
+                Ok(SocketAddrV6::new(ip, port, 0, scope_id(zone)?).into())
+            }
+            _ => Ok(SocketAddr::new(remote, port)),
+        }
+    }
+
+    /// Returns `remote` as the host of a URL, in brackets with the [zone](Self::zone) for IPv6,
+    /// like `[fe80::1%25eth0]`.
+    pub fn url_host(&self, remote: IpAddr) -> String {
+        match (remote, &self.zone) {
+            (IpAddr::V4(_), _) => remote.to_string(),
+            (IpAddr::V6(_), Some(zone)) => format!("[{remote}%25{zone}]"),
+            (IpAddr::V6(_), None) => format!("[{remote}]"),
+        }
+    }
+
+    /// Creates a UDP socket for datagrams to `remote`, bound as set.
+    ///
+    /// # Errors
+    ///
+    /// See [Bind::apply].
+    pub fn udp_socket(&self, remote: IpAddr) -> io::Result<UdpSocket> {
+        let socket = Socket::new(domain(remote), Type::DGRAM, Some(Protocol::UDP))?;
+        self.apply(&socket, remote)?;
+        if self.source.is_none() {
+            socket.bind(&SocketAddr::new(unspecified(remote), 0).into())?;
+        }
+        Ok(socket.into())
+    }
+
+    /// Opens a TCP connection to `remote`, bound as set, waiting up to `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// See [Bind::apply]. Returns an error if the connection failed or timed out, too.
+    pub fn tcp_connect(&self, remote: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        if !self.is_set() {
+            return TcpStream::connect_timeout(&remote, timeout);
+        }
+        let socket = Socket::new(domain(remote.ip()), Type::STREAM, Some(Protocol::TCP))?;
+        self.apply(&socket, remote.ip())?;
+        socket.connect_timeout(&remote.into(), timeout)?;
+        Ok(socket.into())
+    }
+
+    /// Returns the value of the `CURLOPT_INTERFACE` option of curl for this binding.
+    #[cfg(feature = "http")]
+    pub(crate) fn curl_interface(&self) -> Option<String> {
+        match (&self.interface, self.source) {
+            (Some(interface), Some(source)) => Some(format!("ifhost!{interface}!{source}")),
+            (Some(interface), None) => Some(format!("if!{interface}")),
+            (None, Some(source)) => Some(format!("host!{source}")),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Parses an IP address with an optional zone, like `fe80::1%eth0` or `fe80::1%2`.
+///
+/// # Errors
+///
+/// Returns an error if the address is invalid, or if an IPv4 address or an empty zone is given
+/// with a zone.
+///
+/// # Examples
+///
+/// ```rust
+/// use netpulse::checks::bind::parse_scoped;
+///
+/// let (address, zone) = parse_scoped("fe80::1%eth0").unwrap();
+/// assert_eq!(address, "fe80::1".parse::<std::net::IpAddr>().unwrap());
+/// assert_eq!(zone.as_deref(), Some("eth0"));
+/// assert_eq!(parse_scoped("1.1.1.1").unwrap().1, None);
+/// assert!(parse_scoped("1.1.1.1%eth0").is_err());
+/// ```
+pub fn parse_scoped(raw: &str) -> Result<(IpAddr, Option<String>), String> {
+    let (address, zone) = match raw.split_once('%') {
+        Some((address, zone)) => (address, Some(zone)),
+        None => (raw, None),
+    };
+    let address: IpAddr = address
+        .parse()
+        .map_err(|_| format!("Not a valid IP address: {raw}"))?;
+    match zone {
+        Some(zone) if address.is_ipv4() => Err(format!(
+            "IPv4 addresses have no zone, remove '%{zone}' from {raw}"
+        )),
+        Some("") => Err(format!("The zone of {raw} is empty")),
+        zone => Ok((address, zone.map(str::to_string))),
+    }
+}
+
+/// Formats an IP address with its zone if it has one, like `fe80::1%eth0`, see
+/// [parse_scoped].
+pub fn fmt_scoped(address: IpAddr, zone: Option<&str>) -> String {
+    match zone {
+        Some(zone) => format!("{address}%{zone}"),
+        None => address.to_string(),
+    }
+}
+
+/// Returns the scope ID of `zone`, which is the number or the name of an interface.
+fn scope_id(zone: &str) -> io::Result<u32> {
+    match zone.parse() {
+        Ok(id) => Ok(id),
+        Err(_) => interface_index(zone),
+    }
+}
+
+/// Looks up the number of the interface `name` in `/sys/class/net`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn interface_index(name: &str) -> io::Result<u32> {
+    let not_found = || {
+        io::Error::new(
+            ErrorKind::NotFound,
+            format!("there is no interface named {name}"),
+        )
+    };
+    if name.contains('/') {
+        return Err(not_found());
+    }
+    std::fs::read_to_string(format!("/sys/class/net/{name}/ifindex"))
+        .map_err(|_| not_found())?
+        .trim()
+        .parse()
+        .map_err(|_| not_found())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn interface_index(name: &str) -> io::Result<u32> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        format!("zones by interface name like {name} are only supported on Linux, use its number"),
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_device(socket: &Socket, interface: &str) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn bind_device(_socket: &Socket, interface: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        format!("binding to the interface {interface} is only supported on Linux"),
+    ))
+}
+
+fn domain(remote: IpAddr) -> Domain {
+    match remote {
+        IpAddr::V4(_) => Domain::IPV4,
+        IpAddr::V6(_) => Domain::IPV6,
+    }
+}
+
+fn unspecified(remote: IpAddr) -> IpAddr {
+    match remote {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bind_source() {
+        let localhost: IpAddr = Ipv4Addr::LOCALHOST.into();
+        let bind = Bind {
+            source: Some(localhost),
+            ..Default::default()
+        };
+        assert_eq!(bind.name().as_deref(), Some("127.0.0.1"));
+
+        let socket = bind.udp_socket(localhost).unwrap();
+        assert_eq!(socket.local_addr().unwrap().ip(), localhost);
+        let socket = Bind::default().udp_socket(localhost).unwrap();
+        assert!(socket.local_addr().unwrap().ip().is_unspecified());
+
+        let err = bind.udp_socket(Ipv6Addr::LOCALHOST.into()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_zone() {
+        let link_local: IpAddr = "fe80::1".parse().unwrap();
+        let bind = Bind {
+            zone: Some("7".to_string()),
+            ..Default::default()
+        };
+        match bind.remote(link_local, 53).unwrap() {
+            SocketAddr::V6(addr) => assert_eq!(addr.scope_id(), 7),
+            SocketAddr::V4(_) => unreachable!(),
+        }
+        assert_eq!(bind.url_host(link_local), "[fe80::1%257]");
+        assert_eq!(Bind::default().url_host(link_local), "[fe80::1]");
+        assert_eq!(
+            bind.remote(Ipv4Addr::LOCALHOST.into(), 53).unwrap(),
+            "127.0.0.1:53".parse().unwrap()
+        );
+
+        #[cfg(target_os = "linux")]
+        {
+            let lo = Bind {
+                zone: Some("lo".to_string()),
+                ..Default::default()
+            };
+            assert!(lo.remote(link_local, 0).is_ok());
+        }
+        let missing = Bind {
+            zone: Some("netpulse-missing0".to_string()),
+            ..Default::default()
+        };
+        assert!(missing.remote(link_local, 0).is_err());
+
+        let (address, zone) = parse_scoped("fe80::1%eth0").unwrap();
+        assert_eq!(fmt_scoped(address, zone.as_deref()), "fe80::1%eth0");
+        assert!(parse_scoped("fe80::1%").is_err());
+    }
+}
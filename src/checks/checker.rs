@@ -0,0 +1,449 @@
+//! Pluggable implementations of the check types, see [Checker].
+//!
+//! Every [CheckType] is made by a [Checker], which only performs the check and returns a
+//! [CheckOutcome]. Building the [Check] from it, with the type flag, label, interface, retries
+//! and so on, is left to [CheckType::make_target] and [CheckType::make_configured], so a
+//! [Checker] does not need to know about any of that.
+//!
+//! The built-in checkers are returned by [builtin]. A [Checker] can be [registered](register) for
+//! a [CheckType] to replace the built-in one for the whole process, for example to check a
+//! service in a different way, or to make checks without network access in tests.
+//!
+//! # Adding a protocol
+//!
+//! A new protocol like an SSH banner or SMTP check needs:
+//! - A new [CheckType] after the existing ones, and a type flag in a free bit of
+//!   [CheckFlag](crate::records::CheckFlag), so the serialized form of older checks stays valid
+//! - A [Checker] for it, returned by [builtin]
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use netpulse::checks::bind::Bind;
+//! use netpulse::checks::checker::{self, CheckOutcome, Checker};
+//! use netpulse::config::Target;
+//! use netpulse::records::CheckType;
+//!
+//! struct Instant;
+//!
+//! impl Checker for Instant {
+//!     fn check(&self, _target: &Target, _bind: &Bind) -> CheckOutcome {
+//!         CheckOutcome::success(Duration::from_millis(1))
+//!     }
+//! }
+//!
+//! checker::register(CheckType::Ntp, Instant);
+//! let check = CheckType::Ntp.make("192.0.2.1".parse().unwrap());
+//! assert!(check.is_success());
+//! checker::unregister(CheckType::Ntp);
+//! ```
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+
+use tracing::error;
+
+use super::bind::Bind;
+#[cfg(feature = "ping")]
+use super::PingOptions;
+use crate::config::Target;
+use crate::errors::CheckError;
+#[cfg(any(feature = "http", feature = "http-rustls"))]
+use crate::records::IpType;
+use crate::records::{Check, CheckFlag, CheckType, Measurement};
+
+/// Performs the checks of one [CheckType], see the [module docs](self).
+///
+/// A [Checker] is shared between the threads that make the checks of a round.
+pub trait Checker: Send + Sync {
+    /// Checks `target`, bound to the interface or source address in `bind`.
+    ///
+    /// The check should give up after [TIMEOUT](crate::TIMEOUT).
+    fn check(&self, target: &Target, bind: &Bind) -> CheckOutcome;
+}
+
+/// Result of a [Checker], which is recorded in a [Check].
+#[derive(Debug, Default)]
+pub struct CheckOutcome {
+    /// Why the check failed, [None] if it succeeded
+    pub error: Option<CheckError>,
+    /// How long the check took
+    pub latency: Option<Duration>,
+    /// Status code of an HTTP response
+    pub http_status: Option<u16>,
+    /// Additional result, like the size of an MTU check
+    pub measurement: Option<Measurement>,
+    /// Address that was actually checked, if it is not the address of the [Target], like the
+    /// address a URL resolved to
+    pub remote: Option<IpAddr>,
+}
+
+impl CheckOutcome {
+    /// Creates the outcome of a successful check that took `latency`.
+    pub fn success(latency: Duration) -> Self {
+        Self {
+            latency: Some(latency),
+            ..Default::default()
+        }
+    }
+
+    /// Creates the outcome of a check that failed with `err`.
+    pub fn failure(err: CheckError) -> Self {
+        Self {
+            error: Some(err),
+            ..Default::default()
+        }
+    }
+
+    /// Creates the outcome of an HTTP or HTTP/3 request from its latency and status code.
+    ///
+    /// A status of 500 or more counts as [CheckError::ServerError].
+    pub fn http(result: Result<(Duration, u16), CheckError>) -> Self {
+        match result {
+            Err(err) => Self::failure(err),
+            Ok((latency, status)) => Self {
+                error: (status >= 500).then_some(CheckError::ServerError(status)),
+                http_status: Some(status),
+                ..Self::success(latency)
+            },
+        }
+    }
+
+    /// Sets the [Measurement] of this outcome.
+    pub fn with_measurement(mut self, measurement: Measurement) -> Self {
+        self.measurement = Some(measurement);
+        self
+    }
+
+    /// Returns true if the check succeeded.
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Records this outcome of a check of `check_type` in `check`.
+    ///
+    /// A failure is logged, and its [reason](CheckError::reason) is added to the flags of the
+    /// [Check]. Otherwise, the [Check] gets [CheckFlag::Success].
+    pub fn record(self, check_type: CheckType, check: &mut Check) {
+        match self.error {
+            Some(err) => {
+                error!("error while performing a {check_type} check: {err}");
+                check.add_failure(&err);
+            }
+            None => check.add_flag(CheckFlag::Success),
+        }
+        check.set_latency(self.latency);
+        check.set_http_status(self.http_status);
+        check.set_measurement(self.measurement);
+        if let Some(remote) = self.remote {
+            check.set_target(remote);
+        }
+    }
+}
+
+impl From<Result<Duration, CheckError>> for CheckOutcome {
+    fn from(value: Result<Duration, CheckError>) -> Self {
+        match value {
+            Ok(latency) => Self::success(latency),
+            Err(err) => Self::failure(err),
+        }
+    }
+}
+
+/// [Checkers](Checker) that were [registered](register), by [CheckType]
+fn registrations() -> &'static RwLock<HashMap<CheckType, Arc<dyn Checker>>> {
+    static REGISTRATIONS: OnceLock<RwLock<HashMap<CheckType, Arc<dyn Checker>>>> = OnceLock::new();
+    REGISTRATIONS.get_or_init(Default::default)
+}
+
+/// Registers `checker` to make all following checks of `check_type` in this process, instead
+/// of the built-in [Checker].
+///
+/// A [Checker] that was registered for `check_type` before is replaced.
+///
+/// # Panics
+///
+/// Panics if the lock of the registrations is poisoned.
+pub fn register(check_type: CheckType, checker: impl Checker + 'static) {
+    registrations()
+        .write()
+        .expect("lock is poisoned")
+        .insert(check_type, Arc::new(checker));
+}
+
+/// Removes the [Checker] [registered](register) for `check_type`, so the built-in one is used
+/// again.
+///
+/// # Panics
+///
+/// Panics if the lock of the registrations is poisoned.
+pub fn unregister(check_type: CheckType) {
+    registrations()
+        .write()
+        .expect("lock is poisoned")
+        .remove(&check_type);
+}
+
+/// Returns the [Checker] [registered](register) for `check_type`, if there is one.
+///
+/// # Panics
+///
+/// Panics if the lock of the registrations is poisoned.
+pub fn registered(check_type: CheckType) -> Option<Arc<dyn Checker>> {
+    registrations()
+        .read()
+        .expect("lock is poisoned")
+        .get(&check_type)
+        .cloned()
+}
+
+/// Returns the [Checker] that makes checks of `check_type`: the [registered] one if there is
+/// one, the [builtin] one otherwise.
+pub fn get(check_type: CheckType) -> Option<Arc<dyn Checker>> {
+    registered(check_type).or_else(|| builtin(check_type))
+}
+
+/// Returns the built-in [Checker] for `check_type`.
+///
+/// Returns [None] for [CheckType::Unknown], and for types whose feature is not enabled, see the
+/// [feature flags](super#feature-flags).
+pub fn builtin(check_type: CheckType) -> Option<Arc<dyn Checker>> {
+    Some(match check_type {
+        #[cfg(any(feature = "http", feature = "http-rustls"))]
+        CheckType::Http => Arc::new(HttpChecker),
+        #[cfg(feature = "ping")]
+        CheckType::Icmp | CheckType::Gateway => Arc::new(PingChecker::default()),
+        #[cfg(feature = "mtu")]
+        CheckType::Mtu => Arc::new(MtuChecker),
+        CheckType::Dns => Arc::new(DnsChecker),
+        #[cfg(feature = "doh")]
+        CheckType::Doh => Arc::new(DohChecker),
+        #[cfg(feature = "dot")]
+        CheckType::Dot => Arc::new(DotChecker),
+        CheckType::Ntp => Arc::new(NtpChecker),
+        #[cfg(feature = "bandwidth")]
+        CheckType::Bandwidth => Arc::new(BandwidthChecker),
+        #[cfg(feature = "quic")]
+        CheckType::Quic => Arc::new(QuicChecker),
+        _ => return None,
+    })
+}
+
+/// Requests the URL of the [Target], or its bare address over HTTP, see
+/// [check_http_url_via](super::check_http_url_via).
+///
+/// An HTTP status of 500 or more counts as [CheckError::ServerError], see [CheckOutcome::http].
+#[cfg(any(feature = "http", feature = "http-rustls"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpChecker;
+
+#[cfg(any(feature = "http", feature = "http-rustls"))]
+impl Checker for HttpChecker {
+    fn check(&self, target: &Target, bind: &Bind) -> CheckOutcome {
+        let remote = target.address;
+        CheckOutcome::http(match &target.url {
+            Some(url) => super::check_http_url_via(url, IpType::from(remote), bind),
+            None => super::check_http_via(remote, bind),
+        })
+    }
+}
+
+/// Makes a QUIC handshake and an HTTP/3 `HEAD` request, see [quic](super::quic).
+///
+/// An HTTP status of 500 or more counts as [CheckError::ServerError].
+#[cfg(feature = "quic")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuicChecker;
+
+#[cfg(feature = "quic")]
+impl Checker for QuicChecker {
+    fn check(&self, target: &Target, bind: &Bind) -> CheckOutcome {
+        CheckOutcome::http(super::quic::check_quic_via(target, bind))
+    }
+}
+
+/// Sends a single ICMP echo with [PingOptions] and records the time to live of the reply as
+/// [Measurement::Ttl], see [ping_via](super::ping_via).
+#[cfg(feature = "ping")]
+#[derive(Debug, Clone, Default)]
+pub struct PingChecker {
+    /// Options of the echo request
+    pub options: PingOptions,
+}
+
+#[cfg(feature = "ping")]
+impl Checker for PingChecker {
+    fn check(&self, target: &Target, bind: &Bind) -> CheckOutcome {
+        match super::ping_via(target.address, &self.options, bind) {
+            Err(err) => CheckOutcome::failure(err),
+            Ok(pong) => CheckOutcome {
+                measurement: pong.ttl.map(|ttl| Measurement::Ttl { ttl }),
+                ..CheckOutcome::success(pong.latency)
+            },
+        }
+    }
+}
+
+/// Probes the path MTU and records it as [Measurement::Mtu], see [mtu](super::mtu).
+#[cfg(feature = "mtu")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MtuChecker;
+
+#[cfg(feature = "mtu")]
+impl Checker for MtuChecker {
+    fn check(&self, target: &Target, bind: &Bind) -> CheckOutcome {
+        match super::mtu::path_mtu_via(target.address, bind) {
+            Err(err) => CheckOutcome::failure(err),
+            Ok(size) => CheckOutcome::default().with_measurement(Measurement::Mtu { size }),
+        }
+    }
+}
+
+/// Queries a DNS server over UDP, see [dns](super::dns).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DnsChecker;
+
+impl Checker for DnsChecker {
+    fn check(&self, target: &Target, bind: &Bind) -> CheckOutcome {
+        super::dns::check_dns_via(target.address, bind).into()
+    }
+}
+
+/// Queries a DNS server over HTTPS, see [dns](super::dns).
+#[cfg(feature = "doh")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DohChecker;
+
+#[cfg(feature = "doh")]
+impl Checker for DohChecker {
+    fn check(&self, target: &Target, bind: &Bind) -> CheckOutcome {
+        super::dns::check_doh_via(target.address, bind).into()
+    }
+}
+
+/// Queries a DNS server over TLS, see [dns](super::dns).
+#[cfg(feature = "dot")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DotChecker;
+
+#[cfg(feature = "dot")]
+impl Checker for DotChecker {
+    fn check(&self, target: &Target, bind: &Bind) -> CheckOutcome {
+        super::dns::check_dot_via(target.address, bind).into()
+    }
+}
+
+/// Queries an NTP server and records the clock offset as [Measurement::Ntp], see
+/// [ntp](super::ntp).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NtpChecker;
+
+impl Checker for NtpChecker {
+    fn check(&self, target: &Target, bind: &Bind) -> CheckOutcome {
+        match super::ntp::check_ntp_via(target.address, bind) {
+            Err(err) => CheckOutcome::failure(err),
+            Ok((latency, offset)) => {
+                CheckOutcome::success(latency).with_measurement(Measurement::Ntp { offset })
+            }
+        }
+    }
+}
+
+/// Downloads the URL of the [Target] and records the throughput as [Measurement::Throughput],
+/// see [bandwidth](super::bandwidth).
+///
+/// The download is not bound, and the address the URL resolved to is recorded as the target.
+///
+/// # Panics
+///
+/// [BandwidthChecker::check] panics if the [Target] has no URL.
+#[cfg(feature = "bandwidth")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BandwidthChecker;
+
+#[cfg(feature = "bandwidth")]
+impl Checker for BandwidthChecker {
+    fn check(&self, target: &Target, _bind: &Bind) -> CheckOutcome {
+        let Some(url) = &target.url else {
+            panic!("Trying to make a bandwidth check without a url");
+        };
+        match super::bandwidth::sample_throughput(url) {
+            Err(err) => CheckOutcome::failure(err),
+            Ok(sample) => CheckOutcome {
+                remote: sample.remote,
+                ..CheckOutcome::success(sample.latency).with_measurement(Measurement::Throughput {
+                    bytes_per_second: sample.bytes_per_second,
+                })
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Answers from every address but 192.0.2.1
+    struct Fake;
+
+    impl Checker for Fake {
+        fn check(&self, target: &Target, _bind: &Bind) -> CheckOutcome {
+            if target.address == IpAddr::from([192, 0, 2, 1]) {
+                CheckOutcome::failure(CheckError::NoReply)
+            } else {
+                CheckOutcome::success(Duration::from_millis(12))
+                    .with_measurement(Measurement::Ntp { offset: -3 })
+            }
+        }
+    }
+
+    #[test]
+    fn test_registered_checker() {
+        assert!(builtin(CheckType::Unknown).is_none());
+        assert!(builtin(CheckType::Ntp).is_some());
+
+        // NTP checks are not made anywhere else in the tests
+        register(CheckType::Ntp, Fake);
+        assert!(registered(CheckType::Ntp).is_some());
+
+        let check = CheckType::Ntp.make("192.0.2.2".parse().unwrap());
+        assert!(check.is_success());
+        assert_eq!(check.calc_type().unwrap(), CheckType::Ntp);
+        assert_eq!(check.latency(), Some(12));
+        assert_eq!(check.measurement(), Some(Measurement::Ntp { offset: -3 }));
+
+        let check = CheckType::Ntp.make("192.0.2.1".parse().unwrap());
+        assert!(!check.is_success());
+        assert_eq!(check.failure_reason(), Some(CheckFlag::Timeout));
+        assert_eq!(check.latency(), None);
+
+        unregister(CheckType::Ntp);
+        assert!(registered(CheckType::Ntp).is_none());
+    }
+
+    #[test]
+    fn test_outcome() {
+        let outcome = CheckOutcome::from(Ok(Duration::from_millis(5)));
+        assert!(outcome.is_success());
+        let outcome = CheckOutcome::from(Err(CheckError::ServerError(503)));
+        assert!(!outcome.is_success());
+
+        let mut check = Check::new(
+            chrono::Utc::now(),
+            CheckFlag::TypeHTTP,
+            None,
+            IpAddr::from([1, 1, 1, 1]),
+        );
+        CheckOutcome {
+            http_status: Some(503),
+            ..outcome
+        }
+        .record(CheckType::Http, &mut check);
+        assert_eq!(check.failure_reason(), Some(CheckFlag::ServerError));
+        assert_eq!(check.http_status(), Some(503));
+    }
+}
@@ -0,0 +1,257 @@
+//! DNS checks over plain UDP, HTTPS (DoH) and TLS (DoT).
+//!
+//! All three send the same query for the A record of [QUERY_NAME] to the target and check that
+//! the target answered it without an error. The answer itself is not looked at, the check is
+//! about the resolver being reachable, not about the records.
+//!
+//! - [check_dns] sends the query over UDP to port 53
+//! - [check_doh] POSTs the query to `https://<target>/dns-query`, requires the `doh` feature
+//! - [check_dot] sends the query over a TLS connection to port 853, requires the `dot` feature
+//!
+//! Encrypted DNS is verified against the address of the target, so the certificate of the
+//! resolver must contain its IP address, like those of the big public resolvers do.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::net::IpAddr;
+//! use netpulse::checks::dns::check_dns;
+//!
+//! let addr: IpAddr = "1.1.1.1".parse().unwrap();
+//! match check_dns(addr) {
+//!     Ok(latency) => println!("DNS latency: {latency:?}"),
+//!     Err(e) => eprintln!("DNS check failed: {e}"),
+//! }
+//! ```
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use super::bind::Bind;
+use crate::errors::CheckError;
+use crate::TIMEOUT;
+
+/// Name whose A record is queried
+pub const QUERY_NAME: &str = "example.com";
+/// Port of plain DNS
+pub const DNS_PORT: u16 = 53;
+/// Port of DNS over TLS
+pub const DOT_PORT: u16 = 853;
+
+/// Size of the header of a DNS message
+const HEADER_LEN: usize = 12;
+
+/// Sends a DNS query over UDP to `remote` and waits for the answer.
+///
+/// Returns the latency.
+///
+/// # Errors
+///
+/// Returns [CheckError::Io] if the query could not be sent or no answer came within
+/// [TIMEOUT], and [CheckError::BadDnsResponse] if the answer was not valid.
+pub fn check_dns(remote: IpAddr) -> Result<Duration, CheckError> {
+    check_dns_via(remote, &Bind::default())
+}
+
+/// Works like [check_dns], but sends the query as set in `bind`, see [bind](super::bind).
+///
+/// # Errors
+///
+/// See [check_dns] and [Bind::apply].
+pub fn check_dns_via(remote: IpAddr, bind: &Bind) -> Result<Duration, CheckError> {
+    let socket = bind.udp_socket(remote)?;
+    socket.set_read_timeout(Some(TIMEOUT))?;
+    socket.connect(bind.remote(remote, DNS_PORT)?)?;
+
+    let id = query_id();
+    let start = Instant::now();
+    socket.send(&query(id))?;
+    let mut buf = [0; 512];
+    let len = socket.recv(&mut buf)?;
+    let latency = start.elapsed();
+
+    check_response(&buf[..len], id)?;
+    Ok(latency)
+}
+
+/// Sends a DNS query over HTTPS to `remote` and waits for the answer.
+///
+/// The query is POSTed to `https://<remote>/dns-query` as described in RFC 8484. Returns the
+/// latency in milliseconds.
+///
+/// # Errors
+///
+/// Returns [CheckError::Http] if the request failed, and [CheckError::BadDnsResponse] if the
+/// server did not answer with a valid DNS message.
+#[cfg(feature = "doh")]
+pub fn check_doh(remote: IpAddr) -> Result<Duration, CheckError> {
+    check_doh_via(remote, &Bind::default())
+}
+
+/// Works like [check_doh], but sends the query as set in `bind`, see [bind](super::bind).
+///
+/// # Errors
+///
+/// See [check_doh].
+#[cfg(feature = "doh")]
+pub fn check_doh_via(remote: IpAddr, bind: &Bind) -> Result<Duration, CheckError> {
+    let url = format!("https://{}/dns-query", bind.url_host(remote));
+    let id = query_id();
+    let body = query(id);
+
+    let start = Instant::now();
+    let mut easy = curl::easy::Easy::new();
+    easy.url(&url)?;
+    if let Some(interface) = bind.curl_interface() {
+        easy.interface(&interface)?;
+    }
+    easy.post(true)?;
+    easy.post_fields_copy(&body)?;
+    let mut headers = curl::easy::List::new();
+    headers.append("Content-Type: application/dns-message")?;
+    headers.append("Accept: application/dns-message")?;
+    easy.http_headers(headers)?;
+    easy.timeout(TIMEOUT)?;
+
+    let mut response = Vec::new();
+    {
+        let mut transfer = easy.transfer();
+        transfer.write_function(|data| {
+            response.extend_from_slice(data);
+            Ok(data.len())
+        })?;
+        transfer.perform()?;
+    }
+    let latency = start.elapsed();
+
+    if easy.response_code()? != 200 {
+        return Err(CheckError::BadDnsResponse(
+            "the server did not answer with 200",
+        ));
+    }
+    check_response(&response, id)?;
+    Ok(latency)
+}
+
+/// Sends a DNS query over TLS to `remote` and waits for the answer.
+///
+/// The certificate of `remote` is verified against its IP address with the
+/// [webpki roots](webpki_roots). Returns the latency, including the TLS handshake.
+///
+/// # Errors
+///
+/// Returns [CheckError::Io] if the connection failed or timed out, [CheckError::Tls] if the
+/// TLS handshake failed, and [CheckError::BadDnsResponse] if the answer was not valid.
+#[cfg(feature = "dot")]
+pub fn check_dot(remote: IpAddr) -> Result<Duration, CheckError> {
+    check_dot_via(remote, &Bind::default())
+}
+
+/// Works like [check_dot], but connects as set in `bind`, see [bind](super::bind).
+///
+/// # Errors
+///
+/// See [check_dot] and [Bind::apply].
+#[cfg(feature = "dot")]
+pub fn check_dot_via(remote: IpAddr, bind: &Bind) -> Result<Duration, CheckError> {
+    use std::io::{Read, Write};
+    use std::sync::Arc;
+
+    let roots = rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = rustls::pki_types::ServerName::IpAddress(remote.into());
+
+    let start = Instant::now();
+    let tcp = bind.tcp_connect(bind.remote(remote, DOT_PORT)?, TIMEOUT)?;
+    tcp.set_read_timeout(Some(TIMEOUT))?;
+    tcp.set_write_timeout(Some(TIMEOUT))?;
+    let connection = rustls::ClientConnection::new(Arc::new(config), name)?;
+    let mut stream = rustls::StreamOwned::new(connection, tcp);
+
+    // messages over TCP are prefixed with their length
+    let id = query_id();
+    let query = query(id);
+    let mut message = (query.len() as u16).to_be_bytes().to_vec();
+    message.extend_from_slice(&query);
+    stream.write_all(&message)?;
+
+    let mut len = [0; 2];
+    stream.read_exact(&mut len)?;
+    let mut response = vec![0; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response)?;
+    let latency = start.elapsed();
+
+    check_response(&response, id)?;
+    Ok(latency)
+}
+
+/// Returns a fresh ID for a query
+fn query_id() -> u16 {
+    // not security relevant, the answer is only checked for being an answer
+    (std::process::id() as u16) ^ (chrono::Utc::now().timestamp_subsec_nanos() as u16)
+}
+
+/// Builds a query for the A record of [QUERY_NAME] with `id`
+fn query(id: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(HEADER_LEN + QUERY_NAME.len() + 6);
+    packet.extend_from_slice(&id.to_be_bytes());
+    // flags: recursion desired
+    packet.extend_from_slice(&[0x01, 0x00]);
+    // one question, no answer, authority or additional records
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in QUERY_NAME.split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    // type A, class IN
+    packet.extend_from_slice(&[0, 1, 0, 1]);
+    packet
+}
+
+/// Checks that `response` answers the query with `id` without an error
+fn check_response(response: &[u8], id: u16) -> Result<(), CheckError> {
+    if response.len() < HEADER_LEN {
+        return Err(CheckError::BadDnsResponse("the response is too short"));
+    }
+    if response[0..2] != id.to_be_bytes() {
+        return Err(CheckError::BadDnsResponse("the response has the wrong id"));
+    }
+    if response[2] & 0x80 == 0 {
+        return Err(CheckError::BadDnsResponse("the response is not an answer"));
+    }
+    // SERVFAIL and REFUSED mean the resolver is not working, NXDOMAIN would still be an answer
+    match response[3] & 0x0f {
+        0 | 3 => Ok(()),
+        _ => Err(CheckError::BadDnsResponse(
+            "the server answered with an error",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_query_and_response() {
+        let query = query(0x1234);
+        assert_eq!(&query[0..2], &[0x12, 0x34]);
+        assert_eq!(query.len(), HEADER_LEN + "example.com".len() + 2 + 4);
+        assert_eq!(&query[HEADER_LEN..HEADER_LEN + 8], b"\x07example");
+
+        let mut response = query.clone();
+        response[2] |= 0x80;
+        assert!(check_response(&response, 0x1234).is_ok());
+        assert!(check_response(&response, 0x4321).is_err());
+        assert!(check_response(&query, 0x1234).is_err());
+        assert!(check_response(&response[..4], 0x1234).is_err());
+        // SERVFAIL
+        response[3] |= 0x02;
+        assert!(check_response(&response, 0x1234).is_err());
+    }
+}
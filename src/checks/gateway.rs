@@ -0,0 +1,151 @@
+//! Discovery of the default gateway.
+//!
+//! The [gateway check](crate::records::CheckType::Gateway) pings the router that all traffic to
+//! the internet goes through. If the gateway answers while the targets do not, the problem is
+//! upstream. If the gateway does not answer either, the local network is down.
+//!
+//! The gateways are read from the routing table of the kernel in `/proc/net/route` and
+//! `/proc/net/ipv6_route` on every round, so a changed network is picked up without a restart.
+//!
+//! IPv6 gateways are usually link local addresses, which can only be reached over a specific
+//! interface. Their [Target] gets the interface of the route as its zone, see
+//! [zones](super::bind#zones).
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use tracing::{debug, warn};
+
+use crate::config::Target;
+use crate::records::IpType;
+
+/// Routing table of the kernel for IPv4
+pub const ROUTES_V4: &str = "/proc/net/route";
+/// Routing table of the kernel for IPv6
+pub const ROUTES_V6: &str = "/proc/net/ipv6_route";
+/// Label of the [Targets](Target) of gateway checks
+pub const GATEWAY_LABEL: &str = "gateway";
+
+/// Flag of a route that goes through a gateway, `RTF_GATEWAY` in the kernel
+const RTF_GATEWAY: u32 = 0x2;
+
+/// Returns the default gateway for `ip_type` from the routing table of the kernel.
+///
+/// Returns [None] if there is no default route through a gateway or the routing table could
+/// not be read.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use netpulse::checks::gateway::default_gateway;
+/// use netpulse::records::IpType;
+///
+/// match default_gateway(IpType::V4) {
+///     Some(gateway) => println!("default gateway: {gateway}"),
+///     None => println!("no default gateway"),
+/// }
+/// ```
+pub fn default_gateway(ip_type: IpType) -> Option<IpAddr> {
+    let path = match ip_type {
+        IpType::V4 => ROUTES_V4,
+        IpType::V6 => ROUTES_V6,
+    };
+    let table = match std::fs::read_to_string(path) {
+        Ok(table) => table,
+        Err(err) => {
+            warn!("could not read the routing table {path}: {err}");
+            return None;
+        }
+    };
+    match ip_type {
+        IpType::V4 => parse_routes_v4(&table).map(IpAddr::from),
+        IpType::V6 => parse_routes_v6(&table).map(|(gateway, _)| IpAddr::from(gateway)),
+    }
+}
+
+/// Returns a [Target] for every default gateway.
+///
+/// Link local IPv6 gateways get the interface of their route as zone.
+pub fn gateway_targets() -> Vec<Target> {
+    let v4 = default_gateway(IpType::V4).map(|gateway| (gateway, None));
+    let v6 = match std::fs::read_to_string(ROUTES_V6) {
+        Ok(table) => parse_routes_v6(&table).map(|(gateway, interface)| {
+            let link_local = (gateway.segments()[0] & 0xffc0) == 0xfe80;
+            if link_local {
+                debug!("the gateway {gateway} is link local, checking it over {interface}");
+            }
+            (IpAddr::from(gateway), link_local.then_some(interface))
+        }),
+        Err(err) => {
+            warn!("could not read the routing table {ROUTES_V6}: {err}");
+            None
+        }
+    };
+    [v4, v6]
+        .into_iter()
+        .flatten()
+        .map(|(gateway, zone)| Target {
+            zone,
+            label: Some(GATEWAY_LABEL.to_string()),
+            ..Target::new(gateway)
+        })
+        .collect()
+}
+
+/// Finds the gateway of the default route in the format of [ROUTES_V4]
+///
+/// Addresses are hex in the byte order of the host, the first line is a header.
+fn parse_routes_v4(table: &str) -> Option<Ipv4Addr> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let destination = u32::from_str_radix(fields.get(1)?, 16).ok()?;
+        let gateway = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        let flags = u32::from_str_radix(fields.get(3)?, 16).ok()?;
+        (destination == 0 && flags & RTF_GATEWAY != 0 && gateway != 0)
+            .then(|| Ipv4Addr::from(gateway.to_ne_bytes()))
+    })
+}
+
+/// Finds the next hop of the default route and its interface in the format of [ROUTES_V6]
+///
+/// Addresses are 32 hex digits in network byte order, there is no header.
+fn parse_routes_v6(table: &str) -> Option<(Ipv6Addr, String)> {
+    table.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let destination = u128::from_str_radix(fields.first()?, 16).ok()?;
+        let prefix_len = u8::from_str_radix(fields.get(1)?, 16).ok()?;
+        let next_hop = u128::from_str_radix(fields.get(4)?, 16).ok()?;
+        let flags = u32::from_str_radix(fields.get(8)?, 16).ok()?;
+        let interface = fields.get(9)?;
+        (destination == 0 && prefix_len == 0 && flags & RTF_GATEWAY != 0 && next_hop != 0)
+            .then(|| (Ipv6Addr::from(next_hop), interface.to_string()))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_routes() {
+        let v4 = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t0002A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0
+eth0\t00000000\t0102A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0
+";
+        assert_eq!(parse_routes_v4(v4), Some(Ipv4Addr::new(192, 168, 2, 1)));
+        assert_eq!(
+            parse_routes_v4(&v4.lines().take(2).collect::<Vec<_>>().join("\n")),
+            None
+        );
+
+        let v6 = "\
+20010db8000000000000000000000000 40 00000000000000000000000000000000 00 00000000000000000000000000000000 00000100 00000001 00000000 00000001     eth0
+00000000000000000000000000000000 00 00000000000000000000000000000000 00 fe800000000000000000000000000001 00000400 00000002 00000000 00450003     eth0
+";
+        assert_eq!(
+            parse_routes_v6(v6),
+            Some(("fe80::1".parse().unwrap(), "eth0".to_string()))
+        );
+        assert_eq!(parse_routes_v6(v6.lines().next().unwrap()), None);
+    }
+}
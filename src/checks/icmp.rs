@@ -0,0 +1,372 @@
+//! Raw ICMP echo requests with control over the packet.
+//!
+//! The [EchoSocket] of this module sends echo requests of any size, can set the "don't fragment"
+//! bit and the time to live, and tells the time to live of the reply. The ICMP checks, see
+//! [ping_with](super::ping_with), the [MTU check](super::mtu) and the
+//! [traceroute](super::traceroute) use it.
+//!
+//! Like all raw sockets, this requires the `CAP_NET_RAW` capability.
+
+use std::io::{self, ErrorKind};
+use std::mem::MaybeUninit;
+use std::net::{IpAddr, SocketAddr};
+use std::os::fd::AsRawFd;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+use super::bind::Bind;
+
+/// Size of the IPv4 header without options
+pub const IPV4_HEADER_LEN: u16 = 20;
+/// Size of the IPv6 header without extension headers
+pub const IPV6_HEADER_LEN: u16 = 40;
+/// Size of the header of an ICMP echo request
+pub const ICMP_HEADER_LEN: u16 = 8;
+
+/// Sequence number of the next echo request, shared by all sockets of this process
+static NEXT_SEQ: AtomicU16 = AtomicU16::new(0);
+
+/// Answer to an echo request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply {
+    /// The target answered
+    Echo {
+        /// Round-trip time of the echo
+        rtt: Duration,
+        /// Time to live (hop limit for IPv6) of the reply when it arrived, if it is known
+        ttl: Option<u8>,
+    },
+    /// The packet was too big for the path and was not fragmented, because it had the "don't
+    /// fragment" bit set
+    TooBig,
+    /// The contained router dropped the packet, because its time to live ran out
+    TimeExceeded(IpAddr),
+    /// The contained host reported that the target can not be reached
+    Unreachable(IpAddr),
+}
+
+/// A raw ICMP socket that sends echo requests to one target.
+#[derive(Debug)]
+pub struct EchoSocket {
+    socket: Socket,
+    remote: IpAddr,
+    /// Where the requests are sent, `remote` with the scope ID of its zone, see [EchoSocket::bind]
+    destination: SocketAddr,
+    ident: u16,
+}
+
+impl EchoSocket {
+    /// Creates a raw ICMP socket for echo requests to `remote`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket could not be created, usually because `CAP_NET_RAW` is
+    /// missing.
+    pub fn new(remote: IpAddr) -> io::Result<Self> {
+        let socket = match remote {
+            IpAddr::V4(_) => Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?,
+            IpAddr::V6(_) => Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6))?,
+        };
+        let echo = Self {
+            socket,
+            remote,
+            destination: SocketAddr::new(remote, 0),
+            ident: std::process::id() as u16,
+        };
+        // raw ICMPv6 sockets do not get the IP header, the hop limit comes as ancillary data
+        if remote.is_ipv6() {
+            echo.set_option(libc::IPPROTO_IPV6, libc::IPV6_RECVHOPLIMIT, 1)?;
+        }
+        Ok(echo)
+    }
+
+    /// Binds the socket to an interface or source address, and sends the requests into the
+    /// zone of the target, see [bind](super::bind).
+    ///
+    /// # Errors
+    ///
+    /// See [Bind::apply] and [Bind::remote].
+    pub fn bind(&mut self, bind: &Bind) -> io::Result<()> {
+        self.destination = bind.remote(self.remote, 0)?;
+        bind.apply(&self.socket, self.remote)
+    }
+
+    /// Sets the "don't fragment" bit on all packets and ignores the cached path MTU.
+    ///
+    /// Packets that are too big for the path are then dropped instead of fragmented, see
+    /// [Reply::TooBig].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket option could not be set.
+    pub fn set_dont_fragment(&self) -> io::Result<()> {
+        match self.remote {
+            IpAddr::V4(_) => self.set_option(
+                libc::IPPROTO_IP,
+                libc::IP_MTU_DISCOVER,
+                libc::IP_PMTUDISC_PROBE,
+            ),
+            IpAddr::V6(_) => {
+                self.set_option(
+                    libc::IPPROTO_IPV6,
+                    libc::IPV6_MTU_DISCOVER,
+                    libc::IPV6_PMTUDISC_PROBE,
+                )?;
+                self.set_option(libc::IPPROTO_IPV6, libc::IPV6_DONTFRAG, 1)
+            }
+        }
+    }
+
+    fn set_option(
+        &self,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: libc::c_int,
+    ) -> io::Result<()> {
+        // SAFETY: the file descriptor is valid for the lifetime of the socket, and the option
+        // value is a c_int that lives until the call returns
+        let ret = unsafe {
+            libc::setsockopt(
+                self.socket.as_raw_fd(),
+                level,
+                name,
+                &value as *const libc::c_int as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Sets the time to live (hop limit for IPv6) of all packets.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket option could not be set.
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        match self.remote {
+            IpAddr::V4(_) => self.socket.set_ttl_v4(ttl),
+            IpAddr::V6(_) => self.socket.set_unicast_hops_v6(ttl),
+        }
+    }
+
+    /// Returns the size of the IP header of the packets of this socket.
+    pub fn ip_header_len(&self) -> u16 {
+        match self.remote {
+            IpAddr::V4(_) => IPV4_HEADER_LEN,
+            IpAddr::V6(_) => IPV6_HEADER_LEN,
+        }
+    }
+
+    /// Sends an echo request of `size` bytes, including the IP header, and waits up to
+    /// `timeout` for the answer.
+    ///
+    /// Returns [None] if no answer came in time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request could not be sent or the socket failed.
+    pub fn echo(&self, size: u16, timeout: Duration) -> io::Result<Option<Reply>> {
+        let seq = NEXT_SEQ.fetch_add(1, Ordering::Relaxed);
+        let payload_len = size.saturating_sub(self.ip_header_len() + ICMP_HEADER_LEN);
+        let request = self.request(seq, payload_len as usize);
+
+        let start = Instant::now();
+        let destination = SockAddr::from(self.destination);
+        match self.socket.send_to(&request, &destination) {
+            Ok(_) => (),
+            // the packet is bigger than the MTU of the interface
+            Err(e) if e.raw_os_error() == Some(libc::EMSGSIZE) => return Ok(Some(Reply::TooBig)),
+            Err(e) => return Err(e),
+        }
+
+        let deadline = start + timeout;
+        let mut buf = [MaybeUninit::<u8>::uninit(); 2048];
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Ok(None);
+            }
+            self.socket.set_read_timeout(Some(left))?;
+            let (packet, from, hop_limit) = match self.recv(&mut buf) {
+                Ok(received) => received,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            };
+            let from = from.as_socket().map_or(self.remote, |a| a.ip());
+            if let Some(mut reply) = self.parse(&packet, from, seq, start.elapsed()) {
+                if let Reply::Echo { ttl, .. } = &mut reply {
+                    *ttl = ttl.or(hop_limit);
+                }
+                return Ok(Some(reply));
+            }
+            // the raw socket gets all ICMP packets, this one was for someone else
+        }
+    }
+
+    /// Receives one packet into `buf`.
+    ///
+    /// Returns the packet, where it came from and the hop limit from the ancillary data, which
+    /// only IPv6 sockets get.
+    fn recv(&self, buf: &mut [MaybeUninit<u8>]) -> io::Result<(Vec<u8>, SockAddr, Option<u8>)> {
+        // room for a few control messages, aligned like a cmsghdr
+        let mut control = [0u64; 16];
+        let mut len = 0;
+        let mut hop_limit = None;
+        // SAFETY: try_init hands us storage for the address, which recvmsg fills in. All
+        // pointers in the msghdr point to buffers that live until after the call, and the
+        // control messages are only read within the length recvmsg reported.
+        let ((), from) = unsafe {
+            SockAddr::try_init(|storage, storage_len| {
+                let mut iov = libc::iovec {
+                    iov_base: buf.as_mut_ptr().cast(),
+                    iov_len: buf.len(),
+                };
+                let mut msg: libc::msghdr = std::mem::zeroed();
+                msg.msg_name = storage.cast();
+                msg.msg_namelen = *storage_len;
+                msg.msg_iov = &mut iov;
+                msg.msg_iovlen = 1;
+                msg.msg_control = control.as_mut_ptr().cast();
+                msg.msg_controllen = std::mem::size_of_val(&control) as _;
+                let received = libc::recvmsg(self.socket.as_raw_fd(), &mut msg, 0);
+                if received < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                len = received as usize;
+                *storage_len = msg.msg_namelen;
+
+                let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+                while !cmsg.is_null() {
+                    if (*cmsg).cmsg_level == libc::IPPROTO_IPV6
+                        && (*cmsg).cmsg_type == libc::IPV6_HOPLIMIT
+                    {
+                        let value = libc::CMSG_DATA(cmsg).cast::<libc::c_int>().read_unaligned();
+                        hop_limit = u8::try_from(value).ok();
+                    }
+                    cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+                }
+                Ok(())
+            })?
+        };
+        // SAFETY: recvmsg initialized the first len bytes
+        let packet = buf[..len.min(buf.len())]
+            .iter()
+            .map(|b| unsafe { b.assume_init() })
+            .collect();
+        Ok((packet, from, hop_limit))
+    }
+
+    /// Builds an echo request
+    fn request(&self, seq: u16, payload_len: usize) -> Vec<u8> {
+        let kind = match self.remote {
+            IpAddr::V4(_) => 8,
+            IpAddr::V6(_) => 128,
+        };
+        let mut packet = vec![kind, 0, 0, 0];
+        packet.extend_from_slice(&self.ident.to_be_bytes());
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend((0..payload_len).map(|i| i as u8));
+        // the kernel fills in the checksum of ICMPv6 itself
+        if self.remote.is_ipv4() {
+            let checksum = checksum(&packet);
+            packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+        }
+        packet
+    }
+
+    /// Returns the [Reply] if `packet` from `from` answers the echo request with `seq`
+    fn parse(&self, packet: &[u8], from: IpAddr, seq: u16, rtt: Duration) -> Option<Reply> {
+        let (icmp, inner_ip_len, ttl) = match self.remote {
+            IpAddr::V4(_) => {
+                // raw IPv4 sockets get the IP header as well
+                let ihl = (*packet.first()? & 0x0f) as usize * 4;
+                let icmp = packet.get(ihl..)?;
+                let embedded = icmp.get(ICMP_HEADER_LEN as usize..).unwrap_or_default();
+                let inner_ihl = embedded.first().map_or(0, |b| (b & 0x0f) as usize * 4);
+                (icmp, inner_ihl, packet.get(8).copied())
+            }
+            IpAddr::V6(_) => (packet, IPV6_HEADER_LEN as usize, None),
+        };
+        let kind = *icmp.first()?;
+        let code = *icmp.get(1)?;
+
+        let reply = match (self.remote, kind, code) {
+            (IpAddr::V4(_), 0, _) | (IpAddr::V6(_), 129, _) => {
+                return self.matches(icmp, seq).then_some(Reply::Echo { rtt, ttl });
+            }
+            // destination unreachable, fragmentation needed
+            (IpAddr::V4(_), 3, 4) | (IpAddr::V6(_), 2, _) => Reply::TooBig,
+            (IpAddr::V4(_), 3, _) | (IpAddr::V6(_), 1, _) => Reply::Unreachable(from),
+            (IpAddr::V4(_), 11, _) | (IpAddr::V6(_), 3, _) => Reply::TimeExceeded(from),
+            _ => return None,
+        };
+        // errors contain the start of our request after their own header
+        let embedded = icmp.get(ICMP_HEADER_LEN as usize + inner_ip_len..)?;
+        self.matches(embedded, seq).then_some(reply)
+    }
+
+    /// Returns true if the ICMP message has our identifier and `seq`
+    fn matches(&self, icmp: &[u8], seq: u16) -> bool {
+        icmp.len() >= ICMP_HEADER_LEN as usize
+            && icmp[4..6] == self.ident.to_be_bytes()
+            && icmp[6..8] == seq.to_be_bytes()
+    }
+}
+
+/// Calculates the internet checksum of `data`
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_checksum() {
+        // echo request with identifier 1 and sequence number 1, without payload
+        let packet = [8, 0, 0, 0, 0, 1, 0, 1];
+        assert_eq!(checksum(&packet), 0xf7fd);
+        let mut with_sum = packet;
+        with_sum[2..4].copy_from_slice(&0xf7fdu16.to_be_bytes());
+        assert_eq!(checksum(&with_sum), 0);
+    }
+
+    #[test]
+    fn test_parse_echo_reply() {
+        let socket = EchoSocket {
+            socket: Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap(),
+            remote: "192.0.2.1".parse().unwrap(),
+            destination: "192.0.2.1:0".parse().unwrap(),
+            ident: 7,
+        };
+        let rtt = Duration::from_millis(12);
+        // IPv4 header with a TTL of 57, then an echo reply with identifier 7 and sequence 3
+        let mut packet = vec![0x45, 0, 0, 28, 0, 0, 0, 0, 57, 1, 0, 0];
+        packet.extend([192, 0, 2, 1, 192, 0, 2, 2]);
+        packet.extend([0, 0, 0, 0, 0, 7, 0, 3]);
+
+        let from = socket.remote;
+        assert_eq!(
+            socket.parse(&packet, from, 3, rtt),
+            Some(Reply::Echo { rtt, ttl: Some(57) })
+        );
+        // the reply to another request
+        assert_eq!(socket.parse(&packet, from, 4, rtt), None);
+    }
+}
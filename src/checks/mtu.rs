@@ -0,0 +1,95 @@
+//! Detection of path MTU problems.
+//!
+//! A connection with a broken path MTU works for small packets, but big transfers hang: packets
+//! that are too big for some link on the way are dropped, and the sender is never told to make
+//! them smaller. A single ping does not notice that, because echoes are small.
+//!
+//! [path_mtu] sends echo requests with the "don't fragment" bit set in different sizes and finds
+//! the largest one that gets through. If the full [MAX_MTU] gets through, that is a single echo.
+//! Otherwise the size is narrowed down with a binary search.
+//!
+//! Requires the `mtu` feature and the `CAP_NET_RAW` capability.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use tracing::trace;
+
+use super::bind::Bind;
+use super::icmp::{EchoSocket, Reply};
+use crate::errors::CheckError;
+
+/// Largest packet size that is probed, the MTU of an ethernet link
+pub const MAX_MTU: u16 = 1500;
+/// Smallest packet size that is probed for IPv4, every IPv4 host must accept datagrams this big
+pub const MIN_MTU_V4: u16 = 576;
+/// Smallest packet size that is probed for IPv6, the minimum MTU of IPv6 links
+pub const MIN_MTU_V6: u16 = 1280;
+/// How long to wait for the answer to a single probe
+///
+/// Much shorter than [TIMEOUT](crate::TIMEOUT), because a search needs up to a dozen probes.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Finds the largest packet size that gets to `remote` and back without fragmentation.
+///
+/// The size includes the IP header, like an MTU.
+///
+/// # Errors
+///
+/// Returns [CheckError::NoReply] if not even a packet of the minimum size (see [MIN_MTU_V4] and
+/// [MIN_MTU_V6]) got through, and [CheckError::Io] if the raw socket could not be used.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::net::IpAddr;
+/// use netpulse::checks::mtu::path_mtu;
+///
+/// let addr: IpAddr = "1.1.1.1".parse().unwrap();
+/// match path_mtu(addr) {
+///     Ok(mtu) => println!("path MTU: {mtu}"),
+///     Err(e) => eprintln!("MTU check failed: {e}"),
+/// }
+/// ```
+pub fn path_mtu(remote: IpAddr) -> Result<u16, CheckError> {
+    path_mtu_via(remote, &Bind::default())
+}
+
+/// Works like [path_mtu], but sends the probes as set in `bind`, see [bind](super::bind).
+///
+/// # Errors
+///
+/// See [path_mtu] and [Bind::apply].
+pub fn path_mtu_via(remote: IpAddr, bind: &Bind) -> Result<u16, CheckError> {
+    let mut socket = EchoSocket::new(remote)?;
+    socket.bind(bind)?;
+    socket.set_dont_fragment()?;
+    let fits = |size: u16| -> Result<bool, CheckError> {
+        let reply = socket.echo(size, PROBE_TIMEOUT)?;
+        trace!("MTU probe of {size} bytes to {remote}: {reply:?}");
+        Ok(matches!(reply, Some(Reply::Echo { .. })))
+    };
+
+    if fits(MAX_MTU)? {
+        return Ok(MAX_MTU);
+    }
+    let mut low = match remote {
+        IpAddr::V4(_) => MIN_MTU_V4,
+        IpAddr::V6(_) => MIN_MTU_V6,
+    };
+    if !fits(low)? {
+        return Err(CheckError::NoReply);
+    }
+
+    // low always fits, high never does
+    let mut high = MAX_MTU;
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        if fits(middle)? {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(low)
+}
@@ -0,0 +1,171 @@
+//! NTP reachability check with SNTP.
+//!
+//! [check_ntp] sends a single SNTP query (RFC 4330) to an NTP server and measures the round-trip
+//! time and the offset of the local clock against the server. An NTP server that does not answer
+//! or answers as unsynchronized is another sign of a subtly broken connection, and a large
+//! offset means the clock of this host drifts.
+//!
+//! The clock is not adjusted, the check only measures.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use std::net::IpAddr;
+//! use netpulse::checks::ntp::check_ntp;
+//!
+//! let addr: IpAddr = "162.159.200.1".parse().unwrap();
+//! match check_ntp(addr) {
+//!     Ok((rtt, offset)) => println!("NTP round trip {rtt:?}, offset {offset}ms"),
+//!     Err(e) => eprintln!("NTP check failed: {e}"),
+//! }
+//! ```
+
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::bind::Bind;
+use crate::errors::CheckError;
+use crate::TIMEOUT;
+
+/// Port of NTP
+pub const NTP_PORT: u16 = 123;
+
+/// Size of an NTP packet without extensions
+const PACKET_LEN: usize = 48;
+/// Seconds between the NTP epoch (1900) and the unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+
+/// Sends an SNTP query to `remote` and waits for the answer.
+///
+/// Returns the round-trip time and the offset of the local clock against the server in
+/// milliseconds. A positive offset means the local clock is behind.
+///
+/// # Errors
+///
+/// Returns [CheckError::Io] if the query could not be sent or no answer came within
+/// [TIMEOUT], and [CheckError::BadNtpResponse] if the answer was not valid or the server is not
+/// synchronized.
+pub fn check_ntp(remote: IpAddr) -> Result<(Duration, i32), CheckError> {
+    check_ntp_via(remote, &Bind::default())
+}
+
+/// Works like [check_ntp], but sends the request as set in `bind`, see [bind](super::bind).
+///
+/// # Errors
+///
+/// See [check_ntp] and [Bind::apply].
+pub fn check_ntp_via(remote: IpAddr, bind: &Bind) -> Result<(Duration, i32), CheckError> {
+    let socket = bind.udp_socket(remote)?;
+    socket.set_read_timeout(Some(TIMEOUT))?;
+    socket.connect(bind.remote(remote, NTP_PORT)?)?;
+
+    let sent = SystemTime::now();
+    socket.send(&request(to_ntp(sent)))?;
+    let mut buf = [0; PACKET_LEN];
+    let len = socket.recv(&mut buf)?;
+    let received = SystemTime::now();
+
+    let (rtt, offset) = evaluate(&buf[..len], to_ntp(sent), to_ntp(received))?;
+    Ok((rtt, offset_millis(offset)))
+}
+
+/// Builds a client request with `transmit` as its transmit timestamp
+fn request(transmit: u64) -> [u8; PACKET_LEN] {
+    let mut packet = [0; PACKET_LEN];
+    // no leap second warning, version 4, mode 3 (client)
+    packet[0] = 0b00_100_011;
+    packet[40..48].copy_from_slice(&transmit.to_be_bytes());
+    packet
+}
+
+/// Checks the answer to a request sent at `t1` and received at `t4`, and calculates the
+/// round-trip time and the clock offset in nanoseconds
+fn evaluate(response: &[u8], t1: u64, t4: u64) -> Result<(Duration, i64), CheckError> {
+    if response.len() < PACKET_LEN {
+        return Err(CheckError::BadNtpResponse("the response is too short"));
+    }
+    let leap = response[0] >> 6;
+    let mode = response[0] & 0b111;
+    let stratum = response[1];
+    let timestamp = |idx: usize| {
+        u64::from_be_bytes(
+            response[idx..idx + 8]
+                .try_into()
+                .expect("slice has 8 bytes"),
+        )
+    };
+    if mode != 4 {
+        return Err(CheckError::BadNtpResponse(
+            "the response is not from a server",
+        ));
+    }
+    if timestamp(24) != t1 {
+        return Err(CheckError::BadNtpResponse(
+            "the response is for another request",
+        ));
+    }
+    // stratum 0 is a "kiss of death", the server refuses to serve us
+    if leap == 3 || stratum == 0 {
+        return Err(CheckError::BadNtpResponse("the server is not synchronized"));
+    }
+    let t2 = timestamp(32);
+    let t3 = timestamp(40);
+
+    let nanos = |ntp: u64| ntp_nanos(ntp) as i128;
+    let (t1, t2, t3, t4) = (nanos(t1), nanos(t2), nanos(t3), nanos(t4));
+    let rtt = ((t4 - t1) - (t3 - t2)).max(0);
+    let offset = ((t2 - t1) + (t3 - t4)) / 2;
+    Ok((Duration::from_nanos(rtt as u64), offset as i64))
+}
+
+/// Converts a [SystemTime] to an NTP timestamp, seconds since 1900 in the upper 32 bits and the
+/// fraction of a second in the lower 32 bits
+fn to_ntp(time: SystemTime) -> u64 {
+    let since_unix = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_unix.as_secs() + NTP_UNIX_OFFSET;
+    let fraction = (since_unix.subsec_nanos() as u64) * (1 << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Converts an NTP timestamp to nanoseconds since 1900
+fn ntp_nanos(ntp: u64) -> u128 {
+    let seconds = (ntp >> 32) as u128;
+    let fraction = (ntp & 0xffff_ffff) as u128;
+    seconds * 1_000_000_000 + fraction * 1_000_000_000 / (1 << 32)
+}
+
+/// Converts an offset in nanoseconds to whole milliseconds, saturating at the limits of [i32]
+fn offset_millis(offset: i64) -> i32 {
+    (offset as f64 / 1_000_000.0).round() as i32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let t1 = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        // the server clock is 500ms ahead, each way takes 10ms, the server takes 2ms
+        let t2 = t1 + Duration::from_millis(510);
+        let t3 = t2 + Duration::from_millis(2);
+        let t4 = t1 + Duration::from_millis(22);
+
+        let mut response = request(to_ntp(t3));
+        response[0] = 0b00_100_100;
+        response[1] = 2;
+        response[24..32].copy_from_slice(&to_ntp(t1).to_be_bytes());
+        response[32..40].copy_from_slice(&to_ntp(t2).to_be_bytes());
+
+        let (rtt, offset) = evaluate(&response, to_ntp(t1), to_ntp(t4)).unwrap();
+        assert_eq!((rtt.as_secs_f64() * 1000.0).round(), 20.0);
+        assert_eq!(offset_millis(offset), 500);
+
+        // not our request
+        assert!(evaluate(&response, to_ntp(t2), to_ntp(t4)).is_err());
+        // kiss of death
+        response[1] = 0;
+        assert!(evaluate(&response, to_ntp(t1), to_ntp(t4)).is_err());
+        assert!(evaluate(&response[..40], to_ntp(t1), to_ntp(t4)).is_err());
+    }
+}
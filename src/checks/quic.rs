@@ -0,0 +1,548 @@
+//! QUIC checks with an HTTP/3 `HEAD` request.
+//!
+//! Some networks break UDP on port 443 while TCP works. Browsers then fall back from HTTP/3 to
+//! HTTP/2, which hides the problem from the [HTTP checks](super::check_http), but makes pages
+//! slower. A QUIC check makes a QUIC handshake with the target and sends an HTTP/3 `HEAD`
+//! request, so a broken QUIC path shows up as its own
+//! [check type](crate::records::CheckType::Quic).
+//!
+//! The request goes to the address of the [Target], to the URL of the [Target] if it has one,
+//! and to `https://<address>/` otherwise. The certificate of the server is verified against the
+//! roots of [webpki_roots], for the host of the URL or the address.
+//!
+//! Only as much of HTTP/3 is implemented as the check needs: the request only uses the static
+//! table of QPACK, and of the response, only the `:status` is read.
+//!
+//! Requires the `quic` feature.
+
+use std::io;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::bind::Bind;
+use crate::config::Target;
+use crate::errors::CheckError;
+use crate::TIMEOUT;
+
+/// Default UDP port of HTTP/3
+pub const QUIC_PORT: u16 = 443;
+/// Protocol that is negotiated in the TLS handshake
+const ALPN: &[u8] = b"h3";
+/// Application error code for closing a connection without an error, `H3_NO_ERROR`
+const H3_NO_ERROR: u32 = 0x100;
+
+/// Frame type of HTTP/3 headers
+const FRAME_HEADERS: u64 = 0x01;
+/// Frame type of HTTP/3 settings
+const FRAME_SETTINGS: u64 = 0x04;
+/// Stream type of the HTTP/3 control stream
+const STREAM_CONTROL: u64 = 0x00;
+
+/// Index of `:authority` in the static table of QPACK
+const STATIC_AUTHORITY: u64 = 0;
+/// Index of `:path /` in the static table of QPACK
+const STATIC_PATH: u64 = 1;
+/// Index of `:method HEAD` in the static table of QPACK
+const STATIC_METHOD_HEAD: u64 = 18;
+/// Index of `:scheme https` in the static table of QPACK
+const STATIC_SCHEME_HTTPS: u64 = 23;
+/// Indices of `:status` in the static table of QPACK, with their value
+const STATIC_STATUS: [(u64, u16); 14] = [
+    (24, 103),
+    (25, 200),
+    (26, 304),
+    (27, 404),
+    (28, 503),
+    (63, 100),
+    (64, 204),
+    (65, 206),
+    (66, 302),
+    (67, 400),
+    (68, 403),
+    (69, 421),
+    (70, 425),
+    (71, 500),
+];
+
+/// Makes a QUIC check of `target`, bound as set in `bind`.
+///
+/// Returns the time from the start of the handshake until the response, and the HTTP status
+/// code of the response.
+///
+/// Starts a [tokio] runtime for the check, use [check_quic_async] on a runtime.
+///
+/// # Errors
+///
+/// Returns [CheckError] if:
+/// - The URL of the target is not an `https` URL
+/// - The handshake failed, for example because the certificate is not valid
+/// - The target did not answer within [TIMEOUT]
+/// - The response is not a valid HTTP/3 response
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use netpulse::checks::bind::Bind;
+/// use netpulse::checks::quic::check_quic_via;
+/// use netpulse::config::Target;
+///
+/// let target = Target::new("1.1.1.1".parse().unwrap());
+/// let (latency, status) = check_quic_via(&target, &Bind::default()).unwrap();
+/// println!("HTTP/3 status {status} after {latency:?}");
+/// ```
+pub fn check_quic_via(target: &Target, bind: &Bind) -> Result<(Duration, u16), CheckError> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(check_quic_async(target, bind))
+}
+
+/// Makes a QUIC check of `target`, bound as set in `bind`, on the current [tokio] runtime.
+///
+/// See [check_quic_via].
+///
+/// # Errors
+///
+/// See [check_quic_via].
+pub async fn check_quic_async(target: &Target, bind: &Bind) -> Result<(Duration, u16), CheckError> {
+    let request = Request::of_target(target)?;
+    match tokio::time::timeout(TIMEOUT, head(&request, target.address, bind)).await {
+        Ok(result) => result,
+        Err(_) => Err(CheckError::NoReply),
+    }
+}
+
+/// Connects to `remote` and sends the `HEAD` request
+async fn head(
+    request: &Request,
+    remote: IpAddr,
+    bind: &Bind,
+) -> Result<(Duration, u16), CheckError> {
+    let socket = bind.udp_socket(remote)?;
+    let runtime = quinn::default_runtime()
+        .ok_or_else(|| io::Error::other("QUIC checks need a tokio runtime"))?;
+    let endpoint = quinn::Endpoint::new(quinn::EndpointConfig::default(), None, socket, runtime)?;
+
+    let start = Instant::now();
+    let connection = endpoint
+        .connect_with(
+            client_config()?,
+            bind.remote(remote, request.port)?,
+            &request.server_name,
+        )?
+        .await?;
+
+    // the control stream must stay open for the whole connection
+    let mut control = connection.open_uni().await?;
+    let mut settings = Vec::new();
+    varint(&mut settings, STREAM_CONTROL);
+    varint(&mut settings, FRAME_SETTINGS);
+    varint(&mut settings, 0);
+    control
+        .write_all(&settings)
+        .await
+        .map_err(io::Error::from)?;
+
+    let (mut send, mut recv) = connection.open_bi().await?;
+    send.write_all(&request.headers())
+        .await
+        .map_err(io::Error::from)?;
+    send.finish().map_err(io::Error::from)?;
+
+    let mut response = Vec::new();
+    let mut buf = [0; 4096];
+    let status = loop {
+        if let Some(status) = response_status(&response)? {
+            break status;
+        }
+        match recv.read(&mut buf).await.map_err(io::Error::from)? {
+            Some(len) => response.extend_from_slice(&buf[..len]),
+            None => return Err(CheckError::BadHttp3Response("the response has no headers")),
+        }
+    };
+    let latency = start.elapsed();
+
+    connection.close(H3_NO_ERROR.into(), b"");
+    Ok((latency, status))
+}
+
+/// Returns the configuration of the QUIC client, which verifies the server against the roots
+/// of [webpki_roots] and negotiates HTTP/3
+fn client_config() -> Result<quinn::ClientConfig, CheckError> {
+    let roots = rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut tls = rustls::ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    tls.alpn_protocols = vec![ALPN.to_vec()];
+    let crypto =
+        quinn::crypto::rustls::QuicClientConfig::try_from(tls).map_err(io::Error::other)?;
+
+    let mut transport = quinn::TransportConfig::default();
+    transport.max_idle_timeout(Some(quinn::IdleTimeout::from(quinn::VarInt::from_u32(
+        TIMEOUT.as_millis() as u32,
+    ))));
+    let mut config = quinn::ClientConfig::new(Arc::new(crypto));
+    config.transport_config(Arc::new(transport));
+    Ok(config)
+}
+
+/// Where the `HEAD` request of a QUIC check goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Request {
+    /// Name the certificate of the server is verified for
+    server_name: String,
+    /// Value of the `:authority` of the request
+    authority: String,
+    /// UDP port of the server
+    port: u16,
+    /// Value of the `:path` of the request
+    path: String,
+}
+
+impl Request {
+    /// Returns the request for the URL of `target`, or for its address if it has no URL
+    fn of_target(target: &Target) -> Result<Self, CheckError> {
+        let Some(url) = &target.url else {
+            let server_name = target.address.to_string();
+            return Ok(Self {
+                authority: match target.address {
+                    IpAddr::V4(_) => server_name.clone(),
+                    IpAddr::V6(_) => format!("[{server_name}]"),
+                },
+                server_name,
+                port: QUIC_PORT,
+                path: "/".to_string(),
+            });
+        };
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("not an https url: {url}"),
+            )
+        };
+
+        let rest = url.strip_prefix("https://").ok_or_else(invalid)?;
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(index) if rest[index..].starts_with('/') => {
+                (&rest[..index], rest[index..].to_string())
+            }
+            Some(index) => (&rest[..index], format!("/{}", &rest[index..])),
+            None => (rest, "/".to_string()),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.ends_with(']') => {
+                (host, port.parse().map_err(|_| invalid())?)
+            }
+            _ => (authority, QUIC_PORT),
+        };
+        if host.is_empty() {
+            return Err(invalid().into());
+        }
+        Ok(Self {
+            server_name: host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string(),
+            authority: authority.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /// Encodes the HEADERS frame of the `HEAD` request
+    fn headers(&self) -> Vec<u8> {
+        // no dynamic table: the required insert count and the base are zero
+        let mut block = vec![0, 0];
+        indexed(&mut block, STATIC_METHOD_HEAD);
+        indexed(&mut block, STATIC_SCHEME_HTTPS);
+        literal(&mut block, STATIC_AUTHORITY, &self.authority);
+        if self.path == "/" {
+            indexed(&mut block, STATIC_PATH);
+        } else {
+            literal(&mut block, STATIC_PATH, &self.path);
+        }
+
+        let mut frame = Vec::new();
+        varint(&mut frame, FRAME_HEADERS);
+        varint(&mut frame, block.len() as u64);
+        frame.extend_from_slice(&block);
+        frame
+    }
+}
+
+/// Appends a field line that refers to an entry of the static table of QPACK
+fn indexed(block: &mut Vec<u8>, index: u64) {
+    prefixed_int(block, 0b1100_0000, 6, index);
+}
+
+/// Appends a field line with the name of an entry of the static table of QPACK and `value`
+fn literal(block: &mut Vec<u8>, index: u64, value: &str) {
+    prefixed_int(block, 0b0101_0000, 4, index);
+    prefixed_int(block, 0, 7, value.len() as u64);
+    block.extend_from_slice(value.as_bytes());
+}
+
+/// Appends `value` as an integer of QPACK, in the lowest `prefix` bits of `first` and the bytes
+/// after it
+fn prefixed_int(buf: &mut Vec<u8>, first: u8, prefix: u8, value: u64) {
+    let max = (1u64 << prefix) - 1;
+    if value < max {
+        buf.push(first | value as u8);
+        return;
+    }
+    buf.push(first | max as u8);
+    let mut rest = value - max;
+    while rest >= 0x80 {
+        buf.push(0x80 | (rest & 0x7f) as u8);
+        rest >>= 7;
+    }
+    buf.push(rest as u8);
+}
+
+/// Reads an integer of QPACK with a `prefix` of bits in the first byte of `bytes`.
+///
+/// Returns the integer and the number of bytes it took.
+fn read_prefixed_int(bytes: &[u8], prefix: u8) -> Option<(u64, usize)> {
+    let max = (1u64 << prefix) - 1;
+    let value = *bytes.first()? as u64 & max;
+    if value < max {
+        return Some((value, 1));
+    }
+    let mut value = max;
+    for (i, byte) in bytes.iter().enumerate().skip(1).take(8) {
+        value += ((byte & 0x7f) as u64) << (7 * (i - 1));
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Appends `value` as a variable-length integer of QUIC
+fn varint(buf: &mut Vec<u8>, value: u64) {
+    match value {
+        0..0x40 => buf.push(value as u8),
+        0x40..0x4000 => buf.extend_from_slice(&(value as u16 | 0x4000).to_be_bytes()),
+        0x4000..0x4000_0000 => buf.extend_from_slice(&(value as u32 | 0x8000_0000).to_be_bytes()),
+        _ => buf.extend_from_slice(&(value | 0xc000_0000_0000_0000).to_be_bytes()),
+    }
+}
+
+/// Reads a variable-length integer of QUIC.
+///
+/// Returns the integer and the number of bytes it took.
+fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let len = 1 << (bytes.first()? >> 6);
+    let mut value = (bytes[0] & 0x3f) as u64;
+    for byte in bytes.get(1..len)? {
+        value = (value << 8) | *byte as u64;
+    }
+    Some((value, len))
+}
+
+/// Returns the status of the response on the request stream, or [None] if its headers did not
+/// arrive yet
+fn response_status(stream: &[u8]) -> Result<Option<u16>, CheckError> {
+    let mut rest = stream;
+    loop {
+        let Some((frame_type, type_len)) = read_varint(rest) else {
+            return Ok(None);
+        };
+        let Some((len, len_len)) = read_varint(&rest[type_len..]) else {
+            return Ok(None);
+        };
+        let start = type_len + len_len;
+        let Some(payload) = rest.get(start..start + len as usize) else {
+            return Ok(None);
+        };
+        if frame_type == FRAME_HEADERS {
+            return status(payload).map(Some);
+        }
+        // frames of unknown types may come first, and must be ignored
+        rest = &rest[start + len as usize..];
+    }
+}
+
+/// Reads the `:status` of a field block, which is the first field of a response
+fn status(block: &[u8]) -> Result<u16, CheckError> {
+    const MALFORMED: CheckError = CheckError::BadHttp3Response("malformed headers");
+    let (_insert_count, len) = read_prefixed_int(block, 8).ok_or(MALFORMED)?;
+    let block = &block[len..];
+    let (_base, len) = read_prefixed_int(block, 7).ok_or(MALFORMED)?;
+    let line = &block[len..];
+    let first = *line.first().ok_or(MALFORMED)?;
+
+    let (index, value) = if first & 0b1000_0000 != 0 {
+        // indexed field line, must be a static one since there is no dynamic table
+        if first & 0b0100_0000 == 0 {
+            return Err(CheckError::BadHttp3Response("dynamic table in headers"));
+        }
+        let (index, _) = read_prefixed_int(line, 6).ok_or(MALFORMED)?;
+        (index, None)
+    } else if first & 0b1100_0000 == 0b0100_0000 {
+        // literal field line with a name of the static table
+        if first & 0b0001_0000 == 0 {
+            return Err(CheckError::BadHttp3Response("dynamic table in headers"));
+        }
+        let (index, len) = read_prefixed_int(line, 4).ok_or(MALFORMED)?;
+        let value = &line[len..];
+        let huffman = value.first().ok_or(MALFORMED)? & 0b1000_0000 != 0;
+        let (value_len, len) = read_prefixed_int(value, 7).ok_or(MALFORMED)?;
+        let value = value.get(len..len + value_len as usize).ok_or(MALFORMED)?;
+        let value = if huffman {
+            huffman_digits(value).ok_or(MALFORMED)?
+        } else {
+            String::from_utf8_lossy(value).into_owned()
+        };
+        (index, Some(value))
+    } else {
+        return Err(CheckError::BadHttp3Response(
+            "the first header is not the status",
+        ));
+    };
+
+    let Some((_, static_status)) = STATIC_STATUS.iter().find(|(i, _)| *i == index) else {
+        return Err(CheckError::BadHttp3Response(
+            "the first header is not the status",
+        ));
+    };
+    match value {
+        None => Ok(*static_status),
+        Some(value) => value.parse().map_err(|_| MALFORMED),
+    }
+}
+
+/// Decodes a Huffman coded value of only digits, like a status, see Appendix B of RFC 7541.
+///
+/// `0` to `2` have 5 bit codes from `0b00000`, `3` to `9` have 6 bit codes from `0b011001`.
+fn huffman_digits(bytes: &[u8]) -> Option<String> {
+    let mut digits = String::new();
+    let mut bits = 0u32;
+    let mut len = 0;
+    for byte in bytes {
+        bits = (bits << 8) | *byte as u32;
+        len += 8;
+        while len >= 5 {
+            let code = (bits >> (len - 5)) & 0b1_1111;
+            if code <= 2 {
+                digits.push(char::from(b'0' + code as u8));
+                len -= 5;
+            } else if len >= 6
+                && (0b01_1001..=0b01_1111).contains(&((bits >> (len - 6)) & 0b11_1111))
+            {
+                let code = (bits >> (len - 6)) & 0b11_1111;
+                digits.push(char::from(b'3' + (code - 0b01_1001) as u8));
+                len -= 6;
+            } else {
+                break;
+            }
+        }
+        if len > 16 {
+            return None;
+        }
+        bits &= (1 << len) - 1;
+    }
+    // the rest is padding of at most 7 one bits
+    (len < 8 && bits == (1 << len) - 1).then_some(digits)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let target = Target::new("2606:4700:4700::1111".parse().unwrap());
+        let request = Request::of_target(&target).unwrap();
+        assert_eq!(request.server_name, "2606:4700:4700::1111");
+        assert_eq!(request.authority, "[2606:4700:4700::1111]");
+        assert_eq!(request.port, QUIC_PORT);
+
+        let mut target = Target::new("192.0.2.10".parse().unwrap());
+        target.url = Some("https://my-service.example:8443/health?full#top".to_string());
+        let request = Request::of_target(&target).unwrap();
+        assert_eq!(
+            request,
+            Request {
+                server_name: "my-service.example".to_string(),
+                authority: "my-service.example:8443".to_string(),
+                port: 8443,
+                path: "/health?full".to_string(),
+            }
+        );
+
+        target.url = Some("https://[2001:db8::1]".to_string());
+        let request = Request::of_target(&target).unwrap();
+        assert_eq!(request.server_name, "2001:db8::1");
+        assert_eq!(request.port, QUIC_PORT);
+        assert_eq!(request.path, "/");
+
+        target.url = Some("http://my-service.example".to_string());
+        assert!(Request::of_target(&target).is_err());
+
+        // :method HEAD, :scheme https, :authority "a.b", :path /
+        let request = Request {
+            server_name: "a.b".to_string(),
+            authority: "a.b".to_string(),
+            port: QUIC_PORT,
+            path: "/".to_string(),
+        };
+        assert_eq!(
+            request.headers(),
+            [0x01, 0x0a, 0, 0, 0xd2, 0xd7, 0x50, 0x03, b'a', b'.', b'b', 0xc1]
+        );
+    }
+
+    #[test]
+    fn test_integers() {
+        for value in [0, 63, 64, 16383, 16384, 1 << 30, u32::MAX as u64 * 4] {
+            let mut buf = Vec::new();
+            varint(&mut buf, value);
+            assert_eq!(read_varint(&buf), Some((value, buf.len())));
+        }
+        for (prefix, value) in [(6, 5), (6, 63), (6, 71), (4, 15), (7, 1337)] {
+            let mut buf = Vec::new();
+            prefixed_int(&mut buf, 0, prefix, value);
+            assert_eq!(read_prefixed_int(&buf, prefix), Some((value, buf.len())));
+        }
+        let mut buf = Vec::new();
+        prefixed_int(&mut buf, 0, 5, 1337);
+        assert_eq!(buf, [0x1f, 0x9a, 0x0a]); // example of RFC 7541
+    }
+
+    #[test]
+    fn test_response_status() {
+        // indexed :status 200
+        assert_eq!(
+            response_status(&[0x01, 0x03, 0, 0, 0xd9]).unwrap(),
+            Some(200)
+        );
+        // indexed :status 500, after a reserved frame
+        assert_eq!(
+            response_status(&[0x21, 0x01, 0xff, 0x01, 0x04, 0, 0, 0xff, 0x08]).unwrap(),
+            Some(500)
+        );
+        // literal :status 418 with the name of :status 200
+        assert_eq!(
+            response_status(&[0x01, 0x08, 0, 0, 0x5f, 0x0a, 0x03, b'4', b'1', b'8']).unwrap(),
+            Some(418)
+        );
+        // Huffman coded :status 302
+        assert_eq!(
+            response_status(&[0x01, 0x07, 0, 0, 0x5f, 0x0a, 0x82, 0x64, 0x02]).unwrap(),
+            Some(302)
+        );
+        // not complete yet
+        assert_eq!(response_status(&[0x01, 0x03, 0, 0]).unwrap(), None);
+        // :path / first
+        assert!(response_status(&[0x01, 0x03, 0, 0, 0xc1]).is_err());
+
+        assert_eq!(huffman_digits(&[0x10, 0x01]).as_deref(), Some("200"));
+        assert_eq!(huffman_digits(&[0x64, 0x02]).as_deref(), Some("302")); // no padding
+        assert_eq!(huffman_digits(&[0x64, 0x02, 0xff]), None);
+    }
+}
@@ -0,0 +1,91 @@
+//! Bounded traceroute to find where the path to a target breaks.
+//!
+//! [traceroute] sends echo requests with increasing time to live. Every router on the way drops
+//! the request whose time to live runs out with it and usually answers with an ICMP error, which
+//! reveals its address. The last router that answered is where the path dies.
+//!
+//! The trace is bounded: it stops after [max hops](crate::config::TraceConfig::max_hops), and
+//! after [SILENT_HOPS] hops in a row did not answer, as nothing behind those is likely to
+//! answer either.
+//!
+//! Requires the `traceroute` feature and the `CAP_NET_RAW` capability.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use tracing::trace;
+
+use super::bind::Bind;
+use super::icmp::{EchoSocket, Reply};
+use crate::errors::CheckError;
+use crate::records::trace::Trace;
+
+/// How long to wait for the answer of a single hop
+pub const HOP_TIMEOUT: Duration = Duration::from_secs(1);
+/// After how many hops in a row without an answer the trace stops
+pub const SILENT_HOPS: usize = 3;
+/// Packet size of the echo requests, small enough for every path
+const PROBE_SIZE: u16 = 64;
+
+/// Traces the path to `remote` over at most `max_hops` hops.
+///
+/// The [Trace] gets the current time as its timestamp.
+///
+/// # Errors
+///
+/// Returns [CheckError::Io] if the raw socket could not be used.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::net::IpAddr;
+/// use netpulse::checks::traceroute::traceroute;
+///
+/// let addr: IpAddr = "1.1.1.1".parse().unwrap();
+/// let trace = traceroute(addr, 16).unwrap();
+/// println!("last answer from {:?}", trace.last_hop());
+/// ```
+pub fn traceroute(remote: IpAddr, max_hops: u8) -> Result<Trace, CheckError> {
+    traceroute_via(remote, max_hops, &Bind::default())
+}
+
+/// Works like [traceroute], but sends the echo requests as set in `bind`, see
+/// [bind](super::bind).
+///
+/// # Errors
+///
+/// See [traceroute] and [Bind::apply].
+pub fn traceroute_via(remote: IpAddr, max_hops: u8, bind: &Bind) -> Result<Trace, CheckError> {
+    let mut socket = EchoSocket::new(remote)?;
+    socket.bind(bind)?;
+    let mut hops: Vec<Option<IpAddr>> = Vec::new();
+    let mut reached = false;
+
+    for ttl in 1..=max_hops {
+        socket.set_ttl(ttl as u32)?;
+        let reply = socket.echo(PROBE_SIZE, HOP_TIMEOUT)?;
+        trace!("trace to {remote}, hop {ttl}: {reply:?}");
+        match reply {
+            Some(Reply::Echo { .. }) => {
+                hops.push(Some(remote));
+                reached = true;
+                break;
+            }
+            Some(Reply::TimeExceeded(router)) => hops.push(Some(router)),
+            Some(Reply::Unreachable(router)) => {
+                hops.push(Some(router));
+                break;
+            }
+            Some(Reply::TooBig) | None => hops.push(None),
+        }
+        if hops.len() >= SILENT_HOPS && hops.iter().rev().take(SILENT_HOPS).all(Option::is_none) {
+            break;
+        }
+    }
+
+    // the silent hops at the end tell nothing
+    while hops.last() == Some(&None) {
+        hops.pop();
+    }
+    Ok(Trace::new(chrono::Utc::now(), remote, hops, reached))
+}
@@ -2,11 +2,12 @@
 //!
 //! This module provides shared utilities used by both the netpulse reader
 //! and netpulsed daemon binaries, including:
-//! - Privilege checks
+//! - Privilege checks and dropping privileges, see [drop_privileges]
 //! - Logging setup
 //! - PID file management
 //! - Process management
 //! - User interaction
+//! - Completion scripts for the shells, see [completions]
 //!
 //! # Exits
 //!
@@ -17,6 +18,11 @@
 //! Logging can be configured via the `NETPULSE_LOG_LEVEL` environment variable.
 //! Valid levels are: TRACE, DEBUG, INFO, WARN, ERROR
 //!
+//! Where the logs go is configured via the `NETPULSE_LOG_BACKEND` environment variable, see
+//! [LogBackend]. With `journald`, the fields of log events (like `check_type`, `target` and
+//! `latency` of made checks) are kept as journal fields, so they can be filtered with
+//! `journalctl CHECK_TYPE=HTTP(S)`.
+//!
 //! # Examples
 //!
 //! ```rust,no_run
@@ -34,7 +40,7 @@
 //! }
 //! ```
 use std::fmt::{Display, Write};
-use std::io::{self, Write as _};
+use std::io::{self, IsTerminal, Write as _};
 use std::process::Command;
 use std::str::FromStr;
 
@@ -43,8 +49,46 @@ use sysinfo::{Pid, System};
 use tracing::{debug, error, trace, warn};
 use tracing_subscriber::FmtSubscriber;
 
+#[cfg(unix)]
+use crate::errors::RunError;
+
+pub mod completions;
+
 /// Environment variable name for configuring log level
 pub const ENV_LOG_LEVEL: &str = "NETPULSE_LOG_LEVEL";
+/// Environment variable name for configuring where logs are written, see [LogBackend]
+pub const ENV_LOG_BACKEND: &str = "NETPULSE_LOG_BACKEND";
+
+/// Where log events are written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogBackend {
+    /// Human readable text on stdout
+    #[default]
+    Stdout,
+    /// Human readable text on stdout with the local time, colored if stdout is a terminal
+    ///
+    /// Meant for watching the daemon in a terminal, like with `netpulsed --foreground`.
+    Pretty,
+    /// Structured records to the systemd journal
+    ///
+    /// Requires the `journald` feature.
+    Journald,
+}
+
+impl FromStr for LogBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stdout" => Ok(Self::Stdout),
+            "pretty" => Ok(Self::Pretty),
+            "journald" => Ok(Self::Journald),
+            _ => Err(format!(
+                "unknown log backend '{s}', must be one of 'stdout', 'pretty', 'journald'"
+            )),
+        }
+    }
+}
 
 /// a hacky way to make [Command] [Display]
 trait CommandExt {
@@ -84,6 +128,7 @@ impl CommandExt for Command {
 /// # Exits
 ///
 /// Exits the program with status code 1 if not running as root.
+#[cfg(unix)]
 pub fn root_guard() {
     if !nix::unistd::getuid().is_root() {
         eprintln!("This needs to be run as root");
@@ -91,6 +136,60 @@ pub fn root_guard() {
     }
 }
 
+/// Drops the root privileges of the process to `user`, but keeps `CAP_NET_RAW`.
+///
+/// Without `CAP_NET_RAW`, no raw sockets can be opened, which ICMP, MTU and gateway checks and
+/// traceroutes need. The capability is kept in the permitted, effective and inheritable sets and
+/// raised in the ambient set, so it also survives executing other programs. All other
+/// capabilities are gone afterwards.
+///
+/// Capabilities belong to threads, so this must be called before any threads are spawned, which
+/// then inherit them.
+///
+/// # Errors
+///
+/// Returns [RunError] if `user` does not exist or the user or capabilities could not be changed.
+///
+/// # Platforms
+///
+/// Only Linux has capabilities. On other unix systems, the privileges are just dropped.
+#[cfg(unix)]
+pub fn drop_privileges(user: &str) -> Result<(), RunError> {
+    use nix::unistd;
+
+    let user = unistd::User::from_name(user)
+        .map_err(io::Error::from)?
+        .ok_or_else(|| RunError::NoSuchUser(user.to_string()))?;
+
+    // without this, setuid clears the permitted capabilities as well
+    #[cfg(target_os = "linux")]
+    caps::securebits::set_keepcaps(true)?;
+    unistd::setgroups(&[user.gid]).map_err(io::Error::from)?;
+    unistd::setgid(user.gid).map_err(io::Error::from)?;
+    unistd::setuid(user.uid).map_err(io::Error::from)?;
+    #[cfg(target_os = "linux")]
+    keep_net_raw()?;
+    debug!(
+        "dropped privileges to {} (uid {}), keeping CAP_NET_RAW",
+        user.name, user.uid
+    );
+    Ok(())
+}
+
+/// Limits the capabilities to `CAP_NET_RAW` after [drop_privileges] changed the user.
+#[cfg(target_os = "linux")]
+fn keep_net_raw() -> Result<(), RunError> {
+    use caps::{CapSet, Capability, CapsHashSet};
+
+    caps::securebits::set_keepcaps(false)?;
+    let net_raw: CapsHashSet = [Capability::CAP_NET_RAW].into_iter().collect();
+    caps::set(None, CapSet::Permitted, &net_raw)?;
+    caps::set(None, CapSet::Effective, &net_raw)?;
+    caps::set(None, CapSet::Inheritable, &net_raw)?;
+    caps::raise(None, CapSet::Ambient, Capability::CAP_NET_RAW)?;
+    Ok(())
+}
+
 /// Displays program usage information and exits.
 ///
 /// Formats and prints the usage information using the provided program name
@@ -114,7 +213,10 @@ pub fn print_usage(program: &str, opts: Options) -> ! {
 ///
 /// The log level can be overridden by setting the [ENV_LOG_LEVEL] environment variable.
 /// Logging is configured without timestamps (relies on systemd/journald for timing)
-/// and without module targets for cleaner output.
+/// and without module targets for cleaner output. [LogBackend::Pretty] adds the local time.
+///
+/// The [LogBackend] is selected with the [ENV_LOG_BACKEND] environment variable. If the journal
+/// is requested but not available, logs go to stdout.
 ///
 /// # Arguments
 ///
@@ -123,7 +225,7 @@ pub fn print_usage(program: &str, opts: Options) -> ! {
 /// # Exits
 ///
 /// Exits with status code 1 if:
-/// - Invalid log level specified in environment variable
+/// - Invalid log level or backend specified in environment variable
 /// - Failed to set up logging system
 pub fn init_logging(level: tracing::Level) {
     let level: tracing::Level = match std::env::var(ENV_LOG_LEVEL) {
@@ -137,6 +239,36 @@ pub fn init_logging(level: tracing::Level) {
             Ok(ll) => ll,
         },
     };
+    let backend: LogBackend = match std::env::var(ENV_LOG_BACKEND) {
+        Err(_) => LogBackend::default(),
+        Ok(raw) => match LogBackend::from_str(&raw) {
+            Err(e) => {
+                eprintln!("Bad log backend was given with the environment variable '{ENV_LOG_BACKEND}': {e}");
+                std::process::exit(1)
+            }
+            Ok(b) => b,
+        },
+    };
+
+    if backend == LogBackend::Journald && init_journald(level) {
+        trace!("logging to journald initialized with level {level}");
+        return;
+    }
+
+    if backend == LogBackend::Pretty {
+        let subscriber = FmtSubscriber::builder()
+            .with_max_level(level)
+            .with_timer(tracing_subscriber::fmt::time::ChronoLocal::new(
+                "%H:%M:%S%.3f".to_string(),
+            ))
+            .with_ansi(io::stdout().is_terminal())
+            .with_target(false)
+            .finish();
+        tracing::subscriber::set_global_default(subscriber)
+            .expect("setting default subscriber failed");
+        trace!("pretty logging initialized with level {level}");
+        return;
+    }
 
     // a builder for `FmtSubscriber`.
     let subscriber = FmtSubscriber::builder()
@@ -155,6 +287,33 @@ pub fn init_logging(level: tracing::Level) {
     trace!("logging initialized with level {level}");
 }
 
+/// Sets up logging to the systemd journal, returns false if that is not possible.
+#[cfg(feature = "journald")]
+fn init_journald(level: tracing::Level) -> bool {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let layer = match tracing_journald::layer() {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Could not connect to journald, logging to stdout instead: {e}");
+            return false;
+        }
+    };
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_subscriber::filter::LevelFilter::from_level(level))
+        .with(layer);
+
+    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+    true
+}
+
+/// Sets up logging to the systemd journal, returns false if that is not possible.
+#[cfg(not(feature = "journald"))]
+fn init_journald(_level: tracing::Level) -> bool {
+    eprintln!("netpulse was built without the journald feature, logging to stdout instead");
+    false
+}
+
 /// Prompts the user for confirmation with a custom message.
 /// Returns true if the user confirms, false otherwise.
 ///
@@ -252,6 +411,12 @@ pub fn getpid_running() -> Option<Pid> {
         .filter(|p| p.pid().as_u32() != pid_of_current_process) // ignore the currently running
         // process
         .collect();
+    // in user mode, the daemons of other users are none of our business
+    #[cfg(unix)]
+    if crate::paths::user_mode() {
+        let current_uid = nix::unistd::getuid().as_raw();
+        processes.retain(|p| p.user_id().is_some_and(|uid| **uid == current_uid));
+    }
 
     if processes.is_empty() {
         None
@@ -0,0 +1,403 @@
+//! Completion scripts for the shells, generated from the definitions of the options of a binary.
+//!
+//! A binary defines its options once as a table of [OptDefs](OptDef). The same table builds its
+//! [Options], see [options], and its completion scripts, see [generate], so they can not
+//! disagree. The value of an option is completed with:
+//! - file names, if its hint ends with `FILE`, `STORE` or `PATH`, like `--store [LABEL=]STORE`
+//! - fixed choices, if its hint lists them in lowercase, like `--summary weekly|monthly`
+//! - nothing otherwise
+//!
+//! # Example
+//!
+//! ```rust
+//! use netpulse::common::completions::{generate, options, OptDef, Shell};
+//!
+//! const OPTIONS: &[OptDef] = &[
+//!     OptDef::flag("h", "help", "print this help menu"),
+//!     OptDef::opt("", "summary", "summarize per period", "weekly|monthly"),
+//! ];
+//!
+//! let opts = options(OPTIONS);
+//! assert!(opts.parse(["--summary", "weekly"]).is_ok());
+//!
+//! let script = generate("netpulse", OPTIONS, Shell::Fish);
+//! assert!(script.contains("complete -c netpulse -l summary -x -a 'weekly monthly'"));
+//! ```
+
+use std::fmt::{Display, Write};
+use std::str::FromStr;
+
+use getopts::Options;
+
+/// Shells that completion scripts can be [generated](generate) for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Shell {
+    /// GNU Bash, the script is meant for `bash-completion`
+    Bash,
+    /// Z shell, the script is meant for a directory in `$fpath`
+    Zsh,
+    /// fish, the script is meant for `~/.config/fish/completions`
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            _ => Err(format!(
+                "unknown shell '{s}', must be one of 'bash', 'zsh', 'fish'"
+            )),
+        }
+    }
+}
+
+impl Display for Shell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bash => write!(f, "bash"),
+            Self::Zsh => write!(f, "zsh"),
+            Self::Fish => write!(f, "fish"),
+        }
+    }
+}
+
+/// How an option takes a value, like the methods of [Options] that add it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Arity {
+    /// No value, see [Options::optflag]
+    Flag,
+    /// One value, see [Options::optopt]
+    Opt,
+    /// An optional value, see [Options::optflagopt]
+    FlagOpt,
+    /// One value, the option can be given multiple times, see [Options::optmulti]
+    Multi,
+}
+
+/// What the value of an option is completed with
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    /// The option is a flag without a value
+    Flag,
+    /// Anything, nothing is suggested
+    Any,
+    /// File names
+    Files,
+    /// One of these choices
+    Choices(Vec<String>),
+}
+
+/// The definition of an option, see the [module docs](self)
+///
+/// The fields are the arguments of the methods of [Options] that add the option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OptDef {
+    /// Short name without the `-`, or empty
+    pub short: &'static str,
+    /// Long name without the `--`, or empty
+    pub long: &'static str,
+    /// Description for the usage and the completions
+    pub desc: &'static str,
+    /// Name of the value, like `FILE`, empty for flags
+    pub hint: &'static str,
+    /// How the option takes a value
+    pub arity: Arity,
+}
+
+impl OptDef {
+    /// Defines a flag without a value.
+    pub const fn flag(short: &'static str, long: &'static str, desc: &'static str) -> Self {
+        Self {
+            short,
+            long,
+            desc,
+            hint: "",
+            arity: Arity::Flag,
+        }
+    }
+
+    /// Defines an option with a value.
+    pub const fn opt(
+        short: &'static str,
+        long: &'static str,
+        desc: &'static str,
+        hint: &'static str,
+    ) -> Self {
+        Self {
+            short,
+            long,
+            desc,
+            hint,
+            arity: Arity::Opt,
+        }
+    }
+
+    /// Defines an option with an optional value.
+    pub const fn flag_opt(
+        short: &'static str,
+        long: &'static str,
+        desc: &'static str,
+        hint: &'static str,
+    ) -> Self {
+        Self {
+            short,
+            long,
+            desc,
+            hint,
+            arity: Arity::FlagOpt,
+        }
+    }
+
+    /// Defines an option with a value that can be given multiple times.
+    pub const fn multi(
+        short: &'static str,
+        long: &'static str,
+        desc: &'static str,
+        hint: &'static str,
+    ) -> Self {
+        Self {
+            short,
+            long,
+            desc,
+            hint,
+            arity: Arity::Multi,
+        }
+    }
+
+    /// Tells what the value of the option is completed with, see the [module docs](self).
+    fn value(&self) -> Value {
+        let hint = self.hint;
+        if self.arity == Arity::Flag {
+            return Value::Flag;
+        }
+        if ["FILE", "STORE", "PATH"].iter().any(|s| hint.ends_with(s)) {
+            return Value::Files;
+        }
+        let choices: Vec<&str> = hint.split('|').collect();
+        if choices.len() > 1
+            && choices.iter().all(|c| {
+                !c.is_empty()
+                    && c.chars()
+                        .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '-')
+            })
+        {
+            return Value::Choices(choices.into_iter().map(String::from).collect());
+        }
+        Value::Any
+    }
+
+    /// Returns the short and long forms of the option, like `-s` and `--store`.
+    fn forms(&self) -> Vec<String> {
+        let short = (!self.short.is_empty()).then(|| format!("-{}", self.short));
+        let long = (!self.long.is_empty()).then(|| format!("--{}", self.long));
+        short.into_iter().chain(long).collect()
+    }
+}
+
+/// Builds the [Options] of a binary from the definitions of its options.
+pub fn options(defs: &[OptDef]) -> Options {
+    let mut opts = Options::new();
+    for def in defs {
+        match def.arity {
+            Arity::Flag => opts.optflag(def.short, def.long, def.desc),
+            Arity::Opt => opts.optopt(def.short, def.long, def.desc, def.hint),
+            Arity::FlagOpt => opts.optflagopt(def.short, def.long, def.desc, def.hint),
+            Arity::Multi => opts.optmulti(def.short, def.long, def.desc, def.hint),
+        };
+    }
+    opts
+}
+
+/// Generates a completion script of `shell` for `program` with the options defined by `defs`.
+///
+/// The script is meant to be written to a file, see [Shell] for where each shell looks for
+/// them.
+pub fn generate(program: &str, defs: &[OptDef], shell: Shell) -> String {
+    let result = match shell {
+        Shell::Bash => bash(program, defs),
+        Shell::Zsh => zsh(program, defs),
+        Shell::Fish => fish(program, defs),
+    };
+    result.expect("could not write to a string")
+}
+
+fn bash(program: &str, specs: &[OptDef]) -> Result<String, std::fmt::Error> {
+    let function = format!("_{}", program.replace('-', "_"));
+    let mut buf = String::new();
+    writeln!(
+        buf,
+        "# bash completion for {program}, generated by `{program} --completions bash`"
+    )?;
+    writeln!(buf, "{function}() {{")?;
+    writeln!(buf, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(buf, "    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+    writeln!(buf, "    case \"$prev\" in")?;
+    for spec in specs {
+        let reply = match spec.value() {
+            Value::Flag => continue,
+            Value::Any => "COMPREPLY=()".to_string(),
+            Value::Files => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+            Value::Choices(choices) => format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                choices.join(" ")
+            ),
+        };
+        writeln!(buf, "        {})", spec.forms().join("|"))?;
+        writeln!(buf, "            {reply}")?;
+        writeln!(buf, "            return")?;
+        writeln!(buf, "            ;;")?;
+    }
+    writeln!(buf, "    esac")?;
+    let all: Vec<String> = specs.iter().flat_map(OptDef::forms).collect();
+    writeln!(
+        buf,
+        "    COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        all.join(" ")
+    )?;
+    writeln!(buf, "}}")?;
+    writeln!(buf, "complete -F {function} {program}")?;
+    Ok(buf)
+}
+
+fn zsh(program: &str, specs: &[OptDef]) -> Result<String, std::fmt::Error> {
+    // inside of single quotes and the brackets of _arguments
+    let escape = |s: &str| {
+        s.replace('\\', "\\\\")
+            .replace('[', "\\[")
+            .replace(']', "\\]")
+            .replace(':', "\\:")
+            .replace('\'', "'\\''")
+    };
+    let mut buf = String::new();
+    writeln!(buf, "#compdef {program}")?;
+    writeln!(
+        buf,
+        "# zsh completion for {program}, generated by `{program} --completions zsh`"
+    )?;
+    writeln!(buf)?;
+    write!(buf, "_arguments -s")?;
+    for spec in specs {
+        // an optional value is marked by a second colon
+        let colon = if spec.arity == Arity::FlagOpt {
+            "::"
+        } else {
+            ":"
+        };
+        let action = match spec.value() {
+            Value::Flag => String::new(),
+            Value::Any => format!("{colon}{}:", escape(spec.hint)),
+            Value::Files => format!("{colon}{}:_files", escape(spec.hint)),
+            Value::Choices(choices) => {
+                format!("{colon}{}:({})", escape(spec.hint), choices.join(" "))
+            }
+        };
+        for form in spec.forms() {
+            write!(buf, " \\\n    '{form}[{}]{action}'", escape(spec.desc))?;
+        }
+    }
+    writeln!(buf)?;
+    Ok(buf)
+}
+
+fn fish(program: &str, specs: &[OptDef]) -> Result<String, std::fmt::Error> {
+    // inside of single quotes
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('\'', "\\'");
+    let mut buf = String::new();
+    writeln!(
+        buf,
+        "# fish completion for {program}, generated by `{program} --completions fish`"
+    )?;
+    for spec in specs {
+        write!(buf, "complete -c {program}")?;
+        if !spec.short.is_empty() {
+            write!(buf, " -s {}", spec.short)?;
+        }
+        if !spec.long.is_empty() {
+            write!(buf, " -l {}", spec.long)?;
+        }
+        match spec.value() {
+            Value::Flag => (),
+            Value::Any => write!(buf, " -x")?,
+            Value::Files => write!(buf, " -r -F")?,
+            Value::Choices(choices) => write!(buf, " -x -a '{}'", choices.join(" "))?,
+        }
+        writeln!(buf, " -d '{}'", escape(spec.desc))?;
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const OPTIONS: &[OptDef] = &[
+        OptDef::flag("h", "help", "print this help menu"),
+        OptDef::opt(
+            "",
+            "between",
+            "only consider checks made between START and END, like --since START --until END",
+            "START END",
+        ),
+        OptDef::multi(
+            "s",
+            "store",
+            "read this store file instead of the default one",
+            "[LABEL=]STORE",
+        ),
+        OptDef::opt("", "type", "only consider checks of this type", "http|icmp"),
+        OptDef::opt("", "target", "only this target", "IP|NAME"),
+        OptDef::flag_opt("", "calendar", "show the last MONTHS months", "MONTHS"),
+    ];
+
+    #[test]
+    fn test_options() {
+        let opts = options(OPTIONS);
+        let matches = opts
+            .parse([
+                "-h",
+                "-s",
+                "a",
+                "--store",
+                "b",
+                "--calendar",
+                "--type",
+                "http",
+            ])
+            .unwrap();
+        assert!(matches.opt_present("help"));
+        assert_eq!(matches.opt_strs("store"), ["a", "b"]);
+        assert!(matches.opt_present("calendar"));
+        assert_eq!(matches.opt_str("type").as_deref(), Some("http"));
+        assert!(opts.usage("netpulse").contains("--between START END"));
+    }
+
+    #[test]
+    fn test_completions() {
+        assert_eq!(OPTIONS[0].forms(), ["-h", "--help"]);
+        assert_eq!(OPTIONS[0].value(), Value::Flag);
+        assert_eq!(OPTIONS[1].forms(), ["--between"]);
+        assert_eq!(OPTIONS[1].value(), Value::Any);
+        assert_eq!(OPTIONS[2].forms(), ["-s", "--store"]);
+        assert_eq!(OPTIONS[2].value(), Value::Files);
+        assert_eq!(
+            OPTIONS[3].value(),
+            Value::Choices(vec!["http".to_string(), "icmp".to_string()])
+        );
+        assert_eq!(OPTIONS[4].value(), Value::Any);
+
+        let bash = generate("netpulse", OPTIONS, Shell::Bash);
+        assert!(bash.contains("        -s|--store)\n"));
+        assert!(bash.contains("complete -F _netpulse netpulse"));
+        let zsh = generate("netpulse", OPTIONS, Shell::Zsh);
+        assert!(zsh.contains(
+            "'--store[read this store file instead of the default one]:\\[LABEL=\\]STORE:_files'"
+        ));
+        assert!(zsh.contains("'--calendar[show the last MONTHS months]::MONTHS:'"));
+        let fish = generate("netpulse", OPTIONS, Shell::Fish);
+        assert!(fish.contains("complete -c netpulse -s s -l store -r -F -d"));
+    }
+}
@@ -0,0 +1,1103 @@
+//! Configuration of netpulse.
+//!
+//! The configuration is read from a TOML file at [CONFIG_PATH], or from the path in the
+//! environment variable [ENV_CONFIG]. In [user mode](crate::paths::user_mode), the default is
+//! `$XDG_CONFIG_HOME/netpulse.toml` instead. The file is optional: if it does not exist, the
+//! [default configuration](Config::default) is used, which checks the built-in [TARGETS].
+//!
+//! # Example
+//!
+//! ```toml
+//! probe = "living-room"
+//! language = "de"
+//!
+//! [[targets]]
+//! address = "1.1.1.1"
+//! label = "cloudflare-dns"
+//!
+//! [[targets]]
+//! address = "2606:4700:4700::1111"
+//! label = "cloudflare-dns6"
+//!
+//! [[targets]]
+//! address = "192.0.2.10"
+//! label = "my-service"
+//! url = "https://my-service.example:8443/health"
+//!
+//! [retry]
+//! retries = 2
+//! delay = 1000
+//!
+//! [degraded]
+//! http = 3000
+//!
+//! [ping]
+//! count = 10
+//!
+//! [trace]
+//! on_failure = true
+//!
+//! [dns]
+//! protocols = ["udp", "doh", "dot"]
+//!
+//! [[ntp.servers]]
+//! address = "162.159.200.1"
+//! label = "cloudflare-ntp"
+//!
+//! [bandwidth]
+//! url = "https://speed.cloudflare.com/__down?bytes=1000000"
+//!
+//! [sla]
+//! uptime = 99.9
+//! period = "monthly"
+//! basis = "time"
+//!
+//! [severity]
+//! duration_weight = 2.0
+//!
+//! [notify]
+//! webhook = "https://example.com/netpulse-hook"
+//!
+//! [hooks]
+//! outage_start = "/usr/local/bin/netpulse-alert start"
+//! ```
+//!
+//! The label of a [Target] is stored with each [Check](crate::records::Check) and shown by the
+//! analyzer and in dumps instead of the bare address. So is the `probe`, the name of the machine
+//! that made the check, see [Config::probe]. The `language` of the labels of the analysis is
+//! `en` or `de`, see [i18n](crate::analyze::i18n).
+//!
+//! If a [Target] has a `url`, HTTP checks request that URL instead of `http://<address>`. Host
+//! names in the URL are resolved with the IP version of the `address`. With an `interface` or
+//! `source`, its checks leave through that interface or from that address, see
+//! [bind](crate::checks::bind). The `[bind]` table sets them for all targets.
+//!
+//! With a `[retry]` table, failed checks are repeated before they are recorded, see
+//! [RetryConfig]. With a `[degraded]` table, successful checks that are too slow are flagged as
+//! degraded, see [DegradedConfig]. With a `[ping]` table, ICMP checks send a burst of echoes to measure packet
+//! loss, or echoes of another size or time to live, see [PingConfig]. With a `[trace]` table, the path to targets with failed checks is
+//! traced, see [TraceConfig]. With a `[dns]` table, DNS checks are made against the targets,
+//! see [DnsConfig]. NTP servers in the `[ntp]` table are checked with SNTP, see [NtpConfig].
+//! With a `url` in the `[bandwidth]` table, the throughput is sampled every few rounds, see
+//! [BandwidthConfig]. With an `uptime` in the `[sla]` table, the analysis tracks the error budget
+//! of the current period, see [SlaConfig]. The `[severity]` table weighs the duration of outages
+//! when ranking them, see [SeverityConfig]. With a `url` in the `[influx]` table, the daemon writes
+//! its checks to InfluxDB, see [InfluxConfig]. With a `broker` in the `[mqtt]` table, the daemon
+//! publishes its state over MQTT, for example to Home Assistant, see [MqttConfig]. The `[hooks]`
+//! table runs commands when outages start or end, see [HooksConfig]. The `[paths]`
+//! table moves the store, pid file, control socket and logs, see [PathsConfig].
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, trace};
+
+use crate::analyze::i18n::Language;
+use crate::analyze::outage::Severity;
+use crate::analyze::report::{Period, UptimeBasis};
+use crate::checks::bind::{fmt_scoped, parse_scoped, Bind};
+use crate::checks::PingOptions;
+use crate::errors::ConfigError;
+use crate::records::{CheckType, TARGETS};
+
+/// Default location of the config file
+pub const CONFIG_PATH: &str = "/etc/netpulse.toml";
+
+/// Environment variable name for overriding the config file path
+///
+/// If set, its value will be used instead of [CONFIG_PATH].
+pub const ENV_CONFIG: &str = "NETPULSE_CONFIG";
+
+/// Configuration of netpulse, usually loaded with [Config::load].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Name of this machine, recorded with every check, see [Config::probe]
+    pub probe: Option<String>,
+    /// Language of the labels of the analysis, see [i18n](crate::analyze::i18n)
+    pub language: Language,
+    /// Remote hosts to run the checks against
+    pub targets: Vec<Target>,
+    /// Retries of failed checks
+    pub retry: RetryConfig,
+    /// Latency thresholds of degraded checks
+    pub degraded: DegradedConfig,
+    /// Bursts of ICMP echoes
+    pub ping: PingConfig,
+    /// Interface or source address for the checks of all targets
+    pub bind: Bind,
+    /// Traceroutes to targets with failed checks
+    pub trace: TraceConfig,
+    /// DNS checks against the targets
+    pub dns: DnsConfig,
+    /// NTP servers to check
+    pub ntp: NtpConfig,
+    /// Throughput samples
+    pub bandwidth: BandwidthConfig,
+    /// Uptime promised by the ISP
+    pub sla: SlaConfig,
+    /// Ranking of outages
+    pub severity: SeverityConfig,
+    /// Notifications about outages
+    pub notify: NotifyConfig,
+    /// Pushing checks to a central server
+    pub push: PushConfig,
+    /// Writing checks to InfluxDB
+    pub influx: InfluxConfig,
+    /// Publishing the state to an MQTT broker
+    pub mqtt: MqttConfig,
+    /// Scripts to run on events
+    pub hooks: HooksConfig,
+    /// Locations of the files of netpulse
+    pub paths: PathsConfig,
+}
+
+/// Retry policy for failed checks.
+///
+/// A single dropped packet should not look like an outage. With retries, a failed check is made
+/// again before its result is recorded. Checks that needed more than one attempt get the
+/// [Retried](crate::records::CheckFlag::Retried) flag.
+///
+/// No retries are made by default.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// How often to repeat a failed check
+    pub retries: u32,
+    /// Milliseconds to wait before repeating a failed check
+    pub delay: u64,
+}
+
+impl RetryConfig {
+    /// Returns the time to wait before repeating a failed check.
+    pub fn delay(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.delay)
+    }
+
+    /// Returns how much longer than a single attempt a check can take at most with retries.
+    pub fn max_extra_time(&self) -> std::time::Duration {
+        (crate::TIMEOUT + self.delay()) * self.retries
+    }
+}
+
+/// Latency thresholds above which successful checks count as degraded.
+///
+/// A check that succeeds after nine seconds is hardly better than one that failed. If a threshold
+/// in milliseconds is set for a [CheckType], successful checks of that type that took longer get
+/// the [Degraded](crate::records::CheckFlag::Degraded) flag. The analysis shows periods of
+/// degraded checks as outages of their own [severity](crate::analyze::outage::Severity).
+///
+/// No thresholds are set by default.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DegradedConfig {
+    /// Threshold of DNS checks over UDP
+    pub dns: Option<u64>,
+    /// Threshold of DNS checks over HTTPS
+    pub doh: Option<u64>,
+    /// Threshold of DNS checks over TLS
+    pub dot: Option<u64>,
+    /// Threshold of HTTP checks
+    pub http: Option<u64>,
+    /// Threshold of ICMP checks
+    pub icmp: Option<u64>,
+    /// Threshold of gateway checks
+    pub gateway: Option<u64>,
+    /// Threshold of NTP checks
+    pub ntp: Option<u64>,
+    /// Threshold of QUIC checks
+    pub quic: Option<u64>,
+}
+
+impl DegradedConfig {
+    /// Returns the latency threshold of `check_type`, if one is set.
+    pub fn threshold(&self, check_type: CheckType) -> Option<std::time::Duration> {
+        let ms = match check_type {
+            CheckType::Dns => self.dns,
+            CheckType::Doh => self.doh,
+            CheckType::Dot => self.dot,
+            CheckType::Http => self.http,
+            CheckType::Icmp => self.icmp,
+            CheckType::Gateway => self.gateway,
+            CheckType::Ntp => self.ntp,
+            CheckType::Quic => self.quic,
+            CheckType::Mtu | CheckType::Bandwidth | CheckType::Unknown => None,
+        };
+        ms.map(std::time::Duration::from_millis)
+    }
+}
+
+/// Parameters of the ICMP checks, and packet loss measurement.
+///
+/// A single ping per round only tells whether the target was reachable at all. With a
+/// [count](Self::count) of more than one, each ICMP check sends that many echoes and records how
+/// many came back as [Measurement::Loss](crate::records::Measurement::Loss). The check is
+/// successful if any echo came back.
+///
+/// The echoes of ICMP and gateway checks carry [size](Self::size) bytes of payload, are sent with
+/// the [ttl](Self::ttl) if one is set, and wait [timeout](Self::timeout) milliseconds for the
+/// reply, see [PingOptions]. Single pings record the time to live of the reply as
+/// [Measurement::Ttl](crate::records::Measurement::Ttl), so a changed path shows up.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PingConfig {
+    /// How many echoes an ICMP check sends
+    pub count: u8,
+    /// Milliseconds between two echoes of a burst
+    pub interval: u64,
+    /// Bytes of payload of each echo
+    pub size: u16,
+    /// Time to live of the echoes, the system default if not set
+    pub ttl: Option<u8>,
+    /// Milliseconds to wait for the reply to an echo
+    pub timeout: u64,
+}
+
+impl Default for PingConfig {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            interval: 200,
+            size: crate::checks::DEFAULT_PING_SIZE,
+            ttl: None,
+            timeout: crate::TIMEOUT_MS as u64,
+        }
+    }
+}
+
+impl PingConfig {
+    /// Returns the time between two echoes of a burst.
+    pub fn interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.interval)
+    }
+
+    /// Returns how long to wait for the reply to an echo.
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout)
+    }
+
+    /// Returns the [PingOptions] for the echoes of the checks.
+    pub fn options(&self) -> PingOptions {
+        PingOptions {
+            size: self.size,
+            ttl: self.ttl,
+            timeout: self.timeout(),
+        }
+    }
+
+    /// Returns how much longer than a single echo a burst can take at most.
+    pub fn max_extra_time(&self) -> std::time::Duration {
+        (self.timeout() + self.interval()) * self.count.saturating_sub(1) as u32
+    }
+}
+
+/// Traceroute on failure.
+///
+/// A failed check only tells that the target could not be reached, not where the path broke.
+/// If [on_failure](Self::on_failure) is set, the daemon traces the path to every target with a
+/// failed check after the round and stores the [Trace](crate::records::trace::Trace), so the
+/// analysis can tell whether the local network, the ISP or something beyond failed.
+///
+/// Tracing requires the `traceroute` feature and the `CAP_NET_RAW` capability.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TraceConfig {
+    /// Whether to trace the path to targets with failed checks
+    pub on_failure: bool,
+    /// How many hops a trace follows at most
+    pub max_hops: u8,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            on_failure: false,
+            max_hops: 16,
+        }
+    }
+}
+
+/// DNS checks against the targets.
+///
+/// DNS checks only make sense if the targets are DNS resolvers, so none are made by default. For
+/// each of the [protocols](Self::protocols), a DNS check is made against every target, see
+/// [dns](crate::checks::dns).
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DnsConfig {
+    /// Protocols to query the targets with
+    pub protocols: Vec<DnsProtocol>,
+}
+
+impl DnsConfig {
+    /// Returns the [CheckTypes](CheckType) of the configured protocols.
+    pub fn check_types(&self) -> Vec<CheckType> {
+        self.protocols.iter().map(DnsProtocol::check_type).collect()
+    }
+}
+
+/// Protocol of a DNS check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsProtocol {
+    /// Plain DNS over UDP
+    Udp,
+    /// DNS over HTTPS, requires the `doh` feature
+    Doh,
+    /// DNS over TLS, requires the `dot` feature
+    Dot,
+}
+
+impl DnsProtocol {
+    /// Returns the [CheckType] of DNS checks with this protocol.
+    pub fn check_type(&self) -> CheckType {
+        match self {
+            Self::Udp => CheckType::Dns,
+            Self::Doh => CheckType::Doh,
+            Self::Dot => CheckType::Dot,
+        }
+    }
+}
+
+/// NTP checks.
+///
+/// Every round, each of the [servers](Self::servers) gets an SNTP query, which records the
+/// round-trip time and the offset of the local clock, see [ntp](crate::checks::ntp). No NTP
+/// checks are made by default.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NtpConfig {
+    /// NTP servers to query, the `url` of these [Targets](Target) is not used
+    pub servers: Vec<Target>,
+}
+
+/// Throughput samples.
+///
+/// If a [url](Self::url) is set, the daemon downloads the payload at that URL every
+/// [every](Self::every) rounds and records the throughput, see
+/// [bandwidth](crate::checks::bandwidth). As each sample costs traffic, the payload should be
+/// small and the samples rare. No samples are taken by default.
+///
+/// Sampling requires the `bandwidth` feature.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BandwidthConfig {
+    /// URL of the payload to download, like `https://speed.cloudflare.com/__down?bytes=1000000`
+    pub url: Option<String>,
+    /// How many rounds to wait between two samples
+    pub every: u32,
+}
+
+impl Default for BandwidthConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            every: 60,
+        }
+    }
+}
+
+/// Uptime promised by the ISP.
+///
+/// If an [uptime](Self::uptime) is set, the analysis compares the uptime of every
+/// [period](Self::period) against it and shows how much of the error budget of the current
+/// period is left, see [SlaStats](crate::analyze::report::SlaStats). Nothing is tracked by
+/// default.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlaConfig {
+    /// Promised uptime in percent, like `99.9`
+    pub uptime: Option<f64>,
+    /// Calendar period the uptime is promised for
+    pub period: Period,
+    /// Whether the uptime is the ratio of successful checks, or of the time covered by them
+    ///
+    /// The ratio of checks does not count the times netpulse was not running as down. The ratio
+    /// of time does, which is stricter, but can blame the ISP for a machine that was asleep.
+    pub basis: UptimeBasis,
+}
+
+impl Default for SlaConfig {
+    fn default() -> Self {
+        Self {
+            uptime: None,
+            period: Period::Monthly,
+            basis: UptimeBasis::Checks,
+        }
+    }
+}
+
+/// Weighting of outages when ranking them by how bad they were.
+///
+/// The [Severity] of an outage only tells how many of its checks failed, so an outage where all
+/// checks failed for a minute would outrank one where half of them failed all day. The analysis
+/// ranks outages by a [score](Self::score) instead, which weighs in their duration. The graphs
+/// use the same score for each of their time spans.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SeverityConfig {
+    /// Weight of the duration against the ratio of failed checks, 0 to ignore the duration
+    pub duration_weight: f64,
+    /// Duration in seconds at which an outage gets half of the [duration
+    /// weight](Self::duration_weight)
+    pub duration_half: u64,
+    /// Weight of degraded checks against failed ones
+    pub degraded_weight: f64,
+}
+
+impl Default for SeverityConfig {
+    fn default() -> Self {
+        Self {
+            duration_weight: 1.0,
+            duration_half: 3600,
+            degraded_weight: 0.25,
+        }
+    }
+}
+
+impl SeverityConfig {
+    /// Calculates the score of an outage of `severity` that lasted `duration` seconds.
+    ///
+    /// The score is the [ratio](Severity::ratio) of failed checks, or of degraded checks times
+    /// the [degraded weight](Self::degraded_weight), scaled up by the duration:
+    ///
+    /// ```text
+    /// ratio * (1 + duration_weight * duration / (duration + duration_half))
+    /// ```
+    ///
+    /// The duration part grows from 0 towards 1, so an outage without failed checks always
+    /// scores 0, and for a duration of 0 the score is just the ratio.
+    pub fn score(&self, severity: Severity, duration: i64) -> f64 {
+        let ratio = match severity {
+            Severity::Degraded(ratio) => ratio * self.degraded_weight,
+            other => other.ratio(),
+        };
+        let duration = duration.max(0) as f64;
+        let long = if duration == 0.0 {
+            0.0
+        } else {
+            duration / (duration + self.duration_half as f64)
+        };
+        ratio * (1.0 + self.duration_weight * long)
+    }
+}
+
+/// Configuration of the notifications about outages, see [notify](crate::notify).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// URL that a JSON payload is POSTed to when an outage starts or ends
+    pub webhook: Option<String>,
+    /// How often to retry sending a notification if it failed
+    pub retries: u32,
+    /// Minimum time between two outage notifications in seconds
+    ///
+    /// Outages that start earlier after the last notification are not notified, neither is their
+    /// end. This keeps a flaky connection from flooding the receiver.
+    pub min_interval: u64,
+    /// Send emails about long complete outages, see [EmailConfig]
+    pub email: Option<EmailConfig>,
+}
+
+/// SMTP settings for email notifications about outages.
+///
+/// An email is sent when a complete outage has lasted for [min_duration](Self::min_duration)
+/// minutes, and another when connectivity recovers. Sending requires the `notify-email` feature.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailConfig {
+    /// Host name of the SMTP server
+    pub server: String,
+    /// Port of the SMTP server, the default of the [tls](Self::tls) mode if not set
+    pub port: Option<u16>,
+    /// How to encrypt the connection to the SMTP server
+    #[serde(default)]
+    pub tls: EmailTls,
+    /// User name to log in to the SMTP server
+    pub username: Option<String>,
+    /// Password to log in to the SMTP server
+    pub password: Option<String>,
+    /// Sender address of the emails
+    pub from: String,
+    /// Recipient addresses of the emails
+    pub to: Vec<String>,
+    /// How many minutes a complete outage has to last before an email is sent
+    #[serde(default = "EmailConfig::default_min_duration")]
+    pub min_duration: u64,
+}
+
+impl EmailConfig {
+    fn default_min_duration() -> u64 {
+        5
+    }
+}
+
+/// Encryption of the connection to the SMTP server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTls {
+    /// Connect with TLS right away, port 465 by default
+    #[default]
+    Tls,
+    /// Connect in plain text and upgrade with STARTTLS, port 587 by default
+    StartTls,
+    /// Do not encrypt at all, port 25 by default
+    None,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            webhook: None,
+            retries: 3,
+            min_interval: 300,
+            email: None,
+        }
+    }
+}
+
+/// Where and how often the daemon pushes its checks to a central server, see
+/// [push](crate::push).
+///
+/// Nothing is pushed unless a [url](Self::url) is set. Pushing requires the `push` feature.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PushConfig {
+    /// URL that new checks are POSTed to
+    pub url: Option<String>,
+    /// Sent as `Authorization: Bearer <token>` with every request
+    pub token: Option<String>,
+    /// Name of this probe on the server, the host name if not set
+    pub probe: Option<String>,
+    /// Minimum time between two pushes in seconds
+    pub interval: u64,
+}
+
+impl Default for PushConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            token: None,
+            probe: None,
+            interval: 300,
+        }
+    }
+}
+
+/// Where the daemon writes its checks in the InfluxDB line protocol, see
+/// [influx](crate::records::influx).
+///
+/// Nothing is written unless a [url](Self::url) is set. Writing requires the `influx` feature.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InfluxConfig {
+    /// URL of the write API, including the organization and bucket
+    pub url: Option<String>,
+    /// Sent as `Authorization: Token <token>` with every request
+    pub token: Option<String>,
+}
+
+/// Commands the daemon runs when an outage starts or ends, or a round of checks is complete, see
+/// [hooks](crate::hooks).
+///
+/// Each command is run with `sh -c`, in the background, with the details of the event in
+/// environment variables. Nothing is run by default.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Run when an outage starts
+    pub outage_start: Option<String>,
+    /// Run when an outage ends
+    pub outage_end: Option<String>,
+    /// Run after every round of checks
+    pub round: Option<String>,
+    /// Seconds after which a command that is still running is killed
+    pub timeout: u64,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            outage_start: None,
+            outage_end: None,
+            round: None,
+            timeout: 30,
+        }
+    }
+}
+
+impl HooksConfig {
+    /// Returns true if any command is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.outage_start.is_some() || self.outage_end.is_some() || self.round.is_some()
+    }
+
+    /// Returns the time after which a command that is still running is killed.
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeout)
+    }
+}
+
+/// Which MQTT broker the daemon publishes its state to, see [mqtt](crate::mqtt).
+///
+/// Nothing is published unless a [broker](Self::broker) is set. Publishing requires the `mqtt`
+/// feature.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttConfig {
+    /// Host name or address of the broker
+    pub broker: Option<String>,
+    /// Port of the broker
+    pub port: u16,
+    /// User name to log in with
+    pub username: Option<String>,
+    /// Password to log in with
+    pub password: Option<String>,
+    /// First level of all state topics
+    pub topic: String,
+    /// Name of this probe in the topics, the host name if not set
+    pub node: Option<String>,
+    /// Whether to publish discovery payloads for Home Assistant
+    pub discovery: bool,
+    /// First level of the discovery topics, as configured in Home Assistant
+    pub discovery_prefix: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: None,
+            port: 1883,
+            username: None,
+            password: None,
+            topic: "netpulse".to_string(),
+            node: None,
+            discovery: true,
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+/// Locations of the files of netpulse, see [paths](crate::paths).
+///
+/// Locations that are not set keep their default. The environment variables of the
+/// [paths](crate::paths) take precedence over these.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    /// Directory of the store
+    pub store_dir: Option<PathBuf>,
+    /// Pid file of the daemon
+    pub pid_file: Option<PathBuf>,
+    /// Control socket of the daemon
+    pub control_socket: Option<PathBuf>,
+    /// Log that the stdout of the daemon is redirected to
+    pub info_log: Option<PathBuf>,
+    /// Log that the stderr of the daemon is redirected to
+    pub error_log: Option<PathBuf>,
+}
+
+/// A remote host that the checks are run against.
+///
+/// In the config file, the zone of a link-local address is part of the `address`, like
+/// `fe80::1%eth0`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "RawTarget", into = "RawTarget")]
+pub struct Target {
+    /// IP address of the target
+    pub address: IpAddr,
+    /// Zone of a link-local IPv6 address, like `eth0` in `fe80::1%eth0`, see
+    /// [zones](crate::checks::bind#zones)
+    pub zone: Option<String>,
+    /// Human readable name of the target, like `cloudflare-dns`
+    pub label: Option<String>,
+    /// Full URL for HTTP checks, like `https://example.com:8443/health`
+    ///
+    /// If not set, HTTP checks request `http://<address>`.
+    pub url: Option<String>,
+    /// Network interface the checks of this target leave through, like `wwan0`, see
+    /// [bind](crate::checks::bind)
+    pub interface: Option<String>,
+    /// Local address the checks of this target are sent from
+    pub source: Option<IpAddr>,
+}
+
+/// A [Target] as written in the config file, with the zone in the address
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct RawTarget {
+    address: String,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    interface: Option<String>,
+    #[serde(default)]
+    source: Option<IpAddr>,
+}
+
+impl TryFrom<RawTarget> for Target {
+    type Error = String;
+
+    fn try_from(raw: RawTarget) -> Result<Self, Self::Error> {
+        let (address, zone) = parse_scoped(&raw.address)?;
+        Ok(Self {
+            address,
+            zone,
+            label: raw.label,
+            url: raw.url,
+            interface: raw.interface,
+            source: raw.source,
+        })
+    }
+}
+
+impl From<Target> for RawTarget {
+    fn from(target: Target) -> Self {
+        Self {
+            address: fmt_scoped(target.address, target.zone.as_deref()),
+            label: target.label,
+            url: target.url,
+            interface: target.interface,
+            source: target.source,
+        }
+    }
+}
+
+impl Target {
+    /// Creates a [Target] for `address`, without a zone, label, URL or binding.
+    pub fn new(address: IpAddr) -> Self {
+        Self {
+            address,
+            zone: None,
+            label: None,
+            url: None,
+            interface: None,
+            source: None,
+        }
+    }
+
+    /// Returns the interface, source address and zone of this target, see [Config::bind_for] for
+    /// the ones of the `[bind]` table.
+    pub fn bind(&self) -> Bind {
+        Bind {
+            interface: self.interface.clone(),
+            source: self.source,
+            zone: self.zone.clone(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            probe: None,
+            language: Language::English,
+            targets: TARGETS
+                .iter()
+                .map(|t| Target::new(t.parse().expect("a target constant was not an Ip Address")))
+                .collect(),
+            retry: RetryConfig::default(),
+            degraded: DegradedConfig::default(),
+            ping: PingConfig::default(),
+            bind: Bind::default(),
+            trace: TraceConfig::default(),
+            dns: DnsConfig::default(),
+            ntp: NtpConfig::default(),
+            bandwidth: BandwidthConfig::default(),
+            sla: SlaConfig::default(),
+            severity: SeverityConfig::default(),
+            notify: NotifyConfig::default(),
+            push: PushConfig::default(),
+            influx: InfluxConfig::default(),
+            mqtt: MqttConfig::default(),
+            hooks: HooksConfig::default(),
+            paths: PathsConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the path of the config file.
+    ///
+    /// See [paths::config_file](crate::paths::config_file).
+    pub fn path() -> PathBuf {
+        crate::paths::config_file()
+    }
+
+    /// Loads the config file from [Config::path].
+    ///
+    /// If the file does not exist, the [default configuration](Config::default) is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ConfigError] if the file exists but can not be read or parsed.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = Self::path();
+        let raw = match std::fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                trace!("no config file at {}, using defaults", path.display());
+                return Ok(Self::default());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Ok(toml::from_str(&raw)?)
+    }
+
+    /// Loads the config file like [Config::load], but falls back to the
+    /// [default configuration](Config::default) if that fails.
+    ///
+    /// The error is logged, so the daemon keeps working with a broken config file.
+    pub fn load_or_default() -> Self {
+        match Self::load() {
+            Ok(config) => config,
+            Err(err) => {
+                error!("could not load the config, using defaults: {err}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Returns the name of this machine, which is recorded with every
+    /// [Check](crate::records::Check) it makes.
+    ///
+    /// This is the `probe` of the config, or the [probe](PushConfig::probe) of the `[push]`
+    /// table, or the host name, so the checks of several machines can be told apart in a merged
+    /// store or on a [central server](crate::server).
+    pub fn probe(&self) -> String {
+        self.probe
+            .clone()
+            .or_else(|| self.push.probe.clone())
+            .or_else(sysinfo::System::host_name)
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    /// Returns where the checks of `target` leave the host, see [bind](crate::checks::bind).
+    ///
+    /// The interface and source address of the [Target] take precedence over the ones of the
+    /// `[bind]` table. The source address of the table is only used for targets of its IP
+    /// version.
+    pub fn bind_for(&self, target: &Target) -> Bind {
+        let global = &self.bind;
+        Bind {
+            interface: target.interface.clone().or(global.interface.clone()),
+            source: target.source.or(global
+                .source
+                .filter(|s| s.is_ipv4() == target.address.is_ipv4())),
+            zone: target.zone.clone(),
+        }
+    }
+
+    /// Returns how long a round of checks can take before the checks that did not finish are
+    /// recorded as failed.
+    ///
+    /// This is [ROUND_DEADLINE](crate::store::ROUND_DEADLINE), extended by the time that
+    /// [retries](RetryConfig) and bursts of echoes can take on top of a single attempt.
+    pub fn round_deadline(&self) -> std::time::Duration {
+        crate::store::ROUND_DEADLINE
+            + self.ping.max_extra_time() * (self.retry.retries + 1)
+            + self.retry.max_extra_time()
+    }
+
+    /// Returns the label configured for `address`, if any.
+    pub fn label_of(&self, address: IpAddr) -> Option<&str> {
+        self.targets
+            .iter()
+            .find(|t| t.address == address)
+            .and_then(|t| t.label.as_deref())
+    }
+
+    /// Returns the address of the target with `label`, if any.
+    pub fn address_of(&self, label: &str) -> Option<IpAddr> {
+        self.targets
+            .iter()
+            .find(|t| t.label.as_deref() == Some(label))
+            .map(|t| t.address)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            probe = "living-room"
+            language = "de"
+
+            [[targets]]
+            address = "1.1.1.1"
+            label = "cloudflare-dns"
+
+            [[targets]]
+            address = "::1"
+            url = "http://localhost:8080/health"
+            interface = "wwan0"
+
+            [[targets]]
+            address = "fe80::1%eth1"
+            label = "router"
+
+            [bind]
+            interface = "eth0"
+            source = "192.0.2.5"
+
+            [retry]
+            retries = 2
+
+            [degraded]
+            http = 3000
+            icmp = 500
+
+            [ping]
+            count = 10
+            ttl = 32
+
+            [trace]
+            on_failure = true
+
+            [dns]
+            protocols = ["udp", "dot"]
+
+            [[ntp.servers]]
+            address = "162.159.200.1"
+
+            [bandwidth]
+            url = "http://localhost:8080/payload"
+
+            [sla]
+            uptime = 99.9
+            basis = "time"
+
+            [severity]
+            duration_half = 600
+
+            [notify]
+            webhook = "http://localhost:8080/hook"
+            retries = 1
+
+            [notify.email]
+            server = "smtp.example.com"
+            tls = "starttls"
+            from = "netpulse@example.com"
+            to = ["admin@example.com"]
+
+            [push]
+            url = "https://netpulse.example.com/push"
+            token = "secret"
+
+            [influx]
+            url = "http://localhost:8086/api/v2/write?org=home&bucket=netpulse"
+
+            [mqtt]
+            broker = "homeassistant.local"
+            discovery = false
+
+            [hooks]
+            outage_end = "notify-send 'back online'"
+            timeout = 5
+
+            [paths]
+            store_dir = "/srv/netpulse"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.targets.len(), 3);
+        assert_eq!(
+            config.label_of("1.1.1.1".parse().unwrap()),
+            Some("cloudflare-dns")
+        );
+        assert_eq!(config.label_of("::1".parse().unwrap()), None);
+        assert_eq!(
+            config.address_of("cloudflare-dns"),
+            Some("1.1.1.1".parse().unwrap())
+        );
+        assert_eq!(config.address_of("google-dns"), None);
+        assert_eq!(
+            config.targets[1].url.as_deref(),
+            Some("http://localhost:8080/health")
+        );
+
+        let lte = config.bind_for(&config.targets[1]);
+        assert_eq!(lte.interface.as_deref(), Some("wwan0"));
+        assert_eq!(lte.source, None);
+        let fiber = config.bind_for(&config.targets[0]);
+        assert_eq!(fiber.name().as_deref(), Some("eth0"));
+        assert_eq!(fiber.source, Some("192.0.2.5".parse().unwrap()));
+
+        let router = &config.targets[2];
+        assert_eq!(router.address, "fe80::1".parse::<IpAddr>().unwrap());
+        assert_eq!(router.zone.as_deref(), Some("eth1"));
+        assert_eq!(config.bind_for(router).zone.as_deref(), Some("eth1"));
+        assert!(toml::to_string(router)
+            .unwrap()
+            .contains(r#"address = "fe80::1%eth1""#));
+        assert!(toml::from_str::<Target>(r#"address = "1.1.1.1%eth0""#).is_err());
+
+        assert_eq!(config.retry.retries, 2);
+        assert_eq!(config.retry.delay, 0);
+        assert_eq!(
+            config.degraded.threshold(CheckType::Http),
+            Some(std::time::Duration::from_secs(3))
+        );
+        assert_eq!(config.degraded.threshold(CheckType::Dns), None);
+        assert_eq!(config.degraded.threshold(CheckType::Mtu), None);
+        assert_eq!(config.ping.count, 10);
+        assert_eq!(config.ping.interval, 200);
+        assert_eq!(config.ping.options().ttl, Some(32));
+        assert_eq!(config.ping.options().size, 56);
+        assert_eq!(config.ping.timeout(), crate::TIMEOUT);
+        assert!(config.trace.on_failure);
+        assert_eq!(config.trace.max_hops, 16);
+        assert_eq!(
+            config.dns.check_types(),
+            vec![CheckType::Dns, CheckType::Dot]
+        );
+        assert_eq!(config.ntp.servers.len(), 1);
+        assert_eq!(
+            config.bandwidth.url.as_deref(),
+            Some("http://localhost:8080/payload")
+        );
+        assert_eq!(config.bandwidth.every, 60);
+        assert_eq!(config.sla.uptime, Some(99.9));
+        assert_eq!(config.sla.period, Period::Monthly);
+        assert_eq!(config.sla.basis, UptimeBasis::Time);
+        assert_eq!(config.severity.duration_half, 600);
+        assert_eq!(config.severity.duration_weight, 1.0);
+        assert_eq!(config.probe(), "living-room");
+        assert_eq!(config.language, Language::German);
+
+        assert_eq!(
+            config.notify.webhook.as_deref(),
+            Some("http://localhost:8080/hook")
+        );
+        assert_eq!(config.notify.retries, 1);
+        assert_eq!(config.notify.min_interval, 300);
+        let email = config.notify.email.unwrap();
+        assert_eq!(email.tls, EmailTls::StartTls);
+        assert_eq!(email.port, None);
+        assert_eq!(email.min_duration, 5);
+
+        assert_eq!(config.push.token.as_deref(), Some("secret"));
+        assert_eq!(config.push.probe, None);
+        assert!(config.influx.url.is_some());
+        assert_eq!(config.influx.token, None);
+        assert_eq!(config.mqtt.port, 1883);
+        assert_eq!(config.mqtt.topic, "netpulse");
+        assert!(!config.mqtt.discovery);
+        assert_eq!(config.push.interval, 300);
+        assert!(config.hooks.is_enabled());
+        assert_eq!(config.hooks.outage_start, None);
+        assert_eq!(config.hooks.timeout(), std::time::Duration::from_secs(5));
+        assert_eq!(config.paths.store_dir, Some(PathBuf::from("/srv/netpulse")));
+        assert_eq!(config.paths.pid_file, None);
+
+        let empty: Config = toml::from_str("").unwrap();
+        assert_eq!(empty, Config::default());
+    }
+}
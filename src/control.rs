@@ -0,0 +1,256 @@
+//! Control socket of the daemon.
+//!
+//! The daemon listens on the unix socket [DAEMON_CONTROL_SOCKET](crate::DAEMON_CONTROL_SOCKET),
+//! or the [control_socket](crate::paths::control_socket) of the user in user mode, for requests from `netpulsed`, for example to report its status or to make checks right away.
+//!
+//! # Protocol
+//!
+//! The protocol is as simple as possible: the client connects, writes one [Request] as a line of
+//! JSON, and the daemon answers with one [Response] as a line of JSON, then closes the
+//! connection.
+//!
+//! ```text
+//! > {"request":"status"}
+//! < {"response":"status","pid":1234,"started_at":1731121200,...}
+//! ```
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use netpulse::control::{query, Request, Response};
+//!
+//! match query(&Request::Status).unwrap() {
+//!     Response::Status(status) => println!("up for {} seconds", status.uptime()),
+//!     other => println!("unexpected answer: {other:?}"),
+//! }
+//! ```
+
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::errors::ControlError;
+use crate::records::Check;
+use crate::TIMEOUT;
+
+/// How long a client waits for the answer of the daemon
+///
+/// Making checks can take up to [TIMEOUT], so this is a good bit longer.
+pub const ANSWER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A request to the daemon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum Request {
+    /// Ask for the [DaemonStatus]
+    Status,
+    /// Make a round of checks right away, answered with [Response::Checks]
+    CheckNow,
+}
+
+/// The answer of the daemon to a [Request]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum Response {
+    /// Answer to [Request::Status]
+    Status(DaemonStatus),
+    /// Answer to [Request::CheckNow]
+    Checks {
+        /// The checks that were made, they are also added to the store of the daemon
+        checks: Vec<Check>,
+    },
+    /// The request could not be handled
+    Error {
+        /// What went wrong
+        message: String,
+    },
+}
+
+/// Information about the running daemon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    /// Process id of the daemon
+    pub pid: u32,
+    /// Unix timestamp of when the daemon was started
+    pub started_at: i64,
+    /// Unix timestamp of the last time the daemon made checks
+    pub last_wakeup: Option<i64>,
+    /// Number of checks made since the daemon was started
+    pub checks_made: usize,
+    /// Number of checks made since the store was last saved successfully
+    #[serde(default)]
+    pub pending_checks: usize,
+    /// How often saving the store failed since the daemon was started
+    #[serde(default)]
+    pub save_errors: usize,
+    /// Path of the store the daemon writes to
+    pub store_path: PathBuf,
+}
+
+impl DaemonStatus {
+    /// Returns for how many seconds the daemon has been running.
+    pub fn uptime(&self) -> i64 {
+        chrono::Utc::now().timestamp() - self.started_at
+    }
+}
+
+/// Sends a [Request] to the daemon at the [control_socket](crate::paths::control_socket) and waits for
+/// its [Response].
+///
+/// # Errors
+///
+/// Returns [ControlError] if the daemon could not be reached or did not answer properly.
+pub fn query(request: &Request) -> Result<Response, ControlError> {
+    query_at(&crate::paths::control_socket(), request)
+}
+
+/// Like [query], but with the socket at `path`.
+///
+/// # Errors
+///
+/// See [query].
+pub fn query_at(path: &Path, request: &Request) -> Result<Response, ControlError> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write_line(&mut stream, request)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    if line.is_empty() {
+        return Err(ControlError::NoAnswer);
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+fn write_line<T: Serialize>(stream: &mut UnixStream, value: &T) -> Result<(), ControlError> {
+    let mut buf = serde_json::to_vec(value)?;
+    buf.push(b'\n');
+    stream.write_all(&buf)?;
+    Ok(())
+}
+
+/// The listening end of the control socket, used by the daemon.
+///
+/// The socket file is removed when this is dropped.
+#[derive(Debug)]
+pub struct ControlSocket {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl ControlSocket {
+    /// Listens on the socket at `path`, replacing a stale socket file from an earlier run.
+    ///
+    /// The listener does not block, see [handle_pending](Self::handle_pending).
+    ///
+    /// # Errors
+    ///
+    /// Returns [ControlError] if the socket could not be created.
+    pub fn bind(path: &Path) -> Result<Self, ControlError> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => (),
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        // anyone may talk to the daemon, the worst a request can do is make an extra round of checks
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o666))?;
+        Ok(Self {
+            listener,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Answers all pending connections with `handler`, without blocking.
+    ///
+    /// Errors with single connections are logged and do not stop the others.
+    pub fn handle_pending(&self, mut handler: impl FnMut(Request) -> Response) {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    error!("could not accept a connection on the control socket: {e}");
+                    return;
+                }
+            };
+            if let Err(e) = Self::handle(stream, &mut handler) {
+                warn!("error on the control socket: {e}");
+            }
+        }
+    }
+
+    fn handle(
+        mut stream: UnixStream,
+        handler: &mut impl FnMut(Request) -> Response,
+    ) -> Result<(), ControlError> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => handler(request),
+            Err(e) => Response::Error {
+                message: format!("bad request: {e}"),
+            },
+        };
+        write_line(&mut stream, &response)
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("could not remove the control socket: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("netpulse-control-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("netpulsed.sock");
+        let socket = ControlSocket::bind(&path).unwrap();
+        let status = DaemonStatus {
+            pid: 1,
+            started_at: 1731121200,
+            last_wakeup: None,
+            checks_made: 4,
+            pending_checks: 0,
+            save_errors: 0,
+            store_path: PathBuf::from("/var/lib/netpulse/netpulse.store"),
+        };
+
+        let client = {
+            let path = path.clone();
+            std::thread::spawn(move || query_at(&path, &Request::Status).unwrap())
+        };
+        let mut handled = 0;
+        while !client.is_finished() {
+            socket.handle_pending(|request| {
+                assert_eq!(request, Request::Status);
+                handled += 1;
+                Response::Status(status.clone())
+            });
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(client.join().unwrap(), Response::Status(status));
+        assert_eq!(handled, 1);
+
+        drop(socket);
+        assert!(!path.exists());
+        std::fs::remove_dir(dir).unwrap();
+    }
+}
@@ -0,0 +1,472 @@
+//! The check loop of the daemon, to embed netpulse monitoring in other services.
+//!
+//! A [Daemon] owns a [Store] and everything that happens after a round of checks:
+//! - Notifies about outages starting and ending, see [notify](crate::notify)
+//! - Tells the added [CheckObservers](CheckObserver) about the checks and outages, see [observe](crate::observe)
+//! - Pushes its checks to a central server if configured, see [push](crate::push)
+//! - Writes its checks to InfluxDB if configured, see [influx]
+//! - Publishes the state to an MQTT broker if configured, see [mqtt](crate::mqtt)
+//! - Downsamples and archives old checks if configured
+//! - Saves the store, keeping the checks that could not be saved yet, see [MAX_PENDING_CHECKS]
+//! - Answers requests on the [control socket](crate::control), if it was opened
+//! - Tells systemd how the last round went and that it is alive, see [systemd]
+//!
+//! It does not deal with the process: signals, privileges and the pid file are left to the
+//! caller, like `netpulsed`. Call [Daemon::tick] in a loop and sleep for the time it returns, or
+//! call [Daemon::wakeup] whenever checks should be made. Code that should run after every round
+//! can be added with [Daemon::on_round], code that reacts to single checks or outages with
+//! [Daemon::observe].
+//!
+//! Requires the `executable` feature and unix.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use netpulse::daemon::Daemon;
+//! use netpulse::store::Store;
+//!
+//! let mut daemon = Daemon::new(Store::load_or_create().unwrap());
+//! daemon.on_round(|_store, checks| {
+//!     let failed = checks.iter().filter(|c| !c.is_success()).count();
+//!     println!("{failed} of {} checks failed", checks.len());
+//! });
+//! loop {
+//!     let wait = daemon.tick();
+//!     std::thread::sleep(wait.min(std::time::Duration::from_secs(1)));
+//! }
+//! ```
+
+use std::time::Duration;
+
+use deepsize::DeepSizeOf;
+use tracing::{debug, error, info, warn};
+
+use crate::config::{Config, HooksConfig, InfluxConfig, PushConfig};
+use crate::control::{ControlSocket, DaemonStatus, Request, Response};
+use crate::errors::{ControlError, RunError};
+use crate::hooks::ScriptHooks;
+use crate::mqtt::Publisher;
+use crate::notify::Notifier;
+use crate::observe::{CheckObserver, Observers};
+use crate::push::Pusher;
+use crate::records::{display_group, influx, Check, CheckType};
+use crate::store::Store;
+use crate::systemd;
+
+pub mod scheduler;
+use scheduler::Scheduler;
+
+/// How many checks are kept in memory at most while the store cannot be saved
+///
+/// If saving keeps failing, the oldest unsaved checks are dropped beyond this, so the daemon does
+/// not run out of memory. At the default period and targets, this is about two weeks of checks.
+pub const MAX_PENDING_CHECKS: usize = 100_000;
+
+/// Code that runs after every round of checks, see [Daemon::on_round]
+type RoundHook = Box<dyn FnMut(&Store, &[Check]) + Send>;
+
+/// What the daemon did so far, reported over the control socket
+#[derive(Debug)]
+struct Session {
+    started_at: i64,
+    last_wakeup: Option<i64>,
+    checks_made: usize,
+    /// Checks made since the store was last saved successfully
+    pending_checks: usize,
+    /// How often saving the store failed
+    save_errors: usize,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            started_at: chrono::Utc::now().timestamp(),
+            last_wakeup: None,
+            checks_made: 0,
+            pending_checks: 0,
+            save_errors: 0,
+        }
+    }
+
+    fn status(&self, store: &Store) -> DaemonStatus {
+        DaemonStatus {
+            pid: std::process::id(),
+            started_at: self.started_at,
+            last_wakeup: self.last_wakeup,
+            checks_made: self.checks_made,
+            pending_checks: self.pending_checks,
+            save_errors: self.save_errors,
+            store_path: store.file_path().to_path_buf(),
+        }
+    }
+}
+
+/// The check loop of the daemon, see the [module docs](self).
+pub struct Daemon {
+    store: Store,
+    /// Loaded once in [Daemon::new] and again in [Daemon::reload]
+    config: Config,
+    notifier: Notifier,
+    pusher: Pusher,
+    influx: InfluxConfig,
+    publisher: Publisher,
+    session: Session,
+    scheduler: Scheduler,
+    control: Option<ControlSocket>,
+    round_hooks: Vec<RoundHook>,
+    observers: Observers,
+    /// Only the [ScriptHooks] of the config, which are replaced on reload
+    scripts: Observers,
+}
+
+impl std::fmt::Debug for Daemon {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Daemon")
+            .field("store", &self.store.file_path())
+            .field("session", &self.session)
+            .field("scheduler", &self.scheduler)
+            .field("control", &self.control.is_some())
+            .field("round_hooks", &self.round_hooks.len())
+            .field("observers", &self.observers)
+            .field("scripts", &self.scripts)
+            .finish()
+    }
+}
+
+impl Daemon {
+    /// Creates a [Daemon] for `store`, with the checks, notifications, pushing, InfluxDB, MQTT
+    /// and [hooks](crate::hooks) as configured in the [Config].
+    ///
+    /// The [Config] is loaded once here, see [Daemon::reload] to load it again. The first round
+    /// is due at the next multiple of the period, see [scheduler]. The control socket is not
+    /// opened, see [Daemon::open_control_socket].
+    pub fn new(store: Store) -> Self {
+        let config = Config::load_or_default();
+        let probe = config.probe();
+        let scheduler = Scheduler::new(store.period_seconds(), chrono::Utc::now().timestamp());
+        Self {
+            store,
+            notifier: Notifier::new(config.notify.clone()),
+            pusher: Pusher::new(PushConfig {
+                probe: Some(probe),
+                ..config.push.clone()
+            }),
+            influx: config.influx.clone(),
+            publisher: Publisher::new(config.mqtt.clone()),
+            session: Session::new(),
+            scheduler,
+            control: None,
+            round_hooks: Vec::new(),
+            observers: Observers::default(),
+            scripts: script_hooks(config.hooks.clone()),
+            config,
+        }
+    }
+
+    /// Binds the [control socket](crate::paths::control_socket), creating its directory if
+    /// needed. Requests on it are answered in [Daemon::tick].
+    ///
+    /// # Errors
+    ///
+    /// Returns [ControlError] if the socket could not be bound.
+    pub fn open_control_socket(&mut self) -> Result<(), ControlError> {
+        let path = crate::paths::control_socket();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        self.control = Some(ControlSocket::bind(&path)?);
+        Ok(())
+    }
+
+    /// Adds `hook`, which is called with the [Store] and the new [Checks](Check) after every
+    /// round, once the store was saved.
+    pub fn on_round(&mut self, hook: impl FnMut(&Store, &[Check]) + Send + 'static) -> &mut Self {
+        self.round_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Adds `observer`, which is told about every check and about outages starting and ending,
+    /// see [observe](crate::observe).
+    pub fn observe(&mut self, observer: impl CheckObserver + 'static) -> &mut Self {
+        self.observers.add(observer);
+        self
+    }
+
+    /// Returns the [Store] of the daemon.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Returns the [Scheduler] that decides when the next round is due.
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// Returns what the daemon did so far, like it is reported over the control socket.
+    pub fn status(&self) -> DaemonStatus {
+        self.session.status(&self.store)
+    }
+
+    /// Replaces the [Store] and loads the configuration again, like after a SIGHUP.
+    ///
+    /// The rounds are scheduled anew for the period of `store`.
+    pub fn reload(&mut self, store: Store) {
+        let config = Config::load_or_default();
+        let probe = config.probe();
+        self.store = store;
+        self.notifier = Notifier::new(config.notify.clone());
+        self.pusher = Pusher::new(PushConfig {
+            probe: Some(probe),
+            ..config.push.clone()
+        });
+        self.influx = config.influx.clone();
+        self.publisher = Publisher::new(config.mqtt.clone());
+        self.scripts = script_hooks(config.hooks.clone());
+        self.config = config;
+        self.scheduler =
+            Scheduler::new(self.store.period_seconds(), chrono::Utc::now().timestamp());
+    }
+
+    /// Makes a round of checks if one is due and answers pending requests on the control
+    /// socket.
+    ///
+    /// Returns how long to wait until the next round is due. Sleep at most a second or so
+    /// between calls, to answer the control socket in time.
+    pub fn tick(&mut self) -> Duration {
+        if self.scheduler.is_due(chrono::Utc::now().timestamp()) {
+            let result = self.wakeup();
+            let now = chrono::Utc::now().timestamp();
+            match result {
+                Ok(_) => {
+                    self.scheduler.succeeded(now);
+                    // a hung or failing check loop stops the heartbeats
+                    tell_systemd(systemd::watchdog());
+                }
+                Err(err) => {
+                    error!("error in the wakeup turn: {err}");
+                    self.scheduler.failed(now);
+                }
+            }
+            debug!("next wakeup at {}", self.scheduler.next());
+        }
+        self.handle_control();
+        self.scheduler.time_until(chrono::Utc::now().timestamp())
+    }
+
+    /// Answers the pending requests on the control socket, if it is open.
+    fn handle_control(&mut self) {
+        let Some(control) = self.control.take() else {
+            return;
+        };
+        control.handle_pending(|request| match request {
+            Request::Status => Response::Status(self.status()),
+            Request::CheckNow => {
+                info!("checking now, as requested over the control socket");
+                match self.wakeup() {
+                    Ok(checks) => Response::Checks { checks },
+                    Err(err) => Response::Error {
+                        message: err.to_string(),
+                    },
+                }
+            }
+        });
+        self.control = Some(control);
+    }
+
+    /// Runs a round of checks and everything that follows it, see the [module docs](self).
+    ///
+    /// Returns the made checks.
+    ///
+    /// # Errors
+    ///
+    /// Returns [RunError] if the checks could not be formatted for the log. A store that could
+    /// not be saved is not an error, the checks are saved with the next round then.
+    pub fn wakeup(&mut self) -> Result<Vec<Check>, RunError> {
+        info!("waking up!");
+
+        let made_checks = self.store.make_checks(&self.config);
+        self.session.last_wakeup = Some(chrono::Utc::now().timestamp());
+        self.session.checks_made += made_checks.len();
+        self.session.pending_checks += made_checks.len();
+        log_checks(&made_checks)?;
+        tell_systemd(systemd::status(&round_status(&made_checks)));
+        self.notifier.round(&made_checks);
+        self.observers.round(&made_checks);
+        self.scripts.round(&made_checks);
+        self.pusher.round(&made_checks);
+        influx::export(&self.influx, &made_checks);
+        self.publisher.round(&made_checks);
+        let made_checks: Vec<Check> = made_checks.into_iter().cloned().collect();
+
+        shrink(&mut self.store);
+        self.save();
+        for hook in &mut self.round_hooks {
+            hook(&self.store, &made_checks);
+        }
+
+        info!("done!");
+        Ok(made_checks)
+    }
+
+    /// Saves the store, keeping track of the checks that could not be saved yet.
+    ///
+    /// The [outage log](crate::records::outage::OutageLog) is updated first, see
+    /// [Store::update_outages].
+    ///
+    /// Checks that were not saved stay in memory and are saved with the next successful save, but
+    /// at most [MAX_PENDING_CHECKS] of them.
+    pub fn save(&mut self) {
+        self.store.update_outages();
+        match self.store.save() {
+            Ok(()) => self.session.pending_checks = 0,
+            Err(err) => {
+                self.session.save_errors += 1;
+                error!(
+                    "error while saving to file, keeping {} unsaved checks for the next try: {err:}",
+                    self.session.pending_checks
+                );
+                if self.session.pending_checks > MAX_PENDING_CHECKS {
+                    let excess = self.session.pending_checks - MAX_PENDING_CHECKS;
+                    let checks = self.store.checks_mut();
+                    let first_pending = checks.len().saturating_sub(self.session.pending_checks);
+                    checks.drain(first_pending..(first_pending + excess).min(checks.len()));
+                    self.session.pending_checks = MAX_PENDING_CHECKS;
+                    error!("too many unsaved checks, dropped the oldest {excess}");
+                }
+            }
+        }
+    }
+
+    /// Saves the store right away and logs what the daemon holds in memory, like after a
+    /// SIGUSR1.
+    pub fn flush(&mut self) {
+        info!(
+            checks = self.store.checks().len(),
+            aggregates = self.store.aggregates().len(),
+            size_mem = self.store.deep_size_of(),
+            checks_made = self.session.checks_made,
+            last_wakeup = self.session.last_wakeup,
+            pending_checks = self.session.pending_checks,
+            save_errors = self.session.save_errors,
+            "flushing the store to {}",
+            self.store.file_path().display()
+        );
+        self.save();
+    }
+
+    /// Saves the store a last time and removes the control socket.
+    ///
+    /// # Errors
+    ///
+    /// Returns [RunError] if the store could not be saved.
+    pub fn shutdown(mut self) -> Result<(), RunError> {
+        self.store.update_outages();
+        if let Err(err) = self.store.save() {
+            error!("error while saving to file: {err:#?}");
+            return Err(err.into());
+        }
+        if self.control.take().is_some() {
+            remove_control_socket();
+        }
+        Ok(())
+    }
+}
+
+/// Makes one round of checks as configured in `config` and adds them to `store`, without a
+/// [Daemon].
+///
+/// Like [Daemon::wakeup], but outages are not notified and the checks are not pushed, exported or
+/// published, as that needs the state of the earlier rounds and background threads that only a
+/// running [Daemon] keeps. Returns the made checks.
+///
+/// # Errors
+///
+/// Returns [RunError] if the store could not be saved.
+pub fn single_round(store: &mut Store, config: &Config) -> Result<Vec<Check>, RunError> {
+    let made_checks = store.make_checks(config);
+    if let Err(e) = log_checks(&made_checks) {
+        error!("could not format the checks: {e}");
+    }
+    let made_checks: Vec<Check> = made_checks.into_iter().cloned().collect();
+    shrink(store);
+    store.update_outages();
+    store.save()?;
+    Ok(made_checks)
+}
+
+/// Removes the [control socket](crate::paths::control_socket), if it is there.
+///
+/// The socket file is left behind when the daemon exits, as the process ends before it is
+/// dropped.
+pub fn remove_control_socket() {
+    if let Err(err) = std::fs::remove_file(crate::paths::control_socket()) {
+        if !matches!(err.kind(), std::io::ErrorKind::NotFound) {
+            error!("Failed to remove the control socket: {}", err);
+        }
+    }
+}
+
+/// Returns [Observers] with the [ScriptHooks] of `config`, if any are configured.
+fn script_hooks(config: HooksConfig) -> Observers {
+    let mut observers = Observers::default();
+    if config.is_enabled() {
+        observers.add(ScriptHooks::new(config));
+    }
+    observers
+}
+
+/// Logs the checks of one round, each with its fields and all of them as a table for debugging.
+fn log_checks(checks: &[&Check]) -> Result<(), RunError> {
+    for check in checks {
+        info!(
+            check_type = %check.calc_type().unwrap_or(CheckType::Unknown),
+            target = %check.target_scoped(),
+            label = check.label(),
+            latency = check.latency(),
+            http_status = check.http_status(),
+            success = check.is_success(),
+            "made check"
+        );
+    }
+    let mut buf = String::new();
+    display_group(checks, &mut buf)?;
+    debug!("Made checks\n{buf}");
+    Ok(())
+}
+
+/// Downsamples and archives old checks, if configured.
+fn shrink(store: &mut Store) {
+    if let Some(days) = store.downsample_after_days() {
+        store.downsample(days);
+    }
+    #[cfg(feature = "chunked")]
+    if let Some(days) = store.archive_after_days() {
+        if let Err(e) = store.archive(days) {
+            error!("could not archive old checks: {e}");
+        }
+    }
+}
+
+/// Summarizes a round of checks for the status line of systemd.
+fn round_status(checks: &[&Check]) -> String {
+    let failed = checks.iter().filter(|c| !c.is_success()).count();
+    let time = chrono::Local::now().format("%H:%M:%S");
+    if failed == 0 {
+        format!(
+            "last round at {time}: all {} checks succeeded",
+            checks.len()
+        )
+    } else {
+        format!(
+            "last round at {time}: {failed} of {} checks failed",
+            checks.len()
+        )
+    }
+}
+
+/// Logs if a message to systemd could not be sent, see [systemd].
+pub fn tell_systemd(result: std::io::Result<bool>) {
+    if let Err(e) = result {
+        warn!("could not notify systemd: {e}");
+    }
+}
@@ -0,0 +1,145 @@
+//! Scheduling of the wakeups of the daemon.
+//!
+//! Wakeups are aligned to multiples of the period (every full minute by default), so the checks
+//! of different rounds are evenly spaced. The [Scheduler] computes the next deadline from the
+//! current time instead of polling for it, so a wakeup that takes longer than expected or a
+//! suspended machine does not lead to skipped or doubled wakeups: missed deadlines are not
+//! made up for, the next one is simply the next aligned one in the future.
+//!
+//! # Jitter
+//!
+//! Many daemons checking the same targets at the same full minute cause bursts of traffic. Set
+//! [ENV_JITTER] to a number of seconds to delay each wakeup by a random amount up to that.
+//!
+//! # Backoff
+//!
+//! If a wakeup fails as a whole, the next ones are spaced further apart: every failure in a row
+//! doubles the distance, up to [MAX_BACKOFF] periods. The first successful wakeup resets it.
+
+use std::hash::{BuildHasher, RandomState};
+use std::time::Duration;
+
+use tracing::warn;
+
+/// Environment variable for the maximum random delay of a wakeup in seconds
+pub const ENV_JITTER: &str = "NETPULSE_JITTER";
+/// How many periods the wakeups are spaced apart at most after failures
+pub const MAX_BACKOFF: i64 = 8;
+
+/// Decides when the daemon wakes up next
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    /// Seconds between two wakeups
+    period: i64,
+    /// Maximum random delay of a wakeup in seconds
+    jitter: i64,
+    /// Unix timestamp of the next wakeup
+    next: i64,
+    /// How many wakeups in a row failed
+    failures: u32,
+}
+
+impl Scheduler {
+    /// Creates a [Scheduler] with the jitter from [ENV_JITTER], due at the next aligned time.
+    pub fn new(period: i64, now: i64) -> Self {
+        let jitter = match std::env::var(ENV_JITTER) {
+            Err(_) => 0,
+            Ok(raw) => raw.parse().unwrap_or_else(|_| {
+                warn!("bad value for {ENV_JITTER}: '{raw}', using no jitter");
+                0
+            }),
+        };
+        Self::with_jitter(period, jitter, now)
+    }
+
+    /// Creates a [Scheduler] with the given jitter, due at the next aligned time.
+    ///
+    /// The jitter is capped to the period.
+    pub fn with_jitter(period: i64, jitter: i64, now: i64) -> Self {
+        let period = period.max(1);
+        let mut scheduler = Self {
+            period,
+            jitter: jitter.clamp(0, period - 1),
+            next: 0,
+            failures: 0,
+        };
+        scheduler.next = scheduler.deadline_after(now, 1);
+        scheduler
+    }
+
+    /// Returns the unix timestamp of the next wakeup.
+    pub fn next(&self) -> i64 {
+        self.next
+    }
+
+    /// Returns whether the next wakeup is due.
+    pub fn is_due(&self, now: i64) -> bool {
+        now >= self.next
+    }
+
+    /// Returns how long to wait until the next wakeup.
+    pub fn time_until(&self, now: i64) -> Duration {
+        Duration::from_secs((self.next - now).max(0) as u64)
+    }
+
+    /// Schedules the next wakeup after one that succeeded.
+    pub fn succeeded(&mut self, now: i64) {
+        self.failures = 0;
+        self.next = self.deadline_after(now, 1);
+    }
+
+    /// Schedules the next wakeup after one that failed, backing off.
+    pub fn failed(&mut self, now: i64) {
+        self.failures = self.failures.saturating_add(1);
+        let periods = 2_i64.saturating_pow(self.failures).min(MAX_BACKOFF);
+        self.next = self.deadline_after(now, periods);
+    }
+
+    /// Returns the first aligned time at least `periods` periods after the last aligned time
+    /// before `now`, plus jitter.
+    fn deadline_after(&self, now: i64, periods: i64) -> i64 {
+        let aligned = now - now.rem_euclid(self.period) + periods * self.period;
+        aligned + self.random_jitter()
+    }
+
+    fn random_jitter(&self) -> i64 {
+        if self.jitter == 0 {
+            return 0;
+        }
+        // every RandomState is seeded randomly, good enough for spreading out wakeups
+        let random = RandomState::new().hash_one(self.next);
+        (random % (self.jitter as u64 + 1)) as i64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scheduler() {
+        let mut scheduler = Scheduler::with_jitter(60, 0, 1000);
+        assert_eq!(scheduler.next(), 1020);
+        assert!(!scheduler.is_due(1019));
+        assert!(scheduler.is_due(1020));
+        assert_eq!(scheduler.time_until(1000), Duration::from_secs(20));
+
+        // a wakeup that took longer than a period does not fire twice
+        scheduler.succeeded(1090);
+        assert_eq!(scheduler.next(), 1140);
+
+        scheduler.failed(1140);
+        assert_eq!(scheduler.next(), 1260);
+        scheduler.failed(1260);
+        assert_eq!(scheduler.next(), 1500);
+        for _ in 0..10 {
+            scheduler.failed(1500);
+        }
+        assert_eq!(scheduler.next(), 1500 + MAX_BACKOFF * 60);
+        scheduler.succeeded(2000);
+        assert_eq!(scheduler.next(), 2040);
+
+        let jittered = Scheduler::with_jitter(60, 10, 1000);
+        assert!((1020..=1030).contains(&jittered.next()));
+    }
+}
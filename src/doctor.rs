@@ -0,0 +1,338 @@
+//! Diagnostics of the environment netpulse runs in, see `netpulse --doctor`.
+//!
+//! When no checks show up, or all of them fail, the cause is often not the network but the
+//! setup: a store that can not be written, a daemon that is not running, or a missing capability.
+//! [diagnose] goes through everything netpulse needs and returns a [Finding] for each:
+//!
+//! - Whether the store file, or the directory it will be created in, is writable
+//! - Whether the store file has the current [Version]
+//! - Whether this process has the `CAP_NET_RAW` capability that ICMP checks need
+//! - Whether host names can be resolved
+//! - Whether HTTP and ICMP checks against a known-good target work, see [KNOWN_GOOD]
+//! - Whether the daemon is running
+//!
+//! Checks against the network can take up to [TIMEOUT](crate::TIMEOUT) each.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use netpulse::doctor::diagnose;
+//!
+//! for finding in diagnose() {
+//!     println!("{finding}");
+//! }
+//! ```
+
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::checks::bind::Bind;
+use crate::checks::{checker, has_cap_net_raw};
+use crate::common::getpid_running;
+use crate::config::Target;
+use crate::errors::StoreError;
+use crate::records::{CheckType, TARGETS};
+use crate::store::{Store, Version};
+use crate::DAEMON_USER;
+
+/// Target of the HTTP and ICMP checks, which should always answer
+pub const KNOWN_GOOD: &str = TARGETS[0];
+
+/// Host name that is resolved to see whether DNS works
+pub const KNOWN_HOST: &str = "one.one.one.one";
+
+/// How a part of the environment is doing, see [Finding].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Everything is fine
+    Pass,
+    /// Something might not work, or could not be checked
+    Warn,
+    /// Something does not work
+    Fail,
+}
+
+/// Result of one diagnostic, see [diagnose].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    /// What was looked at, like `store version`
+    pub name: &'static str,
+    /// How it is doing
+    pub status: Status,
+    /// What was found, and what to do about it if it is not fine
+    pub detail: String,
+}
+
+impl Finding {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pass => write!(f, "PASS"),
+            Self::Warn => write!(f, "WARN"),
+            Self::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {:<16}: {}", self.status, self.name, self.detail)
+    }
+}
+
+/// Runs all diagnostics, see the [module docs](self).
+pub fn diagnose() -> Vec<Finding> {
+    let path = Store::path();
+    let known_good: IpAddr = KNOWN_GOOD
+        .parse()
+        .expect("the known good target is no address");
+    vec![
+        store_writable(&path),
+        store_version(&path),
+        cap_net_raw(),
+        dns_resolution(KNOWN_HOST),
+        outbound("outbound HTTP", CheckType::Http, known_good),
+        outbound("outbound ICMP", CheckType::Icmp, known_good),
+        daemon(),
+    ]
+}
+
+/// Returns the worst [Status] of `findings`, [Status::Pass] if there are none.
+pub fn worst(findings: &[Finding]) -> Status {
+    findings
+        .iter()
+        .map(|f| f.status)
+        .max()
+        .unwrap_or(Status::Pass)
+}
+
+/// Tells whether the store file at `path`, or the directory it will be created in, is writable.
+///
+/// Outside of [user mode](crate::paths::user_mode), the daemon writes the store as
+/// [DAEMON_USER], so it not being writable by others is only a warning.
+fn store_writable(path: &Path) -> Finding {
+    const NAME: &str = "store writable";
+    let not_ours = if crate::paths::user_mode() {
+        Status::Fail
+    } else {
+        Status::Warn
+    };
+    if path.exists() {
+        return match OpenOptions::new().append(true).open(path) {
+            Ok(_) => Finding::new(NAME, Status::Pass, path.display().to_string()),
+            Err(e) => Finding::new(
+                NAME,
+                not_ours,
+                format!(
+                    "{} can not be written by this user: {e}, the daemon writes it as {DAEMON_USER}",
+                    path.display()
+                ),
+            ),
+        };
+    }
+    let Some(dir) = path.parent() else {
+        return Finding::new(NAME, Status::Fail, "the store has no directory");
+    };
+    if !dir.exists() {
+        return Finding::new(
+            NAME,
+            Status::Warn,
+            format!(
+                "{} does not exist yet, run netpulsed --setup",
+                dir.display()
+            ),
+        );
+    }
+    let probe = dir.join(".netpulse-doctor");
+    match OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Finding::new(
+                NAME,
+                Status::Pass,
+                format!("{} can be created", path.display()),
+            )
+        }
+        Err(e) => Finding::new(
+            NAME,
+            not_ours,
+            format!("{} can not be created by this user: {e}", path.display()),
+        ),
+    }
+}
+
+/// Tells whether the store file at `path` has the current [Version].
+fn store_version(path: &Path) -> Finding {
+    const NAME: &str = "store version";
+    if !path.exists() {
+        return Finding::new(
+            NAME,
+            Status::Warn,
+            "there is no store yet, the daemon creates it with its first checks",
+        );
+    }
+    match Store::peek_file_version_of(path) {
+        Ok(version) if version == Version::CURRENT => Finding::new(
+            NAME,
+            Status::Pass,
+            format!("{version}, the current version"),
+        ),
+        Ok(version) if version < Version::CURRENT => Finding::new(
+            NAME,
+            Status::Warn,
+            format!(
+                "{version}, older than the current version {}, run netpulse --migrate",
+                Version::CURRENT
+            ),
+        ),
+        Ok(version) => Finding::new(
+            NAME,
+            Status::Fail,
+            format!(
+                "{version}, newer than the current version {}, update netpulse",
+                Version::CURRENT
+            ),
+        ),
+        Err(StoreError::NewerStore(raw)) => Finding::new(
+            NAME,
+            Status::Warn,
+            format!(
+                "{raw}, newer than the current version {}, it can only be read, update netpulse",
+                Version::CURRENT
+            ),
+        ),
+        Err(e) => Finding::new(
+            NAME,
+            Status::Fail,
+            format!("could not be read: {e}, try netpulse --repair"),
+        ),
+    }
+}
+
+/// Tells whether this process has the `CAP_NET_RAW` capability, see [has_cap_net_raw].
+fn cap_net_raw() -> Finding {
+    const NAME: &str = "CAP_NET_RAW";
+    if has_cap_net_raw() {
+        Finding::new(NAME, Status::Pass, "raw sockets can be opened")
+    } else {
+        Finding::new(
+            NAME,
+            Status::Warn,
+            "missing for this process, ICMP checks only work if the daemon has it",
+        )
+    }
+}
+
+/// Tells whether `host` can be resolved by the resolver of the system.
+fn dns_resolution(host: &str) -> Finding {
+    const NAME: &str = "DNS resolution";
+    match (host, 0).to_socket_addrs() {
+        Ok(mut addrs) => match addrs.next() {
+            Some(addr) => Finding::new(
+                NAME,
+                Status::Pass,
+                format!("{host} resolves to {}", addr.ip()),
+            ),
+            None => Finding::new(NAME, Status::Fail, format!("{host} has no addresses")),
+        },
+        Err(e) => Finding::new(
+            NAME,
+            Status::Fail,
+            format!("{host} could not be resolved: {e}"),
+        ),
+    }
+}
+
+/// Makes a check of `check_type` against `remote` with its [Checker](checker::Checker).
+///
+/// ICMP checks are skipped without the `CAP_NET_RAW` capability, as they could not work.
+fn outbound(name: &'static str, check_type: CheckType, remote: IpAddr) -> Finding {
+    let Some(checker) = checker::get(check_type) else {
+        return Finding::new(
+            name,
+            Status::Warn,
+            format!("skipped, {check_type} checks are not enabled in this build"),
+        );
+    };
+    if matches!(check_type, CheckType::Icmp) && !has_cap_net_raw() {
+        return Finding::new(name, Status::Warn, "skipped, CAP_NET_RAW is missing");
+    }
+    let outcome = checker.check(&Target::new(remote), &Bind::default());
+    match (outcome.error, outcome.latency) {
+        (None, latency) => Finding::new(
+            name,
+            Status::Pass,
+            format!(
+                "{remote} answered{}",
+                latency.map_or(String::new(), |l| format!(" in {} ms", l.as_millis()))
+            ),
+        ),
+        (Some(e), _) => Finding::new(name, Status::Fail, format!("{remote} failed: {e}")),
+    }
+}
+
+/// Tells whether the daemon is running, see [getpid_running].
+fn daemon() -> Finding {
+    const NAME: &str = "daemon";
+    match getpid_running() {
+        Some(pid) => Finding::new(NAME, Status::Pass, format!("running with pid {pid}")),
+        None => Finding::new(
+            NAME,
+            Status::Fail,
+            "not running, start it with netpulsed --daemon",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_store_findings() {
+        let dir = std::env::temp_dir().join(format!("netpulse-doctor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("netpulse.store");
+
+        assert_eq!(store_writable(&path).status, Status::Pass);
+        assert!(!dir.join(".netpulse-doctor").exists());
+        assert_eq!(store_version(&path).status, Status::Warn);
+
+        std::fs::write(&path, b"garbage").unwrap();
+        assert_eq!(store_writable(&path).status, Status::Pass);
+        assert_eq!(store_version(&path).status, Status::Fail);
+
+        let missing = dir.join("missing").join("netpulse.store");
+        assert_eq!(store_writable(&missing).status, Status::Warn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_worst() {
+        let finding = |status| Finding::new("test", status, "");
+        assert_eq!(worst(&[]), Status::Pass);
+        assert_eq!(
+            worst(&[finding(Status::Pass), finding(Status::Warn)]),
+            Status::Warn
+        );
+        assert_eq!(
+            worst(&[finding(Status::Fail), finding(Status::Warn)]),
+            Status::Fail
+        );
+        assert!(finding(Status::Fail).to_string().starts_with("[FAIL] test"));
+    }
+}
@@ -5,6 +5,10 @@
 //! - [`CheckError`] - Errors that occur during network checks (HTTP, ICMP)
 //! - [`RunError`] - Errors specific to executable operations
 //! - [`AnalysisError`] - Errors that occur during analysis and report generation
+//! - [`ConfigError`] - Errors that occur while loading the configuration file
+//! - [`NotifyError`] - Errors that occur while sending notifications about outages
+//! - [`PushError`] - Errors that occur while pushing checks to a central server or InfluxDB
+//! - [`ControlError`] - Errors that occur on the control socket of the daemon
 //!
 //! All error types implement the standard Error trait and provide detailed error information.
 //!
@@ -59,6 +63,25 @@ pub enum StoreError {
         #[from]
         source: bincode::Error,
     },
+    /// Failed to encode a record of the store as CBOR.
+    ///
+    /// Since [Version::V3](crate::store::Version::V3), the records of the store are encoded as
+    /// CBOR.
+    #[error("Could not encode the store as CBOR: {source}")]
+    CborEncode {
+        /// Underlying error
+        #[from]
+        source: ciborium::ser::Error<std::io::Error>,
+    },
+    /// Failed to decode a record of the store from CBOR.
+    ///
+    /// This typically indicates corruption, like [StoreError::Load] for older stores.
+    #[error("Could not decode the store from CBOR: {source}")]
+    CborDecode {
+        /// Underlying error
+        #[from]
+        source: ciborium::de::Error<std::io::Error>,
+    },
     /// Failed to convert data to UTF-8.
     ///
     /// This can occur when reading store metadata like file hashes.
@@ -92,9 +115,51 @@ pub enum StoreError {
     /// is not defined. Only known [Versions][crate::store::Version] are valid.
     #[error("Tried to load a store version that does not exist: {0}")]
     BadStoreVersion(u8),
+    /// The store file was written by a newer version of netpulse.
+    ///
+    /// Its [Version](crate::store::Version) is newer than
+    /// [Version::CURRENT](crate::store::Version::CURRENT), so it can not be migrated.
+    #[error("The store has version {0}, which is newer than this version of netpulse")]
+    NewerStore(u8),
     /// A store can be loaded as readonly if it's corrupted or there is a version mismatch
     #[error("Tried to save a readonly store")]
     IsReadonly,
+    /// The store file is truncated or corrupted.
+    ///
+    /// Since [Version::V3](crate::store::Version::V3), the store file contains a checksum of
+    /// its data. This error occurs when that data can not be read completely or does not match
+    /// the checksum.
+    #[error("The store file is corrupt or truncated")]
+    CorruptStore,
+    /// The data of the store file is compressed, but this build of netpulse does not have the
+    /// `compression` feature to decompress it.
+    #[error(
+        "The store file is compressed, but netpulse was built without the compression feature"
+    )]
+    CompressionUnsupported,
+    /// Another process held the lock of the store file for too long.
+    ///
+    /// This variant contains the path of the lock file, see [lock](crate::store::lock). Usually
+    /// another daemon or a `netpulse --rewrite` is writing the store.
+    #[error("The store is locked by another process, see {}", .0.display())]
+    Locked(std::path::PathBuf),
+    /// The name of a probe can not be used as the name of its store file.
+    ///
+    /// This variant is only available when the `server` feature is enabled, see
+    /// [is_valid_probe](crate::server::is_valid_probe).
+    #[cfg(feature = "server")]
+    #[error("Bad probe name: {0:?}")]
+    BadProbe(String),
+    /// An error occurred in the SQLite database.
+    ///
+    /// This variant is only available when the `sqlite` feature is enabled.
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite Error: {source}")]
+    Sqlite {
+        /// Underlying error
+        #[from]
+        source: rusqlite::Error,
+    },
 }
 
 /// Errors that can occur during network checks.
@@ -112,16 +177,54 @@ pub enum CheckError {
         #[from]
         source: std::io::Error,
     },
-    /// An error occurred during ICMP ping.
+    /// The target did not answer at all.
+    #[error("The target did not answer")]
+    NoReply,
+    /// The DNS server answered, but not with a valid answer to the query.
+    #[error("Bad DNS response: {0}")]
+    BadDnsResponse(&'static str),
+    /// The NTP server answered, but not with a valid answer, or it is not synchronized.
+    #[error("Bad NTP response: {0}")]
+    BadNtpResponse(&'static str),
+    /// The server answered, but with an error status, like an HTTP status of 500 or more.
+    #[error("Server error, status {0}")]
+    ServerError(u16),
+    /// An error occurred during the TLS handshake of a DNS over TLS or QUIC check.
     ///
-    /// This variant is only available when the `ping` feature is enabled.
-    #[cfg(feature = "ping")]
-    #[error("Ping Error: {source}")]
-    Ping {
+    /// This variant is only available when the `dot` or `quic` feature is enabled.
+    #[cfg(any(feature = "dot", feature = "quic"))]
+    #[error("TLS Error: {source}")]
+    Tls {
         /// Underlying error
         #[from]
-        source: ping::Error,
+        source: rustls::Error,
     },
+    /// The QUIC connection of a QUIC check failed or was closed.
+    ///
+    /// This variant is only available when the `quic` feature is enabled.
+    #[cfg(feature = "quic")]
+    #[error("QUIC Error: {source}")]
+    Quic {
+        /// Underlying error
+        #[from]
+        source: quinn::ConnectionError,
+    },
+    /// The QUIC connection of a QUIC check could not be started.
+    ///
+    /// This variant is only available when the `quic` feature is enabled.
+    #[cfg(feature = "quic")]
+    #[error("QUIC Error: {source}")]
+    QuicConnect {
+        /// Underlying error
+        #[from]
+        source: quinn::ConnectError,
+    },
+    /// The server answered a QUIC check, but not with a valid HTTP/3 response.
+    ///
+    /// This variant is only available when the `quic` feature is enabled.
+    #[cfg(feature = "quic")]
+    #[error("Bad HTTP/3 response: {0}")]
+    BadHttp3Response(&'static str),
     /// An error occurred during HTTP check.
     ///
     /// This variant is only available when the `http` feature is enabled.
@@ -132,6 +235,106 @@ pub enum CheckError {
         #[from]
         source: curl::Error,
     },
+    /// An error occurred during an HTTP check with the pure Rust client.
+    ///
+    /// This variant is only available when the `http-rustls` feature is enabled.
+    #[cfg(feature = "http-rustls")]
+    #[error("Http Error: {source}")]
+    HttpRustls {
+        /// Underlying error
+        #[from]
+        source: Box<ureq::Error>,
+    },
+    /// An error occurred during an asynchronous HTTP check.
+    ///
+    /// This variant is only available when the `async` feature is enabled.
+    #[cfg(feature = "async")]
+    #[error("Http Error: {source}")]
+    AsyncHttp {
+        /// Underlying error
+        #[from]
+        source: reqwest::Error,
+    },
+    /// A blocking check could not be run on the runtime of an asynchronous check.
+    ///
+    /// This variant is only available when the `async` feature is enabled.
+    #[cfg(feature = "async")]
+    #[error("Could not run the check: {source}")]
+    Join {
+        /// Underlying error
+        #[from]
+        source: tokio::task::JoinError,
+    },
+}
+
+impl CheckError {
+    /// Returns the [CheckFlag] that tells why a check failed with this error, if it is known.
+    ///
+    /// - [CheckFlag::Timeout]: the target did not answer in time
+    /// - [CheckFlag::Refused]: the target refused or reset the connection
+    /// - [CheckFlag::Unreachable]: there is no route to the target
+    /// - [CheckFlag::DnsFailure]: a host name could not be resolved, or the DNS server did not
+    ///   give a valid answer
+    /// - [CheckFlag::ServerError]: the server answered with an error status
+    pub fn reason(&self) -> Option<CheckFlag> {
+        match self {
+            Self::NoReply => Some(CheckFlag::Timeout),
+            Self::ServerError(_) => Some(CheckFlag::ServerError),
+            Self::BadDnsResponse(_) => Some(CheckFlag::DnsFailure),
+            #[cfg(feature = "http")]
+            Self::Http { source } if source.is_operation_timedout() => Some(CheckFlag::Timeout),
+            #[cfg(feature = "http")]
+            Self::Http { source } if source.is_couldnt_resolve_host() => {
+                Some(CheckFlag::DnsFailure)
+            }
+            #[cfg(feature = "http")]
+            Self::Http { source } if source.is_couldnt_connect() => Some(CheckFlag::Unreachable),
+            #[cfg(feature = "http-rustls")]
+            Self::HttpRustls { source } => match source.as_ref() {
+                ureq::Error::Timeout(_) => Some(CheckFlag::Timeout),
+                ureq::Error::HostNotFound => Some(CheckFlag::DnsFailure),
+                ureq::Error::ConnectionFailed => Some(CheckFlag::Unreachable),
+                ureq::Error::Io(err) => io_reason(err),
+                _ => None,
+            },
+            #[cfg(feature = "async")]
+            Self::AsyncHttp { source } if source.is_timeout() => Some(CheckFlag::Timeout),
+            #[cfg(feature = "quic")]
+            Self::Quic {
+                source: quinn::ConnectionError::TimedOut,
+            } => Some(CheckFlag::Timeout),
+            #[cfg(feature = "quic")]
+            Self::Quic {
+                source: quinn::ConnectionError::Reset,
+            } => Some(CheckFlag::Refused),
+            _ => {
+                // most errors of the network wrap an I/O error somewhere
+                let mut source = std::error::Error::source(self);
+                while let Some(err) = source {
+                    if let Some(err) = err.downcast_ref::<std::io::Error>() {
+                        return io_reason(err);
+                    }
+                    source = err.source();
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Returns the [CheckFlag] that tells why a check failed with the I/O error `err`, see
+/// [CheckError::reason].
+fn io_reason(err: &std::io::Error) -> Option<CheckFlag> {
+    use std::io::ErrorKind;
+    match err.kind() {
+        // sockets with a read timeout report it as WouldBlock on unix
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => Some(CheckFlag::Timeout),
+        ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset => Some(CheckFlag::Refused),
+        ErrorKind::HostUnreachable | ErrorKind::NetworkUnreachable | ErrorKind::NetworkDown => {
+            Some(CheckFlag::Unreachable)
+        }
+        _ => None,
+    }
 }
 
 /// Errors that can occur during daemon operations.
@@ -161,6 +364,43 @@ pub enum RunError {
         #[from]
         source: std::fmt::Error,
     },
+    /// An error occurred while analyzing the store.
+    #[error("Something went wrong with the analysis: {source}")]
+    AnalysisError {
+        /// Underlying error
+        #[from]
+        source: AnalysisError,
+    },
+    /// Failed to serialize output as JSON.
+    ///
+    /// This variant is only available when the `executable` feature is enabled.
+    #[cfg(feature = "executable")]
+    #[error("Could not serialize to JSON: {source}")]
+    Json {
+        /// Underlying error
+        #[from]
+        source: serde_json::Error,
+    },
+    /// An error occurred while loading the configuration.
+    #[error("Something went wrong with the config: {source}")]
+    ConfigError {
+        /// Underlying error
+        #[from]
+        source: ConfigError,
+    },
+    /// The capabilities of the process could not be changed.
+    ///
+    /// This variant is only available on Linux.
+    #[cfg(target_os = "linux")]
+    #[error("Could not change the capabilities: {source}")]
+    Caps {
+        /// Underlying error
+        #[from]
+        source: caps::errors::CapsError,
+    },
+    /// The user to drop the privileges to does not exist.
+    #[error("The user {0} does not exist")]
+    NoSuchUser(String),
 }
 
 /// Errors that can occur during analysis and report generation.
@@ -190,4 +430,150 @@ pub enum AnalysisError {
         #[from]
         source: std::io::Error,
     },
+    /// A graph could not be drawn.
+    ///
+    /// This variant is only available when the `graph` feature is enabled.
+    #[cfg(feature = "graph")]
+    #[error("Could not draw the graph: {reason}")]
+    Graph {
+        /// What went wrong
+        reason: String,
+    },
+}
+
+/// Errors that can occur while loading the [Config](crate::config::Config).
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    /// An I/O error occurred while reading the config file.
+    #[error("IO Error: {source}")]
+    Io {
+        /// Underlying error
+        #[from]
+        source: std::io::Error,
+    },
+    /// The config file is not valid TOML or does not match the expected structure.
+    #[error("Could not parse the config file: {source}")]
+    Parse {
+        /// Underlying error
+        #[from]
+        source: toml::de::Error,
+    },
+}
+
+/// Errors that can occur while sending a notification, see [notify](crate::notify).
+#[derive(Error, Debug)]
+pub enum NotifyError {
+    /// The request to the webhook failed.
+    ///
+    /// This variant is only available when the `webhook` feature is enabled.
+    #[cfg(feature = "webhook")]
+    #[error("Http Error: {source}")]
+    Http {
+        /// Underlying error
+        #[from]
+        source: curl::Error,
+    },
+    /// The payload of the notification could not be serialized.
+    ///
+    /// This variant is only available when the `webhook` feature is enabled.
+    #[cfg(feature = "webhook")]
+    #[error("Could not serialize the notification: {source}")]
+    Json {
+        /// Underlying error
+        #[from]
+        source: serde_json::Error,
+    },
+    /// The email could not be sent.
+    ///
+    /// This variant is only available when the `notify-email` feature is enabled.
+    #[cfg(feature = "notify-email")]
+    #[error("SMTP Error: {source}")]
+    Smtp {
+        /// Underlying error
+        #[from]
+        source: lettre::transport::smtp::Error,
+    },
+    /// The email could not be built.
+    ///
+    /// This variant is only available when the `notify-email` feature is enabled.
+    #[cfg(feature = "notify-email")]
+    #[error("Could not build the email: {source}")]
+    Email {
+        /// Underlying error
+        #[from]
+        source: lettre::error::Error,
+    },
+    /// An address in the [EmailConfig](crate::config::EmailConfig) is not valid.
+    ///
+    /// This variant is only available when the `notify-email` feature is enabled.
+    #[cfg(feature = "notify-email")]
+    #[error("Bad email address: {source}")]
+    Address {
+        /// Underlying error
+        #[from]
+        source: lettre::address::AddressError,
+    },
+    /// The receiver answered with a status code that is not a success.
+    #[error("The receiver answered with HTTP status {status}")]
+    BadStatus {
+        /// HTTP status code of the response
+        status: u32,
+    },
+}
+
+/// Errors that can occur while pushing checks to a central server, see [push](crate::push), or
+/// writing them to InfluxDB, see [influx](crate::records::influx).
+#[derive(Error, Debug)]
+pub enum PushError {
+    /// The request to the server failed.
+    ///
+    /// This variant is only available when the `push` or `influx` feature is enabled.
+    #[cfg(any(feature = "push", feature = "influx"))]
+    #[error("Http Error: {source}")]
+    Http {
+        /// Underlying error
+        #[from]
+        source: curl::Error,
+    },
+    /// The checks could not be serialized.
+    ///
+    /// This variant is only available when the `push` feature is enabled.
+    #[cfg(feature = "push")]
+    #[error("Could not serialize the checks: {source}")]
+    Json {
+        /// Underlying error
+        #[from]
+        source: serde_json::Error,
+    },
+    /// The server answered with a status code that is not a success.
+    #[error("The server answered with HTTP status {status}")]
+    BadStatus {
+        /// HTTP status code of the response
+        status: u32,
+    },
+}
+
+/// Errors that can occur while talking over the [control socket](crate::control) of the daemon.
+#[derive(Error, Debug)]
+pub enum ControlError {
+    /// An I/O error occurred on the socket.
+    #[error("IO Error: {source}")]
+    Io {
+        /// Underlying error
+        #[from]
+        source: std::io::Error,
+    },
+    /// A message could not be (de)serialized.
+    ///
+    /// This variant is only available when the `executable` feature is enabled.
+    #[cfg(feature = "executable")]
+    #[error("Bad message on the control socket: {source}")]
+    Json {
+        /// Underlying error
+        #[from]
+        source: serde_json::Error,
+    },
+    /// The daemon closed the connection without answering.
+    #[error("The daemon did not answer")]
+    NoAnswer,
 }
@@ -0,0 +1,241 @@
+//! Commands that the daemon runs on events, configured in the `[hooks]` table, see [HooksConfig].
+//!
+//! [ScriptHooks] is a [CheckObserver] that runs the command of an event with `sh -c` when an
+//! outage starts or ends, or a round of checks is complete. The commands run in the background,
+//! so a slow one does not hold up the checks, and are killed after the
+//! [timeout](HooksConfig::timeout). Their output goes to the log of the daemon.
+//!
+//! # Environment
+//!
+//! The details of the event are passed in environment variables:
+//!
+//! | Variable | Events | Content |
+//! |---|---|---|
+//! | `NETPULSE_EVENT` | all | `outage_started`, `outage_ended` or `round` |
+//! | `NETPULSE_JSON` | all | the outage like the webhook payload, or the checks of the round |
+//! | `NETPULSE_START`, `NETPULSE_END` | outages | unix timestamps of the first and last check |
+//! | `NETPULSE_DURATION` | outages | seconds between the first and last check |
+//! | `NETPULSE_SEVERITY` | outages | `complete`, `partial`, `degraded` or `none` |
+//! | `NETPULSE_TARGETS` | outages | affected targets, separated by commas |
+//! | `NETPULSE_CHECK_TYPES` | outages | affected check types, separated by commas |
+//! | `NETPULSE_SUMMARY` | outages | a one line description, like in `netpulse --outages` |
+//! | `NETPULSE_TIME` | rounds | unix timestamp of the round |
+//! | `NETPULSE_CHECKS` | all | number of checks |
+//! | `NETPULSE_FAILED` | rounds | number of failed checks |
+//!
+//! # Example
+//!
+//! ```toml
+//! [hooks]
+//! outage_start = "logger -t netpulse \"outage: $NETPULSE_SUMMARY\""
+//! outage_end = "/usr/local/bin/netpulse-recovered"
+//! timeout = 10
+//! ```
+
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, error, warn};
+
+use crate::analyze::outage::{OutageSummary, Severity};
+use crate::config::HooksConfig;
+use crate::notify::Event;
+use crate::observe::CheckObserver;
+use crate::records::Check;
+
+/// How often to look whether a running command has exited
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runs the commands of a [HooksConfig] on events, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct ScriptHooks {
+    config: HooksConfig,
+}
+
+impl ScriptHooks {
+    /// Creates [ScriptHooks] that run the commands of `config`.
+    pub fn new(config: HooksConfig) -> Self {
+        Self { config }
+    }
+
+    /// Runs `command` in a background thread with the environment `env`.
+    fn spawn(&self, command: &str, env: Vec<(&'static str, String)>) {
+        let command = command.to_string();
+        let timeout = self.config.timeout();
+        std::thread::spawn(move || run(&command, &env, timeout));
+    }
+}
+
+impl CheckObserver for ScriptHooks {
+    fn on_outage_start(&mut self, outage: &OutageSummary) {
+        if let Some(command) = &self.config.outage_start {
+            self.spawn(command, outage_env(&Event::OutageStarted(outage.clone())));
+        }
+    }
+
+    fn on_outage_end(&mut self, outage: &OutageSummary) {
+        if let Some(command) = &self.config.outage_end {
+            self.spawn(command, outage_env(&Event::OutageEnded(outage.clone())));
+        }
+    }
+
+    fn on_round(&mut self, checks: &[&Check]) {
+        if let Some(command) = &self.config.round {
+            self.spawn(command, round_env(checks));
+        }
+    }
+}
+
+/// Runs `command` with `sh -c` and waits for it, killing it after `timeout`.
+///
+/// Returns true if the command exited successfully in time.
+fn run(command: &str, env: &[(&'static str, String)], timeout: Duration) -> bool {
+    let mut child = match Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            error!("could not run the hook '{command}': {e}");
+            return false;
+        }
+    };
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => {
+                debug!("the hook '{command}' finished");
+                return true;
+            }
+            Ok(Some(status)) => {
+                warn!("the hook '{command}' failed: {status}");
+                return false;
+            }
+            Ok(None) if start.elapsed() < timeout => std::thread::sleep(POLL_INTERVAL),
+            Ok(None) => {
+                warn!(
+                    "the hook '{command}' ran longer than {}s, killing it",
+                    timeout.as_secs()
+                );
+                if let Err(e) = child.kill() {
+                    error!("could not kill the hook '{command}': {e}");
+                }
+                let _ = child.wait();
+                return false;
+            }
+            Err(e) => {
+                error!("could not wait for the hook '{command}': {e}");
+                return false;
+            }
+        }
+    }
+}
+
+/// Returns the environment of the command for an outage [Event].
+fn outage_env(event: &Event) -> Vec<(&'static str, String)> {
+    let (name, outage) = match event {
+        Event::OutageStarted(outage) => ("outage_started", outage),
+        Event::OutageEnded(outage) => ("outage_ended", outage),
+    };
+    let join = |items: Vec<String>| items.join(",");
+    vec![
+        ("NETPULSE_EVENT", name.to_string()),
+        (
+            "NETPULSE_JSON",
+            serde_json::to_string(event).unwrap_or_default(),
+        ),
+        ("NETPULSE_START", outage.start.to_string()),
+        ("NETPULSE_END", outage.end.to_string()),
+        ("NETPULSE_DURATION", outage.duration.to_string()),
+        ("NETPULSE_CHECKS", outage.checks.to_string()),
+        (
+            "NETPULSE_SEVERITY",
+            match outage.severity {
+                Severity::Complete => "complete",
+                Severity::Partial(_) => "partial",
+                Severity::Degraded(_) => "degraded",
+                Severity::None => "none",
+            }
+            .to_string(),
+        ),
+        (
+            "NETPULSE_TARGETS",
+            join(outage.targets.iter().map(ToString::to_string).collect()),
+        ),
+        (
+            "NETPULSE_CHECK_TYPES",
+            join(outage.check_types.iter().map(ToString::to_string).collect()),
+        ),
+        (
+            "NETPULSE_SUMMARY",
+            outage.short_report().unwrap_or_default(),
+        ),
+    ]
+}
+
+/// Returns the environment of the command for a complete round of `checks`.
+fn round_env(checks: &[&Check]) -> Vec<(&'static str, String)> {
+    let time = checks.iter().map(|c| c.timestamp()).max().unwrap_or(0);
+    let failed = checks.iter().filter(|c| !c.is_success()).count();
+    vec![
+        ("NETPULSE_EVENT", "round".to_string()),
+        (
+            "NETPULSE_JSON",
+            serde_json::to_string(checks).unwrap_or_default(),
+        ),
+        ("NETPULSE_TIME", time.to_string()),
+        ("NETPULSE_CHECKS", checks.len().to_string()),
+        ("NETPULSE_FAILED", failed.to_string()),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::records::CheckFlag;
+
+    #[test]
+    fn test_run_hooks() {
+        let checks = [
+            Check::builder()
+                .target([1, 1, 1, 1])
+                .http()
+                .success()
+                .at_timestamp(120)
+                .build(),
+            Check::builder()
+                .target([1, 0, 0, 1])
+                .http()
+                .failure(CheckFlag::Timeout)
+                .at_timestamp(120)
+                .build(),
+        ];
+        let refs: Vec<&Check> = checks.iter().collect();
+        let env = round_env(&refs);
+        let timeout = Duration::from_secs(5);
+
+        assert!(run(
+            "test \"$NETPULSE_EVENT $NETPULSE_TIME $NETPULSE_CHECKS $NETPULSE_FAILED\" = 'round 120 2 1'",
+            &env,
+            timeout
+        ));
+        assert!(!run("exit 3", &env, timeout));
+
+        let outage = crate::analyze::outage::Outage::build(&refs[1..])
+            .unwrap()
+            .to_summary();
+        let env = outage_env(&Event::OutageEnded(outage));
+        assert!(run(
+            "test \"$NETPULSE_EVENT $NETPULSE_SEVERITY $NETPULSE_TARGETS\" = 'outage_ended complete 1.0.0.1'",
+            &env,
+            timeout
+        ));
+
+        let start = Instant::now();
+        assert!(!run("sleep 10", &env, Duration::from_millis(200)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
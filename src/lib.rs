@@ -7,23 +7,43 @@
 //! - [`records`] - Defines core types for representing checks and their results
 //! - [`checks`] - Implements the actual connectivity checks
 //! - [`analyze`] - Provides analysis of check results
+//! - [`config`] - Configuration file with the targets to check
+//! - [`control`] - Control socket to talk to the running daemon
+//! - [`daemon`] - The check loop of the daemon, to embed it in other services
+//! - [`paths`] - Locations of the store, config, pid file, control socket and logs
+//! - [`systemd`] - Telling systemd about the state of the daemon
+//! - [`notify`] - Notifications when outages start and end
+//! - [`observe`] - Callbacks for the checks the daemon makes
+//! - [`hooks`] - Scripts the daemon runs when outages start and end
+//! - [`push`] - Pushing checks to a central server
+//! - [`mqtt`] - Publishing the state to an MQTT broker, like for Home Assistant
+//! - [`server`] - Central server collecting the checks of many probes
+//! - [`doctor`] - Diagnostics of the environment netpulse runs in
 //! - [`errors`] - Error types
+//! - `testing` - Deterministic datasets for tests and benchmarks, with the `testing` feature
 //! - [`analyze`] - Analysis functionalities for extrapolating the data in the [Store](store)
 //!
 //! # Example Usage
 //!
 //! ```rust,no_run
+//! use netpulse::config::Config;
 //! use netpulse::store::Store;
 //!
 //! // Load or create store
 //! let mut store = Store::load_or_create().unwrap();
 //!
 //! // Add checks for configured targets
-//! store.make_checks();
+//! store.make_checks(&Config::load_or_default());
 //!
 //! // Save results
 //! store.save().unwrap();
 //! ```
+//!
+//! # Platforms
+//!
+//! Netpulse is made for Linux. The store, the analysis and the HTTP checks also work on Windows,
+//! so the reader `netpulse` can be used there. The daemon, its [`control`] socket and
+//! [`systemd`] need unix.
 
 #![warn(missing_docs)]
 
@@ -32,11 +52,27 @@ pub const TIMEOUT_MS: u16 = 10_000;
 /// How long to wait until considering a connection as timed out
 pub const TIMEOUT: std::time::Duration = std::time::Duration::new(TIMEOUT_MS as u64 / 1000, 0);
 
-/// Lockfile of the daemon containing it#s pid
+/// Lockfile of the daemon containing it#s pid, unless changed, see [paths::pid_file]
+#[cfg(not(target_os = "macos"))]
 pub const DAEMON_PID_FILE: &str = "/run/netpulse/netpulse.pid";
-/// Redirect the stderr of the daemon here
+/// Lockfile of the daemon containing it#s pid, unless changed, see [paths::pid_file]
+///
+/// macOS has no `/run`.
+#[cfg(target_os = "macos")]
+pub const DAEMON_PID_FILE: &str = "/var/run/netpulse/netpulse.pid";
+/// Unix socket the daemon listens on for requests, see [control], unless changed, see
+/// [paths::control_socket]
+#[cfg(not(target_os = "macos"))]
+pub const DAEMON_CONTROL_SOCKET: &str = "/run/netpulse/netpulsed.sock";
+/// Unix socket the daemon listens on for requests, see [control], unless changed, see
+/// [paths::control_socket]
+///
+/// macOS has no `/run`.
+#[cfg(target_os = "macos")]
+pub const DAEMON_CONTROL_SOCKET: &str = "/var/run/netpulse/netpulsed.sock";
+/// Redirect the stderr of the daemon here, unless changed, see [paths::error_log]
 pub const DAEMON_LOG_ERR: &str = "/var/log/netpulse.err";
-/// Redirect the stdout of the daemon here
+/// Redirect the stdout of the daemon here, unless changed, see [paths::info_log]
 pub const DAEMON_LOG_INF: &str = "/var/log/netpulse.log";
 /// username of the user the daemon should drop to after being started
 pub const DAEMON_USER: &str = "netpulse";
@@ -45,6 +81,26 @@ pub mod analyze;
 pub mod checks;
 #[cfg(feature = "executable")]
 pub mod common;
+pub mod config;
+#[cfg(all(feature = "executable", unix))]
+pub mod control;
+#[cfg(all(feature = "executable", unix))]
+pub mod daemon;
+#[cfg(feature = "executable")]
+pub mod doctor;
 pub mod errors;
+#[cfg(all(feature = "executable", unix))]
+pub mod hooks;
+pub mod mqtt;
+pub mod notify;
+pub mod observe;
+pub mod paths;
+pub mod push;
 pub mod records;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod store;
+#[cfg(all(feature = "executable", unix))]
+pub mod systemd;
+#[cfg(feature = "testing")]
+pub mod testing;
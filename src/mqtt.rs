@@ -0,0 +1,478 @@
+//! Publishing the state of the connection to an MQTT broker.
+//!
+//! The daemon feeds the [Checks](Check) of each round into a [Publisher], which publishes the
+//! state of every target and whether there is an outage as retained JSON messages to the broker
+//! of the [MqttConfig]. With [discovery](MqttConfig::discovery), it also publishes the discovery
+//! payloads of [Home Assistant], so netpulse shows up there as a device with a connectivity
+//! sensor per target and a problem sensor for outages, without configuring anything in Home
+//! Assistant.
+//!
+//! # Topics
+//!
+//! `<topic>` is the [topic](MqttConfig::topic) and `<node>` the [node](MqttConfig::node) of the
+//! config, the host name by default.
+//!
+//! - `<topic>/<node>/status`: `online`, or `offline` once the daemon lost its connection to the
+//!   broker
+//! - `<topic>/<node>/outage`: an [OutageState]
+//! - `<topic>/<node>/target/<target>`: a [TargetState] for each target, named by its label or
+//!   address
+//!
+//! A target counts as connected if any of its [Checks](Check) of the last round succeeded. Like
+//! in the outage analysis, there is an outage if any [Check] of the last round failed.
+//!
+//! ```json
+//! {
+//!   "state": "ON",
+//!   "latency": 12.5,
+//!   "checks": 2,
+//!   "failed": 0,
+//!   "check_types": ["http", "icmp"],
+//!   "timestamp": 1731121200
+//! }
+//! ```
+//!
+//! # Home Assistant
+//!
+//! The [Discovery] payloads are published to
+//! `<discovery_prefix>/binary_sensor/<node>/<object>/config` before the first state of a sensor.
+//! The state messages become the attributes of the sensors, so the latency and the current
+//! outage can be used in automations.
+//!
+//! Publishing requires the `mqtt` feature.
+//!
+//! [Home Assistant]: https://www.home-assistant.io/integrations/mqtt/#mqtt-discovery
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::Serialize;
+use tracing::warn;
+
+use crate::analyze::outage::OutageSummary;
+use crate::config::MqttConfig;
+use crate::notify::OutageTracker;
+use crate::records::{Check, CheckType};
+
+/// State of a target after a round, published to `<topic>/<node>/target/<target>`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TargetState {
+    /// `ON` if any check of the target succeeded, `OFF` otherwise
+    pub state: &'static str,
+    /// Mean latency of the successful checks in milliseconds
+    pub latency: Option<f64>,
+    /// Number of checks of the target
+    pub checks: usize,
+    /// Number of failed checks of the target
+    pub failed: usize,
+    /// [Names](CheckType::name) of the types of the checks, sorted
+    pub check_types: Vec<&'static str>,
+    /// Time of the round
+    pub timestamp: i64,
+}
+
+/// Whether there is an outage, published to `<topic>/<node>/outage`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutageState {
+    /// `ON` during an outage, `OFF` otherwise
+    pub state: &'static str,
+    /// The outage so far
+    #[serde(flatten)]
+    pub outage: Option<OutageSummary>,
+}
+
+/// Discovery payload of a binary sensor for Home Assistant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Discovery {
+    /// Name of the sensor
+    pub name: String,
+    /// ID of the sensor, unique across all netpulse probes
+    pub unique_id: String,
+    /// `connectivity` for targets, `problem` for outages
+    pub device_class: &'static str,
+    /// Topic of the state messages
+    pub state_topic: String,
+    /// Extracts `ON` or `OFF` from the state messages
+    pub value_template: &'static str,
+    /// Topic of the attributes, the same as the [state_topic](Self::state_topic)
+    pub json_attributes_topic: String,
+    /// Topic of the status of the daemon
+    pub availability_topic: String,
+    /// The netpulse probe the sensor belongs to
+    pub device: Device,
+}
+
+/// Device of the sensors of a probe in Home Assistant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Device {
+    /// ID of the probe
+    pub identifiers: Vec<String>,
+    /// Name of the probe
+    pub name: String,
+    /// Always `netpulse`
+    pub manufacturer: &'static str,
+    /// Version of netpulse
+    pub sw_version: &'static str,
+}
+
+/// Body of a message
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Payload {
+    /// `online` or `offline`
+    Status(&'static str),
+    /// State of a target
+    Target(TargetState),
+    /// Whether there is an outage
+    Outage(OutageState),
+    /// Discovery payload for Home Assistant
+    Discovery(Discovery),
+}
+
+/// A retained message to publish
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Message {
+    /// Topic to publish to
+    pub topic: String,
+    /// Body of the message
+    pub payload: Payload,
+}
+
+/// Publishes the state of the connection to an MQTT broker, according to an [MqttConfig].
+pub struct Publisher {
+    config: MqttConfig,
+    node: String,
+    tracker: OutageTracker,
+    /// Objects whose discovery payload was published
+    announced: HashSet<String>,
+    #[cfg(feature = "mqtt")]
+    client: Option<rumqttc::Client>,
+}
+
+impl std::fmt::Debug for Publisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Publisher")
+            .field("config", &self.config)
+            .field("node", &self.node)
+            .field("tracker", &self.tracker)
+            .field("announced", &self.announced)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Publisher {
+    /// Creates a new [`Publisher`] and connects to the broker in the background.
+    ///
+    /// The name of the node is the [node](MqttConfig::node) of the config, or the host name.
+    pub fn new(config: MqttConfig) -> Self {
+        let node = slug(&config.node.clone().unwrap_or_else(|| {
+            sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string())
+        }));
+        #[cfg(not(feature = "mqtt"))]
+        if config.broker.is_some() {
+            warn!("an MQTT broker is configured, but netpulse was built without the mqtt feature, not publishing anything");
+        }
+        #[cfg(feature = "mqtt")]
+        let client = connect(&config, &node);
+        Self {
+            config,
+            node,
+            tracker: OutageTracker::new(),
+            announced: HashSet::new(),
+            #[cfg(feature = "mqtt")]
+            client,
+        }
+    }
+
+    /// Returns the name of this probe in the topics.
+    pub fn node(&self) -> &str {
+        &self.node
+    }
+
+    /// Publishes the state after the [Checks](Check) of one round.
+    ///
+    /// The messages are queued and sent in a background thread, so this does not block on the
+    /// network. While the broker can not be reached, messages that do not fit into the queue are
+    /// dropped.
+    pub fn round(&mut self, checks: &[&Check]) {
+        if self.config.broker.is_none() || !cfg!(feature = "mqtt") || checks.is_empty() {
+            return;
+        }
+        let messages = self.messages(checks);
+        self.publish(messages);
+    }
+
+    /// Returns the messages to publish after the [Checks](Check) of one round.
+    fn messages(&mut self, checks: &[&Check]) -> Vec<Message> {
+        self.tracker.update(checks);
+        let timestamp = checks
+            .iter()
+            .map(|c| c.timestamp())
+            .max()
+            .unwrap_or_default();
+        let mut by_target: BTreeMap<String, Vec<&Check>> = BTreeMap::new();
+        for check in checks {
+            by_target
+                .entry(check.target_name())
+                .or_default()
+                .push(check);
+        }
+
+        let mut messages = vec![Message {
+            topic: self.status_topic(),
+            payload: Payload::Status("online"),
+        }];
+        self.announce(&mut messages, "outage", "Outage", "problem");
+        messages.push(Message {
+            topic: self.state_topic("outage"),
+            payload: Payload::Outage(OutageState {
+                state: on_off(self.tracker.in_outage()),
+                outage: self.tracker.current(),
+            }),
+        });
+        for (name, checks) in by_target {
+            let object = format!("target/{}", slug(&name));
+            self.announce(&mut messages, &object, &name, "connectivity");
+            messages.push(Message {
+                topic: self.state_topic(&object),
+                payload: Payload::Target(target_state(&checks, timestamp)),
+            });
+        }
+        messages
+    }
+
+    /// Adds the discovery payload of `object` to `messages`, if it was not published yet.
+    fn announce(
+        &mut self,
+        messages: &mut Vec<Message>,
+        object: &str,
+        name: &str,
+        device_class: &'static str,
+    ) {
+        if !self.config.discovery || !self.announced.insert(object.to_string()) {
+            return;
+        }
+        let object_id = object.replace('/', "_");
+        messages.push(Message {
+            topic: format!(
+                "{}/binary_sensor/{}/{object_id}/config",
+                self.config.discovery_prefix, self.node
+            ),
+            payload: Payload::Discovery(Discovery {
+                name: name.to_string(),
+                unique_id: format!("netpulse_{}_{object_id}", self.node),
+                device_class,
+                state_topic: self.state_topic(object),
+                value_template: "{{ value_json.state }}",
+                json_attributes_topic: self.state_topic(object),
+                availability_topic: self.status_topic(),
+                device: Device {
+                    identifiers: vec![format!("netpulse_{}", self.node)],
+                    name: format!("netpulse {}", self.node),
+                    manufacturer: "netpulse",
+                    sw_version: env!("CARGO_PKG_VERSION"),
+                },
+            }),
+        });
+    }
+
+    fn state_topic(&self, object: &str) -> String {
+        topic(&self.config, &self.node, object)
+    }
+
+    fn status_topic(&self) -> String {
+        self.state_topic("status")
+    }
+
+    #[cfg(feature = "mqtt")]
+    fn publish(&mut self, messages: Vec<Message>) {
+        let Some(client) = &self.client else {
+            return;
+        };
+        for message in messages {
+            let payload = match &message.payload {
+                Payload::Status(status) => status.as_bytes().to_vec(),
+                payload => match serde_json::to_vec(payload) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        warn!("could not serialize the message to {}: {e}", message.topic);
+                        continue;
+                    }
+                },
+            };
+            if let Err(e) =
+                client.try_publish(&message.topic, rumqttc::QoS::AtLeastOnce, true, payload)
+            {
+                warn!("could not publish to {}: {e}", message.topic);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "mqtt"))]
+    fn publish(&mut self, _messages: Vec<Message>) {}
+}
+
+/// Connects to the broker, with `offline` as last will on the status topic.
+#[cfg(feature = "mqtt")]
+fn connect(config: &MqttConfig, node: &str) -> Option<rumqttc::Client> {
+    use rumqttc::{Client, LastWill, MqttOptions, QoS};
+
+    let broker = config.broker.as_ref()?;
+    let mut options = MqttOptions::new(format!("netpulse-{node}"), broker, config.port);
+    options.set_keep_alive(std::time::Duration::from_secs(60));
+    options.set_last_will(LastWill::new(
+        topic(config, node, "status"),
+        "offline",
+        QoS::AtLeastOnce,
+        true,
+    ));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+    let (client, mut connection) = Client::new(options, 256);
+    let broker = broker.clone();
+    std::thread::spawn(move || {
+        // the connection reconnects by itself and ends when the client is dropped
+        for notification in connection.iter() {
+            match notification {
+                Ok(rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_))) => {
+                    tracing::info!("connected to the MQTT broker {broker}");
+                }
+                Ok(_) => (),
+                Err(rumqttc::ConnectionError::RequestsDone) => break,
+                Err(e) => {
+                    warn!("connection to the MQTT broker {broker} failed: {e}");
+                    std::thread::sleep(std::time::Duration::from_secs(5));
+                }
+            }
+        }
+    });
+    Some(client)
+}
+
+/// Returns the topic of `object` of `node`.
+fn topic(config: &MqttConfig, node: &str, object: &str) -> String {
+    format!("{}/{node}/{object}", config.topic)
+}
+
+/// Returns the state of a target after a round with `checks`.
+fn target_state(checks: &[&Check], timestamp: i64) -> TargetState {
+    let latencies: Vec<f64> = checks
+        .iter()
+        .filter_map(|c| c.latency())
+        .map(f64::from)
+        .collect();
+    let mut check_types: Vec<&'static str> = checks
+        .iter()
+        .map(|c| c.calc_type().unwrap_or(CheckType::Unknown).name())
+        .collect();
+    check_types.sort();
+    check_types.dedup();
+    TargetState {
+        state: on_off(checks.iter().any(|c| c.is_success())),
+        latency: (!latencies.is_empty())
+            .then(|| latencies.iter().sum::<f64>() / latencies.len() as f64),
+        checks: checks.len(),
+        failed: checks.iter().filter(|c| !c.is_success()).count(),
+        check_types,
+        timestamp,
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "ON"
+    } else {
+        "OFF"
+    }
+}
+
+/// Makes `name` usable as a topic level and in IDs of Home Assistant.
+fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::records::CheckFlag;
+
+    use super::*;
+
+    fn check(target: &str, flags: impl Into<flagset::FlagSet<CheckFlag>>, latency: u16) -> Check {
+        Check::new(
+            chrono::DateTime::from_timestamp(60, 0).unwrap(),
+            flags,
+            Some(Duration::from_millis(latency.into())),
+            target.parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_messages() {
+        let mut publisher = Publisher::new(MqttConfig {
+            node: Some("Living Room".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(publisher.node(), "living_room");
+
+        let http = check("1.1.1.1", CheckFlag::Success | CheckFlag::TypeHTTP, 10);
+        let icmp = check("1.1.1.1", CheckFlag::Success | CheckFlag::TypeIcmp, 20);
+        let failed = check("::1", CheckFlag::TypeIcmp, 0);
+        let messages = publisher.messages(&[&http, &icmp, &failed]);
+        let topics: Vec<&str> = messages.iter().map(|m| m.topic.as_str()).collect();
+        assert_eq!(
+            topics,
+            vec![
+                "netpulse/living_room/status",
+                "homeassistant/binary_sensor/living_room/outage/config",
+                "netpulse/living_room/outage",
+                "homeassistant/binary_sensor/living_room/target_1_1_1_1/config",
+                "netpulse/living_room/target/1_1_1_1",
+                "homeassistant/binary_sensor/living_room/target___1/config",
+                "netpulse/living_room/target/__1",
+            ]
+        );
+        assert!(matches!(
+            &messages[2].payload,
+            Payload::Outage(OutageState {
+                state: "ON",
+                outage: Some(_)
+            })
+        ));
+        assert_eq!(
+            messages[4].payload,
+            Payload::Target(TargetState {
+                state: "ON",
+                latency: Some(15.0),
+                checks: 2,
+                failed: 0,
+                check_types: vec!["http", "icmp"],
+                timestamp: 60,
+            })
+        );
+        let Payload::Target(state) = &messages[6].payload else {
+            panic!("not the state of a target: {:?}", messages[6]);
+        };
+        assert_eq!((state.state, state.latency, state.failed), ("OFF", None, 1));
+
+        // discovery payloads are only published once
+        let messages = publisher.messages(&[&http]);
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(
+            &messages[1].payload,
+            Payload::Outage(OutageState {
+                state: "OFF",
+                outage: None
+            })
+        ));
+    }
+}
@@ -0,0 +1,548 @@
+//! Notifications when outages start and end.
+//!
+//! The daemon feeds the [Checks](Check) of each round into a [Notifier]. Its [OutageTracker]
+//! detects when the connection goes from working to failing and back, and the [Notifier] sends
+//! an [Event] for each transition to the webhook of the [NotifyConfig].
+//!
+//! A round counts as failed if any of its [Checks](Check) failed, just like in the outage
+//! analysis of [fail_groups](crate::analyze).
+//!
+//! # Webhook payload
+//!
+//! The webhook receives a POST request with a JSON body: the
+//! [OutageSummary] of the outage so far, plus the kind of the event:
+//!
+//! ```json
+//! {
+//!   "event": "outage_ended",
+//!   "start": 1731121200,
+//!   "end": 1731121320,
+//!   "duration": 120,
+//!   "checks": 12,
+//!   "severity": { "kind": "complete" },
+//!   "check_types": ["Http", "IcmpV4"],
+//!   "targets": ["1.1.1.1"]
+//! }
+//! ```
+//!
+//! Failed requests are retried [retries](NotifyConfig::retries) times with exponential backoff.
+//! To avoid flooding the receiver, outages that start less than
+//! [min_interval](NotifyConfig::min_interval) seconds after the last notification are not
+//! notified, and neither is their end.
+//!
+//! Sending requires the `webhook` feature.
+//!
+//! # Emails
+//!
+//! With an [email](NotifyConfig::email) configured, an email is sent when a complete outage (all
+//! checks of each round failed) has lasted for
+//! [min_duration](crate::config::EmailConfig::min_duration) minutes, and another one when
+//! connectivity recovers. Sending requires the `notify-email` feature, see [email].
+
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::analyze::outage::{Family, Location, OutageSummary, Severity};
+use crate::config::NotifyConfig;
+#[cfg(any(feature = "webhook", feature = "notify-email"))]
+use crate::errors::NotifyError;
+use crate::records::{Check, CheckType, IpType};
+
+#[cfg(feature = "notify-email")]
+pub mod email;
+
+/// How long to wait before the first retry of a failed notification
+pub const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// A change of the state of the connection
+#[derive(Debug, PartialEq, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// The first failed round after working rounds
+    OutageStarted(OutageSummary),
+    /// The first working round after failed rounds, with a summary of the whole outage
+    OutageEnded(OutageSummary),
+}
+
+impl Event {
+    /// Returns the [OutageSummary] of this [Event].
+    pub fn summary(&self) -> &OutageSummary {
+        match self {
+            Self::OutageStarted(s) | Self::OutageEnded(s) => s,
+        }
+    }
+}
+
+/// Keeps track of whether the connection is currently in an outage.
+///
+/// Feed it the [Checks](Check) of each round with [update](Self::update).
+///
+/// The [Checks](Check) themselves are not kept, only what the [OutageSummary] needs, so an
+/// outage of days takes as much memory as one of minutes.
+#[derive(Debug, Default, Clone)]
+pub struct OutageTracker {
+    /// What is known about the current outage, [None] if there is none
+    current: Option<RunningOutage>,
+    /// Timestamps of the first and last round of the current run of completely failed rounds
+    complete: Option<(i64, i64)>,
+}
+
+/// Running counts of the [Checks](Check) of an outage, to build its [OutageSummary] from.
+#[derive(Debug, Default, Clone)]
+struct RunningOutage {
+    start: i64,
+    end: i64,
+    checks: usize,
+    failed: usize,
+    degraded: usize,
+    /// Types of the failed or degraded checks
+    check_types: BTreeSet<CheckType>,
+    /// Targets of the failed or degraded checks
+    targets: BTreeSet<IpAddr>,
+    /// Whether there were gateway checks, and whether any of them failed
+    gateway: (bool, bool),
+    /// Whether any check over IPv4 and IPv6 failed
+    failed_ip: [bool; 2],
+    /// Whether any check over IPv4 and IPv6 worked
+    worked_ip: [bool; 2],
+}
+
+impl OutageTracker {
+    /// Creates a new [`OutageTracker`] that is not in an outage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the last rounds failed.
+    pub fn in_outage(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Returns for how many seconds all checks have failed, if the last round failed completely.
+    ///
+    /// This is the time between the first and the last of the consecutive completely failed
+    /// rounds, so it is `0` after the first one.
+    pub fn complete_for(&self) -> Option<i64> {
+        self.complete.map(|(first, last)| last - first)
+    }
+
+    /// Returns a summary of the current outage, if there is one.
+    pub fn current(&self) -> Option<OutageSummary> {
+        self.current.as_ref().map(|current| current.summary(true))
+    }
+
+    /// Processes the [Checks](Check) of one round.
+    ///
+    /// Returns an [Event] if the round started or ended an outage. Empty rounds are ignored.
+    pub fn update(&mut self, checks: &[&Check]) -> Option<Event> {
+        if checks.is_empty() {
+            return None;
+        }
+
+        let time = checks
+            .iter()
+            .map(|c| c.timestamp())
+            .max()
+            .unwrap_or_default();
+        self.complete = if checks.iter().all(|c| !c.is_success()) {
+            Some((self.complete.map_or(time, |(first, _)| first), time))
+        } else {
+            None
+        };
+
+        if checks.iter().any(|c| !c.is_success()) {
+            let started = !self.in_outage();
+            let current = self.current.get_or_insert_with(|| RunningOutage {
+                start: time,
+                end: time,
+                ..Default::default()
+            });
+            checks.iter().for_each(|c| current.add(c));
+            started.then(|| Event::OutageStarted(current.summary(true)))
+        } else {
+            self.current
+                .take()
+                .map(|current| Event::OutageEnded(current.summary(false)))
+        }
+    }
+}
+
+impl RunningOutage {
+    /// Counts a [Check] of the outage.
+    fn add(&mut self, check: &Check) {
+        self.start = self.start.min(check.timestamp());
+        self.end = self.end.max(check.timestamp());
+        self.checks += 1;
+        if !check.is_success() {
+            self.failed += 1;
+        }
+        if check.is_degraded() {
+            self.degraded += 1;
+        }
+        if !check.is_success() || check.is_degraded() {
+            self.check_types
+                .insert(check.calc_type().unwrap_or(CheckType::Unknown));
+            self.targets.insert(check.target());
+        }
+        if check.calc_type().is_ok_and(|t| t == CheckType::Gateway) {
+            self.gateway.0 = true;
+            self.gateway.1 |= !check.is_success();
+        }
+        let ip = match check.ip_type() {
+            IpType::V4 => 0,
+            IpType::V6 => 1,
+        };
+        if check.is_success() {
+            self.worked_ip[ip] = true;
+        } else {
+            self.failed_ip[ip] = true;
+        }
+    }
+
+    /// Summarizes the outage like [Outage::to_summary](crate::analyze::outage::Outage::to_summary).
+    fn summary(&self, ongoing: bool) -> OutageSummary {
+        OutageSummary {
+            start: self.start,
+            end: self.end,
+            duration: self.end - self.start,
+            checks: self.checks,
+            severity: Severity::of_counts(self.checks, self.failed, self.degraded),
+            check_types: self.check_types.iter().copied().collect(),
+            targets: self.targets.iter().copied().collect(),
+            location: Location::of_gateway(self.gateway.0, self.gateway.1),
+            family: Family::of(self.failed_ip, self.worked_ip),
+            ongoing,
+        }
+    }
+}
+
+/// Sends notifications about outages, according to a [NotifyConfig].
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    config: NotifyConfig,
+    tracker: OutageTracker,
+    /// When the last notification was sent
+    last_sent: Option<Instant>,
+    /// Whether the start of the current outage was not notified because of rate limiting
+    suppressed: bool,
+    /// Whether an email about the current outage was sent
+    email_sent: bool,
+}
+
+impl Notifier {
+    /// Creates a new [`Notifier`].
+    pub fn new(config: NotifyConfig) -> Self {
+        Self {
+            config,
+            tracker: OutageTracker::new(),
+            last_sent: None,
+            suppressed: false,
+            email_sent: false,
+        }
+    }
+
+    /// Processes the [Checks](Check) of one round and notifies about a started or ended outage.
+    ///
+    /// Notifications are sent in a background thread, so this does not block on the network.
+    pub fn round(&mut self, checks: &[&Check]) {
+        let event = self.tracker.update(checks);
+        if let Some(event) = &event {
+            self.notify_webhook(event.clone());
+        }
+        self.notify_email(event.as_ref());
+    }
+
+    fn notify_webhook(&mut self, event: Event) {
+        if self.config.webhook.is_none() {
+            return;
+        }
+        if !self.should_send(&event, Instant::now()) {
+            info!("rate limited, not notifying about {event:?}");
+            return;
+        }
+        self.send_hook(event);
+    }
+
+    /// Decides whether to send an email after a round.
+    ///
+    /// Returns the [Event] to send an email about: [Event::OutageStarted] with the current outage
+    /// once it was complete for long enough, and [Event::OutageEnded] when it ends.
+    fn email_event(&mut self, event: Option<&Event>) -> Option<Event> {
+        let min_duration = self.config.email.as_ref()?.min_duration as i64 * 60;
+        match event {
+            Some(ended @ Event::OutageEnded(_)) if self.email_sent => {
+                self.email_sent = false;
+                Some(ended.clone())
+            }
+            _ if !self.email_sent
+                && self
+                    .tracker
+                    .complete_for()
+                    .is_some_and(|d| d >= min_duration) =>
+            {
+                self.email_sent = true;
+                self.tracker.current().map(Event::OutageStarted)
+            }
+            _ => None,
+        }
+    }
+
+    fn notify_email(&mut self, event: Option<&Event>) {
+        if let Some(event) = self.email_event(event) {
+            self.send_email(event);
+        }
+    }
+
+    /// Applies the rate limit to an [Event] and remembers when the last notification was sent.
+    fn should_send(&mut self, event: &Event, now: Instant) -> bool {
+        let send = match event {
+            Event::OutageStarted(_) => {
+                self.suppressed = self.last_sent.is_some_and(|last| {
+                    now.duration_since(last) < Duration::from_secs(self.config.min_interval)
+                });
+                !self.suppressed
+            }
+            Event::OutageEnded(_) => !self.suppressed,
+        };
+        if send {
+            self.last_sent = Some(now);
+        }
+        send
+    }
+
+    #[cfg(feature = "webhook")]
+    fn send_hook(&self, event: Event) {
+        let Some(url) = self.config.webhook.clone() else {
+            return;
+        };
+        let retries = self.config.retries;
+        std::thread::spawn(move || {
+            if let Err(err) = send_webhook(&url, &event, retries) {
+                tracing::error!("could not notify the webhook about {event:?}: {err}");
+            }
+        });
+    }
+
+    #[cfg(not(feature = "webhook"))]
+    fn send_hook(&self, event: Event) {
+        warn!("a webhook is configured, but netpulse was built without the webhook feature, not notifying about {event:?}");
+    }
+
+    #[cfg(feature = "notify-email")]
+    fn send_email(&self, event: Event) {
+        let Some(config) = self.config.email.clone() else {
+            return;
+        };
+        let retries = self.config.retries;
+        std::thread::spawn(move || {
+            if let Err(err) = with_retries(retries, || email::send(&config, &event)) {
+                tracing::error!("could not send an email about {event:?}: {err}");
+            }
+        });
+    }
+
+    #[cfg(not(feature = "notify-email"))]
+    fn send_email(&self, event: Event) {
+        warn!("an email is configured, but netpulse was built without the notify-email feature, not notifying about {event:?}");
+    }
+}
+
+/// Runs `f` and retries it up to `retries` times if it fails.
+///
+/// Waits [RETRY_DELAY] before the first retry and twice as long before each further one.
+#[cfg(any(feature = "webhook", feature = "notify-email"))]
+fn with_retries(
+    retries: u32,
+    mut f: impl FnMut() -> Result<(), NotifyError>,
+) -> Result<(), NotifyError> {
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt < retries => {
+                warn!("sending a notification failed, retrying in {delay:?}: {err}");
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// POSTs an [Event] as JSON to a webhook.
+///
+/// Retries up to `retries` times if the request fails or the response status is not a success,
+/// with exponential backoff starting at [RETRY_DELAY].
+///
+/// # Errors
+///
+/// Returns the [NotifyError] of the last attempt if all attempts failed.
+#[cfg(feature = "webhook")]
+pub fn send_webhook(url: &str, event: &Event, retries: u32) -> Result<(), NotifyError> {
+    let payload = serde_json::to_vec(event)?;
+    with_retries(retries, || post_json(url, &payload))
+}
+
+#[cfg(feature = "webhook")]
+fn post_json(url: &str, payload: &[u8]) -> Result<(), NotifyError> {
+    let status = crate::checks::post(url, "application/json", &[], payload)?;
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(NotifyError::BadStatus { status })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::analyze::outage::Outage;
+
+    fn round(minute: i64, ok: bool) -> Vec<Check> {
+        let target: IpAddr = "1.1.1.1".parse().unwrap();
+        let t =
+            Utc.with_ymd_and_hms(2024, 11, 9, 3, 0, 0).unwrap() + chrono::Duration::minutes(minute);
+        let check = Check::builder().target(target).http().at(t);
+        vec![if ok {
+            check.success().latency(20)
+        } else {
+            check
+        }
+        .build()]
+    }
+
+    #[test]
+    fn test_outage_tracker() {
+        let mut tracker = OutageTracker::new();
+        let mut events = Vec::new();
+        for (minute, ok) in [true, false, false, false, true, true]
+            .into_iter()
+            .enumerate()
+        {
+            let checks = round(minute as i64, ok);
+            let refs: Vec<&Check> = checks.iter().collect();
+            events.push(tracker.update(&refs));
+        }
+        assert!(!tracker.in_outage());
+        assert_eq!(tracker.update(&[]), None);
+
+        assert!(events[0].is_none());
+        assert!(matches!(&events[1], Some(Event::OutageStarted(s)) if s.checks == 1));
+        assert!(events[2].is_none() && events[3].is_none());
+        match &events[4] {
+            Some(Event::OutageEnded(s)) => {
+                assert_eq!(s.checks, 3);
+                assert_eq!(s.duration, 120);
+            }
+            other => panic!("expected the outage to end, got {other:?}"),
+        }
+        assert!(events[5].is_none());
+    }
+
+    #[test]
+    fn test_tracker_summary_matches_outage() {
+        let t = Utc.with_ymd_and_hms(2024, 11, 9, 3, 0, 0).unwrap();
+        let mut all = Vec::new();
+        let mut tracker = OutageTracker::new();
+        for minute in 0..3 {
+            let at = t + chrono::Duration::minutes(minute);
+            let checks = vec![
+                Check::builder().target([1, 1, 1, 1]).http().at(at).build(),
+                Check::builder()
+                    .target("2606:4700:4700::1111".parse::<IpAddr>().unwrap())
+                    .http()
+                    .at(at)
+                    .success()
+                    .latency(20)
+                    .build(),
+                Check::builder()
+                    .target([192, 168, 1, 1])
+                    .check_type(CheckType::Gateway)
+                    .at(at)
+                    .success()
+                    .latency(1)
+                    .build(),
+            ];
+            let refs: Vec<&Check> = checks.iter().collect();
+            tracker.update(&refs);
+            all.extend(checks);
+        }
+
+        let refs: Vec<&Check> = all.iter().collect();
+        let expected = OutageSummary {
+            ongoing: true,
+            ..Outage::build(&refs).unwrap().to_summary()
+        };
+        assert_eq!(tracker.current(), Some(expected));
+        assert_eq!(tracker.current().unwrap().family, Some(Family::Ipv4Only));
+        assert_eq!(
+            tracker.current().unwrap().location,
+            Some(Location::Upstream)
+        );
+    }
+
+    #[test]
+    fn test_email_after_complete_outage() {
+        let mut notifier = Notifier::new(NotifyConfig {
+            email: Some(crate::config::EmailConfig {
+                server: "localhost".to_string(),
+                port: None,
+                tls: crate::config::EmailTls::None,
+                username: None,
+                password: None,
+                from: "netpulse@localhost".to_string(),
+                to: vec!["root@localhost".to_string()],
+                min_duration: 2,
+            }),
+            ..Default::default()
+        });
+        let mut emails = Vec::new();
+        for (minute, ok) in [false, false, false, false, true, false]
+            .into_iter()
+            .enumerate()
+        {
+            let checks = round(minute as i64, ok);
+            let refs: Vec<&Check> = checks.iter().collect();
+            let event = notifier.tracker.update(&refs);
+            emails.push(notifier.email_event(event.as_ref()));
+        }
+
+        assert!(emails[0].is_none() && emails[1].is_none());
+        assert!(matches!(&emails[2], Some(Event::OutageStarted(s)) if s.duration == 120));
+        assert!(emails[3].is_none());
+        assert!(matches!(&emails[4], Some(Event::OutageEnded(s)) if s.checks == 4));
+        assert!(emails[5].is_none());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut notifier = Notifier::new(NotifyConfig {
+            webhook: Some("http://localhost/hook".to_string()),
+            min_interval: 60,
+            ..Default::default()
+        });
+        let checks = round(0, false);
+        let refs: Vec<&Check> = checks.iter().collect();
+        let summary = Outage::build(&refs).unwrap().to_summary();
+        let start = Event::OutageStarted(summary.clone());
+        let end = Event::OutageEnded(summary);
+
+        let now = Instant::now();
+        assert!(notifier.should_send(&start, now));
+        assert!(notifier.should_send(&end, now + Duration::from_secs(10)));
+        // too soon after the last notification, the end is suppressed too
+        assert!(!notifier.should_send(&start, now + Duration::from_secs(20)));
+        assert!(!notifier.should_send(&end, now + Duration::from_secs(100)));
+        assert!(notifier.should_send(&start, now + Duration::from_secs(200)));
+    }
+}
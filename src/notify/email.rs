@@ -0,0 +1,112 @@
+//! Sending emails about outages over SMTP.
+//!
+//! This module is only available when the `notify-email` feature is enabled. The
+//! [Notifier](super::Notifier) decides when to send an email, this module builds and sends it
+//! according to the [EmailConfig].
+
+use std::fmt::Write;
+
+use chrono::{Local, TimeZone};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::analyze::fmt_timestamp;
+use crate::analyze::outage::OutageSummary;
+use crate::config::{EmailConfig, EmailTls};
+use crate::errors::NotifyError;
+use crate::TIMEOUT;
+
+use super::Event;
+
+/// Sends an email about an [Event] once.
+///
+/// # Errors
+///
+/// Returns a [NotifyError] if an address in the [EmailConfig] is invalid or the SMTP server
+/// could not be reached or rejected the email.
+pub fn send(config: &EmailConfig, event: &Event) -> Result<(), NotifyError> {
+    let (subject, body) = compose(event);
+
+    let mut builder = Message::builder()
+        .from(config.from.parse::<Mailbox>()?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN);
+    for to in &config.to {
+        builder = builder.to(to.parse::<Mailbox>()?);
+    }
+    let message = builder.body(body)?;
+
+    let mut transport = match config.tls {
+        EmailTls::Tls => SmtpTransport::relay(&config.server)?,
+        EmailTls::StartTls => SmtpTransport::starttls_relay(&config.server)?,
+        EmailTls::None => SmtpTransport::builder_dangerous(&config.server),
+    };
+    if let Some(port) = config.port {
+        transport = transport.port(port);
+    }
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+    }
+
+    transport.timeout(Some(TIMEOUT)).build().send(&message)?;
+    Ok(())
+}
+
+/// Returns the subject and body of the email about an [Event]
+fn compose(event: &Event) -> (String, String) {
+    let summary = event.summary();
+    let start = fmt_timestamp(Local.timestamp_opt(summary.start, 0).unwrap());
+    match event {
+        Event::OutageStarted(_) => (
+            format!("netpulse: complete outage since {start}"),
+            format!(
+                "All checks have been failing since {start}.\n\n{}",
+                describe(summary)
+            ),
+        ),
+        Event::OutageEnded(_) => (
+            "netpulse: connectivity recovered".to_string(),
+            format!(
+                "The outage that started at {start} is over.\n\n{}",
+                describe(summary)
+            ),
+        ),
+    }
+}
+
+/// Describes an [OutageSummary] in plain text
+fn describe(summary: &OutageSummary) -> String {
+    let mut buf = String::new();
+    let fmt_time = |t: i64| fmt_timestamp(Local.timestamp_opt(t, 0).unwrap());
+    let list = |items: Vec<String>| items.join(", ");
+    // writing to a String does not fail
+    let _ = writeln!(buf, "Start:       {}", fmt_time(summary.start));
+    let _ = writeln!(buf, "End:         {}", fmt_time(summary.end));
+    let _ = writeln!(
+        buf,
+        "Duration:    {}m {}s",
+        summary.duration / 60,
+        summary.duration % 60
+    );
+    let _ = writeln!(buf, "Checks:      {}", summary.checks);
+    let _ = writeln!(buf, "Severity:    {}", summary.severity);
+    if let Some(family) = summary.family {
+        let _ = writeln!(buf, "Family:      {family}");
+    }
+    if let Some(location) = summary.location {
+        let _ = writeln!(buf, "Location:    {location}");
+    }
+    let _ = writeln!(
+        buf,
+        "Check types: {}",
+        list(summary.check_types.iter().map(|t| t.to_string()).collect())
+    );
+    let _ = writeln!(
+        buf,
+        "Targets:     {}",
+        list(summary.targets.iter().map(|t| t.to_string()).collect())
+    );
+    buf
+}
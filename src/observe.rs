@@ -0,0 +1,240 @@
+//! Callbacks for the checks the daemon makes, see [CheckObserver].
+//!
+//! Code that reacts to checks, like a notification backend or an application that embeds the
+//! [daemon](crate::daemon), implements [CheckObserver] and is added to the daemon. [Observers]
+//! feeds the [Checks](Check) of each round to all of them, and tells them when an outage starts
+//! or ends, with the same [OutageTracker] that the [Notifier](crate::notify::Notifier) uses.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::sync::{Arc, Mutex};
+//!
+//! use netpulse::analyze::outage::OutageSummary;
+//! use netpulse::observe::{CheckObserver, Observers};
+//! use netpulse::records::{Check, CheckFlag};
+//!
+//! #[derive(Default)]
+//! struct Counter {
+//!     checks: Arc<Mutex<usize>>,
+//!     outages: Arc<Mutex<usize>>,
+//! }
+//!
+//! impl CheckObserver for Counter {
+//!     fn on_check(&mut self, _check: &Check) {
+//!         *self.checks.lock().unwrap() += 1;
+//!     }
+//!
+//!     fn on_outage_start(&mut self, _outage: &OutageSummary) {
+//!         *self.outages.lock().unwrap() += 1;
+//!     }
+//! }
+//!
+//! let counter = Counter::default();
+//! let (checks, outages) = (counter.checks.clone(), counter.outages.clone());
+//! let mut observers = Observers::default();
+//! observers.add(counter);
+//! let failed = Check::builder().target([1, 1, 1, 1]).http().failure(CheckFlag::Timeout).build();
+//! let ok = Check::builder().target([1, 0, 0, 1]).http().success().build();
+//! observers.round(&[&failed, &ok]);
+//! assert_eq!(*checks.lock().unwrap(), 2);
+//! assert_eq!(*outages.lock().unwrap(), 1);
+//! ```
+
+use crate::analyze::outage::OutageSummary;
+use crate::notify::{Event, OutageTracker};
+use crate::records::Check;
+
+/// Reacts to the checks the daemon makes, see the [module docs](self).
+///
+/// All methods do nothing by default, so only the interesting ones need to be implemented. They
+/// are called in the check loop of the daemon, so they should return quickly and move slow work,
+/// like network requests, to another thread.
+pub trait CheckObserver: Send {
+    /// Called for every [Check] of a round once the round is complete, before the other methods.
+    fn on_check(&mut self, check: &Check) {
+        let _ = check;
+    }
+
+    /// Called with the first failed round after working rounds.
+    ///
+    /// The [OutageSummary] covers only that round and is [ongoing](OutageSummary::ongoing).
+    fn on_outage_start(&mut self, outage: &OutageSummary) {
+        let _ = outage;
+    }
+
+    /// Called with the first working round after failed rounds, with a summary of the whole
+    /// outage.
+    fn on_outage_end(&mut self, outage: &OutageSummary) {
+        let _ = outage;
+    }
+
+    /// Called with all [Checks](Check) of a round once it is complete, after the other methods.
+    fn on_round(&mut self, checks: &[&Check]) {
+        let _ = checks;
+    }
+}
+
+/// A set of [CheckObservers](CheckObserver) that are told about the same rounds of checks.
+#[derive(Default)]
+pub struct Observers {
+    observers: Vec<Box<dyn CheckObserver>>,
+    tracker: OutageTracker,
+}
+
+impl std::fmt::Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Observers")
+            .field("observers", &self.observers.len())
+            .field("tracker", &self.tracker)
+            .finish()
+    }
+}
+
+impl Observers {
+    /// Adds `observer`, which is told about all following rounds.
+    pub fn add(&mut self, observer: impl CheckObserver + 'static) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Returns how many [CheckObservers](CheckObserver) there are.
+    pub fn len(&self) -> usize {
+        self.observers.len()
+    }
+
+    /// Returns true if there are no [CheckObservers](CheckObserver).
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+
+    /// Tells all [CheckObservers](CheckObserver) about the [Checks](Check) of one round, about
+    /// the outage it started or ended, if any, and that the round is complete.
+    ///
+    /// The outages are tracked even without [CheckObservers](CheckObserver), so one that is added
+    /// later is told when the running outage ends.
+    pub fn round(&mut self, checks: &[&Check]) {
+        let event = self.tracker.update(checks);
+        if self.observers.is_empty() {
+            return;
+        }
+        for observer in &mut self.observers {
+            for check in checks {
+                observer.on_check(check);
+            }
+        }
+        match event {
+            Some(Event::OutageStarted(summary)) => self
+                .observers
+                .iter_mut()
+                .for_each(|o| o.on_outage_start(&summary)),
+            Some(Event::OutageEnded(summary)) => self
+                .observers
+                .iter_mut()
+                .for_each(|o| o.on_outage_end(&summary)),
+            None => (),
+        }
+        for observer in &mut self.observers {
+            observer.on_round(checks);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::records::CheckFlag;
+
+    #[derive(Debug, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl CheckObserver for Recorder {
+        fn on_check(&mut self, check: &Check) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("check {}", check.is_success()));
+        }
+
+        fn on_outage_start(&mut self, outage: &OutageSummary) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("start {}", outage.start));
+        }
+
+        fn on_outage_end(&mut self, outage: &OutageSummary) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("end {}-{}", outage.start, outage.end));
+        }
+
+        fn on_round(&mut self, checks: &[&Check]) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("round {}", checks.len()));
+        }
+    }
+
+    #[test]
+    fn test_observers() {
+        let round = |time: i64, ok: bool| {
+            let builder = Check::builder()
+                .target([1, 1, 1, 1])
+                .http()
+                .at_timestamp(time);
+            if ok {
+                builder.success().build()
+            } else {
+                builder.failure(CheckFlag::Timeout).build()
+            }
+        };
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut observers = Observers::default();
+        observers.add(Recorder(log.clone()));
+
+        for (time, ok) in [(0, true), (60, false), (120, false), (180, true)] {
+            observers.round(&[&round(time, ok)]);
+        }
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "check true",
+                "round 1",
+                "check false",
+                "start 60",
+                "round 1",
+                "check false",
+                "round 1",
+                "check true",
+                "end 60-120",
+                "round 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_observer_added_during_outage() {
+        let check = |time: i64, ok: bool| {
+            let builder = Check::builder()
+                .target([1, 1, 1, 1])
+                .http()
+                .at_timestamp(time);
+            if ok {
+                builder.success().build()
+            } else {
+                builder.failure(CheckFlag::Timeout).build()
+            }
+        };
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut observers = Observers::default();
+        observers.round(&[&check(0, true)]);
+        observers.round(&[&check(60, false)]);
+        observers.add(Recorder(log.clone()));
+        observers.round(&[&check(120, true)]);
+        assert_eq!(*log.lock().unwrap(), ["check true", "end 60-60", "round 1"]);
+    }
+}
@@ -0,0 +1,239 @@
+//! Locations of the files of netpulse.
+//!
+//! Every location is resolved in this order:
+//!
+//! 1. its environment variable, like [ENV_PID_FILE]
+//! 2. the `[paths]` table of the config file, see [PathsConfig]
+//! 3. in [user mode](user_mode), the XDG directories of the invoking user
+//! 4. the system wide default, like [DAEMON_PID_FILE]
+//!
+//! | Location | Environment | Config | User mode | Default |
+//! |---|---|---|---|---|
+//! | [store_dir] | [ENV_PATH] | `store_dir` | `$XDG_DATA_HOME/netpulse` | [DB_PATH] |
+//! | [pid_file] | [ENV_PID_FILE] | `pid_file` | `$XDG_RUNTIME_DIR/netpulse/netpulse.pid` | [DAEMON_PID_FILE] |
+//! | [control_socket] | [ENV_CONTROL_SOCKET] | `control_socket` | `$XDG_RUNTIME_DIR/netpulse/netpulsed.sock` | [DAEMON_CONTROL_SOCKET] |
+//! | [info_log] | [ENV_INFO_LOG] | `info_log` | `$XDG_STATE_HOME/netpulse/netpulse.log` | [DAEMON_LOG_INF] |
+//! | [error_log] | [ENV_ERROR_LOG] | `error_log` | `$XDG_STATE_HOME/netpulse/netpulse.err` | [DAEMON_LOG_ERR] |
+//!
+//! The config file itself can not be moved in the config, so [config_file] only honors
+//! [ENV_CONFIG] and the user mode.
+//!
+//! The `[paths]` table is read once, the first time a location is resolved.
+//!
+//! # Example
+//!
+//! ```toml
+//! [paths]
+//! store_dir = "/srv/netpulse"
+//! control_socket = "/run/netpulse.sock"
+//! ```
+//!
+//! # User Mode
+//!
+//! By default, netpulse is installed for the whole system: the daemon runs as
+//! [DAEMON_USER](crate::DAEMON_USER) and keeps its files in `/var/lib`, `/run` and `/etc`. On
+//! machines without root, set [ENV_USER_MODE] (or pass `--user` to the executables) to run the
+//! daemon as the invoking user instead. The files are then in the XDG directories of that user.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::{Config, PathsConfig, CONFIG_PATH, ENV_CONFIG};
+use crate::store::{DB_NAME, DB_PATH, ENV_PATH};
+use crate::{DAEMON_CONTROL_SOCKET, DAEMON_LOG_ERR, DAEMON_LOG_INF, DAEMON_PID_FILE};
+
+/// Environment variable that enables the [user mode](user_mode) if set to anything but `0`
+pub const ENV_USER_MODE: &str = "NETPULSE_USER_MODE";
+/// Environment variable with the path of the pid file, see [pid_file]
+pub const ENV_PID_FILE: &str = "NETPULSE_PID_FILE";
+/// Environment variable with the path of the control socket, see [control_socket]
+pub const ENV_CONTROL_SOCKET: &str = "NETPULSE_CONTROL_SOCKET";
+/// Environment variable with the path of the info log, see [info_log]
+pub const ENV_INFO_LOG: &str = "NETPULSE_INFO_LOG";
+/// Environment variable with the path of the error log, see [error_log]
+pub const ENV_ERROR_LOG: &str = "NETPULSE_ERROR_LOG";
+
+/// Returns true if netpulse runs in user mode, see [ENV_USER_MODE].
+///
+/// In user mode, the daemon runs as the invoking user and no root privileges are needed. The
+/// files are in the XDG directories of that user:
+///
+/// - the store in `$XDG_DATA_HOME/netpulse` (`~/.local/share/netpulse`)
+/// - the config at `$XDG_CONFIG_HOME/netpulse.toml` (`~/.config/netpulse.toml`)
+/// - the control socket and pid file in `$XDG_RUNTIME_DIR/netpulse`
+/// - the logs in `$XDG_STATE_HOME/netpulse` (`~/.local/state/netpulse`)
+pub fn user_mode() -> bool {
+    std::env::var_os(ENV_USER_MODE).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Returns the path of the config file.
+///
+/// This is [ENV_CONFIG] if set, otherwise `$XDG_CONFIG_HOME/netpulse.toml` in
+/// [user mode](user_mode) or [CONFIG_PATH].
+pub fn config_file() -> PathBuf {
+    resolve(
+        std::env::var_os(ENV_CONFIG),
+        None,
+        || xdg_dir("XDG_CONFIG_HOME", ".config").join("netpulse.toml"),
+        CONFIG_PATH,
+    )
+}
+
+/// Returns the directory of the store.
+pub fn store_dir() -> PathBuf {
+    resolve(
+        std::env::var_os(ENV_PATH),
+        configured().store_dir.as_deref(),
+        || xdg_dir("XDG_DATA_HOME", ".local/share").join("netpulse"),
+        DB_PATH,
+    )
+}
+
+/// Returns the path of the store file, [DB_NAME] in the [store_dir].
+pub fn store_file() -> PathBuf {
+    store_dir().join(DB_NAME)
+}
+
+/// Returns the path of the pid file of the daemon.
+pub fn pid_file() -> PathBuf {
+    resolve(
+        std::env::var_os(ENV_PID_FILE),
+        configured().pid_file.as_deref(),
+        || runtime_dir().join("netpulse.pid"),
+        DAEMON_PID_FILE,
+    )
+}
+
+/// Returns the path of the control socket of the daemon, see [control](crate::control).
+pub fn control_socket() -> PathBuf {
+    resolve(
+        std::env::var_os(ENV_CONTROL_SOCKET),
+        configured().control_socket.as_deref(),
+        || runtime_dir().join("netpulsed.sock"),
+        DAEMON_CONTROL_SOCKET,
+    )
+}
+
+/// Returns the path of the log that the stdout of the daemon is redirected to.
+pub fn info_log() -> PathBuf {
+    resolve(
+        std::env::var_os(ENV_INFO_LOG),
+        configured().info_log.as_deref(),
+        || state_dir().join("netpulse.log"),
+        DAEMON_LOG_INF,
+    )
+}
+
+/// Returns the path of the log that the stderr of the daemon is redirected to.
+pub fn error_log() -> PathBuf {
+    resolve(
+        std::env::var_os(ENV_ERROR_LOG),
+        configured().error_log.as_deref(),
+        || state_dir().join("netpulse.err"),
+        DAEMON_LOG_ERR,
+    )
+}
+
+/// Returns the directory for the runtime files of the daemon in [user mode](user_mode).
+///
+/// This is `$XDG_RUNTIME_DIR/netpulse`. If `$XDG_RUNTIME_DIR` is not set, a directory of the
+/// user in the temporary directory is used instead.
+pub fn runtime_dir() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("netpulse"),
+        #[cfg(unix)]
+        _ => std::env::temp_dir().join(format!("netpulse-{}", nix::unistd::getuid())),
+        #[cfg(not(unix))]
+        _ => std::env::temp_dir().join("netpulse"),
+    }
+}
+
+/// Returns the directory for the logs of the daemon in [user mode](user_mode).
+fn state_dir() -> PathBuf {
+    xdg_dir("XDG_STATE_HOME", ".local/state").join("netpulse")
+}
+
+/// Returns the `[paths]` table of the config file, loaded on the first call.
+///
+/// A broken config file is reported by whoever loads the whole config, here it just means that
+/// nothing is configured.
+fn configured() -> &'static PathsConfig {
+    static PATHS: OnceLock<PathsConfig> = OnceLock::new();
+    PATHS.get_or_init(|| Config::load().map(|c| c.paths).unwrap_or_default())
+}
+
+/// Picks a location in the order described in the [module docs](self).
+fn resolve(
+    env: Option<OsString>,
+    configured: Option<&Path>,
+    user: impl FnOnce() -> PathBuf,
+    system: &str,
+) -> PathBuf {
+    match (env, configured) {
+        (Some(var), _) if !var.is_empty() => PathBuf::from(var),
+        (_, Some(path)) => path.to_path_buf(),
+        _ if user_mode() => user(),
+        _ => PathBuf::from(system),
+    }
+}
+
+/// Returns the XDG base directory in `var`, or `fallback` in the home directory of the user.
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    match std::env::var_os(var) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_dir().join(fallback),
+    }
+}
+
+/// Returns the home directory of the invoking user.
+#[cfg(unix)]
+fn home_dir() -> PathBuf {
+    match std::env::var_os("HOME") {
+        Some(home) if !home.is_empty() => PathBuf::from(home),
+        _ => nix::unistd::User::from_uid(nix::unistd::getuid())
+            .ok()
+            .flatten()
+            .map(|user| user.dir)
+            .unwrap_or_else(|| PathBuf::from("/")),
+    }
+}
+
+/// Returns the home directory of the invoking user.
+#[cfg(not(unix))]
+fn home_dir() -> PathBuf {
+    std::env::var_os("USERPROFILE")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let user = || PathBuf::from("/home/user/.local/share/netpulse");
+        assert_eq!(
+            resolve(
+                Some("/tmp/env".into()),
+                Some(Path::new("/srv/config")),
+                user,
+                DB_PATH
+            ),
+            PathBuf::from("/tmp/env")
+        );
+        assert_eq!(
+            resolve(
+                Some("".into()),
+                Some(Path::new("/srv/config")),
+                user,
+                DB_PATH
+            ),
+            PathBuf::from("/srv/config")
+        );
+        if !user_mode() {
+            assert_eq!(resolve(None, None, user, DB_PATH), PathBuf::from(DB_PATH));
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Pushing checks to a central server.
+//!
+//! To watch several places from one point, every probe can push the [Checks](Check) it makes to
+//! a central server. The daemon feeds the [Checks](Check) of each round into a [Pusher], which
+//! collects them and sends them as a [Batch] every [interval](PushConfig::interval) seconds to
+//! the [url](PushConfig::url) of the [PushConfig].
+//!
+//! # Payload
+//!
+//! The server receives a POST request with the [Batch] as JSON body. If a
+//! [token](PushConfig::token) is configured, it is sent in the `Authorization: Bearer <token>`
+//! header.
+//!
+//! ```json
+//! {
+//!   "probe": "laptop",
+//!   "checks": [
+//!     {
+//!       "timestamp": 1731121200,
+//!       "flags": 4097,
+//!       "latency": 12000,
+//!       "target": "1.1.1.1",
+//!       "label": "cloudflare-dns",
+//!       "http_status": 200,
+//!       "measurement": null,
+//!       "interface": null,
+//!       "zone": null,
+//!       "probe": null
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Any answer with a 2xx status counts as received. If sending failed, the [Checks](Check) are
+//! sent again with the next batch. At most [MAX_PENDING] [Checks](Check) are kept for that, older
+//! ones are dropped. [Checks](Check) that were not pushed yet when the daemon stops are not
+//! pushed at all.
+//!
+//! Sending requires the `push` feature.
+
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::config::PushConfig;
+use crate::errors::PushError;
+use crate::records::Check;
+
+/// How many [Checks](Check) are kept at most while the server can not be reached
+pub const MAX_PENDING: usize = 100_000;
+
+/// [Checks](Check) of one probe, the body of a push request
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Batch {
+    /// Name of the probe that made the [Checks](Check)
+    pub probe: String,
+    /// The [Checks](Check), oldest first
+    pub checks: Vec<Check>,
+}
+
+/// Result of a push in the background, with the [Checks](Check) if it failed
+type Sending = JoinHandle<Result<(), (Vec<Check>, PushError)>>;
+
+/// Pushes the [Checks](Check) of the daemon to a central server, according to a [PushConfig].
+#[derive(Debug)]
+pub struct Pusher {
+    config: PushConfig,
+    probe: String,
+    /// Checks that were not pushed yet, oldest first
+    pending: Vec<Check>,
+    /// When the last push was started
+    last_push: Option<Instant>,
+    sending: Option<Sending>,
+}
+
+impl Pusher {
+    /// Creates a new [`Pusher`].
+    ///
+    /// The name of the probe is the [probe](PushConfig::probe) of the config, or the host name.
+    pub fn new(config: PushConfig) -> Self {
+        let probe = config.probe.clone().unwrap_or_else(|| {
+            sysinfo::System::host_name().unwrap_or_else(|| "unknown".to_string())
+        });
+        Self {
+            config,
+            probe,
+            pending: Vec::new(),
+            last_push: None,
+            sending: None,
+        }
+    }
+
+    /// Returns the name of this probe.
+    pub fn probe(&self) -> &str {
+        &self.probe
+    }
+
+    /// Returns the number of [Checks](Check) that were not pushed yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Collects the [Checks](Check) of one round and pushes them if the interval passed.
+    ///
+    /// The [Checks](Check) are sent in a background thread, so this does not block on the
+    /// network.
+    pub fn round(&mut self, checks: &[&Check]) {
+        if self.config.url.is_none() {
+            return;
+        }
+        self.collect_sent();
+        self.pending.extend(checks.iter().map(|c| (*c).clone()));
+        if self.pending.len() > MAX_PENDING {
+            let excess = self.pending.len() - MAX_PENDING;
+            self.pending.drain(..excess);
+            warn!("too many checks were not pushed, dropped the oldest {excess}");
+        }
+        if self.sending.is_none() && !self.pending.is_empty() && self.is_due(Instant::now()) {
+            self.last_push = Some(Instant::now());
+            self.send();
+        }
+    }
+
+    /// Returns whether the [interval](PushConfig::interval) since the last push passed.
+    fn is_due(&self, now: Instant) -> bool {
+        self.last_push.is_none_or(|last| {
+            now.duration_since(last) >= Duration::from_secs(self.config.interval)
+        })
+    }
+
+    /// Takes the result of a finished push and keeps its [Checks](Check) if it failed.
+    fn collect_sent(&mut self) {
+        if !self.sending.as_ref().is_some_and(JoinHandle::is_finished) {
+            return;
+        }
+        let sending = self.sending.take().expect("a finished push has a handle");
+        match sending.join() {
+            Ok(Ok(())) => (),
+            Ok(Err((mut checks, err))) => {
+                error!(
+                    "could not push {} checks, trying again with the next push: {err}",
+                    checks.len()
+                );
+                checks.append(&mut self.pending);
+                self.pending = checks;
+            }
+            Err(_) => error!("the thread pushing checks panicked, the checks are lost"),
+        }
+    }
+
+    #[cfg(feature = "push")]
+    fn send(&mut self) {
+        let Some(url) = self.config.url.clone() else {
+            return;
+        };
+        let token = self.config.token.clone();
+        let batch = Batch {
+            probe: self.probe.clone(),
+            checks: std::mem::take(&mut self.pending),
+        };
+        self.sending = Some(std::thread::spawn(move || {
+            match push_batch(&url, token.as_deref(), &batch) {
+                Ok(()) => {
+                    tracing::info!("pushed {} checks to {url}", batch.checks.len());
+                    Ok(())
+                }
+                Err(err) => Err((batch.checks, err)),
+            }
+        }));
+    }
+
+    #[cfg(not(feature = "push"))]
+    fn send(&mut self) {
+        warn!(
+            "a push url is configured, but netpulse was built without the push feature, not pushing {} checks",
+            self.pending.len()
+        );
+        self.pending.clear();
+    }
+}
+
+/// POSTs a [Batch] as JSON to `url`.
+///
+/// If `token` is set, it is sent in the `Authorization: Bearer <token>` header.
+///
+/// # Errors
+///
+/// Returns [PushError] if the request failed or the response status is not a success.
+#[cfg(feature = "push")]
+pub fn push_batch(url: &str, token: Option<&str>, batch: &Batch) -> Result<(), PushError> {
+    let payload = serde_json::to_vec(batch)?;
+    let headers: Vec<String> = token
+        .map(|token| format!("Authorization: Bearer {token}"))
+        .into_iter()
+        .collect();
+    let status = crate::checks::post(url, "application/json", &headers, &payload)?;
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(PushError::BadStatus { status })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::records::CheckFlag;
+
+    use super::*;
+
+    fn check(minute: i64) -> Check {
+        Check::new(
+            chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+            CheckFlag::Success | CheckFlag::TypeHTTP,
+            Some(Duration::from_millis(12)),
+            "1.1.1.1".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_failed_push_is_kept() {
+        let mut pusher = Pusher::new(PushConfig {
+            url: Some("http://localhost:8080/push".to_string()),
+            probe: Some("laptop".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(pusher.probe(), "laptop");
+        assert!(pusher.is_due(Instant::now()));
+
+        pusher.last_push = Some(Instant::now());
+        assert!(!pusher.is_due(Instant::now()));
+        let new = check(2);
+        pusher.round(&[&new]);
+        assert_eq!(pusher.pending(), 1);
+
+        // a push of older checks fails while the new one comes in
+        let sent = vec![check(0), check(1)];
+        pusher.sending = Some(std::thread::spawn(move || {
+            Err((sent, PushError::BadStatus { status: 503 }))
+        }));
+        while !pusher.sending.as_ref().unwrap().is_finished() {
+            std::thread::yield_now();
+        }
+        pusher.collect_sent();
+        assert!(pusher.sending.is_none());
+        assert_eq!(pusher.pending, vec![check(0), check(1), check(2)]);
+    }
+}
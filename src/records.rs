@@ -2,8 +2,13 @@
 //!
 //! This module defines the fundamental types used throughout netpulse:
 //! - [`Check`] - Result of a single connectivity check
+//! - [`CheckBuilder`](builder::CheckBuilder) - Builds a [`Check`] step by step, for fixtures and
+//!   integrations
 //! - [`CheckType`] - Different types of checks (HTTP, ICMP, DNS)
 //! - [`CheckFlag`] - Flags indicating check status and metadata
+//! - [`Measurement`] - Additional results that only some checks produce
+//! - [`Aggregate`](aggregate::Aggregate) - Hourly summary of old checks
+//! - [`Anonymizer`](anonymize::Anonymizer) - Replaces addresses and names for sharing checks
 //!
 //! # Check Types
 //!
@@ -11,12 +16,14 @@
 //! - HTTP(S) - Web connectivity checks
 //! - ICMPv4/v6 - Ping checks
 //! - DNS - Domain name resolution (planned)
+//! - MTU - Path MTU probing with unfragmented ICMP echoes
 //!
 //! # Check Flags
 //!
 //! Checks use a bitflag system to track:
 //! - Success/failure status
-//! - Failure reasons (timeout, unreachable)
+//! - Failure reasons (timeout, unreachable, server error)
+//! - Whether the check needed retries
 //! - Protocol used (IPv4/IPv6)
 //! - Check type (HTTP, ICMP, DNS)
 //!
@@ -25,7 +32,7 @@
 //! # Example
 //!
 //! ```rust
-//! # #[cfg(feature = "http")] {// only works with that feature
+//! # #[cfg(any(feature = "http", feature = "http-rustls"))] {// only works with that feature
 //! use netpulse::records::{CheckType, Check};
 //!
 //! // Create new HTTP check
@@ -41,17 +48,32 @@
 use std::fmt::{Display, Write};
 use std::hash::Hash;
 use std::net::IpAddr;
+use std::time::Duration;
 
 use chrono::{DateTime, Local, TimeZone, Timelike, Utc};
 use deepsize::DeepSizeOf;
 use flagset::{flags, FlagSet};
 use serde::{Deserialize, Serialize};
-use tracing::error;
+use tracing::debug;
 
-use crate::analyze::fmt_timestamp;
-use crate::errors::StoreError;
+use crate::analyze::{fmt_throughput, fmt_timestamp};
+use crate::checks::bind::{fmt_scoped, Bind};
+#[cfg(feature = "ping")]
+use crate::checks::checker::PingChecker;
+use crate::checks::checker::{self, Checker};
+#[cfg(feature = "ping")]
+use crate::config::PingConfig;
+use crate::config::{Config, Target};
+use crate::errors::{CheckError, StoreError};
 use crate::store::Version;
 
+pub mod aggregate;
+pub mod anonymize;
+pub mod builder;
+pub mod influx;
+pub mod outage;
+pub mod trace;
+
 /// Type of [IpAddr]
 ///
 /// This enum can be used to work with just abstract IP versions, not whole [Ip Addresses](IpAddr).
@@ -71,27 +93,133 @@ pub enum IpType {
 /// when parsed.
 pub const TARGETS: &[&str] = &["1.1.1.1", "2606:4700:4700::1111"];
 
+/// Additional result of a [Check] that only some kinds of checks produce.
+///
+/// New variants must only ever be added at the end, so stores with older variants can still be
+/// read.
+#[derive(Debug, PartialEq, Eq, Hash, Deserialize, Serialize, Clone, Copy, DeepSizeOf)]
+pub enum Measurement {
+    /// Result of a burst of ICMP echoes, see [PingConfig](crate::config::PingConfig)
+    Loss {
+        /// Number of echo requests sent
+        sent: u8,
+        /// Number of echo replies received
+        received: u8,
+    },
+    /// Result of an MTU check, see [mtu](crate::checks::mtu)
+    Mtu {
+        /// Largest packet size in bytes that got through without fragmentation
+        size: u16,
+    },
+    /// Result of an NTP check, see [ntp](crate::checks::ntp)
+    Ntp {
+        /// Offset of the local clock against the server in milliseconds, positive if the local
+        /// clock is behind
+        offset: i32,
+    },
+    /// Result of a throughput sample, see [bandwidth](crate::checks::bandwidth)
+    Throughput {
+        /// Bytes per second while downloading the payload
+        bytes_per_second: u32,
+    },
+    /// Time to live of the reply to a single ICMP echo, see [Pong](crate::checks::Pong)
+    Ttl {
+        /// Time to live (hop limit for IPv6) of the reply when it arrived
+        ttl: u8,
+    },
+}
+
+impl Measurement {
+    /// Returns the ratio of lost packets between 0.0 and 1.0, if this is a [Measurement::Loss].
+    pub fn loss_ratio(&self) -> Option<f64> {
+        match self {
+            Self::Loss { sent, received } if *sent > 0 => {
+                Some(1.0 - (*received as f64 / *sent as f64))
+            }
+            Self::Loss { .. } => None,
+            Self::Mtu { .. } | Self::Ntp { .. } | Self::Throughput { .. } | Self::Ttl { .. } => {
+                None
+            }
+        }
+    }
+}
+
 flags! {
     /// Flags describing the status and type of a check.
     ///
     /// Uses a bitflag system to efficiently store multiple properties:
-    /// - Result flags (bits 0-7): Success, failure reasons
-    /// - Type flags (bits 8-15): Check type (HTTP, ICMP, DNS)
+    /// - Result flags (bits 0-6): Success, failure reasons, see [Check::failure_reason]
+    /// - Type flags (bits 7-15 and 17): Check type (HTTP, ICMP, DNS, DoH, DoT, NTP, MTU,
+    ///   gateway, bandwidth, QUIC)
+    /// - Quality flags (bit 16): Degraded, see [Check::is_degraded]
     #[derive(Hash, Deserialize, Serialize)]
-    pub enum CheckFlag: u16 {
+    pub enum CheckFlag: u32 {
         /// If this is not set, the check will be considered failed
         Success     =   0b0000_0000_0000_0001,
         /// Failure because of a timeout
         Timeout     =   0b0000_0000_0000_0010,
         /// Failure because the destination is unreachable
         Unreachable =   0b0000_0000_0000_0100,
+        /// Failure because the server answered with an error status (HTTP 5xx)
+        ServerError =   0b0000_0000_0000_1000,
+        /// The result was only reached after more than one attempt
+        Retried     =   0b0000_0000_0001_0000,
+        /// Failure because the target refused or reset the connection
+        Refused     =   0b0000_0000_0010_0000,
+        /// Failure because a host name could not be resolved, or the DNS server did not give a
+        /// valid answer
+        DnsFailure  =   0b0000_0000_0100_0000,
 
+        /// The Check sampled the throughput
+        TypeBandwidth = 0b0000_0000_1000_0000,
+
+        /// The Check probed the path MTU
+        TypeMtu     =   0b0000_0001_0000_0000,
+        /// The Check pinged the default gateway
+        TypeGateway =   0b0000_0010_0000_0000,
+        /// The Check used DNS over TLS
+        TypeDoT     =   0b0000_0100_0000_0000,
+        /// The Check queried an NTP server
+        TypeNtp     =   0b0000_1000_0000_0000,
         /// The Check used HTTP/HTTPS
         TypeHTTP    =   0b0001_0000_0000_0000,
+        /// The Check used DNS over HTTPS
+        TypeDoH     =   0b0010_0000_0000_0000,
         /// Check type was ICMP (ping)
         TypeIcmp    =   0b0100_0000_0000_0000,
         /// The Check used DNS
         TypeDns     =   0b1000_0000_0000_0000,
+
+        /// The Check succeeded, but took longer than the configured threshold, see
+        /// [DegradedConfig](crate::config::DegradedConfig)
+        Degraded    = 0b1_0000_0000_0000_0000,
+
+        /// The Check made a QUIC handshake and an HTTP/3 request
+        TypeQuic    = 0b10_0000_0000_0000_0000,
+    }
+}
+
+impl CheckFlag {
+    /// Flags that tell why a [Check] failed, see [Check::failure_reason]
+    pub const REASONS: [CheckFlag; 5] = [
+        CheckFlag::Timeout,
+        CheckFlag::Refused,
+        CheckFlag::Unreachable,
+        CheckFlag::DnsFailure,
+        CheckFlag::ServerError,
+    ];
+
+    /// Returns a short description of this flag, like `timeout`, if it is one of the
+    /// [REASONS](CheckFlag::REASONS).
+    pub fn reason_name(&self) -> Option<&'static str> {
+        Some(match self {
+            CheckFlag::Timeout => "timeout",
+            CheckFlag::Refused => "connection refused",
+            CheckFlag::Unreachable => "unreachable",
+            CheckFlag::DnsFailure => "DNS failure",
+            CheckFlag::ServerError => "server error",
+            _ => return None,
+        })
     }
 }
 
@@ -99,9 +227,11 @@ flags! {
 ///
 /// This enum represents the different kinds of checks that can be performed.
 /// Each variant corresponds to a specific protocol or method of testing connectivity.
-#[derive(Debug, PartialEq, Eq, Hash, Deserialize, Serialize, Clone, Copy, DeepSizeOf)]
+#[derive(
+    Debug, PartialEq, Eq, Hash, Deserialize, Serialize, Clone, Copy, DeepSizeOf, PartialOrd, Ord,
+)]
 pub enum CheckType {
-    /// DNS resolution check (not yet implemented)
+    /// DNS query over UDP, see [dns](crate::checks::dns)
     Dns,
     /// HTTP/HTTPS connectivity check
     Http,
@@ -109,6 +239,23 @@ pub enum CheckType {
     Icmp,
     /// Unknown or invalid check type
     Unknown,
+    /// Path MTU check with ICMP echoes that must not be fragmented
+    ///
+    /// Added after [CheckType::Unknown] to keep the serialized form of the other types.
+    Mtu,
+    /// ICMP ping of the default gateway, see [gateway](crate::checks::gateway)
+    Gateway,
+    /// DNS query over HTTPS, see [dns](crate::checks::dns)
+    Doh,
+    /// DNS query over TLS, see [dns](crate::checks::dns)
+    Dot,
+    /// SNTP query to an NTP server, see [ntp](crate::checks::ntp)
+    Ntp,
+    /// Download of a fixed payload to sample the throughput, see
+    /// [bandwidth](crate::checks::bandwidth)
+    Bandwidth,
+    /// QUIC handshake and HTTP/3 `HEAD` request, see [quic](crate::checks::quic)
+    Quic,
 }
 impl CheckType {
     /// Creates and performs a new network check of this type.
@@ -123,62 +270,169 @@ impl CheckType {
     ///
     /// # Feature Requirements
     ///
-    /// - HTTP checks require the `http` feature
-    /// - ICMP checks require the `ping` feature
+    /// - HTTP checks require the `http` or `http-rustls` feature
+    /// - ICMP and gateway checks require the `ping` feature
+    /// - DoH and DoT checks require the `doh` and `dot` features
+    /// - Bandwidth checks require the `bandwidth` feature and the `url` of the [Target]
+    /// - QUIC checks require the `quic` feature
     ///
     /// # Panics
     ///
-    /// - If HTTP check is attempted without `http` feature
-    /// - If ICMP check is attempted without `ping` feature
+    /// - If HTTP check is attempted without `http` or `http-rustls` feature
+    /// - If ICMP or gateway check is attempted without `ping` feature
+    /// - If DoH or DoT check is attempted without `doh` or `dot` feature
+    /// - If bandwidth check is attempted without `bandwidth` feature or without a url
+    /// - If QUIC check is attempted without `quic` feature
     /// - If check type is `Unknown`
-    /// - If check type is `Dns` (not yet implemented)
     pub fn make(&self, remote: IpAddr) -> Check {
-        let mut check = Check::new(Utc::now(), FlagSet::default(), None, remote);
+        self.make_target(&Target::new(remote))
+    }
 
-        match self {
-            #[cfg(feature = "http")]
-            Self::Http => {
-                check.add_flag(CheckFlag::TypeHTTP);
-                match crate::checks::check_http(remote) {
-                    Err(err) => {
-                        error!("error while performing an Http check: {err}")
-                    }
-                    Ok(lat) => {
-                        check.add_flag(CheckFlag::Success);
-                        check.latency = Some(lat);
-                    }
-                }
-            }
-            #[cfg(not(feature = "http"))]
-            Self::Http => {
-                panic!("Trying to make a http check, but the http feature is not enabled")
-            }
+    /// Creates and performs a new network check of this type against a configured [Target].
+    ///
+    /// Works like [CheckType::make], but the [Check] gets the label of the [Target], and HTTP
+    /// checks request the URL of the [Target] if it has one.
+    ///
+    /// The check is performed by the [Checker] of this type, see [checker].
+    ///
+    /// The check is bound to the interface or source address of the [Target], if it has one,
+    /// see [Target::bind].
+    ///
+    /// # Panics
+    ///
+    /// See [CheckType::make].
+    pub fn make_target(&self, target: &Target) -> Check {
+        self.make_bound(target, &target.bind())
+    }
 
-            #[cfg(feature = "ping")]
-            Self::Icmp => {
-                check.add_flag(CheckFlag::TypeIcmp);
-                match crate::checks::just_fucking_ping(remote) {
-                    Err(err) => {
-                        error!("error while performing an ICMPv4 check: {err}")
-                    }
-                    Ok(lat) => {
-                        check.add_flag(CheckFlag::Success);
-                        check.latency = Some(lat);
-                    }
-                }
-            }
-            #[cfg(not(feature = "ping"))]
-            Self::Icmp => {
-                panic!("Trying to make a ICMPv4 check, but the ping feature is not enabled")
-            }
-            Self::Unknown => {
-                panic!("tried to make an Unknown check");
+    /// Makes a check like [CheckType::make_target], bound as set in `bind`
+    fn make_bound(&self, target: &Target, bind: &Bind) -> Check {
+        match checker::get(*self) {
+            Some(checker) => self.make_with(checker.as_ref(), target, bind),
+            None if *self == Self::Unknown => panic!("tried to make an Unknown check"),
+            None => panic!("Trying to make a {self} check, but its feature is not enabled"),
+        }
+    }
+
+    /// Makes a check of this type with `checker`, bound as set in `bind`
+    fn make_with(&self, checker: &dyn Checker, target: &Target, bind: &Bind) -> Check {
+        let mut check = Check::new(Utc::now(), FlagSet::default(), None, target.address);
+        check.set_label(target.label.clone());
+        check.interface = bind.name();
+        check.zone = target.zone.clone();
+        if let Some(flag) = self.flag() {
+            check.add_flag(flag);
+        }
+        checker.check(target, bind).record(*self, &mut check);
+        check
+    }
+
+    /// Creates and performs a new network check of this type as configured.
+    ///
+    /// Works like [CheckType::make_target], but:
+    /// - The check is bound to the interface or source address of the target, or the one of the
+    ///   `[bind]` table, see [Config::bind_for]
+    /// - ICMP and gateway checks send their echoes with the
+    ///   [options](crate::config::PingConfig::options) of the
+    ///   [PingConfig](crate::config::PingConfig), unless a [Checker] is
+    ///   [registered](checker::register) for them
+    /// - ICMP checks send a burst of echoes if the [count](crate::config::PingConfig::count) of
+    ///   the [PingConfig](crate::config::PingConfig) is more than one, and record the
+    ///   [Measurement::Loss]
+    /// - A failed check is repeated up to [retries](crate::config::RetryConfig::retries) times,
+    ///   waiting [delay](crate::config::RetryConfig::delay) in between. The last attempt is
+    ///   returned. If more than one attempt was made, it has the [CheckFlag::Retried] flag, so a
+    ///   check that passed on retry can be told apart from a clean success.
+    /// - A successful check that took longer than the threshold of its type in the
+    ///   [DegradedConfig](crate::config::DegradedConfig) gets the [CheckFlag::Degraded] flag
+    ///
+    /// # Panics
+    ///
+    /// See [CheckType::make].
+    pub fn make_configured(&self, target: &Target, config: &Config) -> Check {
+        let retry = &config.retry;
+        let mut check = self.make_once(target, config);
+        for attempt in 1..=retry.retries {
+            if check.is_success() {
+                break;
             }
-            Self::Dns => {
-                todo!("dns not done yet")
+            debug!(
+                "{self} check for {} failed, retrying ({attempt}/{})",
+                target.address, retry.retries
+            );
+            std::thread::sleep(retry.delay());
+            check = self.make_once(target, config);
+            check.add_flag(CheckFlag::Retried);
+        }
+        if let Some(threshold) = config.degraded.threshold(*self) {
+            check.check_degraded(threshold);
+        }
+        check
+    }
+
+    /// Makes a single attempt of a check for [CheckType::make_configured]
+    #[cfg_attr(not(feature = "ping"), allow(unused_variables))]
+    pub(crate) fn make_once(&self, target: &Target, config: &Config) -> Check {
+        let bind = config.bind_for(target);
+        #[cfg(feature = "ping")]
+        if matches!(self, Self::Icmp | Self::Gateway) && checker::registered(*self).is_none() {
+            if *self == Self::Icmp && config.ping.count > 1 {
+                return make_icmp_burst(target, &config.ping, &bind);
             }
+            let checker = PingChecker {
+                options: config.ping.options(),
+            };
+            return self.make_with(&checker, target, &bind);
         }
+        self.make_bound(target, &bind)
+    }
+
+    /// Returns the [CheckFlag] marking a [Check] as this type, or [None] for [CheckType::Unknown].
+    pub fn flag(&self) -> Option<CheckFlag> {
+        match self {
+            Self::Dns => Some(CheckFlag::TypeDns),
+            Self::Http => Some(CheckFlag::TypeHTTP),
+            Self::Icmp => Some(CheckFlag::TypeIcmp),
+            Self::Mtu => Some(CheckFlag::TypeMtu),
+            Self::Gateway => Some(CheckFlag::TypeGateway),
+            Self::Doh => Some(CheckFlag::TypeDoH),
+            Self::Dot => Some(CheckFlag::TypeDoT),
+            Self::Ntp => Some(CheckFlag::TypeNtp),
+            Self::Bandwidth => Some(CheckFlag::TypeBandwidth),
+            Self::Quic => Some(CheckFlag::TypeQuic),
+            Self::Unknown => None,
+        }
+    }
 
+    /// Returns the lowercase name of this type, like `http` or `dot`.
+    ///
+    /// This is the name [FromStr](std::str::FromStr) parses.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Dns => "dns",
+            Self::Http => "http",
+            Self::Icmp => "icmp",
+            Self::Mtu => "mtu",
+            Self::Gateway => "gateway",
+            Self::Doh => "doh",
+            Self::Dot => "dot",
+            Self::Ntp => "ntp",
+            Self::Bandwidth => "bandwidth",
+            Self::Quic => "quic",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Creates a failed [Check] of this type against a [Target] that did not finish in time.
+    ///
+    /// The [Check] has the [CheckFlag::Timeout] flag and no latency.
+    pub fn timed_out(&self, target: &Target) -> Check {
+        let mut check = Check::new(Utc::now(), CheckFlag::Timeout, None, target.address);
+        if let Some(flag) = self.flag() {
+            check.add_flag(flag);
+        }
+        check.set_label(target.label.clone());
+        check.zone = target.zone.clone();
         check
     }
 
@@ -186,24 +440,86 @@ impl CheckType {
     ///
     /// Used for iterating over available check types, e.g., during analysis.
     pub const fn all() -> &'static [Self] {
-        &[Self::Dns, Self::Http, Self::Icmp]
+        &[
+            Self::Dns,
+            Self::Http,
+            Self::Icmp,
+            Self::Mtu,
+            Self::Gateway,
+            Self::Doh,
+            Self::Dot,
+            Self::Ntp,
+            Self::Bandwidth,
+            Self::Quic,
+        ]
     }
 
     /// Returns a slice of check types enabled by default.
     ///
-    /// Currently only includes HTTP checks because ICMP requires special
-    /// privileges (CAP_NET_RAW) which are lost when the daemon drops privileges, and DNS is not
-    /// implemented.
+    /// Includes the HTTP checks, and the ICMP checks if the `ping` feature is enabled. ICMP needs
+    /// CAP_NET_RAW, which the daemon keeps when it drops its privileges, see
+    /// [drop_privileges](crate::common::drop_privileges). MTU checks are only made if the `mtu`
+    /// feature is enabled, they need CAP_NET_RAW as well. QUIC checks are only made if the `quic`
+    /// feature is enabled.
+    ///
+    /// DNS checks only make sense against resolvers, so they are not enabled by default, see
+    /// [DnsConfig](crate::config::DnsConfig). NTP checks are made against the configured NTP
+    /// servers instead of the targets, see [NtpConfig](crate::config::NtpConfig).
+    ///
+    /// Gateway checks are not made against the configured targets, but against the default
+    /// gateways, see [gateway_targets](crate::checks::gateway::gateway_targets).
     pub const fn default_enabled() -> &'static [Self] {
         &[
-            #[cfg(feature = "http")]
+            #[cfg(any(feature = "http", feature = "http-rustls"))]
             Self::Http,
             #[cfg(feature = "ping")]
             Self::Icmp,
+            #[cfg(feature = "mtu")]
+            Self::Mtu,
+            #[cfg(feature = "ping")]
+            Self::Gateway,
+            #[cfg(feature = "quic")]
+            Self::Quic,
         ]
     }
 }
 
+/// Sends a burst of ICMP echoes to a [Target] and records the [Measurement::Loss].
+///
+/// The check is successful if any echo came back, its latency is the mean of all echoes that
+/// came back.
+#[cfg(feature = "ping")]
+fn make_icmp_burst(target: &Target, ping: &PingConfig, bind: &Bind) -> Check {
+    let mut check = Check::new(Utc::now(), CheckFlag::TypeIcmp, None, target.address);
+    check.set_label(target.label.clone());
+    check.interface = bind.name();
+    check.zone = target.zone.clone();
+
+    let (received, latency, _ttl) = crate::checks::ping_burst(
+        target.address,
+        ping.count,
+        ping.interval(),
+        &ping.options(),
+        bind,
+    );
+    check.set_measurement(Some(Measurement::Loss {
+        sent: ping.count,
+        received,
+    }));
+    if received > 0 {
+        check.add_flag(CheckFlag::Success);
+        check.set_latency(latency);
+    } else {
+        check.add_flag(CheckFlag::Timeout);
+    }
+    check
+}
+
+/// Converts `latency` to microseconds for a [Check], cut off at [u32::MAX].
+fn micros(latency: Duration) -> u32 {
+    latency.as_micros().try_into().unwrap_or(u32::MAX)
+}
+
 impl Display for CheckType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -213,12 +529,40 @@ impl Display for CheckType {
                 Self::Dns => "DNS",
                 Self::Http => "HTTP(S)",
                 Self::Icmp => "ICMP",
+                Self::Mtu => "MTU",
+                Self::Gateway => "Gateway",
+                Self::Doh => "DoH",
+                Self::Dot => "DoT",
+                Self::Ntp => "NTP",
+                Self::Bandwidth => "Bandwidth",
+                Self::Quic => "QUIC",
                 Self::Unknown => "Unknown",
             }
         )
     }
 }
 
+impl std::str::FromStr for CheckType {
+    type Err = String;
+
+    /// Parses the lowercase [name](CheckType::name) of a [CheckType], like `http` or `dot`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "dns" => Self::Dns,
+            "http" => Self::Http,
+            "icmp" => Self::Icmp,
+            "mtu" => Self::Mtu,
+            "gateway" => Self::Gateway,
+            "doh" => Self::Doh,
+            "dot" => Self::Dot,
+            "ntp" => Self::Ntp,
+            "bandwidth" => Self::Bandwidth,
+            "quic" => Self::Quic,
+            _ => return Err(format!("Not a valid check type: {s}")),
+        })
+    }
+}
+
 /// Result of a single network connectivity check.
 ///
 /// Contains all information about a check attempt including:
@@ -226,8 +570,13 @@ impl Display for CheckType {
 /// - What type of check it was
 /// - Whether it succeeded
 /// - Measured latency (if successful)
-/// - Target address
-#[derive(Debug, PartialEq, Eq, Hash, Deserialize, Serialize, Clone, Copy)]
+/// - Target address and its label
+///
+/// Unlike in netpulse 0.8, a [Check] is [Clone] but not [Copy]. Its label, interface, zone and
+/// probe are [Strings](String) from the [Config], which can name any
+/// number of targets, so they can not be interned into `&'static str` without leaking memory
+/// in long running processes like the daemon or the server.
+#[derive(Debug, PartialEq, Eq, Hash, Deserialize, Serialize, Clone)]
 pub struct Check {
     /// Unix timestamp when check was performed (seconds since UNIX_EPOCH)
     timestamp: i64,
@@ -235,18 +584,35 @@ pub struct Check {
     ///
     /// Stored as a bitset where each bit represents a [CheckFlag]
     flags: FlagSet<CheckFlag>,
-    /// Round-trip latency in milliseconds if check succeeded
+    /// Round-trip latency in microseconds if check succeeded
     ///
-    /// Only present if check succeeded and must be less than
-    /// [TIMEOUT_MS](crate::TIMEOUT_MS)
-    latency: Option<u16>,
+    /// Only present if check succeeded. Before [Version::V3], this was a [u16] in milliseconds.
+    latency: Option<u32>,
     /// Target IP address that was checked
     target: IpAddr,
+    /// Human readable name of the target, see [Target](crate::config::Target)
+    label: Option<String>,
+    /// Status code of the HTTP response, if this was an HTTP check that got a response
+    http_status: Option<u16>,
+    /// Additional result of the check, if it produces one
+    measurement: Option<Measurement>,
+    /// Network interface or source address the check was bound to, see
+    /// [bind](crate::checks::bind)
+    interface: Option<String>,
+    /// Zone of a link-local target, like `eth0` in `fe80::1%eth0`, see
+    /// [zones](crate::checks::bind#zones)
+    zone: Option<String>,
+    /// Name of the machine that made the check, see [Config::probe]
+    probe: Option<String>,
 }
 
 impl DeepSizeOf for Check {
     fn deep_size_of_children(&self, context: &mut deepsize::Context) -> usize {
         self.latency.deep_size_of_children(context)
+            + self.label.deep_size_of_children(context)
+            + self.interface.deep_size_of_children(context)
+            + self.zone.deep_size_of_children(context)
+            + self.probe.deep_size_of_children(context)
     }
 }
 
@@ -295,7 +661,7 @@ impl Check {
     ///
     /// * `time` - When the check was performed
     /// * `flags` - Initial status flags
-    /// * `latency` - Measured latency (if successful)
+    /// * `latency` - Measured latency (if successful), see [Check::set_latency]
     /// * `target` - Target IP address
     ///
     /// # Panics
@@ -304,7 +670,7 @@ impl Check {
     pub fn new(
         time: impl Into<DateTime<Utc>>,
         flags: impl Into<FlagSet<CheckFlag>>,
-        latency: Option<u16>,
+        latency: Option<Duration>,
         target: IpAddr,
     ) -> Self {
         let mut t: DateTime<Utc> = time.into();
@@ -317,8 +683,37 @@ impl Check {
         Check {
             timestamp: t.timestamp(),
             flags: flags.into(),
+            latency: latency.map(micros),
+            target,
+            label: None,
+            http_status: None,
+            measurement: None,
+            interface: None,
+            zone: None,
+            probe: None,
+        }
+    }
+
+    /// Creates a [Check] from its raw fields, without normalizing the timestamp.
+    ///
+    /// Used to convert [Checks](Check) from older store versions. `latency` is in microseconds.
+    pub(crate) fn from_raw(
+        timestamp: i64,
+        flags: FlagSet<CheckFlag>,
+        latency: Option<u32>,
+        target: IpAddr,
+    ) -> Self {
+        Check {
+            timestamp,
+            flags,
             latency,
             target,
+            label: None,
+            http_status: None,
+            measurement: None,
+            interface: None,
+            zone: None,
+            probe: None,
         }
     }
 
@@ -330,12 +725,46 @@ impl Check {
         self.flags.contains(CheckFlag::Success)
     }
 
-    /// Returns the measured latency if check was successful.
+    /// Returns whether more than one attempt was needed for this check.
+    ///
+    /// See [CheckType::make_configured].
+    pub fn is_retried(&self) -> bool {
+        self.flags.contains(CheckFlag::Retried)
+    }
+
+    /// Returns whether this check succeeded, but took longer than the configured threshold.
+    ///
+    /// See [Check::check_degraded].
+    pub fn is_degraded(&self) -> bool {
+        self.is_success() && self.flags.contains(CheckFlag::Degraded)
+    }
+
+    /// Flags this check as [Degraded](CheckFlag::Degraded) if it succeeded, but its latency
+    /// exceeds `threshold`.
+    pub fn check_degraded(&mut self, threshold: Duration) {
+        if self
+            .latency_us()
+            .is_some_and(|us| us as u128 > threshold.as_micros())
+        {
+            self.add_flag(CheckFlag::Degraded);
+        }
+    }
+
+    /// Returns the measured latency in whole milliseconds if check was successful.
     ///
     /// Returns None if:
     /// - Check failed
     /// - Check succeeded but no latency was recorded
-    pub fn latency(&self) -> Option<u16> {
+    ///
+    /// See [Check::latency_us] for the full precision.
+    pub fn latency(&self) -> Option<u32> {
+        self.latency_us().map(|us| us / 1000)
+    }
+
+    /// Returns the measured latency in microseconds if check was successful.
+    ///
+    /// Like [Check::latency], but with the precision it was recorded with.
+    pub fn latency_us(&self) -> Option<u32> {
         if !self.is_success() {
             None
         } else {
@@ -343,6 +772,14 @@ impl Check {
         }
     }
 
+    /// Sets the measured latency.
+    ///
+    /// The latency is kept in microseconds, up to [u32::MAX] of them (more than an hour).
+    /// Longer latencies are cut off there.
+    pub fn set_latency(&mut self, latency: Option<Duration>) {
+        self.latency = latency.map(micros);
+    }
+
     /// Returns the flags of this [`Check`].
     pub fn flags(&self) -> FlagSet<CheckFlag> {
         self.flags
@@ -373,6 +810,28 @@ impl Check {
         self.flags |= flag
     }
 
+    /// Adds the flag that tells why this [Check] failed with `err`, if it is known.
+    ///
+    /// See [CheckError::reason].
+    pub fn add_failure(&mut self, err: &CheckError) {
+        if let Some(flag) = err.reason() {
+            self.add_flag(flag);
+        }
+    }
+
+    /// Returns the flag that tells why this [Check] failed, one of [CheckFlag::REASONS].
+    ///
+    /// Returns [None] if the check succeeded, or if the reason is not known, for example because
+    /// the check is from a version of netpulse that did not record it.
+    pub fn failure_reason(&self) -> Option<CheckFlag> {
+        if self.is_success() {
+            return None;
+        }
+        CheckFlag::REASONS
+            .into_iter()
+            .find(|flag| self.flags.contains(*flag))
+    }
+
     /// Determines [CheckType] from this checks flags.
     ///
     /// Examines the type and protocol flags to determine the specific
@@ -386,6 +845,20 @@ impl Check {
             CheckType::Dns
         } else if self.flags.contains(CheckFlag::TypeIcmp) {
             CheckType::Icmp
+        } else if self.flags.contains(CheckFlag::TypeMtu) {
+            CheckType::Mtu
+        } else if self.flags.contains(CheckFlag::TypeGateway) {
+            CheckType::Gateway
+        } else if self.flags.contains(CheckFlag::TypeDoH) {
+            CheckType::Doh
+        } else if self.flags.contains(CheckFlag::TypeDoT) {
+            CheckType::Dot
+        } else if self.flags.contains(CheckFlag::TypeNtp) {
+            CheckType::Ntp
+        } else if self.flags.contains(CheckFlag::TypeBandwidth) {
+            CheckType::Bandwidth
+        } else if self.flags.contains(CheckFlag::TypeQuic) {
+            CheckType::Quic
         } else {
             CheckType::Unknown
         })
@@ -412,6 +885,7 @@ impl Check {
         match current {
             Version::V0 => (),
             Version::V1 => self.timestamp = i64::from_ne_bytes(self.timestamp.to_ne_bytes()), // was originally u64
+            Version::V2 => (), // the old layout is converted while loading
             _ => unimplemented!("migrating from Version {current} is not yet imlpemented"),
         }
         Ok(())
@@ -421,23 +895,140 @@ impl Check {
     pub fn target(&self) -> IpAddr {
         self.target
     }
+
+    /// Returns the label of the target of this [`Check`], if it has one.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Sets the label of the target of this [`Check`].
+    pub fn set_label(&mut self, label: Option<String>) {
+        self.label = label;
+    }
+
+    /// Returns the HTTP status code of the response, if this was an HTTP check that got one.
+    ///
+    /// Unlike [Check::latency], this is also set for failed checks, for example if the server
+    /// answered with a 5xx status.
+    pub fn http_status(&self) -> Option<u16> {
+        self.http_status
+    }
+
+    /// Sets the HTTP status code of the response.
+    pub fn set_http_status(&mut self, status: Option<u16>) {
+        self.http_status = status;
+    }
+
+    /// Returns the additional result of this [`Check`], if it has one.
+    pub fn measurement(&self) -> Option<Measurement> {
+        self.measurement
+    }
+
+    /// Returns the network interface or source address this [`Check`] was bound to, if it was,
+    /// see [bind](crate::checks::bind).
+    pub fn interface(&self) -> Option<&str> {
+        self.interface.as_deref()
+    }
+
+    /// Sets the network interface or source address this [`Check`] was bound to.
+    pub fn set_interface(&mut self, interface: Option<String>) {
+        self.interface = interface;
+    }
+
+    /// Returns the zone of the target of this [`Check`], if it is a link-local address that has
+    /// one, see [zones](crate::checks::bind#zones).
+    pub fn zone(&self) -> Option<&str> {
+        self.zone.as_deref()
+    }
+
+    /// Sets the zone of the target of this [`Check`].
+    pub fn set_zone(&mut self, zone: Option<String>) {
+        self.zone = zone;
+    }
+
+    /// Returns the name of the machine that made this [`Check`], if it was recorded, see
+    /// [Config::probe].
+    pub fn probe(&self) -> Option<&str> {
+        self.probe.as_deref()
+    }
+
+    /// Sets the name of the machine that made this [`Check`].
+    pub fn set_probe(&mut self, probe: Option<String>) {
+        self.probe = probe;
+    }
+
+    /// Returns the target address with its zone if it has one, like `fe80::1%eth0`.
+    pub fn target_scoped(&self) -> String {
+        fmt_scoped(self.target, self.zone.as_deref())
+    }
+
+    /// Sets the additional result of this [`Check`].
+    pub fn set_measurement(&mut self, measurement: Option<Measurement>) {
+        self.measurement = measurement;
+    }
+
+    /// Returns the ratio of lost packets between 0.0 and 1.0, if this check sent a burst of
+    /// echoes.
+    pub fn loss_ratio(&self) -> Option<f64> {
+        self.measurement.and_then(|m| m.loss_ratio())
+    }
+
+    /// Returns the label of the target if it has one, otherwise the target address.
+    pub fn target_name(&self) -> String {
+        match &self.label {
+            Some(label) => label.clone(),
+            None => self.target_scoped(),
+        }
+    }
 }
 
 impl Display for Check {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Time: {}\nType: {}\nOk: {}\nTarget: {}\nLatency: {}\nHash: {}",
+            "Time: {}\nType: {}\nOk: {}{}\nTarget: {}\nLatency: {}\nHTTP Status: {}\nHash: {}",
             fmt_timestamp(self.timestamp_parsed()),
             self.calc_type().unwrap_or(CheckType::Unknown),
             self.is_success(),
-            self.target,
-            match self.latency() {
-                Some(l) => format!("{l} ms"),
+            match self.failure_reason().and_then(|flag| flag.reason_name()) {
+                Some(reason) => format!("\nReason: {reason}"),
+                None => String::new(),
+            },
+            match &self.label {
+                Some(label) => format!("{} ({label})", self.target_scoped()),
+                None => self.target_scoped(),
+            },
+            match self.latency_us() {
+                Some(us) => format!("{:.3} ms", us as f64 / 1000.0),
                 None => "(Error)".to_string(),
             },
+            match self.http_status {
+                Some(status) => status.to_string(),
+                None => "(None)".to_string(),
+            },
             self.get_hash()
-        )
+        )?;
+        match self.measurement {
+            Some(Measurement::Loss { sent, received }) => write!(
+                f,
+                "\nLoss: {:.1}% ({received}/{sent} received)",
+                self.loss_ratio().unwrap_or_default() * 100.0
+            )?,
+            Some(Measurement::Mtu { size }) => write!(f, "\nMTU: {size}")?,
+            Some(Measurement::Ntp { offset }) => write!(f, "\nClock Offset: {offset} ms")?,
+            Some(Measurement::Throughput { bytes_per_second }) => {
+                write!(f, "\nThroughput: {}", fmt_throughput(bytes_per_second))?
+            }
+            Some(Measurement::Ttl { ttl }) => write!(f, "\nReply TTL: {ttl}")?,
+            None => (),
+        }
+        if let Some(interface) = &self.interface {
+            write!(f, "\nInterface: {interface}")?;
+        }
+        if let Some(probe) = &self.probe {
+            write!(f, "\nProbe: {probe}")?;
+        }
+        Ok(())
     }
 }
 
@@ -489,27 +1080,61 @@ mod test {
         let _c = Check::new(
             time::SystemTime::now(),
             CheckFlag::Success,
-            Some(TIMEOUT_MS),
+            Some(Duration::from_millis(TIMEOUT_MS.into())),
             "127.0.0.1".parse().unwrap(),
         );
         // if it can be created, that's good enough for me, I'm just worried that I'll change the
         // timeout ms some day and this will break
     }
 
+    #[test]
+    fn test_latency_precision() {
+        let mut c = Check::new(
+            time::SystemTime::now(),
+            CheckFlag::Success,
+            Some(Duration::from_micros(12_345)),
+            "127.0.0.1".parse().unwrap(),
+        );
+        assert_eq!(c.latency_us(), Some(12_345));
+        assert_eq!(c.latency(), Some(12));
+
+        // longer than u16::MAX milliseconds
+        c.set_latency(Some(Duration::from_secs(90)));
+        assert_eq!(c.latency(), Some(90_000));
+        c.set_latency(Some(Duration::from_secs(u64::MAX)));
+        assert_eq!(c.latency_us(), Some(u32::MAX));
+    }
+
+    #[test]
+    fn test_parse_check_type() {
+        for check_type in CheckType::all() {
+            assert_eq!(check_type.name().parse::<CheckType>(), Ok(*check_type));
+        }
+        assert_eq!("HTTP".parse::<CheckType>(), Ok(CheckType::Http));
+        assert!("unknown".parse::<CheckType>().is_err());
+    }
+
     #[test]
     fn test_check_size_of_check() {
         let c = Check::new(
             time::SystemTime::now(),
             CheckFlag::Success,
-            Some(TIMEOUT_MS),
+            Some(Duration::from_millis(TIMEOUT_MS.into())),
             "127.0.0.1".parse().unwrap(),
         );
         assert_eq!(
             c.deep_size_of(),
             std::mem::size_of::<IpAddr>() // self.target
             + std::mem::size_of::<i64>() // self.timestamp
-            + std::mem::size_of::<u16>() // self.flags
-            +3 /* latency */ + 2 // latency padding?
+            + std::mem::size_of::<u32>() // self.flags
+            + std::mem::size_of::<Option<String>>() // self.label
+            + std::mem::size_of::<Option<String>>() // self.interface
+            + std::mem::size_of::<Option<String>>() // self.zone
+            + std::mem::size_of::<Option<String>>() // self.probe
+            + std::mem::size_of::<Option<u16>>() // self.http_status
+            + std::mem::size_of::<Option<Measurement>>() // self.measurement
+            + std::mem::size_of::<Option<u32>>() // self.latency
+            + 7 // padding
         );
         let c1 = Check::new(
             time::SystemTime::now(),
@@ -521,8 +1146,15 @@ mod test {
             c1.deep_size_of(),
             std::mem::size_of::<IpAddr>() // self.target
             + std::mem::size_of::<i64>() // self.timestamp
-            + std::mem::size_of::<u16>() // self.flags
-            +3 /* latency */ + 2 // latency padding?
+            + std::mem::size_of::<u32>() // self.flags
+            + std::mem::size_of::<Option<String>>() // self.label
+            + std::mem::size_of::<Option<String>>() // self.interface
+            + std::mem::size_of::<Option<String>>() // self.zone
+            + std::mem::size_of::<Option<String>>() // self.probe
+            + std::mem::size_of::<Option<u16>>() // self.http_status
+            + std::mem::size_of::<Option<Measurement>>() // self.measurement
+            + std::mem::size_of::<Option<u32>>() // self.latency
+            + 7 // padding
         );
         let c2 = Check::new(
             time::SystemTime::now(),
@@ -534,8 +1166,74 @@ mod test {
             c2.deep_size_of(),
             std::mem::size_of::<IpAddr>() // self.target
             + std::mem::size_of::<i64>() // self.timestamp
-            + std::mem::size_of::<u16>() // self.flags
-            +3 /* latency */ + 2 // latency padding?
+            + std::mem::size_of::<u32>() // self.flags
+            + std::mem::size_of::<Option<String>>() // self.label
+            + std::mem::size_of::<Option<String>>() // self.interface
+            + std::mem::size_of::<Option<String>>() // self.zone
+            + std::mem::size_of::<Option<String>>() // self.probe
+            + std::mem::size_of::<Option<u16>>() // self.http_status
+            + std::mem::size_of::<Option<Measurement>>() // self.measurement
+            + std::mem::size_of::<Option<u32>>() // self.latency
+            + 7 // padding
         )
     }
+
+    #[test]
+    fn test_failure_reason() {
+        use std::io::{Error, ErrorKind};
+
+        let remote: IpAddr = "127.0.0.1".parse().unwrap();
+        let mut check = Check::new(Utc::now(), CheckFlag::TypeHTTP, None, remote);
+        assert_eq!(check.failure_reason(), None);
+        assert!(!check.to_string().contains("Reason"));
+
+        check.add_failure(&CheckError::from(Error::from(ErrorKind::ConnectionRefused)));
+        assert_eq!(check.failure_reason(), Some(CheckFlag::Refused));
+        assert!(check.to_string().contains("Reason: connection refused"));
+
+        let cases = [
+            (ErrorKind::WouldBlock, Some(CheckFlag::Timeout)),
+            (ErrorKind::HostUnreachable, Some(CheckFlag::Unreachable)),
+            (ErrorKind::PermissionDenied, None),
+        ];
+        for (kind, reason) in cases {
+            assert_eq!(CheckError::from(Error::from(kind)).reason(), reason);
+        }
+        assert_eq!(
+            CheckError::BadDnsResponse("wrong id").reason(),
+            Some(CheckFlag::DnsFailure)
+        );
+
+        let ok = Check::new(
+            Utc::now(),
+            CheckFlag::Success | CheckFlag::Timeout | CheckFlag::TypeIcmp,
+            Some(Duration::from_millis(1)),
+            remote,
+        );
+        assert_eq!(ok.failure_reason(), None);
+    }
+
+    #[test]
+    #[cfg(any(feature = "http", feature = "http-rustls"))]
+    fn test_make_configured() {
+        let target = Target {
+            address: "127.0.0.1".parse().unwrap(),
+            zone: None,
+            label: None,
+            url: Some("http://127.0.0.1:1/".to_string()),
+            interface: None,
+            source: None,
+        };
+        let mut config = Config::default();
+        let once = CheckType::Http.make_configured(&target, &config);
+        assert!(!once.is_success());
+        assert!(!once.is_retried());
+
+        config.retry.retries = 2;
+        config.retry.delay = 10;
+        let retried = CheckType::Http.make_configured(&target, &config);
+        assert!(!retried.is_success());
+        assert!(retried.is_retried());
+        assert_eq!(retried.calc_type().unwrap(), CheckType::Http);
+    }
 }
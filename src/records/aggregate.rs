@@ -0,0 +1,295 @@
+//! Hourly summaries of old [Checks](Check).
+//!
+//! A store that runs for a long time accumulates a lot of [Checks](Check), most of which are only
+//! interesting in aggregate after a while. Instead of deleting old data, it can be rolled up into
+//! [Aggregates](Aggregate), one per hour, [CheckType] and target.
+//!
+//! Aggregates keep:
+//! - Success and failure counts
+//! - Latency percentiles (p50, p90, p99) of the successful checks
+//!
+//! The analyzer merges the counts of [Aggregates](Aggregate) with those of the raw
+//! [Checks](Check), so statistics stay correct after downsampling. Outage detection still only
+//! works on raw [Checks](Check), as the exact timing of failures is lost.
+//!
+//! # Example
+//!
+//! ```rust
+//! use netpulse::records::{Check, CheckFlag};
+//! use netpulse::records::aggregate::aggregate_checks;
+//!
+//! let target = "1.1.1.1".parse().unwrap();
+//! let checks = vec![
+//!     Check::new(std::time::SystemTime::now(), CheckFlag::Success | CheckFlag::TypeHTTP, Some(std::time::Duration::from_millis(20)), target),
+//!     Check::new(std::time::SystemTime::now(), CheckFlag::TypeHTTP, None, target),
+//! ];
+//! let aggregates = aggregate_checks(&checks);
+//! assert_eq!(aggregates.len(), 1);
+//! assert_eq!(aggregates[0].total(), 2);
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local, TimeZone};
+use deepsize::DeepSizeOf;
+use serde::{Deserialize, Serialize};
+
+use crate::analyze::fmt_timestamp;
+
+use super::{Check, CheckType, IpType};
+
+/// Length of the time span covered by one [Aggregate], in seconds
+pub const AGGREGATE_SPAN: i64 = 60 * 60;
+
+/// Summary of all [Checks](Check) of one [CheckType] against one target in one hour.
+///
+/// Created by [aggregate_checks] and stored in the [Store](crate::store::Store) when old checks
+/// are downsampled with [Store::downsample](crate::store::Store::downsample).
+#[derive(Debug, PartialEq, Eq, Hash, Deserialize, Serialize, Clone, Copy, PartialOrd, Ord)]
+pub struct Aggregate {
+    /// Unix timestamp of the start of the hour this [Aggregate] covers
+    start: i64,
+    /// Type of the summarized [Checks](Check)
+    check_type: CheckType,
+    /// Target IP address of the summarized [Checks](Check)
+    target: IpAddr,
+    /// Number of successful [Checks](Check)
+    successes: u32,
+    /// Number of failed [Checks](Check)
+    failures: u32,
+    /// Median latency of the successful [Checks](Check) in milliseconds
+    latency_p50: Option<u16>,
+    /// 90th percentile latency of the successful [Checks](Check) in milliseconds
+    latency_p90: Option<u16>,
+    /// 99th percentile latency of the successful [Checks](Check) in milliseconds
+    latency_p99: Option<u16>,
+}
+
+impl DeepSizeOf for Aggregate {
+    fn deep_size_of_children(&self, _context: &mut deepsize::Context) -> usize {
+        0 // nothing lives on the heap
+    }
+}
+
+impl Aggregate {
+    /// Builds an [Aggregate] from [Checks](Check) that all share the same hour, type and target.
+    ///
+    /// Returns [None] if `checks` is empty.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, panics if the checks do not share the same hour, type and target.
+    pub fn from_group(checks: &[&Check]) -> Option<Self> {
+        let first = checks.first()?;
+        let start = hour_of(first.timestamp());
+        let check_type = first.calc_type().unwrap_or(CheckType::Unknown);
+        let target = first.target();
+        debug_assert!(checks.iter().all(|c| hour_of(c.timestamp()) == start
+            && c.calc_type().unwrap_or(CheckType::Unknown) == check_type
+            && c.target() == target));
+
+        // aggregates keep milliseconds, which is precise enough for an hour of checks
+        let mut latencies: Vec<u16> = checks
+            .iter()
+            .filter_map(|c| c.latency())
+            .map(|ms| u16::try_from(ms).unwrap_or(u16::MAX))
+            .collect();
+        latencies.sort_unstable();
+        let successes = checks.iter().filter(|c| c.is_success()).count() as u32;
+
+        Some(Self {
+            start,
+            check_type,
+            target,
+            successes,
+            failures: checks.len() as u32 - successes,
+            latency_p50: percentile(&latencies, 50),
+            latency_p90: percentile(&latencies, 90),
+            latency_p99: percentile(&latencies, 99),
+        })
+    }
+
+    /// Returns the unix timestamp of the start of the hour this [`Aggregate`] covers.
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    /// Returns the unix timestamp of the end of the hour this [`Aggregate`] covers.
+    pub fn end(&self) -> i64 {
+        self.start + AGGREGATE_SPAN
+    }
+
+    /// Returns the start of this [`Aggregate`] in the timezone of the user.
+    pub fn start_parsed(&self) -> DateTime<Local> {
+        Local.timestamp_opt(self.start, 0).unwrap()
+    }
+
+    /// Returns the [CheckType] of the summarized [Checks](Check).
+    pub fn check_type(&self) -> CheckType {
+        self.check_type
+    }
+
+    /// Returns the target of the summarized [Checks](Check).
+    pub fn target(&self) -> IpAddr {
+        self.target
+    }
+
+    /// Replaces the target of the summarized [Checks](Check), see [anonymize](super::anonymize).
+    pub(crate) fn set_target(&mut self, target: IpAddr) {
+        self.target = target;
+    }
+
+    /// Returns whether the summarized [Checks](Check) used IPv4 or IPv6.
+    pub fn ip_type(&self) -> IpType {
+        IpType::from(self.target)
+    }
+
+    /// Returns the number of successful [Checks](Check).
+    pub fn successes(&self) -> u32 {
+        self.successes
+    }
+
+    /// Returns the number of failed [Checks](Check).
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Returns the number of all summarized [Checks](Check).
+    pub fn total(&self) -> u32 {
+        self.successes + self.failures
+    }
+
+    /// Returns the median latency in milliseconds, if any check succeeded.
+    pub fn latency_p50(&self) -> Option<u16> {
+        self.latency_p50
+    }
+
+    /// Returns the 90th percentile latency in milliseconds, if any check succeeded.
+    pub fn latency_p90(&self) -> Option<u16> {
+        self.latency_p90
+    }
+
+    /// Returns the 99th percentile latency in milliseconds, if any check succeeded.
+    pub fn latency_p99(&self) -> Option<u16> {
+        self.latency_p99
+    }
+}
+
+impl Display for Aggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fmt_lat = |l: Option<u16>| match l {
+            Some(l) => format!("{l} ms"),
+            None => "(None)".to_string(),
+        };
+        write!(
+            f,
+            "Hour: {}\nType: {}\nTarget: {}\nOk: {}\nBad: {}\nLatency p50: {}\nLatency p90: {}\nLatency p99: {}",
+            fmt_timestamp(self.start_parsed()),
+            self.check_type,
+            self.target,
+            self.successes,
+            self.failures,
+            fmt_lat(self.latency_p50),
+            fmt_lat(self.latency_p90),
+            fmt_lat(self.latency_p99),
+        )
+    }
+}
+
+/// Rolls up [Checks](Check) into [Aggregates](Aggregate), one per hour, [CheckType] and target.
+///
+/// The returned [Aggregates](Aggregate) are sorted by time.
+pub fn aggregate_checks(checks: &[Check]) -> Vec<Aggregate> {
+    let mut groups: BTreeMap<(i64, CheckType, IpAddr), Vec<&Check>> = BTreeMap::new();
+    for check in checks {
+        groups
+            .entry((
+                hour_of(check.timestamp()),
+                check.calc_type().unwrap_or(CheckType::Unknown),
+                check.target(),
+            ))
+            .or_default()
+            .push(check);
+    }
+
+    groups
+        .values()
+        .filter_map(|group| Aggregate::from_group(group))
+        .collect()
+}
+
+/// Floors a unix timestamp to the start of its hour
+fn hour_of(timestamp: i64) -> i64 {
+    timestamp - timestamp.rem_euclid(AGGREGATE_SPAN)
+}
+
+/// Nearest-rank percentile of already sorted values
+fn percentile(sorted: &[u16], p: usize) -> Option<u16> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    Some(sorted[rank - 1])
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::records::CheckFlag;
+
+    use super::*;
+
+    #[test]
+    fn test_aggregate_checks() {
+        let ip4: IpAddr = "1.1.1.1".parse().unwrap();
+        let ip6: IpAddr = "2606:4700:4700::1111".parse().unwrap();
+        let hour = Utc.with_ymd_and_hms(2024, 11, 9, 3, 0, 0).unwrap();
+        let next_hour = Utc.with_ymd_and_hms(2024, 11, 9, 4, 0, 0).unwrap();
+
+        let mut checks = Vec::new();
+        for (minute, lat) in (0..10).zip([10, 20, 30, 40, 50, 60, 70, 80, 90, 100]) {
+            let t = hour + chrono::Duration::minutes(minute);
+            checks.push(Check::new(
+                t,
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                Some(Duration::from_millis(lat)),
+                ip4,
+            ));
+            checks.push(Check::new(t, CheckFlag::TypeHTTP, None, ip6));
+        }
+        checks.push(Check::new(
+            next_hour,
+            CheckFlag::Success | CheckFlag::TypeHTTP,
+            Some(Duration::from_millis(5)),
+            ip4,
+        ));
+
+        let aggs = aggregate_checks(&checks);
+        assert_eq!(aggs.len(), 3);
+        assert_eq!(
+            aggs.iter().map(|a| a.total()).sum::<u32>() as usize,
+            checks.len()
+        );
+
+        let a = aggs[0];
+        assert_eq!(a.start(), hour.timestamp());
+        assert_eq!(a.target(), ip4);
+        assert_eq!(a.check_type(), CheckType::Http);
+        assert_eq!((a.successes(), a.failures()), (10, 0));
+        assert_eq!(a.latency_p50(), Some(50));
+        assert_eq!(a.latency_p90(), Some(90));
+        assert_eq!(a.latency_p99(), Some(100));
+
+        let b = aggs[1];
+        assert_eq!(b.target(), ip6);
+        assert_eq!((b.successes(), b.failures()), (0, 10));
+        assert_eq!(b.latency_p50(), None);
+
+        assert_eq!(aggs[2].start(), next_hour.timestamp());
+    }
+}
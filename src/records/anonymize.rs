@@ -0,0 +1,255 @@
+//! Export safe copies of [Checks](Check) that do not reveal the infrastructure they were made
+//! from.
+//!
+//! Checks, traces and outages are meant to be shared, for example when complaining to an ISP,
+//! but they contain the addresses of the targets and routers, the names of the targets and
+//! interfaces and the name of the probe. An [Anonymizer] replaces all of that:
+//!
+//! - Addresses are replaced by pseudonyms from ranges reserved for documentation and testing,
+//!   see [Anonymizer::address]. Local addresses stay local and public ones stay public, so the
+//!   [Segment](super::trace::Segment) of a [Trace](super::trace::Trace) does not change.
+//! - The probe is replaced by a pseudonym like `probe-3f2a9c1e`.
+//! - Labels, interfaces and zones are stripped.
+//!
+//! The same address always gets the same pseudonym from the same [Anonymizer], so outages can
+//! still be told apart by target. The pseudonyms are keyed hashes: an [Anonymizer::new] uses a
+//! random key that is never stored, an [Anonymizer::with_secret] derives its key from a secret,
+//! which gives the same pseudonyms for every export made with that secret.
+//!
+//! `netpulse --anonymize` applies this to the stores before printing anything, see
+//! [Store::anonymize](crate::store::Store::anonymize).
+//!
+//! # Example
+//!
+//! ```rust
+//! use netpulse::records::anonymize::Anonymizer;
+//!
+//! let anonymizer = Anonymizer::with_secret("my secret");
+//! let router = anonymizer.address("192.168.1.1".parse().unwrap());
+//! assert_ne!(router, "192.168.1.1".parse::<std::net::IpAddr>().unwrap());
+//! assert_eq!(router, anonymizer.address("192.168.1.1".parse().unwrap()));
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::trace::is_local;
+use super::Check;
+
+/// Context string for deriving the key of an [Anonymizer], see [blake3::derive_key]
+const KEY_CONTEXT: &str = "netpulse 2026-10-16 anonymize addresses and probes";
+
+/// Replaces addresses and names with pseudonyms, see the [module docs](self).
+#[derive(Clone)]
+pub struct Anonymizer {
+    /// Key of the [blake3] hash that makes the pseudonyms
+    key: [u8; blake3::KEY_LEN],
+}
+
+impl std::fmt::Debug for Anonymizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Anonymizer").finish_non_exhaustive()
+    }
+}
+
+impl Default for Anonymizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Anonymizer {
+    /// Creates an [Anonymizer] with a random key, so its pseudonyms can not be linked to those
+    /// of any other export.
+    pub fn new() -> Self {
+        let mut hasher = blake3::Hasher::new_derive_key(KEY_CONTEXT);
+        // the std hashers are seeded from the randomness of the operating system
+        for round in 0..4u8 {
+            let mut seed = RandomState::new().build_hasher();
+            seed.write_u8(round);
+            hasher.update(&seed.finish().to_le_bytes());
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        hasher.update(&now.as_nanos().to_le_bytes());
+        Self {
+            key: *hasher.finalize().as_bytes(),
+        }
+    }
+
+    /// Creates an [Anonymizer] whose key is derived from `secret`, so every export made with the
+    /// same secret uses the same pseudonyms.
+    pub fn with_secret(secret: &str) -> Self {
+        Self {
+            key: blake3::derive_key(KEY_CONTEXT, secret.as_bytes()),
+        }
+    }
+
+    /// Returns the keyed hash of `data`.
+    fn hash(&self, data: &[u8]) -> [u8; blake3::OUT_LEN] {
+        *blake3::keyed_hash(&self.key, data).as_bytes()
+    }
+
+    /// Returns the pseudonym of `addr`.
+    ///
+    /// Loopback and unspecified addresses say nothing about the network and are kept. Other
+    /// addresses are mapped into a range that is never routed, keeping whether they are
+    /// [local](super::trace::Segment::Lan):
+    ///
+    /// | Address      | Pseudonym                          |
+    /// |--------------|------------------------------------|
+    /// | local IPv4   | `10.0.0.0/8`, private              |
+    /// | public IPv4  | `198.18.0.0/15`, for benchmarking  |
+    /// | local IPv6   | `fd00::/8`, unique local           |
+    /// | public IPv6  | `2001:db8::/32`, for documentation |
+    pub fn address(&self, addr: IpAddr) -> IpAddr {
+        if addr.is_loopback() || addr.is_unspecified() {
+            return addr;
+        }
+        let hash = match addr {
+            IpAddr::V4(a) => self.hash(&a.octets()),
+            IpAddr::V6(a) => self.hash(&a.octets()),
+        };
+        match addr {
+            IpAddr::V4(_) => {
+                let bits = u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]);
+                let (prefix, len) = if is_local(addr) {
+                    (Ipv4Addr::new(10, 0, 0, 0), 8)
+                } else {
+                    (Ipv4Addr::new(198, 18, 0, 0), 15)
+                };
+                IpAddr::V4(Ipv4Addr::from(u32::from(prefix) | (bits >> len)))
+            }
+            IpAddr::V6(_) => {
+                let mut bits = [0; 16];
+                bits.copy_from_slice(&hash[..16]);
+                let bits = u128::from_be_bytes(bits);
+                let (prefix, len) = if is_local(addr) {
+                    (Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 0), 8)
+                } else {
+                    (Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 32)
+                };
+                IpAddr::V6(Ipv6Addr::from(u128::from(prefix) | (bits >> len)))
+            }
+        }
+    }
+
+    /// Returns the pseudonym of `name`, like `probe-3f2a9c1e` for the `kind` `probe`.
+    pub fn name(&self, kind: &str, name: &str) -> String {
+        let hash = self.hash(name.as_bytes());
+        format!("{kind}-{}", &blake3::Hash::from(hash).to_hex()[..8])
+    }
+
+    /// Anonymizes `check` in place.
+    ///
+    /// The target and probe are replaced by their pseudonyms, the label, interface and zone are
+    /// stripped. Everything else, like the time, type, result and latency, is kept.
+    pub fn check(&self, check: &mut Check) {
+        check.target = self.address(check.target);
+        check.probe = check.probe.as_deref().map(|p| self.name("probe", p));
+        check.label = None;
+        check.interface = None;
+        check.zone = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::records::trace::{Segment, Trace};
+    use crate::records::{CheckFlag, CheckType};
+
+    #[test]
+    fn test_address() {
+        let anonymizer = Anonymizer::with_secret("secret");
+        let addr = |raw: &str| anonymizer.address(raw.parse().unwrap());
+        let net = |raw: &str| raw.parse::<IpAddr>().unwrap();
+
+        assert_eq!(addr("192.168.1.1"), addr("192.168.1.1"));
+        assert_ne!(addr("192.168.1.1"), addr("192.168.1.2"));
+        assert_eq!(addr("127.0.0.1"), net("127.0.0.1"));
+        assert_eq!(addr("::1"), net("::1"));
+
+        let IpAddr::V4(local) = addr("192.168.1.1") else {
+            panic!("IPv4 became IPv6")
+        };
+        assert_eq!(local.octets()[0], 10);
+        let IpAddr::V4(public) = addr("1.1.1.1") else {
+            panic!("IPv4 became IPv6")
+        };
+        assert_eq!([public.octets()[0], public.octets()[1] & 0xfe], [198, 18]);
+        let IpAddr::V6(local) = addr("fe80::1") else {
+            panic!("IPv6 became IPv4")
+        };
+        assert_eq!(local.segments()[0] & 0xff00, 0xfd00);
+        let IpAddr::V6(public) = addr("2606:4700:4700::1111") else {
+            panic!("IPv6 became IPv4")
+        };
+        assert_eq!(public.segments()[..2], [0x2001, 0xdb8]);
+
+        // another key gives other pseudonyms
+        let other = Anonymizer::with_secret("other");
+        assert_ne!(addr("1.1.1.1"), other.address(net("1.1.1.1")));
+        assert_ne!(
+            Anonymizer::new().address(net("1.1.1.1")),
+            Anonymizer::new().address(net("1.1.1.1"))
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let anonymizer = Anonymizer::with_secret("secret");
+        let mut check = Check::new(
+            chrono::Utc::now(),
+            CheckFlag::Success | CheckFlag::TypeIcmp,
+            Some(std::time::Duration::from_millis(12)),
+            "fe80::1".parse().unwrap(),
+        );
+        check.set_label(Some("router".to_string()));
+        check.set_interface(Some("eth0".to_string()));
+        check.set_zone(Some("eth0".to_string()));
+        check.set_probe(Some("living-room".to_string()));
+        let original = check.clone();
+
+        anonymizer.check(&mut check);
+        assert_eq!(check.target(), anonymizer.address(original.target()));
+        assert_eq!(check.label(), None);
+        assert_eq!(check.interface(), None);
+        assert_eq!(check.zone(), None);
+        let probe = check.probe().unwrap();
+        assert!(probe.starts_with("probe-") && probe.len() == 14);
+        assert!(!probe.contains("living-room"));
+        assert_eq!(check.timestamp(), original.timestamp());
+        assert_eq!(check.calc_type().unwrap(), CheckType::Icmp);
+        assert_eq!(check.latency(), original.latency());
+    }
+
+    #[test]
+    fn test_trace_keeps_segment() {
+        let anonymizer = Anonymizer::new();
+        let hops = [
+            "192.168.1.1",
+            "100.64.0.1",
+            "203.0.113.1",
+            "203.0.113.2",
+            "9.9.9.9",
+        ];
+        let mut trace = Trace::new(
+            chrono::Utc::now(),
+            "1.1.1.1".parse().unwrap(),
+            hops.iter().map(|h| Some(h.parse().unwrap())).collect(),
+            false,
+        );
+        let segment = trace.segment();
+        trace.map_addresses(|a| anonymizer.address(a));
+        assert_eq!(trace.segment(), segment);
+        assert_eq!(segment, Segment::Beyond);
+        assert!(trace
+            .hops()
+            .iter()
+            .flatten()
+            .all(|hop| !hops.contains(&hop.to_string().as_str())));
+    }
+}
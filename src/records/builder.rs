@@ -0,0 +1,201 @@
+//! Building [Checks](Check) step by step, see [CheckBuilder].
+//!
+//! [Check::new] takes the flags as a [FlagSet], which is clumsy when writing fixtures or
+//! importing results from elsewhere. [CheckBuilder] sets the type, result and details of a
+//! [Check] with one method each.
+//!
+//! # Example
+//!
+//! ```rust
+//! use netpulse::records::{Check, CheckType};
+//!
+//! let check = Check::builder()
+//!     .target([1, 1, 1, 1])
+//!     .http()
+//!     .success()
+//!     .latency(12)
+//!     .at_timestamp(1_731_000_000)
+//!     .build();
+//!
+//! assert!(check.is_success());
+//! assert_eq!(check.calc_type().unwrap(), CheckType::Http);
+//! assert_eq!(check.latency(), Some(12));
+//! assert_eq!(check.timestamp(), 1_731_000_000);
+//! ```
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use flagset::FlagSet;
+
+use super::{Check, CheckFlag, CheckType, Measurement};
+
+/// Builder of a [Check], created with [Check::builder].
+///
+/// Unless set otherwise, the [Check] is made now, has failed and has no type. A target must be
+/// set before [building](CheckBuilder::build) it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CheckBuilder {
+    time: Option<DateTime<Utc>>,
+    flags: FlagSet<CheckFlag>,
+    latency: Option<Duration>,
+    target: Option<IpAddr>,
+    label: Option<String>,
+    http_status: Option<u16>,
+    measurement: Option<Measurement>,
+}
+
+impl CheckBuilder {
+    /// Sets the target address of the [Check].
+    pub fn target(mut self, target: impl Into<IpAddr>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Sets the label of the target, see [Check::label].
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Sets the time the [Check] was made at.
+    ///
+    /// Like with [Check::new], the seconds are cut off.
+    pub fn at(mut self, time: impl Into<DateTime<Utc>>) -> Self {
+        self.time = Some(time.into());
+        self
+    }
+
+    /// Sets the time the [Check] was made at as a unix timestamp in seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timestamp is out of the range of [DateTime].
+    pub fn at_timestamp(self, timestamp: i64) -> Self {
+        self.at(DateTime::from_timestamp(timestamp, 0).expect("timestamp is out of range"))
+    }
+
+    /// Sets the type of the [Check].
+    ///
+    /// [CheckType::Unknown] leaves the [Check] without a type.
+    pub fn check_type(mut self, check_type: CheckType) -> Self {
+        for other in CheckType::all().iter().filter_map(CheckType::flag) {
+            self.flags -= other;
+        }
+        if let Some(flag) = check_type.flag() {
+            self.flags |= flag;
+        }
+        self
+    }
+
+    /// Makes the [Check] an [HTTP check](CheckType::Http).
+    pub fn http(self) -> Self {
+        self.check_type(CheckType::Http)
+    }
+
+    /// Makes the [Check] an [ICMP check](CheckType::Icmp).
+    pub fn icmp(self) -> Self {
+        self.check_type(CheckType::Icmp)
+    }
+
+    /// Makes the [Check] a [DNS check](CheckType::Dns).
+    pub fn dns(self) -> Self {
+        self.check_type(CheckType::Dns)
+    }
+
+    /// Marks the [Check] as successful.
+    pub fn success(self) -> Self {
+        self.flag(CheckFlag::Success)
+    }
+
+    /// Marks the [Check] as failed because of `reason`, one of the
+    /// [REASONS](CheckFlag::REASONS) like [CheckFlag::Timeout].
+    pub fn failure(mut self, reason: CheckFlag) -> Self {
+        self.flags -= CheckFlag::Success;
+        self.flag(reason)
+    }
+
+    /// Adds any [CheckFlag], like [CheckFlag::Retried].
+    pub fn flag(mut self, flag: CheckFlag) -> Self {
+        self.flags |= flag;
+        self
+    }
+
+    /// Sets the latency in milliseconds.
+    pub fn latency(self, ms: u64) -> Self {
+        self.latency_duration(Duration::from_millis(ms))
+    }
+
+    /// Sets the latency, see [Check::set_latency].
+    pub fn latency_duration(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+
+    /// Sets the status code of the HTTP response, see [Check::http_status].
+    pub fn http_status(mut self, status: u16) -> Self {
+        self.http_status = Some(status);
+        self
+    }
+
+    /// Sets the [Measurement] of the [Check].
+    pub fn measurement(mut self, measurement: Measurement) -> Self {
+        self.measurement = Some(measurement);
+        self
+    }
+
+    /// Builds the [Check].
+    ///
+    /// # Panics
+    ///
+    /// Panics if no [target](CheckBuilder::target) was set.
+    pub fn build(self) -> Check {
+        let mut check = Check::new(
+            self.time.unwrap_or_else(Utc::now),
+            self.flags,
+            self.latency,
+            self.target.expect("a check needs a target"),
+        );
+        check.set_label(self.label);
+        check.set_http_status(self.http_status);
+        check.set_measurement(self.measurement);
+        check
+    }
+}
+
+impl Check {
+    /// Returns a [CheckBuilder] to build a [Check] step by step.
+    pub fn builder() -> CheckBuilder {
+        CheckBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let target: IpAddr = "2606:4700:4700::1111".parse().unwrap();
+        let check = Check::builder()
+            .target(target)
+            .label("cloudflare-dns")
+            .icmp()
+            .http()
+            .failure(CheckFlag::Timeout)
+            .flag(CheckFlag::Retried)
+            .at_timestamp(90)
+            .build();
+
+        let mut expected = Check::new(
+            DateTime::from_timestamp(60, 0).unwrap(),
+            CheckFlag::TypeHTTP | CheckFlag::Timeout | CheckFlag::Retried,
+            None,
+            target,
+        );
+        expected.set_label(Some("cloudflare-dns".to_string()));
+        assert_eq!(check, expected);
+        assert_eq!(check.failure_reason(), Some(CheckFlag::Timeout));
+    }
+}
@@ -0,0 +1,208 @@
+//! [Checks](Check) in the InfluxDB line protocol.
+//!
+//! To show netpulse data on existing dashboards, like those of Grafana, [Checks](Check) can be
+//! written as lines of the [InfluxDB line protocol], one line per [Check]:
+//!
+//! ```text
+//! netpulse,type=http,target=1.1.1.1,label=cloudflare-dns success=true,latency=12i,http_status=200i,flags=4097i 1731121200000000000
+//! ```
+//!
+//! The measurement is [MEASUREMENT]. The tags are the [type](CheckType::name) and the target of
+//! the [Check], and its label if it has one. The fields are whether it succeeded, its flags and
+//! whichever of the latency in milliseconds, the HTTP status and the values of its [Measurement]
+//! it has:
+//!
+//! - `sent` and `received` for a [Measurement::Loss]
+//! - `mtu` for a [Measurement::Mtu]
+//! - `ntp_offset` in milliseconds for a [Measurement::Ntp]
+//! - `bytes_per_second` for a [Measurement::Throughput]
+//! - `ttl` for a [Measurement::Ttl]
+//!
+//! The timestamp is in nanoseconds, the default precision of InfluxDB.
+//!
+//! `netpulse --influx` prints the [Checks](Check) of the store like this. With an
+//! [InfluxConfig], the daemon also writes the [Checks](Check) of every round to InfluxDB, see
+//! [send_lines]. Sending requires the `influx` feature.
+//!
+//! [InfluxDB line protocol]: https://docs.influxdata.com/influxdb/v2/reference/syntax/line-protocol/
+
+use std::fmt::Write as _;
+use std::io;
+
+use crate::config::InfluxConfig;
+#[cfg(feature = "influx")]
+use crate::errors::PushError;
+
+use super::{Check, CheckType, Measurement};
+
+/// Name of the measurement of all lines
+pub const MEASUREMENT: &str = "netpulse";
+
+/// Returns `check` as a line of the InfluxDB line protocol, without a line break.
+pub fn line(check: &Check) -> String {
+    let mut line = String::from(MEASUREMENT);
+    let check_type = check.calc_type().unwrap_or(CheckType::Unknown);
+    push_tag(&mut line, "type", check_type.name());
+    push_tag(&mut line, "target", &check.target_scoped());
+    if let Some(label) = check.label() {
+        push_tag(&mut line, "label", label);
+    }
+
+    let mut fields = vec![
+        ("success", check.is_success().to_string()),
+        ("flags", format!("{}i", check.flags().bits())),
+    ];
+    if let Some(latency) = check.latency() {
+        fields.push(("latency", format!("{latency}i")));
+    }
+    if let Some(status) = check.http_status() {
+        fields.push(("http_status", format!("{status}i")));
+    }
+    match check.measurement() {
+        Some(Measurement::Loss { sent, received }) => {
+            fields.push(("sent", format!("{sent}i")));
+            fields.push(("received", format!("{received}i")));
+        }
+        Some(Measurement::Mtu { size }) => fields.push(("mtu", format!("{size}i"))),
+        Some(Measurement::Ntp { offset }) => fields.push(("ntp_offset", format!("{offset}i"))),
+        Some(Measurement::Throughput { bytes_per_second }) => {
+            fields.push(("bytes_per_second", format!("{bytes_per_second}i")))
+        }
+        Some(Measurement::Ttl { ttl }) => fields.push(("ttl", format!("{ttl}i"))),
+        None => (),
+    }
+    for (idx, (key, value)) in fields.iter().enumerate() {
+        line.push(if idx == 0 { ' ' } else { ',' });
+        write!(line, "{key}={value}").expect("writing to a String can not fail");
+    }
+
+    write!(line, " {}", check.timestamp() * 1_000_000_000)
+        .expect("writing to a String can not fail");
+    line
+}
+
+/// Writes `checks` to `out`, one line each.
+///
+/// # Errors
+///
+/// Returns the [io::Error] of `out`.
+pub fn write_lines<'check>(
+    out: &mut impl io::Write,
+    checks: impl IntoIterator<Item = &'check Check>,
+) -> io::Result<()> {
+    for check in checks {
+        writeln!(out, "{}", line(check))?;
+    }
+    Ok(())
+}
+
+/// Writes `checks` to the InfluxDB write API at the [url](InfluxConfig::url) of `config`.
+///
+/// The URL must include the bucket and organization, like
+/// `http://localhost:8086/api/v2/write?org=home&bucket=netpulse`. The
+/// [token](InfluxConfig::token) is sent as `Authorization: Token <token>`.
+///
+/// # Errors
+///
+/// Returns [PushError] if the request failed or InfluxDB did not accept the lines.
+#[cfg(feature = "influx")]
+pub fn send_lines(config: &InfluxConfig, checks: &[&Check]) -> Result<(), PushError> {
+    let Some(url) = &config.url else {
+        return Ok(());
+    };
+    let mut body = Vec::new();
+    write_lines(&mut body, checks.iter().copied()).expect("writing to a Vec can not fail");
+
+    let headers: Vec<String> = config
+        .token
+        .iter()
+        .map(|token| format!("Authorization: Token {token}"))
+        .collect();
+    let status = crate::checks::post(url, "text/plain; charset=utf-8", &headers, &body)?;
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(PushError::BadStatus { status })
+    }
+}
+
+/// Writes `checks` to InfluxDB in a background thread, if an [url](InfluxConfig::url) is
+/// configured.
+///
+/// Failures are logged, the [Checks](Check) are not sent again.
+pub fn export(config: &InfluxConfig, checks: &[&Check]) {
+    if config.url.is_none() || checks.is_empty() {
+        return;
+    }
+    #[cfg(feature = "influx")]
+    {
+        let config = config.clone();
+        let checks: Vec<Check> = checks.iter().map(|c| (*c).clone()).collect();
+        std::thread::spawn(move || {
+            let refs: Vec<&Check> = checks.iter().collect();
+            if let Err(err) = send_lines(&config, &refs) {
+                tracing::error!("could not write {} checks to InfluxDB: {err}", checks.len());
+            }
+        });
+    }
+    #[cfg(not(feature = "influx"))]
+    tracing::warn!(
+        "an InfluxDB url is configured, but netpulse was built without the influx feature, not writing {} checks",
+        checks.len()
+    );
+}
+
+/// Appends ` ,key=value` to `line`, escaping the value as the line protocol requires.
+fn push_tag(line: &mut String, key: &str, value: &str) {
+    line.push(',');
+    line.push_str(key);
+    line.push('=');
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ') {
+            line.push('\\');
+        }
+        line.push(c);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::records::CheckFlag;
+
+    use super::*;
+
+    #[test]
+    fn test_line() {
+        let mut check = Check::new(
+            chrono::DateTime::from_timestamp(1731121200, 0).unwrap(),
+            CheckFlag::Success | CheckFlag::TypeHTTP,
+            Some(Duration::from_millis(12)),
+            "1.1.1.1".parse().unwrap(),
+        );
+        check.set_label(Some("cloudflare dns,1".to_string()));
+        check.set_http_status(Some(200));
+        assert_eq!(
+            line(&check),
+            "netpulse,type=http,target=1.1.1.1,label=cloudflare\\ dns\\,1 success=true,flags=4097i,latency=12i,http_status=200i 1731121200000000000"
+        );
+
+        let mut burst = Check::new(
+            chrono::DateTime::from_timestamp(1731121200, 0).unwrap(),
+            CheckFlag::TypeIcmp,
+            None,
+            "::1".parse().unwrap(),
+        );
+        burst.set_measurement(Some(Measurement::Loss {
+            sent: 5,
+            received: 0,
+        }));
+        let mut out = Vec::new();
+        write_lines(&mut out, [&burst]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "netpulse,type=icmp,target=::1 success=false,flags=16384i,sent=5i,received=0i 1731121200000000000\n"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Outages that are kept in the store, so they do not have to be found again, see [OutageLog].
+//!
+//! Finding the outages means walking all [Checks](Check) of a [Store](crate::store::Store). The
+//! daemon does that once, for the new [Checks](Check) only, whenever it saves the store with
+//! [Store::update_outages](crate::store::Store::update_outages). Listing the outages then only
+//! reads the [OutageRecords](OutageRecord), and an outage that still goes on can be told apart,
+//! see [OutageRecord::is_ongoing].
+//!
+//! # Example
+//!
+//! ```rust
+//! use netpulse::records::outage::OutageLog;
+//! use netpulse::records::{Check, CheckFlag};
+//!
+//! let round = |time: i64, ok: bool| {
+//!     let builder = Check::builder().target([1, 1, 1, 1]).http().at_timestamp(time);
+//!     if ok {
+//!         builder.success().build()
+//!     } else {
+//!         builder.failure(CheckFlag::Timeout).build()
+//!     }
+//! };
+//! let checks = [round(0, true), round(60, false), round(120, false)];
+//!
+//! let mut log = OutageLog::default();
+//! log.update(&checks.iter().collect::<Vec<_>>());
+//! assert_eq!(log.records().len(), 1);
+//! assert!(log.ongoing().is_some());
+//!
+//! // the ongoing outage is looked at again, with the new round
+//! assert_eq!(log.resume_from(), 60);
+//! let later = [round(180, true)];
+//! log.update(&checks[1..].iter().chain(&later).collect::<Vec<_>>());
+//! assert_eq!(log.records().len(), 1);
+//! assert!(log.ongoing().is_none());
+//! assert_eq!(log.records()[0].end(), 120);
+//! ```
+
+use std::net::IpAddr;
+
+use deepsize::DeepSizeOf;
+use serde::{Deserialize, Serialize};
+
+use crate::analyze::outage::{Family, Location, Outage, OutageSummary, Severity};
+use crate::analyze::{fail_groups, is_healthy};
+
+use super::{Check, CheckType};
+
+/// An outage as it is kept in the [OutageLog].
+///
+/// Unlike an [Outage], it does not hold the [Checks](Check), only what is needed to summarize
+/// it, see [OutageRecord::to_summary]. Timestamps are unix timestamps in seconds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct OutageRecord {
+    /// Time of the first check of the outage
+    start: i64,
+    /// Time of the last check of the outage
+    end: i64,
+    /// Number of checks in the outage
+    checks: u32,
+    /// Number of failed checks in the outage
+    failed: u32,
+    /// Number of successful but [degraded](Check::is_degraded) checks in the outage
+    degraded: u32,
+    /// Types of the failed or degraded checks, sorted
+    check_types: Vec<CheckType>,
+    /// Targets of the failed or degraded checks, sorted
+    targets: Vec<IpAddr>,
+    /// Where the cause of the outage was, if it could be told
+    location: Option<Location>,
+    /// Which IP versions the outage affected, if it could be told
+    family: Option<Family>,
+    /// Whether the last round of checks was still part of the outage
+    ongoing: bool,
+}
+
+impl DeepSizeOf for OutageRecord {
+    fn deep_size_of_children(&self, _context: &mut deepsize::Context) -> usize {
+        self.check_types.capacity() * std::mem::size_of::<CheckType>()
+            + self.targets.capacity() * std::mem::size_of::<IpAddr>()
+    }
+}
+
+impl OutageRecord {
+    /// Records an [Outage], which is `ongoing` if it lasted until the last round of checks.
+    pub fn new(outage: &Outage, ongoing: bool) -> Self {
+        let summary = outage.to_summary();
+        let count = |f: fn(&Check) -> bool| outage.all().iter().filter(|c| f(c)).count() as u32;
+        Self {
+            start: summary.start,
+            end: summary.end,
+            checks: summary.checks as u32,
+            failed: count(|c| !c.is_success()),
+            degraded: count(|c| c.is_success() && c.is_degraded()),
+            check_types: summary.check_types,
+            targets: summary.targets,
+            location: summary.location,
+            family: summary.family,
+            ongoing,
+        }
+    }
+
+    /// Returns the time of the first check of the outage.
+    pub fn start(&self) -> i64 {
+        self.start
+    }
+
+    /// Returns the time of the last check of the outage.
+    ///
+    /// For an [ongoing](Self::is_ongoing) outage, this is the last round of checks so far.
+    pub fn end(&self) -> i64 {
+        self.end
+    }
+
+    /// Returns true if the outage lasted until the last round of checks when it was recorded.
+    pub fn is_ongoing(&self) -> bool {
+        self.ongoing
+    }
+
+    /// Replaces every target with `f` of it, keeping them sorted, see
+    /// [anonymize](super::anonymize).
+    pub(crate) fn map_targets(&mut self, f: impl Fn(IpAddr) -> IpAddr) {
+        self.targets.iter_mut().for_each(|t| *t = f(*t));
+        self.targets.sort();
+        self.targets.dedup();
+    }
+
+    /// Calculates the [Severity] of the outage, like [Outage::severity].
+    pub fn severity(&self) -> Severity {
+        Severity::of_counts(
+            self.checks as usize,
+            self.failed as usize,
+            self.degraded as usize,
+        )
+    }
+
+    /// Summarizes the outage as an [OutageSummary], like [Outage::to_summary].
+    pub fn to_summary(&self) -> OutageSummary {
+        OutageSummary {
+            start: self.start,
+            end: self.end,
+            duration: self.end - self.start,
+            checks: self.checks as usize,
+            severity: self.severity(),
+            check_types: self.check_types.clone(),
+            targets: self.targets.clone(),
+            location: self.location,
+            family: self.family,
+            ongoing: self.ongoing,
+        }
+    }
+}
+
+/// The outages of a [Store](crate::store::Store), oldest first, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+pub struct OutageLog {
+    /// Outages found so far, oldest first
+    records: Vec<OutageRecord>,
+    /// Time of the last round of checks that was looked at, [None] if none was
+    until: Option<i64>,
+}
+
+impl DeepSizeOf for OutageLog {
+    fn deep_size_of_children(&self, context: &mut deepsize::Context) -> usize {
+        self.records.deep_size_of_children(context)
+    }
+}
+
+impl OutageLog {
+    /// Returns the recorded outages, oldest first.
+    pub fn records(&self) -> &[OutageRecord] {
+        &self.records
+    }
+
+    /// Replaces every target of every outage with `f` of it, see
+    /// [anonymize](super::anonymize).
+    pub(crate) fn map_targets(&mut self, f: impl Fn(IpAddr) -> IpAddr) {
+        self.records.iter_mut().for_each(|r| r.map_targets(&f));
+    }
+
+    /// Returns the time of the last round of checks that was looked at, or [None] if the log
+    /// was never updated.
+    pub fn until(&self) -> Option<i64> {
+        self.until
+    }
+
+    /// Returns the outage that lasted until the last round of checks, if there is one.
+    pub fn ongoing(&self) -> Option<&OutageRecord> {
+        self.records.last().filter(|r| r.is_ongoing())
+    }
+
+    /// Returns the time of the first round of checks that [OutageLog::update] needs.
+    ///
+    /// That is the start of the [ongoing](Self::ongoing) outage, which might go on, or the
+    /// first round after [until](Self::until) otherwise.
+    pub fn resume_from(&self) -> i64 {
+        match (self.ongoing(), self.until) {
+            (Some(ongoing), _) => ongoing.start(),
+            (None, Some(until)) => until + 1,
+            (None, None) => i64::MIN,
+        }
+    }
+
+    /// Records the outages in `checks`, which must be all checks from
+    /// [resume_from](Self::resume_from) on, sorted by time.
+    ///
+    /// The [ongoing](Self::ongoing) outage is replaced, as it might have gone on or ended since.
+    pub fn update(&mut self, checks: &[&Check]) {
+        let Some(last) = checks.last() else {
+            return;
+        };
+        if self.ongoing().is_some() {
+            self.records.pop();
+        }
+        let last_round_failed = checks
+            .iter()
+            .rev()
+            .take_while(|c| c.timestamp() == last.timestamp())
+            .any(|c| !is_healthy(c));
+        let groups = fail_groups(checks);
+        let count = groups.len();
+        for (idx, group) in groups.into_iter().enumerate() {
+            let outage = Outage::try_from(group).expect("check fail group was empty");
+            self.records.push(OutageRecord::new(
+                &outage,
+                last_round_failed && idx + 1 == count,
+            ));
+        }
+        self.until = Some(last.timestamp());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::records::CheckFlag;
+
+    fn round(time: i64, ok: bool) -> Vec<Check> {
+        ["1.1.1.1", "2606:4700:4700::1111"]
+            .iter()
+            .map(|target| {
+                let builder = Check::builder()
+                    .target(target.parse::<IpAddr>().unwrap())
+                    .icmp()
+                    .at_timestamp(time);
+                if ok {
+                    builder.success().latency(10).build()
+                } else {
+                    builder.failure(CheckFlag::Unreachable).build()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_update_in_steps() {
+        let pattern = [true, false, false, true, true, false, true, false, false];
+        let checks: Vec<Check> = pattern
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, ok)| round(idx as i64 * 60, *ok))
+            .collect();
+        let refs: Vec<&Check> = checks.iter().collect();
+
+        let mut whole = OutageLog::default();
+        whole.update(&refs);
+        assert_eq!(whole.records().len(), 3);
+        assert_eq!(whole.until(), Some(8 * 60));
+        let ongoing = whole.ongoing().unwrap();
+        assert_eq!((ongoing.start(), ongoing.end()), (7 * 60, 8 * 60));
+        assert_eq!(ongoing.severity(), Severity::Complete);
+
+        // one round at a time, like the daemon does
+        let mut stepwise = OutageLog::default();
+        for chunk in refs.chunks(2) {
+            let from = stepwise.resume_from();
+            let since: Vec<&Check> = refs
+                .iter()
+                .copied()
+                .filter(|c| c.timestamp() >= from)
+                .take_while(|c| c.timestamp() <= chunk[0].timestamp())
+                .collect();
+            stepwise.update(&since);
+        }
+        assert_eq!(stepwise, whole);
+
+        let mut ended = whole.clone();
+        let next = round(9 * 60, true);
+        let from = ended.resume_from();
+        let since: Vec<&Check> = refs
+            .iter()
+            .copied()
+            .chain(next.iter())
+            .filter(|c| c.timestamp() >= from)
+            .collect();
+        ended.update(&since);
+        assert!(ended.ongoing().is_none());
+        assert_eq!(ended.records().len(), 3);
+        assert_eq!(ended.records()[2].end(), 8 * 60);
+        assert!(!ended.records()[2].to_summary().ongoing);
+    }
+}
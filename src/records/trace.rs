@@ -0,0 +1,242 @@
+//! Traces of the path to targets whose checks failed.
+//!
+//! When a check fails, the daemon can trace the path to its target, see
+//! [TraceConfig](crate::config::TraceConfig). A [Trace] keeps every hop of that path, so a
+//! post-mortem analysis can tell where the path died: in the local network, at the ISP, or
+//! somewhere beyond, see [Segment].
+//!
+//! # Example
+//!
+//! ```rust
+//! use netpulse::records::trace::{Segment, Trace};
+//!
+//! let hops = vec![Some("192.168.1.1".parse().unwrap()), Some("203.0.113.1".parse().unwrap()), None];
+//! let trace = Trace::new(std::time::SystemTime::now(), "1.1.1.1".parse().unwrap(), hops, false);
+//! assert_eq!(trace.last_hop(), Some((2, "203.0.113.1".parse().unwrap())));
+//! assert_eq!(trace.segment(), Segment::Isp);
+//! ```
+
+use std::fmt::Display;
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use deepsize::DeepSizeOf;
+use serde::{Deserialize, Serialize};
+
+use crate::analyze::fmt_timestamp;
+
+/// How many public hops after the local network are considered part of the ISP
+pub const ISP_HOPS: usize = 3;
+
+/// Where the path to a target died, see [Trace::segment].
+///
+/// This is a heuristic: the local network ends at the last hop with a private address, and the
+/// first [ISP_HOPS] public hops after it are assumed to belong to the ISP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Segment {
+    /// No hop outside of the local network answered
+    Lan,
+    /// The path died shortly after the local network
+    Isp,
+    /// The path died further away
+    Beyond,
+    /// The target itself answered, so the path was fine
+    Target,
+}
+
+/// The path to a target at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct Trace {
+    /// Unix timestamp of when the trace was made
+    timestamp: i64,
+    /// Address the path leads to
+    target: IpAddr,
+    /// Address of the router that answered at each hop, [None] if none did
+    hops: Vec<Option<IpAddr>>,
+    /// Whether the target itself answered
+    reached: bool,
+}
+
+impl DeepSizeOf for Trace {
+    fn deep_size_of_children(&self, _context: &mut deepsize::Context) -> usize {
+        self.hops.capacity() * std::mem::size_of::<Option<IpAddr>>()
+    }
+}
+
+impl Trace {
+    /// Creates a [Trace] from the hops of a path.
+    ///
+    /// The first hop is the first router on the path. If `reached` is true, the last hop is the
+    /// target.
+    pub fn new(
+        time: impl Into<DateTime<Utc>>,
+        target: IpAddr,
+        hops: Vec<Option<IpAddr>>,
+        reached: bool,
+    ) -> Self {
+        Self {
+            timestamp: time.into().timestamp(),
+            target,
+            hops,
+            reached,
+        }
+    }
+
+    /// Returns the unix timestamp of when this [`Trace`] was made.
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    /// Returns the time this [`Trace`] was made in the timezone of the user.
+    pub fn timestamp_parsed(&self) -> DateTime<Local> {
+        Local.timestamp_opt(self.timestamp, 0).unwrap()
+    }
+
+    /// Returns the address the path leads to.
+    pub fn target(&self) -> IpAddr {
+        self.target
+    }
+
+    /// Returns the address of the router that answered at each hop, [None] if none did.
+    pub fn hops(&self) -> &[Option<IpAddr>] {
+        &self.hops
+    }
+
+    /// Returns whether the target itself answered.
+    pub fn reached(&self) -> bool {
+        self.reached
+    }
+
+    /// Replaces the target and the address of every hop with `f` of it, see
+    /// [anonymize](super::anonymize).
+    pub(crate) fn map_addresses(&mut self, f: impl Fn(IpAddr) -> IpAddr) {
+        self.target = f(self.target);
+        self.hops
+            .iter_mut()
+            .flatten()
+            .for_each(|hop| *hop = f(*hop));
+    }
+
+    /// Returns the number and address of the last hop that answered, counting from 1.
+    pub fn last_hop(&self) -> Option<(usize, IpAddr)> {
+        self.hops
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(idx, hop)| hop.map(|addr| (idx + 1, addr)))
+    }
+
+    /// Returns where the path died, see [Segment].
+    pub fn segment(&self) -> Segment {
+        if self.reached {
+            return Segment::Target;
+        }
+        let Some((last, addr)) = self.last_hop() else {
+            return Segment::Lan;
+        };
+        if is_local(addr) {
+            return Segment::Lan;
+        }
+        // hops are counted from 1, so this is the number of the first public hop
+        let first_public = self
+            .hops
+            .iter()
+            .position(|hop| hop.is_some_and(|a| !is_local(a)))
+            .map_or(last, |idx| idx + 1);
+        if last - first_public < ISP_HOPS {
+            Segment::Isp
+        } else {
+            Segment::Beyond
+        }
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Time: {}\nTarget: {}\nReached: {}",
+            fmt_timestamp(self.timestamp_parsed()),
+            self.target,
+            self.reached
+        )?;
+        for (idx, hop) in self.hops.iter().enumerate() {
+            match hop {
+                Some(addr) => write!(f, "\n{:>2}: {addr}", idx + 1)?,
+                None => write!(f, "\n{:>2}: *", idx + 1)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Lan => "local network",
+                Self::Isp => "ISP",
+                Self::Beyond => "beyond the ISP",
+                Self::Target => "target reached",
+            }
+        )
+    }
+}
+
+/// Returns true if `addr` can only be part of a local network
+pub(crate) fn is_local(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(a) => a.is_private() || a.is_link_local() || a.is_loopback(),
+        // unique local (fc00::/7) and link local (fe80::/10) addresses
+        IpAddr::V6(a) => {
+            a.is_loopback()
+                || (a.segments()[0] & 0xfe00) == 0xfc00
+                || (a.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trace(hops: &[Option<&str>], reached: bool) -> Trace {
+        Trace::new(
+            DateTime::from_timestamp(0, 0).unwrap(),
+            "1.1.1.1".parse().unwrap(),
+            hops.iter().map(|h| h.map(|a| a.parse().unwrap())).collect(),
+            reached,
+        )
+    }
+
+    #[test]
+    fn test_segment() {
+        assert_eq!(trace(&[], false).segment(), Segment::Lan);
+        assert_eq!(trace(&[Some("192.168.1.1")], false).segment(), Segment::Lan);
+        assert_eq!(
+            trace(&[Some("192.168.1.1"), None, Some("203.0.113.1")], false).segment(),
+            Segment::Isp
+        );
+        assert_eq!(
+            trace(
+                &[
+                    Some("192.168.1.1"),
+                    Some("203.0.113.1"),
+                    Some("203.0.113.2"),
+                    Some("198.51.100.1"),
+                    Some("198.51.100.2"),
+                ],
+                false
+            )
+            .segment(),
+            Segment::Beyond
+        );
+        assert_eq!(
+            trace(&[Some("192.168.1.1"), Some("1.1.1.1")], true).segment(),
+            Segment::Target
+        );
+    }
+}
@@ -0,0 +1,760 @@
+//! Central server that collects the checks of many probes.
+//!
+//! Daemons on several machines can [push](crate::push) their [Checks](crate::records::Check) to
+//! one `netpulse-server`. The [Server] keeps a [Store] for every probe, named after the probe, in
+//! its directory and serves the analysis of all of them, so a small fleet of probes can be
+//! watched from one place.
+//!
+//! # HTTP API
+//!
+//! - `POST /push`: adds the [Checks](crate::records::Check) of a [Batch] to the store of its
+//!   probe, duplicates are skipped
+//! - `GET /probes`: the names of all probes as a JSON array
+//! - `GET /report`: the analysis of all probes as text, like `netpulse --store A --store B`
+//! - `GET /report/<probe>`: the analysis of a single probe as text
+//! - `GET /report.json`: the [Reports](Report) of all probes as a JSON object keyed by probe
+//!
+//! If the server has a token, every request must send it in the `Authorization: Bearer <token>`
+//! header, see [ENV_SERVER_TOKEN].
+//!
+//! At most [MAX_CONNECTIONS] requests are answered at the same time, further connections get a
+//! `503`. Each request must be sent completely within [REQUEST_DEADLINE].
+//!
+//! Requires the `server` feature.
+
+#[cfg(feature = "grafana")]
+pub mod grafana;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use tracing::{error, info, warn};
+
+use crate::analyze::{self, report::Report};
+use crate::config::Config;
+use crate::errors::StoreError;
+use crate::push::Batch;
+use crate::store::{FileBackend, Store, StoreBackend};
+use crate::TIMEOUT;
+
+/// Address the server listens on by default
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8930";
+/// Directory of the stores of the probes by default
+pub const DEFAULT_DIR: &str = "/var/lib/netpulse/probes";
+/// Environment variable with the token that clients must send, see the [module docs](self)
+pub const ENV_SERVER_TOKEN: &str = "NETPULSE_SERVER_TOKEN";
+/// Largest request body the server accepts, in bytes
+pub const MAX_BODY: usize = 64 * 1024 * 1024;
+/// Largest request head the server accepts, in bytes
+const MAX_HEAD: usize = 16 * 1024;
+/// Most requests that are answered at the same time, see the [module docs](self)
+pub const MAX_CONNECTIONS: usize = 64;
+/// Time in which a request must be read and answered completely
+///
+/// Reading and writing also time out after [TIMEOUT] without progress, but a client that
+/// sends a byte now and then would keep the connection open forever without this.
+pub const REQUEST_DEADLINE: Duration = Duration::from_secs(120);
+/// Extension of the store files of the probes
+const STORE_EXTENSION: &str = "store";
+
+/// An answer of the [Server]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// HTTP status code
+    pub status: u16,
+    /// Value of the `Content-Type` header
+    pub content_type: &'static str,
+    /// The body
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    fn text(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into().into_bytes(),
+        }
+    }
+
+    fn json(value: &impl serde::Serialize) -> Self {
+        match serde_json::to_vec_pretty(value) {
+            Ok(body) => Self {
+                status: 200,
+                content_type: "application/json",
+                body,
+            },
+            Err(e) => Self::text(500, format!("could not serialize the answer: {e}")),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            204 => "No Content",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> std::io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+/// Collects the [Checks](crate::records::Check) of many probes, see the [module docs](self).
+#[derive(Debug)]
+pub struct Server {
+    dir: PathBuf,
+    token: Option<String>,
+    /// Ranks the outages and measures the SLA in the reports
+    config: Config,
+    /// The stores of the probes, sorted by name
+    ///
+    /// Every store has its own lock, so saving the store of one probe does not hold up the
+    /// others.
+    probes: Mutex<Vec<(String, Arc<Mutex<Store>>)>>,
+}
+
+impl Server {
+    /// Opens the stores of all probes in `dir`, creating the directory if needed.
+    ///
+    /// If `token` is set, every request must send it, see the [module docs](self). The reports
+    /// rank the outages and measure the SLA with `config`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the directory or a store in it can not be read.
+    pub fn open(dir: &Path, token: Option<String>, config: Config) -> Result<Self, StoreError> {
+        fs::create_dir_all(dir)?;
+        let mut probes = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| e != STORE_EXTENSION) {
+                continue;
+            }
+            let Some(probe) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let mut store = FileBackend::new(&path).load()?;
+            store.set_reject_duplicates(true);
+            info!("loaded {} checks of probe {probe}", store.checks().len());
+            probes.push((probe.to_string(), Arc::new(Mutex::new(store))));
+        }
+        probes.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            token,
+            config,
+            probes: Mutex::new(probes),
+        })
+    }
+
+    /// Returns the names of all probes that pushed checks so far.
+    pub fn probes(&self) -> Vec<String> {
+        self.lock().iter().map(|(probe, _)| probe.clone()).collect()
+    }
+
+    /// Adds the [Checks](crate::records::Check) of `batch` to the store of its probe and saves
+    /// it, creating the store for a new probe.
+    ///
+    /// Returns the number of added [Checks](crate::records::Check), duplicates are skipped.
+    /// [Checks](crate::records::Check) that did not record their
+    /// [probe](crate::records::Check::probe) get the one of the [Batch].
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::BadProbe] if the name of the probe is not [valid](is_valid_probe), or
+    /// another [StoreError] if the store could not be created or saved. The added checks stay
+    /// in memory and are saved with the next push of the probe then.
+    pub fn push(&self, batch: Batch) -> Result<usize, StoreError> {
+        if !is_valid_probe(&batch.probe) {
+            return Err(StoreError::BadProbe(batch.probe));
+        }
+        let mut backend = FileBackend::new(self.store_path(&batch.probe));
+        let store = match self.store(&batch.probe) {
+            Some(store) => store,
+            None => {
+                // create the store without holding up the other probes
+                let mut store = Store::load_or_create_with(&mut backend)?;
+                store.set_reject_duplicates(true);
+                let mut probes = self.lock();
+                match probes.binary_search_by(|(probe, _)| probe.cmp(&batch.probe)) {
+                    // another push of the same probe was faster
+                    Ok(idx) => Arc::clone(&probes[idx].1),
+                    Err(idx) => {
+                        info!("new probe {}", batch.probe);
+                        let store = Arc::new(Mutex::new(store));
+                        probes.insert(idx, (batch.probe.clone(), Arc::clone(&store)));
+                        store
+                    }
+                }
+            }
+        };
+        let mut store = lock_store(&store);
+        let added = batch
+            .checks
+            .into_iter()
+            .filter_map(|mut check| {
+                // daemons before the probe was recorded with the checks
+                if check.probe().is_none() {
+                    check.set_probe(Some(batch.probe.clone()));
+                }
+                store.add_check(check).then_some(())
+            })
+            .count();
+        store.save_to(&mut backend)?;
+        Ok(added)
+    }
+
+    /// Returns the analysis of `probe` as text, or of all probes if it is [None].
+    ///
+    /// Returns [None] if there is no such probe.
+    ///
+    /// # Errors
+    ///
+    /// Returns [AnalysisError](crate::errors::AnalysisError) if the analysis failed.
+    pub fn report(
+        &self,
+        probe: Option<&str>,
+    ) -> Result<Option<String>, crate::errors::AnalysisError> {
+        match probe {
+            Some(probe) => self
+                .store(probe)
+                .map(|store| {
+                    analyze::analyze_with_limit(
+                        &lock_store(&store),
+                        Some(analyze::OUTAGE_LIMIT),
+                        &self.config,
+                    )
+                })
+                .transpose(),
+            None => {
+                let probes = self.stores();
+                let locked: Vec<_> = probes
+                    .iter()
+                    .map(|(probe, store)| (probe.clone(), lock_store(store)))
+                    .collect();
+                let stores: Vec<(String, &Store)> = locked
+                    .iter()
+                    .map(|(probe, store)| (probe.clone(), &**store))
+                    .collect();
+                analyze::analyze_hosts_with_limit(
+                    &stores,
+                    Some(analyze::OUTAGE_LIMIT),
+                    &self.config,
+                )
+                .map(Some)
+            }
+        }
+    }
+
+    /// Accepts connections on `listener` forever, answering each in its own thread.
+    pub fn serve(self: Arc<Self>, listener: TcpListener) {
+        listen(listener, self.token.clone(), move |request| {
+            self.answer(
+                &request.method,
+                &request.path,
+                request.authorization.as_deref(),
+                &request.body,
+            )
+        });
+    }
+
+    /// Answers a request, see the [module docs](self) for the API.
+    pub fn answer(
+        &self,
+        method: &str,
+        path: &str,
+        authorization: Option<&str>,
+        body: &[u8],
+    ) -> HttpResponse {
+        if !is_authorized(self.token.as_deref(), authorization) {
+            return HttpResponse::text(401, "missing or wrong token");
+        }
+        let path = path.split('?').next().unwrap_or_default();
+        match (method, path) {
+            ("POST", "/push") => {
+                let batch: Batch = match serde_json::from_slice(body) {
+                    Ok(batch) => batch,
+                    Err(e) => return HttpResponse::text(400, format!("bad batch: {e}")),
+                };
+                let probe = batch.probe.clone();
+                match self.push(batch) {
+                    Ok(added) => {
+                        info!("added {added} checks of probe {probe}");
+                        HttpResponse::text(204, "")
+                    }
+                    Err(StoreError::BadProbe(_)) => HttpResponse::text(400, "bad probe name"),
+                    Err(e) => {
+                        error!("could not store the checks of probe {probe}: {e}");
+                        HttpResponse::text(500, "could not store the checks")
+                    }
+                }
+            }
+            ("GET", "/probes") => HttpResponse::json(&self.probes()),
+            ("GET", "/report") => self.report_response(None),
+            ("GET", "/report.json") => {
+                let probes = self.stores();
+                let reports: Result<BTreeMap<&String, Report>, _> = probes
+                    .iter()
+                    .map(|(probe, store)| {
+                        Report::build_with(&lock_store(store), &self.config).map(|r| (probe, r))
+                    })
+                    .collect();
+                match reports {
+                    Ok(reports) => HttpResponse::json(&reports),
+                    Err(e) => HttpResponse::text(500, format!("analysis failed: {e}")),
+                }
+            }
+            ("GET", path) if path.starts_with("/report/") => {
+                self.report_response(Some(&path["/report/".len()..]))
+            }
+            (_, "/push" | "/probes" | "/report" | "/report.json") => {
+                HttpResponse::text(405, "method not allowed")
+            }
+            _ => HttpResponse::text(404, "not found"),
+        }
+    }
+
+    fn report_response(&self, probe: Option<&str>) -> HttpResponse {
+        match self.report(probe) {
+            Ok(Some(report)) => HttpResponse::text(200, report),
+            Ok(None) => HttpResponse::text(404, "no such probe"),
+            Err(e) => HttpResponse::text(500, format!("analysis failed: {e}")),
+        }
+    }
+
+    fn store_path(&self, probe: &str) -> PathBuf {
+        self.dir.join(format!("{probe}.{STORE_EXTENSION}"))
+    }
+
+    /// Returns the store of `probe`, if there is one.
+    fn store(&self, probe: &str) -> Option<Arc<Mutex<Store>>> {
+        let probes = self.lock();
+        probes
+            .binary_search_by(|(name, _)| name.as_str().cmp(probe))
+            .ok()
+            .map(|idx| Arc::clone(&probes[idx].1))
+    }
+
+    /// Returns the stores of all probes, without keeping the list of probes locked.
+    fn stores(&self) -> Vec<(String, Arc<Mutex<Store>>)> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<(String, Arc<Mutex<Store>>)>> {
+        // a panic while holding the lock leaves the probes as they are, which is fine to use
+        self.probes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn lock_store(store: &Mutex<Store>) -> MutexGuard<'_, Store> {
+    // a panic while holding the lock leaves the store as it is, which is fine to use
+    store.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns true if `probe` can be used as a name of a probe.
+///
+/// The name becomes the name of the store file, so only ASCII letters, digits, `-`, `_` and `.`
+/// are allowed, and it must not start with a `.`.
+pub fn is_valid_probe(probe: &str) -> bool {
+    !probe.is_empty()
+        && probe.len() <= 64
+        && !probe.starts_with('.')
+        && probe
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Returns true if `authorization`, the value of the `Authorization` header, has the `token`.
+///
+/// Without a token, every request is authorized.
+fn is_authorized(token: Option<&str>, authorization: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    authorization
+        .and_then(|a| a.strip_prefix("Bearer "))
+        .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+}
+
+/// Compares two byte strings in a time that does not depend on where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Accepts connections on `listener` forever, answering each request with `answer` in its own
+/// thread.
+///
+/// If `token` is set, requests without it are answered with `401` before their body is read.
+/// Connections beyond [MAX_CONNECTIONS] are answered with `503` right away.
+fn listen(
+    listener: TcpListener,
+    token: Option<String>,
+    answer: impl Fn(Request) -> HttpResponse + Send + Sync + 'static,
+) {
+    let token: Option<Arc<str>> = token.map(Arc::from);
+    let answer = Arc::new(answer);
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("could not accept a connection: {e}");
+                continue;
+            }
+        };
+        let Some(slot) = Slot::take(&active, MAX_CONNECTIONS) else {
+            warn!("refusing a connection, {MAX_CONNECTIONS} requests are already being answered");
+            // the answer fits into the send buffer, never wait for the client here
+            if stream.set_nonblocking(true).is_ok() {
+                let _ = HttpResponse::text(503, "too many connections").write_to(&mut stream);
+            }
+            continue;
+        };
+        let answer = Arc::clone(&answer);
+        let token = token.clone();
+        std::thread::spawn(move || {
+            let _slot = slot;
+            if let Err(e) = handle(stream, token.as_deref(), &*answer, REQUEST_DEADLINE) {
+                warn!("error while answering a request: {e}");
+            }
+        });
+    }
+}
+
+/// A connection counted in the number of active connections, until it is dropped
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    /// Counts a connection, unless there are `max` active connections already.
+    fn take(active: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self(Arc::clone(active)))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Reads one request from `stream` and answers it, giving up after `deadline`.
+fn handle(
+    stream: TcpStream,
+    token: Option<&str>,
+    answer: &impl Fn(Request) -> HttpResponse,
+    deadline: Duration,
+) -> std::io::Result<()> {
+    let mut stream = DeadlineStream {
+        stream,
+        deadline: Instant::now() + deadline,
+    };
+    let response = match read_request(&mut stream, token)? {
+        Ok(request) => answer(request),
+        Err(response) => response,
+    };
+    response.write_to(&mut stream)
+}
+
+/// A [TcpStream] that fails every read and write after a deadline
+///
+/// Each read and write also times out after [TIMEOUT] without progress.
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl DeadlineStream {
+    /// Returns how long a read or write may take, or an error if the deadline has passed.
+    fn remaining(&self) -> std::io::Result<Duration> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "the request took too long",
+            ));
+        }
+        Ok(remaining.min(TIMEOUT))
+    }
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let timeout = self.remaining()?;
+        self.stream.set_read_timeout(Some(timeout))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for DeadlineStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let timeout = self.remaining()?;
+        self.stream.set_write_timeout(Some(timeout))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// The parts of an HTTP request the [Server] needs
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    body: Vec<u8>,
+}
+
+/// Reads an HTTP request from `stream`.
+///
+/// If `token` is set, the `Authorization` header is checked before the body is read. Returns the
+/// [HttpResponse] to answer with if the request is unauthorized, malformed or too large.
+fn read_request(
+    stream: &mut impl Read,
+    token: Option<&str>,
+) -> std::io::Result<Result<Request, HttpResponse>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let (mut request, head_len, content_length) = loop {
+        let n = stream.read(&mut chunk)?;
+        if n == 0 {
+            return Ok(Err(HttpResponse::text(400, "incomplete request")));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        let mut headers = [httparse::EMPTY_HEADER; 32];
+        let mut parsed = httparse::Request::new(&mut headers);
+        match parsed.parse(&buf) {
+            Ok(httparse::Status::Complete(head_len)) => {
+                let header = |name: &str| {
+                    parsed
+                        .headers
+                        .iter()
+                        .find(|h| h.name.eq_ignore_ascii_case(name))
+                        .and_then(|h| std::str::from_utf8(h.value).ok())
+                        .map(str::to_string)
+                };
+                let content_length: usize = header("Content-Length")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0);
+                let authorization = header("Authorization");
+                if !is_authorized(token, authorization.as_deref()) {
+                    return Ok(Err(HttpResponse::text(401, "missing or wrong token")));
+                }
+                if content_length > MAX_BODY {
+                    return Ok(Err(HttpResponse::text(413, "request too large")));
+                }
+                let request = Request {
+                    method: parsed.method.unwrap_or_default().to_string(),
+                    path: parsed.path.unwrap_or_default().to_string(),
+                    authorization,
+                    body: Vec::new(),
+                };
+                break (request, head_len, content_length);
+            }
+            Ok(httparse::Status::Partial) if buf.len() < MAX_HEAD => (),
+            Ok(httparse::Status::Partial) => {
+                return Ok(Err(HttpResponse::text(413, "request head too large")))
+            }
+            Err(e) => return Ok(Err(HttpResponse::text(400, format!("bad request: {e}")))),
+        }
+    };
+
+    // the buffer only grows with the bytes that actually arrive, the client could lie about the
+    // length
+    let buffered = &buf[head_len..];
+    request
+        .body
+        .extend_from_slice(&buffered[..buffered.len().min(content_length)]);
+    let missing = content_length - request.body.len();
+    stream.take(missing as u64).read_to_end(&mut request.body)?;
+    if request.body.len() < content_length {
+        return Ok(Err(HttpResponse::text(400, "incomplete body")));
+    }
+    Ok(Ok(request))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::records::{Check, CheckFlag};
+
+    use super::*;
+
+    #[test]
+    fn test_push_and_report() {
+        let dir = std::env::temp_dir().join(format!("netpulse-server-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let server = Server::open(&dir, Some("secret".to_string()), Config::default()).unwrap();
+        let batch = Batch {
+            probe: "laptop".to_string(),
+            checks: vec![Check::new(
+                chrono::DateTime::from_timestamp(60, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                Some(Duration::from_millis(12)),
+                "1.1.1.1".parse().unwrap(),
+            )],
+        };
+        let body = serde_json::to_vec(&batch).unwrap();
+
+        assert_eq!(server.answer("POST", "/push", None, &body).status, 401);
+        assert_eq!(
+            server
+                .answer("POST", "/push", Some("Bearer wrong"), &body)
+                .status,
+            401
+        );
+        let auth = Some("Bearer secret");
+        assert_eq!(server.answer("POST", "/push", auth, &body).status, 204);
+        // pushing the same checks again adds nothing
+        assert_eq!(server.push(batch.clone()).unwrap(), 0);
+        assert_eq!(
+            server.answer("GET", "/probes", auth, &[]).body,
+            b"[\n  \"laptop\"\n]"
+        );
+        assert_eq!(
+            server.answer("GET", "/report/laptop", auth, &[]).status,
+            200
+        );
+        assert_eq!(
+            server.answer("GET", "/report/desktop", auth, &[]).status,
+            404
+        );
+        assert_eq!(server.answer("GET", "/push", auth, &[]).status, 405);
+
+        let bad = serde_json::to_vec(&Batch {
+            probe: "../laptop".to_string(),
+            checks: Vec::new(),
+        })
+        .unwrap();
+        assert_eq!(server.answer("POST", "/push", auth, &bad).status, 400);
+
+        // the checks survive a restart
+        drop(server);
+        let server = Server::open(&dir, None, Config::default()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(server.probes(), vec!["laptop".to_string()]);
+        let mut pushed = batch.checks.clone();
+        pushed[0].set_probe(Some("laptop".to_string()));
+        // the checks get the probe of the batch
+        assert_eq!(lock_store(&server.lock()[0].1).checks(), &pushed[..]);
+
+        assert!(matches!(
+            server.push(Batch {
+                probe: ".hidden".to_string(),
+                checks: Vec::new(),
+            }),
+            Err(StoreError::BadProbe(_))
+        ));
+    }
+
+    #[test]
+    fn test_slot() {
+        let active = Arc::new(AtomicUsize::new(0));
+        let first = Slot::take(&active, 2).unwrap();
+        let _second = Slot::take(&active, 2).unwrap();
+        assert!(Slot::take(&active, 2).is_none());
+        drop(first);
+        assert!(Slot::take(&active, 2).is_some());
+        assert_eq!(active.load(Ordering::Acquire), 1);
+    }
+
+    #[test]
+    fn test_request_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // a client that sends a byte every now and then, but never a whole request
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            for byte in b"GET /probes HTTP/1.1\r\n" {
+                if stream.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let start = Instant::now();
+        let err = handle(
+            stream,
+            None,
+            &|_| HttpResponse::text(200, ""),
+            Duration::from_millis(300),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn test_read_request() {
+        let raw = b"POST /push HTTP/1.1\r\nHost: x\r\nauthorization: Bearer t\r\nContent-Length: 4\r\n\r\nbody";
+        let request = read_request(&mut &raw[..], Some("t")).unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/push");
+        assert_eq!(request.authorization.as_deref(), Some("Bearer t"));
+        assert_eq!(request.body, b"body");
+
+        let cut = b"POST /push HTTP/1.1\r\nContent-Length: 10\r\n\r\nbody";
+        assert_eq!(
+            read_request(&mut &cut[..], None)
+                .unwrap()
+                .err()
+                .unwrap()
+                .status,
+            400
+        );
+    }
+
+    #[test]
+    fn test_read_request_unauthorized() {
+        /// Fails the test if the body is read
+        struct Unread;
+        impl Read for Unread {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                panic!("the body of an unauthorized request was read")
+            }
+        }
+
+        let head = b"POST /push HTTP/1.1\r\nauthorization: Bearer wrong\r\nContent-Length: 60000000\r\n\r\n";
+        let mut stream = (&head[..]).chain(Unread);
+        assert_eq!(
+            read_request(&mut stream, Some("t"))
+                .unwrap()
+                .err()
+                .unwrap()
+                .status,
+            401
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! Grafana datasource for the checks of a store.
+//!
+//! `netpulse --grafana ADDR:PORT` serves a [Store] with the protocol of the
+//! [Simple JSON datasource](https://github.com/grafana/simple-json-datasource), so Grafana can
+//! query latency series and outages directly, without exporting anything first. The store is
+//! read again for every request, so new checks of the daemon show up with the next refresh of
+//! the dashboard.
+//!
+//! # HTTP API
+//!
+//! - `GET /`: answers `200 OK`, for testing the datasource in Grafana
+//! - `POST /search`: the names of all [metrics](self#metrics) of the store as a JSON array
+//! - `POST /query`: the requested metrics in the time range of the dashboard
+//! - `POST /annotations`: the outages in the time range, to mark them on graphs
+//!
+//! If the datasource has a token, every request must send it in the
+//! `Authorization: Bearer <token>` header, like for the [Server](super::Server).
+//!
+//! # Metrics
+//!
+//! - `latency`: mean latency of the successful checks in milliseconds
+//! - `latency <target>`: mean latency of the successful checks of one target, named by its label
+//!   or address
+//! - `failed`: number of failed checks
+//! - `success`: percentage of successful checks
+//! - `outages`: a table of the outages, with their start, end, duration, number of checks,
+//!   severity, location and affected IP versions
+//!
+//! The series are summarized over the interval Grafana asks for, but at least over a minute,
+//! which is the resolution of the timestamps of the checks.
+//!
+//! Requires the `grafana` feature.
+
+use std::collections::BTreeMap;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::error;
+
+use crate::analyze::{outage_summaries, CheckAccessConstraints};
+use crate::errors::StoreError;
+use crate::records::Check;
+use crate::store::Store;
+
+use super::{is_authorized, listen, HttpResponse};
+
+/// Shortest interval that series are summarized over, in seconds
+pub const MIN_INTERVAL: i64 = 60;
+
+/// Serves the checks of a store to Grafana, see the [module docs](self).
+#[derive(Debug)]
+pub struct Datasource {
+    path: PathBuf,
+    constraints: CheckAccessConstraints,
+    token: Option<String>,
+}
+
+/// Time range of a dashboard, as RFC 3339 timestamps
+#[derive(Debug, Deserialize)]
+struct TimeRange {
+    from: String,
+    to: String,
+}
+
+/// Body of a `/query` request
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Query {
+    range: TimeRange,
+    #[serde(default)]
+    interval_ms: Option<i64>,
+    #[serde(default)]
+    targets: Vec<QueryTarget>,
+}
+
+/// One metric of a `/query` request
+#[derive(Debug, Deserialize)]
+struct QueryTarget {
+    target: String,
+}
+
+/// Body of an `/annotations` request
+#[derive(Debug, Deserialize)]
+struct AnnotationQuery {
+    range: TimeRange,
+    /// Sent back unchanged with every annotation
+    #[serde(default)]
+    annotation: Value,
+}
+
+impl Datasource {
+    /// Creates a datasource for the store at `path`, considering only the checks that match
+    /// `constraints`.
+    ///
+    /// If `token` is set, every request must send it, see the [module docs](self).
+    pub fn new(path: &Path, constraints: CheckAccessConstraints, token: Option<String>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            constraints,
+            token,
+        }
+    }
+
+    /// Accepts connections on `listener` forever, answering each in its own thread.
+    pub fn serve(self: Arc<Self>, listener: TcpListener) {
+        listen(listener, self.token.clone(), move |request| {
+            self.answer(
+                &request.method,
+                &request.path,
+                request.authorization.as_deref(),
+                &request.body,
+            )
+        });
+    }
+
+    /// Answers a request, see the [module docs](self) for the API.
+    pub fn answer(
+        &self,
+        method: &str,
+        path: &str,
+        authorization: Option<&str>,
+        body: &[u8],
+    ) -> HttpResponse {
+        if !is_authorized(self.token.as_deref(), authorization) {
+            return HttpResponse::text(401, "missing or wrong token");
+        }
+        let path = path.split('?').next().unwrap_or_default();
+        match (method, path) {
+            ("GET", "/") => HttpResponse::text(200, "OK"),
+            ("POST", "/search") => self.respond(None, |store| Ok(json!(metrics(store)))),
+            ("POST", "/query") => {
+                let query: Query = match serde_json::from_slice(body) {
+                    Ok(query) => query,
+                    Err(e) => return HttpResponse::text(400, format!("bad query: {e}")),
+                };
+                let Some(range) = parse_range(&query.range) else {
+                    return HttpResponse::text(400, "bad time range");
+                };
+                let interval = (query.interval_ms.unwrap_or(0) / 1000).max(MIN_INTERVAL);
+                self.respond(Some(range), |store| {
+                    query
+                        .targets
+                        .iter()
+                        .map(|t| {
+                            answer_target(store, &t.target, interval)
+                                .ok_or_else(|| format!("no metric {}", t.target))
+                        })
+                        .collect::<Result<Vec<Value>, String>>()
+                        .map(Value::Array)
+                })
+            }
+            ("POST", "/annotations") => {
+                let query: AnnotationQuery = match serde_json::from_slice(body) {
+                    Ok(query) => query,
+                    Err(e) => return HttpResponse::text(400, format!("bad query: {e}")),
+                };
+                let Some(range) = parse_range(&query.range) else {
+                    return HttpResponse::text(400, "bad time range");
+                };
+                self.respond(Some(range), |store| {
+                    Ok(annotations(store, &query.annotation))
+                })
+            }
+            (_, "/" | "/search" | "/query" | "/annotations") => {
+                HttpResponse::text(405, "method not allowed")
+            }
+            _ => HttpResponse::text(404, "not found"),
+        }
+    }
+
+    /// Loads the store with the checks in `range` and answers with what `f` makes of it.
+    ///
+    /// An error of `f` is answered as a bad request.
+    fn respond(
+        &self,
+        range: Option<(i64, i64)>,
+        f: impl FnOnce(&Store) -> Result<Value, String>,
+    ) -> HttpResponse {
+        let store = match self.load(range) {
+            Ok(store) => store,
+            Err(e) => {
+                error!("could not load the store: {e}");
+                return HttpResponse::text(500, "could not load the store");
+            }
+        };
+        match f(&store) {
+            Ok(value) => HttpResponse::json(&value),
+            Err(message) => HttpResponse::text(400, message),
+        }
+    }
+
+    /// Loads the store, with only the checks in `range` if given.
+    fn load(&self, range: Option<(i64, i64)>) -> Result<Store, StoreError> {
+        let mut constraints = self.constraints.clone();
+        if let Some((from, to)) = range {
+            let local =
+                |timestamp| DateTime::from_timestamp(timestamp, 0).map(|d| d.with_timezone(&Local));
+            constraints.since_date = constraints.since_date.max(local(from));
+            constraints.until_date = match (constraints.until_date, local(to)) {
+                (Some(until), Some(to)) => Some(until.min(to)),
+                (until, to) => until.or(to),
+            };
+        }
+        constraints.load_from(&self.path)
+    }
+}
+
+/// Returns the names of all metrics of `store`, see the [module docs](self#metrics).
+pub fn metrics(store: &Store) -> Vec<String> {
+    let mut targets: Vec<String> = store.checks().iter().map(Check::target_name).collect();
+    targets.sort();
+    targets.dedup();
+    ["latency", "failed", "success", "outages"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(targets.into_iter().map(|t| format!("latency {t}")))
+        .collect()
+}
+
+/// Returns the answer for one metric of a query, or [None] if there is no such metric.
+fn answer_target(store: &Store, target: &str, interval: i64) -> Option<Value> {
+    if target == "outages" {
+        return Some(outage_table(store));
+    }
+    let datapoints = Series::parse(target)?.datapoints(store.checks(), interval);
+    Some(json!({ "target": target, "datapoints": datapoints }))
+}
+
+/// A series of values over time, see the [module docs](self#metrics)
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Series {
+    /// Mean latency, of one target if given
+    Latency(Option<String>),
+    Failed,
+    Success,
+}
+
+impl Series {
+    fn parse(metric: &str) -> Option<Self> {
+        match metric {
+            "latency" => Some(Self::Latency(None)),
+            "failed" => Some(Self::Failed),
+            "success" => Some(Self::Success),
+            _ => metric
+                .strip_prefix("latency ")
+                .map(|target| Self::Latency(Some(target.to_string()))),
+        }
+    }
+
+    /// Returns the value of a single check, or [None] if the check does not count.
+    fn value(&self, check: &Check) -> Option<f64> {
+        match self {
+            Self::Latency(target) => {
+                if target.as_ref().is_some_and(|t| *t != check.target_name()) {
+                    return None;
+                }
+                check.latency().map(f64::from)
+            }
+            Self::Failed => Some(if check.is_success() { 0.0 } else { 1.0 }),
+            Self::Success => Some(if check.is_success() { 100.0 } else { 0.0 }),
+        }
+    }
+
+    /// Returns the datapoints of the series as `[value, milliseconds]` pairs, one per `interval`
+    /// seconds that has checks.
+    fn datapoints(&self, checks: &[Check], interval: i64) -> Vec<Value> {
+        let mut buckets: BTreeMap<i64, (f64, u32)> = BTreeMap::new();
+        for check in checks {
+            if let Some(v) = self.value(check) {
+                let start = check.timestamp() - check.timestamp().rem_euclid(interval);
+                let bucket = buckets.entry(start).or_default();
+                bucket.0 += v;
+                bucket.1 += 1;
+            }
+        }
+        buckets
+            .into_iter()
+            .map(|(start, (total, n))| {
+                let value = match self {
+                    Self::Failed => total,
+                    _ => total / f64::from(n),
+                };
+                json!([value, start * 1000])
+            })
+            .collect()
+    }
+}
+
+/// Returns the outages of `store` as a table.
+fn outage_table(store: &Store) -> Value {
+    let rows: Vec<Value> = outage_summaries(store)
+        .into_iter()
+        .map(|o| {
+            json!([
+                o.start * 1000,
+                o.end * 1000,
+                o.duration,
+                o.checks,
+                o.severity.to_string(),
+                o.location.map(|l| l.to_string()),
+                o.family.map(|f| f.to_string()),
+            ])
+        })
+        .collect();
+    json!({
+        "type": "table",
+        "columns": [
+            { "text": "Start", "type": "time" },
+            { "text": "End", "type": "time" },
+            { "text": "Duration (s)", "type": "number" },
+            { "text": "Checks", "type": "number" },
+            { "text": "Severity", "type": "string" },
+            { "text": "Location", "type": "string" },
+            { "text": "IP Versions", "type": "string" },
+        ],
+        "rows": rows,
+    })
+}
+
+/// Returns the outages of `store` as annotations of `annotation`.
+fn annotations(store: &Store, annotation: &Value) -> Value {
+    outage_summaries(store)
+        .into_iter()
+        .map(|o| {
+            let tags: Vec<String> = o.check_types.iter().map(|t| t.name().to_string()).collect();
+            json!({
+                "annotation": annotation,
+                "time": o.start * 1000,
+                "timeEnd": o.end * 1000,
+                "isRegion": o.start != o.end,
+                "title": format!("Outage: {}", o.severity),
+                "text": format!("{} failed checks over {} s", o.checks, o.duration),
+                "tags": tags,
+            })
+        })
+        .collect()
+}
+
+/// Returns the range as unix timestamps in seconds, or [None] if it is malformed.
+fn parse_range(range: &TimeRange) -> Option<(i64, i64)> {
+    let parse = |raw: &str| {
+        DateTime::parse_from_rfc3339(raw)
+            .ok()
+            .map(|d| d.timestamp())
+    };
+    Some((parse(&range.from)?, parse(&range.to)?))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::records::CheckFlag;
+    use crate::store::{FileBackend, StoreBackend};
+
+    use super::*;
+
+    fn check(minute: i64, success: bool, latency: u16) -> Check {
+        let flags = if success {
+            CheckFlag::Success | CheckFlag::TypeIcmp
+        } else {
+            CheckFlag::TypeIcmp.into()
+        };
+        Check::new(
+            chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+            flags,
+            Some(Duration::from_millis(latency.into())),
+            "1.1.1.1".parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_datasource() {
+        let path = std::env::temp_dir().join(format!("netpulse-grafana-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut backend = FileBackend::new(&path);
+        let mut store = backend.create().unwrap();
+        for (minute, success, latency) in
+            [(0, true, 10), (1, true, 20), (2, false, 0), (3, true, 30)]
+        {
+            store.add_check(check(minute, success, latency));
+        }
+        backend.save(&store).unwrap();
+
+        let datasource =
+            Datasource::new(&path, CheckAccessConstraints::default(), Some("t".into()));
+        let auth = Some("Bearer t");
+        assert_eq!(datasource.answer("GET", "/", None, &[]).status, 401);
+        assert_eq!(datasource.answer("GET", "/", auth, &[]).status, 200);
+        let search = datasource.answer("POST", "/search", auth, &[]);
+        let search: Vec<String> = serde_json::from_slice(&search.body).unwrap();
+        assert!(search.contains(&"latency 1.1.1.1".to_string()));
+
+        let query = br#"{
+            "range": { "from": "1970-01-01T00:00:00Z", "to": "1970-01-01T01:00:00Z" },
+            "intervalMs": 120000,
+            "targets": [
+                { "target": "latency", "refId": "A" },
+                { "target": "failed", "refId": "B" },
+                { "target": "outages", "refId": "C", "type": "table" }
+            ]
+        }"#;
+        let answer = datasource.answer("POST", "/query", auth, query);
+        assert_eq!(answer.status, 200);
+        let answer: Value = serde_json::from_slice(&answer.body).unwrap();
+        assert_eq!(answer[0]["datapoints"], json!([[15.0, 0], [30.0, 120_000]]));
+        assert_eq!(answer[1]["datapoints"], json!([[0.0, 0], [1.0, 120_000]]));
+        assert_eq!(answer[2]["rows"][0][0], json!(120_000));
+
+        let bad = br#"{ "range": { "from": "1970-01-01T00:00:00Z", "to": "1970-01-01T01:00:00Z" }, "targets": [{ "target": "jitter" }] }"#;
+        assert_eq!(datasource.answer("POST", "/query", auth, bad).status, 400);
+
+        let annotations = br#"{
+            "range": { "from": "1970-01-01T00:00:00Z", "to": "1970-01-01T01:00:00Z" },
+            "annotation": { "name": "outages" }
+        }"#;
+        let answer = datasource.answer("POST", "/annotations", auth, annotations);
+        std::fs::remove_file(&path).unwrap();
+        let answer: Value = serde_json::from_slice(&answer.body).unwrap();
+        assert_eq!(answer[0]["time"], json!(120_000));
+        assert_eq!(answer[0]["annotation"]["name"], json!("outages"));
+    }
+}
@@ -10,32 +10,123 @@
 //! - Environment variable: `NETPULSE_STORE_PATH` (for debugging)
 //! - Default path: `/var/lib/netpulse/netpulse.store`
 //!
+//! # Backends
+//!
+//! Reading and writing a [Store] goes through a [StoreBackend]. [Store::load] and [Store::save]
+//! use the store file with [FileBackend], [Store::load_with] and [Store::save_to] take any other
+//! backend. See [backend].
+//!
 //! # Versioning
 //!
 //! The store uses a simple version number to track format changes. [Version::CURRENT] is the current version.
 //! When loading a store, the version is checked and migration is performed if needed.
+//!
+//! Since [Version::V3], the store file starts with an uncompressed [header] holding the
+//! [Version], so [Store::peek_file_version] does not need to decompress anything. The header
+//! also holds when the store was created and last saved, the version of netpulse that saved it
+//! and a random instance ID, see [Store::instance_id]. After it, the store file is split into
+//! sections and records that each carry their length, see [framed]. A store file of a newer
+//! version can then be read by skipping what is unknown instead of guessing its layout, it is
+//! loaded as readonly. The records are encoded as CBOR, which is self describing and can be read
+//! from other languages, see [format].
+
+//! # Integrity
+//!
+//! Since [Version::V3], the serialized store is followed by a [blake3] checksum of the data
+//! ([CHECKSUM_LEN] bytes). [Store::load] verifies it and returns [StoreError::CorruptStore] if
+//! the store file was truncated or otherwise corrupted.
+//!
+//! # Backups
+//!
+//! If [ENV_BACKUPS] is set, [Store::save] rotates the previous store file to
+//! `netpulse.store.bak.1`, `netpulse.store.bak.2`, ... before writing, keeping that many backups.
+//!
+//! # Locking
+//!
+//! The store file is locked while it is written or loaded, so two processes never save it at the
+//! same time and readers never see a save that is half done. See [lock].
+//!
+//! # Streaming
+//!
+//! For questions about a small part of a large store, [Store::stream_checks] reads the
+//! [Checks](Check) one by one instead of loading all of them, and [Store::load_filtered_from]
+//! only keeps the [Checks](Check) that are needed. See [CheckStream].
+//!
+//! # Archive
+//!
+//! With the `chunked` feature, old [Checks](Check) can be moved out of the store into chunk
+//! files with [Store::archive], so the memory used by the daemon does not grow with the history.
+//! See [chunked].
+//!
+//! # SQLite
+//!
+//! With the `sqlite` feature, the data of a store can also be kept in a SQLite database, which
+//! is written incrementally and can be queried with SQL. See [sqlite].
+//!
+//! # Order
+//!
+//! The [Checks](Check) of a [Store] are kept sorted by time, so a time range of them can be
+//! found with a binary search, see [Store::checks_between]. Stores that were written out of
+//! order by older versions are sorted when loaded.
+//!
+//! # Outages
+//!
+//! The store keeps an [OutageLog] of the outages found in its
+//! [Checks](Check), which the daemon updates with [Store::update_outages] before saving. Listing
+//! the outages only reads the log then, see [outage_summaries](crate::analyze::outage_summaries).
+//!
+//! # Downsampling
+//!
+//! Old [Checks](Check) can be rolled up into hourly [Aggregates](Aggregate) with
+//! [Store::downsample] to keep the store small. The daemon does this on every wakeup if
+//! [ENV_DOWNSAMPLE_DAYS] is set.
 
+use std::collections::{HashSet, VecDeque};
 use std::fmt::Display;
 use std::fs::{self};
 use std::hash::Hash;
-use std::io::{ErrorKind, Write};
-use std::os::unix::fs::OpenOptionsExt;
-use std::path::PathBuf;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use deepsize::DeepSizeOf;
 use serde::{Deserialize, Serialize};
 use tracing::{error, info, trace, warn};
 
+use crate::config::{Config, Target};
 use crate::errors::StoreError;
-use crate::records::{Check, CheckType, TARGETS};
+use crate::records::aggregate::{aggregate_checks, Aggregate, AGGREGATE_SPAN};
+use crate::records::anonymize::Anonymizer;
+use crate::records::outage::OutageLog;
+use crate::records::trace::Trace;
+use crate::records::{Check, CheckType};
+#[cfg(unix)]
 use crate::DAEMON_USER;
+use crate::TIMEOUT_MS;
+use header::{Header, Metadata};
+use lock::StoreLock;
 
 #[cfg(feature = "compression")]
 use zstd;
 
+pub mod backend;
+#[cfg(feature = "chunked")]
+pub mod chunked;
+mod format;
+mod framed;
+mod header;
+mod legacy;
+pub mod lock;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+mod stream;
+
+pub use backend::{FileBackend, StoreBackend};
+pub use stream::CheckStream;
+
 /// The filename of the netpulse store database
 ///
 /// Used in combination with [DB_PATH] to form the complete store path.
@@ -44,7 +135,8 @@ pub const DB_NAME: &str = "netpulse.store";
 
 /// Base directory for the netpulse store
 ///
-/// Used in combination with [DB_NAME] to form the complete store path.
+/// Used in combination with [DB_NAME] to form the complete store path, unless the
+/// [store_dir](crate::paths::store_dir) is changed.
 /// Default value: "/var/lib/netpulse"
 pub const DB_PATH: &str = "/var/lib/netpulse";
 
@@ -55,6 +147,18 @@ pub const DB_PATH: &str = "/var/lib/netpulse";
 #[cfg(feature = "compression")]
 pub const ZSTD_COMPRESSION_LEVEL: i32 = 4;
 
+/// Magic bytes at the start of every store file since [Version::V3], see [header]
+pub const MAGIC: [u8; 8] = *b"netpulse";
+
+/// Length of the [blake3] checksum that follows the serialized store data, in bytes
+pub const CHECKSUM_LEN: usize = blake3::OUT_LEN;
+
+/// How often [Store::load_from] reads the store file again if it changed while it was read
+pub const READ_RETRIES: usize = 3;
+/// How long [Store::load_from] waits before reading a store file again that changed while it was
+/// read
+const READ_RETRY_DELAY: Duration = Duration::from_millis(100);
+
 /// Environment variable name for overriding the store path
 ///
 /// If set, its value will be used instead of [DB_PATH] to locate the store.
@@ -67,11 +171,28 @@ pub const DEFAULT_PERIOD: i64 = 60;
 pub const OUTAGE_TIME_SPAN: i64 = DEFAULT_PERIOD * OUTAGE_TIME_FACTOR;
 /// How many [DEFAULT_PERIOD] of a span to consider one [Outage](crate::analyze::Outage)
 pub const OUTAGE_TIME_FACTOR: i64 = 5;
+/// How many threads make [Checks](Check) at the same time at most
+pub const MAX_CHECK_WORKERS: usize = 16;
+/// How long a round of [Checks](Check) may take at most, see [Store::primitive_make_checks]
+///
+/// Twice the [TIMEOUT](crate::TIMEOUT) of a single check, so a round still finishes if some
+/// checks have to wait for a free worker.
+pub const ROUND_DEADLINE: Duration = Duration::from_millis(2 * TIMEOUT_MS as u64);
 /// Environment variable name for the time period after which the daemon wakes up.
 ///
 /// If set, its value will be used instead of [DEFAULT_PERIOD].
 /// Primarily intended for development and testing.
 pub const ENV_PERIOD: &str = "NETPULSE_PERIOD";
+/// Environment variable name for the number of backups of the store file to keep.
+///
+/// If set, [Store::save] rotates the previous store file to `netpulse.store.bak.N` before writing.
+/// If not set, no backups are made.
+pub const ENV_BACKUPS: &str = "NETPULSE_BACKUPS";
+/// Environment variable name for the age in days after which [Checks](Check) are downsampled.
+///
+/// If set, the daemon rolls up [Checks](Check) older than this many days into hourly
+/// [Aggregates](Aggregate). If not set, nothing is downsampled.
+pub const ENV_DOWNSAMPLE_DAYS: &str = "NETPULSE_DOWNSAMPLE_DAYS";
 
 /// Version information for the store format.
 ///
@@ -102,6 +223,7 @@ pub enum Version {
     V0 = 0,
     V1 = 1,
     V2 = 2,
+    V3 = 3,
 }
 
 /// Main storage type for netpulse check results.
@@ -109,15 +231,86 @@ pub enum Version {
 /// The Store handles persistence of check results and provides methods for
 /// loading, saving, and managing the data. It includes versioning support
 /// for future format changes.
-#[derive(Debug, PartialEq, Eq, Hash, Deserialize, Serialize, DeepSizeOf)]
+#[derive(Debug, Deserialize, Serialize, DeepSizeOf)]
 pub struct Store {
     /// Store format version
     version: Version,
     /// Collection of all recorded checks
     checks: Vec<Check>,
+    /// Hourly summaries of downsampled checks
+    aggregates: Vec<Aggregate>,
+    /// Traces of the path to targets whose checks failed
+    traces: Vec<Trace>,
+    /// Outages found in the checks, updated by [Store::update_outages]
+    outages: OutageLog,
     // if true, this store will never be saved
     #[serde(skip)]
     readonly: bool,
+    // the file this store was loaded from, if it's not the default store file
+    #[serde(skip)]
+    source: Option<PathBuf>,
+    // if true, add_check rejects checks that are already in the store
+    #[serde(skip)]
+    reject_duplicates: bool,
+    // kept in the header of the store file
+    #[serde(skip)]
+    metadata: Metadata,
+}
+
+/// What [Store::compact] removed from a [Store].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct CompactReport {
+    /// [Checks](Check) that were exact copies of another [Check]
+    pub duplicate_checks: usize,
+    /// [Checks](Check) without a known [CheckType]
+    pub unknown_checks: usize,
+    /// [Aggregates](Aggregate) that were exact copies of another [Aggregate]
+    pub duplicate_aggregates: usize,
+    /// [Traces](Trace) that were exact copies of another [Trace]
+    pub duplicate_traces: usize,
+}
+
+// The metadata is left out, so a [Store] equals its copy that was saved and loaded again.
+impl PartialEq for Store {
+    fn eq(&self, other: &Self) -> bool {
+        self.data() == other.data()
+    }
+}
+
+impl Eq for Store {}
+
+impl Hash for Store {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.data().hash(state);
+    }
+}
+
+impl CompactReport {
+    /// Returns the number of all removed entries.
+    pub fn total(&self) -> usize {
+        self.duplicate_checks
+            + self.unknown_checks
+            + self.duplicate_aggregates
+            + self.duplicate_traces
+    }
+}
+
+/// What [Store::merge_from] added to a [Store].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MergeReport {
+    /// [Checks](Check) that were not in the [Store] yet
+    pub checks: usize,
+    /// [Aggregates](Aggregate) that were not in the [Store] yet
+    pub aggregates: usize,
+    /// [Traces](Trace) that were not in the [Store] yet
+    pub traces: usize,
+}
+
+impl MergeReport {
+    /// Returns the number of all added entries.
+    pub fn total(&self) -> usize {
+        self.checks + self.aggregates + self.traces
+    }
 }
 
 impl Display for Version {
@@ -134,6 +327,7 @@ impl TryFrom<u8> for Version {
             0 => Self::V0,
             1 => Self::V1,
             2 => Self::V2,
+            3 => Self::V3,
             _ => return Err(StoreError::BadStoreVersion(value)),
         })
     }
@@ -147,18 +341,31 @@ impl From<Version> for u8 {
 
 impl Version {
     /// Current version of the store format
-    pub const CURRENT: Self = Self::V2;
+    pub const CURRENT: Self = Self::V3;
 
     /// List of supported store format versions
     ///
     /// Used for compatibility checking when loading stores.
-    pub const SUPPROTED: &[Self] = &[Self::V0, Self::V1, Self::V2];
+    pub const SUPPROTED: &[Self] = &[Self::V0, Self::V1, Self::V2, Self::V3];
 
     /// Gets the raw [Version] as [u8]
     pub const fn raw(&self) -> u8 {
         *self as u8
     }
 
+    /// Gets the [Version] of a store from its raw `u8`, as read from a store file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::NewerStore] if the store was written by a newer version of netpulse,
+    /// or [StoreError::BadStoreVersion] if `raw` is not a [Version] otherwise.
+    pub fn of_file(raw: u8) -> Result<Self, StoreError> {
+        if raw > Self::CURRENT.raw() {
+            return Err(StoreError::NewerStore(raw));
+        }
+        Self::try_from(raw)
+    }
+
     /// Returns the next sequential [Version], if one exists.
     ///
     /// Used for version migration logic to determine the next version to upgrade to.
@@ -177,13 +384,15 @@ impl Version {
     /// # use netpulse::store::Version;
     /// assert_eq!(Version::V0.next(), Some(Version::V1));
     /// assert_eq!(Version::V1.next(), Some(Version::V2));
+    /// assert_eq!(Version::V2.next(), Some(Version::V3));
     /// assert_eq!(Version::CURRENT.next(), None);  // No version after latest
     /// ```
     pub fn next(&self) -> Option<Self> {
         Some(match *self {
             Self::V0 => Self::V1,
             Self::V1 => Self::V2,
-            Self::V2 => return None,
+            Self::V2 => Self::V3,
+            Self::V3 => return None,
         })
     }
 }
@@ -191,9 +400,11 @@ impl Version {
 impl Store {
     /// Returns the full path to the store file.
     ///
-    /// The path is determined by:
+    /// This is [DB_NAME] in the [store_dir](crate::paths::store_dir), which is determined by:
     /// 1. Checking [ENV_PATH] environment variable
-    /// 2. Falling back to [DB_PATH]/[DB_NAME] if not set
+    /// 2. The `store_dir` in the `[paths]` table of the config
+    /// 3. In [user mode](crate::paths::user_mode), using `$XDG_DATA_HOME/netpulse`
+    /// 4. Falling back to [DB_PATH] otherwise
     ///
     /// # Examples
     ///
@@ -204,13 +415,14 @@ impl Store {
     /// println!("Store located at: {}", path.display());
     /// ```
     pub fn path() -> PathBuf {
-        if let Some(var) = std::env::var_os(ENV_PATH) {
-            let mut p = PathBuf::from(var);
-            p.push(DB_NAME);
-            p
-        } else {
-            PathBuf::from(format!("{DB_PATH}/{DB_NAME}"))
-        }
+        crate::paths::store_file()
+    }
+
+    /// Returns the path of the `n`th backup of the store file.
+    ///
+    /// The most recent backup is `1`. See [Store::backups].
+    pub fn path_backup(n: usize) -> PathBuf {
+        FileBackend::default().path_backup(n)
     }
 
     /// Creates a new empty store with current version.
@@ -220,10 +432,32 @@ impl Store {
         Self {
             version: Version::CURRENT,
             checks: Vec::new(),
+            aggregates: Vec::new(),
+            traces: Vec::new(),
+            outages: OutageLog::default(),
             readonly: false,
+            source: None,
+            reject_duplicates: false,
+            metadata: Metadata::new(),
         }
     }
 
+    /// Creates a readonly [Store] that only lives in memory, holding `checks`.
+    ///
+    /// The [Checks](Check) are sorted by time. The [Store] has the current [Version] and no
+    /// file, so it is never saved. Use it with [Dataset](crate::testing::Dataset) to analyze
+    /// generated [Checks](Check).
+    ///
+    /// Requires the `testing` feature.
+    #[cfg(feature = "testing")]
+    pub fn from_raw_in_mem(checks: Vec<Check>) -> Self {
+        let mut store = Self::new();
+        store.checks = checks;
+        store.sort_if_needed();
+        store.set_readonly();
+        store
+    }
+
     /// Sets up the store directory with proper permissions.
     ///
     /// This function must be called with root privileges before starting the daemon. It:
@@ -243,6 +477,9 @@ impl Store {
     /// 2. Drop privileges to other user user
     /// 3. Use [`Store::load_or_create`], [`Store::create()`] or [`Store::load()`] as lower priviledged user
     ///
+    /// In [user mode](crate::paths::user_mode), the directory belongs to the invoking user, so it is only
+    /// created and no root privileges are needed.
+    ///
     /// # Errors
     ///
     /// Returns [StoreError] if:
@@ -273,8 +510,21 @@ impl Store {
         let parent_path = path
             .parent()
             .expect("the store path has no parent directory");
+        // there is no daemon user to hand the directory to in user mode or on other systems
+        if crate::paths::user_mode() || cfg!(not(unix)) {
+            fs::create_dir_all(parent_path)?;
+            return Ok(());
+        }
+        #[cfg(unix)]
+        Self::setup_daemon_dir(parent_path)?;
+        Ok(())
+    }
+
+    /// Creates `parent_path` and hands it to [DAEMON_USER](crate::DAEMON_USER).
+    #[cfg(unix)]
+    fn setup_daemon_dir(parent_path: &Path) -> Result<(), StoreError> {
         let user = nix::unistd::User::from_name(DAEMON_USER)
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+            .map_err(std::io::Error::other)
             .expect("could not get user for netpulse")
             .ok_or_else(|| {
                 std::io::Error::new(std::io::ErrorKind::NotFound, "netpulse user not found")
@@ -305,31 +555,41 @@ impl Store {
     /// - Serialization fails
     /// - Write fails
     pub fn create() -> Result<Self, StoreError> {
-        let file = match fs::File::options()
-            .read(false)
-            .write(true)
-            .append(false)
-            .create_new(true)
-            .mode(0o644)
-            .open(Self::path())
-        {
-            Ok(file) => file,
-            Err(err) => {
-                error!("opening the store file for writing failed: {err}");
-                return Err(err.into());
-            }
-        };
+        Self::create_with(&mut FileBackend::default())
+    }
 
-        let store = Store::new();
+    /// Creates a new empty store in `backend`, see [StoreBackend::create].
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the [Store] could not be written.
+    pub fn create_with(backend: &mut dyn StoreBackend) -> Result<Self, StoreError> {
+        backend.create()
+    }
+
+    /// Serializes the [Store] into `file`, compressing it if the feature is enabled.
+    ///
+    /// The [Header] is followed by the data in the [framed] layout and its checksum. The data is
+    /// synced to the disk before returning.
+    fn write_to(&self, mut file: fs::File) -> Result<(), StoreError> {
+        Header::new(self.version, self.metadata.saved_now()).write_to(&mut file)?;
 
         #[cfg(feature = "compression")]
         let mut writer = zstd::Encoder::new(file, ZSTD_COMPRESSION_LEVEL)?;
         #[cfg(not(feature = "compression"))]
         let mut writer = file;
 
-        writer.write_all(&bincode::serialize(&store)?)?;
-        writer.flush()?;
-        Ok(store)
+        let mut data = framed::encode(self)?;
+        append_checksum(&mut data);
+        writer.write_all(&data)?;
+
+        #[cfg(feature = "compression")]
+        let file = writer.finish()?;
+        #[cfg(not(feature = "compression"))]
+        let file = writer;
+
+        file.sync_all()?;
+        Ok(())
     }
 
     /// Loads existing store or creates new one if not found.
@@ -347,15 +607,28 @@ impl Store {
     /// ```rust,no_run
     /// use netpulse::store::Store;
     ///
+    /// use netpulse::config::Config;
+    ///
     /// let mut store = Store::load_or_create().unwrap();
-    /// store.make_checks();
+    /// store.make_checks(&Config::load_or_default());
     /// store.save().unwrap();
     /// ```
     pub fn load_or_create() -> Result<Self, StoreError> {
-        match Self::load(false) {
+        Self::load_or_create_with(&mut FileBackend::default())
+    }
+
+    /// Loads the store from `backend` or creates a new one there if there is none yet.
+    ///
+    /// See [Store::load_or_create].
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the store exists but could not be loaded, or could not be created.
+    pub fn load_or_create_with(backend: &mut dyn StoreBackend) -> Result<Self, StoreError> {
+        match Self::load_with(backend, false) {
             Ok(store) => Ok(store),
             Err(err) => match &err {
-                StoreError::DoesNotExist => Self::create(),
+                StoreError::DoesNotExist => Self::create_with(backend),
                 StoreError::Load { source } => {
                     error!("{err}");
 
@@ -372,6 +645,11 @@ impl Store {
 
                     Err(err)
                 }
+                StoreError::CorruptStore => {
+                    error!("{err}");
+                    error!("Not doing anything in case you need to keep old data, consider restoring a backup or running 'netpulse --repair'");
+                    Err(err)
+                }
                 _ => {
                     error!("Error while trying to load the store: {err:#}");
                     Err(err)
@@ -388,6 +666,8 @@ impl Store {
     ///
     /// - Checks version compatibility
     /// - Automatically migrates supported old versions in memory
+    /// - Loads stores of newer versions as readonly, skipping what is unknown, see
+    ///   [newer stores](framed#newer-stores)
     /// - Returns error for unsupported versions
     ///
     /// # Errors
@@ -395,13 +675,216 @@ impl Store {
     /// Returns [StoreError] if:
     /// - Store file doesn't exist
     /// - Read/parse fails
+    /// - Store file is truncated or corrupted ([StoreError::CorruptStore])
     /// - Version unsupported
     pub fn load(readonly: bool) -> Result<Self, StoreError> {
-        let file = match fs::File::options()
+        Self::load_from(&Self::path(), readonly)
+    }
+
+    /// Loads the store from `backend` instead of the store file.
+    ///
+    /// See [backend] for the available backends.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the store could not be loaded, see [StoreBackend::load].
+    pub fn load_with(backend: &dyn StoreBackend, readonly: bool) -> Result<Self, StoreError> {
+        let mut store = backend.load()?;
+        if readonly {
+            store.set_readonly();
+        }
+        Ok(store)
+    }
+
+    /// Loads a store from the file at `path` instead of [Store::path].
+    ///
+    /// Works just like [Store::load] otherwise. The path is remembered, see [Store::file_path].
+    /// Keep in mind that [Store::save] always writes to [Store::path], so a store loaded from
+    /// somewhere else should usually be `readonly`.
+    ///
+    /// # Consistency
+    ///
+    /// The daemon replaces the store file atomically and [locks](lock) it while saving, so a
+    /// store that is loaded while it is saved is the old or the new one, never a mix. Writers
+    /// that do neither, like older versions of netpulse, can still leave a half written file for
+    /// a moment. If the store file could not be decoded and it changed while it was read, it is
+    /// read again, up to [READ_RETRIES] times.
+    ///
+    /// # Errors
+    ///
+    /// See [Store::load].
+    pub fn load_from(path: &Path, readonly: bool) -> Result<Self, StoreError> {
+        let mut retries = 0;
+        loop {
+            let before = file_identity(path);
+            match Self::load_from_once(path, readonly) {
+                Err(
+                    err @ (StoreError::CorruptStore
+                    | StoreError::Load { .. }
+                    | StoreError::Io { .. }),
+                ) if retries < READ_RETRIES && file_identity(path) != before => {
+                    warn!("the store file changed while it was read, reading it again: {err}");
+                    retries += 1;
+                    std::thread::sleep(READ_RETRY_DELAY);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Loads the store file at `path` once, see [Store::load_from].
+    fn load_from_once(path: &Path, readonly: bool) -> Result<Self, StoreError> {
+        let _lock = StoreLock::shared(path)?;
+        let (header, mut reader) = Self::open_reader(path)?;
+
+        // The version is the first field and serialized as a single byte. Older versions have a
+        // different layout and need to be read as such, so peek it before deserializing the rest.
+        let version_raw = [read_version_raw(header.as_ref(), &mut reader)?];
+        let (version, newer) = read_version(version_raw[0]);
+        let reader = (&version_raw[..]).chain(reader);
+        let mut store: Store = match version {
+            Version::V0 | Version::V1 | Version::V2 => {
+                bincode::deserialize_from::<_, legacy::StoreV2>(reader)?.into()
+            }
+            Version::V3 => Self::deserialize_framed(reader, version, newer)?,
+        };
+        store.metadata = match header {
+            Some(header) => header.metadata,
+            None => Metadata::of_legacy(store.oldest_timestamp()),
+        };
+
+        store.migrate_to_current()?;
+        store.sort_if_needed();
+
+        if readonly || newer {
+            store.set_readonly();
+        }
+        if path != Self::path() {
+            store.source = Some(path.to_path_buf());
+        }
+
+        Ok(store)
+    }
+
+    /// Streams the [Checks](Check) of the store file at [Store::path] one by one.
+    ///
+    /// See [CheckStream] and [Store::stream_checks_from].
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the store file can not be opened or its version is not supported.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use netpulse::store::Store;
+    ///
+    /// let failed = Store::stream_checks()
+    ///     .unwrap()
+    ///     .filter_map(Result::ok)
+    ///     .filter(|c| !c.is_success())
+    ///     .count();
+    /// println!("{failed} checks failed");
+    /// ```
+    pub fn stream_checks() -> Result<CheckStream, StoreError> {
+        Self::stream_checks_from(&Self::path())
+    }
+
+    /// Streams the [Checks](Check) of the store file at `path` one by one.
+    ///
+    /// The checksum of the store file is not verified, see [CheckStream]. Stores older than
+    /// [Version::V3] have a different layout of the [Checks](Check) and are loaded as a whole
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the store file can not be opened or its version is not supported.
+    pub fn stream_checks_from(path: &Path) -> Result<CheckStream, StoreError> {
+        let (version, newer, reader) = Self::open_versioned(path)?;
+        if version < Version::V3 {
+            return Ok(CheckStream::from_loaded(
+                Self::load_from(path, true)?.checks,
+            ));
+        }
+        CheckStream::from_framed(reader, newer)
+    }
+
+    /// Loads the store file at `path`, but only keeps the [Checks](Check) for which `keep`
+    /// returns true.
+    ///
+    /// The [Checks](Check) are streamed and filtered while reading, so the others never take up
+    /// memory. The [Aggregates](Aggregate) and [Traces](Trace) are all loaded, the [OutageLog]
+    /// is not, as it describes all [Checks](Check). As the [Store] is incomplete, it is always
+    /// readonly. Like with [Store::stream_checks_from], the checksum of
+    /// the store file is not verified.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the store file can not be opened or decoded, or its version is not
+    /// supported.
+    pub fn load_filtered_from(
+        path: &Path,
+        keep: impl Fn(&Check) -> bool,
+    ) -> Result<Self, StoreError> {
+        let lock = StoreLock::shared(path)?;
+        let (version, newer, reader) = Self::open_versioned(path)?;
+        if version < Version::V3 {
+            drop((reader, lock));
+            let mut store = Self::load_from(path, true)?;
+            store.checks.retain(keep);
+            return Ok(store);
+        }
+
+        let mut store = Store::new();
+        store.version = version;
+        let mut stream = CheckStream::from_framed(reader, newer)?;
+        for check in stream.by_ref() {
+            let check = check?;
+            if keep(&check) {
+                store.checks.push(check);
+            }
+        }
+        let mut reader = stream
+            .into_reader()
+            .expect("a stream read to the end returns its reader");
+        framed::read_sections(&mut reader, &mut store, newer)?;
+        store.forget_outages();
+
+        store.migrate_to_current()?;
+        store.sort_if_needed();
+        store.set_readonly();
+        if path != Self::path() {
+            store.source = Some(path.to_path_buf());
+        }
+        Ok(store)
+    }
+
+    /// Opens the store file at `path` and reads its [Version], leaving the reader right after it.
+    ///
+    /// Also returns whether the store file is newer than this version of netpulse, see
+    /// [read_version].
+    fn open_versioned(path: &Path) -> Result<(Version, bool, Box<dyn Read>), StoreError> {
+        let (header, mut reader) = Self::open_reader(path)?;
+        let (version, newer) = read_version(read_version_raw(header.as_ref(), &mut reader)?);
+        Ok((version, newer, reader))
+    }
+
+    /// Opens the store file for reading.
+    ///
+    /// Returns the [Header] of the store file and a reader of the data after it, decompressing
+    /// the data if needed. Store files older than [Version::V3] have no [Header] and are
+    /// decompressed as a whole if the feature is enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::CompressionUnsupported] if the data is compressed, but the feature is
+    /// not enabled.
+    fn open_reader(path: &Path) -> Result<(Option<Header>, Box<dyn Read>), StoreError> {
+        let mut file = match fs::File::options()
             .read(true)
             .write(false)
             .create_new(false)
-            .open(Self::path())
+            .open(path)
         {
             Ok(file) => file,
             Err(err) => {
@@ -415,57 +898,138 @@ impl Store {
             }
         };
 
-        #[cfg(feature = "compression")]
-        let reader = zstd::Decoder::new(file)?;
-        #[cfg(not(feature = "compression"))]
-        let mut reader = file;
-
-        let mut store: Store = bincode::deserialize_from(reader)?;
+        let (header, start) = Header::read(&mut file)?;
+        let reader: Box<dyn Read> = match &header {
+            Some(header) if !header.compressed => Box::new(file),
+            Some(_) if !cfg!(feature = "compression") => {
+                error!("the store file {} is compressed", path.display());
+                return Err(StoreError::CompressionUnsupported);
+            }
+            Some(_) => decompress(file)?,
+            None => decompress(std::io::Cursor::new(start).chain(file))?,
+        };
+        Ok((header, reader))
+    }
 
-        if store.version != Version::CURRENT {
-            warn!("The store that was loaded is not of the current version: store has {} but the current version is {}", store.version, Version::CURRENT);
-            if Version::SUPPROTED.contains(&store.version) {
+    /// Migrates a freshly loaded [Store] of an older [Version] to [Version::CURRENT] in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::UnsupportedVersion] if the [Version] of the [Store] is not supported.
+    fn migrate_to_current(&mut self) -> Result<(), StoreError> {
+        if self.version != Version::CURRENT {
+            warn!("The store that was loaded is not of the current version: store has {} but the current version is {}", self.version, Version::CURRENT);
+            if Version::SUPPROTED.contains(&self.version) {
                 warn!("The different store version is still supported, migrating to newer version");
                 warn!("Temp migration in memory, can be made permanent by saving");
 
-                if store.version > Version::CURRENT {
-                    warn!("The store version is newer than this version of netpulse can normally handle! Trying to ignore potential differences and loading as READONLY!");
-                    store.readonly = true;
-                }
-
-                while store.version < Version::CURRENT {
-                    for check in store.checks_mut().iter_mut() {
-                        if let Err(e) = check.migrate(Version::V0) {
+                while self.version < Version::CURRENT {
+                    let version = self.version;
+                    for check in self.checks_mut().iter_mut() {
+                        if let Err(e) = check.migrate(version) {
                             panic!("Error while migrating check '{}': {e}", check.get_hash());
                         }
                     }
-                    store.version = store
+                    self.version = self
                         .version
                         .next()
                         .expect("Somehow migrated to a version that does not exist");
                 }
 
-                assert_eq!(store.version, Version::CURRENT);
+                assert_eq!(self.version, Version::CURRENT);
             } else {
                 error!("The store version is not supported");
                 return Err(StoreError::UnsupportedVersion);
             }
         }
+        Ok(())
+    }
 
-        if readonly {
-            store.set_readonly();
+    /// Loads as much as possible from a damaged store file.
+    ///
+    /// Where [Store::load] gives up on the first error, this decompresses and decodes the store
+    /// file until the data ends or can not be decoded anymore, keeping every [Check] and
+    /// [Aggregate] that could be read completely. The checksum is not verified.
+    ///
+    /// The recovered [Store] is migrated to [Version::CURRENT] and can be saved to replace the
+    /// damaged file. Use this to repair a truncated or otherwise corrupted store.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if:
+    /// - Store file doesn't exist
+    /// - Not even the [Version] of the store can be read
+    /// - Version unsupported
+    pub fn load_damaged() -> Result<Self, StoreError> {
+        let (header, mut reader) = Self::open_reader(&Self::path())?;
+
+        let mut raw = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => raw.extend_from_slice(&buf[..n]),
+                Err(err) => {
+                    warn!("Stopped reading the store file early: {err}");
+                    break;
+                }
+            }
         }
+        info!("Read {} bytes of store data", raw.len());
 
+        let mut store = Self::recover(&raw)?;
+        store.metadata = match header {
+            Some(header) => header.metadata,
+            None => Metadata::of_legacy(store.oldest_timestamp()),
+        };
+        store.migrate_to_current()?;
+        store.sort_if_needed();
         Ok(store)
     }
 
-    /// Saves the store to disk.
-    ///
-    /// # File Handling
+    /// Decodes [Checks](Check) and [Aggregates](Aggregate) one by one from the raw store data
+    /// until the data ends or can not be decoded anymore.
+    fn recover(raw: &[u8]) -> Result<Self, StoreError> {
+        let Some(version_raw) = raw.first() else {
+            error!("The store file does not contain any data");
+            return Err(StoreError::CorruptStore);
+        };
+        let (version, _) = read_version(*version_raw);
+        let mut reader = &raw[1..];
+
+        let mut store = Store::new();
+        store.version = version;
+        if version < Version::V3 {
+            recover_checks::<legacy::CheckV2>(&mut reader, &mut store.checks);
+        } else {
+            framed::recover(reader, &mut store);
+        }
+        // the outage log is not recovered, it is found again with the next update
+        Ok(store)
+    }
+
+    /// Reads the rest of the store file and decodes it from the [framed] layout after verifying
+    /// its checksum.
+    ///
+    /// The `reader` still has to start with the [Version], which the checksum covers.
+    fn deserialize_framed(
+        mut reader: impl Read,
+        version: Version,
+        newer: bool,
+    ) -> Result<Self, StoreError> {
+        let mut raw = Vec::new();
+        if let Err(err) = reader.read_to_end(&mut raw) {
+            error!("could not read the store data: {err}");
+            return Err(StoreError::CorruptStore);
+        }
+        let data = verify_checksum(&raw)?;
+        framed::decode(&data[1..], version, newer)
+    }
+
+    /// Saves the store to the store file at [Store::path].
     ///
-    /// - Truncates existing file
-    /// - Optionally compresses if feature enabled
-    /// - Maintains original permissions
+    /// The store file is replaced atomically, so a crash while saving leaves either the old or
+    /// the new store file, never a partially written one. See [FileBackend] for details.
     ///
     /// # Errors
     ///
@@ -475,39 +1039,195 @@ impl Store {
     /// - Serialization fails
     /// - Trying to save a readonly [Store]
     pub fn save(&self) -> Result<(), StoreError> {
+        self.save_to(&mut FileBackend::default())
+    }
+
+    /// Saves the store to `backend`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::IsReadonly] for a readonly [Store], or the [StoreError] of the
+    /// backend if writing failed.
+    pub fn save_to(&self, backend: &mut dyn StoreBackend) -> Result<(), StoreError> {
         info!("Saving the store");
         if self.readonly {
             return Err(StoreError::IsReadonly);
         }
-        let file = match fs::File::options()
-            .read(false)
-            .write(true)
-            .append(false)
-            .create_new(false)
-            .truncate(true)
-            .create(false)
-            .open(Self::path())
-        {
-            Ok(file) => file,
-            Err(err) => match err.kind() {
-                ErrorKind::NotFound => return Err(StoreError::DoesNotExist),
-                _ => return Err(err.into()),
-            },
-        };
+        backend.save(self)
+    }
 
-        #[cfg(feature = "compression")]
-        let mut writer = zstd::Encoder::new(file, ZSTD_COMPRESSION_LEVEL)?;
-        #[cfg(not(feature = "compression"))]
-        let mut writer = file;
+    /// Imports the [Checks](Check) and [Aggregates](Aggregate) of the store file at `path`.
+    ///
+    /// This can be used to combine the stores of multiple machines, or an old store after a
+    /// reinstall. The other store is migrated to [Version::CURRENT] in memory if needed.
+    ///
+    /// [Checks](Check) are de-duplicated by their [hash](Check::get_hash), which covers the
+    /// timestamp and all other data of the [Check], so importing the same store twice does not
+    /// double anything. [Aggregates](Aggregate) and [Traces](Trace) are de-duplicated the same
+    /// way. Everything is sorted by time afterwards.
+    ///
+    /// Returns what was imported, nothing was imported if its [total](MergeReport::total) is 0.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the other store can not be loaded, see [Store::load].
+    pub fn merge_from(&mut self, path: &Path) -> Result<MergeReport, StoreError> {
+        let other = Self::load_from(path, true)?;
+        let mut report = MergeReport::default();
 
-        writer.write_all(&bincode::serialize(&self)?)?;
-        writer.flush()?;
-        Ok(())
+        let mut known: HashSet<blake3::Hash> = self.checks.iter().map(|c| c.get_hash()).collect();
+        let before = self.checks.len();
+        for check in other.checks {
+            if known.insert(check.get_hash()) {
+                self.checks.push(check);
+            }
+        }
+        report.checks = self.checks.len() - before;
+        self.checks.sort();
+
+        let before = self.aggregates.len();
+        let known_aggregates: HashSet<Aggregate> = self.aggregates.iter().copied().collect();
+        self.aggregates.extend(
+            other
+                .aggregates
+                .into_iter()
+                .filter(|a| !known_aggregates.contains(a)),
+        );
+        report.aggregates = self.aggregates.len() - before;
+        self.aggregates.sort();
+
+        let before = self.traces.len();
+        let known_traces: HashSet<Trace> = self.traces.iter().cloned().collect();
+        self.traces.extend(
+            other
+                .traces
+                .into_iter()
+                .filter(|t| !known_traces.contains(t)),
+        );
+        report.traces = self.traces.len() - before;
+        self.traces.sort_by_key(Trace::timestamp);
+
+        // the imported checks can be anywhere in time, so the outages are found again
+        if report.checks > 0 {
+            self.forget_outages();
+            self.update_outages();
+        }
+
+        info!(
+            "Imported {} checks, {} aggregates and {} traces from {}",
+            report.checks,
+            report.aggregates,
+            report.traces,
+            path.display()
+        );
+        Ok(report)
     }
 
     /// Adds a new check to the store.
-    pub fn add_check(&mut self, check: impl Into<Check>) {
-        self.checks.push(check.into());
+    ///
+    /// If [rejecting duplicates](Store::set_reject_duplicates) is enabled, the check is not added
+    /// if the store already has an identical [Check]. Returns true if the check was added.
+    pub fn add_check(&mut self, check: impl Into<Check>) -> bool {
+        self.push_check(check.into(), self.checks.len())
+    }
+
+    /// Adds `check`, unless duplicates are rejected and one of the first `known` [Checks](Check)
+    /// is a duplicate of it. Returns true if the check was added.
+    ///
+    /// A check that is older than the newest one is inserted at its place in time.
+    fn push_check(&mut self, check: Check, known: usize) -> bool {
+        if self.reject_duplicates && self.has_duplicate_of(&check, known) {
+            warn!(
+                "rejecting a duplicate check of {} at {}",
+                check.target(),
+                check.timestamp()
+            );
+            return false;
+        }
+        if self
+            .checks
+            .last()
+            .is_some_and(|last| last.timestamp() > check.timestamp())
+        {
+            let idx = self
+                .checks
+                .partition_point(|c| c.timestamp() <= check.timestamp());
+            self.checks.insert(idx, check);
+        } else {
+            self.checks.push(check);
+        }
+        true
+    }
+
+    /// Sorts the [Checks](Check) by time if they are not already.
+    fn sort_if_needed(&mut self) {
+        if !self.checks.is_sorted() {
+            warn!("the checks of the store were out of order, sorting them");
+            self.checks.sort();
+        }
+    }
+
+    /// Returns the [Checks](Check) made at or after `start` and before `end`, both unix
+    /// timestamps in seconds.
+    ///
+    /// As the [Checks](Check) are sorted by time, this is a binary search and does not look at
+    /// every [Check].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use netpulse::store::Store;
+    ///
+    /// let store = Store::load(true).unwrap();
+    /// let now = chrono::Utc::now().timestamp();
+    /// let last_hour = store.checks_between(now - 3600, now);
+    /// println!("{} checks in the last hour", last_hour.len());
+    /// ```
+    pub fn checks_between(&self, start: i64, end: i64) -> &[Check] {
+        &self.checks[self.range_between(start, end)]
+    }
+
+    /// Returns the [Checks](Check) made at or after `start`, a unix timestamp in seconds.
+    pub fn checks_since(&self, start: i64) -> &[Check] {
+        self.checks_between(start, i64::MAX)
+    }
+
+    /// Returns the range of indices of the [Checks](Check) made at or after `start` and before
+    /// `end`.
+    pub(crate) fn range_between(&self, start: i64, end: i64) -> std::ops::Range<usize> {
+        let first = self.checks.partition_point(|c| c.timestamp() < start);
+        let last = self.checks.partition_point(|c| c.timestamp() < end);
+        first..last.max(first)
+    }
+
+    /// Makes [Store::add_check] reject checks that are already in the store.
+    ///
+    /// A check is a duplicate if a [Check] that is equal in every field exists, like when a probe
+    /// sends the same checks again. Checks of targets that share an address, but differ in label,
+    /// zone, interface or probe, are not duplicates. Off by default.
+    pub fn set_reject_duplicates(&mut self, reject: bool) {
+        self.reject_duplicates = reject;
+    }
+
+    /// Returns true if one of the first `known` [Checks](Check) is identical to `check`.
+    ///
+    /// The whole [Check] is compared, like [Store::compact] does with its
+    /// [hash](Check::get_hash). All of these fields must be equal:
+    ///
+    /// - the timestamp, to the second
+    /// - the flags
+    /// - the latency
+    /// - the target, the label and the zone
+    /// - the HTTP status and the measurement
+    /// - the interface and the probe
+    ///
+    /// Only the newest of them are looked at, as duplicates come from the same round.
+    fn has_duplicate_of(&self, check: &Check, known: usize) -> bool {
+        self.checks[..known]
+            .iter()
+            .rev()
+            .take_while(|c| c.timestamp() >= check.timestamp())
+            .any(|c| c == check)
     }
 
     /// Returns a reference to the checks of this [`Store`].
@@ -515,6 +1235,154 @@ impl Store {
         &self.checks
     }
 
+    /// Returns a reference to the [Aggregates](Aggregate) of downsampled checks of this [`Store`].
+    pub fn aggregates(&self) -> &[Aggregate] {
+        &self.aggregates
+    }
+
+    /// Returns a reference to the [Traces](Trace) of failed checks of this [`Store`].
+    pub fn traces(&self) -> &[Trace] {
+        &self.traces
+    }
+
+    /// Adds a new [Trace] to the store.
+    pub fn add_trace(&mut self, trace: Trace) {
+        self.traces.push(trace);
+    }
+
+    /// Returns the [OutageLog] of this [`Store`], if it is up to date with the last [Check].
+    ///
+    /// The log is updated by the daemon with [Store::update_outages] whenever it saves the
+    /// store. Stores of older versions, stores that were filtered and stores that were changed
+    /// without updating the log have none.
+    pub fn outage_log(&self) -> Option<&OutageLog> {
+        let last = self.checks.last()?;
+        (self.outages.until() == Some(last.timestamp())).then_some(&self.outages)
+    }
+
+    /// Records the outages in the [Checks](Check) that were added since the last update in the
+    /// [OutageLog].
+    ///
+    /// Only the [Checks](Check) from the start of the [ongoing](OutageLog::ongoing) outage or
+    /// after the last update are looked at, so this is cheap to call after every round. If the
+    /// log was never updated, all [Checks](Check) are looked at once.
+    pub fn update_outages(&mut self) {
+        let from = self.outages.resume_from();
+        let range = self.range_between(from, i64::MAX);
+        let checks: Vec<&Check> = self.checks[range].iter().collect();
+        self.outages.update(&checks);
+    }
+
+    /// Drops the [OutageLog], because the [Checks](Check) were changed in a way it does not
+    /// describe anymore.
+    pub(crate) fn forget_outages(&mut self) {
+        self.outages = OutageLog::default();
+    }
+
+    /// Replaces the addresses and names in everything this [`Store`] holds with pseudonyms, so
+    /// it can be shared, see [anonymize](crate::records::anonymize).
+    ///
+    /// The [Store] is made [read only](Store::set_readonly), so the anonymized data can never
+    /// overwrite the store file.
+    pub fn anonymize(&mut self, anonymizer: &Anonymizer) {
+        let address = |addr| anonymizer.address(addr);
+        self.checks.iter_mut().for_each(|c| anonymizer.check(c));
+        self.aggregates
+            .iter_mut()
+            .for_each(|a| a.set_target(address(a.target())));
+        self.traces
+            .iter_mut()
+            .for_each(|t| t.map_addresses(address));
+        self.outages.map_targets(address);
+        self.set_readonly();
+    }
+
+    /// Rolls up all [Checks](Check) older than `older_than_days` into hourly
+    /// [Aggregates](Aggregate).
+    ///
+    /// The cutoff is rounded down to a full hour, so an hour is never split between raw
+    /// [Checks](Check) and an [Aggregate]. The raw [Checks](Check) that were rolled up are
+    /// removed from the [Store].
+    ///
+    /// Returns the number of [Checks](Check) that were rolled up.
+    pub fn downsample(&mut self, older_than_days: i64) -> usize {
+        let cutoff = chrono::Utc::now().timestamp() - older_than_days * 24 * 60 * 60;
+        let cutoff = cutoff - cutoff.rem_euclid(AGGREGATE_SPAN);
+
+        let (old, keep): (Vec<Check>, Vec<Check>) = std::mem::take(&mut self.checks)
+            .into_iter()
+            .partition(|c| c.timestamp() < cutoff);
+        self.checks = keep;
+        if old.is_empty() {
+            return 0;
+        }
+
+        let new_aggregates = aggregate_checks(&old);
+        info!(
+            "Downsampled {} checks into {} aggregates",
+            old.len(),
+            new_aggregates.len()
+        );
+        self.aggregates.extend(new_aggregates);
+        self.aggregates.sort();
+        old.len()
+    }
+
+    /// Cleans up entries that past bugs might have written to the [Store].
+    ///
+    /// Removes exact duplicates of [Checks](Check), [Aggregates](Aggregate) and
+    /// [Traces](Trace), and [Checks](Check) of [CheckType::Unknown], which no check makes on
+    /// purpose. Everything is sorted by time afterwards.
+    ///
+    /// This only changes the [Store] in memory, use [Store::save] to write the result.
+    pub fn compact(&mut self) -> CompactReport {
+        let mut report = CompactReport::default();
+
+        let before = self.checks.len();
+        self.checks
+            .retain(|c| c.calc_type().is_ok_and(|t| t != CheckType::Unknown));
+        report.unknown_checks = before - self.checks.len();
+
+        let before = self.checks.len();
+        let mut known: HashSet<blake3::Hash> = HashSet::new();
+        self.checks.retain(|c| known.insert(c.get_hash()));
+        report.duplicate_checks = before - self.checks.len();
+        self.checks.sort();
+
+        let before = self.aggregates.len();
+        let mut known_aggregates: HashSet<Aggregate> = HashSet::new();
+        self.aggregates.retain(|a| known_aggregates.insert(*a));
+        report.duplicate_aggregates = before - self.aggregates.len();
+        self.aggregates.sort();
+
+        let before = self.traces.len();
+        let mut known_traces: HashSet<Trace> = HashSet::new();
+        self.traces.retain(|t| known_traces.insert(t.clone()));
+        report.duplicate_traces = before - self.traces.len();
+        self.traces.sort_by_key(Trace::timestamp);
+
+        report
+    }
+
+    /// Returns the number of backups of the store file that [Store::save] keeps.
+    ///
+    /// This value is read from the [ENV_BACKUPS] environment variable. If it is not set or not a
+    /// valid number, no backups are made.
+    pub fn backups(&self) -> usize {
+        std::env::var(ENV_BACKUPS)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Returns the age in days after which checks should be downsampled, if configured.
+    ///
+    /// This value is read from the [ENV_DOWNSAMPLE_DAYS] environment variable. If it is not set
+    /// or not a valid number, checks are never downsampled.
+    pub fn downsample_after_days(&self) -> Option<i64> {
+        std::env::var(ENV_DOWNSAMPLE_DAYS).ok()?.parse().ok()
+    }
+
     /// Returns the check interval in seconds.
     ///
     /// This determines how frequently the daemon performs checks.
@@ -561,7 +1429,7 @@ impl Store {
 
     /// Generates SHA-256 hash of the store file on disk.
     ///
-    /// This calls `sha256sum` on the store file.
+    /// This calls `sha256sum` on the [file of this store](Store::file_path).
     ///
     /// # External Dependencies
     ///
@@ -573,7 +1441,7 @@ impl Store {
     /// - sha256sum command fails
     /// - Output parsing fails
     pub fn get_hash_of_file(&self) -> Result<String, StoreError> {
-        let out = Command::new("sha256sum").arg(Self::path()).output()?;
+        let out = Command::new("sha256sum").arg(self.file_path()).output()?;
 
         if !out.status.success() {
             error!(
@@ -591,19 +1459,29 @@ impl Store {
 
     /// Creates and adds checks for all configured targets.
     ///
-    /// Iterates through [CheckType::default_enabled] and [TARGETS] and makes the [Checks](Check).
+    /// Iterates through [CheckType::default_enabled], the configured
+    /// [DNS checks](crate::config::DnsConfig) and the [targets](crate::config::Target) of
+    /// `config` and makes the [Checks](Check).
     ///
-    /// Uses [Self::primitive_make_checks] under the hood, which starts a new thread per [Check].
-    pub fn make_checks(&mut self) -> Vec<&Check> {
-        let last_old = self
-            .checks
-            .iter()
-            .enumerate()
-            .last()
-            .map(|a| a.0)
-            .unwrap_or(0);
+    /// Uses [Self::primitive_make_checks] under the hood, which makes the [Checks](Check) on a pool
+    /// of threads with a deadline for the whole round.
+    ///
+    /// If [tracing on failure](crate::config::TraceConfig) is enabled, the path to each target
+    /// with a failed check is traced afterwards and the [Traces](Trace) are added as well.
+    pub fn make_checks(&mut self, config: &Config) -> Vec<&Check> {
+        let last_old = self.checks.len();
 
-        Self::primitive_make_checks(&mut self.checks);
+        // checks of the same round can look alike, like HTTP checks of two URLs of one host, so
+        // only older checks count as duplicates
+        let mut new_checks = Vec::new();
+        Self::primitive_make_checks(&mut new_checks, config);
+        for check in new_checks {
+            self.push_check(check, last_old);
+        }
+        #[cfg(feature = "traceroute")]
+        self.trace_failures(last_old, config);
+        #[cfg(feature = "bandwidth")]
+        self.sample_bandwidth(config);
 
         let mut made_checks = Vec::new();
         for new_check in self.checks.iter().skip(last_old) {
@@ -613,31 +1491,133 @@ impl Store {
         made_checks
     }
 
-    /// Creates [Checks](Check) for all configured targets in parallel.
+    /// Samples the throughput if the last sample is [every](crate::config::BandwidthConfig::every)
+    /// rounds old.
+    ///
+    /// Does nothing unless a [url](crate::config::BandwidthConfig::url) is set. The sample is
+    /// taken after the other checks of the round, so it does not delay them.
+    #[cfg(feature = "bandwidth")]
+    fn sample_bandwidth(&mut self, config: &Config) {
+        let Some(url) = config.bandwidth.url.clone() else {
+            return;
+        };
+        let period = self.period_seconds();
+        let interval = config.bandwidth.every as i64 * period;
+        let last = self
+            .checks
+            .iter()
+            .rev()
+            .find(|c| matches!(c.calc_type(), Ok(CheckType::Bandwidth)));
+        // half a period of slack, rounds do not start at the exact same second
+        if let Some(last) = last {
+            if chrono::Utc::now().timestamp() - last.timestamp() < interval - period / 2 {
+                return;
+            }
+        }
+
+        let target = Target {
+            address: std::net::Ipv4Addr::UNSPECIFIED.into(),
+            zone: None,
+            label: Some(crate::checks::bandwidth::BANDWIDTH_LABEL.to_string()),
+            url: Some(url),
+            interface: None,
+            source: None,
+        };
+        let check = CheckType::Bandwidth.make_target(&target);
+        if let Some(crate::records::Measurement::Throughput { bytes_per_second }) =
+            check.measurement()
+        {
+            info!(
+                "sampled the throughput: {}",
+                crate::analyze::fmt_throughput(bytes_per_second)
+            );
+        }
+        self.add_check(check);
+    }
+
+    /// Traces the path to every target with a failed [Check] from index `first_new` on.
+    ///
+    /// Does nothing unless [on_failure](crate::config::TraceConfig::on_failure) is set. The
+    /// targets are traced in parallel, once per target and zone even if several checks of it
+    /// failed, see [failed_targets].
+    #[cfg(feature = "traceroute")]
+    fn trace_failures(&mut self, first_new: usize, config: &Config) {
+        if !config.trace.on_failure {
+            return;
+        }
+        let failed = failed_targets(&self.checks[first_new..]);
+        if failed.is_empty() {
+            return;
+        }
+        if !can_use_raw_sockets() {
+            return;
+        }
+
+        let max_hops = config.trace.max_hops;
+        let handles: Vec<_> = failed
+            .into_iter()
+            .map(|(target, zone)| {
+                let bind = crate::checks::bind::Bind {
+                    zone,
+                    ..Default::default()
+                };
+                std::thread::spawn(move || {
+                    crate::checks::traceroute::traceroute_via(target, max_hops, &bind)
+                })
+            })
+            .collect();
+        for handle in handles {
+            match handle.join() {
+                Ok(Ok(trace)) => {
+                    info!(
+                        "traced the path to {} after a failed check: {}",
+                        trace.target(),
+                        trace.segment()
+                    );
+                    self.traces.push(trace);
+                }
+                Ok(Err(err)) => error!("error while tracing a failed check: {err}"),
+                Err(_) => error!("a traceroute thread panicked"),
+            }
+        }
+    }
+
+    /// Creates [Checks](Check) for all targets configured in `config` in parallel.
     ///
     /// Uses multiple threads to perform network checks simultaneously, improving overall
     /// performance when multiple checks are IO-bound (waiting for network responses).
     ///
     /// # Implementation Details
     ///
-    /// - Creates one thread per target/check-type combination
-    /// - Uses [`Arc<Mutex<Vec>>`] to collect results safely
-    /// - Joins all threads before returning
-    /// - Skips ICMP checks if CAP_NET_RAW capability is missing
+    /// - A pool of at most [MAX_CHECK_WORKERS] threads works through the target/check-type
+    ///   combinations
+    /// - Results are collected over a channel until all checks are done or the
+    ///   [deadline](Config::round_deadline) has passed
+    /// - Checks that started but did not finish in time are recorded as failed with
+    ///   [CheckFlag::Timeout](crate::records::CheckFlag::Timeout), their threads are left to
+    ///   finish on their own
+    /// - Checks that did not even start in time are skipped, nothing is known about them. They
+    ///   are made again in the next round
+    /// - Checks are made as configured, see [CheckType::make_configured]. Retries and bursts of
+    ///   ICMP echoes extend the deadline accordingly, see [Config::round_deadline]
+    /// - Gateway checks are made against the default gateways instead of the targets
+    /// - DNS checks are made as [configured](crate::config::DnsConfig)
+    /// - NTP checks are made against the [NTP servers](crate::config::NtpConfig) instead
+    /// - Skips ICMP, MTU and gateway checks in [user mode](crate::paths::user_mode) without the
+    ///   CAP_NET_RAW capability
+    /// - The checks record the [probe](Config::probe) that made them
+    ///
+    /// This way, a hung check can not hold up the caller for longer than the
+    /// [deadline](Config::round_deadline).
     ///
     /// # Arguments
     ///
     /// * `buf` - Vector to store the created checks
-    ///
-    /// # Thread Safety
-    ///
-    /// - Thread-safe collection of results via [`Arc<Mutex>`]
-    /// - Waits for all threads to complete before returning
-    /// - Returns error if thread join fails or mutex is poisoned
+    /// * `config` - The [Config] with the targets and how to check them
     ///
     /// # Performance
     ///
-    /// Creates `n * m` threads where:
+    /// Makes `n * m` checks where:
     /// - n = number of enabled check types
     /// - m = number of targets
     ///
@@ -645,48 +1625,81 @@ impl Store {
     ///
     /// # Panics
     ///
-    /// Panics if:
-    /// - Thread join fails
-    /// - Mutex is poisoned
-    /// - Target IP address is invalid (should be impossible with constant targets)
+    /// Panics if a mutex is poisoned.
     ///
     /// # Example
     ///
     /// ```rust
+    /// use netpulse::config::Config;
     /// use netpulse::store::Store;
     ///
     /// let mut checks = Vec::new();
-    /// Store::primitive_make_checks(&mut checks);
+    /// Store::primitive_make_checks(&mut checks, &Config::default());
     /// println!("Created {} checks", checks.len());
     /// ```
-    pub fn primitive_make_checks(buf: &mut Vec<Check>) {
-        let arcbuf = Arc::new(Mutex::new(Vec::new()));
-        let mut threads = Vec::new();
-        for check_type in CheckType::default_enabled() {
+    pub fn primitive_make_checks(buf: &mut Vec<Check>, config: &Config) {
+        let probe = config.probe();
+        let first = buf.len();
+        let mut jobs = Vec::new();
+        let check_types = CheckType::default_enabled()
+            .iter()
+            .copied()
+            .chain(config.dns.check_types())
+            .chain((!config.ntp.servers.is_empty()).then_some(CheckType::Ntp));
+        for check_type in check_types {
             trace!("check type: {check_type}");
-            if *check_type == CheckType::Icmp && !has_cap_net_raw() {
-                warn!("Does not have CAP_NET_RAW, can't use {check_type}, skipping");
+            if matches!(
+                check_type,
+                CheckType::Icmp | CheckType::Mtu | CheckType::Gateway
+            ) && !can_use_raw_sockets()
+            {
                 continue;
             }
-            for target in TARGETS {
-                let thread_ab = arcbuf.clone();
-                threads.push(std::thread::spawn(move || {
-                    trace!("start thread for {target} with {check_type}");
-                    let check = check_type.make(
-                        std::net::IpAddr::from_str(target)
-                            .expect("a target constant was not an Ip Address"),
-                    );
-                    thread_ab.lock().expect("lock is poisoned").push(check);
-                    trace!("end thread for {target} with {check_type}");
-                }));
+            if check_type == CheckType::Gateway {
+                for target in crate::checks::gateway::gateway_targets() {
+                    jobs.push((check_type, target));
+                }
+                continue;
             }
+            let targets = match check_type {
+                CheckType::Ntp => &config.ntp.servers,
+                _ => &config.targets,
+            };
+            for target in targets {
+                jobs.push((check_type, target.clone()));
+            }
+        }
+
+        let deadline = Instant::now() + config.round_deadline();
+        // the checks run on their own threads, which may outlive the round
+        let config = Arc::new(config.clone());
+        let round = run_pool(
+            jobs,
+            MAX_CHECK_WORKERS,
+            deadline,
+            move |(check_type, target): (CheckType, Target)| {
+                trace!("start check for {} with {check_type}", target.address);
+                let check = check_type.make_configured(&target, &config);
+                trace!("end check for {} with {check_type}", target.address);
+                check
+            },
+        );
+        buf.extend(round.done);
+        if round.skipped > 0 {
+            warn!(
+                "{} checks could not start before the deadline of the round, skipping them",
+                round.skipped
+            );
         }
-        for th in threads {
-            th.join().expect("could not join thread");
+        for (check_type, target) in round.unfinished {
+            warn!(
+                "{check_type} check for {} did not finish in time, recording it as failed",
+                target.address
+            );
+            buf.push(check_type.timed_out(&target));
         }
-        let abuf = arcbuf.lock().unwrap();
-        for check in abuf.iter() {
-            buf.push(*check);
+        for check in &mut buf[first..] {
+            check.set_probe(Some(probe.clone()));
         }
     }
 
@@ -695,14 +1708,98 @@ impl Store {
         self.version
     }
 
+    /// Returns all fields of this [Store] but its metadata, for [PartialEq] and [Hash].
+    #[allow(clippy::type_complexity)]
+    fn data(
+        &self,
+    ) -> (
+        Version,
+        &[Check],
+        &[Aggregate],
+        &[Trace],
+        &OutageLog,
+        bool,
+        &Option<PathBuf>,
+        bool,
+    ) {
+        let Self {
+            version,
+            checks,
+            aggregates,
+            traces,
+            outages,
+            readonly,
+            source,
+            reject_duplicates,
+            metadata: _,
+        } = self;
+        (
+            *version,
+            checks,
+            aggregates,
+            traces,
+            outages,
+            *readonly,
+            source,
+            *reject_duplicates,
+        )
+    }
+
+    /// Returns when this [Store] was created, as unix timestamp.
+    ///
+    /// Store files older than [Version::V3] do not know this, for them it is the time of their
+    /// oldest [Check] or [Aggregate].
+    pub fn created(&self) -> i64 {
+        self.metadata.created
+    }
+
+    /// Returns the unix timestamp of the oldest [Check] or [Aggregate] of this [Store].
+    fn oldest_timestamp(&self) -> Option<i64> {
+        let checks = self.checks.iter().map(Check::timestamp);
+        let aggregates = self.aggregates.iter().map(Aggregate::start);
+        checks.chain(aggregates).min()
+    }
+
+    /// Returns when the store file of this [Store] was last saved, as unix timestamp.
+    ///
+    /// This is the time of the save before this [Store] was loaded, saving it does not change
+    /// it. [None] if it was never saved.
+    pub fn last_saved(&self) -> Option<i64> {
+        self.metadata.saved
+    }
+
+    /// Returns the version of netpulse that last saved the store file of this [Store].
+    ///
+    /// This is usually the daemon, see [Store::last_saved].
+    pub fn written_by(&self) -> &str {
+        &self.metadata.written_by
+    }
+
+    /// Returns the random ID of this [Store].
+    ///
+    /// The ID is made when the [Store] is created and kept in its store file, so it tells
+    /// store files apart, like those of multiple machines. A copy or backup of the store file
+    /// has the same ID.
+    pub fn instance_id(&self) -> uuid::Uuid {
+        self.metadata.instance
+    }
+
     /// Returns a mutable reference to the checks of this [`Store`].
+    ///
+    /// The [Checks](Check) must stay sorted by time, see [Store::checks_between].
     pub fn checks_mut(&mut self) -> &mut Vec<Check> {
         &mut self.checks
     }
 
+    /// Returns a mutable reference to the aggregates of this [`Store`].
+    pub fn aggregates_mut(&mut self) -> &mut Vec<Aggregate> {
+        &mut self.aggregates
+    }
+
     /// Reads only the [Version] from a store file without loading the entire [Store].
     ///
-    /// This function efficiently checks the store version by:
+    /// Since [Version::V3], the version is read from the uncompressed [header] at the start of
+    /// the store file. For older store files, this function checks the store version by:
     /// 1. Opening the store file (decompressing it if enabled)
     /// 2. Deserializing only the version field
     /// 3. Skipping the rest of the data
@@ -715,13 +1812,14 @@ impl Store {
     /// # Feature Flags
     ///
     /// If the "compression" feature is enabled, this function will decompress
-    /// the store file using [zstd] before reading the version.
+    /// store files older than [Version::V3] using [zstd] before reading the version.
     ///
     /// # Errors
     ///
     /// Returns [StoreError] if:
     /// - Store file doesn't exist ([`StoreError::DoesNotExist`])
     /// - Store file is corrupt or truncated ([`StoreError::Load`])
+    /// - Store file is newer than this version of netpulse ([`StoreError::NewerStore`])
     /// - File permissions prevent reading ([`StoreError::Io`])
     /// - Decompression fails (with "compression" feature) ([`StoreError::Io`])
     ///
@@ -738,6 +1836,14 @@ impl Store {
     /// }
     /// ```
     pub fn peek_file_version() -> Result<Version, StoreError> {
+        Self::peek_file_version_of(&Self::path())
+    }
+
+    /// Reads only the [Version] from the store file at `path`.
+    ///
+    /// See [Store::peek_file_version]. With the `sqlite` feature, `path` can also be a SQLite
+    /// database.
+    pub fn peek_file_version_of(path: &Path) -> Result<Version, StoreError> {
         #[derive(Deserialize)]
         struct VersionOnly {
             version: Version,
@@ -745,16 +1851,29 @@ impl Store {
             _rest: serde::de::IgnoredAny,
         }
 
-        let file = std::fs::File::open(Self::path())?;
-        #[cfg(feature = "compression")]
-        let reader = zstd::Decoder::new(file)?;
-        #[cfg(not(feature = "compression"))]
-        let reader = file;
+        #[cfg(feature = "sqlite")]
+        if sqlite::SqliteStore::is_sqlite(path) {
+            return sqlite::SqliteStore::open(path)?.version();
+        }
+
+        let mut file = std::fs::File::open(path)?;
+        let (header, start) = Header::read(&mut file)?;
+        if let Some(header) = header {
+            return Version::of_file(header.version);
+        }
 
+        let reader = decompress(std::io::Cursor::new(start).chain(file))?;
         let version_only: VersionOnly = bincode::deserialize_from(reader)?;
         Ok(version_only.version)
     }
 
+    /// Returns the path of the file this [Store] was loaded from.
+    ///
+    /// This is [Store::path], unless the [Store] was loaded with [Store::load_from].
+    pub fn file_path(&self) -> PathBuf {
+        self.source.clone().unwrap_or_else(Self::path)
+    }
+
     /// True if this [Store] is read only
     pub fn readonly(&self) -> bool {
         self.readonly
@@ -766,17 +1885,714 @@ impl Store {
     }
 }
 
-fn has_cap_net_raw() -> bool {
-    // First check if we're root (which implies all capabilities)
-    if nix::unistd::getuid().is_root() {
-        return true;
+/// Wraps `reader` to decompress it if the feature is enabled.
+fn decompress(reader: impl Read + 'static) -> Result<Box<dyn Read>, StoreError> {
+    #[cfg(feature = "compression")]
+    return Ok(Box::new(zstd::Decoder::new(reader)?));
+    #[cfg(not(feature = "compression"))]
+    return Ok(Box::new(reader));
+}
+
+/// What [run_pool] made of its jobs.
+struct PoolRound<J, R> {
+    /// Results of the jobs that finished in time
+    done: Vec<R>,
+    /// Jobs that were started, but did not finish in time
+    unfinished: Vec<J>,
+    /// How many jobs were never started
+    skipped: usize,
+}
+
+/// Works through `jobs` with a pool of at most `workers` threads until all are done or the
+/// `deadline` has passed, see [Store::primitive_make_checks].
+///
+/// Jobs that were not started by the deadline are dropped. The threads of unfinished jobs are
+/// left to finish on their own.
+///
+/// # Panics
+///
+/// Panics if a mutex is poisoned.
+fn run_pool<J, R>(
+    jobs: Vec<J>,
+    workers: usize,
+    deadline: Instant,
+    work: impl Fn(J) -> R + Clone + Send + 'static,
+) -> PoolRound<J, R>
+where
+    J: Clone + Send + 'static,
+    R: Send + 'static,
+{
+    // jobs can be alike, like targets that share an address, so they are told apart by index
+    let queue: VecDeque<(usize, J)> = jobs.into_iter().enumerate().collect();
+    let mut pending: Vec<(usize, J)> = queue.iter().cloned().collect();
+    let workers = pending.len().min(workers);
+    let queue = Arc::new(Mutex::new(queue));
+
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..workers {
+        let queue = queue.clone();
+        let tx = tx.clone();
+        let work = work.clone();
+        std::thread::spawn(move || loop {
+            let Some((index, job)) = queue.lock().expect("lock is poisoned").pop_front() else {
+                break;
+            };
+            if tx.send((index, work(job))).is_err() {
+                break; // the round is already over
+            }
+        });
     }
+    drop(tx);
+
+    let mut done = Vec::with_capacity(pending.len());
+    while !pending.is_empty() {
+        match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok((index, result)) => {
+                pending.retain(|(i, _)| *i != index);
+                done.push(result);
+            }
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    // jobs that were not even started yet say nothing, unlike the ones that hung
+    let skipped: Vec<usize> = queue
+        .lock()
+        .expect("lock is poisoned")
+        .drain(..)
+        .map(|(index, _)| index)
+        .collect();
+    pending.retain(|(i, _)| !skipped.contains(i));
+    PoolRound {
+        done,
+        unfinished: pending.into_iter().map(|(_, job)| job).collect(),
+        skipped: skipped.len(),
+    }
+}
 
-    // Check current process capabilities
-    if let Ok(caps) = caps::read(None, caps::CapSet::Effective) {
-        caps.contains(&caps::Capability::CAP_NET_RAW)
-    } else {
-        warn!("Could not read capabilities");
-        false
+/// Reads the raw [Version] at the start of the data of a store file.
+///
+/// # Errors
+///
+/// Returns [StoreError::CorruptStore] if it can not be read or differs from the [Header].
+fn read_version_raw(header: Option<&Header>, reader: &mut impl Read) -> Result<u8, StoreError> {
+    let mut version_raw = [0u8; 1];
+    if let Err(err) = reader.read_exact(&mut version_raw) {
+        error!("could not read the version of the store: {err}");
+        return Err(StoreError::CorruptStore);
+    }
+    if header.is_some_and(|h| h.version != version_raw[0]) {
+        error!("the version in the header of the store file does not match its data");
+        return Err(StoreError::CorruptStore);
+    }
+    Ok(version_raw[0])
+}
+
+/// Reads the [Version] of a store file from its first byte.
+///
+/// Also returns whether the store file is newer than this version of netpulse. Newer store files
+/// keep the [framed] layout, so they are read as [Version::CURRENT], skipping what is unknown,
+/// see [newer stores](framed#newer-stores).
+fn read_version(raw: u8) -> (Version, bool) {
+    match Version::try_from(raw) {
+        Ok(version) => (version, false),
+        Err(_) => {
+            warn!("The store has version {raw}, which is newer than this version of netpulse, loading it as READONLY and skipping what is unknown");
+            (Version::CURRENT, true)
+        }
+    }
+}
+
+/// Decodes a length prefixed sequence element by element until the data ends or can not be
+/// decoded anymore.
+///
+/// Returns true if all elements of the sequence could be decoded.
+fn recover_seq<T: serde::de::DeserializeOwned>(
+    reader: &mut &[u8],
+    buf: &mut Vec<T>,
+    what: &str,
+) -> bool {
+    let len: u64 = match bincode::deserialize_from(&mut *reader) {
+        Ok(len) => len,
+        Err(err) => {
+            warn!("Could not read the number of {what}: {err}");
+            return false;
+        }
+    };
+    for idx in 0..len {
+        match bincode::deserialize_from(&mut *reader) {
+            Ok(item) => buf.push(item),
+            Err(err) => {
+                warn!("Recovered {idx} of {len} {what}, stopped at: {err}");
+                return false;
+            }
+        }
+    }
+    info!("Recovered all {len} {what}");
+    true
+}
+
+/// Like [recover_seq] for [Checks](Check), but decodes them in the layout `C` of an older
+/// [Version] and converts them.
+fn recover_checks<C: serde::de::DeserializeOwned + Into<Check>>(
+    reader: &mut &[u8],
+    buf: &mut Vec<Check>,
+) -> bool {
+    let mut old: Vec<C> = Vec::new();
+    let complete = recover_seq(reader, &mut old, "checks");
+    buf.extend(old.into_iter().map(Into::into));
+    complete
+}
+
+/// Returns what tells if the file at `path` was replaced or changed: its inode on unix, its
+/// length and its modification time. [None] if it does not exist.
+fn file_identity(path: &Path) -> Option<(u64, u64, Option<std::time::SystemTime>)> {
+    let meta = fs::metadata(path).ok()?;
+    #[cfg(unix)]
+    let inode = std::os::unix::fs::MetadataExt::ino(&meta);
+    #[cfg(not(unix))]
+    let inode = 0;
+    Some((inode, meta.len(), meta.modified().ok()))
+}
+
+/// Appends the [blake3] checksum of `data` to `data`.
+fn append_checksum(data: &mut Vec<u8>) {
+    let checksum = blake3::hash(data);
+    data.extend_from_slice(checksum.as_bytes());
+}
+
+/// Verifies the checksum at the end of `raw` and returns the data without it.
+///
+/// # Errors
+///
+/// Returns [StoreError::CorruptStore] if `raw` is too short to contain a checksum or the checksum
+/// does not match the data.
+fn verify_checksum(raw: &[u8]) -> Result<&[u8], StoreError> {
+    if raw.len() < CHECKSUM_LEN {
+        error!("the store data is too short to contain a checksum");
+        return Err(StoreError::CorruptStore);
+    }
+    let (data, checksum) = raw.split_at(raw.len() - CHECKSUM_LEN);
+    if blake3::hash(data).as_bytes() != checksum {
+        error!("the checksum of the store data does not match");
+        return Err(StoreError::CorruptStore);
+    }
+    Ok(data)
+}
+
+/// Returns the targets of the failed `checks` with their zone, each once.
+///
+/// A link-local target like `fe80::1` is a different host in every zone, so `fe80::1%eth0` and
+/// `fe80::1%eth1` are two targets.
+#[cfg(feature = "traceroute")]
+fn failed_targets(checks: &[Check]) -> Vec<(std::net::IpAddr, Option<String>)> {
+    let mut failed: Vec<_> = checks
+        .iter()
+        .filter(|c| !c.is_success())
+        .map(|c| (c.target(), c.zone().map(str::to_string)))
+        .collect();
+    failed.sort();
+    failed.dedup();
+    failed
+}
+
+/// Returns false if raw sockets can not be opened and checks that need them should be skipped.
+///
+/// The daemon keeps CAP_NET_RAW when it drops its privileges, so checks that need it are only
+/// skipped in [user mode](crate::paths::user_mode), where they can not work. Otherwise, a missing
+/// capability is a broken setup, and the failed checks show it.
+fn can_use_raw_sockets() -> bool {
+    if crate::paths::user_mode() && !crate::checks::has_cap_net_raw() {
+        trace!("no CAP_NET_RAW in user mode, skipping checks that need raw sockets");
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let store = Store::new();
+        let mut data = bincode::serialize(&store).unwrap();
+        append_checksum(&mut data);
+
+        let verified = verify_checksum(&data).unwrap();
+        assert_eq!(bincode::deserialize::<Store>(verified).unwrap(), store);
+
+        // truncated
+        assert!(matches!(
+            verify_checksum(&data[..data.len() - 1]),
+            Err(StoreError::CorruptStore)
+        ));
+        assert!(matches!(
+            verify_checksum(&data[..4]),
+            Err(StoreError::CorruptStore)
+        ));
+
+        // flipped bit
+        let mut flipped = data.clone();
+        flipped[1] ^= 1;
+        assert!(matches!(
+            verify_checksum(&flipped),
+            Err(StoreError::CorruptStore)
+        ));
+    }
+
+    #[test]
+    fn test_recover_truncated() {
+        use crate::records::CheckFlag;
+
+        // the layout before the framed one, see test_recover in framed
+        let target: std::net::IpAddr = "1.1.1.1".parse().unwrap();
+        let flags: flagset::FlagSet<CheckFlag> = CheckFlag::Success | CheckFlag::TypeHTTP;
+        let old_checks: Vec<_> = (0..10i64)
+            .map(|minute| (minute * 60, flags.bits() as u16, Some(10u16), target))
+            .collect();
+        let data = bincode::serialize(&(Version::V2, &old_checks)).unwrap();
+
+        let recovered = Store::recover(&data).unwrap();
+        assert_eq!(recovered.checks().len(), 10);
+
+        // cut off in the middle of the last check
+        let check_len = bincode::serialized_size(&old_checks[0]).unwrap() as usize;
+        let cut = 1 + 8 + 9 * check_len + check_len / 2;
+        let recovered = Store::recover(&data[..cut]).unwrap();
+        assert_eq!(
+            recovered.checks(),
+            &Store::recover(&data).unwrap().checks()[..9]
+        );
+
+        assert!(matches!(Store::recover(&[]), Err(StoreError::CorruptStore)));
+    }
+
+    #[test]
+    fn test_migrate_v2() {
+        use crate::records::CheckFlag;
+
+        // same layout as legacy::StoreV2, the latency is in milliseconds
+        let target: std::net::IpAddr = "1.1.1.1".parse().unwrap();
+        let flags: flagset::FlagSet<CheckFlag> = CheckFlag::Success | CheckFlag::TypeHTTP;
+        // the flags were serialized as a u16
+        let raw_flags = flags.bits() as u16;
+        let old = (
+            Version::V2,
+            vec![(60i64, raw_flags, Some(65_000u16), target)],
+        );
+        let data = bincode::serialize(&old).unwrap();
+
+        let mut store: Store = bincode::deserialize::<legacy::StoreV2>(&data)
+            .unwrap()
+            .into();
+        store.migrate_to_current().unwrap();
+        assert_eq!(store.version(), Version::CURRENT);
+        assert_eq!(
+            store.checks(),
+            &[Check::new(
+                chrono::DateTime::from_timestamp(60, 0).unwrap(),
+                flags,
+                Some(Duration::from_secs(65)),
+                target
+            )]
+        );
+        assert_eq!(store.checks()[0].latency_us(), Some(65_000_000));
+        assert_eq!(Store::recover(&data).unwrap().checks(), store.checks());
+    }
+
+    #[test]
+    fn test_recover_traces() {
+        let time = chrono::DateTime::from_timestamp(60, 0).unwrap();
+        let mut store = Store::new();
+        store.add_check(Check::new(
+            time,
+            crate::records::CheckFlag::TypeHTTP,
+            None,
+            "1.1.1.1".parse().unwrap(),
+        ));
+        store.add_trace(Trace::new(
+            time,
+            "1.1.1.1".parse().unwrap(),
+            vec![Some("192.168.1.1".parse().unwrap()), None],
+            false,
+        ));
+        let mut data = framed::encode(&store).unwrap();
+        append_checksum(&mut data);
+        assert_eq!(Store::recover(&data).unwrap(), store);
+    }
+
+    #[test]
+    fn test_compact() {
+        use crate::records::CheckFlag;
+
+        let target = "1.1.1.1".parse().unwrap();
+        let check = |minute: i64, flags: flagset::FlagSet<CheckFlag>| {
+            Check::new(
+                chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                flags,
+                None,
+                target,
+            )
+        };
+        let ok = CheckFlag::Success | CheckFlag::TypeHTTP;
+        let mut store = Store::new();
+        store.add_check(check(2, ok));
+        store.add_check(check(1, ok));
+        store.add_check(check(2, ok));
+        store.add_check(check(3, CheckFlag::Success.into()));
+        store.add_check(check(1, ok));
+
+        let report = store.compact();
+        assert_eq!(report.duplicate_checks, 2);
+        assert_eq!(report.unknown_checks, 1);
+        assert_eq!(report.total(), 3);
+        assert_eq!(store.checks(), &[check(1, ok), check(2, ok)]);
+        assert_eq!(store.compact(), CompactReport::default());
+    }
+
+    #[test]
+    fn test_run_pool() {
+        // jobs that are alike all get their result
+        let far = Instant::now() + Duration::from_secs(10);
+        let mut round = run_pool(vec![("1.1.1.1", 1), ("1.1.1.1", 2)], 4, far, |job| job);
+        round.done.sort();
+        assert_eq!(round.done, vec![("1.1.1.1", 1), ("1.1.1.1", 2)]);
+        assert!(round.unfinished.is_empty());
+        assert_eq!(round.skipped, 0);
+
+        // the one worker hangs on the first job, the others never start
+        let soon = Instant::now() + Duration::from_millis(100);
+        let round = run_pool(vec![1, 2, 3], 1, soon, |job| {
+            std::thread::sleep(Duration::from_millis(500));
+            job
+        });
+        assert!(round.done.is_empty());
+        assert_eq!(round.unfinished, vec![1]);
+        assert_eq!(round.skipped, 2);
+    }
+
+    #[test]
+    fn test_merge_from() {
+        use crate::records::CheckFlag;
+
+        let check = |minute: i64| {
+            Check::new(
+                chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                None,
+                "1.1.1.1".parse().unwrap(),
+            )
+        };
+        let mut store = Store::new();
+        store.add_check(check(120));
+
+        // a store that only has downsampled checks left
+        let mut other = Store::new();
+        other.aggregates = aggregate_checks(&[check(1), check(2)]);
+        let path = std::env::temp_dir().join(format!("netpulse-merge-{}", std::process::id()));
+        other.write_to(fs::File::create(&path).unwrap()).unwrap();
+
+        let report = store.merge_from(&path).unwrap();
+        assert_eq!(
+            report,
+            MergeReport {
+                checks: 0,
+                aggregates: 1,
+                traces: 0
+            }
+        );
+        assert_eq!(store.aggregates(), other.aggregates());
+        assert_eq!(store.merge_from(&path).unwrap().total(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reject_duplicates() {
+        use crate::records::CheckFlag;
+
+        let time = chrono::DateTime::from_timestamp(60, 0).unwrap();
+        let check = |target: &str| {
+            Check::new(
+                time,
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                Some(Duration::from_millis(10)),
+                target.parse().unwrap(),
+            )
+        };
+        let mut store = Store::new();
+        assert!(store.add_check(check("1.1.1.1")));
+        assert!(store.add_check(check("1.1.1.1")));
+        assert_eq!(store.checks().len(), 2);
+
+        store.set_reject_duplicates(true);
+        assert!(!store.add_check(check("1.1.1.1")));
+        assert!(store.add_check(check("1.0.0.1")));
+        assert_eq!(store.checks().len(), 3);
+
+        // two targets that share an address, checked over two interfaces or with two labels
+        let mut wifi = check("1.1.1.1");
+        wifi.set_interface(Some("wlan0".to_string()));
+        assert!(store.add_check(wifi.clone()));
+        let mut labeled = check("1.1.1.1");
+        labeled.set_label(Some("cloudflare".to_string()));
+        assert!(store.add_check(labeled));
+        assert!(!store.add_check(wifi));
+        assert_eq!(store.checks().len(), 5);
+    }
+
+    #[test]
+    fn test_update_outages() {
+        use crate::records::CheckFlag;
+
+        let round = |time: i64, ok: bool| {
+            let builder = Check::builder()
+                .target([1, 1, 1, 1])
+                .http()
+                .at_timestamp(time);
+            if ok {
+                builder.success().latency(10).build()
+            } else {
+                builder.failure(CheckFlag::Timeout).build()
+            }
+        };
+        let mut store = Store::new();
+        assert!(store.outage_log().is_none());
+        for (idx, ok) in [true, false, false].into_iter().enumerate() {
+            store.add_check(round(idx as i64 * 60, ok));
+            store.update_outages();
+        }
+        let ongoing = store.outage_log().unwrap().ongoing().unwrap();
+        assert_eq!((ongoing.start(), ongoing.end()), (60, 120));
+
+        // a new round makes the log stale until it is updated
+        store.add_check(round(180, true));
+        assert!(store.outage_log().is_none());
+        store.update_outages();
+        let log = store.outage_log().unwrap();
+        assert!(log.ongoing().is_none());
+        assert_eq!(log.records().len(), 1);
+        assert_eq!(log.records()[0].end(), 120);
+
+        let data = bincode::serialize(&store).unwrap();
+        assert_eq!(bincode::deserialize::<Store>(&data).unwrap(), store);
+
+        store.forget_outages();
+        assert!(store.outage_log().is_none());
+    }
+
+    #[test]
+    fn test_checks_between() {
+        use crate::records::CheckFlag;
+
+        let target: std::net::IpAddr = "1.1.1.1".parse().unwrap();
+        let check = |minute: i64| {
+            Check::new(
+                chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                None,
+                target,
+            )
+        };
+        let mut store = Store::new();
+        for minute in [0, 1, 2, 5, 3, 4, 6] {
+            store.add_check(check(minute));
+        }
+        assert!(store.checks().is_sorted());
+
+        let minutes = |checks: &[Check]| checks.iter().map(|c| c.timestamp() / 60).collect();
+        let between: Vec<i64> = minutes(store.checks_between(2 * 60, 5 * 60));
+        assert_eq!(between, vec![2, 3, 4]);
+        let since: Vec<i64> = minutes(store.checks_since(5 * 60));
+        assert_eq!(since, vec![5, 6]);
+        assert!(store.checks_between(5 * 60, 2 * 60).is_empty());
+    }
+
+    #[test]
+    fn test_stream_checks() {
+        use crate::records::CheckFlag;
+
+        let mut store = Store::new();
+        for minute in 0..10 {
+            let flags = if minute % 3 == 0 {
+                CheckFlag::TypeHTTP.into()
+            } else {
+                CheckFlag::Success | CheckFlag::TypeHTTP
+            };
+            store.add_check(Check::new(
+                chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                flags,
+                None,
+                "1.1.1.1".parse().unwrap(),
+            ));
+        }
+        store.aggregates = aggregate_checks(&store.checks[..2]);
+        let path = std::env::temp_dir().join(format!("netpulse-stream-{}", std::process::id()));
+        store.write_to(fs::File::create(&path).unwrap()).unwrap();
+
+        let streamed: Vec<Check> = Store::stream_checks_from(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(streamed, store.checks);
+
+        let filtered = Store::load_filtered_from(&path, |c| !c.is_success()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(filtered.checks().len(), 4);
+        assert_eq!(filtered.aggregates(), store.aggregates());
+        assert!(filtered.readonly());
+    }
+
+    #[test]
+    fn test_load_newer_store() {
+        use crate::records::CheckFlag;
+
+        let mut store = Store::new();
+        for minute in 0..3 {
+            store.add_check(Check::new(
+                chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                None,
+                "1.1.1.1".parse().unwrap(),
+            ));
+        }
+        let mut data = framed::encode(&store).unwrap();
+        data[0] = Version::CURRENT.raw() + 1;
+        append_checksum(&mut data);
+        #[cfg(feature = "compression")]
+        let data = zstd::encode_all(&data[..], ZSTD_COMPRESSION_LEVEL).unwrap();
+        let mut file = Vec::new();
+        let metadata = Metadata::new();
+        let mut header = Header::new(Version::CURRENT, metadata.clone());
+        header.version += 1;
+        header.write_to(&mut file).unwrap();
+        file.extend_from_slice(&data);
+        let path = std::env::temp_dir().join(format!("netpulse-newer-{}", std::process::id()));
+        fs::write(&path, file).unwrap();
+        assert!(matches!(
+            Store::peek_file_version_of(&path),
+            Err(StoreError::NewerStore(raw)) if raw == Version::CURRENT.raw() + 1
+        ));
+
+        let loaded = Store::load_from(&path, false).unwrap();
+        assert!(loaded.readonly());
+        assert_eq!(loaded.version(), Version::CURRENT);
+        assert_eq!(loaded.checks(), store.checks());
+        assert_eq!(loaded.instance_id(), metadata.instance);
+        let streamed = Store::stream_checks_from(&path).unwrap().count();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(streamed, store.checks().len());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let dir = std::env::temp_dir().join(format!("netpulse-metadata-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(DB_NAME);
+        let mut backend = FileBackend::new(&path);
+
+        let store = Store::create_with(&mut backend).unwrap();
+        assert_eq!(store.last_saved(), None);
+        assert_eq!(store.written_by(), env!("CARGO_PKG_VERSION"));
+        let loaded = Store::load_from(&path, false).unwrap();
+        assert_eq!(loaded.instance_id(), store.instance_id());
+        assert_eq!(loaded.created(), store.created());
+        assert!(loaded.last_saved().is_some_and(|t| t >= store.created()));
+
+        // a store file without a header gets new metadata, created with its oldest check
+        let target: std::net::IpAddr = "1.1.1.1".parse().unwrap();
+        let flags = crate::records::CheckFlag::TypeHTTP as u16;
+        let old = (
+            Version::V2,
+            vec![
+                (120i64, flags, None::<u16>, target),
+                (60, flags, None, target),
+            ],
+        );
+        let data = bincode::serialize(&old).unwrap();
+        #[cfg(feature = "compression")]
+        let data = zstd::encode_all(&data[..], ZSTD_COMPRESSION_LEVEL).unwrap();
+        fs::write(&path, data).unwrap();
+        let other = Store::load_from(&path, false).unwrap();
+        assert_ne!(other.instance_id(), store.instance_id());
+        assert_eq!(other.created(), 60);
+        assert_eq!(other.last_saved(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_while_saving() {
+        use crate::records::CheckFlag;
+
+        let dir = std::env::temp_dir().join(format!("netpulse-consistent-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(DB_NAME);
+        let mut backend = FileBackend::new(&path);
+        backend.create().unwrap();
+
+        // the nth saved store has the checks of the first n minutes, so any mix would show
+        let saves = 100;
+        let writer = std::thread::spawn(move || {
+            let mut store = Store::new();
+            for minute in 0..saves {
+                store.add_check(Check::new(
+                    chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                    CheckFlag::Success | CheckFlag::TypeHTTP,
+                    Some(Duration::from_millis(minute as u64)),
+                    "1.1.1.1".parse().unwrap(),
+                ));
+                backend.save(&store).unwrap();
+            }
+        });
+
+        let mut seen = 0;
+        loop {
+            let done = writer.is_finished();
+            let store = Store::load_from(&path, true).unwrap();
+            assert!(store.checks().len() >= seen);
+            seen = store.checks().len();
+            for (minute, check) in store.checks().iter().enumerate() {
+                assert_eq!(check.timestamp(), minute as i64 * 60);
+                assert_eq!(check.latency(), Some(minute as u32));
+            }
+            if done {
+                break;
+            }
+        }
+        writer.join().unwrap();
+        assert_eq!(seen, saves as usize);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "traceroute")]
+    fn test_failed_targets() {
+        use crate::records::CheckFlag;
+
+        let check = |zone: Option<&str>, ok: bool| {
+            let builder = Check::builder()
+                .target("fe80::1".parse::<std::net::IpAddr>().unwrap())
+                .http();
+            let mut check = if ok {
+                builder.success().build()
+            } else {
+                builder.failure(CheckFlag::Timeout).build()
+            };
+            check.set_zone(zone.map(str::to_string));
+            check
+        };
+        let checks = [
+            check(Some("eth0"), false),
+            check(Some("eth1"), false),
+            check(Some("eth1"), false),
+            check(Some("wlan0"), true),
+        ];
+        let ip = "fe80::1".parse().unwrap();
+        assert_eq!(
+            super::failed_targets(&checks),
+            [
+                (ip, Some("eth0".to_string())),
+                (ip, Some("eth1".to_string()))
+            ]
+        );
     }
 }
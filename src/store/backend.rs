@@ -0,0 +1,305 @@
+//! Where a [Store] is kept between runs.
+//!
+//! A [Store] lives in memory while it is used. Reading it from somewhere and writing it back goes
+//! through a [StoreBackend]:
+//!
+//! - [FileBackend]: the store file, encoded with [bincode] and compressed with zstd if the
+//!   `compression` feature is enabled. This is what [Store::load] and [Store::save] use.
+//! - [SqliteStore](super::sqlite::SqliteStore): a SQLite database, with the `sqlite` feature.
+//! - [MemoryBackend]: nothing is written to disk, for tests and short lived stores.
+//!
+//! Other places, like an object storage or a remote API, can be supported by implementing
+//! [StoreBackend] and using [Store::load_with] and [Store::save_to].
+//!
+//! # Examples
+//!
+//! ```rust
+//! use netpulse::store::backend::MemoryBackend;
+//! use netpulse::store::Store;
+//!
+//! let mut backend = MemoryBackend::default();
+//! let store = Store::create_with(&mut backend).unwrap();
+//! store.save_to(&mut backend).unwrap();
+//! assert_eq!(Store::load_with(&backend, true).unwrap().checks(), store.checks());
+//! ```
+
+use std::fs;
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use tracing::{error, warn};
+
+use crate::errors::StoreError;
+
+use super::header::Header;
+use super::lock::StoreLock;
+use super::{framed, Store, Version};
+
+/// A place to keep a [Store] between runs, see the [module docs](self).
+pub trait StoreBackend: std::fmt::Debug + Send {
+    /// Reads the [Store] that was last saved.
+    ///
+    /// The [Store] should be migrated to the current [Version](super::Version) and its
+    /// [Checks](crate::records::Check) sorted by time, like [Store::load_from] does.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::DoesNotExist] if nothing was saved yet, or another [StoreError] if
+    /// the [Store] could not be read.
+    fn load(&self) -> Result<Store, StoreError>;
+
+    /// Writes `store`, replacing what was saved before.
+    ///
+    /// Readonly [Stores](Store) are never passed to this, see [Store::save_to].
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the [Store] could not be written.
+    fn save(&mut self, store: &Store) -> Result<(), StoreError>;
+
+    /// Creates an empty [Store] and saves it for the first time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the [Store] could not be written.
+    fn create(&mut self) -> Result<Store, StoreError> {
+        let store = Store::new();
+        self.save(&store)?;
+        Ok(store)
+    }
+}
+
+/// Keeps the [Store] in a store file, see [Store::path].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileBackend {
+    path: PathBuf,
+}
+
+impl Default for FileBackend {
+    /// The store file at [Store::path].
+    fn default() -> Self {
+        Self::new(Store::path())
+    }
+}
+
+impl FileBackend {
+    /// Creates a backend for the store file at `path`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Returns the path of the store file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the `n`th backup of the store file.
+    ///
+    /// The most recent backup is `1`. See [Store::backups].
+    pub fn path_backup(&self, n: usize) -> PathBuf {
+        let mut p = self.path.clone().into_os_string();
+        p.push(format!(".bak.{n}"));
+        PathBuf::from(p)
+    }
+
+    /// Returns the path of the temporary file used while saving the store.
+    ///
+    /// It lives in the same directory as the store file, so that it can be renamed atomically.
+    fn path_tmp(&self) -> PathBuf {
+        let mut p = self.path.clone().into_os_string();
+        p.push(".tmp");
+        PathBuf::from(p)
+    }
+
+    /// Rotates the backups of the store file and backs up the current store file.
+    ///
+    /// The oldest backup (`keep`) is overwritten, every other backup moves up by one, and the
+    /// current store file is copied to backup `1`.
+    fn rotate_backups(&self, keep: usize) -> Result<(), StoreError> {
+        for n in (1..keep).rev() {
+            let from = self.path_backup(n);
+            if from.exists() {
+                fs::rename(&from, self.path_backup(n + 1))?;
+            }
+        }
+        match fs::copy(&self.path, self.path_backup(1)) {
+            Ok(_) => Ok(()),
+            Err(err) => match err.kind() {
+                ErrorKind::NotFound => Err(StoreError::DoesNotExist),
+                _ => {
+                    error!("could not back up the store file: {err}");
+                    Err(err.into())
+                }
+            },
+        }
+    }
+}
+
+impl StoreBackend for FileBackend {
+    fn load(&self) -> Result<Store, StoreError> {
+        Store::load_from(&self.path, false)
+    }
+
+    /// Writes the store file.
+    ///
+    /// # File Handling
+    ///
+    /// - Rotates backups of the existing file if [ENV_BACKUPS](super::ENV_BACKUPS) is set
+    /// - Writes to a temporary file next to the store file
+    /// - Optionally compresses if feature enabled
+    /// - Maintains original permissions
+    /// - Atomically renames the temporary file over the existing file
+    ///
+    /// A crash while saving leaves either the old or the new store file, never a partially
+    /// written one. The store file is [locked](super::lock) exclusively while saving.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::DoesNotExist] if the store file does not exist yet, see
+    /// [FileBackend::create], or [StoreError::Locked] if another process is using the store file.
+    fn save(&mut self, store: &Store) -> Result<(), StoreError> {
+        let _lock = StoreLock::exclusive(&self.path)?;
+        let permissions = match fs::metadata(&self.path) {
+            Ok(meta) => meta.permissions(),
+            Err(err) => match err.kind() {
+                ErrorKind::NotFound => return Err(StoreError::DoesNotExist),
+                _ => return Err(err.into()),
+            },
+        };
+        let backups = store.backups();
+        if backups > 0 {
+            self.rotate_backups(backups)?;
+        }
+
+        let tmp_path = self.path_tmp();
+        let mut options = fs::File::options();
+        options
+            .read(false)
+            .write(true)
+            .append(false)
+            .create(true)
+            .truncate(true);
+        #[cfg(unix)]
+        options.mode(0o644);
+        let file = options
+            .open(&tmp_path)
+            .inspect_err(|e| error!("opening the temporary store file failed: {e}"))?;
+        file.set_permissions(permissions)?;
+
+        if let Err(err) = store.write_to(file) {
+            error!("writing the temporary store file failed: {err}");
+            if let Err(e) = fs::remove_file(&tmp_path) {
+                warn!("could not remove the temporary store file: {e}");
+            }
+            return Err(err);
+        }
+
+        fs::rename(&tmp_path, &self.path)?;
+        // make sure the rename itself is on the disk, directories can not be opened on windows
+        #[cfg(unix)]
+        if let Some(parent) = self.path.parent() {
+            fs::File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Creates the store file with permissions 0o644.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the store file already exists or could not be written.
+    fn create(&mut self) -> Result<Store, StoreError> {
+        let _lock = StoreLock::exclusive(&self.path)?;
+        let mut options = fs::File::options();
+        options
+            .read(false)
+            .write(true)
+            .append(false)
+            .create_new(true);
+        #[cfg(unix)]
+        options.mode(0o644);
+        let file = match options.open(&self.path) {
+            Ok(file) => file,
+            Err(err) => {
+                error!("opening the store file for writing failed: {err}");
+                return Err(err.into());
+            }
+        };
+
+        let mut store = Store::new();
+        store.write_to(file)?;
+        if self.path != Store::path() {
+            store.source = Some(self.path.clone());
+        }
+        Ok(store)
+    }
+}
+
+/// Keeps the [Store] in memory only.
+///
+/// The [Store] is encoded like in a store file, so loading it gives a fresh copy.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct MemoryBackend {
+    data: Option<Vec<u8>>,
+}
+
+impl StoreBackend for MemoryBackend {
+    fn load(&self) -> Result<Store, StoreError> {
+        let Some(data) = &self.data else {
+            return Err(StoreError::DoesNotExist);
+        };
+        let mut reader = &data[..];
+        let (header, _) = Header::read(&mut reader)?;
+        let mut store = framed::decode(&reader[1..], Version::CURRENT, false)?;
+        if let Some(header) = header {
+            store.metadata = header.metadata;
+        }
+        Ok(store)
+    }
+
+    fn save(&mut self, store: &Store) -> Result<(), StoreError> {
+        let mut header = Header::new(store.version, store.metadata.saved_now());
+        header.compressed = false;
+        let mut data = Vec::new();
+        header.write_to(&mut data)?;
+        data.extend(framed::encode(store)?);
+        self.data = Some(data);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::records::{Check, CheckFlag};
+
+    use super::*;
+
+    #[test]
+    fn test_memory_backend() {
+        let mut backend = MemoryBackend::default();
+        assert!(matches!(
+            Store::load_with(&backend, false),
+            Err(StoreError::DoesNotExist)
+        ));
+
+        let mut store = Store::create_with(&mut backend).unwrap();
+        store.add_check(Check::new(
+            chrono::DateTime::from_timestamp(60, 0).unwrap(),
+            CheckFlag::Success | CheckFlag::TypeHTTP,
+            Some(Duration::from_millis(12)),
+            "1.1.1.1".parse().unwrap(),
+        ));
+        store.save_to(&mut backend).unwrap();
+        assert_eq!(Store::load_with(&backend, false).unwrap(), store);
+
+        let readonly = Store::load_with(&backend, true).unwrap();
+        assert!(matches!(
+            readonly.save_to(&mut backend),
+            Err(StoreError::IsReadonly)
+        ));
+    }
+}
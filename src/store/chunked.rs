@@ -0,0 +1,336 @@
+//! Archive of old [Checks](Check) in fixed-size chunk files next to the store.
+//!
+//! The [Store] keeps all of its [Checks](Check) in memory, so the memory used by the daemon
+//! grows with the history. With the `chunked` feature, the daemon moves old [Checks](Check) into
+//! chunk files of [CHUNK_LEN] [Checks](Check) each, see [Store::archive]. Only the newest
+//! [Checks](Check) stay in the [Store].
+//!
+//! Chunk files are never changed after they are written. They are named after the time of their
+//! first and last [Check], so reading a time range only maps the chunk files that overlap it,
+//! see [Chunks::read_between]. The analysis reads them back with [Store::restore_archived].
+//!
+//! Each chunk file holds its [Checks](Check) serialized with [bincode], followed by a [blake3]
+//! checksum like the store file. It is not compressed, so it can be mapped into memory as is.
+//! The name also holds the [Version] of the store format the chunk was written with, like
+//! `1731000000-1731599940.v3.chunk`, so a later format can tell its chunk files apart. Chunk
+//! files of an unknown [Version] are ignored.
+//!
+//! Requires the `chunked` feature.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use tracing::{error, info};
+
+use crate::errors::StoreError;
+use crate::records::Check;
+
+use super::{append_checksum, verify_checksum, Store, Version};
+
+/// Number of [Checks](Check) in each chunk file
+pub const CHUNK_LEN: usize = 10_000;
+/// Extension of chunk files
+pub const CHUNK_EXTENSION: &str = "chunk";
+/// Environment variable to move [Checks](Check) older than that many days into chunk files
+///
+/// Used by the daemon, see [Store::archive_after_days].
+pub const ENV_ARCHIVE_DAYS: &str = "NETPULSE_ARCHIVE_DAYS";
+
+/// A chunk file and the time range of its [Checks](Check).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChunkInfo {
+    /// Path of the chunk file
+    pub path: PathBuf,
+    /// Unix timestamp of the first [Check] in the chunk
+    pub first: i64,
+    /// Unix timestamp of the last [Check] in the chunk
+    pub last: i64,
+    /// Version of the store format the chunk was written with
+    pub version: Version,
+}
+
+/// The chunk files of a [Store], see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunks {
+    dir: PathBuf,
+    chunks: Vec<ChunkInfo>,
+}
+
+impl Chunks {
+    /// Lists the chunk files in `dir`, oldest first.
+    ///
+    /// A missing directory has no chunks. Files that are not named like chunk files are
+    /// ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::Io] if the directory can not be read.
+    pub fn open(dir: &Path) -> Result<Self, StoreError> {
+        let mut chunks = Vec::new();
+        if dir.exists() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if let Some(info) = Self::parse_name(&path) {
+                    chunks.push(info);
+                }
+            }
+        }
+        chunks.sort_by_key(|c| (c.first, c.last));
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            chunks,
+        })
+    }
+
+    /// Returns the directory of the chunk files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the chunk files, oldest first.
+    pub fn infos(&self) -> &[ChunkInfo] {
+        &self.chunks
+    }
+
+    /// Writes `checks` into a new chunk file.
+    ///
+    /// `checks` must be sorted by time. Writing the same [Checks](Check) again replaces the
+    /// chunk file instead of adding another one.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the chunk file could not be written.
+    pub fn write(&mut self, checks: &[Check]) -> Result<(), StoreError> {
+        let (Some(first), Some(last)) = (checks.first(), checks.last()) else {
+            return Ok(());
+        };
+        fs::create_dir_all(&self.dir)?;
+        let info = ChunkInfo {
+            path: self.dir.join(format!(
+                "{}-{}.v{}.{CHUNK_EXTENSION}",
+                first.timestamp(),
+                last.timestamp(),
+                Version::CURRENT
+            )),
+            first: first.timestamp(),
+            last: last.timestamp(),
+            version: Version::CURRENT,
+        };
+
+        let mut data = bincode::serialize(checks)?;
+        append_checksum(&mut data);
+        // write to a temporary file first, so a crash never leaves half a chunk behind
+        let tmp = info.path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &info.path)?;
+
+        if !self.chunks.contains(&info) {
+            self.chunks.push(info);
+            self.chunks.sort_by_key(|c| (c.first, c.last));
+        }
+        Ok(())
+    }
+
+    /// Reads the [Checks](Check) made at or after `start` and before `end` from the chunk files.
+    ///
+    /// Only the chunk files that overlap the range are mapped into memory and decoded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if a chunk file can not be read, or [StoreError::CorruptStore] if
+    /// its checksum does not match.
+    pub fn read_between(&self, start: i64, end: i64) -> Result<Vec<Check>, StoreError> {
+        let mut checks = Vec::new();
+        for info in self
+            .chunks
+            .iter()
+            .filter(|c| c.last >= start && c.first < end)
+        {
+            checks.extend(
+                Self::read_chunk(info)?
+                    .into_iter()
+                    .filter(|c| c.timestamp() >= start && c.timestamp() < end),
+            );
+        }
+        Ok(checks)
+    }
+
+    /// Maps a chunk file into memory and decodes its [Checks](Check).
+    fn read_chunk(info: &ChunkInfo) -> Result<Vec<Check>, StoreError> {
+        let file = fs::File::open(&info.path)?;
+        // SAFETY: chunk files are never changed after they were written, see [Chunks::write]
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let data = verify_checksum(&map).inspect_err(|_| {
+            error!("the chunk file {} is damaged", info.path.display());
+        })?;
+        Ok(bincode::deserialize(data)?)
+    }
+
+    /// Reads the time range and version of a chunk file from its name, like
+    /// `1731000000-1731599940.v3.chunk`.
+    fn parse_name(path: &Path) -> Option<ChunkInfo> {
+        if path.extension()? != CHUNK_EXTENSION {
+            return None;
+        }
+        let stem = path.file_stem()?.to_str()?;
+        let (range, version) = stem.rsplit_once(".v")?;
+        let version = Version::try_from(version.parse::<u8>().ok()?).ok()?;
+        let (first, last) = range.split_once('-')?;
+        Some(ChunkInfo {
+            path: path.to_path_buf(),
+            first: first.parse().ok()?,
+            last: last.parse().ok()?,
+            version,
+        })
+    }
+}
+
+impl Store {
+    /// Returns the directory of the chunk files of this [Store], next to its store file.
+    pub fn chunks_dir(&self) -> PathBuf {
+        let mut dir = self.file_path().into_os_string();
+        dir.push(".chunks");
+        PathBuf::from(dir)
+    }
+
+    /// Moves the [Checks](Check) older than `older_than_days` into chunk files.
+    ///
+    /// Only full chunks of [CHUNK_LEN] [Checks](Check) are moved, the rest stays in the [Store]
+    /// until there are enough of them. The moved [Checks](Check) are removed from the [Store],
+    /// so [Store::save] should follow.
+    ///
+    /// Returns the number of [Checks](Check) that were moved.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if a chunk file could not be written. The [Checks](Check) of that
+    /// chunk stay in the [Store] then.
+    pub fn archive(&mut self, older_than_days: i64) -> Result<usize, StoreError> {
+        let cutoff = chrono::Utc::now().timestamp() - older_than_days * 24 * 60 * 60;
+        let old = self.checks.partition_point(|c| c.timestamp() < cutoff);
+        let full_chunks = old / CHUNK_LEN;
+        if full_chunks == 0 {
+            return Ok(0);
+        }
+
+        let mut chunks = Chunks::open(&self.chunks_dir())?;
+        let mut moved = 0;
+        for chunk in self.checks[..full_chunks * CHUNK_LEN].chunks(CHUNK_LEN) {
+            if let Err(err) = chunks.write(chunk) {
+                self.checks.drain(..moved);
+                return Err(err);
+            }
+            moved += chunk.len();
+        }
+        self.checks.drain(..moved);
+        info!("Archived {moved} checks into {full_chunks} chunk files");
+        Ok(moved)
+    }
+
+    /// Reads the archived [Checks](Check) made at or after `start` and before `end` back into
+    /// the [Store], keeping only those for which `keep` returns true.
+    ///
+    /// The [Store] is made readonly, as saving it would store the archived [Checks](Check)
+    /// twice. [Checks](Check) that are still in the [Store] are not read again.
+    ///
+    /// Returns the number of restored [Checks](Check).
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if a chunk file can not be read, see [Chunks::read_between].
+    pub fn restore_archived(
+        &mut self,
+        start: i64,
+        end: i64,
+        keep: impl Fn(&Check) -> bool,
+    ) -> Result<usize, StoreError> {
+        let chunks = Chunks::open(&self.chunks_dir())?;
+        if chunks.infos().is_empty() {
+            return Ok(0);
+        }
+        // if the daemon stopped between writing a chunk and saving the store, its checks are
+        // in both
+        let end = self.checks.first().map_or(end, |c| end.min(c.timestamp()));
+        let mut restored: Vec<Check> = chunks
+            .read_between(start, end)?
+            .into_iter()
+            .filter(|c| keep(c))
+            .collect();
+        let count = restored.len();
+        restored.append(&mut self.checks);
+        self.checks = restored;
+        self.sort_if_needed();
+        self.set_readonly();
+        Ok(count)
+    }
+
+    /// Returns after how many days the daemon moves [Checks](Check) into chunk files.
+    ///
+    /// This value is read from the [ENV_ARCHIVE_DAYS] environment variable. If it is not set or
+    /// not a valid number, nothing is archived.
+    pub fn archive_after_days(&self) -> Option<i64> {
+        std::env::var(ENV_ARCHIVE_DAYS).ok()?.parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::records::CheckFlag;
+
+    use super::*;
+
+    #[test]
+    fn test_archive_and_restore() {
+        let dir = std::env::temp_dir().join(format!("netpulse-chunks-{}", std::process::id()));
+        let mut store = Store::new();
+        store.source = Some(dir.join("test.store"));
+        let now = chrono::Utc::now().timestamp();
+        // checks are made once per minute
+        let old = now - now.rem_euclid(60) - 30 * 24 * 60 * 60;
+        for idx in 0..(CHUNK_LEN as i64 * 2 + 10) {
+            store.add_check(Check::new(
+                chrono::DateTime::from_timestamp(old + idx * 60, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                None,
+                "1.1.1.1".parse().unwrap(),
+            ));
+        }
+        let all = store.checks().to_vec();
+
+        assert_eq!(store.archive(7).unwrap(), CHUNK_LEN * 2);
+        assert_eq!(store.checks(), &all[CHUNK_LEN * 2..]);
+        let chunks = Chunks::open(&store.chunks_dir()).unwrap();
+        assert_eq!(chunks.infos().len(), 2);
+        assert_eq!(chunks.infos()[0].version, Version::CURRENT);
+        assert_eq!(chunks.infos()[1].first, old + CHUNK_LEN as i64 * 60);
+
+        let restored = store
+            .restore_archived(old + 5 * 60, i64::MAX, |c| (c.timestamp() - old) % 120 == 0)
+            .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        // every second check from the 6th on of both chunks
+        assert_eq!(restored, CHUNK_LEN - 3);
+        assert!(store.checks().is_sorted());
+        assert!(store.readonly());
+        assert_eq!(store.checks().last(), all.last());
+    }
+
+    #[test]
+    fn test_parse_name() {
+        let info = Chunks::parse_name(Path::new("/tmp/60-120.v3.chunk")).unwrap();
+        assert_eq!(
+            (info.first, info.last, info.version),
+            (60, 120, Version::V3)
+        );
+        // not a chunk file, no version or an unknown one
+        assert_eq!(Chunks::parse_name(Path::new("/tmp/60-120.v3.tmp")), None);
+        assert_eq!(Chunks::parse_name(Path::new("/tmp/60-120.chunk")), None);
+        assert_eq!(
+            Chunks::parse_name(Path::new("/tmp/60-120.v250.chunk")),
+            None
+        );
+    }
+}
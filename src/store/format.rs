@@ -0,0 +1,82 @@
+//! The serde format the records of a store file are encoded with.
+//!
+//! The [framed](super::framed) layout separates the records of a store file, each record is
+//! encoded as [CBOR](https://cbor.io). Unlike [bincode], CBOR is self describing, every record
+//! is a map of its field names to their values. Tools in other languages can read it with any
+//! CBOR library, and fields that are added or removed later are handled by their names instead
+//! of their position.
+//!
+//! [Checks](crate::records::Check) are maps like this, in the CBOR diagnostic notation:
+//!
+//! ```text
+//! {"timestamp": 1731000000, "flags": 4097, "latency": 12000, "target": {"V4": [1, 1, 1, 1]},
+//!  "label": "cloudflare", "http_status": null, ...}
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::StoreError;
+
+/// Encodes `value` as a record, see the [module docs](self).
+pub(super) fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, StoreError> {
+    let mut data = Vec::new();
+    ciborium::into_writer(value, &mut data)?;
+    Ok(data)
+}
+
+/// Decodes a `T` from the record in `data`.
+///
+/// Fields that `T` does not know are skipped by their names, see
+/// [newer stores](super::framed#newer-stores).
+pub(super) fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, StoreError> {
+    Ok(ciborium::from_reader(data)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::records::{Check, CheckFlag};
+
+    #[test]
+    fn test_cbor_is_self_describing() {
+        let mut check = Check::new(
+            chrono::DateTime::from_timestamp(1731000000, 0).unwrap(),
+            CheckFlag::Success | CheckFlag::TypeHTTP,
+            Some(std::time::Duration::from_millis(12)),
+            "1.1.1.1".parse().unwrap(),
+        );
+        check.set_label(Some("cloudflare".to_string()));
+
+        let data = encode(&check).unwrap();
+        assert_eq!(decode::<Check>(&data).unwrap(), check);
+
+        // any CBOR reader can make sense of it, without knowing the Rust type
+        let value: ciborium::Value = ciborium::from_reader(&data[..]).unwrap();
+        let map = value.as_map().unwrap();
+        let field = |name: &str| {
+            map.iter()
+                .find(|(key, _)| key.as_text() == Some(name))
+                .map(|(_, value)| value.clone())
+        };
+        assert_eq!(
+            field("timestamp").and_then(|v| v.as_integer()),
+            Some(1731000000.into())
+        );
+        assert_eq!(
+            field("label").and_then(|v| v.as_text().map(str::to_string)),
+            Some("cloudflare".to_string())
+        );
+        assert_eq!(
+            field("flags").and_then(|v| v.as_integer()),
+            Some(check.flags().bits().into())
+        );
+
+        // a field added by a newer version is skipped by its name
+        let mut newer = map.clone();
+        newer.push(("new field".into(), 42.into()));
+        let mut data = Vec::new();
+        ciborium::into_writer(&ciborium::Value::Map(newer), &mut data).unwrap();
+        assert_eq!(decode::<Check>(&data).unwrap(), check);
+    }
+}
@@ -0,0 +1,384 @@
+//! The framed layout of the store file since [Version::V3].
+//!
+//! Before [Version::V3], the [Store] is encoded with [bincode] as a whole. [bincode] is not self
+//! describing, so a reader has to know the exact layout of every field to find where the next
+//! one starts. A store written by a newer version of netpulse could only be guessed at.
+//!
+//! Since [Version::V3], the data after the [Version] is a sequence of sections, each starting
+//! with its [Section] tag and the length of its payload, and ending with [Section::End]:
+//!
+//! | Bytes   | Content                                       |
+//! |---------|-----------------------------------------------|
+//! | 1       | [Version]                                     |
+//! | 2       | tag of the first [Section], little endian     |
+//! | 8       | length of its payload, little endian          |
+//! | length  | payload                                       |
+//! | ...     | more sections                                 |
+//! | 2 + 8   | [Section::End] with an empty payload          |
+//! | 32      | checksum of all of the above, see [CHECKSUM_LEN](super::CHECKSUM_LEN) |
+//!
+//! The payload of [Section::Checks], [Section::Aggregates] and [Section::Traces] is the number
+//! of records as a little endian [u64], followed by each record with its length in front, also as
+//! a little endian [u64]. The records are encoded as [CBOR].
+//! [Section::Outages] holds the [OutageLog](crate::records::outage::OutageLog) as a single record. The checks are always the first
+//! section, so they can be [streamed](super::CheckStream).
+//!
+//! # Newer stores
+//!
+//! Later versions keep this framing and only ever add new fields to a record or new sections. A
+//! store file of a version newer than [Version::CURRENT] is read by skipping the sections with
+//! unknown tags. The records are CBOR maps, so the keys of fields this version does not know are
+//! ignored. Records that still can not be decoded, for example because of a new variant, are
+//! skipped with a warning. A store of a newer version is always opened readonly, so it is not
+//! overwritten without the new fields, see [Store::load].
+//!
+//! [CBOR]: super::format#
+
+use std::io::Read;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::{error, info, trace, warn};
+
+use crate::errors::StoreError;
+
+use super::format;
+use super::{Store, Version};
+
+/// Tags of the sections of a framed store file, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub(super) enum Section {
+    /// Ends the sections, the checksum follows
+    End = 0,
+    /// The [Checks](crate::records::Check), always the first section
+    Checks = 1,
+    /// The [Aggregates](crate::records::aggregate::Aggregate)
+    Aggregates = 2,
+    /// The [Traces](crate::records::trace::Trace)
+    Traces = 3,
+    /// The [OutageLog](crate::records::outage::OutageLog)
+    Outages = 4,
+}
+
+impl Section {
+    /// Returns the [Section] with the `tag`, [None] if it is unknown.
+    fn from_tag(tag: u16) -> Option<Self> {
+        Some(match tag {
+            0 => Self::End,
+            1 => Self::Checks,
+            2 => Self::Aggregates,
+            3 => Self::Traces,
+            4 => Self::Outages,
+            _ => return None,
+        })
+    }
+}
+
+/// Encodes `store` in the framed layout, starting with its [Version] and without the checksum.
+pub(super) fn encode(store: &Store) -> Result<Vec<u8>, StoreError> {
+    let mut data = vec![store.version.raw()];
+    write_section(&mut data, Section::Checks, &encode_records(&store.checks)?);
+    write_section(
+        &mut data,
+        Section::Aggregates,
+        &encode_records(&store.aggregates)?,
+    );
+    write_section(&mut data, Section::Traces, &encode_records(&store.traces)?);
+    write_section(
+        &mut data,
+        Section::Outages,
+        &format::encode(&store.outages)?,
+    );
+    write_section(&mut data, Section::End, &[]);
+    Ok(data)
+}
+
+/// Decodes a [Store] of `version` from the framed `data` after the [Version], without the
+/// checksum.
+///
+/// If the store is `newer` than this version of netpulse, records that can not be decoded are
+/// skipped, otherwise they are an error.
+///
+/// # Errors
+///
+/// Returns [StoreError] if the sections are cut off or a record can not be decoded.
+pub(super) fn decode(mut data: &[u8], version: Version, newer: bool) -> Result<Store, StoreError> {
+    let mut store = Store::new();
+    store.version = version;
+    read_sections(&mut data, &mut store, newer)?;
+    if !data.is_empty() {
+        error!("the store data goes on after its last section");
+        return Err(StoreError::CorruptStore);
+    }
+    Ok(store)
+}
+
+/// Reads sections from `reader` into `store` until [Section::End].
+///
+/// Unknown sections are skipped. Used by [decode], and to read the sections after the
+/// [Checks](crate::records::Check) once they were [streamed](super::CheckStream).
+pub(super) fn read_sections(
+    reader: &mut impl Read,
+    store: &mut Store,
+    newer: bool,
+) -> Result<(), StoreError> {
+    loop {
+        let (tag, len) = read_section_header(reader)?;
+        let payload = read_payload(reader, len)?;
+        match Section::from_tag(tag) {
+            Some(Section::End) => return Ok(()),
+            Some(Section::Checks) => store.checks = decode_records(&payload, newer)?,
+            Some(Section::Aggregates) => store.aggregates = decode_records(&payload, newer)?,
+            Some(Section::Traces) => store.traces = decode_records(&payload, newer)?,
+            Some(Section::Outages) => match format::decode(&payload) {
+                Ok(outages) => store.outages = outages,
+                // the outage log is found again with the next update
+                Err(err) if newer => warn!("skipping the outage log of the newer store: {err}"),
+                Err(err) => return Err(err),
+            },
+            None => trace!("skipping the unknown section {tag} of {len} bytes"),
+        }
+    }
+}
+
+/// Reads the header of the [Section::Checks] from `reader` and returns the number of
+/// [Checks](crate::records::Check) that follow.
+///
+/// # Errors
+///
+/// Returns [StoreError::CorruptStore] if the first section is not [Section::Checks].
+pub(super) fn read_checks_header(reader: &mut impl Read) -> Result<u64, StoreError> {
+    let (tag, _) = read_section_header(reader)?;
+    if Section::from_tag(tag) != Some(Section::Checks) {
+        error!("the first section of the store is {tag}, not the checks");
+        return Err(StoreError::CorruptStore);
+    }
+    read_u64(reader)
+}
+
+/// Reads the next record from `reader` and decodes it.
+///
+/// Returns [None] if the store is `newer` and the record could not be decoded, see the
+/// [module docs](self#newer-stores).
+pub(super) fn read_record<T: DeserializeOwned>(
+    reader: &mut impl Read,
+    newer: bool,
+) -> Result<Option<T>, StoreError> {
+    let len = read_u64(reader)?;
+    let record = read_payload(reader, len)?;
+    match format::decode(&record) {
+        Ok(record) => Ok(Some(record)),
+        Err(err) if newer => {
+            warn!("skipping a record of the newer store that could not be decoded: {err}");
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Decodes as much as possible from the damaged framed `data` after the [Version] into
+/// `store`, see [Store::load_damaged].
+///
+/// Every record that could be read completely is kept. The [OutageLog] is not recovered, it is
+/// found again with the next update.
+///
+/// [OutageLog]: crate::records::outage::OutageLog
+pub(super) fn recover(mut data: &[u8], store: &mut Store) {
+    loop {
+        let (tag, len) = match read_section_header(&mut data) {
+            Ok(header) => header,
+            Err(err) => {
+                warn!("Could not read the next section: {err}");
+                return;
+            }
+        };
+        let available = data.len().min(usize::try_from(len).unwrap_or(usize::MAX));
+        let (mut payload, rest) = data.split_at(available);
+        data = rest;
+        let complete = match Section::from_tag(tag) {
+            Some(Section::End) => return,
+            Some(Section::Checks) => recover_records(&mut payload, &mut store.checks, "checks"),
+            Some(Section::Aggregates) => {
+                recover_records(&mut payload, &mut store.aggregates, "aggregates")
+            }
+            Some(Section::Traces) => recover_records(&mut payload, &mut store.traces, "traces"),
+            Some(Section::Outages) | None => true,
+        };
+        if !complete || (available as u64) < len {
+            return;
+        }
+    }
+}
+
+/// Decodes records one by one until `payload` ends or they can not be decoded anymore.
+///
+/// Returns true if all records could be decoded.
+fn recover_records<T: DeserializeOwned>(payload: &mut &[u8], buf: &mut Vec<T>, what: &str) -> bool {
+    let len = match read_u64(payload) {
+        Ok(len) => len,
+        Err(err) => {
+            warn!("Could not read the number of {what}: {err}");
+            return false;
+        }
+    };
+    for idx in 0..len {
+        match read_record(payload, false) {
+            Ok(Some(record)) => buf.push(record),
+            Ok(None) => unreachable!("records are only skipped in newer stores"),
+            Err(err) => {
+                warn!("Recovered {idx} of {len} {what}, stopped at: {err}");
+                return false;
+            }
+        }
+    }
+    info!("Recovered all {len} {what}");
+    true
+}
+
+/// Appends a section with `payload` to `data`.
+fn write_section(data: &mut Vec<u8>, section: Section, payload: &[u8]) {
+    data.extend_from_slice(&(section as u16).to_le_bytes());
+    data.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    data.extend_from_slice(payload);
+}
+
+/// Encodes `records` as the payload of a sequence section.
+fn encode_records<T: Serialize>(records: &[T]) -> Result<Vec<u8>, StoreError> {
+    let mut payload = (records.len() as u64).to_le_bytes().to_vec();
+    for record in records {
+        let encoded = format::encode(record)?;
+        payload.extend_from_slice(&(encoded.len() as u64).to_le_bytes());
+        payload.extend_from_slice(&encoded);
+    }
+    Ok(payload)
+}
+
+/// Decodes the records of a sequence section, see [read_record].
+fn decode_records<T: DeserializeOwned>(
+    mut payload: &[u8],
+    newer: bool,
+) -> Result<Vec<T>, StoreError> {
+    let len = read_u64(&mut payload)?;
+    let mut records = Vec::new();
+    for _ in 0..len {
+        if let Some(record) = read_record(&mut payload, newer)? {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Reads the tag and the length of the payload of the next section.
+fn read_section_header(reader: &mut impl Read) -> Result<(u16, u64), StoreError> {
+    let mut tag = [0u8; 2];
+    read_exact(reader, &mut tag)?;
+    Ok((u16::from_le_bytes(tag), read_u64(reader)?))
+}
+
+/// Reads a little endian [u64].
+fn read_u64(reader: &mut impl Read) -> Result<u64, StoreError> {
+    let mut raw = [0u8; 8];
+    read_exact(reader, &mut raw)?;
+    Ok(u64::from_le_bytes(raw))
+}
+
+/// Reads the next `len` bytes.
+///
+/// The length comes from the data, so the buffer only grows with what was actually read.
+fn read_payload(reader: &mut impl Read, len: u64) -> Result<Vec<u8>, StoreError> {
+    let mut payload = Vec::new();
+    reader.take(len).read_to_end(&mut payload)?;
+    if (payload.len() as u64) < len {
+        error!("the store data ends in the middle of a section");
+        return Err(StoreError::CorruptStore);
+    }
+    Ok(payload)
+}
+
+/// Fills `buf`, returning [StoreError::CorruptStore] if the data ends before.
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), StoreError> {
+    reader.read_exact(buf).map_err(|err| {
+        error!("the store data ends too early: {err}");
+        StoreError::CorruptStore
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::records::{Check, CheckFlag};
+
+    fn store() -> Store {
+        let mut store = Store::new();
+        for minute in 0..5 {
+            store.add_check(Check::new(
+                chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                Some(std::time::Duration::from_millis(minute as u64)),
+                "1.1.1.1".parse().unwrap(),
+            ));
+        }
+        store.aggregates = crate::records::aggregate::aggregate_checks(&store.checks[..2]);
+        store
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let store = store();
+        let data = encode(&store).unwrap();
+        assert_eq!(data[0], Version::CURRENT.raw());
+        assert_eq!(decode(&data[1..], Version::CURRENT, false).unwrap(), store);
+        assert!(matches!(
+            decode(&data[1..data.len() - 3], Version::CURRENT, false),
+            Err(StoreError::CorruptStore)
+        ));
+    }
+
+    #[test]
+    fn test_newer_store() {
+        let store = store();
+        let mut data = vec![Version::CURRENT.raw() + 1];
+        // a newer version appended a field to every check and added a section
+        let mut checks = (store.checks.len() as u64).to_le_bytes().to_vec();
+        for check in &store.checks {
+            let mut record = format::encode(check).unwrap();
+            record.extend_from_slice(b"new field");
+            checks.extend_from_slice(&(record.len() as u64).to_le_bytes());
+            checks.extend_from_slice(&record);
+        }
+        write_section(&mut data, Section::Checks, &checks);
+        // and changed the aggregates, so one of them can not be decoded anymore
+        let mut aggregates = 1u64.to_le_bytes().to_vec();
+        aggregates.extend_from_slice(&1u64.to_le_bytes());
+        aggregates.push(42);
+        write_section(&mut data, Section::Aggregates, &aggregates);
+        data.extend_from_slice(&99u16.to_le_bytes());
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(b"new");
+        write_section(&mut data, Section::End, &[]);
+
+        let decoded = decode(&data[1..], Version::CURRENT, true).unwrap();
+        assert_eq!(decoded.checks, store.checks);
+        assert!(decoded.aggregates.is_empty());
+        // a store of a known version must not lose records silently
+        assert!(decode(&data[1..], Version::CURRENT, false).is_err());
+    }
+
+    #[test]
+    fn test_recover() {
+        let store = store();
+        let data = encode(&store).unwrap();
+        let mut recovered = Store::new();
+        recover(&data[1..data.len() - 10], &mut recovered);
+        assert_eq!(recovered.checks, store.checks);
+        assert_eq!(recovered.aggregates, store.aggregates);
+
+        // cut off in the middle of the third check
+        let record_len = |check| 8 + format::encode(check).unwrap().len();
+        let cut = 1 + 10 + 8 + record_len(&store.checks[0]) + record_len(&store.checks[1]) + 4;
+        let mut recovered = Store::new();
+        recover(&data[1..cut], &mut recovered);
+        assert_eq!(recovered.checks, store.checks[..2]);
+    }
+}
@@ -0,0 +1,237 @@
+//! The uncompressed header of the store file since [Version::V3].
+//!
+//! Before, the whole store file was compressed, so even reading its [Version] meant setting up
+//! the decompression. Since [Version::V3], the store file starts with a small header that is
+//! never compressed:
+//!
+//! | Bytes | Content                                                      |
+//! |-------|--------------------------------------------------------------|
+//! | 8     | [MAGIC], `netpulse` in ASCII                                 |
+//! | 1     | [Version]                                                    |
+//! | 1     | flags, bit 0 is set if the data is compressed with [zstd]    |
+//! | 4     | length of the [Metadata], little endian                      |
+//! | ...   | the [Metadata] encoded as CBOR                               |
+//! | ...   | the data in the [framed](super::framed) layout               |
+//!
+//! [Store::peek_file_version](super::Store::peek_file_version) only reads the first bytes then.
+//! The data still starts with the [Version], which its checksum covers.
+//!
+//! The [Metadata] tells store files apart, like those of multiple machines or their backups.
+//! Like the records, it is a map of field names to values, so newer versions can add fields.
+//!
+//! Older store files start with the magic bytes of [zstd] or with their [Version], so they are
+//! told apart by the [MAGIC].
+
+use std::io::{Read, Write};
+
+use deepsize::DeepSizeOf;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::errors::StoreError;
+
+use super::{Version, MAGIC};
+
+/// Flag of a [Header] whose data is compressed with [zstd]
+const FLAG_COMPRESSED: u8 = 0b0000_0001;
+
+/// The header of a store file, see the [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Header {
+    /// Raw [Version] of the store file, which can be newer than [Version::CURRENT]
+    pub(super) version: u8,
+    /// True if the data after the header is compressed with [zstd]
+    pub(super) compressed: bool,
+    /// [Metadata] of the store
+    pub(super) metadata: Metadata,
+}
+
+/// Metadata of a [Store](super::Store), see the [module docs](self).
+///
+/// Store files older than [Version::V3] have no [Metadata], they get new [Metadata] when they
+/// are loaded, see [Metadata::of_legacy].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(super) struct Metadata {
+    /// When the store was created, as unix timestamp
+    pub(super) created: i64,
+    /// When the store file was last saved, as unix timestamp
+    pub(super) saved: Option<i64>,
+    /// Version of netpulse that last saved the store file
+    pub(super) written_by: String,
+    /// Random ID of the store, which it keeps for its whole life
+    pub(super) instance: Uuid,
+}
+
+impl Header {
+    /// Length of the [Header] before the [Metadata] in bytes
+    pub(super) const LEN: usize = MAGIC.len() + 2;
+
+    /// Creates the [Header] this build of netpulse writes for `version`.
+    pub(super) fn new(version: Version, metadata: Metadata) -> Self {
+        Self {
+            version: version.raw(),
+            compressed: cfg!(feature = "compression"),
+            metadata,
+        }
+    }
+
+    /// Writes the [Header] to `writer`.
+    pub(super) fn write_to(&self, writer: &mut impl Write) -> Result<(), StoreError> {
+        let flags = if self.compressed { FLAG_COMPRESSED } else { 0 };
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[self.version, flags])?;
+        let mut data = Vec::new();
+        ciborium::into_writer(&self.metadata, &mut data)?;
+        writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        writer.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Reads the [Header] from the start of a store file.
+    ///
+    /// Returns [None] if the store file is older than [Version::V3] and has no [Header]. The
+    /// bytes that were read then belong to the data and are returned too.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::CorruptStore] if the [Header] is cut off, or [StoreError::CborDecode]
+    /// if its [Metadata] can not be decoded.
+    pub(super) fn read(reader: &mut impl Read) -> Result<(Option<Self>, Vec<u8>), StoreError> {
+        let mut start = Vec::with_capacity(Self::LEN);
+        reader.take(Self::LEN as u64).read_to_end(&mut start)?;
+        let Some((version, compressed)) = Self::parse(&start)? else {
+            return Ok((None, start));
+        };
+        let mut len = [0u8; 4];
+        reader.read_exact(&mut len).map_err(cut_off)?;
+        let len = u32::from_le_bytes(len) as usize;
+        // not read_exact, a corrupt length should not allocate gigabytes
+        let mut data = Vec::new();
+        reader.take(len as u64).read_to_end(&mut data)?;
+        if data.len() != len {
+            return Err(cut_off(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        let header = Self {
+            version,
+            compressed,
+            metadata: ciborium::from_reader(&data[..])?,
+        };
+        Ok((Some(header), start))
+    }
+
+    /// Parses the first [Header::LEN] bytes of a store file, or all of them if it is shorter.
+    ///
+    /// Returns the raw [Version] and whether the data is compressed, or [None] if the store file
+    /// has no [Header].
+    fn parse(start: &[u8]) -> Result<Option<(u8, bool)>, StoreError> {
+        let Some(rest) = start.strip_prefix(&MAGIC) else {
+            return Ok(None);
+        };
+        let &[version, flags] = rest else {
+            error!("the header of the store file is cut off");
+            return Err(StoreError::CorruptStore);
+        };
+        Ok(Some((version, flags & FLAG_COMPRESSED != 0)))
+    }
+}
+
+impl Metadata {
+    /// Creates the [Metadata] of a new store, with a random [instance](Metadata::instance).
+    pub(super) fn new() -> Self {
+        Self {
+            created: chrono::Utc::now().timestamp(),
+            saved: None,
+            written_by: env!("CARGO_PKG_VERSION").to_string(),
+            instance: Uuid::new_v4(),
+        }
+    }
+
+    /// Creates the [Metadata] of a store file older than [Version::V3], which has none.
+    ///
+    /// The store was created before its `oldest` record, so that is its creation time. A store
+    /// without records gets the current time.
+    pub(super) fn of_legacy(oldest: Option<i64>) -> Self {
+        let mut metadata = Self::new();
+        if let Some(oldest) = oldest {
+            metadata.created = oldest;
+        }
+        metadata
+    }
+
+    /// Returns the [Metadata] to write when the store file is saved now.
+    pub(super) fn saved_now(&self) -> Self {
+        Self {
+            saved: Some(chrono::Utc::now().timestamp()),
+            written_by: env!("CARGO_PKG_VERSION").to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for Metadata {
+    /// Same as [Metadata::new].
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeepSizeOf for Metadata {
+    fn deep_size_of_children(&self, context: &mut deepsize::Context) -> usize {
+        self.written_by.deep_size_of_children(context)
+    }
+}
+
+/// Logs that the [Header] is cut off and returns [StoreError::CorruptStore].
+fn cut_off(err: std::io::Error) -> StoreError {
+    error!("the header of the store file is cut off: {err}");
+    StoreError::CorruptStore
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        let header = Header::new(Version::CURRENT, Metadata::new());
+        let mut data = Vec::new();
+        header.write_to(&mut data).unwrap();
+        assert!(data.starts_with(b"netpulse"));
+        data.extend_from_slice(b"rest");
+        let mut reader = &data[..];
+        assert_eq!(Header::read(&mut reader).unwrap().0, Some(header));
+        assert_eq!(reader, b"rest");
+
+        // older store files, compressed or not
+        assert_eq!(
+            Header::parse(&[0x28, 0xb5, 0x2f, 0xfd, 0, 0]).unwrap(),
+            None
+        );
+        assert_eq!(Header::parse(&[Version::V2.raw(), 0, 0]).unwrap(), None);
+        assert!(matches!(
+            Header::parse(&data[..Header::LEN - 1]),
+            Err(StoreError::CorruptStore)
+        ));
+        assert!(matches!(
+            Header::read(&mut &data[..Header::LEN + 6]),
+            Err(StoreError::CorruptStore)
+        ));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata = Metadata::new();
+        assert_eq!(metadata.saved, None);
+        assert_eq!(metadata.instance.get_version_num(), 4);
+        assert_ne!(metadata.instance, Metadata::new().instance);
+
+        assert_eq!(Metadata::of_legacy(Some(60)).created, 60);
+        assert!(Metadata::of_legacy(None).created >= metadata.created);
+
+        let saved = metadata.saved_now();
+        assert!(saved.saved.is_some());
+        assert_eq!(saved.created, metadata.created);
+        assert_eq!(saved.instance, metadata.instance);
+    }
+}
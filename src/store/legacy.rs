@@ -0,0 +1,68 @@
+//! Frozen layout of the [Store] versions before [Version::V3].
+//!
+//! [bincode] is not self describing, so a [Store] of an older [Version] with a different layout
+//! can not be deserialized into the current [Store] directly. The old layout is kept here and
+//! converted into the current [Store], after which the usual in-memory migration takes place.
+
+use std::net::IpAddr;
+
+use flagset::FlagSet;
+use serde::{Deserialize, Serialize};
+
+use crate::records::outage::OutageLog;
+use crate::records::{Check, CheckFlag};
+
+use super::{Store, Version};
+
+/// Layout of a [Check] for [Version::V0] to [Version::V2]
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) struct CheckV2 {
+    timestamp: i64,
+    flags: u16,
+    latency: Option<u16>,
+    target: IpAddr,
+}
+
+impl From<CheckV2> for Check {
+    fn from(value: CheckV2) -> Self {
+        Check::from_raw(
+            value.timestamp,
+            flags(value.flags),
+            micros(value.latency),
+            value.target,
+        )
+    }
+}
+
+/// Widens the flags of the old layout, which were serialized as a [u16]
+fn flags(raw: u16) -> FlagSet<CheckFlag> {
+    FlagSet::new_truncated(raw.into())
+}
+
+/// Converts the latency of the old layout from milliseconds to microseconds
+fn micros(latency: Option<u16>) -> Option<u32> {
+    latency.map(|ms| ms as u32 * 1000)
+}
+
+/// Layout of the [Store] for [Version::V0] to [Version::V2]
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) struct StoreV2 {
+    pub(super) version: Version,
+    pub(super) checks: Vec<CheckV2>,
+}
+
+impl From<StoreV2> for Store {
+    fn from(value: StoreV2) -> Self {
+        Store {
+            version: value.version,
+            checks: value.checks.into_iter().map(Check::from).collect(),
+            aggregates: Vec::new(),
+            traces: Vec::new(),
+            outages: OutageLog::default(),
+            readonly: false,
+            source: None,
+            reject_duplicates: false,
+            metadata: Default::default(),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Advisory locking of the store file, so two processes never write it at the same time.
+//!
+//! The lock is taken on a lock file next to the store file, see [StoreLock::path_for], because
+//! the store file itself is replaced on every save. [FileBackend](super::backend::FileBackend)
+//! locks it exclusively while writing, and [Store::load_from](super::Store::load_from) shared
+//! while reading. So readers never see a save that is half done, and a second daemon or a
+//! `netpulse --rewrite` can not interleave its save with the one of the daemon.
+//!
+//! Both wait up to [LOCK_WAIT] for the lock, then give up with [StoreError::Locked]. The locks
+//! are advisory, they only keep out processes that also use them.
+//!
+//! # Example
+//!
+//! ```rust
+//! use netpulse::store::lock::StoreLock;
+//!
+//! let dir = std::env::temp_dir().join(format!("netpulse-lock-doc-{}", std::process::id()));
+//! std::fs::create_dir_all(&dir).unwrap();
+//! let store = dir.join("netpulse.store");
+//!
+//! let lock = StoreLock::exclusive(&store).unwrap();
+//! assert!(StoreLock::path_for(&store).exists());
+//! drop(lock);
+//! assert!(StoreLock::shared(&store).unwrap().is_some());
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use std::fs::{self, TryLockError};
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, trace};
+
+use crate::errors::StoreError;
+
+/// How long to wait for the lock of the store file before giving up with [StoreError::Locked]
+pub const LOCK_WAIT: Duration = Duration::from_secs(10);
+/// How long to sleep between tries to take the lock
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A lock of the store file, released when dropped, see the [module docs](self).
+#[derive(Debug)]
+pub struct StoreLock {
+    _file: fs::File,
+}
+
+impl StoreLock {
+    /// Returns the path of the lock file of the store file at `store`, like
+    /// `netpulse.store.lock`.
+    pub fn path_for(store: &Path) -> PathBuf {
+        let mut p = store.to_path_buf().into_os_string();
+        p.push(".lock");
+        PathBuf::from(p)
+    }
+
+    /// Locks the store file at `store` for writing, waiting up to [LOCK_WAIT].
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::Locked] if another process holds the lock for longer, or
+    /// [StoreError::Io] if the lock file can not be opened.
+    pub fn exclusive(store: &Path) -> Result<Self, StoreError> {
+        Self::exclusive_within(store, LOCK_WAIT)
+    }
+
+    /// Locks the store file at `store` for reading, waiting up to [LOCK_WAIT].
+    ///
+    /// Other readers can hold the lock at the same time. The lock file is not created, so reading
+    /// leaves nothing behind. Returns [None] if there is no lock file, as no writer ever saved the
+    /// store with locking then, or if it can not be opened. The store is read without a lock
+    /// then.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::Locked] if a writer holds the lock for longer.
+    pub fn shared(store: &Path) -> Result<Option<Self>, StoreError> {
+        Self::shared_within(store, LOCK_WAIT)
+    }
+
+    fn exclusive_within(store: &Path, wait: Duration) -> Result<Self, StoreError> {
+        let path = Self::path_for(store);
+        let file = Self::open(&path)?;
+        acquire(&path, wait, || file.try_lock())?;
+        trace!("locked {} exclusively", path.display());
+        Ok(Self { _file: file })
+    }
+
+    fn shared_within(store: &Path, wait: Duration) -> Result<Option<Self>, StoreError> {
+        let path = Self::path_for(store);
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::NotFound | ErrorKind::PermissionDenied
+                ) =>
+            {
+                debug!(
+                    "could not open the lock file {}, reading without a lock: {err}",
+                    path.display()
+                );
+                return Ok(None);
+            }
+            Err(err) => return Err(err.into()),
+        };
+        acquire(&path, wait, || file.try_lock_shared())?;
+        trace!("locked {} shared", path.display());
+        Ok(Some(Self { _file: file }))
+    }
+
+    /// Opens the lock file at `path` for a writer, creating it if needed.
+    ///
+    /// Locks work on files that are only open for reading too, so a lock file that was created by
+    /// another user can still be used.
+    fn open(path: &Path) -> Result<fs::File, StoreError> {
+        let mut options = fs::File::options();
+        options.read(true).write(true).create(true).truncate(false);
+        #[cfg(unix)]
+        options.mode(0o644);
+        match options.open(path) {
+            Ok(file) => Ok(file),
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => Ok(fs::File::open(path)?),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Calls `try_lock` until it succeeds or `wait` has passed.
+fn acquire(
+    path: &Path,
+    wait: Duration,
+    try_lock: impl Fn() -> Result<(), TryLockError>,
+) -> Result<(), StoreError> {
+    let start = Instant::now();
+    loop {
+        match try_lock() {
+            Ok(()) => return Ok(()),
+            Err(TryLockError::WouldBlock) if start.elapsed() < wait => {
+                std::thread::sleep(RETRY_INTERVAL)
+            }
+            Err(TryLockError::WouldBlock) => return Err(StoreError::Locked(path.to_path_buf())),
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lock_exclusion() {
+        let dir = std::env::temp_dir().join(format!("netpulse-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let store = dir.join("netpulse.store");
+        let no_wait = Duration::ZERO;
+
+        assert!(StoreLock::shared_within(&store, no_wait).unwrap().is_none());
+        assert!(!StoreLock::path_for(&store).exists());
+        let writer = StoreLock::exclusive_within(&store, no_wait).unwrap();
+        assert!(matches!(
+            StoreLock::exclusive_within(&store, no_wait),
+            Err(StoreError::Locked(p)) if p == StoreLock::path_for(&store)
+        ));
+        assert!(matches!(
+            StoreLock::shared_within(&store, no_wait),
+            Err(StoreError::Locked(_))
+        ));
+        drop(writer);
+
+        let reader = StoreLock::shared_within(&store, no_wait).unwrap();
+        let other_reader = StoreLock::shared_within(&store, no_wait).unwrap();
+        assert!(reader.is_some() && other_reader.is_some());
+        assert!(matches!(
+            StoreLock::exclusive_within(&store, no_wait),
+            Err(StoreError::Locked(_))
+        ));
+        drop((reader, other_reader));
+
+        // a writer waits for the reader to finish
+        let reader = StoreLock::shared_within(&store, no_wait).unwrap();
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            drop(reader);
+        });
+        StoreLock::exclusive_within(&store, Duration::from_secs(5)).unwrap();
+        release.join().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
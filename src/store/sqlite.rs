@@ -0,0 +1,556 @@
+//! SQLite database as an alternative to the store file.
+//!
+//! The store file is written as a whole on every save and can only be read by netpulse. A
+//! [SqliteStore] keeps the same data in a SQLite database instead. [Checks](Check) are inserted
+//! one by one, every write is a transaction, so an interrupted write never damages older data,
+//! and the data can be queried with plain SQL:
+//!
+//! ```sql
+//! -- failed checks of the last day
+//! SELECT datetime(timestamp, 'unixepoch'), target, label
+//! FROM checks
+//! WHERE flags & 1 = 0 AND timestamp > unixepoch() - 86400;
+//! ```
+//!
+//! `netpulse --export-sqlite FILE` copies the store file into a database, see
+//! [SqliteStore::import_store]. `netpulse --store FILE` reads a database like a store file.
+//!
+//! A [SqliteStore] is also a [StoreBackend], so a [Store] can be kept in a database instead of
+//! the store file with [Store::load_with] and [Store::save_to].
+//!
+//! # Schema
+//!
+//! - `checks`: `timestamp`, `flags` (the bits of the [CheckFlags](crate::records::CheckFlag)),
+//!   `latency` (in microseconds), `target` (with the zone of a link-local address, like
+//!   `fe80::1%eth0`), `label`, `http_status` and `measurement` (a
+//!   [Measurement](crate::records::Measurement) encoded with [bincode]), `interface` (see
+//!   [Check::interface]) and `probe` (see [Check::probe])
+//! - `aggregates`: `start`, `target` and `data` (an [Aggregate] encoded with [bincode])
+//! - `traces`: `timestamp`, `target` and `data` (a [Trace] encoded with [bincode])
+//! - `meta`: `key` and `value`, holds the `version` of the [Store] layout
+//!
+//! The [OutageLog](crate::records::outage::OutageLog) of a [Store] is not kept, the outages are
+//! found in the [Checks](Check) again when a database is read.
+//!
+//! Requires the `sqlite` feature.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use flagset::FlagSet;
+use rusqlite::{params, Connection, Row};
+use tracing::error;
+
+use crate::checks::bind::parse_scoped;
+use crate::errors::StoreError;
+use crate::records::aggregate::Aggregate;
+use crate::records::trace::Trace;
+use crate::records::{Check, CheckFlag};
+
+use super::{Store, StoreBackend, Version};
+
+/// The first bytes of every SQLite database file
+pub const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS checks (
+    id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    flags INTEGER NOT NULL,
+    latency INTEGER,
+    target TEXT NOT NULL,
+    label TEXT,
+    http_status INTEGER,
+    measurement BLOB,
+    interface TEXT,
+    probe TEXT
+);
+CREATE UNIQUE INDEX IF NOT EXISTS checks_unique
+    ON checks (timestamp, target, flags, ifnull(label, ''), ifnull(interface, ''), ifnull(probe, ''));
+CREATE TABLE IF NOT EXISTS aggregates (
+    id INTEGER PRIMARY KEY,
+    start INTEGER NOT NULL,
+    target TEXT NOT NULL,
+    data BLOB NOT NULL UNIQUE
+);
+CREATE INDEX IF NOT EXISTS aggregates_start ON aggregates (start);
+CREATE TABLE IF NOT EXISTS traces (
+    id INTEGER PRIMARY KEY,
+    timestamp INTEGER NOT NULL,
+    target TEXT NOT NULL,
+    data BLOB NOT NULL UNIQUE
+);
+";
+
+const INSERT_CHECK: &str = "INSERT OR IGNORE INTO checks
+    (timestamp, flags, latency, target, label, http_status, measurement, interface, probe)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)";
+const SELECT_CHECKS: &str = "SELECT timestamp, flags, latency, target, label, http_status,
+    measurement, interface, probe FROM checks WHERE timestamp >= ?1 AND timestamp < ?2 ORDER BY timestamp, id";
+
+/// The data of a [Store] in a SQLite database, see the [module docs](self).
+#[derive(Debug)]
+pub struct SqliteStore {
+    conn: Connection,
+    path: PathBuf,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it and its tables if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::Sqlite] if the database can not be opened or is not a netpulse
+    /// database, and [StoreError::NewerStore] if it was written by a newer version of netpulse.
+    pub fn open(path: &Path) -> Result<Self, StoreError> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO meta (key, value) VALUES ('version', ?1)",
+            params![Version::CURRENT.raw().to_string()],
+        )?;
+        let sqlite = Self {
+            conn,
+            path: path.to_path_buf(),
+        };
+        sqlite.version().inspect_err(|_| {
+            error!("the database {} is not supported", path.display());
+        })?;
+        Ok(sqlite)
+    }
+
+    /// Returns the [Version] of the [Store] layout the database was written with.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError::Sqlite] if the version can not be read, [StoreError::CorruptStore] if
+    /// it is not a number and [StoreError::NewerStore] if it is newer than [Version::CURRENT].
+    pub fn version(&self) -> Result<Version, StoreError> {
+        let raw: String =
+            self.conn
+                .query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| {
+                    row.get(0)
+                })?;
+        raw.parse::<u8>()
+            .map_err(|_| StoreError::CorruptStore)
+            .and_then(Version::of_file)
+    }
+
+    /// Returns true if the file at `path` is a SQLite database.
+    pub fn is_sqlite(path: &Path) -> bool {
+        let mut magic = [0u8; SQLITE_MAGIC.len()];
+        std::fs::File::open(path)
+            .and_then(|mut file| file.read_exact(&mut magic))
+            .is_ok()
+            && &magic == SQLITE_MAGIC
+    }
+
+    /// Returns the path of the database.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Inserts a [Check] right away.
+    ///
+    /// A [Check] with the same timestamp, target (with its zone), flags, label, interface and
+    /// probe as one that is already in the database is skipped. Returns true if the [Check] was
+    /// inserted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the [Check] could not be written.
+    pub fn add_check(&mut self, check: &Check) -> Result<bool, StoreError> {
+        insert_check(&self.conn, check)
+    }
+
+    /// Inserts all `checks` in a single transaction, skipping duplicates like
+    /// [SqliteStore::add_check].
+    ///
+    /// Returns the number of inserted [Checks](Check).
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the [Checks](Check) could not be written. Then none of them are.
+    pub fn add_checks<'a>(
+        &mut self,
+        checks: impl IntoIterator<Item = &'a Check>,
+    ) -> Result<usize, StoreError> {
+        let tx = self.conn.transaction()?;
+        let mut inserted = 0;
+        for check in checks {
+            if insert_check(&tx, check)? {
+                inserted += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /// Inserts an [Aggregate], skipping it if it is already in the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the [Aggregate] could not be written.
+    pub fn add_aggregate(&mut self, aggregate: &Aggregate) -> Result<(), StoreError> {
+        insert_aggregate(&self.conn, aggregate)
+    }
+
+    /// Inserts a [Trace], skipping it if it is already in the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the [Trace] could not be written.
+    pub fn add_trace(&mut self, trace: &Trace) -> Result<(), StoreError> {
+        insert_trace(&self.conn, trace)
+    }
+
+    /// Returns the number of [Checks](Check) in the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the database could not be read.
+    pub fn len(&self) -> Result<usize, StoreError> {
+        let len: i64 = self
+            .conn
+            .query_row("SELECT count(*) FROM checks", [], |row| row.get(0))?;
+        Ok(len as usize)
+    }
+
+    /// Returns true if there are no [Checks](Check) in the database.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the database could not be read.
+    pub fn is_empty(&self) -> Result<bool, StoreError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Returns the [Checks](Check) made at or after `start` and before `end`, sorted by time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the database could not be read or holds invalid data.
+    pub fn checks_between(&self, start: i64, end: i64) -> Result<Vec<Check>, StoreError> {
+        let mut stmt = self.conn.prepare_cached(SELECT_CHECKS)?;
+        let rows = stmt.query_map(params![start, end], read_check)?;
+        let mut checks = Vec::new();
+        for row in rows {
+            checks.push(row??);
+        }
+        Ok(checks)
+    }
+
+    /// Returns all [Checks](Check), sorted by time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the database could not be read or holds invalid data.
+    pub fn checks(&self) -> Result<Vec<Check>, StoreError> {
+        self.checks_between(i64::MIN, i64::MAX)
+    }
+
+    /// Returns all [Aggregates](Aggregate), sorted by time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the database could not be read or holds invalid data.
+    pub fn aggregates(&self) -> Result<Vec<Aggregate>, StoreError> {
+        self.blobs("SELECT data FROM aggregates ORDER BY start, id")
+    }
+
+    /// Returns all [Traces](Trace), sorted by time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the database could not be read or holds invalid data.
+    pub fn traces(&self) -> Result<Vec<Trace>, StoreError> {
+        self.blobs("SELECT data FROM traces ORDER BY timestamp, id")
+    }
+
+    /// Copies the [Checks](Check), [Aggregates](Aggregate) and [Traces](Trace) of `store` into
+    /// the database in a single transaction.
+    ///
+    /// This is how a store file is migrated to SQLite. Data that is already in the database is
+    /// skipped, so importing the same store again only adds what is new.
+    ///
+    /// Returns the number of inserted [Checks](Check).
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the data could not be written. Then none of it is.
+    pub fn import_store(&mut self, store: &Store) -> Result<usize, StoreError> {
+        let tx = self.conn.transaction()?;
+        let mut inserted = 0;
+        for check in store.checks() {
+            if insert_check(&tx, check)? {
+                inserted += 1;
+            }
+        }
+        for aggregate in store.aggregates() {
+            insert_aggregate(&tx, aggregate)?;
+        }
+        for trace in store.traces() {
+            insert_trace(&tx, trace)?;
+        }
+        tx.commit()?;
+        Ok(inserted)
+    }
+
+    /// Reads the database into a readonly [Store], so it can be analyzed like a store file.
+    ///
+    /// Only the [Checks](Check) made at or after `start` and before `end` for which `keep`
+    /// returns true are read. All [Aggregates](Aggregate) and [Traces](Trace) are read.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the database could not be read or holds invalid data.
+    pub fn to_store(
+        &self,
+        start: i64,
+        end: i64,
+        keep: impl Fn(&Check) -> bool,
+    ) -> Result<Store, StoreError> {
+        let mut store = self.read(start, end, keep)?;
+        store.set_readonly();
+        Ok(store)
+    }
+
+    /// Reads the database into a [Store], see [SqliteStore::to_store].
+    fn read(
+        &self,
+        start: i64,
+        end: i64,
+        keep: impl Fn(&Check) -> bool,
+    ) -> Result<Store, StoreError> {
+        let mut store = Store::new();
+        store.checks = self.checks_between(start, end)?;
+        store.checks.retain(keep);
+        store.aggregates = self.aggregates()?;
+        store.traces = self.traces()?;
+        store.source = Some(self.path.clone());
+        Ok(store)
+    }
+
+    /// Reads all values of the single blob column selected by `query`.
+    fn blobs<T: serde::de::DeserializeOwned>(&self, query: &str) -> Result<Vec<T>, StoreError> {
+        let mut stmt = self.conn.prepare_cached(query)?;
+        let rows = stmt.query_map([], |row| row.get::<_, Vec<u8>>(0))?;
+        let mut values = Vec::new();
+        for row in rows {
+            values.push(bincode::deserialize(&row?)?);
+        }
+        Ok(values)
+    }
+}
+
+impl StoreBackend for SqliteStore {
+    fn load(&self) -> Result<Store, StoreError> {
+        self.read(i64::MIN, i64::MAX, |_| true)
+    }
+
+    /// Writes the new data of `store` in a single transaction.
+    ///
+    /// Only what is not in the database yet is inserted, so saving is cheap no matter how large
+    /// the [Store] is. [Checks](Check) older than the first [Check] of `store`, like those that
+    /// were [downsampled](Store::downsample), are deleted.
+    fn save(&mut self, store: &Store) -> Result<(), StoreError> {
+        let tx = self.conn.transaction()?;
+        if let Some(first) = store.checks().first() {
+            tx.execute(
+                "DELETE FROM checks WHERE timestamp < ?1",
+                params![first.timestamp()],
+            )?;
+        }
+        for check in store.checks() {
+            insert_check(&tx, check)?;
+        }
+        for aggregate in store.aggregates() {
+            insert_aggregate(&tx, aggregate)?;
+        }
+        for trace in store.traces() {
+            insert_trace(&tx, trace)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// Inserts `check` unless it is a duplicate, returns true if it was inserted
+fn insert_check(conn: &Connection, check: &Check) -> Result<bool, StoreError> {
+    let measurement = check
+        .measurement()
+        .map(|m| bincode::serialize(&m))
+        .transpose()?;
+    let changed = conn.prepare_cached(INSERT_CHECK)?.execute(params![
+        check.timestamp(),
+        check.flags().bits(),
+        check.latency_us(),
+        check.target_scoped(),
+        check.label(),
+        check.http_status(),
+        measurement,
+        check.interface(),
+        check.probe(),
+    ])?;
+    Ok(changed > 0)
+}
+
+fn insert_aggregate(conn: &Connection, aggregate: &Aggregate) -> Result<(), StoreError> {
+    conn.prepare_cached(
+        "INSERT OR IGNORE INTO aggregates (start, target, data) VALUES (?1, ?2, ?3)",
+    )?
+    .execute(params![
+        aggregate.start(),
+        aggregate.target().to_string(),
+        bincode::serialize(aggregate)?,
+    ])?;
+    Ok(())
+}
+
+fn insert_trace(conn: &Connection, trace: &Trace) -> Result<(), StoreError> {
+    conn.prepare_cached(
+        "INSERT OR IGNORE INTO traces (timestamp, target, data) VALUES (?1, ?2, ?3)",
+    )?
+    .execute(params![
+        trace.timestamp(),
+        trace.target().to_string(),
+        bincode::serialize(trace)?,
+    ])?;
+    Ok(())
+}
+
+/// Reads a row of [SELECT_CHECKS] into a [Check]
+///
+/// The outer [Result] is for SQLite, the inner one for the data in the row.
+fn read_check(row: &Row<'_>) -> rusqlite::Result<Result<Check, StoreError>> {
+    let target: String = row.get(3)?;
+    let measurement: Option<Vec<u8>> = row.get(6)?;
+    let flags: FlagSet<CheckFlag> = FlagSet::new_truncated(row.get(1)?);
+    let (label, http_status): (Option<String>, Option<u16>) = (row.get(4)?, row.get(5)?);
+    let timestamp: i64 = row.get(0)?;
+    let latency: Option<u32> = row.get(2)?;
+    let (interface, probe): (Option<String>, Option<String>) = (row.get(7)?, row.get(8)?);
+    Ok((|| {
+        let Ok((address, zone)) = parse_scoped(&target) else {
+            error!("the database has a check with the invalid target {target}");
+            return Err(StoreError::CorruptStore);
+        };
+        let mut check = Check::from_raw(timestamp, flags, latency, address);
+        check.set_zone(zone);
+        check.set_label(label);
+        check.set_http_status(http_status);
+        check.set_measurement(measurement.map(|m| bincode::deserialize(&m)).transpose()?);
+        check.set_interface(interface);
+        check.set_probe(probe);
+        Ok(check)
+    })())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::records::Measurement;
+
+    use super::*;
+
+    #[test]
+    fn test_import_and_read() {
+        let path = std::env::temp_dir().join(format!("netpulse-sqlite-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = Store::new();
+        for minute in 0..5 {
+            let mut check = Check::new(
+                chrono::DateTime::from_timestamp(minute * 60, 0).unwrap(),
+                CheckFlag::Success | CheckFlag::TypeHTTP,
+                Some(Duration::from_millis(12)),
+                "1.1.1.1".parse().unwrap(),
+            );
+            check.set_label(Some("cloudflare-dns".to_string()));
+            check.set_http_status(Some(200));
+            store.add_check(check);
+        }
+        let mut mtu = Check::new(
+            chrono::DateTime::from_timestamp(300, 0).unwrap(),
+            CheckFlag::Success | CheckFlag::TypeMtu,
+            Some(Duration::from_millis(3)),
+            "2606:4700:4700::1111".parse().unwrap(),
+        );
+        mtu.set_measurement(Some(Measurement::Mtu { size: 1492 }));
+        mtu.set_interface(Some("wwan0".to_string()));
+        mtu.set_probe(Some("living-room".to_string()));
+        let mut link_local = Check::new(
+            chrono::DateTime::from_timestamp(300, 0).unwrap(),
+            CheckFlag::Success | CheckFlag::TypeIcmp,
+            Some(Duration::from_millis(1)),
+            "fe80::1".parse().unwrap(),
+        );
+        link_local.set_zone(Some("eth0".to_string()));
+        store.add_check(link_local);
+        store.add_check(mtu);
+
+        let mut db = SqliteStore::open(&path).unwrap();
+        assert!(SqliteStore::is_sqlite(&path));
+        assert_eq!(db.version().unwrap(), Version::CURRENT);
+        assert_eq!(db.import_store(&store).unwrap(), 7);
+        assert_eq!(db.import_store(&store).unwrap(), 0);
+        assert_eq!(db.len().unwrap(), 7);
+        assert_eq!(db.checks().unwrap(), store.checks());
+        assert_eq!(db.checks_between(60, 180).unwrap(), &store.checks()[1..3]);
+
+        let read = db
+            .to_store(120, i64::MAX, |c| c.latency() == Some(12))
+            .unwrap();
+        assert_eq!(read.checks(), &store.checks()[2..5]);
+        assert!(read.readonly());
+
+        // saving as a backend drops the checks that left the store
+        store.checks_mut().drain(..2);
+        store.save_to(&mut db).unwrap();
+        let loaded = Store::load_with(&db, false).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.checks(), store.checks());
+    }
+
+    #[test]
+    fn test_checks_of_uplinks_and_probes() {
+        let path =
+            std::env::temp_dir().join(format!("netpulse-sqlite-uplinks-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // alike but for the interface or the probe, like checks over two uplinks or from two
+        // probes in the same round
+        let check = Check::new(
+            chrono::DateTime::from_timestamp(60, 0).unwrap(),
+            CheckFlag::Success | CheckFlag::TypeHTTP,
+            Some(Duration::from_millis(12)),
+            "1.1.1.1".parse().unwrap(),
+        );
+        let mut checks = vec![check.clone()];
+        for interface in ["eth0", "wwan0"] {
+            let mut check = check.clone();
+            check.set_interface(Some(interface.to_string()));
+            checks.push(check);
+        }
+        for probe in ["living-room", "office"] {
+            let mut check = check.clone();
+            check.set_probe(Some(probe.to_string()));
+            checks.push(check);
+        }
+
+        let mut db = SqliteStore::open(&path).unwrap();
+        assert_eq!(db.add_checks(&checks).unwrap(), 5);
+        assert_eq!(db.add_checks(&checks).unwrap(), 0);
+        let read = db.checks().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.len(), 5);
+        for check in &checks {
+            assert!(read.contains(check));
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Reading the [Checks](Check) of a store file one by one.
+//!
+//! [Store::load](super::Store::load) reads and decodes the whole store file before anything can
+//! be done with it. For a large store and a question about a small part of it, like the outages
+//! since yesterday, a [CheckStream] decodes one [Check] at a time instead, so the ones that are
+//! not needed never pile up in memory.
+//!
+//! The checksum at the end of the store file can only be verified after reading all of it, so a
+//! [CheckStream] does not verify it. Damaged data usually fails to decode and ends the stream
+//! with an error.
+
+use std::io::Read;
+
+use crate::errors::StoreError;
+use crate::records::Check;
+
+use super::framed;
+
+/// Iterator over the [Checks](Check) of a store file, see the [module docs](self).
+///
+/// Create it with [Store::stream_checks](super::Store::stream_checks). After the first error,
+/// the stream ends.
+pub struct CheckStream {
+    inner: Inner,
+}
+
+enum Inner {
+    /// The checks are decoded from the reader as they are requested
+    Reading {
+        reader: Box<dyn Read>,
+        remaining: u64,
+        /// The store is newer than this version of netpulse, see
+        /// [newer stores](framed#newer-stores)
+        newer: bool,
+    },
+    /// The store had an older layout and was loaded as a whole
+    Loaded(std::vec::IntoIter<Check>),
+    /// All checks were read or an error occurred
+    Done,
+}
+
+impl CheckStream {
+    /// Creates a stream of the checks section at the current position of `reader`, which is
+    /// right after the version of a store file in the [framed] layout.
+    ///
+    /// # Errors
+    ///
+    /// Returns [StoreError] if the header of the checks section could not be read.
+    pub(super) fn from_framed(mut reader: Box<dyn Read>, newer: bool) -> Result<Self, StoreError> {
+        let remaining = framed::read_checks_header(&mut reader)?;
+        Ok(Self {
+            inner: Inner::Reading {
+                reader,
+                remaining,
+                newer,
+            },
+        })
+    }
+
+    /// Creates a stream of checks that were already loaded.
+    pub(super) fn from_loaded(checks: Vec<Check>) -> Self {
+        Self {
+            inner: Inner::Loaded(checks.into_iter()),
+        }
+    }
+
+    /// Returns the reader behind the stream once all [Checks](Check) were read, so the rest of
+    /// the store can be read from it.
+    ///
+    /// Returns [None] if the stream was not read to the end or did not come from a reader.
+    pub(super) fn into_reader(self) -> Option<Box<dyn Read>> {
+        match self.inner {
+            Inner::Reading {
+                reader,
+                remaining: 0,
+                ..
+            } => Some(reader),
+            _ => None,
+        }
+    }
+}
+
+impl Iterator for CheckStream {
+    type Item = Result<Check, StoreError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match &mut self.inner {
+                Inner::Reading { remaining: 0, .. } | Inner::Done => return None,
+                Inner::Reading {
+                    reader,
+                    remaining,
+                    newer,
+                } => {
+                    match framed::read_record(reader, *newer) {
+                        Ok(check) => {
+                            *remaining -= 1;
+                            // records of newer stores that could not be decoded are skipped
+                            if let Some(check) = check {
+                                return Some(Ok(check));
+                            }
+                        }
+                        Err(err) => {
+                            self.inner = Inner::Done;
+                            return Some(Err(err));
+                        }
+                    }
+                }
+                Inner::Loaded(checks) => return checks.next().map(Ok),
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for CheckStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.inner {
+            Inner::Reading { remaining, .. } => f
+                .debug_struct("CheckStream")
+                .field("remaining", remaining)
+                .finish(),
+            Inner::Loaded(checks) => f
+                .debug_struct("CheckStream")
+                .field("remaining", &checks.len())
+                .finish(),
+            Inner::Done => f.debug_struct("CheckStream").finish_non_exhaustive(),
+        }
+    }
+}
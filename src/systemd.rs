@@ -0,0 +1,167 @@
+//! Telling systemd about the state of the daemon.
+//!
+//! When the daemon runs as a service of `Type=notify`, systemd passes the path of a socket in
+//! [ENV_NOTIFY_SOCKET]. The daemon sends short messages to it, see `sd_notify(3)`:
+//!
+//! - [ready]: the store is loaded and the daemon is about to make its first checks, so systemd
+//!   considers the service started only then
+//! - [status]: a line about the last round of checks, shown by `systemctl status netpulsed`
+//! - [watchdog]: a heartbeat after every successful round of checks, see [Watchdog](self#watchdog)
+//! - [stopping]: the daemon shuts down
+//!
+//! Outside of systemd, [ENV_NOTIFY_SOCKET] is not set and nothing is sent.
+//!
+//! # Watchdog
+//!
+//! The service has `WatchdogSec=` set to [WATCHDOG_PERIODS] check periods. The daemon only sends
+//! a heartbeat after a successful round of checks, so if the check loop hangs or every round
+//! fails for that long, systemd restarts the daemon.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use netpulse::systemd;
+//!
+//! systemd::ready().unwrap();
+//! systemd::status("last round at 12:00: 4 checks, 0 failed").unwrap();
+//! ```
+
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+use crate::store::{DEFAULT_PERIOD, ENV_PERIOD};
+
+/// Environment variable with the path of the notification socket of systemd
+pub const ENV_NOTIFY_SOCKET: &str = "NOTIFY_SOCKET";
+/// Environment variable with the watchdog timeout of the unit in microseconds
+pub const ENV_WATCHDOG_USEC: &str = "WATCHDOG_USEC";
+/// How many check periods the watchdog of the service waits for a successful round
+pub const WATCHDOG_PERIODS: i64 = 5;
+
+/// Environment variable with the pid of the process that should send the watchdog heartbeats
+pub const ENV_WATCHDOG_PID: &str = "WATCHDOG_PID";
+
+/// Sends `state` to the notification socket of systemd.
+///
+/// `state` is made of newline separated assignments like `READY=1`. Returns `false` if the
+/// daemon does not run under systemd, so nothing was sent.
+///
+/// # Errors
+///
+/// Returns an [io::Error] if the message could not be sent.
+pub fn notify(state: &str) -> io::Result<bool> {
+    match std::env::var_os(ENV_NOTIFY_SOCKET) {
+        Some(socket) if !socket.is_empty() => {
+            notify_to(&socket.to_string_lossy(), state)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Tells systemd that the daemon finished starting up.
+///
+/// # Errors
+///
+/// Returns an [io::Error] if the message could not be sent.
+pub fn ready() -> io::Result<bool> {
+    notify("READY=1")
+}
+
+/// Sets the status line of the service, shown by `systemctl status`.
+///
+/// # Errors
+///
+/// Returns an [io::Error] if the message could not be sent.
+pub fn status(status: &str) -> io::Result<bool> {
+    // a line break would start a new assignment
+    notify(&format!("STATUS={}", status.replace('\n', " ")))
+}
+
+/// Sends a heartbeat to the watchdog of systemd.
+///
+/// # Errors
+///
+/// Returns an [io::Error] if the message could not be sent.
+pub fn watchdog() -> io::Result<bool> {
+    notify("WATCHDOG=1")
+}
+
+/// Tells systemd that the daemon is shutting down.
+///
+/// # Errors
+///
+/// Returns an [io::Error] if the message could not be sent.
+pub fn stopping() -> io::Result<bool> {
+    notify("STOPPING=1")
+}
+
+/// Returns how long the watchdog of systemd waits for a heartbeat from this process.
+///
+/// This is the `WatchdogSec=` of the unit, or [None] if the watchdog is not enabled for this
+/// process.
+pub fn watchdog_timeout() -> Option<Duration> {
+    if let Ok(pid) = std::env::var(ENV_WATCHDOG_PID) {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    let usec: u64 = std::env::var(ENV_WATCHDOG_USEC).ok()?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Returns the `WatchdogSec=` for the service, in seconds.
+///
+/// This is [WATCHDOG_PERIODS] times the check period, which can be changed with [ENV_PERIOD].
+pub fn watchdog_sec() -> i64 {
+    let period = std::env::var(ENV_PERIOD)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_PERIOD);
+    period * WATCHDOG_PERIODS
+}
+
+/// Sends `state` to the socket at `path`.
+///
+/// A leading `@` stands for a socket in the abstract namespace.
+fn notify_to(path: &str, state: &str) -> io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract sockets only exist on Linux",
+            ));
+        }
+        None => {
+            socket.send_to(state.as_bytes(), path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_notify_to() {
+        let path = std::env::temp_dir().join(format!("netpulse-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        notify_to(path.to_str().unwrap(), "READY=1\nSTATUS=ok").unwrap();
+
+        let mut buf = [0u8; 64];
+        let n = receiver.recv(&mut buf).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&buf[..n], b"READY=1\nSTATUS=ok");
+    }
+}
@@ -0,0 +1,194 @@
+//! Deterministic datasets of [Checks](Check) for tests and benchmarks.
+//!
+//! Building a realistic [Store] otherwise needs a daemon that ran for days. A [Dataset] describes
+//! rounds of checks, one per minute, against some targets, with outages at fixed intervals and
+//! some scattered failures. The same [Dataset] always generates the same [Checks](Check), so
+//! results can be compared between runs.
+//!
+//! [Store::from_raw_in_mem] wraps the generated [Checks](Check) in a [Store] that is never saved.
+//!
+//! Requires the `testing` feature.
+//!
+//! # Example
+//!
+//! ```rust
+//! use netpulse::analyze::outage_summaries;
+//! use netpulse::testing::Dataset;
+//!
+//! let dataset = Dataset {
+//!     rounds: 24 * 60,
+//!     ..Default::default()
+//! };
+//! let store = dataset.store();
+//! assert_eq!(store.checks().len(), dataset.len());
+//! assert!(!outage_summaries(&store).is_empty());
+//! ```
+
+use std::net::IpAddr;
+
+use crate::records::{Check, CheckFlag, CheckType, TARGETS};
+use crate::store::Store;
+
+/// Time of the first round of a [Dataset] by default, 2024-11-09 00:00 UTC
+pub const DEFAULT_START: i64 = 1_731_110_400;
+
+/// Description of a generated set of [Checks](Check), see the [module docs](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dataset {
+    /// Unix timestamp of the first round
+    pub start: i64,
+    /// Number of rounds, one per minute
+    pub rounds: usize,
+    /// Targets that every round checks
+    pub targets: Vec<IpAddr>,
+    /// Types of the checks of every round
+    pub check_types: Vec<CheckType>,
+    /// Number of rounds from the start of one outage to the start of the next, no outages if
+    /// [None]
+    pub outage_every: Option<usize>,
+    /// Number of rounds that each outage lasts, in which every check fails
+    pub outage_rounds: usize,
+    /// Ratio of checks outside of outages that fail on their own, between `0.0` and `1.0`
+    pub flaky_ratio: f64,
+    /// Seed of the latencies and the scattered failures
+    pub seed: u64,
+}
+
+impl Default for Dataset {
+    fn default() -> Self {
+        Self {
+            start: DEFAULT_START,
+            rounds: 60,
+            targets: TARGETS
+                .iter()
+                .map(|t| t.parse().expect("a target constant was not an Ip Address"))
+                .collect(),
+            check_types: vec![CheckType::Http, CheckType::Icmp],
+            outage_every: Some(180),
+            outage_rounds: 5,
+            flaky_ratio: 0.01,
+            seed: 42,
+        }
+    }
+}
+
+impl Dataset {
+    /// Creates the default [Dataset] with about `checks` [Checks](Check).
+    ///
+    /// The number of rounds is rounded up so every round is complete. Useful for benchmarks.
+    pub fn with_checks(checks: usize) -> Self {
+        let dataset = Self::default();
+        let per_round = dataset.per_round().max(1);
+        Self {
+            rounds: checks.div_ceil(per_round),
+            ..dataset
+        }
+    }
+
+    /// Returns the number of [Checks](Check) in each round.
+    pub fn per_round(&self) -> usize {
+        self.targets.len() * self.check_types.len()
+    }
+
+    /// Returns the number of [Checks](Check) this [Dataset] generates.
+    pub fn len(&self) -> usize {
+        self.rounds * self.per_round()
+    }
+
+    /// Returns true if this [Dataset] generates no [Checks](Check).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the round with index `round` is part of an outage.
+    pub fn in_outage(&self, round: usize) -> bool {
+        self.outage_every
+            .is_some_and(|every| every > 0 && round % every < self.outage_rounds)
+    }
+
+    /// Generates the [Checks](Check), sorted by time.
+    pub fn checks(&self) -> Vec<Check> {
+        let mut rng = XorShift::new(self.seed);
+        let mut checks = Vec::with_capacity(self.len());
+        for round in 0..self.rounds {
+            let time = self.start + round as i64 * 60;
+            let outage = self.in_outage(round);
+            for check_type in &self.check_types {
+                for target in &self.targets {
+                    let builder = Check::builder()
+                        .target(*target)
+                        .check_type(*check_type)
+                        .at_timestamp(time);
+                    let flaky = rng.next_f64() < self.flaky_ratio;
+                    let latency = 10 + rng.next() % 40;
+                    checks.push(if outage || flaky {
+                        builder.failure(CheckFlag::Timeout).build()
+                    } else {
+                        builder.success().latency(latency).build()
+                    });
+                }
+            }
+        }
+        checks
+    }
+
+    /// Generates the [Checks](Check) into a [Store] that is never saved, see
+    /// [Store::from_raw_in_mem].
+    pub fn store(&self) -> Store {
+        Store::from_raw_in_mem(self.checks())
+    }
+}
+
+/// Small pseudo random number generator, so the datasets do not depend on a `rand` crate
+#[derive(Debug, Clone)]
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // the state must never be zero
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Returns a number in `0.0..1.0`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dataset_is_deterministic() {
+        let dataset = Dataset {
+            rounds: 400,
+            ..Default::default()
+        };
+        let checks = dataset.checks();
+        assert_eq!(checks, dataset.checks());
+        assert_eq!(checks.len(), dataset.len());
+        assert!(checks.is_sorted());
+
+        // rounds 0..5 and 180..185 and 360..365 are outages
+        let outage_checks = 3 * dataset.outage_rounds * dataset.per_round();
+        let failed = checks.iter().filter(|c| !c.is_success()).count();
+        assert!(failed >= outage_checks);
+        assert!(failed < outage_checks * 2);
+
+        let other = Dataset {
+            seed: 7,
+            ..dataset.clone()
+        };
+        assert_ne!(other.checks(), checks);
+
+        assert_eq!(Dataset::with_checks(1001).len(), 1004);
+    }
+}